@@ -0,0 +1,10 @@
+//! Any input must decode to a value or an error, never a panic.
+//!
+//! Runs `exercise()` from src/components/fuzz.rs, the entry point the
+//! mutation tests drive. Run with `cargo +nightly fuzz run parse`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|bytes: &[u8]| awwasm_parser::components::fuzz::exercise(bytes));
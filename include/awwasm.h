@@ -0,0 +1,89 @@
+/* C API of awwasm-parser, built with the `capi` feature. */
+
+#ifndef AWWASM_H
+#define AWWASM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum {
+    AWWASM_OPERANDS_NONE = 0,
+    AWWASM_OPERANDS_INDEX = 1,
+    AWWASM_OPERANDS_INDEX_PAIR = 2,
+    AWWASM_OPERANDS_MEM_ARG = 3,
+    AWWASM_OPERANDS_I32 = 4,
+    AWWASM_OPERANDS_I64 = 5,
+    AWWASM_OPERANDS_F32 = 6,
+    AWWASM_OPERANDS_F64 = 7,
+    AWWASM_OPERANDS_BLOCK_TYPE = 8,
+    AWWASM_OPERANDS_BR_TABLE = 9,
+    AWWASM_OPERANDS_VAL_TYPE = 10,
+    AWWASM_OPERANDS_BLOCK_TYPE_INDEX = 11,
+    AWWASM_OPERANDS_REF_TYPE = 12,
+} AwwasmCOperandKind;
+
+typedef struct {
+    uint32_t align;
+    uint32_t offset;
+    uint32_t memory;
+} AwwasmCMemArg;
+
+typedef struct {
+    uint32_t target_count;
+    uint32_t default_target;
+} AwwasmCBrTable;
+
+/* A `(ref null? ht)` type: `type_byte` is 0x63 (nullable) or 0x64, and
+ * `heap_type` is a type index, or -16 for `func` and -17 for `extern`. */
+typedef struct {
+    uint8_t type_byte;
+    int64_t heap_type;
+} AwwasmCRefType;
+
+typedef union {
+    uint32_t index;
+    uint32_t index_pair[2];
+    AwwasmCMemArg mem_arg;
+    int32_t i32;
+    int64_t i64;
+    uint32_t f32_bits;
+    uint64_t f64_bits;
+    uint8_t block_type;
+    AwwasmCBrTable br_table;
+    uint8_t val_type;
+    AwwasmCRefType ref_type;
+} AwwasmCOperands;
+
+/* One decoded instruction. Nested blocks are flattened, and the `else`,
+ * `catch`, `catch_all`, `delegate` and `end` markers that close them are
+ * records of their own. */
+typedef struct {
+    uint8_t opcode;      /* 0xFC for the misc prefix, 0xFE for atomics */
+    uint32_t sub_opcode; /* of a 0xFC or 0xFE instruction, otherwise 0 */
+    size_t offset;
+    size_t length;
+    uint32_t depth;
+    uint32_t kind; /* AwwasmCOperandKind */
+    AwwasmCOperands operands;
+} AwwasmCInstr;
+
+typedef struct AwwasmBodyIter AwwasmBodyIter;
+
+/* Start decoding a function body (locals, then instructions). `body` must
+ * stay valid until the iterator is freed. Returns NULL on error. */
+AwwasmBodyIter *awwasm_body_iter_new(const uint8_t *body, size_t len);
+
+/* 1: a record was written to `out`; 0: end of body; -1: decode error. */
+int32_t awwasm_body_iter_next(AwwasmBodyIter *iter, AwwasmCInstr *out);
+
+void awwasm_body_iter_free(AwwasmBodyIter *iter);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* AWWASM_H */
@@ -0,0 +1,40 @@
+use awwasm_parser::components::explain;
+#[cfg(feature = "tui")]
+use awwasm_parser::components::explorer;
+use awwasm_parser::components::module::AwwasmModule;
+
+const USAGE: &str = "usage: awwasm explain <file.wasm>
+       awwasm query <query> <file.wasm>
+       awwasm tui <file.wasm>    (with the tui feature)";
+
+fn read(path: &str) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))
+}
+
+fn resolve(bytes: &[u8]) -> anyhow::Result<AwwasmModule<'_>> {
+    let mut module = AwwasmModule::new(bytes)?;
+    module.resolve_all_sections()?;
+    Ok(module)
+}
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [command, path] if command == "explain" => {
+            let bytes = read(path)?;
+            println!("{}", explain::module(&resolve(&bytes)?));
+            Ok(())
+        }
+        [command, query, path] if command == "query" => {
+            let bytes = read(path)?;
+            print!("{}", resolve(&bytes)?.query(query)?);
+            Ok(())
+        }
+        #[cfg(feature = "tui")]
+        [command, path] if command == "tui" => explorer::run(&read(path)?),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
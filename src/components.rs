@@ -1,4 +1,74 @@
 pub mod module;
+pub mod component;
+pub mod component_types;
 pub mod section;
+pub mod error;
 pub mod types;
-pub mod instructions;
\ No newline at end of file
+pub mod leb128;
+pub mod lossy;
+pub mod owned;
+pub mod module_json;
+pub mod visitor;
+pub mod payload;
+pub mod reader;
+pub mod index_space;
+pub mod imports;
+pub mod exports;
+pub mod parse_limits;
+pub mod lazy_functions;
+pub mod spans;
+#[cfg(feature = "tokio")]
+pub mod async_stream;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod instructions;
+pub mod editor;
+pub mod encoder;
+pub mod merge;
+pub mod explain;
+pub mod diff;
+pub mod validate;
+pub mod typecheck;
+pub mod indices;
+pub mod names;
+pub mod lint;
+pub mod producers;
+pub mod cancel;
+pub mod custom_reader;
+pub mod dylink;
+pub mod linking;
+pub mod branch_hints;
+pub mod conformance;
+pub mod linker_sim;
+pub mod metadata;
+pub mod dwarf;
+pub mod disasm;
+pub mod dump;
+pub mod hexdump;
+pub mod floats;
+pub mod features;
+pub mod init_graph;
+pub mod data_reads;
+pub mod bindings;
+pub mod instruction_index;
+pub mod passes;
+pub mod query;
+pub mod report;
+pub mod transform;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzz;
+#[cfg(feature = "archive")]
+pub mod archive;
+#[cfg(any(test, feature = "capi"))]
+pub mod capi;
+#[cfg(feature = "allocator-api2")]
+pub mod allocator;
+#[cfg(feature = "tui")]
+pub mod explorer;
+
+#[cfg(test)]
+pub(crate) mod lossless;
+#[cfg(test)]
+pub(crate) mod spec_suite;
@@ -0,0 +1,111 @@
+use crate::components::passes::{split_sections, AwwasmOwnedSection, AwwasmPassModule};
+use crate::components::section::SectionCode;
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::vec::Vec;
+
+/// An `AwwasmOwnedSection` whose payload lives in the allocator `A`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmOwnedSectionIn<A: Allocator = Global> {
+    pub id: SectionCode,
+    pub payload: Vec<u8, A>,
+}
+
+/// An `AwwasmPassModule` whose section list and payloads live in the
+/// allocator `A`, so an embedder can account for and cap the memory an
+/// owned copy of a module takes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmPassModuleIn<A: Allocator + Clone = Global> {
+    pub version: u32,
+    pub sections: Vec<AwwasmOwnedSectionIn<A>, A>,
+}
+
+impl<A: Allocator + Clone> AwwasmPassModuleIn<A> {
+    /// Split a module binary into its sections, like `AwwasmPassModule::new()`,
+    /// allocating in `alloc`. An allocation the allocator refuses is returned
+    /// as an error instead of aborting.
+    pub fn new_in(bytes: &[u8], alloc: A) -> anyhow::Result<Self> {
+        let mut sections = Vec::new_in(alloc.clone());
+        let version = split_sections(bytes, |id, payload| {
+            let mut owned = Vec::new_in(alloc.clone());
+            owned.try_reserve_exact(payload.len())
+                .map_err(|_| anyhow::anyhow!("Failed to allocate {} bytes for WASM {:?} section", payload.len(), id))?;
+            owned.extend_from_slice(payload);
+            sections.try_reserve(1)
+                .map_err(|_| anyhow::anyhow!("Failed to allocate WASM section list"))?;
+            sections.push(AwwasmOwnedSectionIn { id, payload: owned });
+            Ok(())
+        })?;
+        Ok(Self { version, sections })
+    }
+
+    /// Bytes currently held in `A`: the section list plus every payload.
+    pub fn allocated_bytes(&self) -> usize {
+        self.sections.capacity() * std::mem::size_of::<AwwasmOwnedSectionIn<A>>()
+            + self.sections.iter().map(|s| s.payload.capacity()).sum::<usize>()
+    }
+
+    /// Copy into a globally allocated `AwwasmPassModule`, e.g. to run passes.
+    pub fn to_pass_module(&self) -> AwwasmPassModule {
+        AwwasmPassModule {
+            version: self.version,
+            sections: self.sections.iter()
+                .map(|s| AwwasmOwnedSection { id: s.id.clone(), payload: s.payload.to_vec() })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::allocator::AwwasmPassModuleIn;
+    use allocator_api2::alloc::{AllocError, Allocator, Global};
+    use std::alloc::Layout;
+    use std::cell::Cell;
+    use std::ptr::NonNull;
+
+    // Tracks live bytes and refuses allocations past `limit`.
+    #[derive(Debug)]
+    struct Capped {
+        used: Cell<usize>,
+        limit: usize,
+    }
+
+    unsafe impl Allocator for &Capped {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            if self.used.get() + layout.size() > self.limit {
+                return Err(AllocError);
+            }
+            self.used.set(self.used.get() + layout.size());
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.used.set(self.used.get() - layout.size());
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn pass_module_in_allocator_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1)
+                (func (export "f") (result i32) (i32.const 42))
+                (data (i32.const 0) "some bytes to copy")
+            )
+        "#)?;
+
+        let roomy = Capped { used: Cell::new(0), limit: 4096 };
+        let owned = AwwasmPassModuleIn::new_in(&module, &roomy)?;
+        assert_eq!(roomy.used.get(), owned.allocated_bytes());
+        assert_eq!(owned.to_pass_module().encode(), module);
+        drop(owned);
+        assert_eq!(roomy.used.get(), 0);
+
+        let tight = Capped { used: Cell::new(0), limit: 16 };
+        let err = AwwasmPassModuleIn::new_in(&module, &tight).unwrap_err();
+        assert!(err.to_string().starts_with("Failed to allocate"), "{}", err);
+        assert_eq!(tight.used.get(), 0);
+        Ok(())
+    }
+}
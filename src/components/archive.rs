@@ -0,0 +1,214 @@
+use crate::components::features::AwwasmParseOptions;
+use crate::components::module::AwwasmModule;
+use crate::components::parse_limits::AwwasmParseLimits;
+use crate::limits::{MAX_WASM_FUNCTIONS, MAX_WASM_MODULE_SIZE};
+use std::io::{Read, Seek, SeekFrom};
+
+const ZIP_MAGIC: &[u8] = b"PK";
+const WASM_EXTENSION: &str = ".wasm";
+
+/// Limits applied while walking an archive with `parse_all()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmArchiveLimits {
+    /// Largest `.wasm` entry that is read; bigger entries are reported as errors
+    /// without being read.
+    pub max_module_size: u64,
+    /// Most functions (imports and definitions) a module may declare.
+    pub max_functions: u32,
+    /// Most `.wasm` entries visited before the walk fails.
+    pub max_entries: usize,
+    /// Caps each entry is parsed with; see `AwwasmModule::new_with_options()`.
+    pub parse: AwwasmParseLimits,
+}
+
+impl Default for AwwasmArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_module_size: MAX_WASM_MODULE_SIZE as u64,
+            max_functions: MAX_WASM_FUNCTIONS as u32,
+            max_entries: 10_000,
+            parse: AwwasmParseLimits::untrusted(),
+        }
+    }
+}
+
+/// Walk a `.tar` or `.zip` archive and parse every `.wasm` entry.
+///
+/// The format is detected from the first bytes. Each entry is parsed with the
+/// `parse` caps and resolved, then handed to `visit` with its path; the module
+/// borrows a buffer that is reused for the next entry. Per-entry failures (too
+/// large, malformed, over the function limit or a `parse` cap) go to `visit`;
+/// only errors reading the archive itself, or exceeding `max_entries`, end the
+/// walk.
+pub fn parse_all<R: Read + Seek>(
+    mut reader: R,
+    limits: &AwwasmArchiveLimits,
+    mut visit: impl FnMut(&str, anyhow::Result<AwwasmModule<'_>>),
+) -> anyhow::Result<()> {
+    let mut magic = Vec::with_capacity(ZIP_MAGIC.len());
+    (&mut reader).take(ZIP_MAGIC.len() as u64).read_to_end(&mut magic)
+        .map_err(|e| anyhow::anyhow!("Failed to read archive: {}", e))?;
+    reader.seek(SeekFrom::Start(0))
+        .map_err(|e| anyhow::anyhow!("Failed to read archive: {}", e))?;
+
+    let mut walker = Walker { limits, entries: 0, buf: Vec::new() };
+    if magic == ZIP_MAGIC {
+        walker.zip(reader, &mut visit)
+    } else {
+        walker.tar(reader, &mut visit)
+    }
+}
+
+struct Walker<'l> {
+    limits: &'l AwwasmArchiveLimits,
+    entries: usize,
+    buf: Vec<u8>,
+}
+
+impl Walker<'_> {
+    fn tar<R: Read>(&mut self, reader: R, visit: &mut impl FnMut(&str, anyhow::Result<AwwasmModule<'_>>)) -> anyhow::Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        let entries = archive.entries().map_err(|e| anyhow::anyhow!("Failed to read tar archive: {}", e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| anyhow::anyhow!("Failed to read tar archive: {}", e))?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()
+                .map_err(|e| anyhow::anyhow!("Failed to read tar archive: {}", e))?
+                .to_string_lossy()
+                .into_owned();
+            let size = entry.size();
+            self.entry(&path, size, entry, visit)?;
+        }
+        Ok(())
+    }
+
+    fn zip<R: Read + Seek>(&mut self, reader: R, visit: &mut impl FnMut(&str, anyhow::Result<AwwasmModule<'_>>)) -> anyhow::Result<()> {
+        let mut archive = zip::ZipArchive::new(reader).map_err(|e| anyhow::anyhow!("Failed to read zip archive: {}", e))?;
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).map_err(|e| anyhow::anyhow!("Failed to read zip archive: {}", e))?;
+            if !file.is_file() {
+                continue;
+            }
+            let path = file.name().to_owned();
+            let size = file.size();
+            self.entry(&path, size, file, visit)?;
+        }
+        Ok(())
+    }
+
+    fn entry(&mut self, path: &str, size: u64, reader: impl Read, visit: &mut impl FnMut(&str, anyhow::Result<AwwasmModule<'_>>)) -> anyhow::Result<()> {
+        if !path.ends_with(WASM_EXTENSION) {
+            return Ok(());
+        }
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(anyhow::anyhow!("Archive has more than {} wasm entries", self.limits.max_entries));
+        }
+        if size > self.limits.max_module_size {
+            visit(path, Err(anyhow::anyhow!("{} is {} bytes, over the {} byte limit", path, size, self.limits.max_module_size)));
+            return Ok(());
+        }
+
+        self.buf.clear();
+        // The header size is only a hint; never read past the limit.
+        if let Err(e) = reader.take(self.limits.max_module_size + 1).read_to_end(&mut self.buf) {
+            visit(path, Err(anyhow::anyhow!("Failed to read {}: {}", path, e)));
+            return Ok(());
+        }
+        if self.buf.len() as u64 > self.limits.max_module_size {
+            visit(path, Err(anyhow::anyhow!("{} is over the {} byte limit", path, self.limits.max_module_size)));
+            return Ok(());
+        }
+        visit(path, parse_module(&self.buf, self.limits));
+        Ok(())
+    }
+}
+
+fn parse_module<'a>(bytes: &'a [u8], limits: &AwwasmArchiveLimits) -> anyhow::Result<AwwasmModule<'a>> {
+    let options = AwwasmParseOptions { limits: limits.parse, ..Default::default() };
+    let module = AwwasmModule::new_with_options(bytes, &options)?;
+    if module.func_count() > limits.max_functions {
+        return Err(anyhow::anyhow!("Module has {} functions, over the limit of {}", module.func_count(), limits.max_functions));
+    }
+    Ok(module)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::archive::{parse_all, AwwasmArchiveLimits};
+    use crate::components::parse_limits::AwwasmParseLimits;
+    use std::io::{Cursor, Write};
+
+    fn wasm_files() -> anyhow::Result<Vec<(&'static str, Vec<u8>)>> {
+        Ok(vec![
+            ("lib/a.wasm", wat::parse_str("(module (func) (func))")?),
+            ("README.md", b"not wasm".to_vec()),
+            ("lib/broken.wasm", b"not a module".to_vec()),
+            ("b.wasm", wat::parse_str("(module (func) (func) (func) (func))")?),
+        ])
+    }
+
+    fn walk(archive: Vec<u8>, limits: &AwwasmArchiveLimits) -> anyhow::Result<Vec<(String, Result<u32, String>)>> {
+        let mut seen = Vec::new();
+        parse_all(Cursor::new(archive), limits, |path, result| {
+            seen.push((path.to_owned(), result.map(|m| m.func_count()).map_err(|e| e.to_string())));
+        })?;
+        Ok(seen)
+    }
+
+    fn tar_archive() -> anyhow::Result<Vec<u8>> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, bytes) in wasm_files()? {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, &bytes[..])?;
+        }
+        Ok(builder.into_inner()?)
+    }
+
+    fn zip_archive() -> anyhow::Result<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("lib/", zip::write::SimpleFileOptions::default())?;
+        for (path, bytes) in wasm_files()? {
+            writer.start_file(path, zip::write::SimpleFileOptions::default())?;
+            writer.write_all(&bytes)?;
+        }
+        Ok(writer.finish()?.into_inner())
+    }
+
+    #[test]
+    fn parse_all_test() -> anyhow::Result<()> {
+        let limits = AwwasmArchiveLimits { max_functions: 3, ..Default::default() };
+        for archive in [tar_archive()?, zip_archive()?] {
+            let seen = walk(archive, &limits)?;
+            assert_eq!(seen.len(), 3);
+            assert_eq!(seen[0], ("lib/a.wasm".to_owned(), Ok(2)));
+            assert_eq!(seen[1].0, "lib/broken.wasm");
+            assert!(seen[1].1.is_err());
+            assert_eq!(seen[2], ("b.wasm".to_owned(), Err("Module has 4 functions, over the limit of 3".to_owned())));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn parse_all_limits_test() -> anyhow::Result<()> {
+        let limits = AwwasmArchiveLimits { max_module_size: 16, ..Default::default() };
+        let seen = walk(tar_archive()?, &limits)?;
+        assert!(seen[0].1.as_ref().unwrap_err().contains("over the 16 byte limit"));
+
+        let limits = AwwasmArchiveLimits { max_entries: 2, ..Default::default() };
+        let err = walk(zip_archive()?, &limits).unwrap_err();
+        assert_eq!(err.to_string(), "Archive has more than 2 wasm entries");
+
+        let limits = AwwasmArchiveLimits { parse: AwwasmParseLimits { max_section_entries: 3, ..Default::default() }, ..Default::default() };
+        let seen = walk(tar_archive()?, &limits)?;
+        assert_eq!(seen[0], ("lib/a.wasm".to_owned(), Ok(2)));
+        let err = "Failed to parse WASM module: module exceeds the section entries limit in the Function section: 4 > 3";
+        assert_eq!(seen[2], ("b.wasm".to_owned(), Err(err.to_owned())));
+        Ok(())
+    }
+}
@@ -0,0 +1,292 @@
+use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+use crate::components::leb128::leb128_u32;
+use crate::components::module::AwwasmModulePreamble;
+use crate::components::parse_limits::{exceeds, AwwasmParseLimits};
+use crate::components::section::{AwwasmSection, AwwasmSectionHeader, SectionCode, TruncatedSection};
+use crate::components::types::AwwasmCodeSectionItem;
+use crate::consts::{WASM_PREAMBLE_MAGIC_SIZE_BYTES, WASM_PREAMBLE_VERSION_SIZE_BYTES};
+use nom::combinator::complete;
+use nom_derive::Parse;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const PREAMBLE_BYTES: usize = WASM_PREAMBLE_MAGIC_SIZE_BYTES + WASM_PREAMBLE_VERSION_SIZE_BYTES;
+// Longest encoding of a u32.
+const MAX_LEB128_U32_BYTES: usize = 5;
+
+/// Bytes of one section or function body received by
+/// `AwwasmAsyncPayloadStream`, with their offset in the module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmReceived {
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl AwwasmReceived {
+    /// The section, for bytes from `AwwasmAsyncPayload::Section`.
+    pub fn section(&self) -> anyhow::Result<AwwasmSection<'_>> {
+        let (_, section) = complete(AwwasmSection::parse)(&self.bytes).map_err(|e| self.located(AwwasmParseError::nom("module", e).within(&self.bytes)))?;
+        Ok(section)
+    }
+
+    /// The function body, for bytes from `AwwasmAsyncPayload::CodeEntry`.
+    pub fn code(&self) -> anyhow::Result<AwwasmCodeSectionItem<'_>> {
+        let (_, item) = complete(AwwasmCodeSectionItem::parse)(&self.bytes).map_err(|e| self.located(AwwasmParseError::nom("Code Section", e).within(&self.bytes)))?;
+        Ok(item)
+    }
+
+    fn located(&self, mut err: AwwasmParseError) -> AwwasmParseError {
+        err.locate((self.bytes.as_ptr() as usize).wrapping_sub(self.offset), None, None);
+        err
+    }
+}
+
+/// One event from `AwwasmAsyncPayloadStream`, the owned counterpart of
+/// `AwwasmPayload`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmAsyncPayload {
+    /// The preamble of a core module, always the first event.
+    Version(u32),
+    /// A whole section other than the code section, header included.
+    Section(AwwasmReceived),
+    /// The start of the code section, followed by `count` `CodeEntry`
+    /// events unless the stream fails.
+    CodeSectionStart { count: u32 },
+    /// One function body, size prefix included.
+    CodeEntry(AwwasmReceived),
+    /// The input ended after a complete section; always the last event.
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Preamble,
+    Sections,
+    // Entries and bytes left in the code section.
+    Code { remaining: u32, body: usize },
+    Done,
+}
+
+/// Parses a module from an `AsyncRead`, such as a download in progress,
+/// yielding each section, and each function body, as soon as all of its
+/// bytes have arrived. Requires the `tokio` feature.
+///
+/// Only the current section or body is buffered, and only as far as the
+/// input goes, whatever size its header declares. The first error ends the
+/// stream.
+#[derive(Debug)]
+pub struct AwwasmAsyncPayloadStream<R> {
+    inner: R,
+    offset: usize,
+    state: State,
+    limits: AwwasmParseLimits,
+    // Sections started so far.
+    sections: usize,
+}
+
+impl<R: AsyncRead + Unpin> AwwasmAsyncPayloadStream<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_limits(inner, AwwasmParseLimits::default())
+    }
+
+    /// Like `new`, but failing with `LimitExceeded` once the module is over
+    /// one of `limits`. Counts and sizes are checked as their headers
+    /// arrive, before the bytes they announce are buffered.
+    pub fn with_limits(inner: R, limits: AwwasmParseLimits) -> Self {
+        AwwasmAsyncPayloadStream { inner, offset: 0, state: State::Preamble, limits, sections: 0 }
+    }
+
+    /// Offset in the module of the next byte to be read.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// The next event, or `None` after `End` or an error.
+    pub async fn next(&mut self) -> Option<anyhow::Result<AwwasmAsyncPayload>> {
+        if self.state == State::Done {
+            return None;
+        }
+        let payload = self.step().await;
+        if payload.is_err() {
+            self.state = State::Done;
+        }
+        Some(payload)
+    }
+
+    async fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0u8; 1];
+        match self.inner.read(&mut byte).await? {
+            0 => Ok(None),
+            _ => {
+                self.offset += 1;
+                Ok(Some(byte[0]))
+            }
+        }
+    }
+
+    // Append up to `n` bytes to `buf`, returning how many arrived.
+    async fn read_up_to(&mut self, n: usize, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let read = (&mut self.inner).take(n as u64).read_to_end(buf).await?;
+        self.offset += read;
+        Ok(read)
+    }
+
+    // Append a LEB128 u32 to `buf`, as far as it goes.
+    async fn read_leb128(&mut self, buf: &mut Vec<u8>) -> io::Result<()> {
+        for _ in 0..MAX_LEB128_U32_BYTES {
+            let Some(byte) = self.read_byte().await? else { break };
+            buf.push(byte);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn step(&mut self) -> anyhow::Result<AwwasmAsyncPayload> {
+        if let State::Code { remaining: 0, body } = self.state {
+            // Bytes after the last entry are skipped, as `resolve()` does.
+            self.read_up_to(body, &mut Vec::new()).await?;
+            self.state = State::Sections;
+        }
+        match self.state {
+            State::Preamble => {
+                let mut buf = Vec::with_capacity(PREAMBLE_BYTES);
+                self.read_up_to(PREAMBLE_BYTES, &mut buf).await?;
+                let preamble = AwwasmModulePreamble::new(&buf)?;
+                preamble.check_core_module()?;
+                self.state = State::Sections;
+                Ok(AwwasmAsyncPayload::Version(preamble.version))
+            }
+            State::Sections => {
+                let offset = self.offset;
+                let Some(id) = self.read_byte().await? else {
+                    self.state = State::Done;
+                    return Ok(AwwasmAsyncPayload::End);
+                };
+                let mut received = AwwasmReceived { offset, bytes: vec![id] };
+                self.read_leb128(&mut received.bytes).await?;
+                let (_, header) = complete(AwwasmSectionHeader::parse)(&received.bytes[..])
+                    .map_err(|e| received.located(AwwasmParseError::section_header("section header", e).within(&received.bytes)))?;
+                let size = header.section_size as usize;
+                self.sections += 1;
+                exceeds("sections", None, self.sections as u64, self.limits.max_sections as u64)?;
+                if header.section_type == SectionCode::Code {
+                    let mut count = Vec::new();
+                    self.read_leb128(&mut count).await?;
+                    let count_at = AwwasmReceived { offset: self.offset - count.len(), bytes: count };
+                    let (_, entries) = complete(leb128_u32)(&count_at.bytes[..])
+                        .map_err(|e| count_at.located(AwwasmParseError::nom("Code Section", e).within(&count_at.bytes)))?;
+                    exceeds("section entries", Some(&SectionCode::Code), entries as u64, self.limits.max_section_entries as u64)?;
+                    let body = size.checked_sub(count_at.bytes.len()).ok_or_else(|| AwwasmParseError::from(
+                        TruncatedSection { section: SectionCode::Code, declared: header.section_size, available: count_at.bytes.len() }
+                    ))?;
+                    self.state = State::Code { remaining: entries, body };
+                    return Ok(AwwasmAsyncPayload::CodeSectionStart { count: entries });
+                }
+                let read = self.read_up_to(size, &mut received.bytes).await?;
+                if read < size {
+                    return Err(AwwasmParseError::from(TruncatedSection { section: header.section_type, declared: header.section_size, available: read }).into());
+                }
+                // A section that does not decode is left for the consumer.
+                if let Ok(section) = received.section() {
+                    self.limits.check_section(&section)?;
+                }
+                Ok(AwwasmAsyncPayload::Section(received))
+            }
+            State::Code { remaining, body } => {
+                let mut received = AwwasmReceived { offset: self.offset, bytes: Vec::new() };
+                self.read_leb128(&mut received.bytes).await?;
+                let (_, size) = complete(leb128_u32)(&received.bytes[..])
+                    .map_err(|e| received.located(AwwasmParseError::nom("Code Section", e).within(&received.bytes)))?;
+                exceeds("function body size", Some(&SectionCode::Code), size as u64, self.limits.max_function_body_size as u64)?;
+                let size = size as usize;
+                let prefix = received.bytes.len();
+                let available = body.saturating_sub(prefix);
+                let read = self.read_up_to(size.min(available), &mut received.bytes).await?;
+                if prefix > body || read < size {
+                    // The body runs past the end of the code section or the input.
+                    let mut err = AwwasmParseError::new("Code Section", AwwasmParseErrorKind::Truncated);
+                    (err.offset, err.section) = (Some(self.offset), Some(SectionCode::Code));
+                    return Err(err.into());
+                }
+                if let Ok(item) = received.code() {
+                    self.limits.check_body(&item)?;
+                }
+                self.state = State::Code { remaining: remaining - 1, body: body - prefix - size };
+                Ok(AwwasmAsyncPayload::CodeEntry(received))
+            }
+            State::Done => Ok(AwwasmAsyncPayload::End),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::async_stream::{AwwasmAsyncPayload, AwwasmAsyncPayloadStream};
+    use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+    use crate::components::parse_limits::AwwasmParseLimits;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncRead, ReadBuf};
+
+    // Delivers at most three bytes per read, like a slow download.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(mut self: Pin<&mut Self>, _: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+            let n = self.0.len().min(buf.remaining()).min(3);
+            buf.put_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    async fn collect_events(bytes: &[u8]) -> Vec<anyhow::Result<String>> {
+        let mut stream = AwwasmAsyncPayloadStream::new(Trickle(bytes));
+        let mut events = Vec::new();
+        while let Some(payload) = stream.next().await {
+            events.push(payload.and_then(|p| Ok(match p {
+                AwwasmAsyncPayload::Section(received) => format!("{:?} at {}", received.section()?.section_header.section_type, received.offset),
+                AwwasmAsyncPayload::CodeEntry(received) => format!("body {:02x?} at {}", received.code()?.func_body, received.offset),
+                other => format!("{:?}", other),
+            })));
+        }
+        events
+    }
+
+    #[test]
+    fn async_payload_stream_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"(module (func (export "f")) (func (nop)) (data "hi"))"#)?;
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        let events: Vec<String> = runtime.block_on(collect_events(&bytes)).into_iter().collect::<anyhow::Result<_>>()?;
+        assert_eq!(events, vec![
+            "Version(1)", "Type at 8", "Function at 14", "Export at 19", "CodeSectionStart { count: 2 }",
+            "body [00, 0b] at 29", "body [00, 01, 0b] at 32", "Data at 36", "End",
+        ]);
+
+        let events = runtime.block_on(collect_events(&bytes[..bytes.len() - 1]));
+        let err = events.last().expect("stream should yield").as_ref().unwrap_err();
+        assert!(matches!(err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind), Some(AwwasmParseErrorKind::TruncatedSection(_))));
+
+        // The second body is over the limit before its bytes are read.
+        let limits = AwwasmParseLimits { max_function_body_size: 2, ..Default::default() };
+        let mut stream = AwwasmAsyncPayloadStream::with_limits(Trickle(&bytes), limits);
+        let err = runtime.block_on(async {
+            loop {
+                match stream.next().await {
+                    Some(Err(err)) => return err,
+                    Some(Ok(_)) => {}
+                    None => panic!("stream should fail"),
+                }
+            }
+        });
+        assert_eq!(stream.offset(), 33);
+        assert!(matches!(err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind), Some(AwwasmParseErrorKind::LimitExceeded { actual: 3, max: 2, .. })));
+        Ok(())
+    }
+}
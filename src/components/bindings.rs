@@ -0,0 +1,129 @@
+use crate::components::module::AwwasmModule;
+use crate::components::types::{AwwasmTypeSectionItem, ValType};
+
+// Parameter name fragments that suggest a pointer into linear memory, or the
+// length of the region it points to.
+const PTR_HINTS: [&str; 5] = ["ptr", "buf", "addr", "data", "str"];
+const LEN_HINTS: [&str; 4] = ["len", "size", "count", "cap"];
+
+/// How a signature maps onto a typical native host calling convention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmAbiClass {
+    /// Scalar parameters and at most one scalar result: passes in registers.
+    Registers,
+    /// More than one result: hosts without multi-value lower the results
+    /// through a caller-allocated return area in linear memory.
+    ReturnArea,
+    /// Takes or returns `v128` or reference values, which need engine-specific glue.
+    Opaque,
+}
+
+/// Two adjacent parameters that look like a (pointer, length) pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmPtrLenPair {
+    pub ptr: u32,
+    pub len: u32,
+}
+
+/// Result of `classify()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmAbi {
+    pub class: AwwasmAbiClass,
+    /// Parameter pairs that look like a buffer or string, in order.
+    pub ptr_len_pairs: Vec<AwwasmPtrLenPair>,
+}
+
+/// Classify a function signature for FFI glue generation.
+///
+/// `param_names` are optional names for the parameters, by position (e.g.
+/// from the name section's local names); they drive the pointer/length
+/// heuristic, which needs an integer parameter whose name suggests a pointer
+/// followed by one whose name suggests a length. Without names no pairs are
+/// reported.
+pub fn classify(func_type: &AwwasmTypeSectionItem, param_names: &[Option<&str>]) -> AwwasmAbi {
+    let scalar = |t: &ValType| t.is_num();
+    let class = if !func_type.fn_args.iter().chain(&func_type.fn_rets).all(scalar) {
+        AwwasmAbiClass::Opaque
+    } else if func_type.fn_rets.len() > 1 {
+        AwwasmAbiClass::ReturnArea
+    } else {
+        AwwasmAbiClass::Registers
+    };
+
+    let is_int = |t: &ValType| matches!(t, ValType::I32 | ValType::I64);
+    let named = |i: usize, hints: &[&str]| {
+        param_names.get(i).copied().flatten()
+            .map(|n| n.to_ascii_lowercase())
+            .is_some_and(|n| hints.iter().any(|h| n.contains(h)))
+    };
+    let mut ptr_len_pairs = Vec::new();
+    let mut i = 0;
+    while i + 1 < func_type.fn_args.len() {
+        if is_int(&func_type.fn_args[i]) && is_int(&func_type.fn_args[i + 1]) && named(i, &PTR_HINTS) && named(i + 1, &LEN_HINTS) {
+            ptr_len_pairs.push(AwwasmPtrLenPair { ptr: i as u32, len: i as u32 + 1 });
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    AwwasmAbi { class, ptr_len_pairs }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Classify the function at `func_idx`, using its parameter names from
+    /// the name section when there is one.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn function_abi(&self, func_idx: u32) -> anyhow::Result<AwwasmAbi> {
+        let func_type = self.signature_of(func_idx)
+            .ok_or_else(|| anyhow::anyhow!("Function {} has no signature in the module", func_idx))?;
+        let names = self.names()?;
+        let param_names: Vec<Option<&str>> = (0..func_type.fn_args.len() as u32)
+            .map(|i| names.as_ref().and_then(|n| n.local_name(func_idx, i)))
+            .collect();
+        Ok(classify(func_type, &param_names))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::bindings::{AwwasmAbiClass, AwwasmPtrLenPair};
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn function_abi_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "now" (func (result i64)))
+                (func (param $msg_ptr i32) (param $msg_len i32))
+                (func (param $fd i32) (param $buf i32) (param $buf_len i32) (param $flags i32) (result i32)
+                    (i32.const 0))
+                (func (param i32 i32) (result i32 i64)
+                    (i32.const 0) (i64.const 0))
+                (func (param externref))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let now = module_parsed.function_abi(0)?;
+        assert_eq!(now.class, AwwasmAbiClass::Registers);
+        assert!(now.ptr_len_pairs.is_empty());
+
+        let log = module_parsed.function_abi(1)?;
+        assert_eq!(log.class, AwwasmAbiClass::Registers);
+        assert_eq!(log.ptr_len_pairs, vec![AwwasmPtrLenPair { ptr: 0, len: 1 }]);
+
+        let write = module_parsed.function_abi(2)?;
+        assert_eq!(write.class, AwwasmAbiClass::Registers);
+        assert_eq!(write.ptr_len_pairs, vec![AwwasmPtrLenPair { ptr: 1, len: 2 }]);
+
+        let unnamed = module_parsed.function_abi(3)?;
+        assert_eq!(unnamed.class, AwwasmAbiClass::ReturnArea);
+        assert!(unnamed.ptr_len_pairs.is_empty());
+
+        assert_eq!(module_parsed.function_abi(4)?.class, AwwasmAbiClass::Opaque);
+        assert!(module_parsed.function_abi(5).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+use crate::components::error::AwwasmParseError;
+use crate::components::index_space::{AwwasmDefinedFunc, AwwasmIndexed};
+use crate::components::instructions::{span_in, AwwasmInstruction};
+use crate::components::module::AwwasmModule;
+use crate::components::passes::write_name;
+use crate::components::section::write_leb128_u32;
+use num_derive::FromPrimitive;
+use nom_derive::*;
+use crate::components::leb128::leb128_u32;
+use nom::combinator::{all_consuming, verify};
+use nom::multi::length_count;
+
+pub(crate) const BRANCH_HINT_SECTION: &[u8] = b"metadata.code.branch_hint";
+
+/// Which way a hinted `if` or `br_if` is expected to go.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, Nom)]
+#[nom(LittleEndian)]
+pub enum AwwasmBranchHintValue {
+    Unlikely = 0,
+    Likely = 1,
+}
+
+/// A hint for the branch instruction at `offset`, counted in bytes from the
+/// start of the function body (its local declarations included).
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmBranchHint {
+    #[nom(Parse = "leb128_u32")]
+    pub offset: u32,
+    /// Size of the hint value; always 1.
+    #[nom(Parse = "verify(leb128_u32, |size| *size == 1)")]
+    pub size: u32,
+    pub value: AwwasmBranchHintValue,
+}
+
+/// The branch hints of one function, in increasing offset order.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmFunctionBranchHints {
+    #[nom(Parse = "leb128_u32")]
+    pub func_idx: u32,
+    #[nom(LengthCount = "leb128_u32")]
+    pub hints: Vec<AwwasmBranchHint>,
+}
+
+/// Decoded contents of the `metadata.code.branch_hint` custom section.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmBranchHintSection {
+    /// Functions with hints, in increasing function index order.
+    pub functions: Vec<AwwasmFunctionBranchHints>,
+}
+
+impl AwwasmBranchHintSection {
+    /// Decode the payload of a `metadata.code.branch_hint` custom section.
+    pub fn parse(payload: &[u8]) -> anyhow::Result<Self> {
+        let (_, functions) = all_consuming(length_count(leb128_u32, AwwasmFunctionBranchHints::parse))(payload)
+            .map_err(|e| AwwasmParseError::nom("Branch Hint Section", e))?;
+        Ok(Self { functions })
+    }
+
+    /// Hints of the function at `func_idx` in the function index space.
+    pub fn function(&self, func_idx: u32) -> Option<&[AwwasmBranchHint]> {
+        self.functions.iter().find(|f| f.func_idx == func_idx).map(|f| &f.hints[..])
+    }
+
+    /// Encode the section as a custom section payload, name included.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_name(&mut out, BRANCH_HINT_SECTION);
+        write_leb128_u32(&mut out, self.functions.len() as u32);
+        for f in &self.functions {
+            write_leb128_u32(&mut out, f.func_idx);
+            write_leb128_u32(&mut out, f.hints.len() as u32);
+            for hint in &f.hints {
+                write_leb128_u32(&mut out, hint.offset);
+                write_leb128_u32(&mut out, hint.size);
+                out.push(hint.value as u8);
+            }
+        }
+        out
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Decode the `metadata.code.branch_hint` custom section, if the module has one.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn branch_hints(&self) -> anyhow::Result<Option<AwwasmBranchHintSection>> {
+        self.custom_section(BRANCH_HINT_SECTION)
+            .map(|c| AwwasmBranchHintSection::parse(c.payload))
+            .transpose()
+    }
+
+    /// The instructions of the function at `func_idx` that carry a branch
+    /// hint, with the hint, in body order. Nested instructions are included;
+    /// hints at an offset where no instruction starts are dropped.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn hinted_instructions(&self, func_idx: u32) -> anyhow::Result<Vec<(AwwasmInstruction<'a>, AwwasmBranchHintValue)>> {
+        let Some(section) = self.branch_hints()? else {
+            return Ok(Vec::new());
+        };
+        let Some(hints) = section.function(func_idx) else {
+            return Ok(Vec::new());
+        };
+        let item = match self.func(func_idx) {
+            Some(AwwasmIndexed::Defined(AwwasmDefinedFunc { code: Some(code), .. })) => Some(code),
+            _ => None,
+        };
+        let item = item.ok_or_else(|| anyhow::anyhow!("Branch hints reference function {} which has no body", func_idx))?;
+
+        let mut hinted = Vec::new();
+        for instr in &item.instructions_with_limits(&self.limits)? {
+            instr.walk(&mut |instr| {
+                let Some(span) = span_in(item.func_body, instr.encoding) else { return };
+                if let Some(hint) = hints.iter().find(|h| h.offset as usize == span.start) {
+                    hinted.push((instr.clone(), hint.value));
+                }
+            });
+        }
+        Ok(hinted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::branch_hints::{AwwasmBranchHintSection, AwwasmBranchHintValue};
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn decode_branch_hint_section_test() -> anyhow::Result<()> {
+        // Function 1 (after one import): `if` at offset 3 is likely, the
+        // nested `br_if` at offset 9 unlikely.
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "f" (func))
+                (@custom "metadata.code.branch_hint" (before code) "\01\01\02\03\01\01\09\01\00")
+                (func (param i32) (result i32)
+                    (if (result i32) (local.get 0)
+                        (then (block (br_if 0 (local.get 0))) (i32.const 1))
+                        (else (i32.const 2))))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let section = module_parsed.branch_hints()?.expect("branch hint section should exist");
+        assert_eq!(section.functions.len(), 1);
+        assert_eq!(section.function(0), None);
+        let custom = module_parsed.custom_section(b"metadata.code.branch_hint").expect("custom section");
+        assert_eq!(section.encode()[26..], custom.payload[..]);
+
+        let hinted: Vec<(String, AwwasmBranchHintValue)> = module_parsed.hinted_instructions(1)?.into_iter()
+            .map(|(instr, value)| (instr.to_string(), value))
+            .collect();
+        assert_eq!(hinted, vec![
+            ("if i32".to_owned(), AwwasmBranchHintValue::Likely),
+            ("br_if 0".to_owned(), AwwasmBranchHintValue::Unlikely),
+        ]);
+        assert!(module_parsed.hinted_instructions(0)?.is_empty());
+
+        assert!(AwwasmBranchHintSection::parse(b"\x01\x00\x01\x03\x02\x01").is_err());
+        Ok(())
+    }
+}
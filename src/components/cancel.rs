@@ -0,0 +1,117 @@
+use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How many instructions are decoded between cancellation checks, counting
+/// top-level instructions and, separately, those within any one's bodies.
+pub const CANCEL_CHECK_INTERVAL: usize = 1024;
+
+/// Cooperative cancellation for long-running parses.
+///
+/// Clones share the same flag, so one clone can be handed to the parsing
+/// thread while another is cancelled from a watchdog. A token can also carry a
+/// deadline, after which it reports itself cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that cancels itself once `timeout` has elapsed.
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self {
+            cancelled: Arc::default(),
+            deadline: Instant::now().checked_add(timeout),
+        }
+    }
+
+    /// Request cancellation; parses using this token stop at their next check.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    /// Fail with an `AwwasmParseError` of kind `Cancelled` if cancellation
+    /// was requested.
+    pub fn check(&self) -> Result<(), AwwasmParseError> {
+        if self.is_cancelled() {
+            Err(AwwasmParseError::module(AwwasmParseErrorKind::Cancelled))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::cancel::CancellationToken;
+    use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+    use crate::components::instructions::{InstrContext, InstructionIterator};
+    use crate::components::module::AwwasmModule;
+    use std::time::Duration;
+
+    fn is_cancelled(err: &anyhow::Error) -> bool {
+        err.downcast_ref::<AwwasmParseError>().is_some_and(|e| e.kind == AwwasmParseErrorKind::Cancelled)
+    }
+
+    #[test]
+    fn cancelled_token_stops_parsing_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1)
+                (func (result i32) i32.const 1)
+            )
+        "#)?;
+
+        let token = CancellationToken::new();
+        let mut module_parsed = AwwasmModule::new_with_cancel(&module, &token)?;
+        module_parsed.resolve_all_sections_with_cancel(&token)?;
+        let code = module_parsed.code.clone().expect("code should exist");
+        assert_eq!(code[0].instructions_with_cancel(&token)?.len(), 2);
+
+        // A clone shares the flag.
+        token.clone().cancel();
+        let err = AwwasmModule::new_with_cancel(&module, &token).unwrap_err();
+        assert!(is_cancelled(&err));
+        let err = module_parsed.resolve_all_sections_with_cancel(&token).unwrap_err();
+        assert!(is_cancelled(&err));
+        let err = code[0].instructions_with_cancel(&token).unwrap_err();
+        assert!(is_cancelled(&err));
+        Ok(())
+    }
+
+    #[test]
+    fn cancelled_token_stops_nested_body_test() -> anyhow::Result<()> {
+        // A single block holding thousands of instructions is one top-level
+        // instruction, so only the check inside the body can stop it.
+        let body = [&[0x02, 0x40][..], &[0x01; 3000], &[0x0b]].concat();
+        let token = CancellationToken::new();
+        let ctx = InstrContext::default().with_cancel(&token);
+        assert!(InstructionIterator::with_context(&body, ctx.clone()).all(|instr| instr.is_ok()));
+
+        token.cancel();
+        let err = InstructionIterator::with_context(&body, ctx).next().expect("should yield").unwrap_err();
+        assert_eq!(AwwasmParseError::instruction("Function body", err).kind, AwwasmParseErrorKind::Cancelled);
+        Ok(())
+    }
+
+    #[test]
+    fn expired_deadline_cancels_test() -> anyhow::Result<()> {
+        let module = wat::parse_str("(module (func))")?;
+        let token = CancellationToken::with_timeout(Duration::ZERO);
+        assert!(token.is_cancelled());
+        assert!(is_cancelled(&AwwasmModule::new_with_cancel(&module, &token).unwrap_err()));
+        assert!(!CancellationToken::with_timeout(Duration::from_secs(3600)).is_cancelled());
+        Ok(())
+    }
+}
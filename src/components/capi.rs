@@ -0,0 +1,407 @@
+//! C ABI for streaming decoded function bodies. The declarations are in
+//! `include/awwasm.h`.
+
+use crate::components::instructions::*;
+use crate::components::types::{AwwasmFunctionLocals, ValType};
+use nom::multi::length_count;
+use nom_derive::Parse;
+use crate::components::leb128::leb128_u32;
+use std::collections::VecDeque;
+
+/// Which member of `AwwasmCOperands` a record uses.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmCOperandKind {
+    None = 0,
+    /// `index`: a label, function, local or global index, or the memory index
+    /// of `memory.size`/`memory.grow`.
+    Index = 1,
+    /// `index_pair`: the type and table index of `call_indirect` and
+    /// `return_call_indirect`.
+    IndexPair = 2,
+    MemArg = 3,
+    I32 = 4,
+    I64 = 5,
+    /// `f32_bits`: the constant's bit pattern.
+    F32 = 6,
+    /// `f64_bits`: the constant's bit pattern.
+    F64 = 7,
+    /// `block_type`: `0x40` for no result, otherwise a value type byte.
+    /// `(ref null? ht)` block types are `RefType` records.
+    BlockType = 8,
+    /// `br_table`: the target count and default. The targets themselves are
+    /// in the record's encoding.
+    BrTable = 9,
+    /// `val_type`: the value type byte of a typed `select` or `ref.null`.
+    /// `(ref null? ht)` types are `RefType` records.
+    ValType = 10,
+    /// `index`: a block type given as a type section index (multi-value).
+    BlockTypeIndex = 11,
+    /// `ref_type`: a `(ref null? ht)` block type, typed `select` type or
+    /// `ref.null` type (function references proposal).
+    RefType = 12,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmCMemArg {
+    pub align: u32,
+    pub offset: u32,
+    /// Memory index; 0 unless the module uses multi-memory.
+    pub memory: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmCBrTable {
+    pub target_count: u32,
+    pub default_target: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmCRefType {
+    /// `0x63` for `(ref null ht)`, `0x64` for `(ref ht)`.
+    pub type_byte: u8,
+    /// The heap type's s33 value: a type index, or negative for an abstract
+    /// heap type (-16 for `func`, -17 for `extern`).
+    pub heap_type: i64,
+}
+
+/// Immediates of one instruction; `AwwasmCInstr::kind` says which member is set.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union AwwasmCOperands {
+    pub index: u32,
+    pub index_pair: [u32; 2],
+    pub mem_arg: AwwasmCMemArg,
+    pub i32: i32,
+    pub i64: i64,
+    pub f32_bits: u32,
+    pub f64_bits: u64,
+    pub block_type: u8,
+    pub br_table: AwwasmCBrTable,
+    pub val_type: u8,
+    pub ref_type: AwwasmCRefType,
+}
+
+/// One decoded instruction. Nested blocks are flattened: their instructions
+/// follow the `block`, `loop`, `if`, `try` or `try_table` record one level
+/// deeper, and the `else`, `catch`, `catch_all`, `delegate` and `end`
+/// markers that close them are records of their own.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AwwasmCInstr {
+    /// Opcode byte (`0xFC` for the misc prefix, `0xFE` for atomics).
+    pub opcode: u8,
+    /// Sub-opcode of a `0xFC` or `0xFE` instruction, otherwise 0.
+    pub sub_opcode: u32,
+    /// Offset of the instruction from the start of the body passed to
+    /// `awwasm_body_iter_new`.
+    pub offset: usize,
+    /// Encoded length; for structured instructions only the opcode, block
+    /// type and (for `try_table`) catch clauses.
+    pub length: usize,
+    /// Block nesting depth.
+    pub depth: u32,
+    pub kind: AwwasmCOperandKind,
+    pub operands: AwwasmCOperands,
+}
+
+const NO_OPERANDS: AwwasmCOperands = AwwasmCOperands { f64_bits: 0 };
+
+/// Iterator state behind the C handle.
+pub struct AwwasmBodyIter {
+    body: &'static [u8],
+    instrs: InstructionIterator<'static>,
+    // Flattened records of the current top-level instruction.
+    pending: VecDeque<AwwasmCInstr>,
+    failed: bool,
+}
+
+impl AwwasmBodyIter {
+    fn new(body: &'static [u8]) -> Option<Self> {
+        let (code, _) = length_count(leb128_u32::<nom::error::Error<&[u8]>>, AwwasmFunctionLocals::parse)(body).ok()?;
+        Some(Self { body, instrs: InstructionIterator::new(code), pending: VecDeque::new(), failed: false })
+    }
+
+    fn next_record(&mut self) -> Option<Result<AwwasmCInstr, ()>> {
+        if self.failed {
+            return Some(Err(()));
+        }
+        if self.pending.is_empty() {
+            match self.instrs.next()? {
+                Ok(instr) => self.flatten(&instr, 0),
+                Err(_) => {
+                    self.failed = true;
+                    return Some(Err(()));
+                }
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+
+    fn flatten(&mut self, instr: &AwwasmInstruction, depth: u32) {
+        let (kind, operands) = c_operands(&instr.operands);
+        let sub_opcode = match &instr.operands {
+            AwwasmOperands::Misc(op) => op.sub_op as u32,
+            AwwasmOperands::Atomic(op) => op.sub_op as u32,
+            _ => 0,
+        };
+        self.push(instr.encoding, sub_opcode, depth, kind, operands);
+        for (instrs, marker) in instr.operands.bodies() {
+            for instr in instrs {
+                self.flatten(instr, depth + 1);
+            }
+            // `catch` and `delegate` markers carry an index.
+            let (kind, operands) = AwwasmInstruction::parse(marker)
+                .map_or((AwwasmCOperandKind::None, NO_OPERANDS), |(_, m)| c_operands(&m.operands));
+            self.push(marker, 0, depth, kind, operands);
+        }
+    }
+
+    fn push(&mut self, encoding: &[u8], sub_opcode: u32, depth: u32, kind: AwwasmCOperandKind, operands: AwwasmCOperands) {
+        self.pending.push_back(AwwasmCInstr {
+            opcode: encoding.first().copied().unwrap_or_default(),
+            sub_opcode,
+            offset: span_in(self.body, encoding).map_or(0, |span| span.start),
+            length: encoding.len(),
+            depth,
+            kind,
+            operands,
+        });
+    }
+}
+
+// A value type immediate as a `kind` record, or a `RefType` record for
+// `(ref null? ht)`, whose heap type does not fit in one byte.
+fn c_val_type(kind: AwwasmCOperandKind, ty: ValType) -> (AwwasmCOperandKind, AwwasmCOperands) {
+    match ty {
+        ValType::Ref(r) => (AwwasmCOperandKind::RefType, AwwasmCOperands {
+            ref_type: AwwasmCRefType { type_byte: ty.encode(), heap_type: r.heap_type.s33() },
+        }),
+        _ if kind == AwwasmCOperandKind::BlockType => (kind, AwwasmCOperands { block_type: ty.encode() }),
+        _ => (kind, AwwasmCOperands { val_type: ty.encode() }),
+    }
+}
+
+fn c_operands(operands: &AwwasmOperands) -> (AwwasmCOperandKind, AwwasmCOperands) {
+    use AwwasmOperands::*;
+    let index = |index: u32| (AwwasmCOperandKind::Index, AwwasmCOperands { index });
+    if let Some(m) = operands.mem_arg() {
+        return (AwwasmCOperandKind::MemArg, AwwasmCOperands { mem_arg: AwwasmCMemArg { align: m.align, offset: m.offset, memory: m.memory() } });
+    }
+    match operands.block_type() {
+        Some(BlockType::Empty) => return (AwwasmCOperandKind::BlockType, AwwasmCOperands { block_type: 0x40 }),
+        Some(BlockType::Value(ty)) => return c_val_type(AwwasmCOperandKind::BlockType, ty),
+        Some(BlockType::TypeIndex(idx)) => return (AwwasmCOperandKind::BlockTypeIndex, AwwasmCOperands { index: idx }),
+        None => {}
+    }
+    match operands {
+        Br(op) | BrIf(op) | BrOnNull(op) | BrOnNonNull(op) | Rethrow(op) | Delegate(op) => index(op.labelidx),
+        CallRef(op) | ReturnCallRef(op) => index(op.typeidx),
+        Throw(op) | Catch(op) => index(op.index),
+        BrTable(op) => (AwwasmCOperandKind::BrTable, AwwasmCOperands {
+            br_table: AwwasmCBrTable { target_count: op.target_count, default_target: op.default },
+        }),
+        Call(op) | ReturnCall(op) | RefFunc(op) => index(op.funcidx),
+        RefNull(op) => c_val_type(AwwasmCOperandKind::ValType, ValType::from(op.heap_type)),
+        CallIndirect(op) | ReturnCallIndirect(op) => (AwwasmCOperandKind::IndexPair, AwwasmCOperands { index_pair: [op.typeidx, op.tableidx] }),
+        SelectTyped(op) => match op.types.first() {
+            Some(ty) => c_val_type(AwwasmCOperandKind::ValType, *ty),
+            None => (AwwasmCOperandKind::ValType, AwwasmCOperands { val_type: 0 }),
+        },
+        LocalGet(op) | LocalSet(op) | LocalTee(op) | GlobalGet(op) | GlobalSet(op) => index(op.index),
+        MemorySize(op) | MemoryGrow(op) => index(op.memidx),
+        Misc(op) => match &op.operands {
+            AwwasmMiscOperands::MemoryInit(m) => (AwwasmCOperandKind::IndexPair, AwwasmCOperands { index_pair: [m.dataidx, m.memidx] }),
+            AwwasmMiscOperands::DataDrop(d) => index(d.dataidx),
+            AwwasmMiscOperands::MemoryCopy(m) => (AwwasmCOperandKind::IndexPair, AwwasmCOperands { index_pair: [m.dst_memidx, m.src_memidx] }),
+            AwwasmMiscOperands::MemoryFill(m) => index(m.index),
+            _ => (AwwasmCOperandKind::None, NO_OPERANDS),
+        },
+        I32Const(op) => (AwwasmCOperandKind::I32, AwwasmCOperands { i32: op.value }),
+        I64Const(op) => (AwwasmCOperandKind::I64, AwwasmCOperands { i64: op.value }),
+        F32Const(op) => (AwwasmCOperandKind::F32, AwwasmCOperands { f32_bits: op.value.to_bits() }),
+        F64Const(op) => (AwwasmCOperandKind::F64, AwwasmCOperands { f64_bits: op.value.to_bits() }),
+        _ => (AwwasmCOperandKind::None, NO_OPERANDS),
+    }
+}
+
+/// Start decoding a function body: its locals followed by its instructions,
+/// as stored in a code section entry. Returns null if `body` is null or its
+/// locals do not decode.
+///
+/// # Safety
+///
+/// `body` must point to `len` readable bytes that stay valid and unchanged
+/// until the iterator is freed.
+#[no_mangle]
+pub unsafe extern "C" fn awwasm_body_iter_new(body: *const u8, len: usize) -> *mut AwwasmBodyIter {
+    if body.is_null() {
+        return std::ptr::null_mut();
+    }
+    let body = std::slice::from_raw_parts(body, len);
+    match AwwasmBodyIter::new(body) {
+        Some(iter) => Box::into_raw(Box::new(iter)),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Decode the next record into `out`. Returns 1 if a record was written, 0 at
+/// the end of the body, and -1 on a decode error (and on every call after one)
+/// or a null argument.
+///
+/// # Safety
+///
+/// `iter` must come from `awwasm_body_iter_new` and not have been freed; `out`
+/// must point to writable memory for one `AwwasmCInstr`.
+#[no_mangle]
+pub unsafe extern "C" fn awwasm_body_iter_next(iter: *mut AwwasmBodyIter, out: *mut AwwasmCInstr) -> i32 {
+    let (Some(iter), false) = (iter.as_mut(), out.is_null()) else {
+        return -1;
+    };
+    match iter.next_record() {
+        Some(Ok(record)) => {
+            out.write(record);
+            1
+        }
+        Some(Err(())) => -1,
+        None => 0,
+    }
+}
+
+/// Free an iterator. Null is ignored.
+///
+/// # Safety
+///
+/// `iter` must be null or come from `awwasm_body_iter_new`, and must not be
+/// used again.
+#[no_mangle]
+pub unsafe extern "C" fn awwasm_body_iter_free(iter: *mut AwwasmBodyIter) {
+    if !iter.is_null() {
+        drop(Box::from_raw(iter));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::capi::*;
+    use crate::components::module::AwwasmModule;
+    use std::mem::MaybeUninit;
+
+    fn records(body: &[u8]) -> (Vec<AwwasmCInstr>, i32) {
+        let mut out = Vec::new();
+        unsafe {
+            let iter = awwasm_body_iter_new(body.as_ptr(), body.len());
+            assert!(!iter.is_null());
+            let mut record = MaybeUninit::<AwwasmCInstr>::uninit();
+            let status = loop {
+                match awwasm_body_iter_next(iter, record.as_mut_ptr()) {
+                    1 => out.push(record.assume_init()),
+                    status => break status,
+                }
+            };
+            awwasm_body_iter_free(iter);
+            (out, status)
+        }
+    }
+
+    #[test]
+    fn body_iter_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1)
+                (func (param i32) (local i64)
+                    (if (local.get 0)
+                        (then (drop (i64.load offset=16 (i32.const 0))))
+                        (else (call_indirect (i32.const 1))))
+                    (drop (f32.const 1.5))
+                    (i32.trunc_sat_f32_u (f32.const 0)) (drop))
+                (table 1 funcref)
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        let body = module_parsed.code.as_ref().expect("code should exist")[0].func_body;
+
+        let (records, status) = records(body);
+        assert_eq!(status, 0);
+        let opcodes: Vec<(u8, u32)> = records.iter().map(|r| (r.opcode, r.depth)).collect();
+        assert_eq!(opcodes, vec![
+            (0x20, 0), (0x04, 0),
+            (0x41, 1), (0x29, 1), (0x1a, 1), (0x05, 0),
+            (0x41, 1), (0x11, 1), (0x0b, 0),
+            (0x43, 0), (0x1a, 0), (0x43, 0), (0xfc, 0), (0x1a, 0), (0x0b, 0),
+        ]);
+        // The body starts with one local declaration (0x01 0x01 0x7e).
+        assert_eq!((records[0].offset, records[0].length), (3, 2));
+        assert_eq!(records[1].kind, AwwasmCOperandKind::BlockType);
+        unsafe {
+            assert_eq!(records[1].operands.block_type, 0x40);
+            assert_eq!(records[3].operands.mem_arg, AwwasmCMemArg { align: 3, offset: 16, memory: 0 });
+            assert_eq!(records[7].operands.index_pair, [1, 0]);
+            assert_eq!(records[9].operands.f32_bits, 1.5f32.to_bits());
+        }
+        assert_eq!((records[12].sub_opcode, records[12].kind), (1, AwwasmCOperandKind::None));
+        Ok(())
+    }
+
+    #[test]
+    fn body_iter_ref_types_test() -> anyhow::Result<()> {
+        // The type index of each `(ref null? 1)` immediate is kept. No locals,
+        // `block (result (ref null 1)) ref.null 1 end`, `ref.null 1`,
+        // `ref.null 1`, `i32.const 0`, `select (result (ref null 1))`, two
+        // `drop`s, `ref.null func`, `drop`, `end`.
+        let body = [
+            0x00, 0x02, 0x63, 0x01, 0xd0, 0x01, 0x0b, 0xd0, 0x01, 0xd0, 0x01, 0x41, 0x00,
+            0x1c, 0x01, 0x63, 0x01, 0x1a, 0x1a, 0xd0, 0x70, 0x1a, 0x0b,
+        ];
+        let (records, status) = records(&body);
+        assert_eq!(status, 0);
+        let ref_types: Vec<(u8, AwwasmCRefType)> = records.iter()
+            .filter(|r| r.kind == AwwasmCOperandKind::RefType)
+            .map(|r| (r.opcode, unsafe { r.operands.ref_type }))
+            .collect();
+        let nullable_u = AwwasmCRefType { type_byte: 0x63, heap_type: 1 };
+        assert_eq!(ref_types, vec![(0x02, nullable_u), (0xd0, nullable_u), (0xd0, nullable_u), (0xd0, nullable_u), (0x1c, nullable_u)]);
+        let func = records.iter().rev().find(|r| r.opcode == 0xd0).expect("ref.null func");
+        assert_eq!((func.kind, unsafe { func.operands.val_type }), (AwwasmCOperandKind::ValType, 0x70));
+        Ok(())
+    }
+
+    #[test]
+    fn body_iter_atomic_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1 1 shared)
+                (func (drop (i32.atomic.rmw.add offset=8 (i32.const 0) (i32.const 1))))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        let body = module_parsed.code.as_ref().expect("code should exist")[0].func_body;
+
+        let (records, status) = records(body);
+        assert_eq!(status, 0);
+        let atomic = &records[2];
+        assert_eq!((atomic.opcode, atomic.sub_opcode, atomic.kind), (0xfe, 0x1e, AwwasmCOperandKind::MemArg));
+        assert_eq!(unsafe { atomic.operands.mem_arg }, AwwasmCMemArg { align: 2, offset: 8, memory: 0 });
+        assert_eq!((records[3].opcode, records[3].sub_opcode), (0x1a, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn body_iter_errors_test() {
+        // No locals, then an undefined opcode.
+        let (records, status) = records(&[0x00, 0x41, 0x00, 0xff]);
+        assert_eq!(records.len(), 1);
+        assert_eq!(status, -1);
+        unsafe {
+            assert!(awwasm_body_iter_new(std::ptr::null(), 0).is_null());
+            assert!(awwasm_body_iter_new([0x05u8].as_ptr(), 1).is_null());
+            assert_eq!(awwasm_body_iter_next(std::ptr::null_mut(), std::ptr::null_mut()), -1);
+            awwasm_body_iter_free(std::ptr::null_mut());
+        }
+    }
+}
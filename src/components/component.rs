@@ -0,0 +1,204 @@
+use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+use crate::components::module::{AwwasmModule, AwwasmModulePreamble};
+use crate::components::types::{AwwasmCustomSectionItem, AwwasmName};
+use num_derive::FromPrimitive;
+use nom_derive::*;
+use crate::components::leb128::leb128_u32;
+use nom::combinator::complete;
+use nom::number::complete::le_u8;
+
+/// Layer field of a component preamble; core modules have layer 0.
+pub const COMPONENT_LAYER: u16 = 1;
+
+/// Section ids of the component binary format.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive)]
+pub enum AwwasmComponentSectionCode {
+    Custom = 0x00,
+    CoreModule = 0x01,
+    CoreInstance = 0x02,
+    CoreType = 0x03,
+    Component = 0x04,
+    Instance = 0x05,
+    Alias = 0x06,
+    Type = 0x07,
+    Canon = 0x08,
+    Start = 0x09,
+    Import = 0x0a,
+    Export = 0x0b,
+    Value = 0x0c,
+}
+
+/// A section of a component, undecoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmComponentSection<'a> {
+    pub id: AwwasmComponentSectionCode,
+    /// Everything after the section header. For `CoreModule` and `Component`
+    /// sections this is a complete nested binary, preamble included.
+    pub payload: &'a [u8],
+}
+
+/// The outer structure of a component binary: its sections in binary order.
+///
+/// Sections that hold nested binaries are decoded further into
+/// `AwwasmModule`s and `AwwasmComponent`s. Type, import, export, alias and
+/// canon sections are decoded on demand (see `component_types`); the rest
+/// are left as raw payloads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmComponent<'a> {
+    pub preamble: AwwasmModulePreamble<'a>,
+    pub sections: Vec<AwwasmComponentSection<'a>>,
+}
+
+impl<'a> AwwasmComponent<'a> {
+    /// Split a component binary into its sections.
+    ///
+    /// Fails if the preamble is not a component's; core modules are parsed
+    /// with `AwwasmModule::new()`.
+    pub fn new(input: &'a [u8]) -> anyhow::Result<Self> {
+        let (mut input, preamble) = AwwasmModulePreamble::parse(input)
+            .map_err(|e| AwwasmParseError::nom("component preamble", e))?;
+        if !preamble.is_component() {
+            let detail = format!("layer {} is not a component", preamble.layer());
+            return Err(AwwasmParseError::new("component preamble", AwwasmParseErrorKind::Malformed { detail }).into());
+        }
+        let mut sections = Vec::new();
+        while !input.is_empty() {
+            let (body, (id, size)) = complete(|i| {
+                let (i, id) = le_u8(i)?;
+                let (i, size) = leb128_u32(i)?;
+                Ok((i, (id, size)))
+            })(input)
+            .map_err(|e: nom::Err<nom::error::Error<&[u8]>>| AwwasmParseError::nom("component section header", e))?;
+            let id: AwwasmComponentSectionCode = num_traits::FromPrimitive::from_u8(id)
+                .ok_or_else(|| AwwasmParseError::new("component section header", AwwasmParseErrorKind::UnknownSection { id }))?;
+            if size as usize > body.len() {
+                return Err(AwwasmParseError::new(format!("component {:?} section", id), AwwasmParseErrorKind::Truncated).into());
+            }
+            let (payload, rest) = body.split_at(size as usize);
+            sections.push(AwwasmComponentSection { id, payload });
+            input = rest;
+        }
+        Ok(Self { preamble, sections })
+    }
+
+    fn payloads(&self, id: AwwasmComponentSectionCode) -> impl Iterator<Item = &'a [u8]> + '_ {
+        self.sections.iter().filter(move |s| s.id == id).map(|s| s.payload)
+    }
+
+    /// The core modules embedded directly in this component, in binary
+    /// order, parsed but not resolved.
+    pub fn core_modules(&self) -> anyhow::Result<Vec<AwwasmModule<'a>>> {
+        self.payloads(AwwasmComponentSectionCode::CoreModule).map(AwwasmModule::new).collect()
+    }
+
+    /// The components nested directly in this one, in binary order.
+    pub fn components(&self) -> anyhow::Result<Vec<AwwasmComponent<'a>>> {
+        self.payloads(AwwasmComponentSectionCode::Component).map(AwwasmComponent::new).collect()
+    }
+
+    /// Every core module in the component and, depth-first, in the
+    /// components nested in it.
+    pub fn all_core_modules(&self) -> anyhow::Result<Vec<AwwasmModule<'a>>> {
+        // Walk with an explicit stack of the components being visited, so
+        // components nested arbitrarily deep cannot overflow the call stack.
+        let mut modules = Vec::new();
+        let mut stack = vec![self.sections.clone().into_iter()];
+        while let Some(sections) = stack.last_mut() {
+            let Some(section) = sections.next() else {
+                stack.pop();
+                continue;
+            };
+            match section.id {
+                AwwasmComponentSectionCode::CoreModule => modules.push(AwwasmModule::new(section.payload)?),
+                AwwasmComponentSectionCode::Component => stack.push(AwwasmComponent::new(section.payload)?.sections.into_iter()),
+                _ => {}
+            }
+        }
+        Ok(modules)
+    }
+
+    /// Custom sections of the component itself, in binary order.
+    pub fn customs(&self) -> anyhow::Result<Vec<AwwasmCustomSectionItem<'a>>> {
+        self.payloads(AwwasmComponentSectionCode::Custom)
+            .map(|payload| {
+                let (data, name) = AwwasmName::parse(payload)
+                    .map_err(|e| AwwasmParseError::nom("component custom section", e))?;
+                Ok(AwwasmCustomSectionItem { name, payload: data })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::component::{AwwasmComponent, AwwasmComponentSectionCode};
+    use crate::components::module::AwwasmModule;
+    use crate::components::section::{leb128_len_u32, write_leb128_u32};
+
+    #[test]
+    fn decode_component_test() -> anyhow::Result<()> {
+        let component = wat::parse_str(r#"
+            (component
+                (core module $m (func (export "f")))
+                (core module)
+                (component (core module (memory 1)))
+                (core instance (instantiate $m))
+            )
+        "#)?;
+        assert_eq!(&component[4..8], &[0x0d, 0x00, 0x01, 0x00]);
+
+        let err = AwwasmModule::new(&component).unwrap_err();
+        assert!(err.to_string().contains("is a component"), "{}", err);
+
+        let parsed = AwwasmComponent::new(&component)?;
+        assert!(parsed.preamble.is_component());
+        let ids: Vec<AwwasmComponentSectionCode> = parsed.sections.iter().map(|s| s.id).collect();
+        assert_eq!(ids[..4], [
+            AwwasmComponentSectionCode::CoreModule,
+            AwwasmComponentSectionCode::CoreModule,
+            AwwasmComponentSectionCode::Component,
+            AwwasmComponentSectionCode::CoreInstance,
+        ]);
+
+        let mut modules = parsed.core_modules()?;
+        assert_eq!(modules.len(), 2);
+        modules[0].resolve_all_sections()?;
+        assert_eq!(modules[0].exports.as_ref().map(|e| &*e[0].name.bytes), Some(&b"f"[..]));
+        assert_eq!(parsed.components()?.len(), 1);
+
+        let mut all = parsed.all_core_modules()?;
+        assert_eq!(all.len(), 3);
+        all[2].resolve_all_sections()?;
+        assert_eq!(all[2].memory_count(), 1);
+
+        let module = wat::parse_str("(module)")?;
+        assert!(AwwasmComponent::new(&module).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn deeply_nested_components_test() -> anyhow::Result<()> {
+        // A core module inside 100k components, each the only section of
+        // the one around it.
+        const COMPONENT: &[u8] = b"\0asm\x0d\0\x01\0";
+        let innermost = [COMPONENT, &[0x01, 0x08], b"\0asm\x01\0\0\0"].concat();
+        let mut sizes = vec![innermost.len() as u32];
+        for _ in 0..100_000 {
+            let inner = *sizes.last().unwrap();
+            sizes.push(COMPONENT.len() as u32 + 1 + leb128_len_u32(inner) + inner);
+        }
+        let mut binary = Vec::new();
+        for size in sizes.iter().rev().skip(1) {
+            binary.extend_from_slice(COMPONENT);
+            binary.push(0x04);
+            write_leb128_u32(&mut binary, *size);
+        }
+        binary.extend_from_slice(&innermost);
+        assert_eq!(binary.len() as u32, *sizes.last().unwrap());
+
+        let modules = AwwasmComponent::new(&binary)?.all_core_modules()?;
+        assert_eq!(modules.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,1058 @@
+use crate::components::component::{AwwasmComponent, AwwasmComponentSectionCode};
+use crate::components::error::{limit_exceeded, AwwasmParseError, AwwasmParseErrorKind};
+use crate::components::instructions::NESTING_TOO_DEEP;
+use crate::components::types::{
+    AwwasmGlobalType, AwwasmImportSectionItem, AwwasmMemoryParams, AwwasmName, AwwasmTableSectionItem,
+    AwwasmTagSectionItem, AwwasmTypeSectionItem, ValType,
+};
+use nom_derive::Parse;
+use crate::components::leb128::{leb128_i64, leb128_u32};
+use nom::IResult;
+use nom::bytes::complete::tag;
+use nom::combinator::{all_consuming, recognize};
+use nom::multi::length_count;
+use nom::number::complete::le_u8;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// Sort bytes of the component binary format.
+const SORT_CORE: u8 = 0x00;
+const SORT_FUNC: u8 = 0x01;
+const SORT_VALUE: u8 = 0x02;
+const SORT_TYPE: u8 = 0x03;
+const SORT_COMPONENT: u8 = 0x04;
+const SORT_INSTANCE: u8 = 0x05;
+const CORE_SORT_TYPE: u8 = 0x10;
+const CORE_SORT_MODULE: u8 = 0x11;
+
+/// How deep component, instance and module types may nest inside one
+/// another, and how deep `AwwasmComponent::world()` follows type references.
+pub const MAX_TYPE_NESTING_DEPTH: u32 = 100;
+
+/// Type references `AwwasmComponent::world()` expands in total, so types
+/// that refer to one another many times over cannot blow up its output.
+pub const MAX_RESOLVED_TYPES: u32 = 100_000;
+
+fn fail<T>(i: &[u8]) -> IResult<&[u8], T> {
+    Err(nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Switch)))
+}
+
+// `0x00` for absent, `0x01` followed by the value.
+fn optional<'a, T>(i: &'a [u8], f: impl Fn(&'a [u8]) -> IResult<&'a [u8], T>) -> IResult<&'a [u8], Option<T>> {
+    match le_u8(i)? {
+        (i, 0x00) => Ok((i, None)),
+        (i, 0x01) => f(i).map(|(i, v)| (i, Some(v))),
+        _ => fail(i),
+    }
+}
+
+/// A primitive component value type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmPrimitiveValType {
+    Bool,
+    S8,
+    U8,
+    S16,
+    U16,
+    S32,
+    U32,
+    S64,
+    U64,
+    F32,
+    F64,
+    Char,
+    String,
+}
+
+impl AwwasmPrimitiveValType {
+    fn from_byte(byte: u8) -> Option<Self> {
+        use AwwasmPrimitiveValType::*;
+        Some(match byte {
+            0x7f => Bool,
+            0x7e => S8,
+            0x7d => U8,
+            0x7c => S16,
+            0x7b => U16,
+            0x7a => S32,
+            0x79 => U32,
+            0x78 => S64,
+            0x77 => U64,
+            0x76 => F32,
+            0x75 => F64,
+            0x74 => Char,
+            0x73 => String,
+            _ => return None,
+        })
+    }
+
+    /// Name of the type in WIT (e.g. `u32`, `string`).
+    pub fn name(&self) -> &'static str {
+        use AwwasmPrimitiveValType::*;
+        match self {
+            Bool => "bool",
+            S8 => "s8",
+            U8 => "u8",
+            S16 => "s16",
+            U16 => "u16",
+            S32 => "s32",
+            U32 => "u32",
+            S64 => "s64",
+            U64 => "u64",
+            F32 => "f32",
+            F64 => "f64",
+            Char => "char",
+            String => "string",
+        }
+    }
+}
+
+/// A component value type: a primitive, or an index into the type index space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmComponentValType {
+    Primitive(AwwasmPrimitiveValType),
+    Type(u32),
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmComponentValType {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        if let Some(p) = i.first().and_then(|b| AwwasmPrimitiveValType::from_byte(*b)) {
+            return Ok((&i[1..], AwwasmComponentValType::Primitive(p)));
+        }
+        let (rest, idx) = leb128_i64(i)?;
+        match u32::try_from(idx) {
+            Ok(idx) => Ok((rest, AwwasmComponentValType::Type(idx))),
+            Err(_) => fail(i),
+        }
+    }
+}
+
+/// A named record field, function parameter or named result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmComponentField<'a> {
+    pub name: AwwasmName<'a>,
+    pub ty: AwwasmComponentValType,
+}
+
+fn field(i: &[u8]) -> IResult<&[u8], AwwasmComponentField<'_>> {
+    let (i, name) = AwwasmName::parse(i)?;
+    let (i, ty) = AwwasmComponentValType::parse(i)?;
+    Ok((i, AwwasmComponentField { name, ty }))
+}
+
+/// A case of a `variant`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmVariantCase<'a> {
+    pub name: AwwasmName<'a>,
+    pub ty: Option<AwwasmComponentValType>,
+    /// Index of the case this one refines, if any.
+    pub refines: Option<u32>,
+}
+
+/// A value type defined in a type section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmComponentDefinedType<'a> {
+    Primitive(AwwasmPrimitiveValType),
+    Record(Vec<AwwasmComponentField<'a>>),
+    Variant(Vec<AwwasmVariantCase<'a>>),
+    List(AwwasmComponentValType),
+    Tuple(Vec<AwwasmComponentValType>),
+    Flags(Vec<AwwasmName<'a>>),
+    Enum(Vec<AwwasmName<'a>>),
+    Union(Vec<AwwasmComponentValType>),
+    Option(AwwasmComponentValType),
+    Result { ok: Option<AwwasmComponentValType>, err: Option<AwwasmComponentValType> },
+    /// An owned handle to the resource type at the index.
+    Own(u32),
+    /// A borrowed handle to the resource type at the index.
+    Borrow(u32),
+}
+
+fn defined_type(i: &[u8]) -> IResult<&[u8], AwwasmComponentDefinedType<'_>> {
+    use AwwasmComponentDefinedType::*;
+    let val = AwwasmComponentValType::parse;
+    let Some(&byte) = i.first() else { return fail(i) };
+    if let Some(p) = AwwasmPrimitiveValType::from_byte(byte) {
+        return Ok((&i[1..], Primitive(p)));
+    }
+    let i = &i[1..];
+    match byte {
+        0x72 => length_count(leb128_u32, field)(i).map(|(i, v)| (i, Record(v))),
+        0x71 => length_count(leb128_u32, |i| {
+            let (i, name) = AwwasmName::parse(i)?;
+            let (i, ty) = optional(i, val)?;
+            let (i, refines) = optional(i, leb128_u32)?;
+            Ok((i, AwwasmVariantCase { name, ty, refines }))
+        })(i).map(|(i, v)| (i, Variant(v))),
+        0x70 => val(i).map(|(i, v)| (i, List(v))),
+        0x6f => length_count(leb128_u32, val)(i).map(|(i, v)| (i, Tuple(v))),
+        0x6e => length_count(leb128_u32, AwwasmName::parse)(i).map(|(i, v)| (i, Flags(v))),
+        0x6d => length_count(leb128_u32, AwwasmName::parse)(i).map(|(i, v)| (i, Enum(v))),
+        0x6c => length_count(leb128_u32, val)(i).map(|(i, v)| (i, Union(v))),
+        0x6b => val(i).map(|(i, v)| (i, Option(v))),
+        0x6a => {
+            let (i, ok) = optional(i, val)?;
+            let (i, err) = optional(i, val)?;
+            Ok((i, Result { ok, err }))
+        }
+        0x69 => leb128_u32(i).map(|(i, v)| (i, Own(v))),
+        0x68 => leb128_u32(i).map(|(i, v)| (i, Borrow(v))),
+        _ => fail(i),
+    }
+}
+
+/// Results of a component function: one unnamed value, or a list of named ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmComponentResults<'a> {
+    Unnamed(AwwasmComponentValType),
+    Named(Vec<AwwasmComponentField<'a>>),
+}
+
+/// A component function type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmComponentFuncType<'a> {
+    pub params: Vec<AwwasmComponentField<'a>>,
+    pub results: AwwasmComponentResults<'a>,
+}
+
+/// What `sort` an item or index belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmComponentSort {
+    /// A core sort, by its byte (e.g. 0x11 for core modules).
+    Core(u8),
+    Func,
+    Value,
+    Type,
+    Component,
+    Instance,
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmComponentSort {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (rest, byte) = le_u8(i)?;
+        match byte {
+            SORT_CORE => le_u8(rest).map(|(i, core)| (i, AwwasmComponentSort::Core(core))),
+            SORT_FUNC => Ok((rest, AwwasmComponentSort::Func)),
+            SORT_VALUE => Ok((rest, AwwasmComponentSort::Value)),
+            SORT_TYPE => Ok((rest, AwwasmComponentSort::Type)),
+            SORT_COMPONENT => Ok((rest, AwwasmComponentSort::Component)),
+            SORT_INSTANCE => Ok((rest, AwwasmComponentSort::Instance)),
+            _ => fail(i),
+        }
+    }
+}
+
+/// Bound on an imported or exported type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmTypeBounds {
+    /// Equal to the type at the index.
+    Eq(u32),
+    /// A fresh resource type.
+    SubResource,
+}
+
+/// The type of an imported or exported item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmComponentTypeRef {
+    Module(u32),
+    Func(u32),
+    Value(AwwasmComponentValType),
+    Type(AwwasmTypeBounds),
+    Instance(u32),
+    Component(u32),
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmComponentTypeRef {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        use AwwasmComponentTypeRef::*;
+        let (rest, sort) = AwwasmComponentSort::parse(i)?;
+        match sort {
+            AwwasmComponentSort::Core(CORE_SORT_MODULE) => leb128_u32(rest).map(|(i, v)| (i, Module(v))),
+            AwwasmComponentSort::Func => leb128_u32(rest).map(|(i, v)| (i, Func(v))),
+            AwwasmComponentSort::Value => AwwasmComponentValType::parse(rest).map(|(i, v)| (i, Value(v))),
+            AwwasmComponentSort::Type => match le_u8(rest)? {
+                (i, 0x00) => leb128_u32(i).map(|(i, v)| (i, Type(AwwasmTypeBounds::Eq(v)))),
+                (i, 0x01) => Ok((i, Type(AwwasmTypeBounds::SubResource))),
+                _ => fail(rest),
+            },
+            AwwasmComponentSort::Instance => leb128_u32(rest).map(|(i, v)| (i, Instance(v))),
+            AwwasmComponentSort::Component => leb128_u32(rest).map(|(i, v)| (i, Component(v))),
+            AwwasmComponentSort::Core(_) => fail(i),
+        }
+    }
+}
+
+/// An import or export name: a plain kebab-case name (`0x00`) or an
+/// interface id such as `wasi:cli/stdout` (`0x01`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmComponentExternName<'a> {
+    pub interface: bool,
+    pub name: AwwasmName<'a>,
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmComponentExternName<'a> {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (rest, kind) = le_u8(i)?;
+        if kind > 0x01 {
+            return fail(i);
+        }
+        let (rest, name) = AwwasmName::parse(rest)?;
+        Ok((rest, Self { interface: kind == 0x01, name }))
+    }
+}
+
+impl fmt::Display for AwwasmComponentExternName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.name.bytes))
+    }
+}
+
+/// An item of the alias section, or an alias declaration in a type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmComponentAlias<'a> {
+    InstanceExport { sort: AwwasmComponentSort, instance: u32, name: AwwasmName<'a> },
+    CoreInstanceExport { sort: AwwasmComponentSort, instance: u32, name: AwwasmName<'a> },
+    /// Item `index` of `sort` in the enclosing component `count` levels out.
+    Outer { sort: AwwasmComponentSort, count: u32, index: u32 },
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmComponentAlias<'a> {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (i, sort) = AwwasmComponentSort::parse(i)?;
+        let (rest, target) = le_u8(i)?;
+        match target {
+            0x00 | 0x01 => {
+                let (rest, instance) = leb128_u32(rest)?;
+                let (rest, name) = AwwasmName::parse(rest)?;
+                Ok((rest, match target {
+                    0x00 => AwwasmComponentAlias::InstanceExport { sort, instance, name },
+                    _ => AwwasmComponentAlias::CoreInstanceExport { sort, instance, name },
+                }))
+            }
+            0x02 => {
+                let (rest, count) = leb128_u32(rest)?;
+                let (rest, index) = leb128_u32(rest)?;
+                Ok((rest, AwwasmComponentAlias::Outer { sort, count, index }))
+            }
+            _ => fail(i),
+        }
+    }
+}
+
+/// A declaration inside a component or instance type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmComponentTypeDecl<'a> {
+    /// A core type, kept as encoded.
+    CoreType(&'a [u8]),
+    Type(AwwasmComponentType<'a>),
+    Alias(AwwasmComponentAlias<'a>),
+    Import(AwwasmComponentExternName<'a>, AwwasmComponentTypeRef),
+    Export(AwwasmComponentExternName<'a>, AwwasmComponentTypeRef),
+}
+
+/// An entry of a component type section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmComponentType<'a> {
+    Defined(AwwasmComponentDefinedType<'a>),
+    Func(AwwasmComponentFuncType<'a>),
+    Component(Vec<AwwasmComponentTypeDecl<'a>>),
+    Instance(Vec<AwwasmComponentTypeDecl<'a>>),
+    /// A resource with its core representation and optional destructor.
+    Resource { rep: ValType, dtor: Option<u32> },
+}
+
+// Skip a core function or module type, returning nothing; `recognize` it to
+// keep the bytes.
+fn core_type(i: &[u8], depth: u32) -> IResult<&[u8], ()> {
+    nest(i, depth)?;
+    match i.first() {
+        Some(0x60) => AwwasmTypeSectionItem::parse(i).map(|(i, _)| (i, ())),
+        Some(0x50) => {
+            let (mut i, count) = leb128_u32(&i[1..])?;
+            for _ in 0..count {
+                let (rest, kind) = le_u8(i)?;
+                i = match kind {
+                    0x00 => AwwasmImportSectionItem::parse(rest)?.0,
+                    0x01 => core_type(rest, depth + 1)?.0,
+                    0x02 => {
+                        let (rest, _) = tag([CORE_SORT_TYPE, 0x01])(rest)?;
+                        leb128_u32(leb128_u32(rest)?.0)?.0
+                    }
+                    0x03 => core_extern_desc(AwwasmName::parse(rest)?.0)?.0,
+                    _ => return fail(i),
+                };
+            }
+            Ok((i, ()))
+        }
+        _ => fail(i),
+    }
+}
+
+// The kind and type of a core import or export, as in a core import entry.
+fn core_extern_desc(i: &[u8]) -> IResult<&[u8], ()> {
+    let (rest, kind) = le_u8(i)?;
+    let rest = match kind {
+        0x00 => leb128_u32(rest)?.0,
+        0x01 => AwwasmTableSectionItem::parse(rest)?.0,
+        0x02 => AwwasmMemoryParams::parse(rest)?.0,
+        0x03 => AwwasmGlobalType::parse(rest)?.0,
+        0x04 => AwwasmTagSectionItem::parse(rest)?.0,
+        _ => return fail(i),
+    };
+    Ok((rest, ()))
+}
+
+fn type_decls(i: &[u8], imports: bool, depth: u32) -> IResult<&[u8], Vec<AwwasmComponentTypeDecl<'_>>> {
+    length_count(leb128_u32, |i| {
+        let (rest, kind) = le_u8(i)?;
+        match kind {
+            0x00 => recognize(|i| core_type(i, depth + 1))(rest).map(|(i, v)| (i, AwwasmComponentTypeDecl::CoreType(v))),
+            0x01 => component_type(rest, depth + 1).map(|(i, v)| (i, AwwasmComponentTypeDecl::Type(v))),
+            0x02 => AwwasmComponentAlias::parse(rest).map(|(i, v)| (i, AwwasmComponentTypeDecl::Alias(v))),
+            0x03 | 0x04 if imports || kind == 0x04 => {
+                let (rest, name) = AwwasmComponentExternName::parse(rest)?;
+                let (rest, ty) = AwwasmComponentTypeRef::parse(rest)?;
+                Ok((rest, match kind {
+                    0x03 => AwwasmComponentTypeDecl::Import(name, ty),
+                    _ => AwwasmComponentTypeDecl::Export(name, ty),
+                }))
+            }
+            _ => fail(i),
+        }
+    })(i)
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmComponentType<'a> {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        component_type(i, 0)
+    }
+}
+
+// Fail once types declared inside component and instance types nest
+// `MAX_TYPE_NESTING_DEPTH` deep, before the recursion can exhaust the stack.
+fn nest(i: &[u8], depth: u32) -> IResult<&[u8], ()> {
+    if depth >= MAX_TYPE_NESTING_DEPTH {
+        return Err(nom::Err::Failure(nom::error::Error::new(i, NESTING_TOO_DEEP)));
+    }
+    Ok((i, ()))
+}
+
+fn component_type(i: &[u8], depth: u32) -> IResult<&[u8], AwwasmComponentType<'_>> {
+    nest(i, depth)?;
+    match i.first() {
+        Some(0x40) => {
+            let (rest, params) = length_count(leb128_u32, field)(&i[1..])?;
+            let (rest, results) = match le_u8(rest)? {
+                (rest, 0x00) => AwwasmComponentValType::parse(rest).map(|(i, v)| (i, AwwasmComponentResults::Unnamed(v)))?,
+                (rest, 0x01) => length_count(leb128_u32, field)(rest).map(|(i, v)| (i, AwwasmComponentResults::Named(v)))?,
+                _ => return fail(rest),
+            };
+            Ok((rest, AwwasmComponentType::Func(AwwasmComponentFuncType { params, results })))
+        }
+        Some(0x41) => type_decls(&i[1..], true, depth).map(|(i, v)| (i, AwwasmComponentType::Component(v))),
+        Some(0x42) => type_decls(&i[1..], false, depth).map(|(i, v)| (i, AwwasmComponentType::Instance(v))),
+        Some(0x3f) => {
+            let (rest, rep) = ValType::parse(&i[1..])?;
+            let (rest, dtor) = optional(rest, leb128_u32)?;
+            Ok((rest, AwwasmComponentType::Resource { rep, dtor }))
+        }
+        _ => defined_type(i).map(|(i, v)| (i, AwwasmComponentType::Defined(v))),
+    }
+}
+
+/// An entry of a component import section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmComponentImport<'a> {
+    pub name: AwwasmComponentExternName<'a>,
+    pub ty: AwwasmComponentTypeRef,
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmComponentImport<'a> {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (i, name) = AwwasmComponentExternName::parse(i)?;
+        let (i, ty) = AwwasmComponentTypeRef::parse(i)?;
+        Ok((i, Self { name, ty }))
+    }
+}
+
+/// An entry of a component export section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmComponentExport<'a> {
+    pub name: AwwasmComponentExternName<'a>,
+    pub sort: AwwasmComponentSort,
+    pub index: u32,
+    /// The type the export is ascribed, if any.
+    pub ty: Option<AwwasmComponentTypeRef>,
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmComponentExport<'a> {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (i, name) = AwwasmComponentExternName::parse(i)?;
+        let (i, sort) = AwwasmComponentSort::parse(i)?;
+        let (i, index) = leb128_u32(i)?;
+        let (i, ty) = optional(i, AwwasmComponentTypeRef::parse)?;
+        Ok((i, Self { name, sort, index, ty }))
+    }
+}
+
+/// A canonical ABI option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmCanonOption {
+    Utf8,
+    Utf16,
+    CompactUtf16,
+    Memory(u32),
+    Realloc(u32),
+    PostReturn(u32),
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmCanonOption {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        match le_u8(i)? {
+            (rest, 0x00) => Ok((rest, AwwasmCanonOption::Utf8)),
+            (rest, 0x01) => Ok((rest, AwwasmCanonOption::Utf16)),
+            (rest, 0x02) => Ok((rest, AwwasmCanonOption::CompactUtf16)),
+            (rest, 0x03) => leb128_u32(rest).map(|(i, v)| (i, AwwasmCanonOption::Memory(v))),
+            (rest, 0x04) => leb128_u32(rest).map(|(i, v)| (i, AwwasmCanonOption::Realloc(v))),
+            (rest, 0x05) => leb128_u32(rest).map(|(i, v)| (i, AwwasmCanonOption::PostReturn(v))),
+            _ => fail(i),
+        }
+    }
+}
+
+/// An entry of a component canonical function section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmCanonical {
+    /// Lift a core function to a component function of type `type_idx`.
+    Lift { core_func: u32, options: Vec<AwwasmCanonOption>, type_idx: u32 },
+    /// Lower a component function to a core function.
+    Lower { func: u32, options: Vec<AwwasmCanonOption> },
+    ResourceNew(u32),
+    ResourceDrop(u32),
+    ResourceRep(u32),
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmCanonical {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let mut options = length_count(leb128_u32, AwwasmCanonOption::parse);
+        match le_u8(i)? {
+            (rest, 0x00) => {
+                let (rest, _) = tag([0x00])(rest)?;
+                let (rest, core_func) = leb128_u32(rest)?;
+                let (rest, options) = options(rest)?;
+                let (rest, type_idx) = leb128_u32(rest)?;
+                Ok((rest, AwwasmCanonical::Lift { core_func, options, type_idx }))
+            }
+            (rest, 0x01) => {
+                let (rest, _) = tag([0x00])(rest)?;
+                let (rest, func) = leb128_u32(rest)?;
+                let (rest, options) = options(rest)?;
+                Ok((rest, AwwasmCanonical::Lower { func, options }))
+            }
+            (rest, 0x02) => leb128_u32(rest).map(|(i, v)| (i, AwwasmCanonical::ResourceNew(v))),
+            (rest, 0x03) => leb128_u32(rest).map(|(i, v)| (i, AwwasmCanonical::ResourceDrop(v))),
+            (rest, 0x04) => leb128_u32(rest).map(|(i, v)| (i, AwwasmCanonical::ResourceRep(v))),
+            _ => fail(i),
+        }
+    }
+}
+
+/// A function signature in WIT terms, with types rendered as WIT type
+/// expressions (e.g. `list<u8>`, `option<string>`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmWitFunc {
+    pub params: Vec<(String, String)>,
+    /// Named results; a single unnamed result has an empty name.
+    pub results: Vec<(String, String)>,
+}
+
+impl fmt::Display for AwwasmWitFunc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |items: &[(String, String)]| items.iter().map(|(n, t)| format!("{}: {}", n, t)).collect::<Vec<_>>().join(", ");
+        write!(f, "func({})", list(&self.params))?;
+        match &self.results[..] {
+            [] => Ok(()),
+            [(name, ty)] if name.is_empty() => write!(f, " -> {}", ty),
+            results => write!(f, " -> ({})", list(results)),
+        }
+    }
+}
+
+/// What an import or export of a component is, from `AwwasmComponent::world()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmWorldItemKind {
+    Func(AwwasmWitFunc),
+    /// An instance, with the functions it exports.
+    Interface(Vec<(String, AwwasmWitFunc)>),
+    Type,
+    /// Core modules, values, components, or anything the signature of which
+    /// could not be recovered.
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmWorldItem {
+    pub name: String,
+    pub kind: AwwasmWorldItemKind,
+}
+
+/// The imports and exports of a component, with function signatures.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmComponentWorld {
+    pub imports: Vec<AwwasmWorldItem>,
+    pub exports: Vec<AwwasmWorldItem>,
+}
+
+/// WIT-like text, one import or export per line and interface functions
+/// indented under their interface.
+impl fmt::Display for AwwasmComponentWorld {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items = self.imports.iter().map(|i| ("import", i)).chain(self.exports.iter().map(|e| ("export", e)));
+        for (dir, item) in items {
+            match &item.kind {
+                AwwasmWorldItemKind::Func(func) => writeln!(f, "{} {}: {}", dir, item.name, func)?,
+                AwwasmWorldItemKind::Interface(funcs) => {
+                    writeln!(f, "{} {}: interface {{", dir, item.name)?;
+                    for (name, func) in funcs {
+                        writeln!(f, "  {}: {}", name, func)?;
+                    }
+                    writeln!(f, "}}")?;
+                }
+                AwwasmWorldItemKind::Type => writeln!(f, "{} {}: type", dir, item.name)?,
+                AwwasmWorldItemKind::Other => writeln!(f, "{} {}", dir, item.name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// An entry of a type index space while resolving names and signatures.
+#[derive(Debug, Clone)]
+enum TypeEntry<'t, 'a> {
+    Def(&'t AwwasmComponentType<'a>),
+    /// Imported, exported or aliased under this name.
+    Named(String),
+    Unknown,
+}
+
+// The type index space of a component or instance type, with the names
+// types were exported under.
+struct Scope<'t, 'a, 'p> {
+    types: Vec<TypeEntry<'t, 'a>>,
+    names: HashMap<u32, String>,
+    parent: Option<&'p Scope<'t, 'a, 'p>>,
+    // The types being resolved, outermost first: the visited set that
+    // rejects cyclic references.
+    resolving: RefCell<Vec<u32>>,
+    // Type references left to expand, shared with nested scopes.
+    budget: Rc<Cell<u32>>,
+}
+
+impl<'t, 'a, 'p> Scope<'t, 'a, 'p> {
+    fn new(parent: Option<&'p Scope<'t, 'a, 'p>>) -> Self {
+        let budget = parent.map_or_else(|| Rc::new(Cell::new(MAX_RESOLVED_TYPES)), |p| p.budget.clone());
+        Scope { types: Vec::new(), names: HashMap::new(), parent, resolving: RefCell::new(Vec::new()), budget }
+    }
+
+    // Add a type named by an import or export, and name the type it equals.
+    fn add_named(&mut self, name: &AwwasmComponentExternName, bounds: Option<AwwasmTypeBounds>) {
+        if let Some(AwwasmTypeBounds::Eq(idx)) = bounds {
+            self.names.entry(idx).or_insert_with(|| name.to_string());
+        }
+        self.types.push(TypeEntry::Named(name.to_string()));
+    }
+
+    fn add_alias(&mut self, alias: &AwwasmComponentAlias) {
+        match alias {
+            AwwasmComponentAlias::InstanceExport { sort: AwwasmComponentSort::Type, name, .. } =>
+                self.types.push(TypeEntry::Named(String::from_utf8_lossy(&name.bytes).into_owned())),
+            AwwasmComponentAlias::Outer { sort: AwwasmComponentSort::Type, count, index } => {
+                let mut scope = Some(&*self);
+                for _ in 0..*count {
+                    scope = scope.and_then(|s| s.parent);
+                }
+                let entry = match scope {
+                    Some(s) => match s.names.get(index) {
+                        Some(name) => TypeEntry::Named(name.clone()),
+                        None => s.types.get(*index as usize).cloned().unwrap_or(TypeEntry::Unknown),
+                    },
+                    None => TypeEntry::Unknown,
+                };
+                self.types.push(entry);
+            }
+            _ => {}
+        }
+    }
+
+    fn val(&self, ty: &AwwasmComponentValType) -> Result<String, AwwasmParseError> {
+        match ty {
+            AwwasmComponentValType::Primitive(p) => Ok(p.name().to_owned()),
+            AwwasmComponentValType::Type(idx) => self.index(*idx),
+        }
+    }
+
+    // Resolve type `idx` with `resolve`. A type may only refer to types
+    // defined before it, so references to itself or to a later type, which
+    // could form a cycle, are rejected, as are chains of references deeper
+    // than `MAX_TYPE_NESTING_DEPTH` and expansions past the budget.
+    fn enter<T>(&self, idx: u32, resolve: impl FnOnce() -> Result<T, AwwasmParseError>) -> Result<T, AwwasmParseError> {
+        let mut resolving = self.resolving.borrow_mut();
+        if let Some(&outer) = resolving.last() {
+            if resolving.contains(&idx) {
+                return Err(malformed(format!("type {} refers to itself", idx)));
+            }
+            if idx >= outer {
+                return Err(malformed(format!("type {} refers to type {}, which is not defined before it", outer, idx)));
+            }
+        }
+        if resolving.len() >= MAX_TYPE_NESTING_DEPTH as usize {
+            return Err(AwwasmParseError::new("component type", limit_exceeded("type nesting depth", MAX_TYPE_NESTING_DEPTH)));
+        }
+        let Some(budget) = self.budget.get().checked_sub(1) else {
+            return Err(AwwasmParseError::new("component type", limit_exceeded("resolved types", MAX_RESOLVED_TYPES)));
+        };
+        self.budget.set(budget);
+        resolving.push(idx);
+        drop(resolving);
+        let resolved = resolve();
+        self.resolving.borrow_mut().pop();
+        resolved
+    }
+
+    fn index(&self, idx: u32) -> Result<String, AwwasmParseError> {
+        self.enter(idx, || {
+            if let Some(name) = self.names.get(&idx) {
+                return Ok(name.clone());
+            }
+            Ok(match self.types.get(idx as usize) {
+                Some(TypeEntry::Named(name)) => name.clone(),
+                Some(TypeEntry::Def(AwwasmComponentType::Defined(d))) => self.defined(d)?,
+                Some(TypeEntry::Def(AwwasmComponentType::Resource { .. })) => "resource".to_owned(),
+                Some(TypeEntry::Def(AwwasmComponentType::Func(func))) => self.func(func)?.to_string(),
+                _ => format!("type{}", idx),
+            })
+        })
+    }
+
+    fn defined(&self, ty: &AwwasmComponentDefinedType) -> Result<String, AwwasmParseError> {
+        use AwwasmComponentDefinedType::*;
+        let name = |name: &AwwasmName| String::from_utf8_lossy(&name.bytes).into_owned();
+        let names = |names: &[AwwasmName]| names.iter().map(name).collect::<Vec<_>>().join(", ");
+        let vals = |vals: &[AwwasmComponentValType]| vals.iter().map(|v| self.val(v)).collect::<std::result::Result<Vec<_>, _>>().map(|v| v.join(", "));
+        Ok(match ty {
+            Primitive(p) => p.name().to_owned(),
+            Record(fields) => format!("record {{ {} }}", fields.iter()
+                .map(|f| Ok(format!("{}: {}", name(&f.name), self.val(&f.ty)?)))
+                .collect::<std::result::Result<Vec<_>, AwwasmParseError>>()?.join(", ")),
+            Variant(cases) => format!("variant {{ {} }}", cases.iter()
+                .map(|c| Ok(match &c.ty {
+                    Some(ty) => format!("{}({})", name(&c.name), self.val(ty)?),
+                    None => name(&c.name),
+                }))
+                .collect::<std::result::Result<Vec<_>, AwwasmParseError>>()?.join(", ")),
+            List(ty) => format!("list<{}>", self.val(ty)?),
+            Tuple(tys) => format!("tuple<{}>", vals(tys)?),
+            Flags(flags) => format!("flags {{ {} }}", names(flags)),
+            Enum(cases) => format!("enum {{ {} }}", names(cases)),
+            Union(tys) => format!("union {{ {} }}", vals(tys)?),
+            Option(ty) => format!("option<{}>", self.val(ty)?),
+            Result { ok: None, err: None } => "result".to_owned(),
+            Result { ok: Some(ok), err: None } => format!("result<{}>", self.val(ok)?),
+            Result { ok: None, err: Some(err) } => format!("result<_, {}>", self.val(err)?),
+            Result { ok: Some(ok), err: Some(err) } => format!("result<{}, {}>", self.val(ok)?, self.val(err)?),
+            Own(idx) => self.index(*idx)?,
+            Borrow(idx) => format!("borrow<{}>", self.index(*idx)?),
+        })
+    }
+
+    fn func(&self, func: &AwwasmComponentFuncType) -> Result<AwwasmWitFunc, AwwasmParseError> {
+        let fields = |fields: &[AwwasmComponentField]| fields.iter()
+            .map(|f| Ok((String::from_utf8_lossy(&f.name.bytes).into_owned(), self.val(&f.ty)?)))
+            .collect::<Result<Vec<_>, AwwasmParseError>>();
+        Ok(AwwasmWitFunc {
+            params: fields(&func.params)?,
+            results: match &func.results {
+                AwwasmComponentResults::Unnamed(ty) => vec![(String::new(), self.val(ty)?)],
+                AwwasmComponentResults::Named(named) => fields(named)?,
+            },
+        })
+    }
+
+    fn func_at(&self, idx: u32) -> Result<Option<AwwasmWitFunc>, AwwasmParseError> {
+        match self.types.get(idx as usize) {
+            Some(TypeEntry::Def(AwwasmComponentType::Func(func))) => self.enter(idx, || self.func(func)).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    // The functions an instance type exports, with signatures resolved in
+    // the instance type's own scope.
+    fn instance(&self, idx: u32) -> Result<Option<Vec<(String, AwwasmWitFunc)>>, AwwasmParseError> {
+        let Some(TypeEntry::Def(AwwasmComponentType::Instance(decls))) = self.types.get(idx as usize) else {
+            return Ok(None);
+        };
+        let mut scope = Scope::new(Some(self));
+        let mut funcs = Vec::new();
+        for decl in decls {
+            match decl {
+                AwwasmComponentTypeDecl::Type(ty) => scope.types.push(TypeEntry::Def(ty)),
+                AwwasmComponentTypeDecl::Alias(alias) => scope.add_alias(alias),
+                AwwasmComponentTypeDecl::Export(name, AwwasmComponentTypeRef::Type(bounds)) => scope.add_named(name, Some(*bounds)),
+                AwwasmComponentTypeDecl::Export(name, AwwasmComponentTypeRef::Func(ty)) => {
+                    funcs.extend(scope.func_at(*ty)?.map(|func| (name.to_string(), func)));
+                }
+                _ => {}
+            }
+        }
+        Ok(Some(funcs))
+    }
+}
+
+fn malformed(detail: String) -> AwwasmParseError {
+    AwwasmParseError::new("component type", AwwasmParseErrorKind::Malformed { detail })
+}
+
+fn item_kind(scope: &Scope, ty: &AwwasmComponentTypeRef) -> Result<AwwasmWorldItemKind, AwwasmParseError> {
+    Ok(match ty {
+        AwwasmComponentTypeRef::Func(idx) => scope.func_at(*idx)?.map_or(AwwasmWorldItemKind::Other, AwwasmWorldItemKind::Func),
+        AwwasmComponentTypeRef::Instance(idx) => scope.instance(*idx)?.map_or(AwwasmWorldItemKind::Other, AwwasmWorldItemKind::Interface),
+        AwwasmComponentTypeRef::Type(_) => AwwasmWorldItemKind::Type,
+        _ => AwwasmWorldItemKind::Other,
+    })
+}
+
+impl<'a> AwwasmComponent<'a> {
+    fn section_items<T>(&self, id: AwwasmComponentSectionCode, what: &str, item: impl Fn(&'a [u8]) -> IResult<&'a [u8], T> + Copy) -> anyhow::Result<Vec<T>> {
+        let mut items = Vec::new();
+        for section in self.sections.iter().filter(|s| s.id == id) {
+            let (_, mut parsed) = all_consuming(length_count(leb128_u32, item))(section.payload).map_err(|e| {
+                let too_deep = matches!(&e, nom::Err::Failure(e) if e.code == NESTING_TOO_DEEP);
+                let mut err = AwwasmParseError::nom(format!("component {} section", what), e);
+                if too_deep {
+                    err.kind = limit_exceeded("type nesting depth", MAX_TYPE_NESTING_DEPTH);
+                }
+                err
+            })?;
+            items.append(&mut parsed);
+        }
+        Ok(items)
+    }
+
+    /// Entries of every type section, in binary order.
+    pub fn types(&self) -> anyhow::Result<Vec<AwwasmComponentType<'a>>> {
+        self.section_items(AwwasmComponentSectionCode::Type, "type", AwwasmComponentType::parse)
+    }
+
+    /// Entries of every import section, in binary order.
+    pub fn imports(&self) -> anyhow::Result<Vec<AwwasmComponentImport<'a>>> {
+        self.section_items(AwwasmComponentSectionCode::Import, "import", AwwasmComponentImport::parse)
+    }
+
+    /// Entries of every export section, in binary order.
+    pub fn exports(&self) -> anyhow::Result<Vec<AwwasmComponentExport<'a>>> {
+        self.section_items(AwwasmComponentSectionCode::Export, "export", AwwasmComponentExport::parse)
+    }
+
+    /// Entries of every alias section, in binary order.
+    pub fn aliases(&self) -> anyhow::Result<Vec<AwwasmComponentAlias<'a>>> {
+        self.section_items(AwwasmComponentSectionCode::Alias, "alias", AwwasmComponentAlias::parse)
+    }
+
+    /// Entries of every canonical function section, in binary order.
+    pub fn canonicals(&self) -> anyhow::Result<Vec<AwwasmCanonical>> {
+        self.section_items(AwwasmComponentSectionCode::Canon, "canon", AwwasmCanonical::parse)
+    }
+
+    /// Recover the component's imports and exports with their function
+    /// signatures, the shape a WIT world describes.
+    ///
+    /// Walks the sections in order to rebuild the type, function and
+    /// instance index spaces. Types are printed by the name they were
+    /// imported or exported under, otherwise structurally. Functions whose
+    /// type cannot be traced (e.g. aliased from an instantiated component)
+    /// are reported as `Other`.
+    pub fn world(&self) -> anyhow::Result<AwwasmComponentWorld> {
+        // Parse every section first so the scope can borrow the types.
+        let mut parsed = Vec::new();
+        for section in &self.sections {
+            let one = AwwasmComponent { preamble: self.preamble.clone(), sections: vec![section.clone()] };
+            parsed.push(match section.id {
+                AwwasmComponentSectionCode::Type => Parsed::Types(one.types()?),
+                AwwasmComponentSectionCode::Import => Parsed::Imports(one.imports()?),
+                AwwasmComponentSectionCode::Export => Parsed::Exports(one.exports()?),
+                AwwasmComponentSectionCode::Alias => Parsed::Aliases(one.aliases()?),
+                AwwasmComponentSectionCode::Canon => Parsed::Canon(one.canonicals()?),
+                AwwasmComponentSectionCode::Instance => {
+                    let (_, count) = leb128_u32::<nom::error::Error<&[u8]>>(section.payload)
+                        .map_err(|e| AwwasmParseError::nom("component instance section", e))?;
+                    Parsed::Instances(count)
+                }
+                _ => Parsed::Other,
+            });
+        }
+
+        let mut scope = Scope::new(None);
+        let mut funcs: Vec<Option<AwwasmWitFunc>> = Vec::new();
+        let mut instances: Vec<Option<Vec<(String, AwwasmWitFunc)>>> = Vec::new();
+        let mut world = AwwasmComponentWorld::default();
+        for section in &parsed {
+            match section {
+                Parsed::Types(types) => scope.types.extend(types.iter().map(TypeEntry::Def)),
+                Parsed::Imports(imports) => for import in imports {
+                    let kind = item_kind(&scope, &import.ty)?;
+                    match (&import.ty, &kind) {
+                        (AwwasmComponentTypeRef::Func(_), AwwasmWorldItemKind::Func(func)) => funcs.push(Some(func.clone())),
+                        (AwwasmComponentTypeRef::Func(_), _) => funcs.push(None),
+                        (AwwasmComponentTypeRef::Instance(_), AwwasmWorldItemKind::Interface(f)) => instances.push(Some(f.clone())),
+                        (AwwasmComponentTypeRef::Instance(_), _) => instances.push(None),
+                        (AwwasmComponentTypeRef::Type(bounds), _) => scope.add_named(&import.name, Some(*bounds)),
+                        _ => {}
+                    }
+                    world.imports.push(AwwasmWorldItem { name: import.name.to_string(), kind });
+                },
+                Parsed::Aliases(aliases) => for alias in aliases {
+                    match alias {
+                        AwwasmComponentAlias::InstanceExport { sort: AwwasmComponentSort::Func, instance, name } => {
+                            let name = String::from_utf8_lossy(&name.bytes);
+                            funcs.push(instances.get(*instance as usize).cloned().flatten()
+                                .and_then(|funcs| funcs.into_iter().find(|(n, _)| *n == name).map(|(_, f)| f)));
+                        }
+                        AwwasmComponentAlias::InstanceExport { sort: AwwasmComponentSort::Instance, .. }
+                        | AwwasmComponentAlias::Outer { sort: AwwasmComponentSort::Instance, .. } => instances.push(None),
+                        AwwasmComponentAlias::Outer { sort: AwwasmComponentSort::Func, .. } => funcs.push(None),
+                        alias => scope.add_alias(alias),
+                    }
+                },
+                Parsed::Canon(canon) => for c in canon {
+                    if let AwwasmCanonical::Lift { type_idx, .. } = c {
+                        funcs.push(scope.func_at(*type_idx)?);
+                    }
+                },
+                Parsed::Instances(count) => instances.extend((0..*count).map(|_| None)),
+                Parsed::Exports(exports) => for export in exports {
+                    let name = export.name.to_string();
+                    let kind = match export.sort {
+                        AwwasmComponentSort::Func => {
+                            let func = funcs.get(export.index as usize).cloned().flatten();
+                            funcs.push(func.clone());
+                            func.map_or(AwwasmWorldItemKind::Other, AwwasmWorldItemKind::Func)
+                        }
+                        AwwasmComponentSort::Instance => {
+                            let instance = instances.get(export.index as usize).cloned().flatten();
+                            instances.push(instance.clone());
+                            instance.map_or(AwwasmWorldItemKind::Other, AwwasmWorldItemKind::Interface)
+                        }
+                        AwwasmComponentSort::Type => {
+                            scope.add_named(&export.name, Some(AwwasmTypeBounds::Eq(export.index)));
+                            AwwasmWorldItemKind::Type
+                        }
+                        _ => AwwasmWorldItemKind::Other,
+                    };
+                    world.exports.push(AwwasmWorldItem { name, kind });
+                },
+                Parsed::Other => {}
+            }
+        }
+        Ok(world)
+    }
+}
+
+// A section decoded for `world()`.
+enum Parsed<'a> {
+    Types(Vec<AwwasmComponentType<'a>>),
+    Imports(Vec<AwwasmComponentImport<'a>>),
+    Exports(Vec<AwwasmComponentExport<'a>>),
+    Aliases(Vec<AwwasmComponentAlias<'a>>),
+    Canon(Vec<AwwasmCanonical>),
+    /// Number of instances the section creates.
+    Instances(u32),
+    Other,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::component::AwwasmComponent;
+    use crate::components::component_types::{AwwasmComponentType, AwwasmComponentTypeRef, AwwasmWorldItemKind, MAX_TYPE_NESTING_DEPTH};
+    use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+    use crate::components::section::write_leb128_u32;
+
+    #[test]
+    fn component_world_test() -> anyhow::Result<()> {
+        let component = wat::parse_str(r#"
+            (component
+                (import "host" (instance $host
+                    (type $level (enum "info" "error"))
+                    (export $l "level" (type (eq $level)))
+                    (export "log" (func (param "level" $l) (param "msg" string)))
+                ))
+                (alias export $host "log" (func $log))
+                (core module $m
+                    (import "host" "log" (func (param i32 i32 i32)))
+                    (memory (export "mem") 1)
+                    (func (export "run") (param i32 i32 i32 i32) (result i32) unreachable)
+                    (func (export "realloc") (param i32 i32 i32 i32) (result i32) unreachable))
+                (core func $log-lowered (canon lower (func $log)))
+                (core instance $i (instantiate $m (with "host" (instance (export "log" (func $log-lowered))))))
+                (type $point (record (field "x" u32) (field "y" u32)))
+                (export $p "point" (type $point))
+                (type $run-type (func (param "a" u32) (param "b" (list u8)) (param "p" $p) (result (option string))))
+                (func $run (type $run-type)
+                    (canon lift (core func $i "run") (memory $i "mem") (realloc (func $i "realloc"))))
+                (export "run" (func $run))
+            )
+        "#)?;
+        let parsed = AwwasmComponent::new(&component)?;
+        let imports = parsed.imports()?;
+        assert_eq!(imports.len(), 1);
+        assert!(matches!(imports[0].ty, AwwasmComponentTypeRef::Instance(0)));
+        assert!(matches!(parsed.types()?[0], AwwasmComponentType::Instance(_)));
+
+        let world = parsed.world()?;
+        let AwwasmWorldItemKind::Interface(host) = &world.imports[0].kind else { panic!("expected an interface") };
+        assert_eq!(host[0].0, "log");
+        assert_eq!(world.exports.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), vec!["point", "run"]);
+        assert_eq!(world.to_string(), "\
+            import host: interface {\n  \
+              log: func(level: level, msg: string)\n\
+            }\n\
+            export point: type\n\
+            export run: func(a: u32, b: list<u8>, p: point) -> option<string>\n");
+        Ok(())
+    }
+
+    #[test]
+    fn component_type_cycle_test() -> anyhow::Result<()> {
+        let component = [
+            &b"\0asm\x0d\0\x01\0"[..],
+            // Type section: type 0 = list<type 0>, type 1 = func() -> type 0.
+            &[0x07, 0x07, 0x02, 0x70, 0x00, 0x40, 0x00, 0x00, 0x00],
+            // Import section: "f" as a function of type 1.
+            &[0x0a, 0x06, 0x01, 0x00, 0x01, b'f', 0x01, 0x01],
+        ].concat();
+        let parsed = AwwasmComponent::new(&component)?;
+        assert_eq!(parsed.types()?.len(), 2);
+        let err = parsed.world().unwrap_err().downcast::<AwwasmParseError>()?;
+        assert_eq!(err.kind, AwwasmParseErrorKind::Malformed { detail: "type 0 refers to itself".into() });
+        Ok(())
+    }
+
+    #[test]
+    fn component_type_nesting_limit_test() -> anyhow::Result<()> {
+        // One instance type declaring an instance type declaring ... 200k
+        // levels deep.
+        let mut types = vec![0x01];
+        for _ in 0..200_000 {
+            types.extend_from_slice(&[0x42, 0x01, 0x01]);
+        }
+        types.extend_from_slice(&[0x42, 0x00]);
+        let mut component = b"\0asm\x0d\0\x01\0\x07".to_vec();
+        write_leb128_u32(&mut component, types.len() as u32);
+        component.extend_from_slice(&types);
+
+        let err = AwwasmComponent::new(&component)?.types().unwrap_err().downcast::<AwwasmParseError>()?;
+        let max = MAX_TYPE_NESTING_DEPTH as u64;
+        assert_eq!(err.kind, AwwasmParseErrorKind::LimitExceeded { limit: "type nesting depth", section: None, actual: max + 1, max });
+        Ok(())
+    }
+}
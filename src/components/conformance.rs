@@ -0,0 +1,133 @@
+use crate::json;
+
+/// Whether an entry is a binary-format production or a validation rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConformanceKind {
+    Production,
+    Validation,
+}
+
+/// How much of an entry the crate currently implements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConformanceStatus {
+    Implemented,
+    Partial,
+    Missing,
+}
+
+/// One row of the conformance table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConformanceEntry {
+    /// Stable identifier, named after the spec section it covers.
+    pub id: &'static str,
+    pub kind: ConformanceKind,
+    pub status: ConformanceStatus,
+    /// What is missing, for `Partial` and `Missing` entries.
+    pub note: &'static str,
+}
+
+const fn entry(id: &'static str, kind: ConformanceKind, status: ConformanceStatus, note: &'static str) -> ConformanceEntry {
+    ConformanceEntry { id, kind, status, note }
+}
+
+use ConformanceKind::{Production, Validation};
+use ConformanceStatus::{Implemented, Missing, Partial};
+
+/// What the crate implements of the core spec. Update this alongside any
+/// change that adds, completes or removes support for one of these entries.
+pub static CONFORMANCE: &[ConformanceEntry] = &[
+    // Binary format: module structure
+    entry("binary.module.preamble", Production, Implemented, ""),
+    entry("binary.module.section_order", Validation, Implemented, "rejected by strict parsing, reported by validate() otherwise"),
+    entry("binary.values.leb128", Production, Implemented, "padding and set unused bits are accepted; leb128_issues() reports them"),
+    entry("binary.values.name", Production, Implemented, ""),
+    entry("binary.types.valtype", Production, Implemented, ""),
+    entry("binary.types.reftype", Production, Partial, "(ref null? ht) value types; tables still take funcref/externref only"),
+    entry("binary.types.limits", Production, Partial, "the shared, custom page size and memory64 flags are decoded; limits must fit in 32 bits"),
+    entry("binary.section.custom", Production, Implemented, ""),
+    entry("binary.section.type", Production, Implemented, ""),
+    entry("binary.section.import", Production, Implemented, ""),
+    entry("binary.section.function", Production, Implemented, ""),
+    entry("binary.section.table", Production, Implemented, ""),
+    entry("binary.section.memory", Production, Partial, "32-bit limits only"),
+    entry("binary.section.global", Production, Implemented, ""),
+    entry("binary.section.export", Production, Implemented, ""),
+    entry("binary.section.start", Production, Implemented, ""),
+    entry("binary.section.element", Production, Implemented, ""),
+    entry("binary.section.code", Production, Implemented, ""),
+    entry("binary.section.data", Production, Implemented, ""),
+    entry("binary.section.datacount", Production, Implemented, ""),
+    entry("binary.section.tag", Production, Implemented, "exception handling proposal"),
+    // Binary format: instructions
+    entry("binary.instr.control", Production, Implemented, ""),
+    entry("binary.instr.reference", Production, Implemented, ""),
+    entry("binary.instr.parametric", Production, Implemented, ""),
+    entry("binary.instr.variable", Production, Implemented, ""),
+    entry("binary.instr.table", Production, Missing, "table.get and table.set"),
+    entry("binary.instr.memory", Production, Implemented, "including multi-memory memarg and memory.size/grow indices"),
+    entry("binary.instr.numeric", Production, Implemented, ""),
+    entry("binary.instr.sign_extension", Production, Implemented, ""),
+    entry("binary.instr.misc_prefix", Production, Partial, "saturating truncation and the memory bulk ops (0-11); table.init, elem.drop and table.copy/grow/size/fill are not decoded"),
+    entry("binary.instr.vector", Production, Missing, "0xFD SIMD prefix"),
+    entry("binary.instr.atomic", Production, Implemented, "0xFE threads prefix"),
+    entry("binary.instr.function_references", Production, Implemented, "call_ref, return_call_ref, ref.as_non_null, br_on_null and br_on_non_null"),
+    entry("binary.instr.tail_call", Production, Implemented, "return_call and return_call_indirect"),
+    entry("binary.instr.exception", Production, Implemented, "try_table, throw and throw_ref, and the legacy try/catch/delegate"),
+    entry("binary.instr.const", Production, Implemented, "constant expressions, including extended-const add/sub/mul"),
+    // Custom sections (appendix)
+    entry("appendix.custom.name", Production, Implemented, ""),
+    entry("appendix.custom.branch_hint", Production, Implemented, "metadata.code.branch_hint; offsets are rebased by the dedup pass"),
+    // Validation
+    entry("valid.values.name", Validation, Implemented, "import, export and custom section names"),
+    entry("valid.types.functype", Validation, Implemented, "the 0x60 form byte and the type indices of reference value types"),
+    entry("valid.types.limits", Validation, Partial, "64-bit limits are reported as unsupported; the memory64 bound of 2^48 pages is not applied"),
+    entry("valid.module.imports", Validation, Implemented, "function and tag type indices, via validate_indices()"),
+    entry("valid.module.functions", Validation, Implemented, ""),
+    entry("valid.module.exports", Validation, Implemented, ""),
+    entry("valid.module.start", Validation, Partial, "the function index only, via validate_indices(); the [] -> [] signature is not checked"),
+    entry("valid.module.globals", Validation, Implemented, ""),
+    entry("valid.module.elements", Validation, Partial, "init expressions only"),
+    entry("valid.module.data", Validation, Partial, "offset expressions only"),
+    entry("valid.instr.call_indirect", Validation, Implemented, ""),
+    entry("valid.instr.typing", Validation, Partial, "Includes local initialization and declared ref.func targets; SIMD bodies do not decode; exnref from catch_ref clauses is not tracked"),
+];
+
+/// The conformance table as JSON, tagged with the crate version so reports
+/// can be compared across releases.
+pub fn report() -> String {
+    let rows: Vec<String> = CONFORMANCE.iter()
+        .map(|e| format!(
+            "{{\"id\":{},\"kind\":{},\"status\":{},\"note\":{}}}",
+            json::string(e.id),
+            json::string(match e.kind {
+                Production => "production",
+                Validation => "validation",
+            }),
+            json::string(match e.status {
+                Implemented => "implemented",
+                Partial => "partial",
+                Missing => "missing",
+            }),
+            json::string(e.note),
+        ))
+        .collect();
+    format!("{{\"crate_version\":{},\"entries\":[{}]}}", json::string(env!("CARGO_PKG_VERSION")), rows.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::conformance::{report, CONFORMANCE};
+    use std::collections::HashSet;
+
+    #[test]
+    fn conformance_report_test() {
+        let ids: HashSet<&str> = CONFORMANCE.iter().map(|e| e.id).collect();
+        assert_eq!(ids.len(), CONFORMANCE.len(), "conformance ids must be unique");
+
+        let json = report();
+        assert!(json.starts_with(&format!("{{\"crate_version\":\"{}\",\"entries\":[", env!("CARGO_PKG_VERSION"))));
+        assert!(json.contains(r#"{"id":"binary.section.datacount","kind":"production","status":"implemented","note":""}"#));
+        assert!(json.contains(r#"{"id":"valid.instr.call_indirect","kind":"validation","status":"implemented","note":""}"#));
+        assert!(json.ends_with("]}"));
+    }
+}
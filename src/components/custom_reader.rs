@@ -0,0 +1,218 @@
+use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+use crate::components::module::AwwasmModule;
+use crate::components::section::SectionCode;
+use crate::consts::{WASM_MAGIC_NUMBER, WASM_PREAMBLE_MAGIC_SIZE_BYTES, WASM_PREAMBLE_VERSION_SIZE_BYTES};
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Streams the payloads of every custom section called `name`, concatenated in
+/// binary order, straight from a seekable source such as a `File`.
+///
+/// Only section headers and custom section names are read; other sections are
+/// seeked over, and payload bytes are copied directly into the caller's buffer.
+/// Large embedded assets split across several same-named sections can
+/// therefore be piped out without holding the module in memory.
+#[derive(Debug)]
+pub struct AwwasmCustomSectionReader<R> {
+    inner: R,
+    name: Vec<u8>,
+    /// Payload bytes left in the current matching section.
+    remaining: u64,
+    started: bool,
+    done: bool,
+}
+
+impl<R: Read + Seek> AwwasmCustomSectionReader<R> {
+    /// `inner` must be positioned at the start of the module.
+    pub fn new(inner: R, name: &[u8]) -> Self {
+        Self {
+            inner,
+            name: name.to_vec(),
+            remaining: 0,
+            started: false,
+            done: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Position `inner` at the next matching payload, or mark the stream done.
+    fn next_part(&mut self) -> io::Result<()> {
+        if !self.started {
+            let mut preamble = [0u8; WASM_PREAMBLE_MAGIC_SIZE_BYTES + WASM_PREAMBLE_VERSION_SIZE_BYTES];
+            self.inner.read_exact(&mut preamble)?;
+            if preamble[..WASM_PREAMBLE_MAGIC_SIZE_BYTES] != WASM_MAGIC_NUMBER[..] {
+                return Err(invalid_data(AwwasmParseError::new("module preamble", AwwasmParseErrorKind::BadMagic)));
+            }
+            self.started = true;
+        }
+
+        loop {
+            let mut id = [0u8; 1];
+            if self.inner.read(&mut id)? == 0 {
+                self.done = true;
+                return Ok(());
+            }
+            let (size, _) = read_leb128_u32(&mut self.inner)?;
+            let size = size as u64;
+            if id[0] != SectionCode::Custom as u8 {
+                self.inner.seek(SeekFrom::Current(size as i64))?;
+                continue;
+            }
+
+            let (name_len, leb_len) = read_leb128_u32(&mut self.inner)?;
+            let name_header = leb_len as u64 + name_len as u64;
+            if name_header > size {
+                let detail = "name exceeds section size".to_string();
+                return Err(invalid_data(AwwasmParseError::new("Custom Section", AwwasmParseErrorKind::Malformed { detail })));
+            }
+            let mut name = vec![0u8; name_len as usize];
+            self.inner.read_exact(&mut name)?;
+            if name == self.name {
+                self.remaining = size - name_header;
+                if self.remaining > 0 {
+                    return Ok(());
+                }
+            } else {
+                self.inner.seek(SeekFrom::Current((size - name_header) as i64))?;
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for AwwasmCustomSectionReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.remaining == 0 {
+            if self.done {
+                return Ok(0);
+            }
+            self.next_part()?;
+        }
+        let want = buf.len().min(self.remaining.try_into().unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..want])?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "custom section payload is truncated"));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// A `Read` over the payloads of already-parsed custom sections, without copying them.
+#[derive(Debug, Clone)]
+pub struct AwwasmPayloadReader<'a> {
+    parts: Vec<&'a [u8]>,
+}
+
+impl Read for AwwasmPayloadReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(part) = self.parts.first_mut() {
+            if part.is_empty() {
+                self.parts.remove(0);
+                continue;
+            }
+            return part.read(buf);
+        }
+        Ok(0)
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Stream the payloads of every custom section called `name`, concatenated
+    /// in binary order. Requires `resolve_all_sections()`.
+    pub fn custom_section_reader(&self, name: &[u8]) -> AwwasmPayloadReader<'a> {
+        AwwasmPayloadReader {
+            parts: self.customs().iter()
+                .filter(|c| c.name.bytes == name)
+                .map(|c| c.payload)
+                .collect(),
+        }
+    }
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+// Read an unsigned LEB128 u32, returning the value and its encoded length.
+fn read_leb128_u32<R: Read>(r: &mut R) -> io::Result<(u32, usize)> {
+    let mut value: u32 = 0;
+    for i in 0..5 {
+        let mut byte = [0u8; 1];
+        r.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u32) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(invalid_data(AwwasmParseError::new("section header", AwwasmParseErrorKind::BadLeb128)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::custom_reader::AwwasmCustomSectionReader;
+    use crate::components::module::AwwasmModule;
+    use std::io::{Cursor, Read};
+
+    const MULTI_PART: &str = r#"
+        (module
+            (@custom "asset" (before first) "hello, ")
+            (memory 1)
+            (@custom "other" (after memory) "ignored")
+            (@custom "asset" (after memory) "")
+            (func)
+            (@custom "asset" (after func) "world")
+        )
+    "#;
+
+    #[test]
+    fn stream_custom_sections_from_seekable_source_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(MULTI_PART)?;
+
+        let mut out = String::new();
+        AwwasmCustomSectionReader::new(Cursor::new(&module), b"asset").read_to_string(&mut out)?;
+        assert_eq!(out, "hello, world");
+
+        // Small reads cross part boundaries.
+        let mut reader = AwwasmCustomSectionReader::new(Cursor::new(&module), b"asset");
+        let mut buf = [0u8; 3];
+        let mut chunks = Vec::new();
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 { break; }
+            chunks.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(chunks, b"hello, world");
+
+        let mut missing = Vec::new();
+        AwwasmCustomSectionReader::new(Cursor::new(&module), b"nope").read_to_end(&mut missing)?;
+        assert!(missing.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn stream_custom_sections_from_module_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(MULTI_PART)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let mut out = String::new();
+        module_parsed.custom_section_reader(b"asset").read_to_string(&mut out)?;
+        assert_eq!(out, "hello, world");
+        Ok(())
+    }
+
+    #[test]
+    fn stream_rejects_truncated_payload_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"(module (@custom "asset" "0123456789"))"#)?;
+        let truncated = &module[..module.len() - 4];
+        let mut out = Vec::new();
+        let res = AwwasmCustomSectionReader::new(Cursor::new(truncated), b"asset").read_to_end(&mut out);
+        assert!(res.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use crate::components::instructions::*;
+use crate::components::module::AwwasmModule;
+use crate::components::types::AwwasmExportKind;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// Data segments an exported function may read, from
+/// `AwwasmModule::export_data_reads()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmExportDataReads {
+    /// Export name (lossily decoded as UTF-8).
+    pub export: String,
+    pub func_idx: u32,
+    /// Data segments, by index in the data section, that overlap a load with
+    /// a constant address.
+    pub segments: Vec<u32>,
+}
+
+// Bytes read by a load opcode; `None` for anything else.
+fn load_width(opcode: WasmOpCode) -> Option<u64> {
+    use WasmOpCode::*;
+    match opcode {
+        I32Load8S | I32Load8U | I64Load8S | I64Load8U => Some(1),
+        I32Load16S | I32Load16U | I64Load16S | I64Load16U => Some(2),
+        I32Load | F32Load | I64Load32S | I64Load32U => Some(4),
+        I64Load | F64Load => Some(8),
+        _ => None,
+    }
+}
+
+// Collect the address ranges of `i32.const` + load pairs, and the targets
+// of direct calls, in `instrs` and the blocks nested in them.
+fn scan(instrs: &[AwwasmInstruction], reads: &mut Vec<Range<u64>>, calls: &mut Vec<u32>) {
+    for (i, instr) in instrs.iter().enumerate() {
+        match &instr.operands {
+            AwwasmOperands::Call(op) | AwwasmOperands::ReturnCall(op) => calls.push(op.funcidx),
+            operands => operands.bodies().into_iter().for_each(|(body, _)| scan(body, reads, calls)),
+        }
+        let (Some(width), Some(mem_arg)) = (load_width(instr.opcode), instr.operands.mem_arg().filter(|m| m.memory() == 0)) else {
+            continue;
+        };
+        if let Some(AwwasmOperands::I32Const(address)) = i.checked_sub(1).map(|prev| &instrs[prev].operands) {
+            let start = address.value as u32 as u64 + mem_arg.offset as u64;
+            reads.push(start..start + width);
+        }
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// For each exported function, the data segments its loads may touch.
+    ///
+    /// A heuristic for triage: only loads whose address is an `i32.const`
+    /// right before them are considered, only active segments of memory 0
+    /// with a constant offset have a known range, and a function also reads
+    /// whatever the functions it calls directly read.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn export_data_reads(&self) -> anyhow::Result<Vec<AwwasmExportDataReads>> {
+        let mut segments = Vec::new();
+        for (i, seg) in self.data().iter().enumerate() {
+            let Some(offset) = seg.header.offset.as_ref().filter(|_| seg.header.memidx.unwrap_or(0) == 0) else {
+                continue;
+            };
+            if let Ok(start) = eval_const_init_expr(offset.code) {
+                let start = start as u32 as u64;
+                segments.push((i as u32, start..start + seg.size as u64));
+            }
+        }
+
+        let imported = self.imported_func_count();
+        let mut reads = Vec::new();
+        let mut calls = Vec::new();
+        for item in self.code() {
+            let (mut r, mut c) = (Vec::new(), Vec::new());
+            scan(&item.instructions_with_limits(&self.limits)?, &mut r, &mut c);
+            reads.push(r);
+            calls.push(c);
+        }
+
+        let mut result = Vec::new();
+        for export in self.exports().iter().filter(|e| e.kind == AwwasmExportKind::Function) {
+            let mut touched = BTreeSet::new();
+            let mut seen = BTreeSet::new();
+            let mut pending = vec![export.index];
+            while let Some(func_idx) = pending.pop() {
+                let Some(defined) = func_idx.checked_sub(imported).map(|i| i as usize).filter(|i| *i < reads.len()) else {
+                    continue;
+                };
+                if !seen.insert(func_idx) {
+                    continue;
+                }
+                for read in &reads[defined] {
+                    touched.extend(segments.iter()
+                        .filter(|(_, seg)| seg.start < read.end && read.start < seg.end)
+                        .map(|(i, _)| *i));
+                }
+                pending.extend(&calls[defined]);
+            }
+            result.push(AwwasmExportDataReads {
+                export: String::from_utf8_lossy(&export.name.bytes).into_owned(),
+                func_idx: export.index,
+                segments: touched.into_iter().collect(),
+            });
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn export_data_reads_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "hello")
+                (data (i32.const 64) "world")
+                (data "passive")
+                (func (export "greeting") (result i32)
+                    (i32.load (i32.const 16)))
+                (func $helper (result i32)
+                    (if (result i32) (i32.const 1)
+                        (then (i64.load8_u offset=66 (i32.const 0)) (i32.wrap_i64))
+                        (else (i32.const 0))))
+                (func (export "both") (result i32)
+                    (call $log (i32.load16_u (i32.const 19)))
+                    (call $helper))
+                (func (export "dynamic") (param i32) (result i32)
+                    (i32.load offset=16 (local.get 0)))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let reads: Vec<(String, u32, Vec<u32>)> = module_parsed.export_data_reads()?.into_iter()
+            .map(|r| (r.export, r.func_idx, r.segments))
+            .collect();
+        assert_eq!(reads, vec![
+            ("greeting".to_owned(), 1, vec![0]),
+            ("both".to_owned(), 3, vec![0, 1]),
+            ("dynamic".to_owned(), 4, vec![]),
+        ]);
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+use crate::components::module::AwwasmModule;
+use crate::components::types::*;
+use crate::json;
+use core::fmt::Write;
+
+/// Output format for a rendered module diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AwwasmDiffFormat {
+    #[default]
+    UnifiedText,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmDiffOptions {
+    /// Pair functions by name (exports) before falling back to index order.
+    pub align_by_name: bool,
+    /// Include functions that did not change in the rendered output.
+    pub show_unchanged: bool,
+    pub format: AwwasmDiffFormat,
+}
+
+impl Default for AwwasmDiffOptions {
+    fn default() -> Self {
+        Self {
+            align_by_name: true,
+            show_unchanged: false,
+            format: AwwasmDiffFormat::UnifiedText,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmFunctionChange {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+/// One aligned pair of defined functions. Indices are in the function index
+/// space (imports first).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmFunctionDiff {
+    pub name: Option<String>,
+    pub old_index: Option<u32>,
+    pub new_index: Option<u32>,
+    pub old_size: Option<u32>,
+    pub new_size: Option<u32>,
+    pub change: AwwasmFunctionChange,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AwwasmModuleDiff {
+    pub functions: Vec<AwwasmFunctionDiff>,
+}
+
+// A defined function as seen by the differ.
+struct FuncEntry<'m, 'a> {
+    index: u32,
+    name: Option<String>,
+    ty: Option<&'m AwwasmTypeSectionItem<'a>>,
+    body: Option<&'m [u8]>,
+}
+
+fn func_entries<'m, 'a>(module: &'m AwwasmModule<'a>) -> Vec<FuncEntry<'m, 'a>> {
+    let imported = module.imported_func_count();
+    let funcs = module.funcs();
+    let code = module.code();
+    funcs.iter().enumerate().map(|(i, f)| {
+        let index = imported + i as u32;
+        let name = module.exports().iter()
+            .find(|e| e.kind == AwwasmExportKind::Function && e.index == index)
+            .map(|e| String::from_utf8_lossy(&e.name.bytes).into_owned());
+        FuncEntry {
+            index,
+            name,
+            ty: module.types().get(f.type_item_idx as usize),
+            body: code.get(i).map(|c| c.func_body),
+        }
+    }).collect()
+}
+
+fn compare(old: &FuncEntry, new: &FuncEntry) -> AwwasmFunctionDiff {
+    let same = old.ty == new.ty && old.body == new.body;
+    AwwasmFunctionDiff {
+        name: new.name.clone().or_else(|| old.name.clone()),
+        old_index: Some(old.index),
+        new_index: Some(new.index),
+        old_size: old.body.map(|b| b.len() as u32),
+        new_size: new.body.map(|b| b.len() as u32),
+        change: if same { AwwasmFunctionChange::Unchanged } else { AwwasmFunctionChange::Changed },
+    }
+}
+
+fn one_sided(entry: &FuncEntry, change: AwwasmFunctionChange) -> AwwasmFunctionDiff {
+    let (index, size) = (Some(entry.index), entry.body.map(|b| b.len() as u32));
+    let added = change == AwwasmFunctionChange::Added;
+    AwwasmFunctionDiff {
+        name: entry.name.clone(),
+        old_index: if added { None } else { index },
+        new_index: if added { index } else { None },
+        old_size: if added { None } else { size },
+        new_size: if added { size } else { None },
+        change,
+    }
+}
+
+/// Compare the defined functions of two resolved modules.
+///
+/// With `align_by_name`, functions sharing an export name are paired first so
+/// that inserting a function does not shift every later pairing; the
+/// remaining functions are paired in index order.
+pub fn diff_modules(old: &AwwasmModule, new: &AwwasmModule, options: &AwwasmDiffOptions) -> AwwasmModuleDiff {
+    let old_funcs = func_entries(old);
+    let new_funcs = func_entries(new);
+    let mut old_used = vec![false; old_funcs.len()];
+    let mut new_used = vec![false; new_funcs.len()];
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+
+    if options.align_by_name {
+        for (ni, n) in new_funcs.iter().enumerate() {
+            let Some(name) = &n.name else { continue };
+            if let Some(oi) = old_funcs.iter().position(|o| o.name.as_ref() == Some(name)) {
+                if !old_used[oi] {
+                    old_used[oi] = true;
+                    new_used[ni] = true;
+                    pairs.push((oi, ni));
+                }
+            }
+        }
+    }
+
+    // Pair whatever is left by position among the unmatched functions.
+    // Named functions without a counterpart stay unpaired (added/removed).
+    let positional = |used: &[bool], funcs: &[FuncEntry]| -> Vec<usize> {
+        (0..funcs.len())
+            .filter(|i| !used[*i])
+            .filter(|i| !options.align_by_name || funcs[*i].name.is_none())
+            .collect()
+    };
+    let old_rest = positional(&old_used, &old_funcs);
+    let new_rest = positional(&new_used, &new_funcs);
+    for (oi, ni) in old_rest.iter().zip(new_rest.iter()) {
+        old_used[*oi] = true;
+        new_used[*ni] = true;
+        pairs.push((*oi, *ni));
+    }
+
+    let mut functions: Vec<AwwasmFunctionDiff> = pairs.iter()
+        .map(|(oi, ni)| compare(&old_funcs[*oi], &new_funcs[*ni]))
+        .collect();
+    functions.extend(old_funcs.iter().zip(&old_used)
+        .filter(|(_, used)| !**used)
+        .map(|(f, _)| one_sided(f, AwwasmFunctionChange::Removed)));
+    functions.extend(new_funcs.iter().zip(&new_used)
+        .filter(|(_, used)| !**used)
+        .map(|(f, _)| one_sided(f, AwwasmFunctionChange::Added)));
+    functions.sort_by_key(|f| (f.new_index.or(f.old_index), f.new_index.is_none()));
+
+    AwwasmModuleDiff { functions }
+}
+
+impl AwwasmModuleDiff {
+    /// Whether any function was added, removed or changed.
+    pub fn has_changes(&self) -> bool {
+        self.functions.iter().any(|f| f.change != AwwasmFunctionChange::Unchanged)
+    }
+
+    pub fn render(&self, options: &AwwasmDiffOptions) -> String {
+        match options.format {
+            AwwasmDiffFormat::UnifiedText => self.render_text(options),
+            AwwasmDiffFormat::Json => self.render_json(options),
+        }
+    }
+
+    fn visible<'s>(&'s self, options: &AwwasmDiffOptions) -> impl Iterator<Item = &'s AwwasmFunctionDiff> {
+        let show_unchanged = options.show_unchanged;
+        self.functions.iter().filter(move |f| show_unchanged || f.change != AwwasmFunctionChange::Unchanged)
+    }
+
+    fn render_text(&self, options: &AwwasmDiffOptions) -> String {
+        let mut out = String::from("--- old\n+++ new\n");
+        for f in self.visible(options) {
+            let label = match (&f.name, f.new_index.or(f.old_index)) {
+                (Some(name), _) => format!("func {}", name),
+                (None, Some(idx)) => format!("func[{}]", idx),
+                (None, None) => String::from("func"),
+            };
+            let _ = match f.change {
+                AwwasmFunctionChange::Added => writeln!(out, "+ {} (index {}, {} bytes)",
+                    label, opt(f.new_index), opt(f.new_size)),
+                AwwasmFunctionChange::Removed => writeln!(out, "- {} (index {}, {} bytes)",
+                    label, opt(f.old_index), opt(f.old_size)),
+                AwwasmFunctionChange::Changed => writeln!(out, "~ {} (index {} -> {}, {} -> {} bytes)",
+                    label, opt(f.old_index), opt(f.new_index), opt(f.old_size), opt(f.new_size)),
+                AwwasmFunctionChange::Unchanged => writeln!(out, "  {} (index {} -> {})",
+                    label, opt(f.old_index), opt(f.new_index)),
+            };
+        }
+        out
+    }
+
+    fn render_json(&self, options: &AwwasmDiffOptions) -> String {
+        let entries: Vec<String> = self.visible(options).map(|f| {
+            let change = match f.change {
+                AwwasmFunctionChange::Added => "added",
+                AwwasmFunctionChange::Removed => "removed",
+                AwwasmFunctionChange::Changed => "changed",
+                AwwasmFunctionChange::Unchanged => "unchanged",
+            };
+            format!(
+                "{{\"name\":{},\"change\":\"{}\",\"old_index\":{},\"new_index\":{},\"old_size\":{},\"new_size\":{}}}",
+                f.name.as_deref().map_or_else(|| String::from("null"), json::string),
+                change,
+                json::opt_number(f.old_index), json::opt_number(f.new_index),
+                json::opt_number(f.old_size), json::opt_number(f.new_size),
+            )
+        }).collect();
+        format!("{{\"functions\":[{}]}}", entries.join(","))
+    }
+}
+
+fn opt(v: Option<u32>) -> String {
+    v.map_or_else(|| String::from("?"), |v| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::diff::*;
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn diff_aligns_functions_by_export_name_test() -> anyhow::Result<()> {
+        let old_bytes = wat::parse_str(r#"
+            (module
+                (func (export "a") (result i32) i32.const 1)
+                (func (export "b") (result i32) i32.const 2))
+        "#)?;
+        // A function inserted at the front shifts every index by one.
+        let new_bytes = wat::parse_str(r#"
+            (module
+                (func (export "new") (result i32) i32.const 0)
+                (func (export "a") (result i32) i32.const 1)
+                (func (export "b") (result i32) i32.const 3))
+        "#)?;
+        let mut old = AwwasmModule::new(&old_bytes)?;
+        old.resolve_all_sections()?;
+        let mut new = AwwasmModule::new(&new_bytes)?;
+        new.resolve_all_sections()?;
+
+        let options = AwwasmDiffOptions::default();
+        let diff = diff_modules(&old, &new, &options);
+        let changes: Vec<_> = diff.functions.iter()
+            .map(|f| (f.name.as_deref(), f.change))
+            .collect();
+        assert_eq!(changes, vec![
+            (Some("new"), AwwasmFunctionChange::Added),
+            (Some("a"), AwwasmFunctionChange::Unchanged),
+            (Some("b"), AwwasmFunctionChange::Changed),
+        ]);
+        assert_eq!(diff.render(&options), "--- old\n+++ new\n+ func new (index 0, 4 bytes)\n~ func b (index 1 -> 2, 4 -> 4 bytes)\n");
+
+        let json = diff.render(&AwwasmDiffOptions { format: AwwasmDiffFormat::Json, ..options.clone() });
+        assert!(json.starts_with("{\"functions\":[{\"name\":\"new\",\"change\":\"added\""));
+
+        // Without name alignment every later function looks changed.
+        let by_index = diff_modules(&old, &new, &AwwasmDiffOptions { align_by_name: false, ..options });
+        assert_eq!(by_index.functions.iter().filter(|f| f.change == AwwasmFunctionChange::Changed).count(), 2);
+        Ok(())
+    }
+}
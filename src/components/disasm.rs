@@ -0,0 +1,181 @@
+use crate::components::floats::AwwasmFloatFormat;
+use crate::components::instructions::*;
+use crate::components::parse_limits::AwwasmParseLimits;
+use crate::components::types::AwwasmCodeSectionItem;
+use std::fmt::Write;
+
+// Width of the byte column in `Columns` style, as in `wasm-objdump -d`.
+const BYTES_COLUMN_WIDTH: usize = 26;
+
+/// How `function()` lays out each instruction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AwwasmDisasmStyle {
+    /// Mnemonic and operands, indented by block depth.
+    #[default]
+    Text,
+    /// `offset: bytes | mnemonic operands`, laid out like `wasm-objdump -d`.
+    /// Offsets are relative to the start of the module.
+    Columns,
+}
+
+/// Options for `function_with_options()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AwwasmDisasmOptions {
+    pub style: AwwasmDisasmStyle,
+    /// How `f32.const` and `f64.const` immediates are written.
+    pub floats: AwwasmFloatFormat,
+}
+
+/// Disassemble one function body, one instruction per line, including the
+/// `else` and `end` markers of nested blocks.
+///
+/// `module_bytes` must be the buffer the module was parsed from; it is used
+/// to locate each instruction in `Columns` style.
+pub fn function(module_bytes: &[u8], item: &AwwasmCodeSectionItem, style: AwwasmDisasmStyle) -> anyhow::Result<String> {
+    function_with_options(module_bytes, item, AwwasmDisasmOptions { style, ..Default::default() })
+}
+
+/// Like `function`, with the float format as well as the style chosen by `options`.
+pub fn function_with_options(module_bytes: &[u8], item: &AwwasmCodeSectionItem, options: AwwasmDisasmOptions) -> anyhow::Result<String> {
+    let mut text = String::new();
+    write_function(&mut text, item, options, &AwwasmParseLimits::default(), &|encoding| span_in(module_bytes, encoding).map(|span| span.start))?;
+    Ok(text)
+}
+
+// Append the disassembly of `item`, decoded under `limits`, to `text`,
+// locating instructions with `offset_of` in `Columns` style.
+pub(crate) fn write_function(
+    text: &mut String,
+    item: &AwwasmCodeSectionItem,
+    options: AwwasmDisasmOptions,
+    limits: &AwwasmParseLimits,
+    offset_of: &dyn Fn(&[u8]) -> Option<usize>,
+) -> anyhow::Result<()> {
+    let mut out = Disassembly { offset_of, options, text };
+    for instr in &item.instructions_with_limits(limits)? {
+        out.instruction(instr, 0);
+    }
+    Ok(())
+}
+
+struct Disassembly<'m> {
+    offset_of: &'m dyn Fn(&[u8]) -> Option<usize>,
+    options: AwwasmDisasmOptions,
+    text: &'m mut String,
+}
+
+impl Disassembly<'_> {
+    fn instruction(&mut self, instr: &AwwasmInstruction, depth: usize) {
+        self.line(instr.encoding, depth, &instr.display_with(self.options.floats).to_string());
+        for (instrs, marker) in instr.operands.bodies() {
+            for instr in instrs {
+                self.instruction(instr, depth + 1);
+            }
+            self.line(marker, depth, &marker_text(marker));
+        }
+    }
+
+    fn line(&mut self, encoding: &[u8], depth: usize, text: &str) {
+        let indent = "  ".repeat(depth);
+        match self.options.style {
+            AwwasmDisasmStyle::Text => {
+                let _ = writeln!(self.text, "{}{}", indent, text);
+            }
+            AwwasmDisasmStyle::Columns => {
+                let offset = (self.offset_of)(encoding).unwrap_or(0);
+                let bytes: Vec<String> = encoding.iter().map(|b| format!("{:02x}", b)).collect();
+                let _ = writeln!(self.text, " {:06x}: {:<width$} | {}{}", offset, bytes.join(" "), indent, text, width = BYTES_COLUMN_WIDTH);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::disasm::{function, function_with_options, AwwasmDisasmOptions, AwwasmDisasmStyle};
+    use crate::components::floats::AwwasmFloatFormat;
+    use crate::components::module::AwwasmModule;
+
+    const MODULE: &str = r#"
+        (module
+            (memory 1)
+            (func (param i32) (result i32)
+                (block (result i32)
+                    (if (local.get 0)
+                        (then (br_if 1 (i32.const 7) (local.get 0)) (unreachable))
+                        (else (i32.load offset=8 (i32.const 0)) (br 1)))
+                    (i32.const -1))))
+    "#;
+
+    #[test]
+    fn disassemble_function_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(MODULE)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        let code = &module_parsed.code.as_ref().expect("code should exist")[0];
+
+        assert_eq!(function(&module, code, AwwasmDisasmStyle::Text)?, "\
+block i32
+  local.get 0
+  if
+    i32.const 7
+    local.get 0
+    br_if 1
+    unreachable
+  else
+    i32.const 0
+    i32.load 2 8
+    br 1
+  end
+  i32.const -1
+end
+end
+");
+        let columns = function(&module, code, AwwasmDisasmStyle::Columns)?;
+        assert_eq!(columns.lines().collect::<Vec<_>>(), vec![
+            " 00001e: 02 7f                      | block i32",
+            " 000020: 20 00                      |   local.get 0",
+            " 000022: 04 40                      |   if",
+            " 000024: 41 07                      |     i32.const 7",
+            " 000026: 20 00                      |     local.get 0",
+            " 000028: 0d 01                      |     br_if 1",
+            " 00002a: 00                         |     unreachable",
+            " 00002b: 05                         |   else",
+            " 00002c: 41 00                      |     i32.const 0",
+            " 00002e: 28 02 08                   |     i32.load 2 8",
+            " 000031: 0c 01                      |     br 1",
+            " 000033: 0b                         |   end",
+            " 000034: 41 7f                      |   i32.const -1",
+            " 000036: 0b                         | end",
+            " 000037: 0b                         | end",
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn disassemble_float_format_test() -> anyhow::Result<()> {
+        let module = wat::parse_str("(module (func (drop (f32.const nan:0x200001)) (drop (f64.const 0.1))))")?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        let code = &module_parsed.code.as_ref().expect("code should exist")[0];
+
+        let text = |floats| function_with_options(&module, code, AwwasmDisasmOptions { floats, ..Default::default() });
+        assert_eq!(text(AwwasmFloatFormat::Decimal)?.lines().step_by(2).collect::<Vec<_>>(), vec!["f32.const nan", "f64.const 0.1", "end"]);
+        assert_eq!(text(AwwasmFloatFormat::HexFloat)?.lines().step_by(2).collect::<Vec<_>>(), vec!["f32.const nan:0x200001", "f64.const 0x1.999999999999ap-4", "end"]);
+        assert_eq!(text(AwwasmFloatFormat::Bits)?.lines().step_by(2).collect::<Vec<_>>(), vec!["f32.const 0x7fa00001", "f64.const 0x3fb999999999999a", "end"]);
+        Ok(())
+    }
+
+    #[test]
+    fn raw_encoding_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(MODULE)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+
+        assert_eq!(instrs[0].raw_encoding(&module), &[0x02, 0x7f]);
+        assert_eq!(instrs[0].span(&module), Some(0x1e..0x20));
+        assert!(instrs[0].raw_encoding(&module.clone()).is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,365 @@
+use crate::components::disasm::{write_function, AwwasmDisasmOptions, AwwasmDisasmStyle};
+use crate::components::floats::AwwasmFloatFormat;
+use crate::components::instructions::InstructionIterator;
+use crate::components::module::AwwasmModule;
+use crate::components::names::AwwasmNameSection;
+use crate::components::section::{leb128_len_u32, SectionCode};
+use crate::components::types::*;
+use crate::consts::{WASM_PREAMBLE_MAGIC_SIZE_BYTES, WASM_PREAMBLE_VERSION_SIZE_BYTES};
+use std::fmt::Write;
+
+// Bytes of a data segment shown per line of its hexdump.
+const DATA_ROW_BYTES: usize = 16;
+
+/// What `AwwasmModule::dump()` prints. The default prints everything, like
+/// `wasm-objdump -h -x -d`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmDumpOptions {
+    /// One line per section with its offsets, size and entry count (`-h`).
+    pub headers: bool,
+    /// Every entry of every section (`-x`).
+    pub details: bool,
+    /// Each function body, one instruction per line with its offset and
+    /// bytes (`-d`).
+    pub disassemble: bool,
+    /// How `f32.const` and `f64.const` immediates are written.
+    pub floats: AwwasmFloatFormat,
+}
+
+impl Default for AwwasmDumpOptions {
+    fn default() -> Self {
+        AwwasmDumpOptions { headers: true, details: true, disassemble: true, floats: AwwasmFloatFormat::default() }
+    }
+}
+
+// Section names as `wasm-objdump` prints them.
+fn section_name(code: &SectionCode) -> &'static str {
+    match code {
+        SectionCode::Element => "Elem",
+        SectionCode::Custom => "Custom",
+        SectionCode::Type => "Type",
+        SectionCode::Import => "Import",
+        SectionCode::Function => "Function",
+        SectionCode::Table => "Table",
+        SectionCode::Memory => "Memory",
+        SectionCode::Global => "Global",
+        SectionCode::Export => "Export",
+        SectionCode::Start => "Start",
+        SectionCode::Code => "Code",
+        SectionCode::Data => "Data",
+        SectionCode::DataCount => "DataCount",
+        SectionCode::Tag => "Tag",
+    }
+}
+
+fn signature(ty: &AwwasmTypeSectionItem) -> String {
+    let list = |types: &[ValType]| types.iter().map(ValType::to_string).collect::<Vec<_>>().join(", ");
+    let results = match ty.fn_rets.len() {
+        0 => String::from("nil"),
+        1 => ty.fn_rets[0].to_string(),
+        _ => format!("({})", list(&ty.fn_rets)),
+    };
+    format!("({}) -> {}", list(&ty.fn_args), results)
+}
+
+fn limits(limits: &AwwasmMemoryParams) -> String {
+    let mut text = format!("initial={}", limits.min);
+    if let Some(max) = limits.max {
+        let _ = write!(text, " max={}", max);
+    }
+    if limits.is_shared() {
+        text.push_str(" shared");
+    }
+    text
+}
+
+fn ref_type(ty: &AwwasmTableReferenceType) -> &'static str {
+    match ty {
+        AwwasmTableReferenceType::Function => "funcref",
+        AwwasmTableReferenceType::Extern => "externref",
+    }
+}
+
+fn global_type(value_type: &ValType, mutability: &AwwasmGlobalMutability) -> String {
+    format!("{} mutable={}", value_type, u8::from(*mutability == AwwasmGlobalMutability::Mutable))
+}
+
+// A constant expression as its instructions, without the final `end`.
+fn init_expr(expr: &AwwasmDataInitExpr) -> String {
+    let instrs: Vec<String> = InstructionIterator::new(expr.code)
+        .map(|instr| instr.map_or_else(|_| String::from("<invalid>"), |instr| instr.to_string()))
+        .collect();
+    instrs.join("; ")
+}
+
+fn hexdump(out: &mut String, bytes: &[u8]) {
+    for (row, chunk) in bytes.chunks(DATA_ROW_BYTES).enumerate() {
+        let pairs: Vec<String> = chunk.chunks(2).map(|pair| pair.iter().map(|b| format!("{:02x}", b)).collect()).collect();
+        let ascii: String = chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+        let _ = writeln!(out, "  - {:07x}: {:<39}  {}", row * DATA_ROW_BYTES, pairs.join(" "), ascii);
+    }
+}
+
+// The name of function `index` as ` <name>`, if the name section has one.
+fn func_name(names: Option<&AwwasmNameSection>, index: u32) -> String {
+    names.and_then(|n| n.function_name(index)).map_or_else(String::new, |name| format!(" <{}>", name))
+}
+
+fn quoted(name: &AwwasmName) -> String {
+    format!("{:?}", String::from_utf8_lossy(&name.bytes))
+}
+
+impl AwwasmModule<'_> {
+    /// A listing of the module in the layout of `wasm-objdump -h -x -d`, or
+    /// the parts of it `options` selects: a section summary, the entries of
+    /// each section, and the disassembly of each function with offsets
+    /// from the start of the module.
+    ///
+    /// Section offsets assume size fields encoded in the fewest bytes, as
+    /// every toolchain emits them. Requires `resolve_all_sections()`. Fails
+    /// if a function body does not decode.
+    pub fn dump(&self, options: AwwasmDumpOptions) -> anyhow::Result<String> {
+        let names = self.names().ok().flatten();
+        let names = names.as_ref();
+        let mut out = format!("file format wasm {:#x}\n", self.preamble.version);
+        if options.headers {
+            self.dump_headers(&mut out);
+        }
+        if options.details {
+            self.dump_details(&mut out, names);
+        }
+        if options.disassemble {
+            let _ = write!(out, "\nCode Disassembly:\n");
+            let disasm = AwwasmDisasmOptions { style: AwwasmDisasmStyle::Columns, floats: options.floats };
+            let imported = self.imported_func_count();
+            for (i, item) in self.code().iter().enumerate() {
+                let index = imported + i as u32;
+                // Offset of the body's size field, as `wasm-objdump` gives.
+                let offset = self.offset_of(item.func_body).map_or(0, |o| o - leb128_len_u32(item.fn_body_size) as usize);
+                let _ = write!(out, "\n{:06x} func[{}]{}:\n", offset, index, func_name(names, index));
+                write_function(&mut out, item, disasm, &self.limits, &|encoding| self.offset_of(encoding))?;
+            }
+        }
+        Ok(out)
+    }
+
+    fn dump_headers(&self, out: &mut String) {
+        let _ = write!(out, "\nSections:\n\n");
+        let mut offset = WASM_PREAMBLE_MAGIC_SIZE_BYTES + WASM_PREAMBLE_VERSION_SIZE_BYTES;
+        let mut customs = self.customs().iter();
+        for sec in self.sections() {
+            let header = &sec.section_header;
+            let size = header.section_size as usize;
+            let start = offset + 1 + leb128_len_u32(header.section_size) as usize;
+            offset = start + size;
+            let _ = write!(out, "{:>9} start={:#010x} end={:#010x} (size={:#010x})", section_name(&header.section_type), start, offset, size);
+            let _ = match header.section_type {
+                SectionCode::Custom => writeln!(out, " {}", customs.next().map_or_else(String::new, |c| quoted(&c.name))),
+                SectionCode::Start => writeln!(out, " start: {}", sec.entry_count),
+                _ => writeln!(out, " count: {}", sec.entry_count),
+            };
+        }
+    }
+
+    fn dump_details(&self, out: &mut String, names: Option<&AwwasmNameSection>) {
+        let _ = write!(out, "\nSection Details:\n\n");
+        let mut customs = self.customs().iter();
+        let mut counts = [0u32; 5];
+        for sec in self.sections() {
+            let code = &sec.section_header.section_type;
+            let _ = match code {
+                SectionCode::Custom | SectionCode::Start | SectionCode::DataCount => writeln!(out, "{}:", section_name(code)),
+                _ => writeln!(out, "{}[{}]:", section_name(code), sec.entry_count),
+            };
+            match code {
+                SectionCode::Custom => if let Some(custom) = customs.next() {
+                    let _ = writeln!(out, " - name: {}", quoted(&custom.name));
+                },
+                SectionCode::Type => for (i, ty) in self.types().iter().enumerate() {
+                    let _ = writeln!(out, " - type[{}] {}", i, signature(ty));
+                },
+                SectionCode::Import => for import in self.imports() {
+                    let count = &mut counts[import.kind.clone() as usize];
+                    let index = *count;
+                    *count += 1;
+                    let what = match import.kind {
+                        AwwasmImportKind::Function => {
+                            format!("func[{}] sig={}{}", index, import.func_type_idx.unwrap_or_default(), func_name(names, index))
+                        }
+                        AwwasmImportKind::Table => import.table.as_ref().map_or_else(String::new, |t| {
+                            format!("table[{}] type={} {}", index, ref_type(&t.elem_type), limits(&t.limits))
+                        }),
+                        AwwasmImportKind::Memory => {
+                            format!("memory[{}] pages: {}", index, import.mem.as_ref().map_or_else(String::new, limits))
+                        }
+                        AwwasmImportKind::Global => import.global.as_ref().map_or_else(String::new, |g| {
+                            format!("global[{}] {}", index, global_type(&g.value_type, &g.mutability))
+                        }),
+                        AwwasmImportKind::Tag => format!("tag[{}] sig={}", index, import.tag.as_ref().map_or(0, |t| t.type_idx)),
+                    };
+                    let _ = writeln!(out, " - {} <- {}.{}", what, String::from_utf8_lossy(&import.module.bytes), String::from_utf8_lossy(&import.name.bytes));
+                },
+                SectionCode::Function => for (i, func) in self.funcs().iter().enumerate() {
+                    let index = counts[0] + i as u32;
+                    let _ = writeln!(out, " - func[{}] sig={}{}", index, func.type_item_idx, func_name(names, index));
+                },
+                SectionCode::Table => for (i, table) in self.tables().iter().enumerate() {
+                    let _ = writeln!(out, " - table[{}] type={} {}", counts[1] + i as u32, ref_type(&table.elem_type), limits(&table.limits));
+                },
+                SectionCode::Memory => for (i, memory) in self.memories().iter().enumerate() {
+                    let _ = writeln!(out, " - memory[{}] pages: {}", counts[2] + i as u32, limits(&memory.limits));
+                },
+                SectionCode::Global => for (i, global) in self.globals().iter().enumerate() {
+                    let ty = global_type(&global.value_type, &global.mutability);
+                    let _ = writeln!(out, " - global[{}] {} - init {}", counts[3] + i as u32, ty, init_expr(&global.init_expr));
+                },
+                SectionCode::Export => for export in self.exports() {
+                    let what = match export.kind {
+                        AwwasmExportKind::Function => format!("func[{}]{}", export.index, func_name(names, export.index)),
+                        AwwasmExportKind::Table => format!("table[{}]", export.index),
+                        AwwasmExportKind::Memory => format!("memory[{}]", export.index),
+                        AwwasmExportKind::Global => format!("global[{}]", export.index),
+                        AwwasmExportKind::Tag => format!("tag[{}]", export.index),
+                    };
+                    let _ = writeln!(out, " - {} -> {}", what, quoted(&export.name));
+                },
+                SectionCode::Start => {
+                    let _ = writeln!(out, " - start function: {}", sec.entry_count);
+                }
+                SectionCode::Element => for (i, element) in self.elements().iter().enumerate() {
+                    self.dump_element(out, names, i, element);
+                },
+                SectionCode::Code => for (i, item) in self.code().iter().enumerate() {
+                    let index = counts[0] + i as u32;
+                    let _ = writeln!(out, " - func[{}] size={}{}", index, item.fn_body_size, func_name(names, index));
+                },
+                SectionCode::Data => for (i, data) in self.data().iter().enumerate() {
+                    let _ = write!(out, " - segment[{}]", i);
+                    let _ = match &data.header.offset {
+                        Some(offset) => write!(out, " memory={} size={} - init {}", data.header.memidx.unwrap_or(0), data.size, init_expr(offset)),
+                        None => write!(out, " passive size={}", data.size),
+                    };
+                    out.push('\n');
+                    hexdump(out, data.data_bytes);
+                },
+                SectionCode::DataCount => {
+                    let _ = writeln!(out, " - data count: {}", sec.entry_count);
+                }
+                SectionCode::Tag => for (i, tag) in self.tags().iter().enumerate() {
+                    let _ = writeln!(out, " - tag[{}] sig={}", counts[4] + i as u32, tag.type_idx);
+                },
+            }
+        }
+    }
+
+    fn dump_element(&self, out: &mut String, names: Option<&AwwasmNameSection>, i: usize, element: &AwwasmElementSectionItem) {
+        use AwwasmElemSegmentBody::*;
+        let (table, funcs, count) = match &element.body {
+            ActiveImplicit(s) => (Some(0), Some(&s.func_indices), s.func_count),
+            ActiveExplicit(s) => (Some(s.tableidx), Some(&s.func_indices), s.func_count),
+            Passive(AwwasmPassiveElemSeg { func_indices, func_count, .. })
+            | Declarative(AwwasmDeclarativeElemSeg { func_indices, func_count, .. }) => (None, Some(func_indices), *func_count),
+            ActiveImplicitExprs(s) => (Some(0), None, s.expr_count),
+            ActiveExplicitExprs(s) => (Some(s.tableidx), None, s.expr_count),
+            PassiveExprs(s) => (None, None, s.expr_count),
+            DeclarativeExprs(s) => (None, None, s.expr_count),
+        };
+        let _ = write!(out, " - segment[{}] flags={}", i, element.flags);
+        if let Some(table) = table {
+            let _ = write!(out, " table={}", table);
+        }
+        let _ = write!(out, " count={}", count);
+        let mut exprs = element.body.init_exprs().into_iter();
+        if table.is_some() {
+            let _ = write!(out, " - init {}", exprs.next().map_or_else(String::new, init_expr));
+        }
+        out.push('\n');
+        for (k, func) in funcs.into_iter().flatten().enumerate() {
+            let _ = writeln!(out, "  - elem[{}] = func[{}]{}", k, func, func_name(names, *func));
+        }
+        for (k, expr) in exprs.enumerate() {
+            let _ = writeln!(out, "  - elem[{}] = {}", k, init_expr(expr));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::dump::AwwasmDumpOptions;
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn dump_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (memory (export "mem") 1 2)
+                (global $g (mut i32) (i32.const 8))
+                (table 1 funcref)
+                (elem (i32.const 0) $run)
+                (func $run (export "run") (param i32) (result i32)
+                    (call $log (local.get 0))
+                    (global.get $g))
+                (data (i32.const 16) "hello, world!\00\01\02\03"))
+        "#)?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        module.resolve_all_sections()?;
+        assert_eq!(module.dump(AwwasmDumpOptions::default())?, r#"file format wasm 0x1
+
+Sections:
+
+     Type start=0x0000000a end=0x00000014 (size=0x0000000a) count: 2
+   Import start=0x00000016 end=0x00000021 (size=0x0000000b) count: 1
+ Function start=0x00000023 end=0x00000025 (size=0x00000002) count: 1
+    Table start=0x00000027 end=0x0000002b (size=0x00000004) count: 1
+   Memory start=0x0000002d end=0x00000031 (size=0x00000004) count: 1
+   Global start=0x00000033 end=0x00000039 (size=0x00000006) count: 1
+   Export start=0x0000003b end=0x00000048 (size=0x0000000d) count: 2
+     Elem start=0x0000004a end=0x00000051 (size=0x00000007) count: 1
+     Code start=0x00000053 end=0x0000005d (size=0x0000000a) count: 1
+     Data start=0x0000005f end=0x00000076 (size=0x00000017) count: 1
+   Custom start=0x00000078 end=0x00000090 (size=0x00000018) "name"
+
+Section Details:
+
+Type[2]:
+ - type[0] (i32) -> nil
+ - type[1] (i32) -> i32
+Import[1]:
+ - func[0] sig=0 <log> <- env.log
+Function[1]:
+ - func[1] sig=1 <run>
+Table[1]:
+ - table[0] type=funcref initial=1
+Memory[1]:
+ - memory[0] pages: initial=1 max=2
+Global[1]:
+ - global[0] i32 mutable=1 - init i32.const 8
+Export[2]:
+ - memory[0] -> "mem"
+ - func[1] <run> -> "run"
+Elem[1]:
+ - segment[0] flags=0 table=0 count=1 - init i32.const 0
+  - elem[0] = func[1] <run>
+Code[1]:
+ - func[1] size=8 <run>
+Data[1]:
+ - segment[0] memory=0 size=17 - init i32.const 16
+  - 0000000: 6865 6c6c 6f2c 2077 6f72 6c64 2100 0102  hello, world!...
+  - 0000010: 03                                       .
+Custom:
+ - name: "name"
+
+Code Disassembly:
+
+000054 func[1] <run>:
+ 000056: 20 00                      | local.get 0
+ 000058: 10 00                      | call 0
+ 00005a: 23 00                      | global.get 0
+ 00005c: 0b                         | end
+"#);
+        let headers = AwwasmDumpOptions { details: false, disassemble: false, ..Default::default() };
+        assert_eq!(module.dump(headers)?.lines().count(), 15);
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use crate::components::module::AwwasmModule;
+use std::collections::BTreeMap;
+
+const DEBUG_SECTION_PREFIX: &str = ".debug_";
+
+/// Embedded DWARF custom sections (`.debug_info`, `.debug_line`, ...), by name.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmDebugSections<'a> {
+    pub sections: BTreeMap<&'a str, &'a [u8]>,
+}
+
+impl<'a> AwwasmDebugSections<'a> {
+    /// Bytes of the section called `name` (e.g. `.debug_info`).
+    pub fn get(&self, name: &str) -> Option<&'a [u8]> {
+        self.sections.get(name).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// Load the sections into `gimli` for symbolication. Missing sections are
+    /// treated as empty, as gimli expects.
+    #[cfg(feature = "gimli")]
+    pub fn dwarf(&self) -> gimli::Dwarf<gimli::EndianSlice<'a, gimli::LittleEndian>> {
+        let load = |id: gimli::SectionId| -> Result<_, std::convert::Infallible> {
+            Ok(gimli::EndianSlice::new(self.get(id.name()).unwrap_or(&[]), gimli::LittleEndian))
+        };
+        match gimli::Dwarf::load(load) {
+            Ok(dwarf) => dwarf,
+            Err(e) => match e {},
+        }
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Collect every `.debug_*` custom section. If a name appears more than
+    /// once, the first section wins.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn debug_sections(&self) -> AwwasmDebugSections<'_> {
+        let mut sections = BTreeMap::new();
+        for custom in self.customs() {
+            if let Some(name) = custom.name.to_str().filter(|n| n.starts_with(DEBUG_SECTION_PREFIX)) {
+                sections.entry(name).or_insert(custom.payload);
+            }
+        }
+        AwwasmDebugSections { sections }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+
+    const MODULE: &str = r#"
+        (module
+            (func)
+            (@custom ".debug_info" "\01\02")
+            (@custom ".debug_str" "main\00helper\00")
+            (@custom "producers" "\00")
+            (@custom ".debug_str" "ignored")
+        )
+    "#;
+
+    #[test]
+    fn debug_sections_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(MODULE)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let debug = module_parsed.debug_sections();
+        assert_eq!(debug.sections.keys().copied().collect::<Vec<_>>(), vec![".debug_info", ".debug_str"]);
+        assert_eq!(debug.get(".debug_info"), Some(&[1u8, 2][..]));
+        assert_eq!(debug.get(".debug_str"), Some(&b"main\0helper\0"[..]));
+        assert_eq!(debug.get(".debug_line"), None);
+        Ok(())
+    }
+
+    #[cfg(feature = "gimli")]
+    #[test]
+    fn debug_sections_gimli_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(MODULE)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let dwarf = module_parsed.debug_sections().dwarf();
+        let s = dwarf.debug_str.get_str(gimli::DebugStrOffset(5)).map_err(|e| anyhow::anyhow!("{}", e))?;
+        assert_eq!(s.slice(), b"helper");
+        Ok(())
+    }
+}
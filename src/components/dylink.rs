@@ -0,0 +1,172 @@
+use crate::components::error::AwwasmParseError;
+use crate::components::module::AwwasmModule;
+use crate::components::names::AwwasmSubsection;
+use crate::components::types::AwwasmName;
+use nom_derive::*;
+use crate::components::leb128::leb128_u32;
+use nom::combinator::all_consuming;
+use nom::multi::length_count;
+
+const DYLINK_SECTION: &[u8] = b"dylink.0";
+
+const MEM_INFO_SUBSECTION: u8 = 1;
+const NEEDED_SUBSECTION: u8 = 2;
+const EXPORT_INFO_SUBSECTION: u8 = 3;
+const IMPORT_INFO_SUBSECTION: u8 = 4;
+const RUNTIME_PATH_SUBSECTION: u8 = 5;
+
+/// Memory and table requirements of a dynamic library.
+///
+/// Alignments are log2 values, as encoded.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmDylinkMemInfo {
+    #[nom(Parse = "leb128_u32")]
+    pub memory_size: u32,
+    #[nom(Parse = "leb128_u32")]
+    pub memory_alignment: u32,
+    #[nom(Parse = "leb128_u32")]
+    pub table_size: u32,
+    #[nom(Parse = "leb128_u32")]
+    pub table_alignment: u32,
+}
+
+/// Symbol flags attached to an export.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmDylinkExportInfo<'a> {
+    pub name: AwwasmName<'a>,
+    #[nom(Parse = "leb128_u32")]
+    pub flags: u32,
+}
+
+/// Symbol flags attached to an import.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmDylinkImportInfo<'a> {
+    pub module: AwwasmName<'a>,
+    pub field: AwwasmName<'a>,
+    #[nom(Parse = "leb128_u32")]
+    pub flags: u32,
+}
+
+/// Decoded contents of the `dylink.0` custom section.
+///
+/// Unknown subsections are skipped.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmDylinkSection<'a> {
+    pub mem_info: Option<AwwasmDylinkMemInfo>,
+    /// Shared libraries this one depends on.
+    pub needed: Vec<AwwasmName<'a>>,
+    pub export_info: Vec<AwwasmDylinkExportInfo<'a>>,
+    pub import_info: Vec<AwwasmDylinkImportInfo<'a>>,
+    /// Search paths for `needed` libraries.
+    pub runtime_path: Vec<AwwasmName<'a>>,
+}
+
+impl<'a> AwwasmDylinkSection<'a> {
+    /// Decode the payload of a `dylink.0` custom section.
+    pub fn parse(payload: &'a [u8]) -> anyhow::Result<Self> {
+        let mut dylink = Self::default();
+        let mut rest = payload;
+        while !rest.is_empty() {
+            let (next, sub) = AwwasmSubsection::parse(rest)
+                .map_err(|e| AwwasmParseError::nom("Dylink Subsection", e))?;
+            let body = sub.body;
+            match sub.id {
+                MEM_INFO_SUBSECTION => {
+                    let (_, info) = all_consuming(AwwasmDylinkMemInfo::parse)(body)
+                        .map_err(|e| AwwasmParseError::nom("Dylink Mem Info", e))?;
+                    dylink.mem_info = Some(info);
+                }
+                NEEDED_SUBSECTION => {
+                    let (_, needed) = all_consuming(length_count(leb128_u32, AwwasmName::parse))(body)
+                        .map_err(|e| AwwasmParseError::nom("Dylink Needed", e))?;
+                    dylink.needed = needed;
+                }
+                EXPORT_INFO_SUBSECTION => {
+                    let (_, info) = all_consuming(length_count(leb128_u32, AwwasmDylinkExportInfo::parse))(body)
+                        .map_err(|e| AwwasmParseError::nom("Dylink Export Info", e))?;
+                    dylink.export_info = info;
+                }
+                IMPORT_INFO_SUBSECTION => {
+                    let (_, info) = all_consuming(length_count(leb128_u32, AwwasmDylinkImportInfo::parse))(body)
+                        .map_err(|e| AwwasmParseError::nom("Dylink Import Info", e))?;
+                    dylink.import_info = info;
+                }
+                RUNTIME_PATH_SUBSECTION => {
+                    let (_, paths) = all_consuming(length_count(leb128_u32, AwwasmName::parse))(body)
+                        .map_err(|e| AwwasmParseError::nom("Dylink Runtime Path", e))?;
+                    dylink.runtime_path = paths;
+                }
+                _ => { /* skip */ }
+            }
+            rest = next;
+        }
+        Ok(dylink)
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Decode the `dylink.0` custom section, if the module has one.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn dylink(&self) -> anyhow::Result<Option<AwwasmDylinkSection<'a>>> {
+        self.custom_section(DYLINK_SECTION)
+            .map(|c| AwwasmDylinkSection::parse(c.payload))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::dylink::AwwasmDylinkMemInfo;
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn decode_dylink_section_test() -> anyhow::Result<()> {
+        // mem_info: 256 bytes of memory (align 2^4), 3 table slots (align 2^0)
+        // needed: ["libc.so"]
+        // export_info: [("f", 0x20)]
+        // import_info: [("env", "g", 0x1)]
+        // subsection 9: unknown, skipped
+        let module = wat::parse_str(r#"
+            (module
+                (@custom "dylink.0" (before first)
+                    "\01\05\80\02\04\03\00"
+                    "\02\09\01\07libc.so"
+                    "\03\04\01\01f\20"
+                    "\04\08\01\03env\01g\01"
+                    "\09\01\00")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let dylink = module_parsed.dylink()?.expect("dylink.0 section should exist");
+        assert_eq!(dylink.mem_info, Some(AwwasmDylinkMemInfo {
+            memory_size: 256,
+            memory_alignment: 4,
+            table_size: 3,
+            table_alignment: 0,
+        }));
+        assert_eq!(dylink.needed.len(), 1);
+        assert_eq!(&dylink.needed[0].bytes[..], b"libc.so");
+        assert_eq!(&dylink.export_info[0].name.bytes[..], b"f");
+        assert_eq!(dylink.export_info[0].flags, 0x20);
+        assert_eq!(&dylink.import_info[0].module.bytes[..], b"env");
+        assert_eq!(&dylink.import_info[0].field.bytes[..], b"g");
+        assert_eq!(dylink.import_info[0].flags, 0x1);
+        assert!(dylink.runtime_path.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_dylink_section_rejects_trailing_bytes_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"(module (@custom "dylink.0" "\01\05\00\00\00\00\00"))"#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        assert!(module_parsed.dylink().is_err());
+        Ok(())
+    }
+}
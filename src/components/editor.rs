@@ -0,0 +1,292 @@
+/// A requested change to a module binary: replace `len` bytes at `offset`
+/// (in the original binary) with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmEdit {
+    pub offset: usize,
+    pub len: usize,
+    pub replacement: Vec<u8>,
+}
+
+/// A minimal byte-level change against the original binary.
+///
+/// `offset` always refers to the original binary, so a list of patches can be
+/// reviewed (or signed) without having to replay earlier patches first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryPatch {
+    pub offset: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+/// Magic at the start of a delta made by `AwwasmEditor::delta()`.
+pub const DELTA_MAGIC: &[u8; 8] = b"AWDELTA1";
+
+/// Produces explicit patch lists for a module binary instead of rewriting it whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmEditor<'a> {
+    pub bytes: &'a [u8],
+}
+
+impl<'a> AwwasmEditor<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    /// Turn a set of edits into the smallest list of `BinaryPatch`es that realizes them.
+    ///
+    /// Bytes an edit leaves unchanged are trimmed away, and same-length edits are
+    /// split into runs of differing bytes. Edits must not overlap.
+    pub fn apply_patches(&self, edits: &[AwwasmEdit]) -> anyhow::Result<Vec<BinaryPatch>> {
+        let mut sorted: Vec<&AwwasmEdit> = edits.iter().collect();
+        sorted.sort_by_key(|e| e.offset);
+
+        let mut patches = Vec::new();
+        let mut prev_end = 0usize;
+        for edit in sorted {
+            let end = edit.offset.checked_add(edit.len)
+                .filter(|end| *end <= self.bytes.len())
+                .ok_or_else(|| anyhow::anyhow!("Edit at offset {} (len {}) is out of bounds", edit.offset, edit.len))?;
+            if edit.offset < prev_end {
+                return Err(anyhow::anyhow!("Edit at offset {} overlaps a previous edit", edit.offset));
+            }
+            prev_end = end;
+            minimal_patches(edit.offset, &self.bytes[edit.offset..end], &edit.replacement, &mut patches);
+        }
+        Ok(patches)
+    }
+
+    /// Rebuild the edited binary from the original bytes and a patch list.
+    pub fn patched(&self, patches: &[BinaryPatch]) -> anyhow::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(self.bytes.len());
+        let mut pos = 0usize;
+        for patch in self.checked_patches(patches)? {
+            out.extend_from_slice(&self.bytes[pos..patch.offset]);
+            out.extend_from_slice(&patch.new);
+            pos = patch.offset + patch.old.len();
+        }
+        out.extend_from_slice(&self.bytes[pos..]);
+        Ok(out)
+    }
+
+    /// Encode a patch list as a bspatch-style delta against the original
+    /// binary, for tools that ship deltas rather than patch lists.
+    ///
+    /// The layout follows bsdiff's, without the compression: `DELTA_MAGIC`,
+    /// then the control, diff and extra block lengths and the new size as
+    /// little-endian `u64`s, then the three blocks. Each control entry is
+    /// three `u64`s `(add, copy, skip)`: add `add` diff bytes to as many
+    /// original bytes, copy `copy` extra bytes, then skip `skip` original
+    /// bytes. Same-length patches become diff bytes, other patches extra
+    /// bytes; the diff block is zero wherever nothing changed.
+    pub fn delta(&self, patches: &[BinaryPatch]) -> anyhow::Result<Vec<u8>> {
+        let (mut ctrl, mut diff, mut extra) = (Vec::new(), Vec::new(), Vec::new());
+        let mut new_size = 0usize;
+        let mut pos = 0usize;
+        for patch in self.checked_patches(patches)? {
+            diff.resize(diff.len() + patch.offset - pos, 0);
+            let (copy, skip) = if patch.old.len() == patch.new.len() {
+                diff.extend(patch.new.iter().zip(&patch.old).map(|(n, o)| n.wrapping_sub(*o)));
+                (0, 0)
+            } else {
+                extra.extend_from_slice(&patch.new);
+                (patch.new.len(), patch.old.len())
+            };
+            let add = patch.offset - pos + (patch.old.len() - skip);
+            for n in [add, copy, skip] {
+                ctrl.extend_from_slice(&(n as u64).to_le_bytes());
+            }
+            new_size += add + copy;
+            pos = patch.offset + patch.old.len();
+        }
+        let rest = self.bytes.len() - pos;
+        diff.resize(diff.len() + rest, 0);
+        for n in [rest, 0, 0] {
+            ctrl.extend_from_slice(&(n as u64).to_le_bytes());
+        }
+        new_size += rest;
+
+        let mut out = DELTA_MAGIC.to_vec();
+        for n in [ctrl.len(), diff.len(), new_size] {
+            out.extend_from_slice(&(n as u64).to_le_bytes());
+        }
+        out.extend(ctrl);
+        out.extend(diff);
+        out.extend(extra);
+        Ok(out)
+    }
+
+    /// Rebuild the edited binary from the original bytes and a delta made
+    /// by `delta()`.
+    pub fn apply_delta(&self, delta: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let truncated = || anyhow::anyhow!("Delta is truncated");
+        let header = delta.strip_prefix(&DELTA_MAGIC[..]).ok_or_else(|| anyhow::anyhow!("Delta does not start with the delta magic"))?;
+        let mut fields = header.chunks_exact(8).map(|c| u64::from_le_bytes(c.try_into().expect("8-byte chunk")) as usize);
+        let (Some(ctrl_len), Some(diff_len), Some(new_size)) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(truncated());
+        };
+        let blocks = &header[24..];
+        let ctrl = blocks.get(..ctrl_len).ok_or_else(truncated)?;
+        let diff = blocks.get(ctrl_len..).and_then(|b| b.get(..diff_len)).ok_or_else(truncated)?;
+        let extra = &blocks[ctrl_len + diff_len..];
+        if ctrl.len() % 24 != 0 {
+            return Err(anyhow::anyhow!("Delta control block is not a whole number of entries"));
+        }
+
+        let mut out = Vec::with_capacity(new_size.min(self.bytes.len() + extra.len()));
+        let (mut old_pos, mut diff_pos, mut extra_pos) = (0usize, 0usize, 0usize);
+        for entry in ctrl.chunks_exact(24) {
+            let mut n = entry.chunks_exact(8).map(|c| u64::from_le_bytes(c.try_into().expect("8-byte chunk")) as usize);
+            let (add, copy, skip) = (n.next().unwrap_or_default(), n.next().unwrap_or_default(), n.next().unwrap_or_default());
+            let old = old_pos.checked_add(add).and_then(|end| self.bytes.get(old_pos..end));
+            let bytes = diff_pos.checked_add(add).and_then(|end| diff.get(diff_pos..end));
+            let (Some(old), Some(bytes)) = (old, bytes) else {
+                return Err(anyhow::anyhow!("Delta adds {} bytes at offset {} past the end of the original", add, old_pos));
+            };
+            out.extend(old.iter().zip(bytes).map(|(o, d)| o.wrapping_add(*d)));
+            let copied = extra_pos.checked_add(copy).and_then(|end| extra.get(extra_pos..end)).ok_or_else(truncated)?;
+            out.extend_from_slice(copied);
+            old_pos = (old_pos + add).checked_add(skip).filter(|end| *end <= self.bytes.len())
+                .ok_or_else(|| anyhow::anyhow!("Delta skips {} bytes at offset {} past the end of the original", skip, old_pos + add))?;
+            diff_pos += add;
+            extra_pos += copy;
+        }
+        if out.len() != new_size {
+            return Err(anyhow::anyhow!("Delta declares {} bytes but produces {}", new_size, out.len()));
+        }
+        Ok(out)
+    }
+
+    // Sort `patches` by offset, checking each lies within the original,
+    // matches its bytes and does not overlap the one before.
+    fn checked_patches<'p>(&self, patches: &'p [BinaryPatch]) -> anyhow::Result<Vec<&'p BinaryPatch>> {
+        let mut sorted: Vec<&BinaryPatch> = patches.iter().collect();
+        sorted.sort_by_key(|p| p.offset);
+
+        let mut prev_end = 0usize;
+        for patch in &sorted {
+            let end = patch.offset.checked_add(patch.old.len())
+                .filter(|end| patch.offset >= prev_end && *end <= self.bytes.len())
+                .ok_or_else(|| anyhow::anyhow!("Patch at offset {} overlaps or is out of bounds", patch.offset))?;
+            if self.bytes[patch.offset..end] != patch.old[..] {
+                return Err(anyhow::anyhow!("Patch at offset {} does not match the original bytes", patch.offset));
+            }
+            prev_end = end;
+        }
+        Ok(sorted)
+    }
+}
+
+// Emit the patches needed to turn `old` (located at `offset`) into `new`.
+fn minimal_patches(offset: usize, old: &[u8], new: &[u8], out: &mut Vec<BinaryPatch>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+    let offset = offset + prefix;
+
+    if old.len() != new.len() {
+        if !old.is_empty() || !new.is_empty() {
+            out.push(BinaryPatch { offset, old: old.to_vec(), new: new.to_vec() });
+        }
+        return;
+    }
+
+    // Same length: only the differing runs need to be recorded.
+    let mut i = 0;
+    while i < old.len() {
+        if old[i] == new[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < old.len() && old[i] != new[i] {
+            i += 1;
+        }
+        out.push(BinaryPatch {
+            offset: offset + start,
+            old: old[start..i].to_vec(),
+            new: new[start..i].to_vec(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::editor::{AwwasmEdit, AwwasmEditor, BinaryPatch, DELTA_MAGIC};
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn apply_patches_trims_unchanged_bytes_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"(module (func (export "abcd")))"#)?;
+        let pos = module.windows(4).position(|w| w == b"abcd").expect("export name");
+        let editor = AwwasmEditor::new(&module);
+
+        // Rewrite the whole name, but only the middle two bytes actually change.
+        let patches = editor.apply_patches(&[AwwasmEdit { offset: pos, len: 4, replacement: b"axyd".to_vec() }])?;
+        assert_eq!(patches, vec![BinaryPatch { offset: pos + 1, old: b"bc".to_vec(), new: b"xy".to_vec() }]);
+
+        let patched = editor.patched(&patches)?;
+        let mut module_parsed = AwwasmModule::new(&patched)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(&module_parsed.exports.as_ref().expect("exports")[0].name.bytes[..], b"axyd");
+        Ok(())
+    }
+
+    #[test]
+    fn apply_patches_splits_same_length_runs_test() -> anyhow::Result<()> {
+        let bytes = [0u8, 1, 2, 3, 4, 5];
+        let editor = AwwasmEditor::new(&bytes);
+        let patches = editor.apply_patches(&[AwwasmEdit { offset: 0, len: 6, replacement: vec![9, 1, 2, 3, 4, 9] }])?;
+        assert_eq!(patches, vec![
+            BinaryPatch { offset: 0, old: vec![0], new: vec![9] },
+            BinaryPatch { offset: 5, old: vec![5], new: vec![9] },
+        ]);
+        assert_eq!(editor.patched(&patches)?, vec![9, 1, 2, 3, 4, 9]);
+        Ok(())
+    }
+
+    #[test]
+    fn apply_patches_rejects_overlapping_edits_test() {
+        let bytes = [0u8; 8];
+        let editor = AwwasmEditor::new(&bytes);
+        let res = editor.apply_patches(&[
+            AwwasmEdit { offset: 0, len: 4, replacement: vec![1; 4] },
+            AwwasmEdit { offset: 2, len: 4, replacement: vec![1; 4] },
+        ]);
+        assert!(res.is_err());
+        assert!(editor.apply_patches(&[AwwasmEdit { offset: 6, len: 4, replacement: vec![] }]).is_err());
+    }
+
+    #[test]
+    fn patched_rejects_out_of_bounds_patches_test() {
+        let bytes = [0u8; 8];
+        let editor = AwwasmEditor::new(&bytes);
+        assert!(editor.patched(&[BinaryPatch { offset: usize::MAX, old: vec![0], new: vec![1] }]).is_err());
+        assert!(editor.patched(&[BinaryPatch { offset: 6, old: vec![0; 4], new: vec![] }]).is_err());
+        assert!(editor.delta(&[BinaryPatch { offset: usize::MAX, old: vec![0], new: vec![1] }]).is_err());
+    }
+
+    #[test]
+    fn delta_round_trip_test() -> anyhow::Result<()> {
+        let bytes: Vec<u8> = (0..32).collect();
+        let editor = AwwasmEditor::new(&bytes);
+        let patches = editor.apply_patches(&[
+            AwwasmEdit { offset: 2, len: 2, replacement: vec![0xff, 0x03] },
+            AwwasmEdit { offset: 10, len: 4, replacement: vec![7] },
+            AwwasmEdit { offset: 20, len: 0, replacement: vec![1, 2, 3] },
+        ])?;
+        let delta = editor.delta(&patches)?;
+        assert_eq!(&delta[..8], DELTA_MAGIC);
+        assert_eq!(editor.apply_delta(&delta)?, editor.patched(&patches)?);
+        assert_eq!(editor.apply_delta(&editor.delta(&[])?)?, bytes);
+
+        // The original must be the one the delta was made against.
+        assert!(AwwasmEditor::new(&bytes[..16]).apply_delta(&delta).is_err());
+        assert!(editor.apply_delta(&delta[..delta.len() - 1]).is_err());
+        assert!(editor.apply_delta(&delta[4..]).is_err());
+        Ok(())
+    }
+}
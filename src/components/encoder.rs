@@ -0,0 +1,423 @@
+use crate::consts::WASM_MAGIC_NUMBER;
+use crate::components::instructions::span_in;
+use crate::components::module::AwwasmModule;
+use crate::components::section::{write_leb128_u32, AwwasmSection, SectionCode};
+use crate::components::types::*;
+
+// Append an item's binary encoding to `out`.
+pub(crate) trait Encode {
+    fn write(&self, out: &mut Vec<u8>);
+}
+
+fn write_vec<T: Encode>(out: &mut Vec<u8>, items: &[T]) {
+    write_leb128_u32(out, items.len() as u32);
+    items.iter().for_each(|item| item.write(out));
+}
+
+fn write_indices(out: &mut Vec<u8>, indices: &[u32]) {
+    write_leb128_u32(out, indices.len() as u32);
+    indices.iter().for_each(|&idx| write_leb128_u32(out, idx));
+}
+
+fn write_val_types(out: &mut Vec<u8>, types: &[ValType]) {
+    write_leb128_u32(out, types.len() as u32);
+    types.iter().for_each(|&t| t.write(out));
+}
+
+impl Encode for AwwasmName<'_> {
+    fn write(&self, out: &mut Vec<u8>) {
+        write_leb128_u32(out, self.bytes.len() as u32);
+        out.extend_from_slice(&self.bytes);
+    }
+}
+
+impl Encode for AwwasmDataInitExpr<'_> {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.code);
+        out.push(self.end);
+    }
+}
+
+impl Encode for AwwasmMemoryParams {
+    fn write(&self, out: &mut Vec<u8>) {
+        write_leb128_u32(out, self.flags);
+        write_leb128_u32(out, self.min);
+        self.max.into_iter().chain(self.page_size_log2).for_each(|v| write_leb128_u32(out, v));
+    }
+}
+
+impl Encode for AwwasmTypeSectionItem<'_> {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.type_magic);
+        write_val_types(out, &self.fn_args);
+        write_val_types(out, &self.fn_rets);
+    }
+}
+
+impl Encode for AwwasmFuncSectionItem {
+    fn write(&self, out: &mut Vec<u8>) {
+        write_leb128_u32(out, self.type_item_idx);
+    }
+}
+
+impl Encode for AwwasmTableSectionItem {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(self.elem_type.clone() as u8);
+        self.limits.write(out);
+    }
+}
+
+impl Encode for AwwasmMemorySectionItem {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.limits.write(out);
+    }
+}
+
+impl Encode for AwwasmTagSectionItem {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(self.attribute);
+        write_leb128_u32(out, self.type_idx);
+    }
+}
+
+impl Encode for AwwasmGlobalType {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.value_type.write(out);
+        out.push(self.mutability.clone() as u8);
+    }
+}
+
+impl Encode for AwwasmGlobalSectionItem<'_> {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.value_type.write(out);
+        out.push(self.mutability.clone() as u8);
+        self.init_expr.write(out);
+    }
+}
+
+impl Encode for AwwasmImportSectionItem<'_> {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.module.write(out);
+        self.name.write(out);
+        out.push(self.kind.clone() as u8);
+        if let Some(idx) = self.func_type_idx {
+            write_leb128_u32(out, idx);
+        }
+        if let Some(table) = &self.table {
+            table.write(out);
+        }
+        if let Some(mem) = &self.mem {
+            mem.write(out);
+        }
+        if let Some(global) = &self.global {
+            global.write(out);
+        }
+        if let Some(tag) = &self.tag {
+            tag.write(out);
+        }
+    }
+}
+
+impl Encode for AwwasmExportSectionItem<'_> {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.name.write(out);
+        out.push(self.kind.clone() as u8);
+        write_leb128_u32(out, self.index);
+    }
+}
+
+impl Encode for AwwasmElementSectionItem<'_> {
+    fn write(&self, out: &mut Vec<u8>) {
+        write_leb128_u32(out, self.flags);
+        match &self.body {
+            AwwasmElemSegmentBody::ActiveImplicit(s) => {
+                s.offset.write(out);
+                write_indices(out, &s.func_indices);
+            }
+            AwwasmElemSegmentBody::Passive(s) => {
+                out.push(s.elemkind.clone() as u8);
+                write_indices(out, &s.func_indices);
+            }
+            AwwasmElemSegmentBody::ActiveExplicit(s) => {
+                write_leb128_u32(out, s.tableidx);
+                s.offset.write(out);
+                out.push(s.elemkind.clone() as u8);
+                write_indices(out, &s.func_indices);
+            }
+            AwwasmElemSegmentBody::Declarative(s) => {
+                out.push(s.elemkind.clone() as u8);
+                write_indices(out, &s.func_indices);
+            }
+            AwwasmElemSegmentBody::ActiveImplicitExprs(s) => {
+                s.offset.write(out);
+                write_vec(out, &s.exprs);
+            }
+            AwwasmElemSegmentBody::PassiveExprs(s) => {
+                out.push(s.reftype.clone() as u8);
+                write_vec(out, &s.exprs);
+            }
+            AwwasmElemSegmentBody::ActiveExplicitExprs(s) => {
+                write_leb128_u32(out, s.tableidx);
+                s.offset.write(out);
+                out.push(s.reftype.clone() as u8);
+                write_vec(out, &s.exprs);
+            }
+            AwwasmElemSegmentBody::DeclarativeExprs(s) => {
+                out.push(s.reftype.clone() as u8);
+                write_vec(out, &s.exprs);
+            }
+        }
+    }
+}
+
+impl Encode for AwwasmFunctionLocals {
+    fn write(&self, out: &mut Vec<u8>) {
+        write_leb128_u32(out, self.type_count);
+        self.param_type.write(out);
+    }
+}
+
+impl Encode for AwwasmCodeSectionItem<'_> {
+    fn write(&self, out: &mut Vec<u8>) {
+        let body = match &self.parsed_func {
+            // `resolve()` moved the locals and the code up to the first
+            // `end` byte out of `func_body`.
+            Some(func) => {
+                let mut body = Vec::new();
+                write_vec(&mut body, &func.fn_rets);
+                body.extend_from_slice(func.code);
+                body.extend_from_slice(self.func_body);
+                body
+            }
+            None => self.func_body.to_vec(),
+        };
+        write_leb128_u32(out, body.len() as u32);
+        out.extend_from_slice(&body);
+    }
+}
+
+impl Encode for AwwasmDataSectionItem<'_> {
+    fn write(&self, out: &mut Vec<u8>) {
+        write_leb128_u32(out, self.header.flags);
+        if let Some(memidx) = self.header.memidx {
+            write_leb128_u32(out, memidx);
+        }
+        if let Some(offset) = &self.header.offset {
+            offset.write(out);
+        }
+        write_leb128_u32(out, self.data_bytes.len() as u32);
+        out.extend_from_slice(self.data_bytes);
+    }
+}
+
+impl Encode for AwwasmCustomSectionItem<'_> {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.name.write(out);
+        out.extend_from_slice(self.payload);
+    }
+}
+
+// Non-custom sections, in the order a valid module lists them.
+const SECTION_ORDER: [SectionCode; 13] = [
+    SectionCode::Type, SectionCode::Import, SectionCode::Function, SectionCode::Table, SectionCode::Memory,
+    SectionCode::Tag, SectionCode::Global, SectionCode::Export, SectionCode::Start, SectionCode::Element,
+    SectionCode::DataCount, SectionCode::Code, SectionCode::Data,
+];
+
+fn write_section(out: &mut Vec<u8>, code: &SectionCode, payload: &[u8]) {
+    out.push(code.clone() as u8);
+    write_leb128_u32(out, payload.len() as u32);
+    out.extend_from_slice(payload);
+}
+
+fn write_custom(out: &mut Vec<u8>, custom: &AwwasmCustomSectionItem) {
+    let mut payload = Vec::new();
+    custom.write(&mut payload);
+    write_section(out, &SectionCode::Custom, &payload);
+}
+
+impl AwwasmModule<'_> {
+    /// The payload of the non-custom section `code`, encoded from its
+    /// resolved field; `None` if that field is not set.
+    fn encode_payload(&self, code: &SectionCode) -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+        match code {
+            SectionCode::Type => write_vec(&mut out, self.types.as_ref()?),
+            SectionCode::Import => write_vec(&mut out, self.imports.as_ref()?),
+            SectionCode::Function => write_vec(&mut out, self.funcs.as_ref()?),
+            SectionCode::Table => write_vec(&mut out, self.tables.as_ref()?),
+            SectionCode::Memory => write_vec(&mut out, self.memories.as_ref()?),
+            SectionCode::Tag => write_vec(&mut out, self.tags.as_ref()?),
+            SectionCode::Global => write_vec(&mut out, self.globals.as_ref()?),
+            SectionCode::Export => write_vec(&mut out, self.exports.as_ref()?),
+            SectionCode::Start => write_leb128_u32(&mut out, self.start.as_ref()?.func_idx),
+            SectionCode::Element => write_vec(&mut out, self.elements.as_ref()?),
+            SectionCode::DataCount => write_leb128_u32(&mut out, self.data_count?),
+            SectionCode::Code => write_vec(&mut out, self.code.as_ref()?),
+            SectionCode::Data => write_vec(&mut out, self.data.as_ref()?),
+            SectionCode::Custom => return None,
+        }
+        Some(out)
+    }
+
+    /// Encode the module as a binary.
+    ///
+    /// Each section with a resolved field is encoded from that field, so
+    /// edits to the resolved items are carried into the output; sections
+    /// that were not resolved are copied from the input as they are. A
+    /// resolved field without a section in the input gets one, in its place
+    /// in the section order. Once `customs` is resolved, each custom section
+    /// in it keeps the position of the section it was parsed from, and the
+    /// ones not parsed from the input go last.
+    ///
+    /// An unmodified module whose LEB128 fields are minimally encoded, as
+    /// toolchains emit them outside of relocatable object files, encodes to
+    /// the same bytes it was parsed from.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(WASM_MAGIC_NUMBER);
+        out.extend_from_slice(&self.preamble.version.to_le_bytes());
+
+        let sections: &[AwwasmSection] = self.sections();
+        // Resolved sections the input lacks, in section order.
+        let mut added = SECTION_ORDER.iter()
+            .filter(|code| !sections.iter().any(|sec| sec.section_header.section_type == **code))
+            .filter_map(|code| Some((code, self.encode_payload(code)?)))
+            .peekable();
+        // Which input section a resolved custom section was parsed from, if any.
+        let parsed_from = |custom: &AwwasmCustomSectionItem| sections.iter()
+            .position(|sec| sec.section_header.section_type == SectionCode::Custom && span_in(sec.encoding, &custom.name.bytes).is_some());
+
+        for (i, sec) in sections.iter().enumerate() {
+            let code = &sec.section_header.section_type;
+            if let Some(order) = code.order() {
+                while let Some((code, payload)) = added.next_if(|(added, _)| added.order() < Some(order)) {
+                    write_section(&mut out, code, &payload);
+                }
+            }
+            match code {
+                // Custom sections dropped from `customs` are left out.
+                SectionCode::Custom if self.customs.is_some() => {
+                    for custom in self.customs().iter().filter(|custom| parsed_from(custom) == Some(i)) {
+                        write_custom(&mut out, custom);
+                    }
+                }
+                _ => {
+                    // Resolved fields hold the last section of each kind.
+                    let last = !sections[i + 1..].iter().any(|later| later.section_header.section_type == *code);
+                    match self.encode_payload(code).filter(|_| last) {
+                        Some(payload) => write_section(&mut out, code, &payload),
+                        None => out.extend_from_slice(sec.encoding),
+                    }
+                }
+            }
+        }
+        for (code, payload) in added {
+            write_section(&mut out, code, &payload);
+        }
+        for custom in self.customs().iter().filter(|custom| parsed_from(custom).is_none()) {
+            write_custom(&mut out, custom);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::fixtures;
+    use crate::components::module::AwwasmModule;
+    use crate::components::section::SectionCode;
+    use crate::components::types::{AwwasmCustomSectionItem, AwwasmName, AwwasmStartSectionItem};
+
+    #[test]
+    fn encode_round_trip_test() -> anyhow::Result<()> {
+        let wat = wat::parse_str(r#"
+            (module
+                (type (func (param i32 i64) (result f32)))
+                (import "env" "f" (func (type 0)))
+                (import "env" "t" (table 1 funcref))
+                (import "env" "m" (memory 1 2))
+                (import "env" "g" (global (mut i32)))
+                (tag (param i32))
+                (table $t 2 10 externref)
+                (memory 1)
+                (global $g (mut i64) (i64.const -1))
+                (global f64 (f64.const 1.5))
+                (func $run (export "run") (param i32) (result i32) (local i64 i64 f32)
+                    (block (br_if 0 (local.get 0)))
+                    (i32.add (local.get 0) (i32.const 1)))
+                (func $other)
+                (start $other)
+                (elem (i32.const 0) func $run)
+                (elem func $other)
+                (elem declare funcref (ref.func $run))
+                (elem (table $t) (i32.const 1) externref (ref.null extern))
+                (data (i32.const 16) "hello")
+                (data "passive")
+                (@custom "first" (before type) "a")
+                (@custom "last" "bc"))
+        "#)?;
+        let inputs = fixtures::VALID.iter().map(|(_, bytes)| bytes.to_vec()).chain([wat]);
+        for bytes in inputs {
+            let mut module = AwwasmModule::new(&bytes)?;
+            assert_eq!(module.encode(), bytes);
+            module.resolve_all_sections()?;
+            assert_eq!(module.encode(), bytes);
+            for item in module.code.iter_mut().flatten() {
+                item.resolve()?;
+            }
+            assert_eq!(module.encode(), bytes);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn encode_edited_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"(module (func (export "run")) (@custom "note" "x"))"#)?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        module.resolve_all_sections()?;
+        module.exports.as_mut().expect("exports should exist")[0].name = AwwasmName::new("main");
+        module.start = Some(AwwasmStartSectionItem { func_idx: 0 });
+
+        let encoded = module.encode();
+        let mut edited = AwwasmModule::new(&encoded)?;
+        edited.resolve_all_sections()?;
+        assert!(edited.section_order_issues().is_empty());
+        assert_eq!(edited.exports()[0].name.as_str()?, "main");
+        assert_eq!(edited.start, Some(AwwasmStartSectionItem { func_idx: 0 }));
+        assert_eq!(edited.customs()[0].name.as_str()?, "note");
+        assert!(edited.validate().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn encode_removed_custom_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (@custom "a" (before first) "1")
+                (type (func))
+                (@custom "b" (after type) "2")
+                (func (type 0))
+                (@custom "c" (after func) "3")
+                (@custom "d" "4"))
+        "#)?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        module.resolve_all_sections()?;
+        // Each custom section's one-letter name (after the id, size and name
+        // length bytes), `None` for the others.
+        let order = |module: &AwwasmModule| -> Vec<Option<u8>> {
+            module.sections().iter().map(|sec| match sec.section_header.section_type {
+                SectionCode::Custom => sec.encoding.get(3).copied(),
+                _ => None,
+            }).collect()
+        };
+        assert_eq!(order(&module), [Some(b'a'), None, Some(b'b'), None, Some(b'c'), None, Some(b'd')]);
+
+        module.customs.as_mut().expect("customs should exist").remove(1);
+        module.customs.as_mut().expect("customs should exist").push(AwwasmCustomSectionItem { name: AwwasmName::new("e"), payload: b"5" });
+        let encoded = module.encode();
+        let edited = AwwasmModule::new(&encoded)?;
+        assert_eq!(order(&edited), [Some(b'a'), None, None, Some(b'c'), None, Some(b'd'), Some(b'e')]);
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+use crate::components::instructions::{DECODE_CANCELLED, DEFAULT_MAX_NESTING_DEPTH, NESTING_TOO_DEEP, TOO_MANY_INSTRUCTIONS};
+use crate::components::leb128::AwwasmLeb128Issue;
+use crate::components::module::AwwasmModule;
+use crate::components::parse_limits::AwwasmParseLimits;
+use crate::components::section::{SectionCode, SectionOrderError, TruncatedSection};
+use nom::error::ErrorKind;
+use std::fmt::Write;
+
+/// What went wrong in an `AwwasmParseError`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AwwasmParseErrorKind {
+    /// The input ended in the middle of the construct.
+    #[error("unexpected end of input")]
+    Truncated,
+    /// The binary does not start with `\0asm`.
+    #[error("magic number not detected")]
+    BadMagic,
+    /// The version field is not one this crate decodes.
+    #[error("unsupported version {version:#x}")]
+    UnsupportedVersion { version: u32 },
+    /// A section id outside the binary format.
+    #[error("unknown section id {id:#04x}")]
+    UnknownSection { id: u8 },
+    /// An opcode the decoder does not know. For prefixed instructions this
+    /// is the byte after the prefix if the prefix itself is known.
+    #[error("unknown opcode {opcode:#04x}")]
+    UnknownOpcode { opcode: u8 },
+    /// A LEB128 integer longer than its type allows.
+    #[error("LEB128 integer is too large")]
+    BadLeb128,
+    /// Any other encoding error, described by `detail`.
+    #[error("{detail}")]
+    Malformed { detail: String },
+    /// A section header declares more bytes than the input has left.
+    #[error("{0}")]
+    TruncatedSection(TruncatedSection),
+    /// A non-custom section repeats or is out of order, under
+    /// `AwwasmSectionOrderMode::Strict`.
+    #[error("{0}")]
+    SectionOrder(SectionOrderError),
+    /// A LEB128 integer is not in canonical form, under
+    /// `AwwasmParseOptions::canonical_leb128`.
+    #[error("{0}")]
+    NonCanonicalLeb128(AwwasmLeb128Issue),
+    /// The module is over a cap in `AwwasmParseLimits`. `limit` names the
+    /// cap, e.g. `"section entries"`; `section` is set for per-section caps.
+    #[error("module exceeds the {limit} limit{}: {actual} > {max}", in_section(.section))]
+    LimitExceeded { limit: &'static str, section: Option<SectionCode>, actual: u64, max: u64 },
+    /// The module uses proposals `AwwasmParseOptions` does not enable,
+    /// named as in `AwwasmFeatures::names()`.
+    #[error("module uses features that are not enabled: {}", .features.join(", "))]
+    UnsupportedFeatures { features: Vec<&'static str> },
+    /// The parse was stopped by its `CancellationToken`.
+    #[error("parsing was cancelled")]
+    Cancelled,
+}
+
+// `limit` exceeded by one, for caps checked as the limited thing is decoded.
+pub(crate) fn limit_exceeded(limit: &'static str, max: u32) -> AwwasmParseErrorKind {
+    AwwasmParseErrorKind::LimitExceeded { limit, section: None, actual: max as u64 + 1, max: max as u64 }
+}
+
+fn in_section(section: &Option<SectionCode>) -> String {
+    section.as_ref().map(|s| format!(" in the {:?} section", s)).unwrap_or_default()
+}
+
+/// Why a binary, or a part of one, failed to decode, and where.
+///
+/// Parse failures, including those from `AwwasmParseOptions` checks and
+/// cancellation, are surfaced through `anyhow::Error`; use
+/// `err.downcast_ref::<AwwasmParseError>()` to branch on `kind`.
+///
+/// Errors from module-level entry points (`AwwasmModule::new()`,
+/// `resolve_all_sections()`, `instruction_index()`) carry their location.
+/// Those from item-level decoders such as
+/// `AwwasmCodeSectionItem::instructions()` do not know their module; pass
+/// them through `AwwasmModule::locate_error()` to fill it in.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Failed to parse WASM {what}{}: {kind}", location(.offset, .section, .func_idx))]
+pub struct AwwasmParseError {
+    pub kind: AwwasmParseErrorKind,
+    /// The construct being decoded, e.g. `"Type Section"` or `"Function body"`.
+    pub what: String,
+    /// Offset in the module binary of the byte decoding stopped at.
+    pub offset: Option<usize>,
+    /// The section being decoded.
+    pub section: Option<SectionCode>,
+    /// The function whose body was being decoded, imports included in the
+    /// index space.
+    pub func_idx: Option<u32>,
+    // Address of the byte decoding stopped at, until `offset` is known.
+    at: Option<usize>,
+}
+
+fn location(offset: &Option<usize>, section: &Option<SectionCode>, func_idx: &Option<u32>) -> String {
+    let mut out = offset.map(|o| format!(" at offset {:#x}", o)).unwrap_or_default();
+    match (section, func_idx) {
+        (Some(section), Some(func_idx)) => out += &format!(" ({:?} section, function {})", section, func_idx),
+        (Some(section), None) => out += &format!(" ({:?} section)", section),
+        (None, Some(func_idx)) => out += &format!(" (function {})", func_idx),
+        (None, None) => {}
+    }
+    out
+}
+
+type NomError<'a> = nom::Err<nom::error::Error<&'a [u8]>>;
+
+impl AwwasmParseError {
+    /// An error of `kind` decoding `what`, with no location yet.
+    pub fn new(what: impl Into<String>, kind: AwwasmParseErrorKind) -> Self {
+        AwwasmParseError { kind, what: what.into(), offset: None, section: None, func_idx: None, at: None }
+    }
+
+    // Classify a nom failure; an unknown enum value (`ErrorKind::Switch`)
+    // becomes `switch(byte)`, or `Malformed` if `switch` is `None`.
+    fn classify(what: String, err: NomError, switch: Option<fn(u8) -> AwwasmParseErrorKind>) -> Self {
+        let e = match err {
+            nom::Err::Incomplete(_) => return Self::new(what, AwwasmParseErrorKind::Truncated),
+            nom::Err::Error(ref e) | nom::Err::Failure(ref e) => e,
+        };
+        let kind = match (e.code, e.input.first(), switch) {
+            (ErrorKind::Eof | ErrorKind::Complete, _, _) => AwwasmParseErrorKind::Truncated,
+            (ErrorKind::TooLarge, _, _) => AwwasmParseErrorKind::BadLeb128,
+            (NESTING_TOO_DEEP, _, _) => limit_exceeded("nesting depth", DEFAULT_MAX_NESTING_DEPTH),
+            (DECODE_CANCELLED, _, _) => AwwasmParseErrorKind::Cancelled,
+            (ErrorKind::Tag, _, _) => AwwasmParseErrorKind::BadMagic,
+            (ErrorKind::Switch, Some(&byte), Some(switch)) => switch(byte),
+            _ => AwwasmParseErrorKind::Malformed { detail: err.to_string() },
+        };
+        AwwasmParseError { at: Some(e.input.as_ptr() as usize), ..Self::new(what, kind) }
+    }
+
+    /// A failure decoding `what`.
+    pub(crate) fn nom(what: impl Into<String>, err: NomError) -> Self {
+        Self::classify(what.into(), err, None)
+    }
+
+    /// A failure decoding a section header, where an unknown value is the
+    /// section id.
+    pub(crate) fn section_header(what: impl Into<String>, err: NomError) -> Self {
+        Self::classify(what.into(), err, Some(|id| AwwasmParseErrorKind::UnknownSection { id }))
+    }
+
+    /// A failure decoding instructions, where an unknown value is an opcode.
+    pub(crate) fn instruction(what: impl Into<String>, err: NomError) -> Self {
+        Self::classify(what.into(), err, Some(|opcode| AwwasmParseErrorKind::UnknownOpcode { opcode }))
+    }
+
+    /// The module-level failure `kind`, such as `Cancelled`, which has no
+    /// position in the binary.
+    pub(crate) fn module(kind: AwwasmParseErrorKind) -> Self {
+        Self::new("module", kind)
+    }
+
+    /// Like `instruction`, for instructions decoded under `limits`.
+    pub(crate) fn instruction_with_limits(what: impl Into<String>, err: NomError, limits: &AwwasmParseLimits) -> Self {
+        let code = match &err {
+            nom::Err::Error(e) | nom::Err::Failure(e) => Some(e.code),
+            nom::Err::Incomplete(_) => None,
+        };
+        let mut error = Self::instruction(what, err);
+        match code {
+            Some(NESTING_TOO_DEEP) => error.kind = limit_exceeded("nesting depth", limits.max_nesting_depth),
+            Some(TOO_MANY_INSTRUCTIONS) => error.kind = limit_exceeded("function instructions", limits.max_function_instructions),
+            _ => {}
+        }
+        error
+    }
+
+    /// Pin an error with no position, such as running out of input, to the
+    /// end of `input`, the bytes that were being decoded.
+    pub(crate) fn within(mut self, input: &[u8]) -> Self {
+        if self.at.is_none() && self.kind == AwwasmParseErrorKind::Truncated {
+            self.at = Some(input.as_ptr_range().end as usize);
+        }
+        self
+    }
+
+    /// Fill in whatever of the location is not known yet, given the address
+    /// the module binary starts at.
+    pub(crate) fn locate(&mut self, start: usize, section: Option<&SectionCode>, func_idx: Option<u32>) {
+        if self.offset.is_none() {
+            self.offset = self.at.and_then(|at| at.checked_sub(start));
+        }
+        if self.section.is_none() {
+            self.section = section.cloned();
+        }
+        if self.func_idx.is_none() {
+            self.func_idx = func_idx;
+        }
+    }
+}
+
+impl From<TruncatedSection> for AwwasmParseError {
+    fn from(error: TruncatedSection) -> Self {
+        AwwasmParseError {
+            section: Some(error.section.clone()),
+            ..Self::new(format!("{:?} Section", error.section), AwwasmParseErrorKind::TruncatedSection(error))
+        }
+    }
+}
+
+impl From<SectionOrderError> for AwwasmParseError {
+    fn from(error: SectionOrderError) -> Self {
+        AwwasmParseError { section: Some(error.section.clone()), ..Self::module(AwwasmParseErrorKind::SectionOrder(error)) }
+    }
+}
+
+impl From<AwwasmLeb128Issue> for AwwasmParseError {
+    fn from(issue: AwwasmLeb128Issue) -> Self {
+        AwwasmParseError { offset: Some(issue.offset), ..Self::module(AwwasmParseErrorKind::NonCanonicalLeb128(issue)) }
+    }
+}
+
+// Call `AwwasmParseError::locate` on `err` if it is one.
+pub(crate) fn locate(mut err: anyhow::Error, start: usize, section: Option<&SectionCode>, func_idx: Option<u32>) -> anyhow::Error {
+    if let Some(e) = err.downcast_mut::<AwwasmParseError>() {
+        e.locate(start, section, func_idx);
+    }
+    err
+}
+
+impl AwwasmModule<'_> {
+    /// Fill in the location of an `AwwasmParseError` from an item-level
+    /// decoder: its offset in this module and, for a failure inside a
+    /// function body, the code section and function index. Other errors
+    /// are returned unchanged.
+    pub fn locate_error(&self, err: anyhow::Error) -> anyhow::Error {
+        let Some(start) = self.start_address() else { return err };
+        let Some(at) = err.downcast_ref::<AwwasmParseError>().and_then(|e| e.at) else { return err };
+        let body = self.code().iter().position(|item| {
+            let range = item.func_body.as_ptr_range();
+            (range.start as usize..=range.end as usize).contains(&at)
+        });
+        match body {
+            Some(i) => locate(err, start, Some(&SectionCode::Code), Some(self.imported_func_count() + i as u32)),
+            None => locate(err, start, None, None),
+        }
+    }
+}
+
+// Bytes per hexdump row, and rows shown either side of the failing one.
+const ROW_BYTES: usize = 16;
+const CONTEXT_ROWS: usize = 2;
+
+/// Render `err` for a terminal: the message, then a hexdump of `module`, the
+/// binary it came from, around the failing byte with that byte marked.
+///
+/// ```text
+/// error: Failed to parse WASM Function body at offset 0x21 (Code section, function 1): unknown opcode 0x30
+///   --> offset 0x21
+/// 00000000: 00 61 73 6d 01 00 00 00 01 04 01 60 00 00 02 07  |.asm.......`....|
+/// 00000010: 01 01 6d 01 66 00 00 03 02 01 00 0a 06 01 04 00  |..m.f...........|
+/// 00000020: fc 30 0b                                         |.0.|
+///              ^^ unknown opcode 0x30
+/// ```
+///
+/// An error with no offset, or one past the end of `module`, is rendered
+/// without the hexdump.
+pub fn render_diagnostic(err: &AwwasmParseError, module: &[u8]) -> String {
+    let mut out = format!("error: {}\n", err);
+    let Some(offset) = err.offset.filter(|&o| o <= module.len()) else { return out };
+    let _ = writeln!(out, "  --> offset {:#x}", offset);
+    let row = offset / ROW_BYTES;
+    let first = row.saturating_sub(CONTEXT_ROWS);
+    let last = (row + CONTEXT_ROWS).min(module.len().saturating_sub(1) / ROW_BYTES).max(row);
+    for r in first..=last {
+        let start = r * ROW_BYTES;
+        let bytes = &module[start.min(module.len())..(start + ROW_BYTES).min(module.len())];
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = bytes.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+        let _ = writeln!(out, "{:08x}: {:<width$}  |{}|", start, hex.join(" "), ascii, width = ROW_BYTES * 3 - 1);
+        if r == row {
+            // Past the last byte, the marker points at where the next would be.
+            let _ = writeln!(out, "{:indent$}^^ {}", "", err.kind, indent = 10 + (offset - start) * 3);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::error::{render_diagnostic, AwwasmParseError, AwwasmParseErrorKind};
+    use crate::components::module::AwwasmModule;
+    use crate::components::section::SectionCode;
+
+    fn parse_error(input: &[u8]) -> AwwasmParseError {
+        let err = AwwasmModule::new(input)
+            .and_then(|mut m| {
+                m.resolve_all_sections()?;
+                m.instruction_index().map(drop)
+            })
+            .unwrap_err();
+        err.downcast_ref::<AwwasmParseError>().cloned().unwrap_or_else(|| panic!("not an AwwasmParseError: {}", err))
+    }
+
+    #[test]
+    fn parse_error_kinds_test() {
+        let kind = |input: &[u8]| parse_error(input).kind;
+        assert_eq!(kind(b"\0asn\x01\0\0\0"), AwwasmParseErrorKind::BadMagic);
+        assert_eq!(kind(b"\0as"), AwwasmParseErrorKind::Truncated);
+        assert_eq!(kind(b"\0asm\x02\0\0\0"), AwwasmParseErrorKind::UnsupportedVersion { version: 2 });
+        assert_eq!(kind(b"\0asm\x01\0\0\0\x0e\x00"), AwwasmParseErrorKind::UnknownSection { id: 0x0e });
+        assert_eq!(kind(b"\0asm\x01\0\0\0\x01\x80\x80\x80\x80\x80\x00"), AwwasmParseErrorKind::BadLeb128);
+
+        let err = parse_error(b"\0asm\x01\0\0\0\x01\x02\x01\x60");
+        assert_eq!((err.kind, err.what.as_str()), (AwwasmParseErrorKind::Truncated, "Type Section"));
+    }
+
+    #[test]
+    fn parse_error_location_test() -> anyhow::Result<()> {
+        // Two functions, one imported; the defined one's body is `0xfc 0x30`,
+        // an unassigned 0xfc sub-opcode, whose 0x30 is at offset 0x21.
+        let module = b"\0asm\x01\0\0\0\x01\x04\x01\x60\x00\x00\x02\x07\x01\x01m\x01f\x00\x00\x03\x02\x01\x00\x0a\x06\x01\x04\x00\xfc\x30\x0b";
+        let err = parse_error(module);
+        assert_eq!(err.kind, AwwasmParseErrorKind::UnknownOpcode { opcode: 0x30 });
+        assert_eq!((err.offset, err.section.clone(), err.func_idx), (Some(0x21), Some(SectionCode::Code), Some(1)));
+        assert_eq!(err.to_string(), "Failed to parse WASM Function body at offset 0x21 (Code section, function 1): unknown opcode 0x30");
+
+        // Item-level decoders leave the location to `locate_error()`.
+        let mut parsed = AwwasmModule::new(module)?;
+        parsed.resolve_all_sections()?;
+        let err = parsed.code.as_ref().unwrap()[0].instructions().unwrap_err();
+        assert_eq!(err.downcast_ref::<AwwasmParseError>().and_then(|e| e.offset), None);
+        let err = parsed.locate_error(err);
+        assert_eq!(err.downcast_ref::<AwwasmParseError>().and_then(|e| e.func_idx), Some(1));
+
+        // A truncated global init expression, pinned to the end of the section.
+        let err = parse_error(b"\0asm\x01\0\0\0\x06\x04\x01\x7f\x00\x41");
+        assert_eq!(err.kind, AwwasmParseErrorKind::Truncated);
+        assert_eq!((err.offset, err.section), (Some(0xe), Some(SectionCode::Global)));
+        Ok(())
+    }
+
+    #[test]
+    fn render_diagnostic_test() {
+        let module = b"\0asm\x01\0\0\0\x01\x04\x01\x60\x00\x00\x02\x07\x01\x01m\x01f\x00\x00\x03\x02\x01\x00\x0a\x06\x01\x04\x00\xfc\x30\x0b";
+        let err = parse_error(module);
+        assert_eq!(render_diagnostic(&err, module), [
+            "error: Failed to parse WASM Function body at offset 0x21 (Code section, function 1): unknown opcode 0x30",
+            "  --> offset 0x21",
+            "00000000: 00 61 73 6d 01 00 00 00 01 04 01 60 00 00 02 07  |.asm.......`....|",
+            "00000010: 01 01 6d 01 66 00 00 03 02 01 00 0a 06 01 04 00  |..m.f...........|",
+            "00000020: fc 30 0b                                         |.0.|",
+            "             ^^ unknown opcode 0x30",
+            "",
+        ].join("\n"));
+
+        // Running out of input points just past the last byte.
+        let module = b"\0asm\x01\0\0\0\x06\x04\x01\x7f\x00\x41";
+        let rendered = render_diagnostic(&parse_error(module), module);
+        assert!(rendered.ends_with("00000000: 00 61 73 6d 01 00 00 00 06 04 01 7f 00 41        |.asm.........A|\n                                                    ^^ unexpected end of input\n"), "{}", rendered);
+
+        let unlocated = AwwasmParseError::new("module", AwwasmParseErrorKind::Truncated);
+        assert_eq!(render_diagnostic(&unlocated, module), "error: Failed to parse WASM module: unexpected end of input\n");
+    }
+}
@@ -0,0 +1,213 @@
+use crate::components::features::AwwasmFeatures;
+use crate::components::module::AwwasmModule;
+use crate::components::types::*;
+
+// Import module names used by the WASI preview1 ABI.
+const WASI_MODULES: [&[u8]; 2] = [b"wasi_snapshot_preview1", b"wasi_unstable"];
+// Listing more imports than this stops being a summary.
+const MAX_LISTED_IMPORTS: usize = 5;
+
+/// Generate a prose summary of a resolved module, e.g.
+/// "Defines 2 functions; exports 1 function and 1 memory; imports WASI fd_write;
+/// 1 memory of 16 pages with 2 data segments totalling 4 KiB; uses SIMD and
+/// bulk memory".
+///
+/// Call `resolve_all_sections()` first; unresolved sections are not described.
+pub fn module(module: &AwwasmModule) -> String {
+    let mut parts: Vec<String> = Vec::new();
+
+    let funcs = module.funcs().len();
+    if funcs > 0 {
+        parts.push(format!("Defines {}", plural(funcs, "function", "functions")));
+    }
+
+    let exports = module.exports();
+    if !exports.is_empty() {
+        let kinds = [
+            (AwwasmExportKind::Function, "function", "functions"),
+            (AwwasmExportKind::Table, "table", "tables"),
+            (AwwasmExportKind::Memory, "memory", "memories"),
+            (AwwasmExportKind::Global, "global", "globals"),
+            (AwwasmExportKind::Tag, "tag", "tags"),
+        ];
+        let counts: Vec<String> = kinds.iter()
+            .map(|(kind, one, many)| (exports.iter().filter(|e| e.kind == *kind).count(), one, many))
+            .filter(|(n, _, _)| *n > 0)
+            .map(|(n, one, many)| plural(n, one, many))
+            .collect();
+        parts.push(format!("exports {}", join_and(&counts)));
+    }
+
+    let imports = module.imports();
+    if !imports.is_empty() {
+        let mut names: Vec<String> = imports.iter()
+            .take(MAX_LISTED_IMPORTS)
+            .map(|i| {
+                let name = String::from_utf8_lossy(&i.name.bytes);
+                if WASI_MODULES.contains(&&*i.module.bytes) {
+                    format!("WASI {}", name)
+                } else {
+                    format!("{}.{}", String::from_utf8_lossy(&i.module.bytes), name)
+                }
+            })
+            .collect();
+        if imports.len() > MAX_LISTED_IMPORTS {
+            names.push(format!("{} more", imports.len() - MAX_LISTED_IMPORTS));
+        }
+        parts.push(format!("imports {}", join_and(&names)));
+    }
+
+    let memories = module.memories().len();
+    let segments = module.data().len();
+    if memories > 0 || segments > 0 {
+        let mut text = match module.memories() {
+            [m] => {
+                let page = match m.limits.page_size() {
+                    65536 => String::from("page"),
+                    size => format!("{}-byte page", size),
+                };
+                format!("1 memory of {}", plural(m.limits.min as usize, &page, &format!("{}s", page)))
+            }
+            _ => plural(memories, "memory", "memories"),
+        };
+        if segments > 0 {
+            let total: usize = module.data().iter().map(|d| d.data_bytes.len()).sum();
+            text.push_str(&format!(" with {} totalling {}", plural(segments, "data segment", "data segments"), byte_size(total)));
+        }
+        parts.push(text);
+    }
+
+    let tables = module.tables().len();
+    if tables > 0 {
+        parts.push(plural(tables, "table", "tables"));
+    }
+    let globals = module.globals().len();
+    if globals > 0 {
+        parts.push(plural(globals, "global", "globals"));
+    }
+    let tags = module.tags().len();
+    if tags > 0 {
+        parts.push(plural(tags, "exception tag", "exception tags"));
+    }
+    if let Some(start) = &module.start {
+        parts.push(format!("starts at function {}", start.func_idx));
+    }
+    let features = feature_names(&module.features_used());
+    if !features.is_empty() {
+        parts.push(format!("uses {}", join_and(&features)));
+    }
+
+    if parts.is_empty() {
+        return String::from("Empty module");
+    }
+    let mut text = parts.join("; ");
+    // Clauses are joined in sentence form; capitalize whichever comes first.
+    if let Some(first) = text.get(0..1) {
+        let upper = first.to_uppercase();
+        text.replace_range(0..1, &upper);
+    }
+    text
+}
+
+// The post-MVP features in use, in prose, most recognizable first.
+fn feature_names(features: &AwwasmFeatures) -> Vec<String> {
+    [
+        (features.simd, "SIMD"),
+        (features.bulk_memory, "bulk memory"),
+        (features.threads, "threads"),
+        (features.exception_handling, "exception handling"),
+        (features.reference_types, "reference types"),
+        (features.function_references, "typed function references"),
+        (features.multi_value, "multi-value"),
+        (features.multi_memory, "multiple memories"),
+        (features.sign_extension, "sign extension"),
+        (features.saturating_float_to_int, "non-trapping float-to-int"),
+        (features.extended_const, "extended constant expressions"),
+        (features.custom_page_sizes, "custom page sizes"),
+        (features.memory64, "64-bit memories"),
+        (features.tail_call, "tail calls"),
+    ]
+    .into_iter()
+    .filter_map(|(used, name)| used.then_some(name.to_string()))
+    .collect()
+}
+
+fn plural(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+fn join_and(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [one] => one.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+fn byte_size(n: usize) -> String {
+    const KIB: usize = 1024;
+    const MIB: usize = 1024 * KIB;
+    if n >= MIB && n.is_multiple_of(MIB) {
+        format!("{} MiB", n / MIB)
+    } else if n >= MIB {
+        format!("{:.1} MiB", n as f64 / MIB as f64)
+    } else if n >= KIB && n.is_multiple_of(KIB) {
+        format!("{} KiB", n / KIB)
+    } else if n >= KIB {
+        format!("{:.1} KiB", n as f64 / KIB as f64)
+    } else {
+        plural(n, "byte", "bytes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::explain;
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn explain_module_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 16)
+                (func (export "_start"))
+                (func (export "helper"))
+                (data (i32.const 0) "hello")
+                (data (i32.const 16) "world!")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        assert_eq!(
+            explain::module(&module_parsed),
+            "Defines 2 functions; exports 2 functions and 1 memory; imports WASI fd_write; \
+             1 memory of 16 pages with 2 data segments totalling 11 bytes"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn explain_features_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1)
+                (func (param v128))
+                (func (memory.fill (i32.const 0) (i32.const 0) (i32.const 0)))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(explain::module(&module_parsed), "Defines 2 functions; 1 memory of 1 page; uses SIMD and bulk memory");
+        Ok(())
+    }
+
+    #[test]
+    fn explain_empty_module_test() -> anyhow::Result<()> {
+        let module = wat::parse_str("(module)")?;
+        let module_parsed = AwwasmModule::new(&module)?;
+        assert_eq!(explain::module(&module_parsed), "Empty module");
+        Ok(())
+    }
+}
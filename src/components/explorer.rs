@@ -0,0 +1,312 @@
+use crate::components::disasm::{self, AwwasmDisasmStyle};
+use crate::components::module::AwwasmModule;
+use crate::components::section::SectionCode;
+use crate::components::types::{AwwasmExportKind, AwwasmName};
+use nom_derive::Parse;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::widgets::{Block, List, ListState, Paragraph};
+use ratatui::Frame;
+
+// Lines the detail pane scrolls per PageUp/PageDown.
+const PAGE_LINES: u16 = 20;
+
+/// What a row of the explorer tree stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmExplorerItem {
+    /// A section, by position in the binary.
+    Section(usize),
+    /// A function body, by position in the code section.
+    Function(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmExplorerRow {
+    pub item: AwwasmExplorerItem,
+    /// Nesting level in the tree: 0 for sections, 1 for functions.
+    pub depth: u8,
+    pub label: String,
+}
+
+// A section header, captured before `resolve_all_sections()` consumes the body.
+struct SectionInfo {
+    id: SectionCode,
+    size: u32,
+    entry_count: u32,
+    custom_name: Option<String>,
+}
+
+/// State of the interactive explorer behind `awwasm tui`: a tree of sections
+/// and functions, a detail pane for the selected row and an incremental search.
+///
+/// Only section headers are decoded up front; a function body is decoded when
+/// it is selected.
+pub struct AwwasmExplorer<'a> {
+    bytes: &'a [u8],
+    module: AwwasmModule<'a>,
+    sections: Vec<SectionInfo>,
+    rows: Vec<AwwasmExplorerRow>,
+    selected: usize,
+    scroll: u16,
+    // Query being typed after `/`; `None` outside search mode.
+    input: Option<String>,
+    query: String,
+    status: String,
+}
+
+impl<'a> AwwasmExplorer<'a> {
+    pub fn new(bytes: &'a [u8]) -> anyhow::Result<Self> {
+        let mut module = AwwasmModule::new(bytes)?;
+        let sections: Vec<SectionInfo> = module.sections().iter().map(|s| SectionInfo {
+            id: s.section_header.section_type.clone(),
+            size: s.section_header.section_size,
+            entry_count: s.entry_count,
+            custom_name: (s.section_header.section_type == SectionCode::Custom)
+                .then(|| AwwasmName::parse(s.section_body).ok())
+                .flatten()
+                .map(|(_, name)| String::from_utf8_lossy(&name.bytes).into_owned()),
+        }).collect();
+        module.resolve_all_sections()?;
+
+        let names = module.names().ok().flatten();
+        let imported = module.imported_func_count();
+        let mut rows = Vec::new();
+        for (i, s) in sections.iter().enumerate() {
+            let label = match (&s.id, &s.custom_name) {
+                (SectionCode::Custom, Some(name)) => format!("Custom \"{}\" ({} bytes)", name, s.size),
+                (SectionCode::Custom | SectionCode::Start | SectionCode::DataCount, _) => format!("{:?} ({} bytes)", s.id, s.size),
+                _ => format!("{:?} ({} {}, {} bytes)", s.id, s.entry_count, if s.entry_count == 1 { "entry" } else { "entries" }, s.size),
+            };
+            rows.push(AwwasmExplorerRow { item: AwwasmExplorerItem::Section(i), depth: 0, label });
+            if s.id != SectionCode::Code {
+                continue;
+            }
+            for (j, item) in module.code().iter().enumerate() {
+                let func_idx = imported + j as u32;
+                let name = names.as_ref().and_then(|n| n.function_name(func_idx)).or_else(|| {
+                    module.exports().iter()
+                        .find(|e| e.kind == AwwasmExportKind::Function && e.index == func_idx)
+                        .and_then(|e| e.name.to_str())
+                });
+                let label = match name {
+                    Some(name) => format!("func {} ${} ({} bytes)", func_idx, name, item.fn_body_size),
+                    None => format!("func {} ({} bytes)", func_idx, item.fn_body_size),
+                };
+                rows.push(AwwasmExplorerRow { item: AwwasmExplorerItem::Function(j), depth: 1, label });
+            }
+        }
+        Ok(Self {
+            bytes,
+            module,
+            sections,
+            rows,
+            selected: 0,
+            scroll: 0,
+            input: None,
+            query: String::new(),
+            status: String::from("j/k move  / search  n next  PgUp/PgDn scroll  q quit"),
+        })
+    }
+
+    pub fn rows(&self) -> &[AwwasmExplorerRow] {
+        &self.rows
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Select row `index`, clamped to the last row.
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.rows.len().saturating_sub(1));
+        self.scroll = 0;
+    }
+
+    /// Lines of the detail pane for the selected row: the disassembly of a
+    /// function (decoded now), or the header of a section.
+    pub fn detail(&self) -> Vec<String> {
+        match self.rows.get(self.selected).map(|r| r.item) {
+            Some(AwwasmExplorerItem::Function(i)) => {
+                let Some(item) = self.module.code().get(i) else {
+                    return Vec::new();
+                };
+                match disasm::function(self.bytes, item, AwwasmDisasmStyle::Columns) {
+                    Ok(text) => text.lines().map(str::to_owned).collect(),
+                    Err(e) => vec![format!("failed to decode body: {}", e)],
+                }
+            }
+            Some(AwwasmExplorerItem::Section(i)) => {
+                let s = &self.sections[i];
+                let mut lines = vec![format!("{:?} section", s.id), format!("size: {} bytes", s.size)];
+                match s.id {
+                    SectionCode::Custom => lines.extend(s.custom_name.as_ref().map(|n| format!("name: {}", n))),
+                    SectionCode::Start => lines.push(format!("start function: {}", s.entry_count)),
+                    SectionCode::DataCount => lines.push(format!("data segments: {}", s.entry_count)),
+                    _ => lines.push(format!("entries: {}", s.entry_count)),
+                }
+                lines
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Select the next row after the current one, wrapping around, whose
+    /// label or disassembly contains `query` (ignoring ASCII case). Returns
+    /// whether one was found.
+    pub fn search(&mut self, query: &str) -> bool {
+        let query = query.to_ascii_lowercase();
+        let start = self.selected;
+        for step in 1..=self.rows.len() {
+            self.selected = (start + step) % self.rows.len();
+            if self.rows[self.selected].label.to_ascii_lowercase().contains(&query)
+                || self.detail().iter().any(|l| l.to_ascii_lowercase().contains(&query))
+            {
+                self.scroll = 0;
+                return true;
+            }
+        }
+        self.selected = start;
+        false
+    }
+
+    /// Apply a key press. Returns `false` once the user asks to quit.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.kind != KeyEventKind::Press {
+            return true;
+        }
+        if let Some(input) = &mut self.input {
+            match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => {
+                    self.query = self.input.take().unwrap_or_default();
+                    self.run_search();
+                }
+                KeyCode::Esc => self.input = None,
+                _ => {}
+            }
+            return true;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.select(self.selected + 1),
+            KeyCode::Up | KeyCode::Char('k') => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Home | KeyCode::Char('g') => self.select(0),
+            KeyCode::End | KeyCode::Char('G') => self.select(usize::MAX),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_add(PAGE_LINES),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(PAGE_LINES),
+            KeyCode::Char('/') => self.input = Some(String::new()),
+            KeyCode::Char('n') if !self.query.is_empty() => self.run_search(),
+            _ => {}
+        }
+        true
+    }
+
+    fn run_search(&mut self) {
+        let query = self.query.clone();
+        self.status = if self.search(&query) {
+            format!("/{}", query)
+        } else {
+            format!("/{}: no match", query)
+        };
+    }
+
+    /// Draw the tree, the detail pane and the status line into `frame`.
+    pub fn render(&self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [tree, detail] = Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(main);
+
+        let items: Vec<String> = self.rows.iter().map(|r| format!("{}{}", "  ".repeat(r.depth as usize), r.label)).collect();
+        let list = List::new(items)
+            .block(Block::bordered().title("Module"))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, tree, &mut ListState::default().with_selected(Some(self.selected)));
+
+        let title = self.rows.get(self.selected).map_or("", |r| r.label.as_str());
+        let text = Paragraph::new(self.detail().join("\n"))
+            .block(Block::bordered().title(title.to_owned()))
+            .scroll((self.scroll, 0));
+        frame.render_widget(text, detail);
+
+        let line = match &self.input {
+            Some(input) => format!("/{}", input),
+            None => self.status.clone(),
+        };
+        frame.render_widget(Paragraph::new(line), status);
+    }
+}
+
+/// Run the explorer on a module binary until the user quits, taking over the
+/// terminal for the duration.
+pub fn run(bytes: &[u8]) -> anyhow::Result<()> {
+    let mut explorer = AwwasmExplorer::new(bytes)?;
+    let mut terminal = ratatui::init();
+    let result = (|| -> anyhow::Result<()> {
+        loop {
+            terminal.draw(|frame| explorer.render(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if !explorer.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    })();
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::explorer::{AwwasmExplorer, AwwasmExplorerItem};
+    use ratatui::backend::TestBackend;
+    use ratatui::crossterm::event::{KeyCode, KeyEvent};
+    use ratatui::Terminal;
+
+    #[test]
+    fn explorer_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "log" (func (param i32)))
+                (func $helper (result i32) (i32.const 7))
+                (func (export "main") (call 0 (call $helper)))
+            )
+        "#)?;
+        let mut explorer = AwwasmExplorer::new(&module)?;
+        let labels: Vec<&str> = explorer.rows().iter().map(|r| r.label.as_str()).collect();
+        assert_eq!(labels, vec![
+            "Type (3 entries, 12 bytes)",
+            "Import (1 entry, 11 bytes)",
+            "Function (2 entries, 3 bytes)",
+            "Export (1 entry, 8 bytes)",
+            "Code (2 entries, 13 bytes)",
+            "func 1 $helper (4 bytes)",
+            "func 2 $main (6 bytes)",
+            "Custom \"name\" (16 bytes)",
+        ]);
+        assert_eq!(explorer.detail(), vec!["Type section", "size: 12 bytes", "entries: 3"]);
+
+        assert!(explorer.search("call 1"));
+        assert_eq!(explorer.rows()[explorer.selected()].item, AwwasmExplorerItem::Function(1));
+        assert!(explorer.detail().iter().any(|l| l.ends_with("| call 1")));
+        assert!(!explorer.search("no such thing"));
+        assert_eq!(explorer.selected(), 6);
+
+        for key in [KeyCode::Char('g'), KeyCode::Char('/'), KeyCode::Char('h'), KeyCode::Char('e'), KeyCode::Enter] {
+            assert!(explorer.handle_key(KeyEvent::from(key)));
+        }
+        assert_eq!(explorer.selected(), 5);
+        assert!(explorer.handle_key(KeyEvent::from(KeyCode::Down)));
+        assert_eq!(explorer.selected(), 6);
+        assert!(!explorer.handle_key(KeyEvent::from(KeyCode::Char('q'))));
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 12))?;
+        terminal.draw(|frame| explorer.render(frame))?;
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("func 2 $main"));
+        assert!(screen.contains("call 1"));
+        Ok(())
+    }
+}
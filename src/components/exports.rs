@@ -0,0 +1,116 @@
+use crate::components::module::AwwasmModule;
+use crate::components::section::SectionCode;
+use crate::components::types::*;
+use std::borrow::Cow;
+
+impl<'a> AwwasmModule<'a> {
+    // The resolved exports, resolving the export section first if needed;
+    // `None` if the module has no export section.
+    fn exports_mut(&mut self) -> anyhow::Result<Option<&mut Vec<AwwasmExportSectionItem<'a>>>> {
+        if self.exports.is_none() {
+            self.resolve_section(SectionCode::Export)?;
+        }
+        Ok(self.exports.as_mut())
+    }
+
+    fn check_unexported(&mut self, name: &str) -> anyhow::Result<()> {
+        if self.exports_mut()?.is_some_and(|exports| exports.iter().any(|e| *e.name.bytes == *name.as_bytes())) {
+            return Err(anyhow::anyhow!("Module already has an export named {:?}", name));
+        }
+        Ok(())
+    }
+
+    /// Rename the export `name` to `new_name`, keeping its place in the
+    /// export section. Fails if there is no such export or `new_name` is
+    /// already exported.
+    ///
+    /// Edits the resolved `exports`, resolving the export section first if
+    /// it is not; `encode()` writes the result.
+    pub fn rename_export(&mut self, name: &str, new_name: impl Into<Cow<'a, str>>) -> anyhow::Result<()> {
+        let new_name = new_name.into();
+        if name != new_name {
+            self.check_unexported(&new_name)?;
+        }
+        let export = self.exports_mut()?
+            .and_then(|exports| exports.iter_mut().find(|e| *e.name.bytes == *name.as_bytes()))
+            .ok_or_else(|| anyhow::anyhow!("Module has no export named {:?}", name))?;
+        export.name = AwwasmName::new(new_name);
+        Ok(())
+    }
+
+    /// Remove the export `name` and return it. The exported item stays in
+    /// the module. Fails if there is no such export.
+    pub fn remove_export(&mut self, name: &str) -> anyhow::Result<AwwasmExportSectionItem<'a>> {
+        let exports = self.exports_mut()?
+            .ok_or_else(|| anyhow::anyhow!("Module has no export named {:?}", name))?;
+        let position = exports.iter().position(|e| *e.name.bytes == *name.as_bytes())
+            .ok_or_else(|| anyhow::anyhow!("Module has no export named {:?}", name))?;
+        Ok(exports.remove(position))
+    }
+
+    /// Export item `index` of `kind` as `name`, after the existing exports.
+    /// Adds an export section if the module has none. Fails if `name` is
+    /// already exported; the index is checked by `validate()`.
+    pub fn add_export(&mut self, kind: AwwasmExportKind, index: u32, name: impl Into<Cow<'a, str>>) -> anyhow::Result<()> {
+        let name = name.into();
+        self.check_unexported(&name)?;
+        self.exports.get_or_insert_with(Vec::new).push(AwwasmExportSectionItem { name: AwwasmName::new(name), kind, index });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+    use crate::components::types::AwwasmExportKind;
+
+    #[test]
+    fn export_editing_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "run"))
+                (func (export "debug_dump")))
+        "#)?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        module.rename_export("run", "_start")?;
+        assert_eq!(module.remove_export("debug_dump")?.index, 1);
+        module.add_export(AwwasmExportKind::Function, 1, "main")?;
+
+        assert!(module.rename_export("missing", "x").is_err());
+        assert!(module.rename_export("memory", "main").is_err());
+        assert!(module.remove_export("debug_dump").is_err());
+        assert!(module.add_export(AwwasmExportKind::Memory, 0, "_start").is_err());
+
+        let encoded = module.encode();
+        let mut edited = AwwasmModule::new(&encoded)?;
+        edited.resolve_all_sections()?;
+        let exports = edited.exports().iter().map(|e| Ok((e.name.as_str()?, e.kind.clone(), e.index))).collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(exports, vec![
+            ("memory", AwwasmExportKind::Memory, 0),
+            ("_start", AwwasmExportKind::Function, 0),
+            ("main", AwwasmExportKind::Function, 1),
+        ]);
+        assert!(edited.validate().is_empty());
+
+        // A module without exports gets an export section.
+        let bytes = wat::parse_str("(module (func))")?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        module.add_export(AwwasmExportKind::Function, 0, "f")?;
+        let encoded = module.encode();
+        let mut edited = AwwasmModule::new(&encoded)?;
+        assert_eq!(edited.resolve_exports()?.len(), 1);
+        assert!(edited.section_order_issues().is_empty());
+
+        // A failed edit does not add an empty export section, and new names
+        // can be owned.
+        let mut module = AwwasmModule::new(&bytes)?;
+        assert!(module.rename_export("f", "g").is_err());
+        assert!(module.remove_export("f").is_err());
+        assert_eq!(module.encode(), bytes);
+        module.add_export(AwwasmExportKind::Function, 0, format!("f{}", 0))?;
+        module.rename_export("f0", String::from("g"))?;
+        assert_eq!(module.exports()[0].name.as_str()?, "g");
+        Ok(())
+    }
+}
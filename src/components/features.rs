@@ -0,0 +1,366 @@
+use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+use crate::components::instructions::*;
+use crate::components::module::AwwasmModule;
+use crate::components::parse_limits::AwwasmParseLimits;
+use crate::components::types::*;
+
+/// Post-MVP proposals a module relies on, from `AwwasmModule::features_used()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AwwasmFeatures {
+    pub sign_extension: bool,
+    pub saturating_float_to_int: bool,
+    pub bulk_memory: bool,
+    /// Shared memories or 0xFE-prefixed atomic instructions.
+    pub threads: bool,
+    pub reference_types: bool,
+    pub multi_value: bool,
+    pub simd: bool,
+    /// A tag section, tag imports or exports, or exception instructions.
+    pub exception_handling: bool,
+    /// Typed references `(ref null? ht)` or `call_ref` and friends.
+    pub function_references: bool,
+    /// More than one memory, or an instruction naming a memory other than 0.
+    pub multi_memory: bool,
+    /// Integer `add`, `sub` or `mul` in a constant expression.
+    pub extended_const: bool,
+    /// A memory declaring its own page size.
+    pub custom_page_sizes: bool,
+    /// A memory with 64-bit limits (limits flag 0x4).
+    pub memory64: bool,
+    /// `return_call`, `return_call_indirect` or `return_call_ref`.
+    pub tail_call: bool,
+}
+
+impl AwwasmFeatures {
+    /// Names of the enabled features, as used by toolchain flags
+    /// (e.g. `bulk-memory`), in declaration order.
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.sign_extension, "sign-extension"),
+            (self.saturating_float_to_int, "nontrapping-float-to-int"),
+            (self.bulk_memory, "bulk-memory"),
+            (self.threads, "threads"),
+            (self.reference_types, "reference-types"),
+            (self.multi_value, "multi-value"),
+            (self.simd, "simd"),
+            (self.exception_handling, "exception-handling"),
+            (self.function_references, "function-references"),
+            (self.multi_memory, "multi-memory"),
+            (self.extended_const, "extended-const"),
+            (self.custom_page_sizes, "custom-page-sizes"),
+            (self.memory64, "memory64"),
+            (self.tail_call, "tail-call"),
+        ]
+        .into_iter()
+        .filter_map(|(used, name)| used.then_some(name))
+        .collect()
+    }
+
+    /// Every proposal the parser detects.
+    pub fn all() -> Self {
+        AwwasmFeatures {
+            sign_extension: true,
+            saturating_float_to_int: true,
+            bulk_memory: true,
+            threads: true,
+            reference_types: true,
+            multi_value: true,
+            simd: true,
+            exception_handling: true,
+            function_references: true,
+            multi_memory: true,
+            extended_const: true,
+            custom_page_sizes: true,
+            memory64: true,
+            tail_call: true,
+        }
+    }
+
+    fn note_val_type(&mut self, ty: ValType) {
+        match ty {
+            ValType::V128 => self.simd = true,
+            ValType::FuncRef | ValType::ExternRef => self.reference_types = true,
+            ValType::Ref(_) => self.function_references = true,
+            _ => {}
+        }
+    }
+
+    fn note_instruction(&mut self, instr: &AwwasmInstruction) {
+        use AwwasmOperands::*;
+        if instr.operands.mem_arg().is_some_and(|m| m.memory() != 0) {
+            self.multi_memory = true;
+        }
+        match &instr.operands {
+            I32Extend8S | I32Extend16S | I64Extend8S | I64Extend16S | I64Extend32S => self.sign_extension = true,
+            Misc(op) if (op.sub_op as u32) < MiscOpCode::MemoryInit as u32 => self.saturating_float_to_int = true,
+            Misc(_) => self.bulk_memory = true,
+            Atomic(_) => self.threads = true,
+            RefNull(RefNullOperands { heap_type: AwwasmHeapType::Type(_) })
+            | CallRef(_) | RefAsNonNull | BrOnNull(_) | BrOnNonNull(_) => self.function_references = true,
+            ReturnCallRef(_) => {
+                self.function_references = true;
+                self.tail_call = true;
+            }
+            ReturnCall(_) | ReturnCallIndirect(_) => self.tail_call = true,
+            RefNull(_) | RefIsNull | RefFunc(_) => self.reference_types = true,
+            MemorySize(op) | MemoryGrow(op) if op.memidx != 0 => self.multi_memory = true,
+            Try(_) | TryTable(_) | Catch(_) | CatchAll | Delegate(_) | Throw(_) | Rethrow(_) | ThrowRef => {
+                self.exception_handling = true;
+            }
+            SelectTyped(op) => op.types.iter().for_each(|ty| self.note_val_type(*ty)),
+            operands => match operands.block_type() {
+                Some(BlockType::Value(ty)) => self.note_val_type(ty),
+                Some(BlockType::TypeIndex(_)) => self.multi_value = true,
+                _ => {}
+            },
+        }
+    }
+}
+
+/// Options for `AwwasmModule::new_with_options()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmParseOptions {
+    /// Proposals the module may use; a module using any other fails to parse.
+    /// Defaults to `AwwasmFeatures::all()`.
+    pub features: AwwasmFeatures,
+    /// How to treat repeated or out-of-order sections. Defaults to
+    /// `AwwasmSectionOrderMode::Lenient`.
+    pub section_order: AwwasmSectionOrderMode,
+    /// Fail with the first `AwwasmLeb128Issue` if any integer in the module
+    /// is padded or sets bits beyond its width. Off by default; costs a
+    /// second parse.
+    pub canonical_leb128: bool,
+    /// Caps on the module's sections, entries and function bodies, checked
+    /// before any section is decoded and kept in `AwwasmModule::limits`.
+    /// Defaults to no caps but the nesting depth.
+    pub limits: AwwasmParseLimits,
+}
+
+impl Default for AwwasmParseOptions {
+    fn default() -> Self {
+        AwwasmParseOptions { features: AwwasmFeatures::all(), section_order: AwwasmSectionOrderMode::default(), canonical_leb128: false, limits: AwwasmParseLimits::default() }
+    }
+}
+
+/// Whether `AwwasmModule::new_with_options()` rejects modules whose
+/// non-custom sections repeat or are out of order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AwwasmSectionOrderMode {
+    /// Fail with the first `SectionOrderError`.
+    Strict,
+    /// Parse anyway; `validate()` reports each issue as a `section_order`
+    /// finding.
+    #[default]
+    Lenient,
+}
+
+impl AwwasmModule<'_> {
+    /// Parse and resolve the module, failing with an `AwwasmParseError` of
+    /// kind `UnsupportedFeatures` if it uses a proposal `options` does not
+    /// enable, `SectionOrder` if its sections are misordered and `options`
+    /// asks for strict ordering, `NonCanonicalLeb128` if it asks for
+    /// canonical integers, or `LimitExceeded` if it is over one of the
+    /// `options.limits` caps.
+    ///
+    /// Only the proposals `AwwasmFeatures` tracks can be gated. Memory64
+    /// limits decode only while they fit in 32 bits; larger ones fail the
+    /// parse whatever the options.
+    pub fn new_with_options<'a>(input: &'a [u8], options: &AwwasmParseOptions) -> anyhow::Result<AwwasmModule<'a>> {
+        let mut module = AwwasmModule::new(input)?;
+        module.limits = options.limits;
+        options.limits.check(&module)?;
+        if options.section_order == AwwasmSectionOrderMode::Strict {
+            if let Some(error) = module.section_order_issues().into_iter().next() {
+                return Err(AwwasmParseError::from(error).into());
+            }
+        }
+        if options.canonical_leb128 {
+            if let Some(issue) = AwwasmModule::leb128_issues(input)?.into_iter().next() {
+                return Err(AwwasmParseError::from(issue).into());
+            }
+        }
+        module.resolve_all_sections()?;
+        let enabled = options.features.names();
+        let features: Vec<&'static str> = module.features_used().names().into_iter()
+            .filter(|name| !enabled.contains(name))
+            .collect();
+        if !features.is_empty() {
+            return Err(AwwasmParseError::module(AwwasmParseErrorKind::UnsupportedFeatures { features }).into());
+        }
+        Ok(module)
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Detect which post-MVP features the module uses, from its types,
+    /// tables, memories and function bodies.
+    ///
+    /// Bodies that fail to decode are skipped; `validate()` reports them.
+    /// Requires `resolve_all_sections()`.
+    pub fn features_used(&self) -> AwwasmFeatures {
+        let mut features = AwwasmFeatures::default();
+        for ty in self.types() {
+            features.multi_value |= ty.fn_rets.len() > 1;
+            ty.fn_args.iter().chain(&ty.fn_rets).for_each(|ty| features.note_val_type(*ty));
+        }
+        let tables = self.table_types();
+        features.reference_types |= tables.len() > 1 || tables.contains(&AwwasmTableReferenceType::Extern);
+        let memories = || self.imports().iter().filter_map(|i| i.mem.as_ref())
+            .chain(self.memories().iter().map(|m| &m.limits));
+        features.threads |= memories().any(|m| m.is_shared());
+        features.custom_page_sizes |= memories().any(|m| m.page_size_log2.is_some());
+        features.memory64 |= memories().any(|m| m.flags & 0x4 != 0);
+        features.multi_memory |= self.memory_count() > 1;
+        features.exception_handling |= self.tags.is_some()
+            || self.imports().iter().any(|i| i.kind == AwwasmImportKind::Tag)
+            || self.exports().iter().any(|e| e.kind == AwwasmExportKind::Tag);
+        let init_exprs = self.globals().iter().map(|g| &g.init_expr)
+            .chain(self.data().iter().filter_map(|d| d.header.offset.as_ref()))
+            .chain(self.elements().iter().flat_map(|e| e.body.init_exprs()));
+        for expr in init_exprs {
+            use WasmOpCode::*;
+            features.extended_const |= InstructionIterator::new(expr.code).flatten()
+                .any(|instr| matches!(instr.opcode, I32Add | I32Sub | I32Mul | I64Add | I64Sub | I64Mul));
+        }
+
+        for item in self.code() {
+            let Ok((locals, _)) = item.locals_and_code() else { continue };
+            locals.iter().for_each(|l| features.note_val_type(l.param_type));
+            let Ok(instrs) = item.instructions_with_limits(&self.limits) else { continue };
+            for instr in &instrs {
+                instr.walk(&mut |instr| features.note_instruction(instr));
+            }
+        }
+        features
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+    use crate::components::features::{AwwasmFeatures, AwwasmParseOptions, AwwasmSectionOrderMode};
+    use crate::components::module::AwwasmModule;
+    use crate::components::section::{SectionCode, SectionOrderError};
+
+    #[test]
+    fn features_used_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1 1 shared)
+                (table 1 externref)
+                (func (param f32) (result i32 i32)
+                    (i32.trunc_sat_f32_s (local.get 0))
+                    (i32.extend8_s (i32.const 1))
+                    (memory.fill (i32.const 0) (i32.const 0) (i32.const 0)))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let features = module_parsed.features_used();
+        assert_eq!(features, AwwasmFeatures {
+            sign_extension: true,
+            saturating_float_to_int: true,
+            bulk_memory: true,
+            threads: true,
+            reference_types: true,
+            multi_value: true,
+            simd: false,
+            exception_handling: false,
+            function_references: false,
+            multi_memory: false,
+            extended_const: false,
+            custom_page_sizes: false,
+            memory64: false,
+            tail_call: false,
+        });
+        assert_eq!(features.names(), vec![
+            "sign-extension", "nontrapping-float-to-int", "bulk-memory", "threads", "reference-types", "multi-value",
+        ]);
+
+        let mvp = wat::parse_str("(module (func (result i32) (i32.add (i32.const 1) (i32.const 2))))")?;
+        let mut mvp_parsed = AwwasmModule::new(&mvp)?;
+        mvp_parsed.resolve_all_sections()?;
+        assert!(mvp_parsed.features_used().names().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn new_with_options_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1 1 shared)
+                (func (result v128) (v128.const i64x2 0 0))
+            )
+        "#)?;
+        let mvp = AwwasmParseOptions { features: AwwasmFeatures::default(), ..Default::default() };
+        let err = AwwasmModule::new_with_options(&module, &mvp).unwrap_err();
+        let kind = err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind);
+        assert_eq!(kind, Some(&AwwasmParseErrorKind::UnsupportedFeatures { features: vec!["threads", "simd"] }));
+        assert_eq!(err.to_string(), "Failed to parse WASM module: module uses features that are not enabled: threads, simd");
+
+        let engine = AwwasmParseOptions { features: AwwasmFeatures { simd: true, threads: true, ..Default::default() }, ..Default::default() };
+        let parsed = AwwasmModule::new_with_options(&module, &engine)?;
+        assert_eq!(parsed.memory_count(), 1);
+        assert!(AwwasmModule::new_with_options(&module, &AwwasmParseOptions::default()).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn memory64_tail_call_features_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory i64 1)
+                (table 1 funcref)
+                (type $t (func (result i32)))
+                (func $f (result i32) (return_call $f))
+                (func (result i32) (return_call_indirect (type $t) (i32.const 0)))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.features_used().names(), vec!["memory64", "tail-call"]);
+
+        let engine = AwwasmParseOptions { features: AwwasmFeatures { memory64: true, ..Default::default() }, ..Default::default() };
+        let err = AwwasmModule::new_with_options(&module, &engine).unwrap_err();
+        let kind = err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind);
+        assert_eq!(kind, Some(&AwwasmParseErrorKind::UnsupportedFeatures { features: vec!["tail-call"] }));
+        Ok(())
+    }
+
+    #[test]
+    fn section_order_test() -> anyhow::Result<()> {
+        let module = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            0x01, 0x01, 0x00,                    // type
+            0x00, 0x02, 0x01, b'x',              // custom "x"
+            0x05, 0x03, 0x01, 0x00, 0x01,        // memory
+            0x01, 0x01, 0x00,                    // type again
+            0x03, 0x01, 0x00,                    // function after memory
+            0x0a, 0x01, 0x00,                    // code
+            0x0c, 0x01, 0x00,                    // data count after code
+        ];
+        let issues = AwwasmModule::new(&module)?.section_order_issues();
+        assert_eq!(issues, vec![
+            SectionOrderError { section: SectionCode::Type, position: 3, after: SectionCode::Type },
+            SectionOrderError { section: SectionCode::Function, position: 4, after: SectionCode::Memory },
+            SectionOrderError { section: SectionCode::DataCount, position: 6, after: SectionCode::Code },
+        ]);
+        assert_eq!(issues[0].to_string(), "Type section at position 3 duplicates an earlier one");
+        assert_eq!(issues[1].to_string(), "Function section at position 4 comes after the Memory section");
+
+        let strict = AwwasmParseOptions { section_order: AwwasmSectionOrderMode::Strict, ..Default::default() };
+        let err = AwwasmModule::new_with_options(&module, &strict).unwrap_err();
+        let kind = err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind);
+        assert_eq!(kind, Some(&AwwasmParseErrorKind::SectionOrder(issues[0].clone())));
+
+        let lenient = AwwasmModule::new_with_options(&module, &AwwasmParseOptions::default())?;
+        let findings: Vec<_> = lenient.validate().into_iter().filter(|f| f.check == "section_order").collect();
+        assert_eq!(findings.len(), 3);
+        assert_eq!(findings[2].message, "DataCount section at position 6 comes after the Code section");
+
+        let ordered = wat::parse_str("(module (type (func)) (memory 1) (func) (data \"\"))")?;
+        assert!(AwwasmModule::new_with_options(&ordered, &strict)?.section_order_issues().is_empty());
+        Ok(())
+    }
+}
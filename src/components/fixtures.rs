@@ -0,0 +1,187 @@
+//! Tiny hand-checked wasm binaries for testing integrations against known
+//! inputs. Available in this crate's tests and, for downstream crates, behind
+//! the `fixtures` feature.
+
+/// The preamble and nothing else.
+pub const EMPTY_MODULE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+];
+
+/// One section of every known kind, in binary order: an imported and two
+/// defined functions, a table, a memory, a global, an export, a start
+/// function, an element and a data segment, plus `name` and `note` custom
+/// sections.
+pub const ONE_OF_EACH_SECTION: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    // type: (i32) -> i32, () -> ()
+    0x01, 0x09, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x00, 0x00,
+    // import: env.f, type 0
+    0x02, 0x09, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x01, 0x66, 0x00, 0x00,
+    // function: types 0 and 1
+    0x03, 0x03, 0x02, 0x00, 0x01,
+    // table: funcref, min 1
+    0x04, 0x04, 0x01, 0x70, 0x00, 0x01,
+    // memory: min 1
+    0x05, 0x03, 0x01, 0x00, 0x01,
+    // global: mut i32 = 0
+    0x06, 0x06, 0x01, 0x7f, 0x01, 0x41, 0x00, 0x0b,
+    // export: "g" = function 1
+    0x07, 0x05, 0x01, 0x01, 0x67, 0x00, 0x01,
+    // start: function 2
+    0x08, 0x01, 0x02,
+    // element: table 0 at offset 0, function 1
+    0x09, 0x07, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x01, 0x01,
+    // datacount: 1
+    0x0c, 0x01, 0x01,
+    // code: (local.get 0), ()
+    0x0a, 0x09, 0x02, 0x04, 0x00, 0x20, 0x00, 0x0b, 0x02, 0x00, 0x0b,
+    // data: memory 0 at offset 0, "hi"
+    0x0b, 0x08, 0x01, 0x00, 0x41, 0x00, 0x0b, 0x02, 0x68, 0x69,
+    // custom "name": functions f, g, s; type 0 is t
+    0x00, 0x17, 0x04, 0x6e, 0x61, 0x6d, 0x65,
+    0x01, 0x0a, 0x03, 0x00, 0x01, 0x66, 0x01, 0x01, 0x67, 0x02, 0x01, 0x73,
+    0x04, 0x04, 0x01, 0x00, 0x01, 0x74,
+    // custom "note": "x"
+    0x00, 0x06, 0x04, 0x6e, 0x6f, 0x74, 0x65, 0x78,
+];
+
+/// Control instructions: `block`, `loop`, `if`/`else`, `br`, `br_if`,
+/// `br_table`, `call`, `call_indirect`, `return`, `unreachable` and `nop`.
+pub const CONTROL_OPCODES: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f,
+    0x03, 0x02, 0x01, 0x00, 0x04, 0x04, 0x01, 0x70, 0x00, 0x01, 0x0a, 0x30, 0x01, 0x2e, 0x00, 0x02,
+    0x7f, 0x03, 0x40, 0x20, 0x00, 0x04, 0x40, 0x0c, 0x01, 0x05, 0x41, 0x01, 0x20, 0x00, 0x0d, 0x02,
+    0x0b, 0x0b, 0x41, 0x02, 0x20, 0x00, 0x0e, 0x01, 0x00, 0x00, 0x0b, 0x41, 0x00, 0x10, 0x00, 0x1a,
+    0x41, 0x00, 0x41, 0x00, 0x11, 0x00, 0x00, 0x1a, 0x0f, 0x00, 0x01, 0x0b,
+];
+
+/// Parametric and variable instructions: `drop`, `select` (plain and typed),
+/// `local.get`/`set`/`tee` and `global.get`/`set`.
+pub const PARAMETRIC_AND_VARIABLE_OPCODES: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60, 0x01, 0x7f, 0x00, 0x03,
+    0x02, 0x01, 0x00, 0x06, 0x06, 0x01, 0x7f, 0x01, 0x41, 0x00, 0x0b, 0x0a, 0x22, 0x01, 0x20, 0x01,
+    0x01, 0x7e, 0x41, 0x01, 0x41, 0x02, 0x20, 0x00, 0x1b, 0x1a, 0x20, 0x01, 0x42, 0x02, 0x20, 0x00,
+    0x1c, 0x01, 0x7e, 0x1a, 0x42, 0x03, 0x22, 0x01, 0x21, 0x01, 0x23, 0x00, 0x24, 0x00, 0x0b,
+];
+
+/// Memory instructions: loads and stores of every width, `memory.size` and
+/// `memory.grow`.
+pub const MEMORY_OPCODES: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02,
+    0x01, 0x00, 0x05, 0x03, 0x01, 0x00, 0x01, 0x0a, 0x4f, 0x01, 0x4d, 0x00, 0x41, 0x00, 0x28, 0x02,
+    0x04, 0x1a, 0x41, 0x00, 0x29, 0x03, 0x00, 0x1a, 0x41, 0x00, 0x2a, 0x02, 0x00, 0x1a, 0x41, 0x00,
+    0x2b, 0x03, 0x00, 0x1a, 0x41, 0x00, 0x2c, 0x00, 0x00, 0x1a, 0x41, 0x00, 0x2f, 0x01, 0x00, 0x1a,
+    0x41, 0x00, 0x34, 0x02, 0x00, 0x1a, 0x41, 0x00, 0x41, 0x01, 0x36, 0x02, 0x00, 0x41, 0x00, 0x42,
+    0x01, 0x3c, 0x00, 0x00, 0x41, 0x00, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, 0x39,
+    0x03, 0x00, 0x3f, 0x00, 0x40, 0x00, 0x1a, 0x0b,
+];
+
+/// Numeric instructions: constants of every type, integer and float
+/// arithmetic and comparisons.
+pub const NUMERIC_OPCODES: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02,
+    0x01, 0x00, 0x0a, 0x41, 0x01, 0x3f, 0x00, 0x41, 0x01, 0x41, 0x02, 0x6a, 0x1a, 0x42, 0x01, 0x42,
+    0x02, 0x7f, 0x1a, 0x43, 0x00, 0x00, 0xc0, 0x3f, 0x43, 0x00, 0x00, 0x00, 0x40, 0x94, 0x1a, 0x44,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x40, 0x9f, 0x1a, 0x41, 0x01, 0x41, 0x02, 0x49, 0x1a,
+    0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x3f, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x40, 0x66, 0x1a, 0x0b,
+];
+
+/// Conversion instructions: wrap, extend, truncate, convert, demote,
+/// reinterpret and sign extension.
+pub const CONVERSION_OPCODES: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02,
+    0x01, 0x00, 0x0a, 0x31, 0x01, 0x2f, 0x00, 0x42, 0x01, 0xa7, 0x1a, 0x41, 0x01, 0xad, 0x1a, 0x43,
+    0x00, 0x00, 0x80, 0x3f, 0xa8, 0x1a, 0x42, 0x01, 0xba, 0x1a, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0xf0, 0x3f, 0xb6, 0x1a, 0x43, 0x00, 0x00, 0x80, 0x3f, 0xbc, 0x1a, 0x42, 0x01, 0xc2, 0x1a,
+    0x41, 0x01, 0xc1, 0x1a, 0x0b,
+];
+
+/// Magic number is `\0wsm` instead of `\0asm`.
+pub const MALFORMED_BAD_MAGIC: &[u8] = &[
+    0x00, 0x77, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+];
+
+/// Input ends inside the version field.
+pub const MALFORMED_TRUNCATED_PREAMBLE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00,
+];
+
+/// A type section declaring 16 bytes with only 2 present.
+pub const MALFORMED_TRUNCATED_SECTION: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    0x01, 0x10, 0x01, 0x60,
+];
+
+/// A section with id 0x7f, which no version of the spec defines.
+pub const MALFORMED_UNKNOWN_SECTION: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    0x7f, 0x01, 0x00,
+];
+
+/// A function body containing the undefined opcode 0xff.
+pub const MALFORMED_UNKNOWN_OPCODE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+    0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+    0x03, 0x02, 0x01, 0x00,
+    0x0a, 0x05, 0x01, 0x03, 0x00, 0xff, 0x0b,
+];
+
+/// Every well-formed fixture, by name.
+pub const VALID: &[(&str, &[u8])] = &[
+    ("empty_module", EMPTY_MODULE),
+    ("one_of_each_section", ONE_OF_EACH_SECTION),
+    ("control_opcodes", CONTROL_OPCODES),
+    ("parametric_and_variable_opcodes", PARAMETRIC_AND_VARIABLE_OPCODES),
+    ("memory_opcodes", MEMORY_OPCODES),
+    ("numeric_opcodes", NUMERIC_OPCODES),
+    ("conversion_opcodes", CONVERSION_OPCODES),
+];
+
+/// Every malformed fixture, by name.
+pub const MALFORMED: &[(&str, &[u8])] = &[
+    ("bad_magic", MALFORMED_BAD_MAGIC),
+    ("truncated_preamble", MALFORMED_TRUNCATED_PREAMBLE),
+    ("truncated_section", MALFORMED_TRUNCATED_SECTION),
+    ("unknown_section", MALFORMED_UNKNOWN_SECTION),
+    ("unknown_opcode", MALFORMED_UNKNOWN_OPCODE),
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::components::fixtures::*;
+    use crate::components::module::AwwasmModule;
+
+    // Parse, resolve and decode every function body.
+    fn decode(bytes: &[u8]) -> anyhow::Result<AwwasmModule<'_>> {
+        let mut module = AwwasmModule::new(bytes)?;
+        module.resolve_all_sections()?;
+        for item in module.code() {
+            item.instructions()?;
+        }
+        Ok(module)
+    }
+
+    #[test]
+    fn valid_fixtures_test() -> anyhow::Result<()> {
+        for (name, bytes) in VALID {
+            decode(bytes).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+        }
+
+        let module = decode(ONE_OF_EACH_SECTION)?;
+        assert_eq!(module.func_count(), 3);
+        assert_eq!(module.start.as_ref().map(|s| s.func_idx), Some(2));
+        assert_eq!(module.data_count, Some(1));
+        assert_eq!(module.names()?.expect("names should exist").function_name(1), Some("g"));
+        assert_eq!(module.custom_section(b"note").map(|c| c.payload), Some(&b"x"[..]));
+        assert!(module.validate().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_fixtures_test() {
+        for (name, bytes) in MALFORMED {
+            assert!(decode(bytes).is_err(), "{} should not decode", name);
+        }
+    }
+}
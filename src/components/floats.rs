@@ -0,0 +1,109 @@
+/// How printers write `f32.const` and `f64.const` immediates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AwwasmFloatFormat {
+    /// Shortest decimal that reads back as the same value (`1.5`, `-inf`).
+    /// NaN payloads are lost (`nan`).
+    #[default]
+    Decimal,
+    /// Hexadecimal float as in the text format (`0x1.8p+0`), bit-exact
+    /// including NaN payloads (`nan:0x200000`).
+    HexFloat,
+    /// The raw bit pattern (`0x3fc00000`).
+    Bits,
+}
+
+// Field widths of an IEEE 754 binary format.
+struct Layout {
+    mantissa_bits: u32,
+    exponent_bits: u32,
+}
+
+const F32: Layout = Layout { mantissa_bits: 23, exponent_bits: 8 };
+const F64: Layout = Layout { mantissa_bits: 52, exponent_bits: 11 };
+
+impl AwwasmFloatFormat {
+    pub fn f32(self, v: f32) -> String {
+        match self {
+            AwwasmFloatFormat::Decimal => decimal(v.is_nan(), v.is_sign_negative(), v),
+            AwwasmFloatFormat::HexFloat => hex_float(v.to_bits() as u64, &F32),
+            AwwasmFloatFormat::Bits => format!("0x{:08x}", v.to_bits()),
+        }
+    }
+
+    pub fn f64(self, v: f64) -> String {
+        match self {
+            AwwasmFloatFormat::Decimal => decimal(v.is_nan(), v.is_sign_negative(), v),
+            AwwasmFloatFormat::HexFloat => hex_float(v.to_bits(), &F64),
+            AwwasmFloatFormat::Bits => format!("0x{:016x}", v.to_bits()),
+        }
+    }
+}
+
+// Rust's `Display` already prints the shortest round-trip decimal; only NaN
+// and the infinities are spelled differently in the text format.
+fn decimal(nan: bool, negative: bool, v: impl std::fmt::Display) -> String {
+    let sign = if negative { "-" } else { "" };
+    match v.to_string().as_str() {
+        _ if nan => format!("{}nan", sign),
+        "inf" | "-inf" => format!("{}inf", sign),
+        s => s.to_owned(),
+    }
+}
+
+fn hex_float(bits: u64, layout: &Layout) -> String {
+    let sign = if bits >> (layout.mantissa_bits + layout.exponent_bits) & 1 == 1 { "-" } else { "" };
+    let max_exponent = (1u64 << layout.exponent_bits) - 1;
+    let exponent = (bits >> layout.mantissa_bits) & max_exponent;
+    let mantissa = bits & ((1u64 << layout.mantissa_bits) - 1);
+    let bias = (max_exponent >> 1) as i64;
+
+    if exponent == max_exponent {
+        let canonical_nan = 1u64 << (layout.mantissa_bits - 1);
+        return match mantissa {
+            0 => format!("{}inf", sign),
+            m if m == canonical_nan => format!("{}nan", sign),
+            m => format!("{}nan:0x{:x}", sign, m),
+        };
+    }
+    if exponent == 0 && mantissa == 0 {
+        return format!("{}0x0p+0", sign);
+    }
+
+    // Pad the mantissa to whole hex digits, then drop trailing zero digits.
+    let digits = layout.mantissa_bits.div_ceil(4);
+    let fraction = format!("{:0width$x}", mantissa << (digits * 4 - layout.mantissa_bits), width = digits as usize);
+    let fraction = fraction.trim_end_matches('0');
+    let (lead, power) = if exponent == 0 { (0, 1 - bias) } else { (1, exponent as i64 - bias) };
+    if fraction.is_empty() {
+        format!("{}0x{}p{:+}", sign, lead, power)
+    } else {
+        format!("{}0x{}.{}p{:+}", sign, lead, fraction, power)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::floats::AwwasmFloatFormat;
+
+    #[test]
+    fn float_format_test() {
+        let hex = AwwasmFloatFormat::HexFloat;
+        assert_eq!(hex.f32(1.5), "0x1.8p+0");
+        assert_eq!(hex.f32(-0.0), "-0x0p+0");
+        assert_eq!(hex.f32(f32::MIN_POSITIVE / 2.0), "0x0.8p-126");
+        assert_eq!(hex.f32(f32::from_bits(0x7fc0_0000)), "nan");
+        assert_eq!(hex.f32(f32::from_bits(0xff80_0001)), "-nan:0x1");
+        assert_eq!(hex.f64(0.1), "0x1.999999999999ap-4");
+        assert_eq!(hex.f64(1024.0), "0x1p+10");
+        assert_eq!(hex.f64(f64::NEG_INFINITY), "-inf");
+
+        let decimal = AwwasmFloatFormat::Decimal;
+        assert_eq!(decimal.f32(0.1), "0.1");
+        assert_eq!(decimal.f64(-2.25), "-2.25");
+        assert_eq!(decimal.f64(f64::INFINITY), "inf");
+        assert_eq!(decimal.f32(f32::from_bits(0xffc0_0001)), "-nan");
+
+        assert_eq!(AwwasmFloatFormat::Bits.f32(1.5), "0x3fc00000");
+        assert_eq!(AwwasmFloatFormat::Bits.f64(1.5), "0x3ff8000000000000");
+    }
+}
@@ -0,0 +1,154 @@
+//! The decoding surface as one fuzzing entry point.
+//!
+//! `exercise()` runs bytes through every decoder and analysis a consumer
+//! reaches from raw bytes. The tests below feed it mutants of the fixtures
+//! from a fixed-seed generator, and `fuzz/fuzz_targets/parse.rs` calls it
+//! from cargo-fuzz for open-ended runs.
+
+use crate::components::component::{AwwasmComponent, AwwasmComponentSectionCode};
+use crate::components::module::AwwasmModule;
+use crate::components::payload::AwwasmPayloadIterator;
+
+/// Decode `bytes` as far as it goes, as a module and as a component, and
+/// run every analysis on the result. Errors are expected; only panics
+/// matter.
+pub fn exercise(bytes: &[u8]) {
+    exercise_module(bytes);
+    exercise_component(bytes);
+}
+
+fn exercise_module(bytes: &[u8]) {
+    let _ = AwwasmModule::leb128_issues(bytes);
+    let _ = AwwasmModule::new_permissive(bytes);
+    let _ = AwwasmModule::parse_lossy(bytes);
+    let _ = AwwasmModule::annotated_hexdump(bytes);
+    AwwasmPayloadIterator::new(bytes).for_each(drop);
+    let Ok(mut module) = AwwasmModule::new(bytes) else { return };
+    let _ = module.section_order_issues();
+    let _ = module.spans();
+    if let Ok(functions) = module.functions_lazy() {
+        functions.flatten().for_each(|(_, _, instrs)| instrs.for_each(drop));
+    }
+    if module.resolve_all_sections().is_err() {
+        return;
+    }
+    for item in module.code() {
+        let _ = item.instructions();
+    }
+    let _ = module.instruction_index();
+    let _ = module.validate();
+    let _ = module.validate_indices();
+    let _ = module.validate_call_indirect();
+    let _ = module.features_used();
+    let _ = module.init_graph();
+    let _ = module.export_data_reads();
+    let _ = module.names();
+    let _ = module.producers();
+    let _ = module.branch_hints();
+    let _ = module.dylink();
+    let _ = module.linking();
+    let _ = module.relocations();
+    let _ = module.to_json();
+    let _ = module.dump(Default::default());
+    let _ = module.encode();
+    let _ = AwwasmModule::merge(std::slice::from_ref(&module));
+}
+
+fn exercise_component(bytes: &[u8]) {
+    let Ok(component) = AwwasmComponent::new(bytes) else { return };
+    let _ = component.types();
+    let _ = component.imports();
+    let _ = component.exports();
+    let _ = component.aliases();
+    let _ = component.canonicals();
+    let _ = component.customs();
+    let _ = component.world();
+    let _ = component.all_core_modules();
+    for section in component.sections.iter().filter(|s| s.id == AwwasmComponentSectionCode::CoreModule) {
+        exercise_module(section.payload);
+    }
+}
+
+// xorshift64*, so runs are reproducible from the seed.
+#[cfg(test)]
+struct Rng(u64);
+
+#[cfg(test)]
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+}
+
+// Apply one to four random edits, leaving the preamble alone half the time
+// so most mutants get past it.
+#[cfg(test)]
+fn mutate(rng: &mut Rng, seed: &[u8]) -> Vec<u8> {
+    let mut bytes = seed.to_vec();
+    let floor = if rng.next().is_multiple_of(2) { 8.min(bytes.len()) } else { 0 };
+    for _ in 0..1 + rng.below(4) {
+        let at = floor + rng.below(bytes.len().saturating_sub(floor));
+        match rng.below(6) {
+            0 if at < bytes.len() => bytes[at] ^= 1 << rng.below(8),
+            1 if at < bytes.len() => bytes[at] = rng.next() as u8,
+            2 if at < bytes.len() => bytes[at] = [0x00, 0x7f, 0x80, 0xff][rng.below(4)],
+            3 => bytes.insert(at, rng.next() as u8),
+            4 if at < bytes.len() => { bytes.remove(at); }
+            _ => bytes.truncate(at),
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::fixtures;
+    use crate::components::fuzz::{exercise, mutate, Rng};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    const MUTANTS_PER_FIXTURE: usize = 2000;
+
+    // Covers every component section `exercise()` decodes, with a core
+    // module and a nested component inside.
+    const COMPONENT: &str = r#"
+        (component
+            (import "host" (instance $host
+                (type $level (enum "info" "error"))
+                (export $l "level" (type (eq $level)))
+                (export "log" (func (param "level" $l) (param "msg" string)))))
+            (alias export $host "log" (func $log))
+            (core module $m
+                (import "host" "log" (func (param i32 i32 i32)))
+                (memory (export "mem") 1)
+                (func (export "run") (param i32) (result i32) local.get 0))
+            (core func $log-lowered (canon lower (func $log)))
+            (core instance $i (instantiate $m (with "host" (instance (export "log" (func $log-lowered))))))
+            (component (core module (memory 1)))
+            (type $run-type (func (param "a" u32) (result (list (tuple u8 string)))))
+            (func $run (type $run-type) (canon lift (core func $i "run") (memory $i "mem")))
+            (export "run" (func $run))
+            (@custom "note" "x"))
+    "#;
+
+    #[test]
+    fn mutated_fixtures_never_panic_test() {
+        let mut rng = Rng(0x5eed_0000_a55a_f00d);
+        let component = wat::parse_str(COMPONENT).expect("the component should assemble");
+        let seeds = fixtures::VALID.iter().chain(fixtures::MALFORMED).copied().chain([("component", &component[..])]);
+        for (name, seed) in seeds {
+            for i in 0..MUTANTS_PER_FIXTURE {
+                let bytes = mutate(&mut rng, seed);
+                if catch_unwind(AssertUnwindSafe(|| exercise(&bytes))).is_err() {
+                    panic!("mutant {} of fixture {} panicked: {:02x?}", i, name, bytes);
+                }
+            }
+        }
+    }
+}
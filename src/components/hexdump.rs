@@ -0,0 +1,257 @@
+use crate::components::error::AwwasmParseError;
+use crate::components::instructions::{marker_text, span_in, AwwasmInstruction};
+use crate::components::leb128::leb128_u32;
+use crate::components::module::AwwasmModule;
+use crate::components::section::{AwwasmSectionHeader, SectionCode, TruncatedSection};
+use crate::components::types::*;
+use crate::consts::{WASM_PREAMBLE_MAGIC_SIZE_BYTES, WASM_PREAMBLE_VERSION_SIZE_BYTES};
+use nom::IResult;
+use nom_derive::Parse;
+use std::fmt::Write;
+
+// Bytes shown per line; longer runs continue on unannotated lines.
+const ROW_BYTES: usize = 8;
+
+// Decode one entry of `input` with `parse`, keeping only where it ends.
+fn skip<'a, T>(input: &'a [u8], parse: fn(&'a [u8]) -> IResult<&'a [u8], T>) -> IResult<&'a [u8], ()> {
+    parse(input).map(|(rest, _)| (rest, ()))
+}
+
+fn leb128(input: &[u8]) -> IResult<&[u8], u32> {
+    leb128_u32::<nom::error::Error<_>>(input)
+}
+
+struct Annotator<'b> {
+    bytes: &'b [u8],
+    // End of the bytes annotated so far.
+    pos: usize,
+    // Function imports seen, to number the bodies in the code section.
+    imported_funcs: u32,
+    text: String,
+}
+
+impl<'b> Annotator<'b> {
+    // Annotate `part`, a subslice of the module, with `note`.
+    fn run(&mut self, part: &[u8], note: &str) {
+        let Some(span) = span_in(self.bytes, part) else { return };
+        if span.start > self.pos {
+            self.lines(self.pos, span.start, "?");
+        }
+        self.lines(span.start, span.end, note);
+    }
+
+    fn lines(&mut self, start: usize, end: usize, note: &str) {
+        for (i, row) in self.bytes[start..end].chunks(ROW_BYTES).enumerate() {
+            let hex: Vec<String> = row.iter().map(|b| format!("{:02x}", b)).collect();
+            let _ = match i {
+                0 => writeln!(self.text, "{:08x}: {:<width$}  ; {}", start, hex.join(" "), note, width = ROW_BYTES * 3 - 1),
+                _ => writeln!(self.text, "{:08x}: {}", start + i * ROW_BYTES, hex.join(" ")),
+            };
+        }
+        self.pos = self.pos.max(end);
+    }
+
+    // Annotate the bytes of `input` that `parse` consumes, returning the rest.
+    fn step<T>(&mut self, input: &'b [u8], parse: impl Fn(&'b [u8]) -> IResult<&'b [u8], T>, what: &str, note: impl Fn(&T) -> String) -> anyhow::Result<(&'b [u8], T)> {
+        let (rest, value) = parse(input).map_err(|e| AwwasmParseError::nom(what, e).within(input))?;
+        self.run(&input[..input.len() - rest.len()], &note(&value));
+        Ok((rest, value))
+    }
+
+    fn module(&mut self) -> anyhow::Result<()> {
+        let bytes = self.bytes;
+        let preamble = WASM_PREAMBLE_MAGIC_SIZE_BYTES + WASM_PREAMBLE_VERSION_SIZE_BYTES;
+        if bytes.len() < preamble {
+            anyhow::bail!("the module is shorter than its {} byte preamble", preamble);
+        }
+        self.run(&bytes[..WASM_PREAMBLE_MAGIC_SIZE_BYTES], "magic");
+        let version = &bytes[WASM_PREAMBLE_MAGIC_SIZE_BYTES..preamble];
+        self.run(version, &format!("version {}", u32::from_le_bytes([version[0], version[1], version[2], version[3]])));
+
+        let mut input = &bytes[preamble..];
+        while !input.is_empty() {
+            let (_, header) = AwwasmSectionHeader::parse(input).map_err(|e| AwwasmParseError::section_header("section header", e).within(input))?;
+            let code = header.section_type;
+            self.run(&input[..1], &format!("section {:?}", code));
+            let (rest, size) = self.step(&input[1..], leb128, "section size", |size| format!("section size {}", size))?;
+            let body = rest.get(..size as usize).ok_or_else(|| AwwasmParseError::from(TruncatedSection { section: code.clone(), declared: size, available: rest.len() }))?;
+            self.section(&code, body)?;
+            input = &rest[size as usize..];
+        }
+        Ok(())
+    }
+
+    fn section(&mut self, code: &SectionCode, body: &'b [u8]) -> anyhow::Result<()> {
+        let mut input = body;
+        match code {
+            SectionCode::Custom => {
+                let (rest, _) = self.step(input, AwwasmName::parse, "custom section name", |name| format!("name {:?}", String::from_utf8_lossy(&name.bytes)))?;
+                self.run(rest, &format!("payload, {} bytes", rest.len()));
+                return Ok(());
+            }
+            SectionCode::Start => {
+                (input, _) = self.step(input, leb128, "start section", |idx| format!("start function {}", idx))?;
+            }
+            SectionCode::DataCount => {
+                (input, _) = self.step(input, leb128, "data count section", |count| format!("data count {}", count))?;
+            }
+            _ => {
+                let (rest, count) = self.step(input, leb128, "entry count", |count| format!("{} entries", count))?;
+                input = rest;
+                for i in 0..count {
+                    input = self.entry(code, i, input)?;
+                }
+            }
+        }
+        if !input.is_empty() {
+            self.run(input, "trailing bytes");
+        }
+        Ok(())
+    }
+
+    fn entry(&mut self, code: &SectionCode, i: u32, input: &'b [u8]) -> anyhow::Result<&'b [u8]> {
+        let what = format!("{:?} Section", code);
+        let (rest, note) = match code {
+            SectionCode::Import => {
+                let (rest, import) = AwwasmImportSectionItem::parse(input).map_err(|e| AwwasmParseError::nom(&what, e).within(input))?;
+                self.imported_funcs += u32::from(import.kind == AwwasmImportKind::Function);
+                let names = (String::from_utf8_lossy(&import.module.bytes), String::from_utf8_lossy(&import.name.bytes));
+                (rest, format!("import[{}] {:?} {}.{}", i, import.kind, names.0, names.1))
+            }
+            SectionCode::Export => {
+                let (rest, export) = AwwasmExportSectionItem::parse(input).map_err(|e| AwwasmParseError::nom(&what, e).within(input))?;
+                (rest, format!("export[{}] {:?}", i, String::from_utf8_lossy(&export.name.bytes)))
+            }
+            SectionCode::Code => return self.body(i, input),
+            _ => {
+                let (parsed, name) = match code {
+                    SectionCode::Type => (skip(input, AwwasmTypeSectionItem::parse), "type"),
+                    SectionCode::Function => (skip(input, AwwasmFuncSectionItem::parse), "func"),
+                    SectionCode::Table => (skip(input, AwwasmTableSectionItem::parse), "table"),
+                    SectionCode::Memory => (skip(input, AwwasmMemorySectionItem::parse), "memory"),
+                    SectionCode::Global => (skip(input, AwwasmGlobalSectionItem::parse), "global"),
+                    SectionCode::Element => (skip(input, AwwasmElementSectionItem::parse), "elem segment"),
+                    SectionCode::Data => (skip(input, AwwasmDataSectionItem::parse), "data segment"),
+                    _ => (skip(input, AwwasmTagSectionItem::parse), "tag"),
+                };
+                let (rest, ()) = parsed.map_err(|e| AwwasmParseError::nom(&what, e).within(input))?;
+                let index = match code {
+                    SectionCode::Function => self.imported_funcs + i,
+                    _ => i,
+                };
+                (rest, format!("{}[{}]", name, index))
+            }
+        };
+        self.run(&input[..input.len() - rest.len()], &note);
+        Ok(rest)
+    }
+
+    // A function body: its size, local declarations, then each instruction.
+    fn body(&mut self, i: u32, input: &'b [u8]) -> anyhow::Result<&'b [u8]> {
+        let index = self.imported_funcs + i;
+        let (rest, item) = AwwasmCodeSectionItem::parse(input).map_err(|e| AwwasmParseError::nom("Code Section", e).within(input))?;
+        let size = &input[..input.len() - rest.len() - item.func_body.len()];
+        self.run(size, &format!("func[{}] body size {}", index, item.fn_body_size));
+        let (locals, code) = item.locals_and_code()?;
+        let declared: u32 = locals.iter().map(|l| l.type_count).sum();
+        self.run(&item.func_body[..item.func_body.len() - code.len()], &format!("{} locals", declared));
+        for instr in &item.instructions()? {
+            self.instruction(instr, 0);
+        }
+        Ok(rest)
+    }
+
+    fn instruction(&mut self, instr: &AwwasmInstruction, depth: usize) {
+        let indent = "  ".repeat(depth);
+        self.run(instr.encoding, &format!("{}{}", indent, instr));
+        for (instrs, marker) in instr.operands.bodies() {
+            for instr in instrs {
+                self.instruction(instr, depth + 1);
+            }
+            self.run(marker, &format!("{}{}", indent, marker_text(marker)));
+        }
+    }
+}
+
+impl AwwasmModule<'_> {
+    /// The module's bytes, one line per field with what it encodes: the
+    /// preamble, each section's id and size, entry counts, each entry, and
+    /// in function bodies the local declarations and every instruction
+    /// with its immediates.
+    ///
+    /// ```text
+    /// 00000008: 01                      ; section Type
+    /// 00000009: 06                      ; section size 6
+    /// 0000000a: 01                      ; 1 entries
+    /// 0000000b: 60 01 7f 01 7f          ; type[0]
+    /// ```
+    ///
+    /// Decoding stops at the first malformed field; the bytes from there
+    /// on are listed under a note with the error. Bytes that no field
+    /// covers are noted `?`.
+    pub fn annotated_hexdump(bytes: &[u8]) -> String {
+        let mut annotator = Annotator { bytes, pos: 0, imported_funcs: 0, text: String::new() };
+        if let Err(e) = annotator.module() {
+            let rest = &bytes[annotator.pos..];
+            annotator.run(rest, &format!("not decoded: {}", e));
+        }
+        annotator.text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn annotated_hexdump_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (import "env" "f" (func (param i32)))
+                (func (export "run") (param i32) (result i32) (local i64)
+                    (if (result i32) (local.get 0) (then (i32.const 1)) (else (i32.const 2)))))
+        "#)?;
+        assert_eq!(AwwasmModule::annotated_hexdump(&bytes), "\
+00000000: 00 61 73 6d              ; magic
+00000004: 01 00 00 00              ; version 1
+00000008: 01                       ; section Type
+00000009: 0a                       ; section size 10
+0000000a: 02                       ; 2 entries
+0000000b: 60 01 7f 00              ; type[0]
+0000000f: 60 01 7f 01 7f           ; type[1]
+00000014: 02                       ; section Import
+00000015: 09                       ; section size 9
+00000016: 01                       ; 1 entries
+00000017: 03 65 6e 76 01 66 00 00  ; import[0] Function env.f
+0000001f: 03                       ; section Function
+00000020: 02                       ; section size 2
+00000021: 01                       ; 1 entries
+00000022: 01                       ; func[1]
+00000023: 07                       ; section Export
+00000024: 07                       ; section size 7
+00000025: 01                       ; 1 entries
+00000026: 03 72 75 6e 00 01        ; export[0] \"run\"
+0000002c: 0a                       ; section Code
+0000002d: 10                       ; section size 16
+0000002e: 01                       ; 1 entries
+0000002f: 0e                       ; func[1] body size 14
+00000030: 01 01 7e                 ; 1 locals
+00000033: 20 00                    ; local.get 0
+00000035: 04 7f                    ; if i32
+00000037: 41 01                    ;   i32.const 1
+00000039: 05                       ; else
+0000003a: 41 02                    ;   i32.const 2
+0000003c: 0b                       ; end
+0000003d: 0b                       ; end
+");
+
+        // A cut-off section leaves the rest of the module listed with the error.
+        let dump = AwwasmModule::annotated_hexdump(&bytes[..bytes.len() - 1]);
+        let tail: Vec<_> = dump.lines().skip_while(|line| !line.contains("not decoded")).collect();
+        assert_eq!(tail, [
+            "0000002e: 01 0e 01 01 7e 20 00 04  ; not decoded: Failed to parse WASM Code Section (Code section): Code section declares 16 bytes but only 15 are available",
+            "00000036: 7f 41 01 05 41 02 0b",
+        ]);
+        Ok(())
+    }
+}
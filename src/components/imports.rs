@@ -0,0 +1,83 @@
+use crate::components::module::AwwasmModule;
+use crate::components::types::*;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// An import with its index in the index space of its kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmImportEntry<'m, 'a> {
+    /// Index in the function, table, memory, global or tag index space.
+    pub index: u32,
+    pub import: &'m AwwasmImportSectionItem<'a>,
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Every import with its index, in declaration order.
+    pub fn import_entries(&self) -> impl Iterator<Item = AwwasmImportEntry<'_, 'a>> + '_ {
+        let mut counts = [0u32; 5];
+        self.imports().iter().map(move |import| {
+            let count = &mut counts[import.kind.clone() as usize];
+            let index = *count;
+            *count += 1;
+            AwwasmImportEntry { index, import }
+        })
+    }
+
+    /// The imports of `kind`, in index order.
+    pub fn imports_of_kind(&self, kind: AwwasmImportKind) -> impl Iterator<Item = AwwasmImportEntry<'_, 'a>> + '_ {
+        self.import_entries().filter(move |entry| entry.import.kind == kind)
+    }
+
+    /// The imports grouped by module name, each group in declaration order.
+    /// Names that are not valid UTF-8 are converted lossily.
+    pub fn imports_by_module(&self) -> BTreeMap<Cow<'_, str>, Vec<AwwasmImportEntry<'_, 'a>>> {
+        let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for entry in self.import_entries() {
+            groups.entry(String::from_utf8_lossy(&entry.import.module.bytes)).or_default().push(entry);
+        }
+        groups
+    }
+
+    /// The import of `name` from `module`, if any.
+    pub fn find_import(&self, module: &str, name: &str) -> Option<AwwasmImportEntry<'_, 'a>> {
+        self.import_entries()
+            .find(|entry| entry.import.module.bytes == module.as_bytes() && entry.import.name.bytes == name.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+    use crate::components::types::AwwasmImportKind;
+
+    #[test]
+    fn imports_by_module_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (import "wasi" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+                (import "env" "memory" (memory 1))
+                (import "env" "log" (func (param i32)))
+                (import "wasi" "proc_exit" (func (param i32)))
+                (import "env" "sp" (global (mut i32))))
+        "#)?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        module.resolve_all_sections()?;
+
+        let mut groups: Vec<(String, Vec<(&str, u32)>)> = Vec::new();
+        for (m, entries) in module.imports_by_module() {
+            let names = entries.iter().map(|e| Ok((e.import.name.as_str()?, e.index))).collect::<anyhow::Result<_>>()?;
+            groups.push((m.into_owned(), names));
+        }
+        assert_eq!(groups, vec![
+            ("env".to_string(), vec![("memory", 0), ("log", 1), ("sp", 0)]),
+            ("wasi".to_string(), vec![("fd_write", 0), ("proc_exit", 2)]),
+        ]);
+
+        let funcs = module.imports_of_kind(AwwasmImportKind::Function).map(|e| Ok((e.import.name.as_str()?, e.index))).collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(funcs, vec![("fd_write", 0), ("log", 1), ("proc_exit", 2)]);
+        assert_eq!(module.imports_of_kind(AwwasmImportKind::Table).count(), 0);
+        assert_eq!(module.find_import("env", "sp").map(|e| e.import.kind.clone()), Some(AwwasmImportKind::Global));
+        assert!(module.find_import("env", "fd_write").is_none());
+        Ok(())
+    }
+}
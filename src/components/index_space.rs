@@ -0,0 +1,222 @@
+use crate::components::module::AwwasmModule;
+use crate::components::types::*;
+
+/// An entry of an index space: an import, or a definition in this module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmIndexed<'m, 'a, D> {
+    Imported(&'m AwwasmImportSectionItem<'a>),
+    Defined(D),
+}
+
+/// A function defined in the module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmDefinedFunc<'m, 'a> {
+    /// Position among the defined functions, in the function and code sections.
+    pub defined_idx: u32,
+    pub type_idx: TypeIdx,
+    /// `None` if the code section has fewer bodies than declared functions.
+    pub code: Option<&'m AwwasmCodeSectionItem<'a>>,
+}
+
+/// A function in the function index space.
+pub type AwwasmFunc<'m, 'a> = AwwasmIndexed<'m, 'a, AwwasmDefinedFunc<'m, 'a>>;
+
+impl AwwasmFunc<'_, '_> {
+    /// The function's type index, for imports and definitions alike.
+    pub fn type_idx(&self) -> TypeIdx {
+        match self {
+            // Function imports always carry a type index.
+            AwwasmIndexed::Imported(import) => import.func_type_idx.unwrap_or_default(),
+            AwwasmIndexed::Defined(def) => def.type_idx,
+        }
+    }
+}
+
+/// The function, table, memory, global and tag index spaces of a module,
+/// with imports numbered before definitions as the binary format does.
+///
+/// Built by `AwwasmModule::index_spaces()` from the resolved sections.
+/// Lookups are by index in the space, e.g. `spaces.func(call.funcidx)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmIndexSpaces<'m, 'a> {
+    module: &'m AwwasmModule<'a>,
+    // Imports of each kind, in index order.
+    funcs: Vec<&'m AwwasmImportSectionItem<'a>>,
+    tables: Vec<&'m AwwasmImportSectionItem<'a>>,
+    memories: Vec<&'m AwwasmImportSectionItem<'a>>,
+    globals: Vec<&'m AwwasmImportSectionItem<'a>>,
+    tags: Vec<&'m AwwasmImportSectionItem<'a>>,
+}
+
+// Look `idx` up in a space of `imports` followed by `defined`.
+fn lookup<'m, 'a, T, D>(
+    imports: &[&'m AwwasmImportSectionItem<'a>],
+    defined: &'m [T],
+    idx: u32,
+    def: impl FnOnce(u32, &'m T) -> D,
+) -> Option<AwwasmIndexed<'m, 'a, D>> {
+    let idx = idx as usize;
+    if let Some(import) = imports.get(idx) {
+        return Some(AwwasmIndexed::Imported(import));
+    }
+    let defined_idx = idx - imports.len();
+    defined.get(defined_idx).map(|item| AwwasmIndexed::Defined(def(defined_idx as u32, item)))
+}
+
+fn space_len<T>(imports: &[&AwwasmImportSectionItem], defined: &[T]) -> u32 {
+    (imports.len() + defined.len()) as u32
+}
+
+impl<'m, 'a> AwwasmIndexSpaces<'m, 'a> {
+    pub fn func(&self, idx: FuncIdx) -> Option<AwwasmFunc<'m, 'a>> {
+        let code = self.module.code();
+        lookup(&self.funcs, self.module.funcs(), idx, |defined_idx, func| AwwasmDefinedFunc {
+            defined_idx,
+            type_idx: func.type_item_idx,
+            code: code.get(defined_idx as usize),
+        })
+    }
+
+    pub fn table(&self, idx: TableIdx) -> Option<AwwasmIndexed<'m, 'a, &'m AwwasmTableSectionItem>> {
+        lookup(&self.tables, self.module.tables(), idx, |_, table| table)
+    }
+
+    pub fn memory(&self, idx: MemIdx) -> Option<AwwasmIndexed<'m, 'a, &'m AwwasmMemorySectionItem>> {
+        lookup(&self.memories, self.module.memories(), idx, |_, memory| memory)
+    }
+
+    pub fn global(&self, idx: GlobalIdx) -> Option<AwwasmIndexed<'m, 'a, &'m AwwasmGlobalSectionItem<'a>>> {
+        lookup(&self.globals, self.module.globals(), idx, |_, global| global)
+    }
+
+    pub fn tag(&self, idx: TagIdx) -> Option<AwwasmIndexed<'m, 'a, &'m AwwasmTagSectionItem>> {
+        lookup(&self.tags, self.module.tags(), idx, |_, tag| tag)
+    }
+
+    /// Signature of the function at `idx`.
+    pub fn signature(&self, idx: FuncIdx) -> Option<&'m AwwasmTypeSectionItem<'a>> {
+        self.module.types().get(self.func(idx)?.type_idx() as usize)
+    }
+
+    /// Every function, imports first, with its index.
+    pub fn funcs(&self) -> impl Iterator<Item = (FuncIdx, AwwasmFunc<'m, 'a>)> + '_ {
+        (0..self.func_count()).filter_map(|idx| Some((idx, self.func(idx)?)))
+    }
+
+    pub fn func_count(&self) -> u32 {
+        space_len(&self.funcs, self.module.funcs())
+    }
+
+    pub fn table_count(&self) -> u32 {
+        space_len(&self.tables, self.module.tables())
+    }
+
+    pub fn memory_count(&self) -> u32 {
+        space_len(&self.memories, self.module.memories())
+    }
+
+    pub fn global_count(&self) -> u32 {
+        space_len(&self.globals, self.module.globals())
+    }
+
+    pub fn tag_count(&self) -> u32 {
+        space_len(&self.tags, self.module.tags())
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// The module's index spaces, for looking items up by the indices
+    /// instructions, exports and segments use. Requires
+    /// `resolve_all_sections()`.
+    pub fn index_spaces(&self) -> AwwasmIndexSpaces<'_, 'a> {
+        let mut spaces = AwwasmIndexSpaces {
+            module: self,
+            funcs: Vec::new(),
+            tables: Vec::new(),
+            memories: Vec::new(),
+            globals: Vec::new(),
+            tags: Vec::new(),
+        };
+        for import in self.imports() {
+            match import.kind {
+                AwwasmImportKind::Function => spaces.funcs.push(import),
+                AwwasmImportKind::Table => spaces.tables.push(import),
+                AwwasmImportKind::Memory => spaces.memories.push(import),
+                AwwasmImportKind::Global => spaces.globals.push(import),
+                AwwasmImportKind::Tag => spaces.tags.push(import),
+            }
+        }
+        spaces
+    }
+
+    /// Signature of the function at `idx` in the function index space,
+    /// whether imported or defined. `None` if either index is out of range.
+    pub fn signature_of(&self, idx: FuncIdx) -> Option<&AwwasmTypeSectionItem<'a>> {
+        self.index_spaces().signature(idx)
+    }
+
+    /// The function at `idx` in the function index space; see
+    /// `index_spaces()` for looking up many.
+    pub fn func(&self, idx: FuncIdx) -> Option<AwwasmFunc<'_, 'a>> {
+        self.index_spaces().func(idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::index_space::{AwwasmDefinedFunc, AwwasmIndexed};
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn index_spaces_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (type (func))
+                (type (func (param i32)))
+                (import "env" "f" (func (type 1)))
+                (import "env" "g" (global i32))
+                (global i64 (i64.const 0))
+                (memory 1)
+                (func (type 0))
+                (func (type 1)))
+        "#)?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        module.resolve_all_sections()?;
+        let spaces = module.index_spaces();
+
+        let AwwasmIndexed::Imported(import) = module.func(0).expect("function 0 should exist") else { panic!("function 0 should be imported") };
+        assert_eq!(import.name.as_str()?, "f");
+        let code = module.code.as_ref().map(|c| &c[1]);
+        assert_eq!(spaces.func(2), Some(AwwasmIndexed::Defined(AwwasmDefinedFunc { defined_idx: 1, type_idx: 1, code })));
+        assert_eq!(spaces.func(3), None);
+        let types: Vec<_> = spaces.funcs().map(|(idx, f)| (idx, f.type_idx())).collect();
+        assert_eq!(types, vec![(0, 1), (1, 0), (2, 1)]);
+
+        assert!(matches!(spaces.global(0), Some(AwwasmIndexed::Imported(_))));
+        assert!(matches!(spaces.global(1), Some(AwwasmIndexed::Defined(g)) if g.value_type.to_string() == "i64"));
+        assert!(matches!(spaces.memory(0), Some(AwwasmIndexed::Defined(_))));
+        assert_eq!((spaces.table(0), spaces.tag(0)), (None, None));
+        assert_eq!((spaces.func_count(), spaces.global_count(), spaces.memory_count()), (3, 2, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn signature_of_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (type $none (func))
+                (type $i32 (func (param i32) (result i32)))
+                (import "env" "g" (global i32))
+                (import "env" "f" (func (type $i32)))
+                (func (type $none))
+                (func (type $i32) (local.get 0)))
+        "#)?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        module.resolve_all_sections()?;
+        let types = module.types.as_ref().expect("types should exist");
+        let signatures: Vec<_> = (0..4).map(|idx| module.signature_of(idx)).collect();
+        assert_eq!(signatures, vec![Some(&types[1]), Some(&types[0]), Some(&types[1]), None]);
+        assert_eq!(module.index_spaces().signature(2), Some(&types[1]));
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+use crate::components::instructions::*;
+use crate::components::module::AwwasmModule;
+use crate::components::types::*;
+use std::fmt;
+
+/// An index space of a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AwwasmIndexSpace {
+    Type,
+    Function,
+    Table,
+    Memory,
+    Global,
+    Data,
+    Tag,
+}
+
+impl AwwasmIndexSpace {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AwwasmIndexSpace::Type => "type",
+            AwwasmIndexSpace::Function => "function",
+            AwwasmIndexSpace::Table => "table",
+            AwwasmIndexSpace::Memory => "memory",
+            AwwasmIndexSpace::Global => "global",
+            AwwasmIndexSpace::Data => "data segment",
+            AwwasmIndexSpace::Tag => "tag",
+        }
+    }
+}
+
+/// Where `validate_indices()` found an index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmIndexLocation {
+    /// The import at this position in the import section.
+    Import(u32),
+    /// The declaration of a function, by index in the function index space.
+    Function(u32),
+    /// A global's init expression, by index in the global index space.
+    Global(u32),
+    /// The export at this position in the export section.
+    Export(u32),
+    Start,
+    Element(u32),
+    Data(u32),
+    /// A tag definition, by index in the tag index space.
+    Tag(u32),
+    /// An instruction, at a byte offset from the start of the function body
+    /// (its local declarations included).
+    Instruction { func_idx: u32, offset: usize },
+}
+
+impl fmt::Display for AwwasmIndexLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AwwasmIndexLocation::Import(i) => write!(f, "import {}", i),
+            AwwasmIndexLocation::Function(i) => write!(f, "function {}", i),
+            AwwasmIndexLocation::Global(i) => write!(f, "global {}", i),
+            AwwasmIndexLocation::Export(i) => write!(f, "export {}", i),
+            AwwasmIndexLocation::Start => f.write_str("start section"),
+            AwwasmIndexLocation::Element(i) => write!(f, "element segment {}", i),
+            AwwasmIndexLocation::Data(i) => write!(f, "data segment {}", i),
+            AwwasmIndexLocation::Tag(i) => write!(f, "tag {}", i),
+            AwwasmIndexLocation::Instruction { func_idx, offset } => write!(f, "function {} at offset {:#x}", func_idx, offset),
+        }
+    }
+}
+
+/// An index past the end of its index space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmIndexViolation {
+    pub location: AwwasmIndexLocation,
+    pub space: AwwasmIndexSpace,
+    pub index: u32,
+    /// Size of the index space, imports included.
+    pub count: u32,
+}
+
+impl fmt::Display for AwwasmIndexViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} {} is out of range, the module has {}", self.location, self.space.name(), self.index, self.count)
+    }
+}
+
+// Indices an instruction's immediates reference, nested bodies excluded.
+fn instruction_indices(operands: &AwwasmOperands) -> Vec<(AwwasmIndexSpace, u32)> {
+    use AwwasmIndexSpace as S;
+    use AwwasmOperands::*;
+    let mut indices = Vec::new();
+    if let Some(m) = operands.mem_arg() {
+        indices.push((S::Memory, m.memory()));
+    }
+    if let Some(BlockType::TypeIndex(idx)) = operands.block_type() {
+        indices.push((S::Type, idx));
+    }
+    match operands {
+        Call(op) | ReturnCall(op) | RefFunc(op) => indices.push((S::Function, op.funcidx)),
+        CallIndirect(op) | ReturnCallIndirect(op) => indices.extend([(S::Type, op.typeidx), (S::Table, op.tableidx)]),
+        CallRef(op) | ReturnCallRef(op) => indices.push((S::Type, op.typeidx)),
+        RefNull(RefNullOperands { heap_type: AwwasmHeapType::Type(idx) }) => indices.push((S::Type, *idx)),
+        GlobalGet(op) | GlobalSet(op) => indices.push((S::Global, op.index)),
+        MemorySize(op) | MemoryGrow(op) => indices.push((S::Memory, op.memidx)),
+        Throw(op) | Catch(op) => indices.push((S::Tag, op.index)),
+        Try(op) => indices.extend(op.handlers.iter().filter_map(|h| h.tag).map(|tag| (S::Tag, tag))),
+        TryTable(op) => indices.extend(op.catches.iter().filter_map(|c| c.tag).map(|tag| (S::Tag, tag))),
+        Misc(op) => match &op.operands {
+            AwwasmMiscOperands::MemoryInit(m) => indices.extend([(S::Data, m.dataidx), (S::Memory, m.memidx)]),
+            AwwasmMiscOperands::DataDrop(d) => indices.push((S::Data, d.dataidx)),
+            AwwasmMiscOperands::MemoryCopy(m) => indices.extend([(S::Memory, m.dst_memidx), (S::Memory, m.src_memidx)]),
+            AwwasmMiscOperands::MemoryFill(m) => indices.push((S::Memory, m.index)),
+            _ => {}
+        },
+        _ => {}
+    }
+    indices
+}
+
+// Sizes of the index spaces, imports included.
+struct IndexSpaces {
+    types: u32,
+    funcs: u32,
+    tables: u32,
+    memories: u32,
+    globals: u32,
+    data: u32,
+    tags: u32,
+}
+
+impl IndexSpaces {
+    fn count(&self, space: AwwasmIndexSpace) -> u32 {
+        match space {
+            AwwasmIndexSpace::Type => self.types,
+            AwwasmIndexSpace::Function => self.funcs,
+            AwwasmIndexSpace::Table => self.tables,
+            AwwasmIndexSpace::Memory => self.memories,
+            AwwasmIndexSpace::Global => self.globals,
+            AwwasmIndexSpace::Data => self.data,
+            AwwasmIndexSpace::Tag => self.tags,
+        }
+    }
+}
+
+struct IndexChecker {
+    spaces: IndexSpaces,
+    violations: Vec<AwwasmIndexViolation>,
+}
+
+impl IndexChecker {
+    fn check(&mut self, location: AwwasmIndexLocation, space: AwwasmIndexSpace, index: u32) {
+        let count = self.spaces.count(space);
+        if index >= count {
+            self.violations.push(AwwasmIndexViolation { location, space, index, count });
+        }
+    }
+
+    fn check_expr(&mut self, location: AwwasmIndexLocation, expr: &AwwasmDataInitExpr) {
+        for instr in InstructionIterator::new(expr.code).flatten() {
+            for (space, index) in instruction_indices(&instr.operands) {
+                self.check(location.clone(), space, index);
+            }
+        }
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Check every index the module references, in its sections and in its
+    /// function bodies, against the index spaces it defines (imports
+    /// included). Returns the out-of-range ones in section order; empty
+    /// means every index resolves.
+    ///
+    /// The data index space is sized by the data count section when there
+    /// is one, else by the data section. Bodies that fail to decode are
+    /// skipped; `validate()` reports them. Requires `resolve_all_sections()`.
+    pub fn validate_indices(&self) -> Vec<AwwasmIndexViolation> {
+        use AwwasmIndexLocation as L;
+        use AwwasmIndexSpace as S;
+        let mut checker = IndexChecker {
+            spaces: IndexSpaces {
+                types: self.types().len() as u32,
+                funcs: self.func_count(),
+                tables: self.table_types().len() as u32,
+                memories: self.memory_count(),
+                globals: self.global_count(),
+                data: self.data_count.unwrap_or_else(|| self.data().len() as u32),
+                tags: self.tag_count(),
+            },
+            violations: Vec::new(),
+        };
+
+        for (i, import) in self.imports().iter().enumerate() {
+            let type_idx = import.func_type_idx.or(import.tag.as_ref().map(|t| t.type_idx));
+            if let Some(idx) = type_idx {
+                checker.check(L::Import(i as u32), S::Type, idx);
+            }
+        }
+        let imported_funcs = self.imported_func_count();
+        for (i, func) in self.funcs().iter().enumerate() {
+            checker.check(L::Function(imported_funcs + i as u32), S::Type, func.type_item_idx);
+        }
+        let imported_globals = self.global_count() - self.globals().len() as u32;
+        for (i, global) in self.globals().iter().enumerate() {
+            checker.check_expr(L::Global(imported_globals + i as u32), &global.init_expr);
+        }
+        let imported_tags = self.tag_count() - self.tags().len() as u32;
+        for (i, tag) in self.tags().iter().enumerate() {
+            checker.check(L::Tag(imported_tags + i as u32), S::Type, tag.type_idx);
+        }
+        for (i, export) in self.exports().iter().enumerate() {
+            let space = match export.kind {
+                AwwasmExportKind::Function => S::Function,
+                AwwasmExportKind::Table => S::Table,
+                AwwasmExportKind::Memory => S::Memory,
+                AwwasmExportKind::Global => S::Global,
+                AwwasmExportKind::Tag => S::Tag,
+            };
+            checker.check(L::Export(i as u32), space, export.index);
+        }
+        if let Some(start) = &self.start {
+            checker.check(L::Start, S::Function, start.func_idx);
+        }
+        for (i, element) in self.elements().iter().enumerate() {
+            let location = L::Element(i as u32);
+            let (table, funcs): (Option<u32>, &[u32]) = match &element.body {
+                AwwasmElemSegmentBody::ActiveImplicit(s) => (Some(0), &s.func_indices),
+                AwwasmElemSegmentBody::ActiveExplicit(s) => (Some(s.tableidx), &s.func_indices),
+                AwwasmElemSegmentBody::Passive(s) => (None, &s.func_indices),
+                AwwasmElemSegmentBody::Declarative(s) => (None, &s.func_indices),
+                AwwasmElemSegmentBody::ActiveImplicitExprs(_) => (Some(0), &[]),
+                AwwasmElemSegmentBody::ActiveExplicitExprs(s) => (Some(s.tableidx), &[]),
+                AwwasmElemSegmentBody::PassiveExprs(_) | AwwasmElemSegmentBody::DeclarativeExprs(_) => (None, &[]),
+            };
+            if let Some(table) = table {
+                checker.check(location.clone(), S::Table, table);
+            }
+            for func in funcs {
+                checker.check(location.clone(), S::Function, *func);
+            }
+            for expr in element.body.init_exprs() {
+                checker.check_expr(location.clone(), expr);
+            }
+        }
+        for (i, data) in self.data().iter().enumerate() {
+            let location = L::Data(i as u32);
+            if data.header.offset.is_some() {
+                checker.check(location.clone(), S::Memory, data.header.memidx.unwrap_or(0));
+            }
+            if let Some(offset) = &data.header.offset {
+                checker.check_expr(location, offset);
+            }
+        }
+        for (i, item) in self.code().iter().enumerate() {
+            let func_idx = imported_funcs + i as u32;
+            let Ok(instrs) = item.instructions_with_limits(&self.limits) else { continue };
+            for instr in &instrs {
+                instr.walk(&mut |instr| {
+                    let offset = span_in(item.func_body, instr.encoding).map_or(0, |s| s.start);
+                    for (space, index) in instruction_indices(&instr.operands) {
+                        checker.check(L::Instruction { func_idx, offset }, space, index);
+                    }
+                });
+            }
+        }
+        checker.violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::indices::{AwwasmIndexLocation, AwwasmIndexSpace, AwwasmIndexViolation};
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn validate_indices_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "g" (global i32))
+                (memory 1)
+                (table 1 funcref)
+                (global i32 (global.get 3))
+                (func $f (param i32)
+                    (call 7 (local.get 0))
+                    (global.set 9 (i32.const 0))
+                    (data.drop 2))
+                (elem (i32.const 0) func $f 5)
+                (data (memory 4) (i32.const 0) "x")
+                (export "t" (table 2))
+                (start 6)
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let violations: Vec<String> = module_parsed.validate_indices().iter().map(|v| v.to_string()).collect();
+        assert_eq!(violations, vec![
+            "global 1: global 3 is out of range, the module has 2",
+            "export 0: table 2 is out of range, the module has 1",
+            "start section: function 6 is out of range, the module has 1",
+            "element segment 0: function 5 is out of range, the module has 1",
+            "data segment 0: memory 4 is out of range, the module has 1",
+            "function 0 at offset 0x3: function 7 is out of range, the module has 1",
+            "function 0 at offset 0x7: global 9 is out of range, the module has 2",
+            "function 0 at offset 0x9: data segment 2 is out of range, the module has 1",
+        ]);
+        assert_eq!(module_parsed.validate_indices()[0], AwwasmIndexViolation {
+            location: AwwasmIndexLocation::Global(1),
+            space: AwwasmIndexSpace::Global,
+            index: 3,
+            count: 2,
+        });
+
+        let valid = wat::parse_str(r#"(module (func $f (call $f)) (table 1 funcref) (elem (i32.const 0) $f) (export "f" (func $f)))"#)?;
+        let mut valid_parsed = AwwasmModule::new(&valid)?;
+        valid_parsed.resolve_all_sections()?;
+        assert!(valid_parsed.validate_indices().is_empty());
+        Ok(())
+    }
+}
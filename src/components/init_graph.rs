@@ -0,0 +1,213 @@
+use crate::components::error::AwwasmParseError;
+use crate::components::instructions::{AwwasmOperands, InstructionIterator};
+use crate::components::module::AwwasmModule;
+use crate::components::types::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Something a loader initializes: a global, memory or table (index spaces
+/// include imports), or a data or element segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AwwasmInitNode {
+    Global(u32),
+    Memory(u32),
+    Table(u32),
+    DataSegment(u32),
+    ElemSegment(u32),
+}
+
+impl fmt::Display for AwwasmInitNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AwwasmInitNode::Global(i) => write!(f, "global {}", i),
+            AwwasmInitNode::Memory(i) => write!(f, "memory {}", i),
+            AwwasmInitNode::Table(i) => write!(f, "table {}", i),
+            AwwasmInitNode::DataSegment(i) => write!(f, "data segment {}", i),
+            AwwasmInitNode::ElemSegment(i) => write!(f, "element segment {}", i),
+        }
+    }
+}
+
+/// Dependencies between a module's globals, memories, tables and segments:
+/// a global depends on the globals its init expression reads, and an active
+/// segment on the memory or table it targets and the globals in its offset
+/// and item expressions.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmInitGraph {
+    /// Every node and what it depends on.
+    pub edges: BTreeMap<AwwasmInitNode, BTreeSet<AwwasmInitNode>>,
+}
+
+impl AwwasmInitGraph {
+    /// What `node` depends on; empty for unknown nodes.
+    pub fn dependencies(&self, node: AwwasmInitNode) -> impl Iterator<Item = AwwasmInitNode> + '_ {
+        self.edges.get(&node).into_iter().flatten().copied()
+    }
+
+    /// Every node, dependencies first. Among nodes that are ready at the same
+    /// time, the smallest (in `AwwasmInitNode` order) comes first.
+    ///
+    /// Fails if the dependencies form a cycle.
+    pub fn topological_order(&self) -> anyhow::Result<Vec<AwwasmInitNode>> {
+        let mut pending: BTreeMap<AwwasmInitNode, usize> = self.edges.iter().map(|(n, deps)| (*n, deps.len())).collect();
+        let mut dependents: BTreeMap<AwwasmInitNode, Vec<AwwasmInitNode>> = BTreeMap::new();
+        for (node, deps) in &self.edges {
+            for dep in deps {
+                dependents.entry(*dep).or_default().push(*node);
+            }
+        }
+
+        let mut ready: BTreeSet<AwwasmInitNode> = pending.iter().filter(|(_, n)| **n == 0).map(|(node, _)| *node).collect();
+        let mut order = Vec::with_capacity(self.edges.len());
+        while let Some(node) = ready.pop_first() {
+            order.push(node);
+            for dependent in dependents.get(&node).into_iter().flatten() {
+                let count = pending.get_mut(dependent).expect("every dependent is a node");
+                *count -= 1;
+                if *count == 0 {
+                    ready.insert(*dependent);
+                }
+            }
+        }
+
+        if order.len() < self.edges.len() {
+            let stuck = pending.iter().find(|(_, n)| **n > 0).map(|(node, _)| *node).expect("a node is left");
+            return Err(anyhow::anyhow!("Initialization cycle involving {}", stuck));
+        }
+        Ok(order)
+    }
+}
+
+// Globals read by `global.get` in a constant expression.
+fn globals_read(expr: &AwwasmDataInitExpr) -> anyhow::Result<Vec<u32>> {
+    let mut globals = Vec::new();
+    for instr in InstructionIterator::new(expr.code) {
+        let instr = instr.map_err(|e| AwwasmParseError::instruction("init expression", e))?;
+        if let AwwasmOperands::GlobalGet(op) = instr.operands {
+            globals.push(op.index);
+        }
+    }
+    Ok(globals)
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Build the initialization dependency graph.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn init_graph(&self) -> anyhow::Result<AwwasmInitGraph> {
+        let mut graph = AwwasmInitGraph::default();
+        let mut add = |node: AwwasmInitNode, deps: Vec<AwwasmInitNode>| {
+            graph.edges.entry(node).or_default().extend(deps);
+        };
+        let reads = |expr: &AwwasmDataInitExpr| -> anyhow::Result<Vec<AwwasmInitNode>> {
+            Ok(globals_read(expr)?.into_iter().map(AwwasmInitNode::Global).collect())
+        };
+
+        for i in 0..self.memory_count() {
+            add(AwwasmInitNode::Memory(i), Vec::new());
+        }
+        for i in 0..self.table_types().len() as u32 {
+            add(AwwasmInitNode::Table(i), Vec::new());
+        }
+
+        let imported_globals = self.global_count() - self.globals().len() as u32;
+        for i in 0..imported_globals {
+            add(AwwasmInitNode::Global(i), Vec::new());
+        }
+        for (i, global) in self.globals().iter().enumerate() {
+            add(AwwasmInitNode::Global(imported_globals + i as u32), reads(&global.init_expr)?);
+        }
+
+        for (i, seg) in self.data().iter().enumerate() {
+            let mut deps = Vec::new();
+            if let Some(offset) = &seg.header.offset {
+                deps.push(AwwasmInitNode::Memory(seg.header.memidx.unwrap_or(0)));
+                deps.extend(reads(offset)?);
+            }
+            add(AwwasmInitNode::DataSegment(i as u32), deps);
+        }
+
+        for (i, seg) in self.elements().iter().enumerate() {
+            let (target, exprs): (Option<(u32, &AwwasmDataInitExpr)>, &[AwwasmDataInitExpr]) = match &seg.body {
+                AwwasmElemSegmentBody::ActiveImplicit(s) => (Some((0, &s.offset)), &[]),
+                AwwasmElemSegmentBody::ActiveExplicit(s) => (Some((s.tableidx, &s.offset)), &[]),
+                AwwasmElemSegmentBody::ActiveImplicitExprs(s) => (Some((0, &s.offset)), &s.exprs),
+                AwwasmElemSegmentBody::ActiveExplicitExprs(s) => (Some((s.tableidx, &s.offset)), &s.exprs),
+                AwwasmElemSegmentBody::PassiveExprs(s) => (None, &s.exprs),
+                AwwasmElemSegmentBody::DeclarativeExprs(s) => (None, &s.exprs),
+                AwwasmElemSegmentBody::Passive(_) | AwwasmElemSegmentBody::Declarative(_) => (None, &[]),
+            };
+            let mut deps = Vec::new();
+            if let Some((table, offset)) = target {
+                deps.push(AwwasmInitNode::Table(table));
+                deps.extend(reads(offset)?);
+            }
+            for expr in exprs {
+                deps.extend(reads(expr)?);
+            }
+            add(AwwasmInitNode::ElemSegment(i as u32), deps);
+        }
+
+        // Dependencies on items the module does not have still become nodes,
+        // so the ordering accounts for them.
+        let missing: Vec<AwwasmInitNode> = graph.edges.values().flatten()
+            .filter(|dep| !graph.edges.contains_key(dep))
+            .copied()
+            .collect();
+        for dep in missing {
+            graph.edges.entry(dep).or_default();
+        }
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::init_graph::AwwasmInitNode::*;
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn init_graph_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "base" (global $base i32))
+                (global $end i32 (global.get $heap))
+                (global $heap i32 (global.get $base))
+                (memory 1)
+                (table 2 funcref)
+                (func $f)
+                (data (global.get $heap) "x")
+                (data "passive")
+                (elem (global.get $base) func $f)
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let graph = module_parsed.init_graph()?;
+        assert_eq!(graph.dependencies(Global(1)).collect::<Vec<_>>(), vec![Global(2)]);
+        assert_eq!(graph.dependencies(DataSegment(0)).collect::<Vec<_>>(), vec![Global(2), Memory(0)]);
+        assert_eq!(graph.dependencies(DataSegment(1)).count(), 0);
+        assert_eq!(graph.dependencies(ElemSegment(0)).collect::<Vec<_>>(), vec![Global(0), Table(0)]);
+        assert_eq!(graph.topological_order()?, vec![
+            Global(0), Global(2), Global(1), Memory(0), Table(0), DataSegment(0), DataSegment(1), ElemSegment(0),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn init_graph_cycle_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (global $a i32 (global.get $b))
+                (global $b i32 (global.get $a))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let err = module_parsed.init_graph()?.topological_order().unwrap_err();
+        assert_eq!(err.to_string(), "Initialization cycle involving global 0");
+        Ok(())
+    }
+}
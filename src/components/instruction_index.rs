@@ -0,0 +1,165 @@
+use crate::components::instructions::{span_in, WasmOpCode};
+use crate::components::module::AwwasmModule;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Where an instruction appears: the function (index space includes imports)
+/// and the offset of its opcode from the start of the function body, the
+/// locals vector included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AwwasmInstructionSite {
+    pub func_idx: u32,
+    pub offset: usize,
+}
+
+/// Every instruction site in a module's code section, keyed by opcode.
+///
+/// Instructions behind the `0xFC` prefix are all filed under `WasmOpCode::Misc`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmInstructionIndex {
+    sites: HashMap<WasmOpCode, Vec<AwwasmInstructionSite>>,
+}
+
+impl AwwasmInstructionIndex {
+    /// Every site of `opcode`, in function order and then body order.
+    pub fn sites(&self, opcode: WasmOpCode) -> &[AwwasmInstructionSite] {
+        self.sites.get(&opcode).map_or(&[], Vec::as_slice)
+    }
+
+    /// Number of sites of `opcode`.
+    pub fn count(&self, opcode: WasmOpCode) -> usize {
+        self.sites(opcode).len()
+    }
+
+    /// Every opcode that appears at least once, in no particular order.
+    pub fn opcodes(&self) -> impl Iterator<Item = WasmOpCode> + '_ {
+        self.sites.keys().copied()
+    }
+}
+
+// What an index was built from: the imported function count and the
+// address and length of every function body. Bodies borrow immutable input,
+// so the same slices always hold the same bytes, and any edit to the
+// imports or the code section changes the key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct IndexKey {
+    imported: u32,
+    bodies: Vec<(usize, usize)>,
+}
+
+// The index built by `instruction_index()`, with the key it was built for.
+// Whether it has been built yet does not affect module equality.
+#[derive(Debug, Default)]
+pub(crate) struct InstructionIndexCache(Mutex<Option<(IndexKey, Arc<AwwasmInstructionIndex>)>>);
+
+impl Clone for InstructionIndexCache {
+    fn clone(&self) -> Self {
+        InstructionIndexCache(Mutex::new(self.lock().clone()))
+    }
+}
+
+impl InstructionIndexCache {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<(IndexKey, Arc<AwwasmInstructionIndex>)>> {
+        // The cache is only ever replaced whole, so a poisoned lock still
+        // holds a consistent value.
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl PartialEq for InstructionIndexCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for InstructionIndexCache {}
+
+impl<'a> AwwasmModule<'a> {
+    /// The instruction index, built on the first call and reused until the
+    /// imports or the function bodies change.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn instruction_index(&self) -> anyhow::Result<Arc<AwwasmInstructionIndex>> {
+        let key = IndexKey {
+            imported: self.imported_func_count(),
+            bodies: self.code().iter().map(|item| (item.func_body.as_ptr() as usize, item.func_body.len())).collect(),
+        };
+        if let Some((built_for, index)) = &*self.instruction_index_cache.lock() {
+            if *built_for == key {
+                return Ok(Arc::clone(index));
+            }
+        }
+        let index = Arc::new(self.build_instruction_index()?);
+        *self.instruction_index_cache.lock() = Some((key, Arc::clone(&index)));
+        Ok(index)
+    }
+
+    fn build_instruction_index(&self) -> anyhow::Result<AwwasmInstructionIndex> {
+        let mut index = AwwasmInstructionIndex::default();
+        let imported = self.imported_func_count();
+        for (i, item) in self.code().iter().enumerate() {
+            let func_idx = imported + i as u32;
+            for instr in &item.instructions_with_limits(&self.limits).map_err(|e| self.locate_error(e))? {
+                instr.walk(&mut |instr| {
+                    let offset = span_in(item.func_body, instr.encoding).map_or(0, |span| span.start);
+                    index.sites.entry(instr.opcode).or_default().push(AwwasmInstructionSite { func_idx, offset });
+                });
+            }
+        }
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::instruction_index::AwwasmInstructionSite;
+    use crate::components::instructions::WasmOpCode;
+    use crate::components::module::AwwasmModule;
+    use std::sync::Arc;
+
+    #[test]
+    fn instruction_index_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "f" (func $f))
+                (memory 1)
+                (table 1 funcref)
+                (func
+                    (drop (memory.grow (i32.const 1)))
+                    (call $f))
+                (func
+                    (block
+                        (call_indirect (i32.const 0))
+                        (drop (memory.grow (i32.const 2))))
+                    (i32.trunc_sat_f32_s (f32.const 0))
+                    (drop))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let index = module_parsed.instruction_index()?;
+        assert_eq!(index.sites(WasmOpCode::MemoryGrow), &[
+            AwwasmInstructionSite { func_idx: 1, offset: 3 },
+            AwwasmInstructionSite { func_idx: 2, offset: 10 },
+        ]);
+        assert_eq!(index.sites(WasmOpCode::CallIndirect), &[AwwasmInstructionSite { func_idx: 2, offset: 5 }]);
+        assert_eq!(index.count(WasmOpCode::Drop), 3);
+        assert_eq!(index.count(WasmOpCode::Misc), 1);
+        assert!(index.sites(WasmOpCode::Unreachable).is_empty());
+
+        // The second call reuses the index built by the first, until a body
+        // is edited.
+        assert!(Arc::ptr_eq(&index, &module_parsed.instruction_index()?));
+        let code = module_parsed.code.as_mut().expect("code should exist");
+        code[0] = code[1].clone();
+        let index = module_parsed.instruction_index()?;
+        assert_eq!(index.count(WasmOpCode::MemoryGrow), 2);
+        assert_eq!(index.count(WasmOpCode::Call), 0);
+        assert_eq!(index.sites(WasmOpCode::CallIndirect), &[
+            AwwasmInstructionSite { func_idx: 1, offset: 5 },
+            AwwasmInstructionSite { func_idx: 2, offset: 5 },
+        ]);
+        Ok(())
+    }
+}
@@ -1,22 +1,59 @@
 use crate::{consts::*};
 use nom_derive::*;
-use nom_leb128::{leb128_u32, leb128_i32, leb128_i64};
-use nom::{branch::alt, bytes::complete::tag, combinator::cond, multi::many_till};
+use crate::components::leb128::{leb128_u32, leb128_i32, leb128_i64, leb128_s33};
+use crate::components::cancel::{CancellationToken, CANCEL_CHECK_INTERVAL};
+use crate::components::floats::AwwasmFloatFormat;
+use crate::components::parse_limits::AwwasmParseLimits;
+use crate::components::types::{AwwasmHeapType, ValType};
+use num_derive::FromPrimitive;
+use nom::{IResult, branch::alt, bytes::complete::tag, combinator::{cond, map}, multi::{count, length_count}};
+use std::cell::Cell;
+use std::fmt;
+use std::ops::Range;
+
+pub(crate) const BLOCK_TYPE_EMPTY: u8 = 0x40;
+
+/// Type of a `block`, `loop`, `if`, `try` or `try_table`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockType {
+    /// No parameters or results (encoded as `0x40`).
+    Empty,
+    /// No parameters and a single result value.
+    Value(ValType),
+    /// The parameters and results of a function type, by index in the type
+    /// section (multi-value). Encoded as a non-negative s33.
+    TypeIndex(u32),
+}
 
-// BlockType using nom_derive with custom parser for the 0x40 case
-#[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Nom)]
-#[nom(LittleEndian)]
-pub enum BlockValueType {
-    VOID = 0x40,
-    I32 = 0x7F,
-    I64 = 0x7E,
-    F32 = 0x7D,
-    F64 = 0x7C,
+impl<'a> Parse<&'a [u8]> for BlockType {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        match i.first() {
+            Some(&BLOCK_TYPE_EMPTY) => Ok((&i[1..], BlockType::Empty)),
+            // A one-byte negative s33: `0x40` or a value type.
+            Some(b) if b & 0xC0 == 0x40 => map(ValType::parse, BlockType::Value)(i),
+            _ => {
+                let (rest, idx) = leb128_s33(i)?;
+                let idx = u32::try_from(idx)
+                    .map_err(|_| nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Verify)))?;
+                Ok((rest, BlockType::TypeIndex(idx)))
+            }
+        }
+    }
+}
+
+/// Block type in text form: nothing for `Empty`, else `i32` or `(type 3)`.
+impl fmt::Display for BlockType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockType::Empty => Ok(()),
+            BlockType::Value(ty) => write!(f, "{}", ty),
+            BlockType::TypeIndex(idx) => write!(f, "(type {})", idx),
+        }
+    }
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Nom)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Nom)]
 #[nom(LittleEndian)]
 pub enum WasmOpCode {
     // Control Flow
@@ -26,6 +63,12 @@ pub enum WasmOpCode {
     Loop = 0x03,
     If = 0x04,
     Else = 0x05,
+    // Exception handling (legacy `try` and `try_table`)
+    Try = 0x06,
+    Catch = 0x07,
+    Throw = 0x08,
+    Rethrow = 0x09,
+    ThrowRef = 0x0A,
     End = 0x0B,
     Br = 0x0C,
     BrIf = 0x0D,
@@ -35,10 +78,20 @@ pub enum WasmOpCode {
     // Calls
     Call = 0x10,
     CallIndirect = 0x11,
+    // Tail calls
+    ReturnCall = 0x12,
+    ReturnCallIndirect = 0x13,
+    // Function references
+    CallRef = 0x14,
+    ReturnCallRef = 0x15,
+    Delegate = 0x18,
+    CatchAll = 0x19,
 
     // Parametric
     Drop = 0x1A,
     Select = 0x1B,
+    SelectTyped = 0x1C,
+    TryTable = 0x1F,
 
     // Variable Access
     LocalGet = 0x20,
@@ -228,17 +281,345 @@ pub enum WasmOpCode {
     I64Extend16S = 0xC3,
     I64Extend32S = 0xC4,
 
+    // Reference Instructions
+    RefNull = 0xD0,
+    RefIsNull = 0xD1,
+    RefFunc = 0xD2,
+    // Function references
+    RefAsNonNull = 0xD4,
+    BrOnNull = 0xD5,
+    BrOnNonNull = 0xD6,
+
     // Miscellaneous (0xFC prefix): trunc_sat, memory.copy, etc.
     Misc = 0xFC,
+    // Threads (0xFE prefix): atomic memory accesses, wait/notify and fence
+    Atomic = 0xFE,
 }
 
-// Core instruction using nom_derive with Selector
-#[derive(Debug, Clone, PartialEq, Eq, Nom)]
-#[nom(LittleEndian)]
+impl WasmOpCode {
+    /// Text-format name of the instruction (e.g. `i32.add`). `Misc` and
+    /// `Atomic` have no name of their own; their sub-opcode selects the instruction.
+    pub fn mnemonic(&self) -> &'static str {
+        use WasmOpCode::*;
+        match self {
+            Unreachable => "unreachable",
+            Nop => "nop",
+            Block => "block",
+            Loop => "loop",
+            If => "if",
+            Else => "else",
+            Try => "try",
+            Catch => "catch",
+            Throw => "throw",
+            Rethrow => "rethrow",
+            ThrowRef => "throw_ref",
+            End => "end",
+            Br => "br",
+            BrIf => "br_if",
+            BrTable => "br_table",
+            Return => "return",
+            Call => "call",
+            CallIndirect => "call_indirect",
+            ReturnCall => "return_call",
+            ReturnCallIndirect => "return_call_indirect",
+            CallRef => "call_ref",
+            ReturnCallRef => "return_call_ref",
+            Delegate => "delegate",
+            CatchAll => "catch_all",
+            TryTable => "try_table",
+            Drop => "drop",
+            Select => "select",
+            SelectTyped => "select",
+            LocalGet => "local.get",
+            LocalSet => "local.set",
+            LocalTee => "local.tee",
+            GlobalGet => "global.get",
+            GlobalSet => "global.set",
+            I32Load => "i32.load",
+            I64Load => "i64.load",
+            F32Load => "f32.load",
+            F64Load => "f64.load",
+            I32Load8S => "i32.load8_s",
+            I32Load8U => "i32.load8_u",
+            I32Load16S => "i32.load16_s",
+            I32Load16U => "i32.load16_u",
+            I64Load8S => "i64.load8_s",
+            I64Load8U => "i64.load8_u",
+            I64Load16S => "i64.load16_s",
+            I64Load16U => "i64.load16_u",
+            I64Load32S => "i64.load32_s",
+            I64Load32U => "i64.load32_u",
+            I32Store => "i32.store",
+            I64Store => "i64.store",
+            F32Store => "f32.store",
+            F64Store => "f64.store",
+            I32Store8 => "i32.store8",
+            I32Store16 => "i32.store16",
+            I64Store8 => "i64.store8",
+            I64Store16 => "i64.store16",
+            I64Store32 => "i64.store32",
+            MemorySize => "memory.size",
+            MemoryGrow => "memory.grow",
+            I32Const => "i32.const",
+            I64Const => "i64.const",
+            F32Const => "f32.const",
+            F64Const => "f64.const",
+            I32Eqz => "i32.eqz",
+            I32Eq => "i32.eq",
+            I32Ne => "i32.ne",
+            I32LtS => "i32.lt_s",
+            I32LtU => "i32.lt_u",
+            I32GtS => "i32.gt_s",
+            I32GtU => "i32.gt_u",
+            I32LeS => "i32.le_s",
+            I32LeU => "i32.le_u",
+            I32GeS => "i32.ge_s",
+            I32GeU => "i32.ge_u",
+            I64Eqz => "i64.eqz",
+            I64Eq => "i64.eq",
+            I64Ne => "i64.ne",
+            I64LtS => "i64.lt_s",
+            I64LtU => "i64.lt_u",
+            I64GtS => "i64.gt_s",
+            I64GtU => "i64.gt_u",
+            I64LeS => "i64.le_s",
+            I64LeU => "i64.le_u",
+            I64GeS => "i64.ge_s",
+            I64GeU => "i64.ge_u",
+            F32Eq => "f32.eq",
+            F32Ne => "f32.ne",
+            F32Lt => "f32.lt",
+            F32Gt => "f32.gt",
+            F32Le => "f32.le",
+            F32Ge => "f32.ge",
+            F64Eq => "f64.eq",
+            F64Ne => "f64.ne",
+            F64Lt => "f64.lt",
+            F64Gt => "f64.gt",
+            F64Le => "f64.le",
+            F64Ge => "f64.ge",
+            I32Clz => "i32.clz",
+            I32Ctz => "i32.ctz",
+            I32Popcnt => "i32.popcnt",
+            I32Add => "i32.add",
+            I32Sub => "i32.sub",
+            I32Mul => "i32.mul",
+            I32DivS => "i32.div_s",
+            I32DivU => "i32.div_u",
+            I32RemS => "i32.rem_s",
+            I32RemU => "i32.rem_u",
+            I32And => "i32.and",
+            I32Or => "i32.or",
+            I32Xor => "i32.xor",
+            I32Shl => "i32.shl",
+            I32ShrS => "i32.shr_s",
+            I32ShrU => "i32.shr_u",
+            I32Rotl => "i32.rotl",
+            I32Rotr => "i32.rotr",
+            I64Clz => "i64.clz",
+            I64Ctz => "i64.ctz",
+            I64Popcnt => "i64.popcnt",
+            I64Add => "i64.add",
+            I64Sub => "i64.sub",
+            I64Mul => "i64.mul",
+            I64DivS => "i64.div_s",
+            I64DivU => "i64.div_u",
+            I64RemS => "i64.rem_s",
+            I64RemU => "i64.rem_u",
+            I64And => "i64.and",
+            I64Or => "i64.or",
+            I64Xor => "i64.xor",
+            I64Shl => "i64.shl",
+            I64ShrS => "i64.shr_s",
+            I64ShrU => "i64.shr_u",
+            I64Rotl => "i64.rotl",
+            I64Rotr => "i64.rotr",
+            F32Abs => "f32.abs",
+            F32Neg => "f32.neg",
+            F32Ceil => "f32.ceil",
+            F32Floor => "f32.floor",
+            F32Trunc => "f32.trunc",
+            F32Nearest => "f32.nearest",
+            F32Sqrt => "f32.sqrt",
+            F32Add => "f32.add",
+            F32Sub => "f32.sub",
+            F32Mul => "f32.mul",
+            F32Div => "f32.div",
+            F32Min => "f32.min",
+            F32Max => "f32.max",
+            F32Copysign => "f32.copysign",
+            F64Abs => "f64.abs",
+            F64Neg => "f64.neg",
+            F64Ceil => "f64.ceil",
+            F64Floor => "f64.floor",
+            F64Trunc => "f64.trunc",
+            F64Nearest => "f64.nearest",
+            F64Sqrt => "f64.sqrt",
+            F64Add => "f64.add",
+            F64Sub => "f64.sub",
+            F64Mul => "f64.mul",
+            F64Div => "f64.div",
+            F64Min => "f64.min",
+            F64Max => "f64.max",
+            F64Copysign => "f64.copysign",
+            I32WrapI64 => "i32.wrap_i64",
+            I32TruncF32S => "i32.trunc_f32_s",
+            I32TruncF32U => "i32.trunc_f32_u",
+            I32TruncF64S => "i32.trunc_f64_s",
+            I32TruncF64U => "i32.trunc_f64_u",
+            I64ExtendI32S => "i64.extend_i32_s",
+            I64ExtendI32U => "i64.extend_i32_u",
+            I64TruncF32S => "i64.trunc_f32_s",
+            I64TruncF32U => "i64.trunc_f32_u",
+            I64TruncF64S => "i64.trunc_f64_s",
+            I64TruncF64U => "i64.trunc_f64_u",
+            F32ConvertI32S => "f32.convert_i32_s",
+            F32ConvertI32U => "f32.convert_i32_u",
+            F32ConvertI64S => "f32.convert_i64_s",
+            F32ConvertI64U => "f32.convert_i64_u",
+            F32DemoteF64 => "f32.demote_f64",
+            F64ConvertI32S => "f64.convert_i32_s",
+            F64ConvertI32U => "f64.convert_i32_u",
+            F64ConvertI64S => "f64.convert_i64_s",
+            F64ConvertI64U => "f64.convert_i64_u",
+            F64PromoteF32 => "f64.promote_f32",
+            I32ReinterpretF32 => "i32.reinterpret_f32",
+            I64ReinterpretF64 => "i64.reinterpret_f64",
+            F32ReinterpretI32 => "f32.reinterpret_i32",
+            F64ReinterpretI64 => "f64.reinterpret_i64",
+            I32Extend8S => "i32.extend8_s",
+            I32Extend16S => "i32.extend16_s",
+            I64Extend8S => "i64.extend8_s",
+            I64Extend16S => "i64.extend16_s",
+            I64Extend32S => "i64.extend32_s",
+            RefNull => "ref.null",
+            RefIsNull => "ref.is_null",
+            RefFunc => "ref.func",
+            RefAsNonNull => "ref.as_non_null",
+            BrOnNull => "br_on_null",
+            BrOnNonNull => "br_on_non_null",
+            Misc => "misc",
+            Atomic => "atomic",
+        }
+    }
+
+    /// Whether the instruction may appear in a constant expression: the MVP
+    /// constants and `global.get`, `ref.null`/`ref.func`, and the integer
+    /// `add`, `sub` and `mul` of the extended-const proposal.
+    pub fn is_const(&self) -> bool {
+        use WasmOpCode::*;
+        matches!(self,
+            I32Const | I64Const | F32Const | F64Const | GlobalGet | RefNull | RefFunc
+            | I32Add | I32Sub | I32Mul | I64Add | I64Sub | I64Mul)
+    }
+}
+
+// Core instruction: the opcode selects how the operands are parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AwwasmInstruction<'a> {
     pub opcode: WasmOpCode,
-    #[nom(Selector = "opcode", Parse = "{ |i| AwwasmOperands::parse(i, opcode) }")]
     pub operands: AwwasmOperands<'a>,
+    /// Opcode and immediates exactly as encoded. For `block`, `loop`, `if`
+    /// and `try` this stops after the block type, and for `try_table` after
+    /// its catch clauses; the nested body is not included.
+    pub encoding: &'a [u8],
+}
+
+/// Deepest nesting of `block`, `loop`, `if`, `try` and `try_table` bodies
+/// decoded unless `AwwasmParseLimits::max_nesting_depth` says otherwise.
+/// Decoding keeps open bodies on the heap, but dropping, comparing or
+/// walking an instruction recurses into its bodies, so this bounds the
+/// stack those take.
+pub const DEFAULT_MAX_NESTING_DEPTH: u32 = 1024;
+
+/// nom error kind of a body nested deeper than the parse allows.
+pub(crate) const NESTING_TOO_DEEP: nom::error::ErrorKind = nom::error::ErrorKind::ManyMN;
+
+/// nom error kind of a body whose decoding was cancelled.
+pub(crate) const DECODE_CANCELLED: nom::error::ErrorKind = nom::error::ErrorKind::Fail;
+
+/// nom error kind of a function body with more instructions than the parse
+/// allows.
+pub(crate) const TOO_MANY_INSTRUCTIONS: nom::error::ErrorKind = nom::error::ErrorKind::Many1Count;
+
+type NomErr<'a> = nom::Err<nom::error::Error<&'a [u8]>>;
+
+// Limits on decoding a function body, and the token cancelling it, which
+// the derived `Parse` impls have no way to carry.
+#[derive(Debug, Clone)]
+pub(crate) struct InstrContext {
+    max_depth: u32,
+    max_instructions: u32,
+    // Instructions decoded so far.
+    decoded: Cell<u32>,
+    cancel: Option<CancellationToken>,
+}
+
+impl Default for InstrContext {
+    fn default() -> Self {
+        InstrContext::new(&AwwasmParseLimits::default())
+    }
+}
+
+impl InstrContext {
+    pub(crate) fn new(limits: &AwwasmParseLimits) -> Self {
+        InstrContext {
+            max_depth: limits.max_nesting_depth,
+            max_instructions: limits.max_function_instructions,
+            decoded: Cell::new(0),
+            cancel: None,
+        }
+    }
+
+    pub(crate) fn with_cancel(self, token: &CancellationToken) -> Self {
+        InstrContext { cancel: Some(token.clone()), ..self }
+    }
+
+    // Fail if a body opened at `i` would be nested `depth` deep.
+    fn enter<'a>(&self, depth: usize, i: &'a [u8]) -> Result<(), NomErr<'a>> {
+        if depth > self.max_depth as usize {
+            return Err(nom::Err::Failure(nom::error::Error::new(i, NESTING_TOO_DEEP)));
+        }
+        Ok(())
+    }
+
+    // Count the instruction at `i`, failing once there are too many, and
+    // every `CANCEL_CHECK_INTERVAL` instructions check the token.
+    fn step<'a>(&self, i: &'a [u8]) -> Result<(), NomErr<'a>> {
+        let decoded = self.decoded.get().saturating_add(1);
+        self.decoded.set(decoded);
+        if decoded > self.max_instructions {
+            return Err(nom::Err::Failure(nom::error::Error::new(i, TOO_MANY_INSTRUCTIONS)));
+        }
+        if decoded.is_multiple_of(CANCEL_CHECK_INTERVAL as u32) && self.cancel.as_ref().is_some_and(CancellationToken::is_cancelled) {
+            return Err(nom::Err::Failure(nom::error::Error::new(i, DECODE_CANCELLED)));
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmInstruction<'a> {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        Self::parse_with(i, &InstrContext::default())
+    }
+}
+
+impl<'a> AwwasmInstruction<'a> {
+    pub(crate) fn parse_with(i: &'a [u8], ctx: &InstrContext) -> IResult<&'a [u8], Self> {
+        ctx.step(i)?;
+        let (after_opcode, opcode) = WasmOpCode::parse(i)?;
+        if !opens_body(opcode) {
+            let (rest, operands) = AwwasmOperands::parse(after_opcode, opcode)?;
+            return Ok((rest, Self { opcode, operands, encoding: &i[..i.len() - rest.len()] }));
+        }
+        ctx.enter(1, i)?;
+        let (rest, open) = OpenBody::new(i, after_opcode, opcode)?;
+        close_bodies(rest, ctx, open)
+    }
+}
+
+fn opens_body(opcode: WasmOpCode) -> bool {
+    matches!(opcode, WasmOpCode::Block | WasmOpCode::Loop | WasmOpCode::If | WasmOpCode::Try | WasmOpCode::TryTable)
 }
 
 // Operands using nom_derive Selector properly
@@ -266,6 +647,31 @@ pub enum AwwasmOperands<'a> {
 
     #[nom(Selector = "WasmOpCode::End")]
     End,
+
+    // Exception handling
+    #[nom(Selector = "WasmOpCode::Try")]
+    Try(TryOperands<'a>),
+
+    #[nom(Selector = "WasmOpCode::Catch")]
+    Catch(IndexOperands),
+
+    #[nom(Selector = "WasmOpCode::CatchAll")]
+    CatchAll,
+
+    #[nom(Selector = "WasmOpCode::Delegate")]
+    Delegate(BrOperands),
+
+    #[nom(Selector = "WasmOpCode::TryTable")]
+    TryTable(TryTableOperands<'a>),
+
+    #[nom(Selector = "WasmOpCode::Throw")]
+    Throw(IndexOperands),
+
+    #[nom(Selector = "WasmOpCode::Rethrow")]
+    Rethrow(BrOperands),
+
+    #[nom(Selector = "WasmOpCode::ThrowRef")]
+    ThrowRef,
     
     // Branches - pure nom_derive
     #[nom(Selector = "WasmOpCode::Br")]
@@ -286,6 +692,18 @@ pub enum AwwasmOperands<'a> {
 
     #[nom(Selector = "WasmOpCode::CallIndirect")]
     CallIndirect(CallIndirectOperands),
+
+    #[nom(Selector = "WasmOpCode::ReturnCall")]
+    ReturnCall(CallOperands),
+
+    #[nom(Selector = "WasmOpCode::ReturnCallIndirect")]
+    ReturnCallIndirect(CallIndirectOperands),
+
+    #[nom(Selector = "WasmOpCode::CallRef")]
+    CallRef(CallRefOperands),
+
+    #[nom(Selector = "WasmOpCode::ReturnCallRef")]
+    ReturnCallRef(CallRefOperands),
  
     // Parametric
     #[nom(Selector = "WasmOpCode::Drop")]
@@ -294,6 +712,9 @@ pub enum AwwasmOperands<'a> {
     #[nom(Selector = "WasmOpCode::Select")]
     Select,
 
+    #[nom(Selector = "WasmOpCode::SelectTyped")]
+    SelectTyped(SelectTypedOperands),
+
     // Variables - pure nom_derive
     #[nom(Selector = "WasmOpCode::LocalGet")]
     LocalGet(IndexOperands),
@@ -336,10 +757,10 @@ pub enum AwwasmOperands<'a> {
     #[nom(Selector = "WasmOpCode::I64Store32")] I64Store32(MemArg),
 
     #[nom(Selector = "WasmOpCode::MemorySize")]
-    MemorySize(MemoryZeroOperands<'a>),
+    MemorySize(MemoryIndexOperands),
 
     #[nom(Selector = "WasmOpCode::MemoryGrow")]
-    MemoryGrow(MemoryZeroOperands<'a>),
+    MemoryGrow(MemoryIndexOperands),
 
     // Constants - pure nom_derive
     #[nom(Selector = "WasmOpCode::I32Const")]
@@ -498,9 +919,75 @@ pub enum AwwasmOperands<'a> {
     #[nom(Selector = "WasmOpCode::I64Extend16S")] I64Extend16S,
     #[nom(Selector = "WasmOpCode::I64Extend32S")] I64Extend32S,
 
+    // Reference instructions
+    #[nom(Selector = "WasmOpCode::RefNull")]
+    RefNull(RefNullOperands),
+
+    #[nom(Selector = "WasmOpCode::RefIsNull")]
+    RefIsNull,
+
+    #[nom(Selector = "WasmOpCode::RefFunc")]
+    RefFunc(CallOperands),
+
+    #[nom(Selector = "WasmOpCode::RefAsNonNull")]
+    RefAsNonNull,
+
+    #[nom(Selector = "WasmOpCode::BrOnNull")]
+    BrOnNull(BrOperands),
+
+    #[nom(Selector = "WasmOpCode::BrOnNonNull")]
+    BrOnNonNull(BrOperands),
+
     // 0xFC prefix: trunc_sat and bulk memory ops
     #[nom(Selector = "WasmOpCode::Misc")]
     Misc(MiscOperands),
+
+    // 0xFE prefix: threads
+    #[nom(Selector = "WasmOpCode::Atomic")]
+    Atomic(AtomicOperands),
+}
+
+impl<'a> AwwasmOperands<'a> {
+    /// The block type of a structured instruction.
+    pub fn block_type(&self) -> Option<BlockType> {
+        use AwwasmOperands::*;
+        match self {
+            Block(BlockOperands { block_type, .. })
+            | Loop(LoopOperands { block_type, .. })
+            | If(IfOperands { block_type, .. })
+            | Try(TryOperands { block_type, .. })
+            | TryTable(TryTableOperands { block_type, .. }) => Some(*block_type),
+            _ => None,
+        }
+    }
+
+    /// The bodies nested in a structured instruction, in order, each with
+    /// the marker (`else`, `catch`, `end`, ...) that closes it.
+    pub fn bodies(&self) -> Vec<&(Vec<AwwasmInstruction<'a>>, &'a [u8])> {
+        use AwwasmOperands::*;
+        match self {
+            Block(op) => vec![&op.body],
+            Loop(op) => vec![&op.body],
+            If(op) => std::iter::once(&op.then_body).chain(op.else_body.as_ref()).collect(),
+            Try(op) => std::iter::once(&op.body).chain(op.handlers.iter().map(|h| &h.body)).collect(),
+            TryTable(op) => vec![&op.body],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The memory immediate of a load, store or atomic memory access.
+    pub fn mem_arg(&self) -> Option<&MemArg> {
+        use AwwasmOperands::*;
+        match self {
+            I32Load(m) | I64Load(m) | F32Load(m) | F64Load(m)
+            | I32Load8S(m) | I32Load8U(m) | I32Load16S(m) | I32Load16U(m)
+            | I64Load8S(m) | I64Load8U(m) | I64Load16S(m) | I64Load16U(m) | I64Load32S(m) | I64Load32U(m)
+            | I32Store(m) | I64Store(m) | F32Store(m) | F64Store(m)
+            | I32Store8(m) | I32Store16(m) | I64Store8(m) | I64Store16(m) | I64Store32(m) => Some(m),
+            Atomic(op) => op.mem_arg.as_ref(),
+            _ => None,
+        }
+    }
 }
 
 // All operand structs using nom_derive
@@ -516,7 +1003,7 @@ pub struct BrOperands {
 pub struct BrTableOperands {
     #[nom(Parse = "leb128_u32")]
     pub target_count: u32,
-    #[nom(Count = "target_count")]
+    #[nom(Parse = "count(leb128_u32, target_count as usize)")]
     pub targets: Vec<u32>,
     #[nom(Parse = "leb128_u32")]
     pub default: u32,
@@ -536,6 +1023,14 @@ pub struct CallOperands {
     pub funcidx: u32,
 }
 
+/// Immediate of `call_ref` and `return_call_ref`: the type of the callee.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct CallRefOperands {
+    #[nom(Parse = "leb128_u32")]
+    pub typeidx: u32,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
 #[nom(LittleEndian)]
 pub struct CallIndirectOperands {
@@ -545,29 +1040,358 @@ pub struct CallIndirectOperands {
     pub tableidx: u32,
 }
 
+// Bit of a memarg's alignment field saying a memory index follows (multi-memory).
+pub(crate) const MEM_ARG_MEMIDX_FLAG: u32 = 0x40;
+
+/// Immediate of a load, store or atomic access.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemArg {
+    /// Alignment exponent, without the multi-memory flag bit.
+    pub align: u32,
+    /// The memory index, if encoded explicitly (multi-memory). Kept apart
+    /// from `memory()` so an explicit index of 0 round-trips.
+    pub memidx: Option<u32>,
+    pub offset: u32,
+}
+
+impl MemArg {
+    /// The memory accessed.
+    pub fn memory(&self) -> u32 {
+        self.memidx.unwrap_or(0)
+    }
+}
+
+impl<'a> Parse<&'a [u8]> for MemArg {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (i, flags) = leb128_u32(i)?;
+        let (i, memidx) = cond(flags & MEM_ARG_MEMIDX_FLAG != 0, leb128_u32)(i)?;
+        let (i, offset) = leb128_u32(i)?;
+        Ok((i, MemArg { align: flags & !MEM_ARG_MEMIDX_FLAG, memidx, offset }))
+    }
+}
+
+/// Immediate of `memory.size` and `memory.grow`: a reserved `0x00` before
+/// multi-memory, now the memory index.
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
 #[nom(LittleEndian)]
-pub struct MemArg {
+pub struct MemoryIndexOperands {
     #[nom(Parse = "leb128_u32")]
-    pub align: u32,
+    pub memidx: u32,
+}
+
+/// Sub-opcodes of the 0xFC prefix, encoded as a LEB128 u32 after the prefix byte.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum MiscOpCode {
+    // Saturating truncation
+    I32TruncSatF32S = 0x00,
+    I32TruncSatF32U = 0x01,
+    I32TruncSatF64S = 0x02,
+    I32TruncSatF64U = 0x03,
+    I64TruncSatF32S = 0x04,
+    I64TruncSatF32U = 0x05,
+    I64TruncSatF64S = 0x06,
+    I64TruncSatF64U = 0x07,
+    // Bulk memory
+    MemoryInit = 0x08,
+    DataDrop = 0x09,
+    MemoryCopy = 0x0A,
+    MemoryFill = 0x0B,
+}
+
+impl MiscOpCode {
+    /// Text-format name of the instruction (e.g. `i32.trunc_sat_f32_s`).
+    pub fn mnemonic(&self) -> &'static str {
+        use MiscOpCode::*;
+        match self {
+            I32TruncSatF32S => "i32.trunc_sat_f32_s",
+            I32TruncSatF32U => "i32.trunc_sat_f32_u",
+            I32TruncSatF64S => "i32.trunc_sat_f64_s",
+            I32TruncSatF64U => "i32.trunc_sat_f64_u",
+            I64TruncSatF32S => "i64.trunc_sat_f32_s",
+            I64TruncSatF32U => "i64.trunc_sat_f32_u",
+            I64TruncSatF64S => "i64.trunc_sat_f64_s",
+            I64TruncSatF64U => "i64.trunc_sat_f64_u",
+            MemoryInit => "memory.init",
+            DataDrop => "data.drop",
+            MemoryCopy => "memory.copy",
+            MemoryFill => "memory.fill",
+        }
+    }
+}
+
+impl<'a> Parse<&'a [u8]> for MiscOpCode {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (rest, sub_op) = leb128_u32(i)?;
+        match num_traits::FromPrimitive::from_u32(sub_op) {
+            Some(op) => Ok((rest, op)),
+            None => Err(nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Switch))),
+        }
+    }
+}
+
+/// Immediates of a 0xFC-prefixed instruction, selected by its sub-opcode.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian, Selector = "MiscOpCode")]
+pub enum AwwasmMiscOperands {
+    // Saturating truncation - no immediates
+    #[nom(Selector = "MiscOpCode::I32TruncSatF32S")] I32TruncSatF32S,
+    #[nom(Selector = "MiscOpCode::I32TruncSatF32U")] I32TruncSatF32U,
+    #[nom(Selector = "MiscOpCode::I32TruncSatF64S")] I32TruncSatF64S,
+    #[nom(Selector = "MiscOpCode::I32TruncSatF64U")] I32TruncSatF64U,
+    #[nom(Selector = "MiscOpCode::I64TruncSatF32S")] I64TruncSatF32S,
+    #[nom(Selector = "MiscOpCode::I64TruncSatF32U")] I64TruncSatF32U,
+    #[nom(Selector = "MiscOpCode::I64TruncSatF64S")] I64TruncSatF64S,
+    #[nom(Selector = "MiscOpCode::I64TruncSatF64U")] I64TruncSatF64U,
+
+    // Bulk memory
+    #[nom(Selector = "MiscOpCode::MemoryInit")] MemoryInit(MemoryInitOperands),
+    #[nom(Selector = "MiscOpCode::DataDrop")]   DataDrop(DataDropOperands),
+    #[nom(Selector = "MiscOpCode::MemoryCopy")] MemoryCopy(MemoryCopyOperands),
+    #[nom(Selector = "MiscOpCode::MemoryFill")] MemoryFill(IndexOperands),
+}
+
+/// `memory.init`: copy from a passive data segment into memory `memidx`.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct MemoryInitOperands {
     #[nom(Parse = "leb128_u32")]
-    pub offset: u32,
+    pub dataidx: u32,
+    #[nom(Parse = "leb128_u32")]
+    pub memidx: u32,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
 #[nom(LittleEndian)]
-pub struct MemoryZeroOperands<'a> {
-    #[nom(Tag(WASM_INSTRUCTION_MEMORY_ZERO))]
-    pub reserved: &'a [u8],
+pub struct DataDropOperands {
+    #[nom(Parse = "leb128_u32")]
+    pub dataidx: u32,
 }
 
-/// 0xFC prefix operands: reads the sub-opcode as a LEB128 u32.
-/// For trunc_sat (sub-ops 0-7) there are no additional bytes.
+/// `memory.copy`: destination memory first, as encoded.
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
 #[nom(LittleEndian)]
-pub struct MiscOperands {
+pub struct MemoryCopyOperands {
     #[nom(Parse = "leb128_u32")]
-    pub sub_op: u32,
+    pub dst_memidx: u32,
+    #[nom(Parse = "leb128_u32")]
+    pub src_memidx: u32,
+}
+
+/// 0xFC prefix operands: the sub-opcode, then the immediates it selects.
+/// Unknown sub-opcodes fail to parse.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct MiscOperands {
+    pub sub_op: MiscOpCode,
+    #[nom(Selector = "sub_op", Parse = "{ |i| AwwasmMiscOperands::parse(i, sub_op) }")]
+    pub operands: AwwasmMiscOperands,
+}
+
+/// Sub-opcodes of the 0xFE (threads) prefix, encoded as a LEB128 u32 after the prefix byte.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+pub enum AtomicOpCode {
+    // Wait and notify
+    MemoryAtomicNotify = 0x00,
+    MemoryAtomicWait32 = 0x01,
+    MemoryAtomicWait64 = 0x02,
+    AtomicFence = 0x03,
+    // Loads
+    I32AtomicLoad = 0x10,
+    I64AtomicLoad = 0x11,
+    I32AtomicLoad8U = 0x12,
+    I32AtomicLoad16U = 0x13,
+    I64AtomicLoad8U = 0x14,
+    I64AtomicLoad16U = 0x15,
+    I64AtomicLoad32U = 0x16,
+    // Stores
+    I32AtomicStore = 0x17,
+    I64AtomicStore = 0x18,
+    I32AtomicStore8 = 0x19,
+    I32AtomicStore16 = 0x1A,
+    I64AtomicStore8 = 0x1B,
+    I64AtomicStore16 = 0x1C,
+    I64AtomicStore32 = 0x1D,
+    // Read-modify-write: add
+    I32AtomicRmwAdd = 0x1E,
+    I64AtomicRmwAdd = 0x1F,
+    I32AtomicRmw8AddU = 0x20,
+    I32AtomicRmw16AddU = 0x21,
+    I64AtomicRmw8AddU = 0x22,
+    I64AtomicRmw16AddU = 0x23,
+    I64AtomicRmw32AddU = 0x24,
+    // Read-modify-write: sub
+    I32AtomicRmwSub = 0x25,
+    I64AtomicRmwSub = 0x26,
+    I32AtomicRmw8SubU = 0x27,
+    I32AtomicRmw16SubU = 0x28,
+    I64AtomicRmw8SubU = 0x29,
+    I64AtomicRmw16SubU = 0x2A,
+    I64AtomicRmw32SubU = 0x2B,
+    // Read-modify-write: and
+    I32AtomicRmwAnd = 0x2C,
+    I64AtomicRmwAnd = 0x2D,
+    I32AtomicRmw8AndU = 0x2E,
+    I32AtomicRmw16AndU = 0x2F,
+    I64AtomicRmw8AndU = 0x30,
+    I64AtomicRmw16AndU = 0x31,
+    I64AtomicRmw32AndU = 0x32,
+    // Read-modify-write: or
+    I32AtomicRmwOr = 0x33,
+    I64AtomicRmwOr = 0x34,
+    I32AtomicRmw8OrU = 0x35,
+    I32AtomicRmw16OrU = 0x36,
+    I64AtomicRmw8OrU = 0x37,
+    I64AtomicRmw16OrU = 0x38,
+    I64AtomicRmw32OrU = 0x39,
+    // Read-modify-write: xor
+    I32AtomicRmwXor = 0x3A,
+    I64AtomicRmwXor = 0x3B,
+    I32AtomicRmw8XorU = 0x3C,
+    I32AtomicRmw16XorU = 0x3D,
+    I64AtomicRmw8XorU = 0x3E,
+    I64AtomicRmw16XorU = 0x3F,
+    I64AtomicRmw32XorU = 0x40,
+    // Read-modify-write: xchg
+    I32AtomicRmwXchg = 0x41,
+    I64AtomicRmwXchg = 0x42,
+    I32AtomicRmw8XchgU = 0x43,
+    I32AtomicRmw16XchgU = 0x44,
+    I64AtomicRmw8XchgU = 0x45,
+    I64AtomicRmw16XchgU = 0x46,
+    I64AtomicRmw32XchgU = 0x47,
+    // Read-modify-write: cmpxchg
+    I32AtomicRmwCmpxchg = 0x48,
+    I64AtomicRmwCmpxchg = 0x49,
+    I32AtomicRmw8CmpxchgU = 0x4A,
+    I32AtomicRmw16CmpxchgU = 0x4B,
+    I64AtomicRmw8CmpxchgU = 0x4C,
+    I64AtomicRmw16CmpxchgU = 0x4D,
+    I64AtomicRmw32CmpxchgU = 0x4E,
+}
+
+impl AtomicOpCode {
+    /// Text-format name of the instruction (e.g. `i32.atomic.rmw.add`).
+    pub fn mnemonic(&self) -> &'static str {
+        use AtomicOpCode::*;
+        match self {
+            MemoryAtomicNotify => "memory.atomic.notify",
+            MemoryAtomicWait32 => "memory.atomic.wait32",
+            MemoryAtomicWait64 => "memory.atomic.wait64",
+            AtomicFence => "atomic.fence",
+            I32AtomicLoad => "i32.atomic.load",
+            I64AtomicLoad => "i64.atomic.load",
+            I32AtomicLoad8U => "i32.atomic.load8_u",
+            I32AtomicLoad16U => "i32.atomic.load16_u",
+            I64AtomicLoad8U => "i64.atomic.load8_u",
+            I64AtomicLoad16U => "i64.atomic.load16_u",
+            I64AtomicLoad32U => "i64.atomic.load32_u",
+            I32AtomicStore => "i32.atomic.store",
+            I64AtomicStore => "i64.atomic.store",
+            I32AtomicStore8 => "i32.atomic.store8",
+            I32AtomicStore16 => "i32.atomic.store16",
+            I64AtomicStore8 => "i64.atomic.store8",
+            I64AtomicStore16 => "i64.atomic.store16",
+            I64AtomicStore32 => "i64.atomic.store32",
+            I32AtomicRmwAdd => "i32.atomic.rmw.add",
+            I64AtomicRmwAdd => "i64.atomic.rmw.add",
+            I32AtomicRmw8AddU => "i32.atomic.rmw8.add_u",
+            I32AtomicRmw16AddU => "i32.atomic.rmw16.add_u",
+            I64AtomicRmw8AddU => "i64.atomic.rmw8.add_u",
+            I64AtomicRmw16AddU => "i64.atomic.rmw16.add_u",
+            I64AtomicRmw32AddU => "i64.atomic.rmw32.add_u",
+            I32AtomicRmwSub => "i32.atomic.rmw.sub",
+            I64AtomicRmwSub => "i64.atomic.rmw.sub",
+            I32AtomicRmw8SubU => "i32.atomic.rmw8.sub_u",
+            I32AtomicRmw16SubU => "i32.atomic.rmw16.sub_u",
+            I64AtomicRmw8SubU => "i64.atomic.rmw8.sub_u",
+            I64AtomicRmw16SubU => "i64.atomic.rmw16.sub_u",
+            I64AtomicRmw32SubU => "i64.atomic.rmw32.sub_u",
+            I32AtomicRmwAnd => "i32.atomic.rmw.and",
+            I64AtomicRmwAnd => "i64.atomic.rmw.and",
+            I32AtomicRmw8AndU => "i32.atomic.rmw8.and_u",
+            I32AtomicRmw16AndU => "i32.atomic.rmw16.and_u",
+            I64AtomicRmw8AndU => "i64.atomic.rmw8.and_u",
+            I64AtomicRmw16AndU => "i64.atomic.rmw16.and_u",
+            I64AtomicRmw32AndU => "i64.atomic.rmw32.and_u",
+            I32AtomicRmwOr => "i32.atomic.rmw.or",
+            I64AtomicRmwOr => "i64.atomic.rmw.or",
+            I32AtomicRmw8OrU => "i32.atomic.rmw8.or_u",
+            I32AtomicRmw16OrU => "i32.atomic.rmw16.or_u",
+            I64AtomicRmw8OrU => "i64.atomic.rmw8.or_u",
+            I64AtomicRmw16OrU => "i64.atomic.rmw16.or_u",
+            I64AtomicRmw32OrU => "i64.atomic.rmw32.or_u",
+            I32AtomicRmwXor => "i32.atomic.rmw.xor",
+            I64AtomicRmwXor => "i64.atomic.rmw.xor",
+            I32AtomicRmw8XorU => "i32.atomic.rmw8.xor_u",
+            I32AtomicRmw16XorU => "i32.atomic.rmw16.xor_u",
+            I64AtomicRmw8XorU => "i64.atomic.rmw8.xor_u",
+            I64AtomicRmw16XorU => "i64.atomic.rmw16.xor_u",
+            I64AtomicRmw32XorU => "i64.atomic.rmw32.xor_u",
+            I32AtomicRmwXchg => "i32.atomic.rmw.xchg",
+            I64AtomicRmwXchg => "i64.atomic.rmw.xchg",
+            I32AtomicRmw8XchgU => "i32.atomic.rmw8.xchg_u",
+            I32AtomicRmw16XchgU => "i32.atomic.rmw16.xchg_u",
+            I64AtomicRmw8XchgU => "i64.atomic.rmw8.xchg_u",
+            I64AtomicRmw16XchgU => "i64.atomic.rmw16.xchg_u",
+            I64AtomicRmw32XchgU => "i64.atomic.rmw32.xchg_u",
+            I32AtomicRmwCmpxchg => "i32.atomic.rmw.cmpxchg",
+            I64AtomicRmwCmpxchg => "i64.atomic.rmw.cmpxchg",
+            I32AtomicRmw8CmpxchgU => "i32.atomic.rmw8.cmpxchg_u",
+            I32AtomicRmw16CmpxchgU => "i32.atomic.rmw16.cmpxchg_u",
+            I64AtomicRmw8CmpxchgU => "i64.atomic.rmw8.cmpxchg_u",
+            I64AtomicRmw16CmpxchgU => "i64.atomic.rmw16.cmpxchg_u",
+            I64AtomicRmw32CmpxchgU => "i64.atomic.rmw32.cmpxchg_u",
+        }
+    }
+
+    /// The alignment (log2 of the access size) every `MemArg` of this
+    /// instruction must have; `None` for `atomic.fence`, which has no `MemArg`.
+    pub fn natural_alignment(&self) -> Option<u32> {
+        use AtomicOpCode::*;
+        match self {
+            AtomicFence => None,
+            I32AtomicLoad8U | I64AtomicLoad8U | I32AtomicStore8 | I64AtomicStore8 | I32AtomicRmw8AddU
+            | I64AtomicRmw8AddU | I32AtomicRmw8SubU | I64AtomicRmw8SubU | I32AtomicRmw8AndU
+            | I64AtomicRmw8AndU | I32AtomicRmw8OrU | I64AtomicRmw8OrU | I32AtomicRmw8XorU | I64AtomicRmw8XorU
+            | I32AtomicRmw8XchgU | I64AtomicRmw8XchgU | I32AtomicRmw8CmpxchgU | I64AtomicRmw8CmpxchgU => Some(0),
+            I32AtomicLoad16U | I64AtomicLoad16U | I32AtomicStore16 | I64AtomicStore16 | I32AtomicRmw16AddU
+            | I64AtomicRmw16AddU | I32AtomicRmw16SubU | I64AtomicRmw16SubU | I32AtomicRmw16AndU
+            | I64AtomicRmw16AndU | I32AtomicRmw16OrU | I64AtomicRmw16OrU | I32AtomicRmw16XorU
+            | I64AtomicRmw16XorU | I32AtomicRmw16XchgU | I64AtomicRmw16XchgU | I32AtomicRmw16CmpxchgU
+            | I64AtomicRmw16CmpxchgU => Some(1),
+            MemoryAtomicNotify | MemoryAtomicWait32 | I32AtomicLoad | I64AtomicLoad32U | I32AtomicStore
+            | I64AtomicStore32 | I32AtomicRmwAdd | I64AtomicRmw32AddU | I32AtomicRmwSub | I64AtomicRmw32SubU
+            | I32AtomicRmwAnd | I64AtomicRmw32AndU | I32AtomicRmwOr | I64AtomicRmw32OrU | I32AtomicRmwXor
+            | I64AtomicRmw32XorU | I32AtomicRmwXchg | I64AtomicRmw32XchgU | I32AtomicRmwCmpxchg
+            | I64AtomicRmw32CmpxchgU => Some(2),
+            MemoryAtomicWait64 | I64AtomicLoad | I64AtomicStore | I64AtomicRmwAdd | I64AtomicRmwSub
+            | I64AtomicRmwAnd | I64AtomicRmwOr | I64AtomicRmwXor | I64AtomicRmwXchg | I64AtomicRmwCmpxchg => Some(3),
+        }
+    }
+}
+
+impl<'a> Parse<&'a [u8]> for AtomicOpCode {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (rest, sub_op) = leb128_u32(i)?;
+        match num_traits::FromPrimitive::from_u32(sub_op) {
+            Some(op) => Ok((rest, op)),
+            None => Err(nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Switch))),
+        }
+    }
+}
+
+/// 0xFE prefix operands: the sub-opcode, then a `MemArg`, or for
+/// `atomic.fence` a reserved zero byte. Unknown sub-opcodes fail to parse.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AtomicOperands {
+    pub sub_op: AtomicOpCode,
+    #[nom(Cond = "sub_op != AtomicOpCode::AtomicFence")]
+    pub mem_arg: Option<MemArg>,
+    #[nom(Cond = "sub_op == AtomicOpCode::AtomicFence", Verify = "*reserved == 0")]
+    pub reserved: Option<u8>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
@@ -601,67 +1425,369 @@ pub struct F64ConstOperands {
 impl Eq for F64ConstOperands {}
 
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct RefNullOperands {
+    pub heap_type: AwwasmHeapType,
+}
+
+/// Operand types of a typed `select` (currently always exactly one type).
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct SelectTypedOperands {
+    #[nom(LengthCount = "leb128_u32")]
+    pub types: Vec<ValType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BlockOperands<'a> {
-    pub block_type: BlockValueType,
-    #[nom(Parse = "many_till(AwwasmInstruction::parse, tag([WASM_FUNC_SECTION_OPCODE_END]))")]
+    pub block_type: BlockType,
     pub body: (Vec<AwwasmInstruction<'a>>, &'a [u8]),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LoopOperands<'a> {
-    pub block_type: BlockValueType,
-    #[nom(Parse = "many_till(AwwasmInstruction::parse, tag([WASM_FUNC_SECTION_OPCODE_END]))")]
+    pub block_type: BlockType,
     pub body: (Vec<AwwasmInstruction<'a>>, &'a [u8]),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IfOperands<'a> {
-    pub block_type: BlockValueType,
-    #[nom(Parse = "many_till(AwwasmInstruction::parse, alt((tag([WASM_FUNC_SECTION_OPCODE_END]), tag([WASM_FUNC_SECTION_OPCODE_THEN]))))")]
+    pub block_type: BlockType,
     pub then_body: (Vec<AwwasmInstruction<'a>>, &'a [u8]),
-    #[nom(Parse = "cond(then_body.1[0] == WASM_FUNC_SECTION_OPCODE_THEN, many_till(AwwasmInstruction::parse, tag([WASM_FUNC_SECTION_OPCODE_END])))")]
     pub else_body: Option<(Vec<AwwasmInstruction<'a>>, &'a [u8])>,
 }
 
-// Custom parsers only for recursive control structures
-/* 
-fn parse_instrs_until_end<'a>(i: &'a [u8]) -> IResult<&'a [u8], Vec<AwwasmInstruction<'a>>> {
-    let (i, (instrs, _end)) = many_till(AwwasmInstruction::parse, tag([0x0B]))(i)?;
-    Ok((i, instrs))
+/// How a `try_table` catch clause matches an exception, and whether it
+/// also pushes the caught `exnref`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, Nom)]
+#[nom(LittleEndian)]
+pub enum AwwasmCatchKind {
+    Catch = 0x00,
+    CatchRef = 0x01,
+    CatchAll = 0x02,
+    CatchAllRef = 0x03,
+}
+
+/// One catch clause of a `try_table`: on a matching exception, branch to `label`.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmTryTableCatch {
+    pub kind: AwwasmCatchKind,
+    #[nom(Cond = "matches!(kind, AwwasmCatchKind::Catch | AwwasmCatchKind::CatchRef)", Parse = "leb128_u32")]
+    pub tag: Option<u32>,
+    #[nom(Parse = "leb128_u32")]
+    pub label: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TryTableOperands<'a> {
+    pub block_type: BlockType,
+    pub catches: Vec<AwwasmTryTableCatch>,
+    pub body: (Vec<AwwasmInstruction<'a>>, &'a [u8]),
+}
+
+/// A `catch tag` or `catch_all` handler of a legacy `try`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmCatchHandler<'a> {
+    /// The tag caught; `None` for `catch_all`.
+    pub tag: Option<u32>,
+    pub body: (Vec<AwwasmInstruction<'a>>, &'a [u8]),
+}
+
+/// Legacy `try`: a body, then its handlers. Each body's marker is the
+/// `catch`, `catch_all`, `delegate` or `end` that closes it, immediates included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TryOperands<'a> {
+    pub block_type: BlockType,
+    pub body: (Vec<AwwasmInstruction<'a>>, &'a [u8]),
+    pub handlers: Vec<AwwasmCatchHandler<'a>>,
+    /// Label a `try ... delegate` forwards exceptions to.
+    pub delegate: Option<u32>,
 }
 
-fn parse_block_operands<'a>(i: &'a [u8]) -> IResult<&'a [u8], BlockOperands<'a>> {
-    let (i, block_type) = parse_block_type(i)?;
-    let (i, body) = parse_instrs_until_end(i)?;
-    Ok((i, BlockOperands { block_type, body }))
+// The marker closing a body inside a legacy `try`, immediates included.
+fn try_marker(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    let rest = match i.first().copied() {
+        Some(op) if op == WasmOpCode::Catch as u8 || op == WasmOpCode::Delegate as u8 => leb128_u32(&i[1..])?.0,
+        Some(op) if op == WasmOpCode::CatchAll as u8 || op == WASM_FUNC_SECTION_OPCODE_END => &i[1..],
+        _ => return Err(nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Tag))),
+    };
+    Ok((rest, &i[..i.len() - rest.len()]))
 }
 
-fn parse_loop_operands<'a>(i: &'a [u8]) -> IResult<&'a [u8], LoopOperands<'a>> {
-    let (i, block_type) = parse_block_type(i)?;
-    let (i, body) = parse_instrs_until_end(i)?;
-    Ok((i, LoopOperands { block_type, body }))
+// A body being decoded: the instruction that opened it, any of its bodies
+// already closed and the instructions of the current one.
+struct OpenBody<'a> {
+    opcode: WasmOpCode,
+    encoding: &'a [u8],
+    block_type: BlockType,
+    catches: Vec<AwwasmTryTableCatch>,
+    /// The then body of an `if`, or the body of a `try`, once closed.
+    first: Option<(Vec<AwwasmInstruction<'a>>, &'a [u8])>,
+    /// Closed handlers of a `try`, and the tag of the one being decoded.
+    handlers: Vec<AwwasmCatchHandler<'a>>,
+    tag: Option<u32>,
+    instrs: Vec<AwwasmInstruction<'a>>,
 }
 
-fn parse_if_operands<'a>(i: &'a [u8]) -> IResult<&'a [u8], IfOperands<'a>> {
-    let (i, block_type) = parse_block_type(i)?;
-    let (i, (then_body, sep)) = many_till(AwwasmInstruction::parse, alt((tag([0x05]), tag([0x0B]))))(i)?;
-    if sep[0] == 0x05 { // Else
-        let (i, (else_body, _end)) = many_till(AwwasmInstruction::parse, tag([0x0B]))(i)?;
-        Ok((i, IfOperands { block_type, then_body, else_body: Some(else_body) }))
-    } else {
-        Ok((i, IfOperands { block_type, then_body, else_body: None }))
+impl<'a> OpenBody<'a> {
+    // Decode the immediates of `opcode`, which opens a body, from
+    // `after_opcode`; the instruction starts at `i`.
+    fn new(i: &'a [u8], after_opcode: &'a [u8], opcode: WasmOpCode) -> IResult<&'a [u8], Self> {
+        let (rest, block_type) = BlockType::parse(after_opcode)?;
+        let (rest, catches) = cond(opcode == WasmOpCode::TryTable, length_count(leb128_u32, AwwasmTryTableCatch::parse))(rest)?;
+        Ok((rest, OpenBody {
+            opcode,
+            encoding: &i[..i.len() - rest.len()],
+            block_type,
+            catches: catches.unwrap_or_default(),
+            first: None,
+            handlers: Vec::new(),
+            tag: None,
+            instrs: Vec::new(),
+        }))
+    }
+
+    // The marker closing the current body, if it comes next.
+    fn marker(&self, i: &'a [u8]) -> IResult<&'a [u8], &'a [u8]> {
+        match self.opcode {
+            WasmOpCode::If if self.first.is_none() => alt((tag([WASM_FUNC_SECTION_OPCODE_END]), tag([WASM_FUNC_SECTION_OPCODE_THEN])))(i),
+            WasmOpCode::Try => try_marker(i),
+            _ => tag([WASM_FUNC_SECTION_OPCODE_END])(i),
+        }
+    }
+
+    // Close the current body at `marker`; the operands if it was the last.
+    fn close(&mut self, marker: &'a [u8]) -> Result<Option<AwwasmOperands<'a>>, NomErr<'a>> {
+        let body = (std::mem::take(&mut self.instrs), marker);
+        let block_type = self.block_type;
+        Ok(Some(match self.opcode {
+            WasmOpCode::Block => AwwasmOperands::Block(BlockOperands { block_type, body }),
+            WasmOpCode::Loop => AwwasmOperands::Loop(LoopOperands { block_type, body }),
+            WasmOpCode::TryTable => AwwasmOperands::TryTable(TryTableOperands { block_type, catches: std::mem::take(&mut self.catches), body }),
+            WasmOpCode::If => match self.first.take() {
+                None if marker[0] == WASM_FUNC_SECTION_OPCODE_THEN => {
+                    self.first = Some(body);
+                    return Ok(None);
+                }
+                None => AwwasmOperands::If(IfOperands { block_type, then_body: body, else_body: None }),
+                Some(then_body) => AwwasmOperands::If(IfOperands { block_type, then_body, else_body: Some(body) }),
+            },
+            _ => {
+                match self.first {
+                    None => self.first = Some(body),
+                    Some(_) => self.handlers.push(AwwasmCatchHandler { tag: self.tag, body }),
+                }
+                self.tag = match marker {
+                    [op, idx @ ..] if *op == WasmOpCode::Catch as u8 => Some(leb128_u32(idx)?.1),
+                    [op] if *op == WasmOpCode::CatchAll as u8 => None,
+                    _ => {
+                        let delegate = match marker {
+                            [op, idx @ ..] if *op == WasmOpCode::Delegate as u8 => Some(leb128_u32(idx)?.1),
+                            _ => None,
+                        };
+                        let handlers = std::mem::take(&mut self.handlers);
+                        return Ok(self.first.take().map(|body| AwwasmOperands::Try(TryOperands { block_type, body, handlers, delegate })));
+                    }
+                };
+                return Ok(None);
+            }
+        }))
+    }
+}
+
+// Decode the bodies of `open`, and of every instruction nested in them,
+// starting at `i`. Open bodies are kept on a stack rather than decoded
+// recursively, so deep nesting costs heap, not stack.
+fn close_bodies<'a>(mut i: &'a [u8], ctx: &InstrContext, open: OpenBody<'a>) -> IResult<&'a [u8], AwwasmInstruction<'a>> {
+    let mut current = open;
+    let mut parents: Vec<OpenBody<'a>> = Vec::new();
+    loop {
+        match current.marker(i) {
+            Ok((rest, marker)) => {
+                i = rest;
+                let Some(operands) = current.close(marker)? else { continue };
+                let instr = AwwasmInstruction { opcode: current.opcode, operands, encoding: current.encoding };
+                match parents.pop() {
+                    Some(parent) => {
+                        current = parent;
+                        current.instrs.push(instr);
+                    }
+                    None => return Ok((i, instr)),
+                }
+                continue;
+            }
+            Err(nom::Err::Error(_)) => {}
+            Err(e) => return Err(e),
+        }
+        ctx.step(i)?;
+        let (after_opcode, opcode) = WasmOpCode::parse(i)?;
+        if opens_body(opcode) {
+            ctx.enter(parents.len() + 2, i)?;
+            let (rest, open) = OpenBody::new(i, after_opcode, opcode)?;
+            parents.push(std::mem::replace(&mut current, open));
+            i = rest;
+        } else {
+            let (rest, operands) = AwwasmOperands::parse(after_opcode, opcode)?;
+            current.instrs.push(AwwasmInstruction { opcode, operands, encoding: &i[..i.len() - rest.len()] });
+            i = rest;
+        }
+    }
+}
+
+// Parsed on their own, the operands of an instruction opening a body.
+macro_rules! nested_operands_parse {
+    ($($operands:ident => $opcode:ident),*) => {$(
+        impl<'nom: 'a, 'a> Parse<&'nom [u8]> for $operands<'a> {
+            fn parse(i: &'nom [u8]) -> IResult<&'nom [u8], Self> {
+                let (rest, open) = OpenBody::new(i, i, WasmOpCode::$opcode)?;
+                match close_bodies(rest, &InstrContext::default(), open)? {
+                    (rest, AwwasmInstruction { operands: AwwasmOperands::$opcode(operands), .. }) => Ok((rest, operands)),
+                    _ => Err(nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Switch))),
+                }
+            }
+        }
+    )*};
+}
+
+nested_operands_parse!(BlockOperands => Block, LoopOperands => Loop, IfOperands => If, TryOperands => Try, TryTableOperands => TryTable);
+
+/// Text of the marker closing a nested body (see `AwwasmOperands::bodies`):
+/// `else`, `catch 0`, `catch_all`, `delegate 1` or `end`.
+pub fn marker_text(marker: &[u8]) -> String {
+    match AwwasmInstruction::parse(marker) {
+        Ok((_, instr)) if instr.opcode != WasmOpCode::End => instr.to_string(),
+        _ => String::from("end"),
+    }
+}
+
+/// Byte range of `inner` within `outer`, if `inner` is a subslice of it.
+pub(crate) fn span_in(outer: &[u8], inner: &[u8]) -> Option<Range<usize>> {
+    let start = (inner.as_ptr() as usize).checked_sub(outer.as_ptr() as usize)?;
+    let end = start + inner.len();
+    (end <= outer.len()).then_some(start..end)
+}
+
+/// Mnemonic and immediates, in the style of `wasm-objdump -d`
+/// (e.g. `i32.load 2 8`, `br_table 0 1 2`). Nested bodies are not printed.
+impl fmt::Display for AwwasmInstruction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display_with(AwwasmFloatFormat::default()).fmt(f)
+    }
+}
+
+/// An instruction printed with a chosen float format, from
+/// `AwwasmInstruction::display_with`.
+pub struct AwwasmInstructionDisplay<'i, 'a> {
+    instr: &'i AwwasmInstruction<'a>,
+    floats: AwwasmFloatFormat,
+}
+
+impl fmt::Display for AwwasmInstructionDisplay<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use AwwasmOperands::*;
+        match &self.instr.operands {
+            Misc(op) => {
+                f.write_str(op.sub_op.mnemonic())?;
+                return match &op.operands {
+                    AwwasmMiscOperands::MemoryInit(m) => write!(f, " {} {}", m.dataidx, m.memidx),
+                    AwwasmMiscOperands::DataDrop(d) => write!(f, " {}", d.dataidx),
+                    AwwasmMiscOperands::MemoryCopy(m) => write!(f, " {} {}", m.dst_memidx, m.src_memidx),
+                    AwwasmMiscOperands::MemoryFill(m) => write!(f, " {}", m.index),
+                    _ => Ok(()),
+                };
+            }
+            Atomic(op) => f.write_str(op.sub_op.mnemonic())?,
+            _ => f.write_str(self.instr.opcode.mnemonic())?,
+        }
+        if let Some(m) = self.instr.operands.mem_arg() {
+            if let Some(memidx) = m.memidx {
+                write!(f, " {}", memidx)?;
+            }
+            return write!(f, " {} {}", m.align, m.offset);
+        }
+        if let Some(block_type) = self.instr.operands.block_type().filter(|t| *t != BlockType::Empty) {
+            write!(f, " {}", block_type)?;
+        }
+        match &self.instr.operands {
+            TryTable(op) => {
+                op.catches.iter().try_for_each(|c| match (c.kind, c.tag) {
+                    (AwwasmCatchKind::Catch, Some(tag)) => write!(f, " (catch {} {})", tag, c.label),
+                    (AwwasmCatchKind::CatchRef, Some(tag)) => write!(f, " (catch_ref {} {})", tag, c.label),
+                    (AwwasmCatchKind::CatchAllRef, _) => write!(f, " (catch_all_ref {})", c.label),
+                    _ => write!(f, " (catch_all {})", c.label),
+                })
+            }
+            Br(op) | BrIf(op) | BrOnNull(op) | BrOnNonNull(op) | Rethrow(op) | Delegate(op) => write!(f, " {}", op.labelidx),
+            Throw(op) | Catch(op) => write!(f, " {}", op.index),
+            BrTable(op) => {
+                op.targets.iter().try_for_each(|t| write!(f, " {}", t))?;
+                write!(f, " {}", op.default)
+            }
+            Call(op) | ReturnCall(op) | RefFunc(op) => write!(f, " {}", op.funcidx),
+            CallIndirect(op) | ReturnCallIndirect(op) => write!(f, " {} {}", op.typeidx, op.tableidx),
+            CallRef(op) | ReturnCallRef(op) => write!(f, " {}", op.typeidx),
+            RefNull(op) => write!(f, " {}", op.heap_type),
+            SelectTyped(op) => op.types.iter().try_for_each(|t| write!(f, " {}", t)),
+            LocalGet(op) | LocalSet(op) | LocalTee(op) | GlobalGet(op) | GlobalSet(op) => write!(f, " {}", op.index),
+            MemorySize(op) | MemoryGrow(op) => write!(f, " {}", op.memidx),
+            I32Const(op) => write!(f, " {}", op.value),
+            I64Const(op) => write!(f, " {}", op.value),
+            F32Const(op) => write!(f, " {}", self.floats.f32(op.value)),
+            F64Const(op) => write!(f, " {}", self.floats.f64(op.value)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'a> AwwasmInstruction<'a> {
+    /// Like `to_string()`, but with `floats` choosing how float constants are written.
+    pub fn display_with(&self, floats: AwwasmFloatFormat) -> AwwasmInstructionDisplay<'_, 'a> {
+        AwwasmInstructionDisplay { instr: self, floats }
+    }
+
+    /// Byte range of this instruction within `module_bytes`, the buffer the
+    /// module was parsed from; `None` if it was decoded from another buffer.
+    pub fn span(&self, module_bytes: &[u8]) -> Option<Range<usize>> {
+        span_in(module_bytes, self.encoding)
+    }
+
+    /// The bytes of `module_bytes` that encode this instruction (see `encoding`);
+    /// empty if it was decoded from another buffer.
+    pub fn raw_encoding<'m>(&self, module_bytes: &'m [u8]) -> &'m [u8] {
+        self.span(module_bytes).map_or(&[], |span| &module_bytes[span])
+    }
+
+    /// Visit this instruction and, depth first, every instruction nested in its blocks.
+    pub fn walk(&self, f: &mut impl FnMut(&AwwasmInstruction<'a>)) {
+        f(self);
+        for (body, _) in self.operands.bodies() {
+            body.iter().for_each(|instr| instr.walk(f));
+        }
     }
 }
-*/
 
 // Lazy iterator for function bodies
+#[derive(Debug, Clone)]
 pub struct InstructionIterator<'a> {
     remaining: &'a [u8],
+    ctx: InstrContext,
 }
 
 impl<'a> InstructionIterator<'a> {
     pub fn new(input: &'a [u8]) -> Self {
-        Self { remaining: input }
+        Self { remaining: input, ctx: InstrContext::default() }
+    }
+
+    /// Like `new`, but nesting bodies as deep as `limits` allows.
+    pub fn with_limits(input: &'a [u8], limits: &AwwasmParseLimits) -> Self {
+        Self { remaining: input, ctx: InstrContext::new(limits) }
+    }
+
+    // Decoding under `ctx`, which may also carry a cancellation token.
+    pub(crate) fn with_context(input: &'a [u8], ctx: InstrContext) -> Self {
+        Self { remaining: input, ctx }
     }
 }
 
@@ -673,7 +1799,7 @@ impl<'a> Iterator for InstructionIterator<'a> {
             return None;
         }
 
-        match AwwasmInstruction::parse(self.remaining) {
+        match AwwasmInstruction::parse_with(self.remaining, &self.ctx) {
             Ok((rest, instr)) => {
                 self.remaining = rest;
                 Some(Ok(instr))
@@ -687,21 +1813,55 @@ impl<'a> Iterator for InstructionIterator<'a> {
 }
 
 
+/// Parse the instructions of a constant expression up to its `end` (0x0B),
+/// which is left in the input, and return their bytes.
+///
+/// Fails on an instruction `WasmOpCode::is_const()` rejects.
+pub fn const_expr(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    let mut rest = i;
+    while rest.first().is_some_and(|b| *b != WASM_FUNC_SECTION_OPCODE_END) {
+        let (after, instr) = AwwasmInstruction::parse(rest)?;
+        if !instr.opcode.is_const() {
+            return Err(nom::Err::Error(nom::error::Error::new(rest, nom::error::ErrorKind::Verify)));
+        }
+        rest = after;
+    }
+    Ok((rest, &i[..i.len() - rest.len()]))
+}
+
 /// Evaluate a constant initializer expression and return its i32 value.
 ///
 /// Used for data segment offsets and global initializers.
-/// The `code` bytes contain the raw instruction (e.g. `i32.const N`)
-/// without the trailing `end` (0x0B) opcode.
+/// The `code` bytes contain the raw instructions (e.g. `i32.const N`, or
+/// `i32.const A i32.const B i32.add` with extended-const) without the
+/// trailing `end` (0x0B) opcode. `global.get` cannot be evaluated without
+/// the module's globals and is an error.
 pub fn eval_const_init_expr(code: &[u8]) -> anyhow::Result<i32> {
     if code.is_empty() {
         return Err(anyhow::anyhow!("empty constant expression"));
     }
 
-    let (_rest, instr) = AwwasmInstruction::parse(code)
-        .map_err(|e| anyhow::anyhow!("failed to parse init expr: {}", e))?;
-
-    match instr.operands {
-        AwwasmOperands::I32Const(op) => Ok(op.value),
-        _ => Err(anyhow::anyhow!("unsupported init expr opcode: {:?}", instr.opcode)),
+    let mut stack: Vec<i32> = Vec::new();
+    for instr in InstructionIterator::new(code) {
+        let instr = instr.map_err(|e| anyhow::anyhow!("failed to parse init expr: {}", e))?;
+        let value = match instr.operands {
+            AwwasmOperands::I32Const(op) => op.value,
+            AwwasmOperands::I32Add | AwwasmOperands::I32Sub | AwwasmOperands::I32Mul => {
+                let (Some(rhs), Some(lhs)) = (stack.pop(), stack.pop()) else {
+                    return Err(anyhow::anyhow!("init expr stack underflow at {:?}", instr.opcode));
+                };
+                match instr.operands {
+                    AwwasmOperands::I32Add => lhs.wrapping_add(rhs),
+                    AwwasmOperands::I32Sub => lhs.wrapping_sub(rhs),
+                    _ => lhs.wrapping_mul(rhs),
+                }
+            }
+            _ => return Err(anyhow::anyhow!("unsupported init expr opcode: {:?}", instr.opcode)),
+        };
+        stack.push(value);
+    }
+    match stack[..] {
+        [value] => Ok(value),
+        _ => Err(anyhow::anyhow!("init expr leaves {} values on the stack", stack.len())),
     }
 }
\ No newline at end of file
@@ -0,0 +1,145 @@
+use crate::components::error::{locate, AwwasmParseError};
+use crate::components::instructions::InstructionIterator;
+use crate::components::module::AwwasmModule;
+use crate::components::parse_limits::AwwasmParseLimits;
+use crate::components::section::{SectionCode, SectionItem};
+use crate::components::types::*;
+use nom_derive::Parse;
+use std::slice;
+
+/// One function from `AwwasmModule::functions_lazy()`: its index in the
+/// function index space, its local declarations, and an iterator decoding
+/// its instructions.
+pub type AwwasmLazyFunction<'a> = (FuncIdx, Vec<AwwasmFunctionLocals>, InstructionIterator<'a>);
+
+#[derive(Debug, Clone)]
+enum Bodies<'m, 'a> {
+    Resolved(slice::Iter<'m, AwwasmCodeSectionItem<'a>>),
+    // Rest of the raw code section body and its entries left.
+    Raw { body: &'a [u8], remaining: u32 },
+}
+
+/// Iterator over the function bodies of a module, decoding one at a time,
+/// from `AwwasmModule::functions_lazy()`.
+///
+/// Stops after the first `Err`, a located `AwwasmParseError` for a body or
+/// local declarations that cannot be decoded.
+#[derive(Debug, Clone)]
+pub struct AwwasmLazyFunctions<'m, 'a> {
+    bodies: Bodies<'m, 'a>,
+    next_idx: FuncIdx,
+    start: Option<usize>,
+    limits: AwwasmParseLimits,
+    done: bool,
+}
+
+impl<'a> AwwasmLazyFunctions<'_, 'a> {
+    fn next_body(&mut self) -> Option<anyhow::Result<AwwasmCodeSectionItem<'a>>> {
+        match &mut self.bodies {
+            Bodies::Resolved(items) => items.next().cloned().map(Ok),
+            Bodies::Raw { remaining: 0, .. } => None,
+            Bodies::Raw { body, remaining } => {
+                *remaining -= 1;
+                Some(match AwwasmCodeSectionItem::parse(body) {
+                    Ok((rest, item)) => {
+                        *body = rest;
+                        Ok(item)
+                    }
+                    Err(e) => Err(AwwasmParseError::nom("Code Section", e).within(body).into()),
+                })
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for AwwasmLazyFunctions<'_, 'a> {
+    type Item = anyhow::Result<AwwasmLazyFunction<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let func_idx = self.next_idx;
+        self.next_idx += 1;
+        let function = self.next_body()?.and_then(|item| {
+            self.limits.check_body(&item)?;
+            let (locals, code) = item.locals_and_code()?;
+            Ok((func_idx, locals, InstructionIterator::with_limits(code, &self.limits)))
+        });
+        let function = function.map_err(|e| {
+            self.done = true;
+            match self.start {
+                Some(start) => locate(e, start, Some(&SectionCode::Code), Some(func_idx)),
+                None => e,
+            }
+        });
+        Some(function)
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Iterate over the defined functions, decoding each body only as it is
+    /// reached, so a scan that stops early or skips most functions does not
+    /// pay for the rest.
+    ///
+    /// Works on the resolved `code` if present, otherwise on the raw code
+    /// section, so the module need not be resolved. Fails only if the
+    /// import section, needed to number the functions, cannot be decoded.
+    /// Bodies are held to `limits`, and one over them ends the iteration
+    /// with `LimitExceeded`.
+    pub fn functions_lazy(&self) -> anyhow::Result<AwwasmLazyFunctions<'_, 'a>> {
+        let raw = |code: SectionCode| self.sections().iter().find(move |sec| sec.section_header.section_type == code);
+        let imported = match (&self.imports, raw(SectionCode::Import)) {
+            (None, Some(sec)) => match sec.clone().resolve()? {
+                SectionItem::ImportSectionItems(imports) => imports.iter().filter(|i| i.kind == AwwasmImportKind::Function).count() as u32,
+                _ => 0,
+            },
+            _ => self.imported_func_count(),
+        };
+        let bodies = match (&self.code, raw(SectionCode::Code)) {
+            (Some(items), _) => Bodies::Resolved(items.iter()),
+            (None, Some(sec)) => Bodies::Raw { body: sec.section_body, remaining: sec.entry_count },
+            (None, None) => Bodies::Resolved([].iter()),
+        };
+        Ok(AwwasmLazyFunctions { bodies, next_idx: imported, start: self.start_address(), limits: self.limits, done: false })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::error::AwwasmParseError;
+    use crate::components::instructions::WasmOpCode;
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn functions_lazy_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (import "env" "f" (func))
+                (func (local i32 i32) (nop))
+                (func (call 0))
+                (func (local f64) (drop (f64.const 1))))
+        "#)?;
+        let module = AwwasmModule::new(&bytes)?;
+        let calls: Vec<_> = module.functions_lazy()?
+            .map(|f| f.map(|(idx, locals, mut instrs)| (idx, locals.len(), instrs.any(|i| i.is_ok_and(|i| i.opcode == WasmOpCode::Call)))))
+            .collect::<anyhow::Result<_>>()?;
+        assert_eq!(calls, vec![(1, 1, false), (2, 0, true), (3, 1, false)]);
+
+        let mut resolved = AwwasmModule::new(&bytes)?;
+        resolved.resolve_all_sections()?;
+        let indices: Vec<_> = resolved.functions_lazy()?.map(|f| f.map(|(idx, ..)| idx)).collect::<anyhow::Result<_>>()?;
+        assert_eq!(indices, vec![1, 2, 3]);
+
+        // The second body's size runs past the code section.
+        let mut bad = bytes.clone();
+        let body = bad.windows(4).position(|w| w == [0x00, 0x10, 0x00, 0x0b]).expect("body should exist");
+        bad[body - 1] = 0x40;
+        let module = AwwasmModule::new(&bad)?;
+        let results: Vec<_> = module.functions_lazy()?.collect();
+        assert_eq!(results.len(), 2);
+        let err = results[1].as_ref().unwrap_err();
+        assert_eq!(err.downcast_ref::<AwwasmParseError>().and_then(|e| e.func_idx), Some(2));
+        Ok(())
+    }
+}
@@ -0,0 +1,514 @@
+use crate::consts::*;
+use crate::components::error::AwwasmParseError;
+use crate::components::instructions::{AtomicOpCode, MiscOpCode, WasmOpCode, BLOCK_TYPE_EMPTY, MEM_ARG_MEMIDX_FLAG};
+use crate::components::module::AwwasmModule;
+use crate::components::section::SectionCode;
+use crate::components::types::{REF_NULL_TYPE, REF_TYPE};
+use nom::error::{ContextError, ParseError};
+use nom::IResult;
+use nom_derive::Parse;
+use num_traits::FromPrimitive;
+use std::fmt;
+
+/// What makes a LEB128 integer non-canonical.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmLeb128Problem {
+    /// Padded with continuation bytes; `minimal` bytes encode the same value.
+    /// The spec allows this, but no mainstream toolchain emits it.
+    NotMinimal { minimal: usize },
+    /// The final byte sets bits beyond the integer's width (or, for signed
+    /// integers, bits that are not its sign extension). Malformed per spec.
+    UnusedBitsSet,
+    /// More bytes than the integer's width allows. Malformed per spec.
+    TooLong,
+}
+
+/// A LEB128 integer that is not in canonical form, from
+/// `AwwasmModule::leb128_issues()`.
+///
+/// Parsing with `AwwasmParseOptions::canonical_leb128` set fails with it as
+/// `AwwasmParseErrorKind::NonCanonicalLeb128`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmLeb128Issue {
+    /// Offset of the first byte of the integer in the module binary.
+    pub offset: usize,
+    /// Encoded length in bytes.
+    pub len: usize,
+    /// Width of the integer the position expects: 32, 33 or 64.
+    pub bits: u32,
+    pub signed: bool,
+    pub problem: AwwasmLeb128Problem,
+}
+
+impl fmt::Display for AwwasmLeb128Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ty = format!("{}{}", if self.signed { 's' } else { 'u' }, self.bits);
+        match self.problem {
+            AwwasmLeb128Problem::NotMinimal { minimal } => write!(f, "LEB128 {} at offset {:#x} uses {} bytes where {} suffice", ty, self.offset, self.len, minimal),
+            AwwasmLeb128Problem::UnusedBitsSet => write!(f, "LEB128 {} at offset {:#x} sets bits beyond its width", ty, self.offset),
+            AwwasmLeb128Problem::TooLong => write!(f, "LEB128 {} at offset {:#x} uses {} bytes, more than its width allows", ty, self.offset, self.len),
+        }
+    }
+}
+
+// The crate's LEB128 readers. Every decoder reads its integers through these,
+// so all of them agree on the widths they accept.
+
+#[inline]
+pub(crate) fn leb128_u32<'a, E: ParseError<&'a [u8]> + ContextError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], u32, E> {
+    nom_leb128::leb128_u32(input)
+}
+
+#[inline]
+pub(crate) fn leb128_i32<'a, E: ParseError<&'a [u8]> + ContextError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], i32, E> {
+    nom_leb128::leb128_i32(input)
+}
+
+#[inline]
+pub(crate) fn leb128_i64<'a, E: ParseError<&'a [u8]> + ContextError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], i64, E> {
+    nom_leb128::leb128_i64(input)
+}
+
+/// An s33, as in block types and heap types, read as an `i64`.
+#[inline]
+pub(crate) fn leb128_s33<'a, E: ParseError<&'a [u8]> + ContextError<&'a [u8]>>(input: &'a [u8]) -> IResult<&'a [u8], i64, E> {
+    nom_leb128::leb128_i64(input)
+}
+
+// Classify the encoded integer `bytes` against a `bits`-wide integer type.
+fn problem(bytes: &[u8], bits: u32, signed: bool) -> Option<AwwasmLeb128Problem> {
+    let max = bits.div_ceil(7) as usize;
+    if bytes.len() > max {
+        return Some(AwwasmLeb128Problem::TooLong);
+    }
+    let last = bytes[bytes.len() - 1] & 0x7f;
+    if bytes.len() == max {
+        let used = bits - 7 * (max as u32 - 1);
+        let unused_ok = if signed {
+            let rest = last >> (used - 1);
+            rest == 0 || rest == 0x7f >> (used - 1)
+        } else {
+            last >> used == 0
+        };
+        if !unused_ok {
+            return Some(AwwasmLeb128Problem::UnusedBitsSet);
+        }
+    }
+    // Redundant trailing bytes: a zero byte (or, signed, 0x7f) whose sign
+    // the byte before already carries.
+    let redundant = |byte: u8, before: u8| match (signed, byte) {
+        (false, 0) => true,
+        (true, 0) => before & 0x40 == 0,
+        (true, 0x7f) => before & 0x40 != 0,
+        _ => false,
+    };
+    let mut minimal = bytes.len();
+    while minimal > 1 && redundant(bytes[minimal - 1] & 0x7f, bytes[minimal - 2] & 0x7f) {
+        minimal -= 1;
+    }
+    (minimal < bytes.len()).then_some(AwwasmLeb128Problem::NotMinimal { minimal })
+}
+
+// A walk over a module binary that reads every LEB128 integer at the width
+// its position expects and records the ones that are not canonical. It
+// follows the structure the parser decodes, but stops quietly at anything
+// malformed: each step returns the bytes after what it read, or `None` to
+// stop, and the parser reports why.
+struct Scan<'a> {
+    bytes: &'a [u8],
+    issues: Vec<AwwasmLeb128Issue>,
+}
+
+impl<'a> Scan<'a> {
+    // Read the integer at the start of `input`, recording it if it is not
+    // canonical, with its low 64 bits.
+    fn int(&mut self, input: &'a [u8], bits: u32, signed: bool) -> Option<(&'a [u8], u64)> {
+        let len = input.iter().position(|b| b & 0x80 == 0)? + 1;
+        let encoded = &input[..len];
+        if let Some(problem) = problem(encoded, bits, signed) {
+            let offset = input.as_ptr() as usize - self.bytes.as_ptr() as usize;
+            self.issues.push(AwwasmLeb128Issue { offset, len, bits, signed, problem });
+        }
+        let value = encoded.iter().rev().fold(0u64, |value, b| value << 7 | u64::from(b & 0x7f));
+        Some((&input[len..], value))
+    }
+
+    fn u32(&mut self, input: &'a [u8]) -> Option<(&'a [u8], u32)> {
+        self.int(input, 32, false).map(|(rest, value)| (rest, value as u32))
+    }
+
+    fn module(&mut self) -> Option<()> {
+        let mut input = self.bytes.get(WASM_PREAMBLE_MAGIC_SIZE_BYTES + WASM_PREAMBLE_VERSION_SIZE_BYTES..)?;
+        while !input.is_empty() {
+            let (&id, rest) = input.split_first()?;
+            let (rest, size) = self.u32(rest)?;
+            let body = rest.get(..size as usize)?;
+            self.section(SectionCode::from_u8(id)?, body)?;
+            input = &rest[size as usize..];
+        }
+        Some(())
+    }
+
+    fn section(&mut self, code: SectionCode, body: &'a [u8]) -> Option<()> {
+        let (mut input, count) = self.u32(body)?;
+        let entry: fn(&mut Self, &'a [u8]) -> Option<&'a [u8]> = match code {
+            // Just the one integer, or for a custom section the length of
+            // its name: payloads are not scanned.
+            SectionCode::Custom | SectionCode::Start | SectionCode::DataCount => return Some(()),
+            SectionCode::Type => Self::func_type,
+            SectionCode::Import => Self::import,
+            SectionCode::Function => |scan, input| Some(scan.u32(input)?.0),
+            SectionCode::Table => Self::table,
+            SectionCode::Memory => Self::limits,
+            SectionCode::Global => |scan, input| {
+                let input = scan.global_type(input)?;
+                scan.expr(input)
+            },
+            SectionCode::Export => |scan, input| {
+                let input = scan.name(input)?;
+                Some(scan.u32(input.get(1..)?)?.0)
+            },
+            SectionCode::Element => Self::element,
+            SectionCode::Code => Self::body,
+            SectionCode::Data => Self::data,
+            SectionCode::Tag => |scan, input| Some(scan.u32(input.get(1..)?)?.0),
+        };
+        for _ in 0..count {
+            input = entry(self, input)?;
+        }
+        input.is_empty().then_some(())
+    }
+
+    // Skip `count` u32s.
+    fn u32s(&mut self, mut input: &'a [u8], count: u64) -> Option<&'a [u8]> {
+        for _ in 0..count {
+            input = self.u32(input)?.0;
+        }
+        Some(input)
+    }
+
+    fn name(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let (rest, len) = self.u32(input)?;
+        rest.get(len as usize..)
+    }
+
+    fn val_type(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        match input.split_first()? {
+            (&(REF_NULL_TYPE | REF_TYPE), rest) => Some(self.int(rest, 33, true)?.0),
+            (_, rest) => Some(rest),
+        }
+    }
+
+    fn val_types(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let (mut input, count) = self.u32(input)?;
+        for _ in 0..count {
+            input = self.val_type(input)?;
+        }
+        Some(input)
+    }
+
+    fn func_type(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let input = input.strip_prefix(WASM_TYPE_SECTION_OPCODE_FUNC)?;
+        let input = self.val_types(input)?;
+        self.val_types(input)
+    }
+
+    fn limits(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let (mut input, flags) = self.u32(input)?;
+        input = self.u32(input)?.0;
+        for bit in [0x1, 0x8] {
+            if flags & bit != 0 {
+                input = self.u32(input)?.0;
+            }
+        }
+        Some(input)
+    }
+
+    fn table(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let input = input.get(1..)?;
+        self.limits(input)
+    }
+
+    fn global_type(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let input = self.val_type(input)?;
+        input.get(1..)
+    }
+
+    fn import(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let input = self.name(input)?;
+        let input = self.name(input)?;
+        match input.split_first()? {
+            (0x00, rest) => Some(self.u32(rest)?.0),
+            (0x01, rest) => self.table(rest),
+            (0x02, rest) => self.limits(rest),
+            (0x03, rest) => self.global_type(rest),
+            (0x04, rest) => Some(self.u32(rest.get(1..)?)?.0),
+            _ => None,
+        }
+    }
+
+    fn element(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let (mut input, flags) = self.u32(input)?;
+        if flags > 7 {
+            return None;
+        }
+        // Bit 0: passive or declarative; bit 1: an explicit table index (when
+        // active) or an element kind; bit 2: expressions instead of indices.
+        if flags & 0x3 == 0x2 {
+            input = self.u32(input)?.0;
+        }
+        if flags & 0x1 == 0 {
+            input = self.expr(input)?;
+        }
+        if flags & 0x3 != 0 {
+            input = input.get(1..)?;
+        }
+        let (mut input, count) = self.u32(input)?;
+        if flags & 0x4 == 0 {
+            return self.u32s(input, count.into());
+        }
+        for _ in 0..count {
+            input = self.expr(input)?;
+        }
+        Some(input)
+    }
+
+    fn data(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let (mut input, flags) = self.u32(input)?;
+        if flags == 0x02 {
+            input = self.u32(input)?.0;
+        }
+        if flags == 0x00 || flags == 0x02 {
+            input = self.expr(input)?;
+        }
+        let (input, size) = self.u32(input)?;
+        input.get(size as usize..)
+    }
+
+    fn body(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let (rest, size) = self.u32(input)?;
+        let body = rest.get(..size as usize)?;
+        let (mut code, count) = self.u32(body)?;
+        for _ in 0..count {
+            code = self.u32(code)?.0;
+            code = self.val_type(code)?;
+        }
+        while !code.is_empty() {
+            code = self.instr(code)?;
+        }
+        Some(&rest[size as usize..])
+    }
+
+    // A constant expression, through its `end`.
+    fn expr(&mut self, mut input: &'a [u8]) -> Option<&'a [u8]> {
+        while *input.first()? != WASM_FUNC_SECTION_OPCODE_END {
+            input = self.instr(input)?;
+        }
+        Some(&input[1..])
+    }
+
+    fn block_type(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        match *input.first()? {
+            BLOCK_TYPE_EMPTY => Some(&input[1..]),
+            b if b & 0xC0 == 0x40 => self.val_type(input),
+            _ => Some(self.int(input, 33, true)?.0),
+        }
+    }
+
+    fn mem_arg(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        let (mut input, flags) = self.u32(input)?;
+        if flags & MEM_ARG_MEMIDX_FLAG != 0 {
+            input = self.u32(input)?.0;
+        }
+        Some(self.u32(input)?.0)
+    }
+
+    // One instruction, with its immediates. Bodies are not nested here: the
+    // markers that close them are instructions of their own.
+    fn instr(&mut self, input: &'a [u8]) -> Option<&'a [u8]> {
+        use WasmOpCode::*;
+        let (input, opcode) = WasmOpCode::parse(input).ok()?;
+        let memory = I32Load as u8..=I64Store32 as u8;
+        match opcode {
+            Block | Loop | If | Try => self.block_type(input),
+            TryTable => {
+                let input = self.block_type(input)?;
+                let (mut input, count) = self.u32(input)?;
+                for _ in 0..count {
+                    let (&kind, rest) = input.split_first()?;
+                    input = if kind <= 0x01 { self.u32(rest)?.0 } else { rest };
+                    input = self.u32(input)?.0;
+                }
+                Some(input)
+            }
+            Catch | Throw | Rethrow | Delegate | Br | BrIf | BrOnNull | BrOnNonNull | Call | ReturnCall | CallRef | ReturnCallRef
+            | RefFunc | LocalGet | LocalSet | LocalTee | GlobalGet | GlobalSet | MemorySize | MemoryGrow => Some(self.u32(input)?.0),
+            BrTable => {
+                let (input, count) = self.u32(input)?;
+                self.u32s(input, u64::from(count) + 1)
+            }
+            CallIndirect | ReturnCallIndirect => self.u32s(input, 2),
+            SelectTyped => self.val_types(input),
+            RefNull => Some(self.int(input, 33, true)?.0),
+            I32Const => Some(self.int(input, 32, true)?.0),
+            I64Const => Some(self.int(input, 64, true)?.0),
+            F32Const => input.get(4..),
+            F64Const => input.get(8..),
+            Misc => {
+                let (input, sub_op) = self.u32(input)?;
+                match MiscOpCode::from_u32(sub_op)? {
+                    MiscOpCode::MemoryInit | MiscOpCode::MemoryCopy => self.u32s(input, 2),
+                    MiscOpCode::DataDrop | MiscOpCode::MemoryFill => Some(self.u32(input)?.0),
+                    _ => Some(input),
+                }
+            }
+            Atomic => {
+                let (input, sub_op) = self.u32(input)?;
+                match AtomicOpCode::from_u32(sub_op)? {
+                    AtomicOpCode::AtomicFence => input.get(1..),
+                    _ => self.mem_arg(input),
+                }
+            }
+            _ if memory.contains(&(opcode as u8)) => self.mem_arg(input),
+            // No immediates.
+            _ => Some(input),
+        }
+    }
+}
+
+impl AwwasmModule<'_> {
+    /// Every non-canonical LEB128 integer in the module's section headers,
+    /// section contents and function bodies, by offset. Custom section
+    /// payloads are not scanned.
+    ///
+    /// Fails like `AwwasmModule::new()` if the module does not parse, except
+    /// that an integer too long for its position fails with that issue, as
+    /// an `AwwasmParseError` of kind `NonCanonicalLeb128`.
+    pub fn leb128_issues(input: &[u8]) -> anyhow::Result<Vec<AwwasmLeb128Issue>> {
+        let mut scan = Scan { bytes: input, issues: Vec::new() };
+        scan.module();
+        let parsed = AwwasmModule::new(input).and_then(|mut module| {
+            module.resolve_all_sections()?;
+            for item in module.code() {
+                item.instructions()?;
+            }
+            Ok(())
+        });
+        match parsed {
+            Ok(()) => Ok(scan.issues),
+            Err(e) => match scan.issues.into_iter().find(|i| i.problem == AwwasmLeb128Problem::TooLong) {
+                Some(issue) => Err(AwwasmParseError::from(issue).into()),
+                None => Err(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+    use crate::components::features::AwwasmParseOptions;
+    use crate::components::fixtures;
+    use crate::components::leb128::{AwwasmLeb128Issue, AwwasmLeb128Problem, Scan};
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn leb128_issues_test() -> anyhow::Result<()> {
+        let canonical = wat::parse_str("(module (func (result i32) (block (result i32) (i32.const -1))))")?;
+        assert!(AwwasmModule::leb128_issues(&canonical)?.is_empty());
+
+        let module = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            0x01, 0x85, 0x80, 0x00, 0x01, 0x60, 0x00, 0x01, 0x7f,      // type section, size padded to 3 bytes
+            0x03, 0x06, 0x01, 0x80, 0x80, 0x80, 0x80, 0x10,            // type index 0 with bit 32 set
+            0x0a, 0x0a, 0x01, 0x08, 0x00,                              // code section, one body
+            0x02, 0x7f,                                                // block (result i32)
+            0x41, 0xff, 0x7f,                                          // i32.const -1 in two bytes
+            0x0b, 0x0b,
+        ];
+        let issues = AwwasmModule::leb128_issues(&module)?;
+        assert_eq!(issues, vec![
+            AwwasmLeb128Issue { offset: 0x9, len: 3, bits: 32, signed: false, problem: AwwasmLeb128Problem::NotMinimal { minimal: 1 } },
+            AwwasmLeb128Issue { offset: 0x14, len: 5, bits: 32, signed: false, problem: AwwasmLeb128Problem::UnusedBitsSet },
+            AwwasmLeb128Issue { offset: 0x21, len: 2, bits: 32, signed: true, problem: AwwasmLeb128Problem::NotMinimal { minimal: 1 } },
+        ]);
+        assert_eq!(issues[0].to_string(), "LEB128 u32 at offset 0x9 uses 3 bytes where 1 suffice");
+        assert_eq!(issues[1].to_string(), "LEB128 u32 at offset 0x14 sets bits beyond its width");
+        assert!(AwwasmModule::new_with_options(&module, &AwwasmParseOptions::default()).is_ok());
+        let strict = AwwasmParseOptions { canonical_leb128: true, ..Default::default() };
+        let err = AwwasmModule::new_with_options(&module, &strict).unwrap_err();
+        let kind = err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind);
+        assert_eq!(kind, Some(&AwwasmParseErrorKind::NonCanonicalLeb128(issues[0].clone())));
+
+        let mut overlong = module.to_vec();
+        overlong[0x12] = 0x07;
+        overlong[0x18] = 0x80;
+        overlong.insert(0x19, 0x00);
+        let err = AwwasmModule::leb128_issues(&overlong).unwrap_err();
+        let kind = err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind);
+        assert!(matches!(kind, Some(AwwasmParseErrorKind::NonCanonicalLeb128(AwwasmLeb128Issue { problem: AwwasmLeb128Problem::TooLong, .. }))), "{:?}", kind);
+        Ok(())
+    }
+
+    #[test]
+    fn leb128_scan_test() -> anyhow::Result<()> {
+        // The scan walks each of these to the end, finding nothing.
+        let wide = wat::parse_str(r#"
+            (module
+                (type $t (func (param i32) (result i32)))
+                (import "env" "f" (func (type $t)))
+                (import "env" "t" (table 1 funcref))
+                (import "env" "m" (memory 1 2 shared))
+                (import "env" "g" (global (mut i64)))
+                (import "env" "e" (tag (param i32)))
+                (memory $m2 1)
+                (table $tab 2 externref)
+                (global $r funcref (ref.null func))
+                (global i64 (i64.const -300))
+                (tag $x (param i32))
+                (func $run (type $t)
+                    (block $b (result i32)
+                        (try (result i32)
+                            (do
+                                (i32.store $m2 (i32.const 0) (i32.load offset=40000 (local.get 0)))
+                                (drop (i32.atomic.rmw.add (i32.const 0) (i32.const 1)))
+                                (atomic.fence)
+                                (memory.init 0 (i32.const 0) (i32.const 0) (i32.const 0))
+                                (data.drop 1)
+                                (memory.copy (i32.const 0) (i32.const 0) (i32.const 0))
+                                (br_table 0 1 0 (local.get 0) (local.get 0)))
+                            (catch $x)
+                            (catch_all (i32.const 0))))
+                    (drop)
+                    (drop (select (result f64) (f64.const 1.5) (f64.const 2) (i32.const 1)))
+                    (call_indirect (type $t) (i32.const 7) (i32.const 0))
+                    (drop (call_ref $t (i32.const 1) (ref.func $run)))
+                    (drop (ref.is_null (global.get $r)))
+                    (drop (memory.grow $m2 (i32.const 1)))
+                    (f32.const 0.5) (drop)
+                    (local.tee 0 (i32.const -1)))
+                (export "run" (func $run))
+                (start 0)
+                (elem (i32.const 0) func $run)
+                (elem func $run)
+                (elem declare funcref (ref.func $run))
+                (elem (table $tab) (i32.const 0) externref (ref.null extern))
+                (data (i32.const 1024) "x")
+                (data "y")
+                (data (memory $m2) (i32.const 0) "z"))
+        "#)?;
+        let inputs = fixtures::VALID.iter().map(|(_, bytes)| *bytes).chain([&wide[..]]);
+        for bytes in inputs {
+            let mut scan = Scan { bytes, issues: Vec::new() };
+            assert_eq!(scan.module(), Some(()));
+            assert!(scan.issues.is_empty(), "{:?}", scan.issues);
+            assert!(AwwasmModule::leb128_issues(bytes)?.is_empty());
+        }
+
+        // No locals, then (try_table (result i32) (catch 0 0) (catch_all_ref 1) throw_ref).
+        let body: &[u8] = &[0x0c, 0x00, 0x1f, 0x7f, 0x02, 0x00, 0x00, 0x00, 0x03, 0x01, 0x0a, 0x0b, 0x0b];
+        let mut scan = Scan { bytes: body, issues: Vec::new() };
+        assert_eq!(scan.body(body), Some(&[][..]));
+        Ok(())
+    }
+}
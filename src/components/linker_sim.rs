@@ -0,0 +1,266 @@
+use crate::components::module::AwwasmModule;
+use crate::components::types::*;
+use std::fmt;
+
+/// A function signature, as declared by a type section entry or a host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmFuncSig {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+impl fmt::Display for AwwasmFuncSig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |types: &[ValType]| types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ");
+        write!(f, "({}) -> ({})", list(&self.params), list(&self.results))
+    }
+}
+
+/// Size limits of a memory or table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmLimits {
+    pub min: u32,
+    pub max: Option<u32>,
+}
+
+impl AwwasmLimits {
+    /// Whether limits `self` (provided) satisfy `expected`: at least as large
+    /// a minimum and, if `expected` has a maximum, a maximum no larger than it.
+    pub fn matches(&self, expected: &AwwasmLimits) -> bool {
+        self.min >= expected.min && match (expected.max, self.max) {
+            (None, _) => true,
+            (Some(e), Some(p)) => p <= e,
+            (Some(_), None) => false,
+        }
+    }
+}
+
+impl From<&AwwasmMemoryParams> for AwwasmLimits {
+    fn from(p: &AwwasmMemoryParams) -> Self {
+        Self { min: p.min, max: p.max }
+    }
+}
+
+impl fmt::Display for AwwasmLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max {
+            Some(max) => write!(f, "{}..{}", self.min, max),
+            None => write!(f, "{}..", self.min),
+        }
+    }
+}
+
+/// What a host provides under a given module/name pair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmHostExtern {
+    Func(AwwasmFuncSig),
+    Table { elem_type: ValType, limits: AwwasmLimits },
+    Memory(AwwasmLimits),
+    Global { value_type: ValType, mutable: bool },
+    /// An exception tag carrying values of the signature's parameter types.
+    Tag(AwwasmFuncSig),
+}
+
+impl AwwasmHostExtern {
+    fn kind_name(&self) -> &'static str {
+        match self {
+            AwwasmHostExtern::Func(_) => "function",
+            AwwasmHostExtern::Table { .. } => "table",
+            AwwasmHostExtern::Memory(_) => "memory",
+            AwwasmHostExtern::Global { .. } => "global",
+            AwwasmHostExtern::Tag(_) => "tag",
+        }
+    }
+}
+
+impl fmt::Display for AwwasmHostExtern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AwwasmHostExtern::Func(sig) => write!(f, "func {}", sig),
+            AwwasmHostExtern::Table { elem_type, limits } => write!(f, "table {} {}", limits, elem_type),
+            AwwasmHostExtern::Memory(limits) => write!(f, "memory {}", limits),
+            AwwasmHostExtern::Global { value_type, mutable: true } => write!(f, "global mut {}", value_type),
+            AwwasmHostExtern::Global { value_type, mutable: false } => write!(f, "global {}", value_type),
+            AwwasmHostExtern::Tag(sig) => write!(f, "tag {}", sig),
+        }
+    }
+}
+
+/// A host-side definition offered for import resolution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmHostDecl {
+    pub module: String,
+    pub name: String,
+    pub item: AwwasmHostExtern,
+}
+
+impl AwwasmHostDecl {
+    pub fn new(module: &str, name: &str, item: AwwasmHostExtern) -> Self {
+        Self { module: module.to_owned(), name: name.to_owned(), item }
+    }
+}
+
+/// Why an import would fail to resolve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmImportProblem {
+    /// No host declaration has this module/name.
+    Unresolved,
+    /// The import names a type index the module does not define.
+    InvalidTypeIndex(u32),
+    /// The import carries no type for its kind, as a hand-built
+    /// `AwwasmImportSectionItem` may.
+    MissingType,
+    /// The host provides something of a different kind or type.
+    Mismatch { expected: AwwasmHostExtern, provided: AwwasmHostExtern },
+}
+
+/// An import that would fail instantiation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmImportIssue {
+    /// Position in the import section.
+    pub import_index: u32,
+    pub module: String,
+    pub name: String,
+    pub problem: AwwasmImportProblem,
+}
+
+impl fmt::Display for AwwasmImportIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "import {} ({}.{}): ", self.import_index, self.module, self.name)?;
+        match &self.problem {
+            AwwasmImportProblem::Unresolved => write!(f, "not provided by the host"),
+            AwwasmImportProblem::InvalidTypeIndex(idx) => write!(f, "references undefined type {}", idx),
+            AwwasmImportProblem::MissingType => write!(f, "carries no type for its kind"),
+            AwwasmImportProblem::Mismatch { expected, provided } if expected.kind_name() != provided.kind_name() =>
+                write!(f, "expected a {}, host provides a {}", expected.kind_name(), provided.kind_name()),
+            AwwasmImportProblem::Mismatch { expected, provided } =>
+                write!(f, "expected {}, host provides {}", expected, provided),
+        }
+    }
+}
+
+// What the module expects for `import`, in host terms.
+fn expected_extern(module: &AwwasmModule, import: &AwwasmImportSectionItem) -> Result<AwwasmHostExtern, AwwasmImportProblem> {
+    let sig = |idx: u32| {
+        let ty = module.types().get(idx as usize)
+            .ok_or(AwwasmImportProblem::InvalidTypeIndex(idx))?;
+        Ok(AwwasmFuncSig { params: ty.fn_args.clone(), results: ty.fn_rets.clone() })
+    };
+    let missing = AwwasmImportProblem::MissingType;
+    match import.kind {
+        AwwasmImportKind::Function => sig(import.func_type_idx.ok_or(missing)?).map(AwwasmHostExtern::Func),
+        AwwasmImportKind::Tag => {
+            let tag = import.tag.as_ref().ok_or(missing)?;
+            sig(tag.type_idx).map(AwwasmHostExtern::Tag)
+        }
+        AwwasmImportKind::Table => {
+            let table = import.table.as_ref().ok_or(missing)?;
+            Ok(AwwasmHostExtern::Table { elem_type: table.elem_type.clone().into(), limits: (&table.limits).into() })
+        }
+        AwwasmImportKind::Memory => {
+            let mem = import.mem.as_ref().ok_or(missing)?;
+            Ok(AwwasmHostExtern::Memory(mem.into()))
+        }
+        AwwasmImportKind::Global => {
+            let global = import.global.as_ref().ok_or(missing)?;
+            Ok(AwwasmHostExtern::Global {
+                value_type: global.value_type,
+                mutable: global.mutability == AwwasmGlobalMutability::Mutable,
+            })
+        }
+    }
+}
+
+fn provides(provided: &AwwasmHostExtern, expected: &AwwasmHostExtern) -> bool {
+    match (provided, expected) {
+        (AwwasmHostExtern::Func(p), AwwasmHostExtern::Func(e)) => p == e,
+        (AwwasmHostExtern::Table { elem_type: pt, limits: pl }, AwwasmHostExtern::Table { elem_type: et, limits: el }) =>
+            pt == et && pl.matches(el),
+        (AwwasmHostExtern::Memory(p), AwwasmHostExtern::Memory(e)) => p.matches(e),
+        (AwwasmHostExtern::Global { .. }, AwwasmHostExtern::Global { .. })
+        | (AwwasmHostExtern::Tag(_), AwwasmHostExtern::Tag(_)) => provided == expected,
+        _ => false,
+    }
+}
+
+/// Check every import of `module` against `host_decls`, reporting each one
+/// that is missing or whose provided definition does not match.
+///
+/// Matching follows the spec's import rules: function and global types must
+/// be identical, and provided memory/table limits must fit within the
+/// imported ones. Requires `resolve_all_sections()`.
+pub fn resolve(module: &AwwasmModule, host_decls: &[AwwasmHostDecl]) -> Vec<AwwasmImportIssue> {
+    let mut issues = Vec::new();
+    for (i, import) in module.imports().iter().enumerate() {
+        let module_name = String::from_utf8_lossy(&import.module.bytes).into_owned();
+        let name = String::from_utf8_lossy(&import.name.bytes).into_owned();
+        let decl = host_decls.iter()
+            .find(|d| d.module.as_bytes() == &*import.module.bytes && d.name.as_bytes() == &*import.name.bytes);
+
+        let problem = match (expected_extern(module, import), decl) {
+            (Err(problem), _) => Some(problem),
+            (Ok(_), None) => Some(AwwasmImportProblem::Unresolved),
+            (Ok(expected), Some(decl)) if !provides(&decl.item, &expected) =>
+                Some(AwwasmImportProblem::Mismatch { expected, provided: decl.item.clone() }),
+            (Ok(_), Some(_)) => None,
+        };
+        if let Some(problem) = problem {
+            issues.push(AwwasmImportIssue { import_index: i as u32, module: module_name, name, problem });
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::linker_sim::*;
+    use crate::components::module::AwwasmModule;
+    use crate::components::types::ValType;
+
+    #[test]
+    fn resolve_imports_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "log" (func (param i32)))
+                (import "env" "now" (func (result i64)))
+                (import "env" "mem" (memory 1 4))
+                (import "env" "g" (global (mut i32)))
+                (import "env" "missing" (func))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let host = vec![
+            AwwasmHostDecl::new("env", "log", AwwasmHostExtern::Func(AwwasmFuncSig { params: vec![ValType::I32], results: vec![] })),
+            AwwasmHostDecl::new("env", "now", AwwasmHostExtern::Func(AwwasmFuncSig { params: vec![], results: vec![ValType::I32] })),
+            AwwasmHostDecl::new("env", "mem", AwwasmHostExtern::Memory(AwwasmLimits { min: 2, max: Some(8) })),
+            AwwasmHostDecl::new("env", "g", AwwasmHostExtern::Memory(AwwasmLimits { min: 1, max: None })),
+        ];
+        let issues = resolve(&module_parsed, &host);
+        let messages: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+        assert_eq!(messages, vec![
+            "import 1 (env.now): expected func () -> (i64), host provides func () -> (i32)",
+            "import 2 (env.mem): expected memory 1..4, host provides memory 2..8",
+            "import 3 (env.g): expected a global, host provides a memory",
+            "import 4 (env.missing): not provided by the host",
+        ]);
+        assert_eq!(issues[3].problem, AwwasmImportProblem::Unresolved);
+
+        let fixed = vec![
+            host[0].clone(),
+            AwwasmHostDecl::new("env", "now", AwwasmHostExtern::Func(AwwasmFuncSig { params: vec![], results: vec![ValType::I64] })),
+            AwwasmHostDecl::new("env", "mem", AwwasmHostExtern::Memory(AwwasmLimits { min: 2, max: Some(4) })),
+            AwwasmHostDecl::new("env", "g", AwwasmHostExtern::Global { value_type: ValType::I32, mutable: true }),
+            AwwasmHostDecl::new("env", "missing", AwwasmHostExtern::Func(AwwasmFuncSig { params: vec![], results: vec![] })),
+        ];
+        assert!(resolve(&module_parsed, &fixed).is_empty());
+
+        // An edited import that lost its type is reported, not a panic.
+        module_parsed.imports.as_mut().expect("imports should exist")[2].mem = None;
+        let issues = resolve(&module_parsed, &fixed);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].to_string(), "import 2 (env.mem): carries no type for its kind");
+        Ok(())
+    }
+}
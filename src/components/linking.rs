@@ -0,0 +1,423 @@
+use crate::components::error::AwwasmParseError;
+use crate::components::module::AwwasmModule;
+use crate::components::names::AwwasmSubsection;
+use crate::components::types::AwwasmName;
+use num_derive::FromPrimitive;
+use nom_derive::*;
+use crate::components::leb128::{leb128_i64, leb128_u32};
+use nom::IResult;
+use nom::combinator::{all_consuming, cond};
+use nom::multi::length_count;
+
+const LINKING_SECTION: &[u8] = b"linking";
+const RELOC_SECTION_PREFIX: &[u8] = b"reloc.";
+const LINKING_VERSION: u32 = 2;
+
+const SEGMENT_INFO_SUBSECTION: u8 = 5;
+const INIT_FUNCS_SUBSECTION: u8 = 6;
+const COMDAT_INFO_SUBSECTION: u8 = 7;
+const SYMBOL_TABLE_SUBSECTION: u8 = 8;
+
+/// What a symbol table entry refers to.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, Nom)]
+#[nom(LittleEndian)]
+pub enum AwwasmSymbolKind {
+    Function = 0,
+    Data = 1,
+    Global = 2,
+    Section = 3,
+    Tag = 4,
+    Table = 5,
+}
+
+/// Symbol flags, as the raw bit set.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AwwasmSymbolFlags(pub u32);
+
+impl AwwasmSymbolFlags {
+    pub const BINDING_WEAK: u32 = 0x1;
+    pub const BINDING_LOCAL: u32 = 0x2;
+    pub const VISIBILITY_HIDDEN: u32 = 0x4;
+    pub const UNDEFINED: u32 = 0x10;
+    pub const EXPORTED: u32 = 0x20;
+    pub const EXPLICIT_NAME: u32 = 0x40;
+    pub const NO_STRIP: u32 = 0x80;
+    pub const TLS: u32 = 0x100;
+    pub const ABSOLUTE: u32 = 0x200;
+
+    pub fn contains(self, flag: u32) -> bool {
+        self.0 & flag == flag
+    }
+
+    pub fn is_weak(self) -> bool {
+        self.contains(Self::BINDING_WEAK)
+    }
+
+    pub fn is_local(self) -> bool {
+        self.contains(Self::BINDING_LOCAL)
+    }
+
+    pub fn is_hidden(self) -> bool {
+        self.contains(Self::VISIBILITY_HIDDEN)
+    }
+
+    pub fn is_undefined(self) -> bool {
+        self.contains(Self::UNDEFINED)
+    }
+
+    pub fn is_exported(self) -> bool {
+        self.contains(Self::EXPORTED)
+    }
+}
+
+/// Location of a defined data symbol within its segment.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmDataSymbolDef {
+    /// Data segment index.
+    #[nom(Parse = "leb128_u32")]
+    pub segment: u32,
+    #[nom(Parse = "leb128_u32")]
+    pub offset: u32,
+    #[nom(Parse = "leb128_u32")]
+    pub size: u32,
+}
+
+/// One symbol table entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmSymbolInfo<'a> {
+    pub kind: AwwasmSymbolKind,
+    pub flags: AwwasmSymbolFlags,
+    /// Function, global, tag, table or section index; `None` for data symbols.
+    pub index: Option<u32>,
+    /// Absent for undefined function/global/tag/table symbols that take the
+    /// import's name, and always absent for section symbols.
+    pub name: Option<AwwasmName<'a>>,
+    /// Present for defined data symbols.
+    pub data: Option<AwwasmDataSymbolDef>,
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmSymbolInfo<'a> {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (i, kind) = AwwasmSymbolKind::parse(i)?;
+        let (i, flags) = leb128_u32(i)?;
+        let flags = AwwasmSymbolFlags(flags);
+        match kind {
+            AwwasmSymbolKind::Data => {
+                let (i, name) = AwwasmName::parse(i)?;
+                let (i, data) = cond(!flags.is_undefined(), AwwasmDataSymbolDef::parse)(i)?;
+                Ok((i, Self { kind, flags, index: None, name: Some(name), data }))
+            }
+            AwwasmSymbolKind::Section => {
+                let (i, index) = leb128_u32(i)?;
+                Ok((i, Self { kind, flags, index: Some(index), name: None, data: None }))
+            }
+            _ => {
+                let (i, index) = leb128_u32(i)?;
+                let has_name = !flags.is_undefined() || flags.contains(AwwasmSymbolFlags::EXPLICIT_NAME);
+                let (i, name) = cond(has_name, AwwasmName::parse)(i)?;
+                Ok((i, Self { kind, flags, index: Some(index), name, data: None }))
+            }
+        }
+    }
+}
+
+/// Name, alignment and flags of a data segment.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmSegmentInfo<'a> {
+    pub name: AwwasmName<'a>,
+    /// Alignment as a power of two.
+    #[nom(Parse = "leb128_u32")]
+    pub alignment: u32,
+    #[nom(Parse = "leb128_u32")]
+    pub flags: u32,
+}
+
+/// A function to run at startup, ordered by ascending priority.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmInitFunc {
+    #[nom(Parse = "leb128_u32")]
+    pub priority: u32,
+    #[nom(Parse = "leb128_u32")]
+    pub symbol_index: u32,
+}
+
+/// A member of a COMDAT group.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmComdatSym {
+    /// 0 data segment, 1 function, 2 global, 3 tag, 4 table, 5 section.
+    pub kind: u8,
+    #[nom(Parse = "leb128_u32")]
+    pub index: u32,
+}
+
+/// A COMDAT group: items the linker keeps at most one copy of.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmComdat<'a> {
+    pub name: AwwasmName<'a>,
+    #[nom(Parse = "leb128_u32")]
+    pub flags: u32,
+    #[nom(LengthCount = "leb128_u32")]
+    pub symbols: Vec<AwwasmComdatSym>,
+}
+
+/// Decoded contents of the `linking` custom section of a relocatable object.
+///
+/// Unknown subsections are skipped.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmLinkingSection<'a> {
+    pub version: u32,
+    pub segments: Vec<AwwasmSegmentInfo<'a>>,
+    pub init_funcs: Vec<AwwasmInitFunc>,
+    pub comdats: Vec<AwwasmComdat<'a>>,
+    pub symbols: Vec<AwwasmSymbolInfo<'a>>,
+}
+
+impl<'a> AwwasmLinkingSection<'a> {
+    /// Decode the payload of a `linking` custom section.
+    pub fn parse(payload: &'a [u8]) -> anyhow::Result<Self> {
+        let (mut rest, version) = leb128_u32::<nom::error::Error<&[u8]>>(payload)
+            .map_err(|e| AwwasmParseError::nom("Linking Section version", e))?;
+        if version != LINKING_VERSION {
+            return Err(anyhow::anyhow!("Unsupported WASM Linking Section version {} (expected {})", version, LINKING_VERSION));
+        }
+
+        let mut linking = Self { version, ..Default::default() };
+        while !rest.is_empty() {
+            let (next, sub) = AwwasmSubsection::parse(rest)
+                .map_err(|e| AwwasmParseError::nom("Linking Subsection", e))?;
+            let body = sub.body;
+            match sub.id {
+                SEGMENT_INFO_SUBSECTION => {
+                    let (_, segments) = all_consuming(length_count(leb128_u32, AwwasmSegmentInfo::parse))(body)
+                        .map_err(|e| AwwasmParseError::nom("Linking Segment Info", e))?;
+                    linking.segments = segments;
+                }
+                INIT_FUNCS_SUBSECTION => {
+                    let (_, init_funcs) = all_consuming(length_count(leb128_u32, AwwasmInitFunc::parse))(body)
+                        .map_err(|e| AwwasmParseError::nom("Linking Init Funcs", e))?;
+                    linking.init_funcs = init_funcs;
+                }
+                COMDAT_INFO_SUBSECTION => {
+                    let (_, comdats) = all_consuming(length_count(leb128_u32, AwwasmComdat::parse))(body)
+                        .map_err(|e| AwwasmParseError::nom("Linking Comdat Info", e))?;
+                    linking.comdats = comdats;
+                }
+                SYMBOL_TABLE_SUBSECTION => {
+                    let (_, symbols) = all_consuming(length_count(leb128_u32, AwwasmSymbolInfo::parse))(body)
+                        .map_err(|e| AwwasmParseError::nom("Linking Symbol Table", e))?;
+                    linking.symbols = symbols;
+                }
+                _ => { /* skip */ }
+            }
+            rest = next;
+        }
+        Ok(linking)
+    }
+}
+
+/// Relocation types from the tool conventions' `Linking.md`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, Nom)]
+#[nom(LittleEndian)]
+pub enum AwwasmRelocType {
+    FunctionIndexLeb = 0,
+    TableIndexSleb = 1,
+    TableIndexI32 = 2,
+    MemoryAddrLeb = 3,
+    MemoryAddrSleb = 4,
+    MemoryAddrI32 = 5,
+    TypeIndexLeb = 6,
+    GlobalIndexLeb = 7,
+    FunctionOffsetI32 = 8,
+    SectionOffsetI32 = 9,
+    TagIndexLeb = 10,
+    MemoryAddrRelSleb = 11,
+    TableIndexRelSleb = 12,
+    GlobalIndexI32 = 13,
+    MemoryAddrLeb64 = 14,
+    MemoryAddrSleb64 = 15,
+    MemoryAddrI64 = 16,
+    MemoryAddrRelSleb64 = 17,
+    TableIndexSleb64 = 18,
+    TableIndexI64 = 19,
+    TableNumberLeb = 20,
+    MemoryAddrTlsSleb = 21,
+    FunctionOffsetI64 = 22,
+    MemoryAddrLocrelI32 = 23,
+    TableIndexRelSleb64 = 24,
+    MemoryAddrTlsSleb64 = 25,
+    FunctionIndexI32 = 26,
+}
+
+impl AwwasmRelocType {
+    /// Whether entries of this type carry a signed addend.
+    pub fn has_addend(self) -> bool {
+        use AwwasmRelocType::*;
+        matches!(self,
+            MemoryAddrLeb | MemoryAddrSleb | MemoryAddrI32 | FunctionOffsetI32 | SectionOffsetI32
+            | MemoryAddrRelSleb | MemoryAddrLeb64 | MemoryAddrSleb64 | MemoryAddrI64
+            | MemoryAddrRelSleb64 | MemoryAddrTlsSleb | FunctionOffsetI64 | MemoryAddrLocrelI32
+            | MemoryAddrTlsSleb64)
+    }
+}
+
+/// One relocation: patch the value at `offset` using symbol (or type) `index`.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmRelocEntry {
+    pub reloc_type: AwwasmRelocType,
+    /// Offset from the start of the target section's payload.
+    #[nom(Parse = "leb128_u32")]
+    pub offset: u32,
+    /// Symbol table index, or a type index for `TypeIndexLeb`.
+    #[nom(Parse = "leb128_u32")]
+    pub index: u32,
+    #[nom(Cond = "reloc_type.has_addend()", Parse = "leb128_i64")]
+    pub addend: Option<i64>,
+}
+
+/// A decoded `reloc.*` custom section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmRelocSection<'a> {
+    /// Full custom section name, e.g. `reloc.CODE`.
+    pub name: AwwasmName<'a>,
+    /// Index of the section the relocations apply to.
+    pub target_section: u32,
+    pub entries: Vec<AwwasmRelocEntry>,
+}
+
+impl<'a> AwwasmRelocSection<'a> {
+    /// Decode the payload of a `reloc.*` custom section called `name`.
+    pub fn parse(name: AwwasmName<'a>, payload: &'a [u8]) -> anyhow::Result<Self> {
+        let (rest, target_section) = leb128_u32::<nom::error::Error<&[u8]>>(payload)
+            .map_err(|e| AwwasmParseError::nom("Reloc Section target", e))?;
+        let (_, entries) = all_consuming(length_count(leb128_u32, AwwasmRelocEntry::parse))(rest)
+            .map_err(|e| AwwasmParseError::nom("Reloc Section entries", e))?;
+        Ok(Self { name, target_section, entries })
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Decode every `reloc.*` custom section, in binary order.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn relocations(&self) -> anyhow::Result<Vec<AwwasmRelocSection<'a>>> {
+        self.customs().iter()
+            .filter(|c| c.name.bytes.starts_with(RELOC_SECTION_PREFIX))
+            .map(|c| AwwasmRelocSection::parse(c.name.clone(), c.payload))
+            .collect()
+    }
+
+    /// Decode the `linking` custom section, if the module has one.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn linking(&self) -> anyhow::Result<Option<AwwasmLinkingSection<'a>>> {
+        self.custom_section(LINKING_SECTION)
+            .map(|c| AwwasmLinkingSection::parse(c.payload))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::linking::{
+        AwwasmDataSymbolDef, AwwasmInitFunc, AwwasmRelocEntry, AwwasmRelocType,
+        AwwasmSymbolFlags, AwwasmSymbolKind,
+    };
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn decode_linking_section_test() -> anyhow::Result<()> {
+        // version 2
+        // symbol table: main (defined function 0), buf (data in segment 0 at
+        //   offset 16, 32 bytes), and an undefined function 1 without a name
+        // segment info: ".data", align 2^4
+        // init funcs: priority 65 -> symbol 0
+        let module = wat::parse_str(r#"
+            (module
+                (@custom "linking"
+                    "\02"
+                    "\08\15\03"
+                    "\00\00\00\04main"
+                    "\01\00\03buf\00\10\20"
+                    "\00\10\01"
+                    "\05\09\01\05.data\04\00"
+                    "\06\03\01\41\00")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let linking = module_parsed.linking()?.expect("linking section should exist");
+        assert_eq!(linking.version, 2);
+        assert_eq!(linking.symbols.len(), 3);
+
+        let main = &linking.symbols[0];
+        assert_eq!(main.kind, AwwasmSymbolKind::Function);
+        assert_eq!(main.index, Some(0));
+        assert_eq!(main.name.as_ref().map(|n| &*n.bytes), Some(&b"main"[..]));
+
+        let buf = &linking.symbols[1];
+        assert_eq!(buf.kind, AwwasmSymbolKind::Data);
+        assert_eq!(buf.name.as_ref().map(|n| &*n.bytes), Some(&b"buf"[..]));
+        assert_eq!(buf.data, Some(AwwasmDataSymbolDef { segment: 0, offset: 16, size: 32 }));
+
+        let import = &linking.symbols[2];
+        assert_eq!(import.flags, AwwasmSymbolFlags(AwwasmSymbolFlags::UNDEFINED));
+        assert!(import.flags.is_undefined() && !import.flags.is_exported());
+        assert_eq!(import.index, Some(1));
+        assert!(import.name.is_none());
+
+        assert_eq!(&linking.segments[0].name.bytes[..], b".data");
+        assert_eq!(linking.segments[0].alignment, 4);
+        assert_eq!(linking.init_funcs, vec![AwwasmInitFunc { priority: 65, symbol_index: 0 }]);
+        assert!(linking.comdats.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_reloc_sections_test() -> anyhow::Result<()> {
+        // reloc.CODE against section 3: a function index (no addend) and a
+        // memory address with addend -4. reloc.DATA against section 5: a
+        // 32-bit table index.
+        let module = wat::parse_str(r#"
+            (module
+                (@custom "reloc.CODE" "\03\02" "\00\04\01" "\04\0a\02\7c")
+                (@custom "reloc.DATA" "\05\01" "\02\00\03")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let relocs = module_parsed.relocations()?;
+        assert_eq!(relocs.len(), 2);
+        assert_eq!(&relocs[0].name.bytes[..], b"reloc.CODE");
+        assert_eq!(relocs[0].target_section, 3);
+        assert_eq!(relocs[0].entries, vec![
+            AwwasmRelocEntry { reloc_type: AwwasmRelocType::FunctionIndexLeb, offset: 4, index: 1, addend: None },
+            AwwasmRelocEntry { reloc_type: AwwasmRelocType::MemoryAddrSleb, offset: 10, index: 2, addend: Some(-4) },
+        ]);
+        assert_eq!(&relocs[1].name.bytes[..], b"reloc.DATA");
+        assert_eq!(relocs[1].target_section, 5);
+        assert_eq!(relocs[1].entries, vec![
+            AwwasmRelocEntry { reloc_type: AwwasmRelocType::TableIndexI32, offset: 0, index: 3, addend: None },
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_linking_section_rejects_unknown_version_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"(module (@custom "linking" "\01"))"#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        let err = module_parsed.linking().unwrap_err();
+        assert!(err.to_string().contains("version 1"));
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use crate::components::module::AwwasmModule;
+use crate::components::section::SectionCode;
+use crate::consts::{WASM_PREAMBLE_MAGIC_SIZE_BYTES, WASM_PREAMBLE_VERSION_SIZE_BYTES};
+use std::fmt;
+
+/// Size limits for a module shipped to the web. `None` disables a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    /// Size of the whole binary.
+    pub total_bytes: Option<u64>,
+    /// Size of the code section body.
+    pub code_section_bytes: Option<u64>,
+    /// Size of any single function body.
+    pub function_bytes: Option<u64>,
+    /// Size of any single data segment's payload.
+    pub data_segment_bytes: Option<u64>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            total_bytes: Some(4 * 1024 * 1024),
+            code_section_bytes: Some(2 * 1024 * 1024),
+            function_bytes: Some(64 * 1024),
+            data_segment_bytes: Some(1024 * 1024),
+        }
+    }
+}
+
+/// Which budget a finding exceeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetRule {
+    TotalSize,
+    CodeSectionSize,
+    /// Function body at this index in the function index space.
+    FunctionSize(u32),
+    /// Data segment at this index.
+    DataSegmentSize(u32),
+}
+
+impl BudgetRule {
+    /// Stable name for CI output and allow-lists.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BudgetRule::TotalSize => "total-size",
+            BudgetRule::CodeSectionSize => "code-section-size",
+            BudgetRule::FunctionSize(_) => "function-size",
+            BudgetRule::DataSegmentSize(_) => "data-segment-size",
+        }
+    }
+}
+
+/// A budget that the module exceeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetFinding {
+    pub rule: BudgetRule,
+    pub size: u64,
+    pub limit: u64,
+}
+
+impl fmt::Display for BudgetFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.rule {
+            BudgetRule::FunctionSize(i) | BudgetRule::DataSegmentSize(i) =>
+                write!(f, "{}[{}]: {} bytes exceeds budget of {} bytes", self.rule.name(), i, self.size, self.limit),
+            _ => write!(f, "{}: {} bytes exceeds budget of {} bytes", self.rule.name(), self.size, self.limit),
+        }
+    }
+}
+
+/// Check a module against `budget`, returning one finding per exceeded limit.
+///
+/// Function and data sizes come from the resolved `code` and `data` items, so
+/// call `resolve_all_sections()` first.
+pub fn web_budget(module: &AwwasmModule, budget: Budget) -> Vec<BudgetFinding> {
+    let mut findings = Vec::new();
+    let mut check = |rule, size: u64, limit: Option<u64>| {
+        if let Some(limit) = limit.filter(|l| size > *l) {
+            findings.push(BudgetFinding { rule, size, limit });
+        }
+    };
+
+    let sections = module.sections();
+    check(BudgetRule::TotalSize, module_size(module), budget.total_bytes);
+
+    if let Some(code) = sections.iter().find(|s| s.section_header.section_type == SectionCode::Code) {
+        check(BudgetRule::CodeSectionSize, code.section_header.section_size as u64, budget.code_section_bytes);
+    }
+
+    let imported = module.imported_func_count();
+    for (i, item) in module.code().iter().enumerate() {
+        check(BudgetRule::FunctionSize(imported + i as u32), item.fn_body_size as u64, budget.function_bytes);
+    }
+    for (i, item) in module.data().iter().enumerate() {
+        check(BudgetRule::DataSegmentSize(i as u32), item.size as u64, budget.data_segment_bytes);
+    }
+
+    findings
+}
+
+// Size of the encoded module: the preamble plus every section with its header.
+pub(crate) fn module_size(module: &AwwasmModule) -> u64 {
+    (WASM_PREAMBLE_MAGIC_SIZE_BYTES + WASM_PREAMBLE_VERSION_SIZE_BYTES) as u64 + module.sections().iter()
+        .map(|s| s.encoding.len() as u64)
+        .sum::<u64>()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::lint::{module_size, web_budget, Budget, BudgetRule};
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn web_budget_findings_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "f" (func))
+                (memory 1)
+                (func (result i32) i32.const 1 i32.const 2 i32.add)
+                (data (i32.const 0) "0123456789")
+                (data (i32.const 16) "ab")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        assert!(web_budget(&module_parsed, Budget::default()).is_empty());
+
+        let findings = web_budget(&module_parsed, Budget {
+            total_bytes: Some(module.len() as u64 - 1),
+            code_section_bytes: None,
+            function_bytes: Some(4),
+            data_segment_bytes: Some(8),
+        });
+        let rules: Vec<BudgetRule> = findings.iter().map(|f| f.rule).collect();
+        assert_eq!(rules, vec![BudgetRule::TotalSize, BudgetRule::FunctionSize(1), BudgetRule::DataSegmentSize(0)]);
+        assert_eq!(findings[0].size, module.len() as u64);
+        assert_eq!(findings[2].to_string(), "data-segment-size[0]: 10 bytes exceeds budget of 8 bytes");
+        Ok(())
+    }
+
+    #[test]
+    fn module_size_padded_header_test() -> anyhow::Result<()> {
+        // A custom section whose size is written as a 5-byte LEB128.
+        let module = b"\0asm\x01\0\0\0\x00\x82\x80\x80\x80\x00\x01a";
+        let mut module_parsed = AwwasmModule::new(module)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_size(&module_parsed), module.len() as u64);
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+// Test-only "lossless parse" mode.
+//
+// Re-walks a module recording the exact bytes each parsed item consumed and
+// asserts that, concatenated, they reproduce the input. Any byte the parser
+// model skips or double-counts shows up as a mismatch.
+
+use crate::components::instructions::*;
+use crate::components::module::AwwasmModulePreamble;
+use crate::components::section::*;
+use crate::components::types::*;
+use nom_derive::Parse;
+use crate::components::leb128::leb128_u32;
+use nom::multi::length_count;
+
+// Bytes consumed by a parser that went from `before` to `after`.
+fn consumed<'a>(before: &'a [u8], after: &'a [u8]) -> &'a [u8] {
+    &before[..before.len() - after.len()]
+}
+
+/// Assert that every byte of `input` is accounted for by the parser model.
+pub(crate) fn assert_lossless(input: &[u8]) {
+    let mut pieces: Vec<&[u8]> = Vec::new();
+
+    let (mut rest, _) = AwwasmModulePreamble::parse(input).expect("preamble");
+    pieces.push(consumed(input, rest));
+
+    while !rest.is_empty() {
+        let (after_header, header) = AwwasmSectionHeader::parse(rest).expect("section header");
+        let (after, mut section) = AwwasmSection::parse(rest).expect("section");
+        let header_len = rest.len() - after_header.len();
+        let section_bytes = consumed(rest, after);
+        assert_eq!(
+            section_bytes.len(),
+            header_len + header.section_size as usize,
+            "{:?} section consumed a different size than it declared",
+            header.section_type,
+        );
+
+        let resolved = section.resolve().expect("section resolves");
+        // Items must account for the whole body, with nothing left over.
+        assert!(section.section_body.is_empty(), "{:?} section left {} unparsed bytes",
+            header.section_type, section.section_body.len());
+        if let SectionItem::CodeSectionItems(code) = resolved {
+            code.iter().for_each(assert_function_lossless);
+        }
+
+        pieces.push(section_bytes);
+        rest = after;
+    }
+
+    assert_eq!(pieces.concat(), input, "consumed bytes do not reproduce the input");
+}
+
+// Every instruction in a body must decode, ending exactly at the body's end.
+fn assert_function_lossless(item: &AwwasmCodeSectionItem) {
+    let body = item.func_body;
+    let (expr, _) = length_count(leb128_u32, AwwasmFunctionLocals::parse)(body)
+        .map_err(|e| format!("{:?}", e))
+        .expect("function locals");
+    let mut pieces: Vec<&[u8]> = vec![consumed(body, expr)];
+
+    let mut rest = expr;
+    while !rest.is_empty() {
+        let (after, _) = AwwasmInstruction::parse(rest)
+            .map_err(|e| format!("{:?}", e))
+            .expect("instruction decodes");
+        pieces.push(consumed(rest, after));
+        rest = after;
+    }
+    assert_eq!(expr.last(), Some(&(WasmOpCode::End as u8)), "function body must end with `end`");
+    assert_eq!(pieces.concat(), body);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::fixtures::VALID;
+    use crate::components::lossless::assert_lossless;
+
+    #[test]
+    fn lossless_fixtures_test() {
+        for (name, bytes) in VALID {
+            // Captured output, shown only to name the fixture that failed.
+            eprintln!("{}", name);
+            assert_lossless(bytes);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use crate::consts::WASM_MAGIC_NUMBER;
+use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+use crate::components::leb128::leb128_u32;
+use crate::components::module::{AwwasmModule, AwwasmModulePreamble};
+use crate::components::section::{AwwasmPartialSection, AwwasmSection, AwwasmSectionHeader, SectionCode, TruncatedSection};
+use nom::combinator::complete;
+use nom::AsBytes;
+use nom_derive::Parse;
+
+/// A module decoded by `AwwasmModule::parse_lossy()`: everything that
+/// decoded, and why the rest did not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmLossyModule<'a> {
+    /// The module with its sections resolved as far as they decode. A
+    /// section that fails partway keeps the entries before the failure; one
+    /// that fails outright is left out. Code section items whose bodies do
+    /// not decode are kept and have a diagnostic naming their function.
+    pub module: AwwasmModule<'a>,
+    /// One per failure, located, by offset.
+    pub diagnostics: Vec<AwwasmParseError>,
+}
+
+impl AwwasmLossyModule<'_> {
+    /// Whether the whole module decoded.
+    pub fn is_clean(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+}
+
+// Convert any error from a decoder of `section` into a located diagnostic.
+fn diagnostic(err: anyhow::Error, start: usize, section: Option<&SectionCode>) -> AwwasmParseError {
+    let mut diagnostic = match err.downcast::<AwwasmParseError>() {
+        Ok(e) => e,
+        Err(e) => {
+            let what = section.map_or_else(|| "module".to_string(), |s| format!("{:?} Section", s));
+            AwwasmParseError::new(what, AwwasmParseErrorKind::Malformed { detail: e.to_string() })
+        }
+    };
+    diagnostic.locate(start, section, None);
+    diagnostic
+}
+
+impl AwwasmModule<'_> {
+    /// Decode as much of `input` as possible, recording each failure instead
+    /// of stopping at the first.
+    ///
+    /// A bad magic number or version is recorded and decoding carries on. A
+    /// section that fails to decode is skipped by its declared size; a bad
+    /// section header or a last section cut short ends decoding, the latter
+    /// kept in `module.truncated`. Every function body is decoded, so
+    /// diagnostics cover them too.
+    ///
+    /// Fails only if `input` is too short for a preamble or is a component.
+    pub fn parse_lossy(input: &[u8]) -> anyhow::Result<AwwasmLossyModule<'_>> {
+        let start = input.as_ptr() as usize;
+        let mut diagnostics = Vec::new();
+        if input.len() < 8 {
+            let mut err = AwwasmParseError::new("module preamble", AwwasmParseErrorKind::Truncated).within(input);
+            err.locate(start, None, None);
+            return Err(err.into());
+        }
+        let preamble = AwwasmModulePreamble {
+            magic: &input[..4],
+            version: u32::from_le_bytes([input[4], input[5], input[6], input[7]]),
+        };
+        if preamble.is_component() {
+            preamble.check_core_module()?;
+        }
+        if preamble.magic != WASM_MAGIC_NUMBER.as_bytes() {
+            let mut err = AwwasmParseError::new("module preamble", AwwasmParseErrorKind::BadMagic);
+            err.offset = Some(0);
+            diagnostics.push(err);
+        }
+        if let Err(e) = preamble.check_core_module() {
+            let mut err = diagnostic(e, start, None);
+            err.offset = Some(4);
+            diagnostics.push(err);
+        }
+        let mut module = AwwasmModule { preamble, ..Default::default() };
+
+        let mut input = &input[8..];
+        while !input.is_empty() {
+            let (body, header) = match complete(AwwasmSectionHeader::parse)(input) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    let err = AwwasmParseError::section_header("section header", e).within(input);
+                    // An unknown section id is still followed by a size to
+                    // skip by.
+                    let skip = match err.kind {
+                        AwwasmParseErrorKind::UnknownSection { .. } => leb128_u32::<nom::error::Error<&[u8]>>(&input[1..]).ok()
+                            .filter(|(rest, size)| *size as usize <= rest.len()),
+                        _ => None,
+                    };
+                    diagnostics.push(diagnostic(err.into(), start, None));
+                    match skip {
+                        Some((rest, size)) => { input = &rest[size as usize..]; continue; }
+                        None => break,
+                    }
+                }
+            };
+            let size = header.section_size as usize;
+            if size > body.len() {
+                let err = AwwasmParseError::new(format!("{:?} Section", header.section_type), AwwasmParseErrorKind::Truncated).within(body);
+                diagnostics.push(diagnostic(err.into(), start, Some(&header.section_type)));
+                let error = TruncatedSection { section: header.section_type, declared: header.section_size, available: body.len() };
+                module.truncated = Some(AwwasmPartialSection { error, partial_body: body });
+                break;
+            }
+            match complete(AwwasmSection::parse)(input) {
+                Ok((_, sec)) => module.sections.get_or_insert_with(Vec::new).push(sec),
+                Err(e) => {
+                    let err = AwwasmParseError::nom(format!("{:?} Section", header.section_type), e).within(&body[..size]);
+                    diagnostics.push(diagnostic(err.into(), start, Some(&header.section_type)));
+                }
+            }
+            input = &body[size..];
+        }
+
+        let mut sections = module.sections.take();
+        for sec in sections.iter_mut().flatten() {
+            let section = sec.section_header.section_type.clone();
+            match sec.resolve_partial() {
+                Ok((items, error)) => {
+                    if let Some(err) = error {
+                        diagnostics.push(diagnostic(err.into(), start, Some(&section)));
+                    }
+                    if let Err(e) = module.store(items) {
+                        diagnostics.push(diagnostic(e, start, Some(&section)));
+                    }
+                }
+                Err(e) => diagnostics.push(diagnostic(e, start, Some(&section))),
+            }
+        }
+        module.sections = sections;
+
+        for item in module.code() {
+            if let Err(e) = item.instructions() {
+                diagnostics.push(diagnostic(module.locate_error(e), start, Some(&SectionCode::Code)));
+            }
+        }
+        diagnostics.sort_by_key(|d| d.offset);
+        Ok(AwwasmLossyModule { module, diagnostics })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::error::AwwasmParseErrorKind;
+    use crate::components::module::AwwasmModule;
+    use crate::components::section::SectionCode;
+
+    #[test]
+    fn parse_lossy_test() -> anyhow::Result<()> {
+        let clean = wat::parse_str(r#"(module (func (export "f") (result i32) (i32.const 1)))"#)?;
+        let lossy = AwwasmModule::parse_lossy(&clean)?;
+        assert!(lossy.is_clean(), "{:?}", lossy.diagnostics);
+        assert_eq!(lossy.module.exports.as_ref().map(Vec::len), Some(1));
+
+        let module = [
+            0x00, 0x61, 0x73, 0x6d, 0x02, 0x00, 0x00, 0x00,            // version 2
+            0x01, 0x07, 0x02, 0x60, 0x00, 0x00, 0x60, 0x00, 0x55,      // second type has a bad result count
+            0x0e, 0x01, 0x00,                                          // unknown section 0x0e
+            0x03, 0x03, 0x02, 0x00, 0x00,                              // two functions of type 0
+            0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x00,                  // export "f", function 0
+            0x0a, 0x07, 0x02, 0x02, 0x00, 0x0b, 0x02, 0x00, 0xd7,      // second body: unknown opcode 0xd7
+            0x0b, 0x05, 0x01,                                          // data section cut short
+        ];
+        let lossy = AwwasmModule::parse_lossy(&module)?;
+        let kinds: Vec<_> = lossy.diagnostics.iter().map(|d| (d.offset, d.section.clone(), d.kind.clone())).collect();
+        assert_eq!(kinds, vec![
+            (Some(0x4), None, AwwasmParseErrorKind::UnsupportedVersion { version: 2 }),
+            (Some(0x11), None, AwwasmParseErrorKind::UnknownSection { id: 0x0e }),
+            (Some(0x11), Some(SectionCode::Type), AwwasmParseErrorKind::Truncated),
+            (Some(0x28), Some(SectionCode::Code), AwwasmParseErrorKind::UnknownOpcode { opcode: 0xd7 }),
+            (Some(0x2c), Some(SectionCode::Data), AwwasmParseErrorKind::Truncated),
+        ]);
+        assert_eq!(lossy.diagnostics[3].func_idx, Some(1));
+
+        let parsed = &lossy.module;
+        assert_eq!(parsed.types.as_ref().map(Vec::len), Some(1));
+        assert_eq!(parsed.funcs.as_ref().map(Vec::len), Some(2));
+        assert_eq!(parsed.exports.as_ref().map(Vec::len), Some(1));
+        assert_eq!(parsed.code.as_ref().map(Vec::len), Some(2));
+        assert!(parsed.code.as_ref().unwrap()[0].instructions().is_ok());
+        assert_eq!(parsed.truncated.as_ref().map(|t| t.error.section.clone()), Some(SectionCode::Data));
+
+        assert!(AwwasmModule::parse_lossy(b"\0asm\x01\0").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,540 @@
+use crate::consts::{WASM_TYPE_SECTION_OPCODE_FUNC, WASM_VERSION};
+use crate::components::encoder::Encode;
+use crate::components::error::AwwasmParseError;
+use crate::components::imports::AwwasmImportEntry;
+use crate::components::index_space::AwwasmIndexed;
+use crate::components::instructions::{span_in, AwwasmMiscOperands, AwwasmOperands, BlockType, InstructionIterator};
+use crate::components::module::AwwasmModule;
+use crate::components::passes::{AwwasmOwnedSection, AwwasmPassModule};
+use crate::components::section::{write_leb128_s33, write_leb128_u32, SectionCode};
+use crate::components::types::*;
+use nom_derive::Parse;
+use std::collections::HashMap;
+
+// The index spaces merging renumbers, besides types and data segments.
+const KINDS: [AwwasmImportKind; 4] = [AwwasmImportKind::Function, AwwasmImportKind::Table, AwwasmImportKind::Memory, AwwasmImportKind::Global];
+
+// Where an entry of a merged index space comes from.
+#[derive(Debug, Clone, Copy)]
+enum Source<'m, 'a> {
+    // An import of `module` no other module's export satisfies.
+    Import { module: usize, import: &'m AwwasmImportSectionItem<'a> },
+    // Definition `idx` of `module`.
+    Defined { module: usize, idx: u32 },
+}
+
+// One merged index space: the imports left over, then the definitions of
+// each module in turn.
+struct Space<'m, 'a> {
+    imports: Vec<(usize, &'m AwwasmImportSectionItem<'a>)>,
+    // Per module, the merged index of each of its indices.
+    maps: Vec<Vec<u32>>,
+}
+
+fn defined_count(module: &AwwasmModule, kind: &AwwasmImportKind) -> u32 {
+    let len = match kind {
+        AwwasmImportKind::Function => module.funcs().len(),
+        AwwasmImportKind::Table => module.tables().len(),
+        AwwasmImportKind::Memory => module.memories().len(),
+        AwwasmImportKind::Global => module.globals().len(),
+        AwwasmImportKind::Tag => module.tags().len(),
+    };
+    len as u32
+}
+
+fn same_signature(a: Option<&AwwasmTypeSectionItem>, b: Option<&AwwasmTypeSectionItem>) -> bool {
+    a.map(|t| (&t.fn_args, &t.fn_rets)) == b.map(|t| (&t.fn_args, &t.fn_rets))
+}
+
+fn import_name(import: &AwwasmImportSectionItem) -> String {
+    format!("\"{}\" \"{}\"", String::from_utf8_lossy(&import.module.bytes), String::from_utf8_lossy(&import.name.bytes))
+}
+
+struct Merger<'m, 'a> {
+    modules: &'m [AwwasmModule<'a>],
+    imports: Vec<Vec<AwwasmImportEntry<'m, 'a>>>,
+    // Every export, by name, with the module exporting it.
+    exports: HashMap<&'m [u8], (usize, &'m AwwasmExportSectionItem<'a>)>,
+}
+
+impl<'m, 'a> Merger<'m, 'a> {
+    fn new(modules: &'m [AwwasmModule<'a>]) -> anyhow::Result<Self> {
+        let mut exports = HashMap::new();
+        for (i, module) in modules.iter().enumerate() {
+            let features = module.features_used();
+            for (used, name) in [(features.exception_handling, "exception handling"), (features.function_references, "typed function references"), (features.multi_memory, "multiple memories")] {
+                if used {
+                    return Err(anyhow::anyhow!("Module {} uses {}, which merging does not support", i, name));
+                }
+            }
+            if let Some(finding) = module.validate().into_iter().next() {
+                return Err(anyhow::anyhow!("Module {} is invalid: {}", i, finding));
+            }
+            for export in module.exports() {
+                if exports.insert(&*export.name.bytes, (i, export)).is_some() {
+                    return Err(anyhow::anyhow!("More than one module exports \"{}\"", String::from_utf8_lossy(&export.name.bytes)));
+                }
+            }
+        }
+        let imports = modules.iter().map(|m| m.import_entries().collect()).collect();
+        Ok(Merger { modules, imports, exports })
+    }
+
+    // Follow entry `idx` of the `kind` space of `module` through the
+    // exports that satisfy its imports.
+    fn source(&self, mut module: usize, kind: &AwwasmImportKind, mut idx: u32) -> anyhow::Result<Source<'m, 'a>> {
+        let links: usize = self.imports.iter().map(Vec::len).sum();
+        for _ in 0..=links {
+            let mut imports = self.imports[module].iter().filter(|e| e.import.kind == *kind);
+            let imported = imports.clone().count() as u32;
+            let Some(entry) = imports.find(|e| e.index == idx) else {
+                return Ok(Source::Defined { module, idx: idx - imported });
+            };
+            let import = entry.import;
+            match self.exports.get(&*import.name.bytes) {
+                Some(&(target, export)) if target != module && export.kind.clone() as u8 == kind.clone() as u8 => {
+                    self.check_link(module, import, target, export.index)?;
+                    (module, idx) = (target, export.index);
+                }
+                _ => return Ok(Source::Import { module, import }),
+            }
+        }
+        Err(anyhow::anyhow!("Imports and exports of the modules form a cycle"))
+    }
+
+    // Check that entry `idx` of `target` can satisfy `import` of `module`.
+    fn check_link(&self, module: usize, import: &AwwasmImportSectionItem, target: usize, idx: u32) -> anyhow::Result<()> {
+        let (importer, exporter) = (&self.modules[module], &self.modules[target]);
+        let matches = match import.kind {
+            AwwasmImportKind::Function => {
+                let expected = import.func_type_idx.and_then(|t| importer.types().get(t as usize));
+                same_signature(expected, exporter.signature_of(idx))
+            }
+            AwwasmImportKind::Global => {
+                let provided = exporter.index_spaces().global(idx).and_then(|global| match global {
+                    AwwasmIndexed::Imported(i) => i.global.clone(),
+                    AwwasmIndexed::Defined(g) => Some(AwwasmGlobalType { value_type: g.value_type, mutability: g.mutability.clone() }),
+                });
+                provided == import.global
+            }
+            _ => true,
+        };
+        if !matches {
+            return Err(anyhow::anyhow!("Import {} of module {} does not match the export of module {}", import_name(import), module, target));
+        }
+        Ok(())
+    }
+
+    // Whether two imports left over can share one entry.
+    fn same_import(&self, (m1, a): (usize, &AwwasmImportSectionItem), (m2, b): (usize, &AwwasmImportSectionItem)) -> bool {
+        match a.kind {
+            AwwasmImportKind::Function => {
+                let ty = |m: usize, i: &AwwasmImportSectionItem| i.func_type_idx.and_then(|t| self.modules[m].types().get(t as usize));
+                same_signature(ty(m1, a), ty(m2, b))
+            }
+            _ => (&a.table, &a.mem, &a.global) == (&b.table, &b.mem, &b.global),
+        }
+    }
+
+    fn space(&self, kind: &AwwasmImportKind) -> anyhow::Result<Space<'m, 'a>> {
+        let mut imports: Vec<(usize, &AwwasmImportSectionItem)> = Vec::new();
+        let mut sources = Vec::new();
+        for (m, entries) in self.imports.iter().enumerate() {
+            let mut module_sources = Vec::new();
+            for entry in entries.iter().filter(|e| e.import.kind == *kind) {
+                let source = self.source(m, kind, entry.index)?;
+                if let Source::Import { module, import } = source {
+                    match imports.iter().find(|(_, i)| (&i.module.bytes, &i.name.bytes) == (&import.module.bytes, &import.name.bytes)) {
+                        Some(&first) if !self.same_import(first, (module, import)) => {
+                            return Err(anyhow::anyhow!("Modules import {} with different types", import_name(import)));
+                        }
+                        Some(_) => {}
+                        None => imports.push((module, import)),
+                    }
+                }
+                module_sources.push(source);
+            }
+            sources.push(module_sources);
+        }
+
+        let mut bases = Vec::new();
+        let mut next = imports.len() as u32;
+        for module in self.modules {
+            bases.push(next);
+            next += defined_count(module, kind);
+        }
+        let maps = sources.iter().enumerate().map(|(m, module_sources)| {
+            module_sources.iter()
+                .map(|source| match source {
+                    Source::Import { import, .. } => imports.iter().position(|(_, i)| (&i.module.bytes, &i.name.bytes) == (&import.module.bytes, &import.name.bytes)).unwrap_or_default() as u32,
+                    Source::Defined { module, idx } => bases[*module] + idx,
+                })
+                .chain((0..defined_count(&self.modules[m], kind)).map(|idx| bases[m] + idx))
+                .collect()
+        }).collect();
+        Ok(Space { imports, maps })
+    }
+}
+
+// Add a section of `count` entries written to `payload`, unless it has none.
+fn push_section(sections: &mut Vec<AwwasmOwnedSection>, id: SectionCode, count: usize, payload: Vec<u8>) {
+    if count > 0 {
+        let mut counted = Vec::with_capacity(payload.len() + 5);
+        write_leb128_u32(&mut counted, count as u32);
+        counted.extend_from_slice(&payload);
+        sections.push(AwwasmOwnedSection { id, payload: counted });
+    }
+}
+
+// How one module's indices change in the merged module.
+pub(crate) struct Renumber<'s> {
+    pub(crate) types: &'s [u32],
+    pub(crate) funcs: &'s [u32],
+    pub(crate) globals: &'s [u32],
+    pub(crate) data_base: u32,
+}
+
+// The merged index for `idx`; inputs are validated, so it is in range.
+pub(crate) fn map(indices: &[u32], idx: u32) -> u32 {
+    indices.get(idx as usize).copied().unwrap_or(idx)
+}
+
+impl Renumber<'_> {
+    // Rewrite the index immediates of the instructions in `code`.
+    pub(crate) fn code(&self, code: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.code_moving(code, &mut Vec::new())
+    }
+
+    // Like `code`, also recording where each rewrite ended in `code` and
+    // how far the bytes after it moved, for rebasing branch hint offsets.
+    pub(crate) fn code_moving(&self, code: &[u8], moved: &mut Vec<(usize, isize)>) -> anyhow::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(code.len());
+        let mut pos = 0;
+        for instr in InstructionIterator::new(code) {
+            let instr = instr.map_err(|e| AwwasmParseError::instruction("Function body", e).within(code))?;
+            instr.walk(&mut |instr| {
+                let Some(span) = span_in(code, instr.encoding) else { return };
+                let mut replacement = vec![instr.encoding[0]];
+                let end = match (&instr.operands, instr.operands.block_type()) {
+                    (AwwasmOperands::Call(op) | AwwasmOperands::ReturnCall(op) | AwwasmOperands::RefFunc(op), _) => {
+                        write_leb128_u32(&mut replacement, map(self.funcs, op.funcidx));
+                        span.end
+                    }
+                    (AwwasmOperands::GlobalGet(op) | AwwasmOperands::GlobalSet(op), _) => {
+                        write_leb128_u32(&mut replacement, map(self.globals, op.index));
+                        span.end
+                    }
+                    (AwwasmOperands::CallIndirect(op) | AwwasmOperands::ReturnCallIndirect(op), _) => {
+                        write_leb128_u32(&mut replacement, map(self.types, op.typeidx));
+                        write_leb128_u32(&mut replacement, op.tableidx);
+                        span.end
+                    }
+                    (AwwasmOperands::Misc(op), _) => {
+                        write_leb128_u32(&mut replacement, op.sub_op as u32);
+                        match &op.operands {
+                            AwwasmMiscOperands::MemoryInit(m) => {
+                                write_leb128_u32(&mut replacement, self.data_base + m.dataidx);
+                                write_leb128_u32(&mut replacement, m.memidx);
+                            }
+                            AwwasmMiscOperands::DataDrop(d) => write_leb128_u32(&mut replacement, self.data_base + d.dataidx),
+                            _ => return,
+                        }
+                        span.end
+                    }
+                    (_, Some(BlockType::TypeIndex(idx))) => {
+                        // Only the block type is replaced; the body follows it.
+                        let Ok((after, _)) = BlockType::parse(&instr.encoding[1..]) else { return };
+                        write_leb128_s33(&mut replacement, map(self.types, idx));
+                        span.end - after.len()
+                    }
+                    _ => return,
+                };
+                out.extend_from_slice(&code[pos..span.start]);
+                out.extend_from_slice(&replacement);
+                pos = end;
+                moved.push((pos, out.len() as isize - pos as isize));
+            });
+        }
+        out.extend_from_slice(&code[pos..]);
+        Ok(out)
+    }
+
+    pub(crate) fn expr<'e>(&self, expr: &AwwasmDataInitExpr, code: &'e mut Vec<u8>) -> anyhow::Result<AwwasmDataInitExpr<'e>> {
+        *code = self.code(expr.code)?;
+        Ok(AwwasmDataInitExpr { code, end: expr.end })
+    }
+}
+
+impl AwwasmModule<'_> {
+    /// Link `modules` into one module binary, as a static linker does.
+    ///
+    /// An import is satisfied by the export of the same name and kind of
+    /// another module, whatever module name it imports from, so `env.add`
+    /// binds to the `add` another module exports. Function types must match,
+    /// as must global types; memory and table limits are not checked. The
+    /// imports left over are kept, one for each module and name.
+    ///
+    /// Types are merged, and the function, global and data segment index
+    /// spaces are concatenated in the order of `modules`, with every
+    /// reference renumbered: in code, constant expressions, element
+    /// segments, exports and the start function. When more than one module
+    /// has a start function, a new function calling each in turn becomes
+    /// the start function. Every export is kept, so export names must be
+    /// unique across the modules. Custom sections are dropped, since the
+    /// indices in them no longer hold.
+    ///
+    /// Each module must be resolved with `resolve_all_sections()` and valid.
+    /// Fails for modules using exception handling, typed function references
+    /// or multiple memories, and when the merged module would have more than
+    /// one memory or table.
+    pub fn merge(modules: &[AwwasmModule]) -> anyhow::Result<Vec<u8>> {
+        let merger = Merger::new(modules)?;
+        let [funcs, tables, memories, globals] = KINDS.map(|kind| merger.space(&kind));
+        let (funcs, tables, memories, globals) = (funcs?, tables?, memories?, globals?);
+        for (space, kind, name) in [(&tables, AwwasmImportKind::Table, "tables"), (&memories, AwwasmImportKind::Memory, "memories")] {
+            let count = space.imports.len() as u32 + modules.iter().map(|m| defined_count(m, &kind)).sum::<u32>();
+            if count > 1 {
+                return Err(anyhow::anyhow!("The merged module would have {} {}; merging supports one", count, name));
+            }
+        }
+
+        // Identical types are merged; the empty type is for a start function
+        // calling those of the modules.
+        let empty = AwwasmTypeSectionItem { type_magic: WASM_TYPE_SECTION_OPCODE_FUNC, fn_args: vec![], fn_rets: vec![] };
+        let mut types: Vec<&AwwasmTypeSectionItem> = Vec::new();
+        let mut type_indices: HashMap<&AwwasmTypeSectionItem, u32> = HashMap::new();
+        let mut type_index = |ty| *type_indices.entry(ty).or_insert_with(|| {
+            types.push(ty);
+            types.len() as u32 - 1
+        });
+        let type_maps: Vec<Vec<u32>> = modules.iter().map(|m| m.types().iter().map(&mut type_index).collect()).collect();
+
+        let mut data_base = 0;
+        let renumbers: Vec<Renumber> = modules.iter().enumerate().map(|(m, module)| {
+            let renumber = Renumber { types: &type_maps[m], funcs: &funcs.maps[m], globals: &globals.maps[m], data_base };
+            data_base += module.data().len() as u32;
+            renumber
+        }).collect();
+
+        let starts: Vec<u32> = modules.iter().enumerate().filter_map(|(m, module)| Some(map(&funcs.maps[m], module.start.as_ref()?.func_idx))).collect();
+        let defined_funcs: u32 = modules.iter().map(|m| defined_count(m, &AwwasmImportKind::Function)).sum();
+        let (start, start_type) = match starts[..] {
+            [] => (None, None),
+            [start] => (Some(start), None),
+            _ => (Some(funcs.imports.len() as u32 + defined_funcs), Some(type_index(&empty))),
+        };
+
+        let mut sections = Vec::new();
+
+        let mut payload = Vec::new();
+        types.iter().for_each(|ty| ty.write(&mut payload));
+        push_section(&mut sections, SectionCode::Type, types.len(), payload);
+
+        let mut payload = Vec::new();
+        let imports = [&funcs, &tables, &memories, &globals].into_iter().flat_map(|space| &space.imports);
+        let mut count = 0;
+        for &(m, import) in imports {
+            let mut import = import.clone();
+            import.func_type_idx = import.func_type_idx.map(|t| map(&type_maps[m], t));
+            import.write(&mut payload);
+            count += 1;
+        }
+        push_section(&mut sections, SectionCode::Import, count, payload);
+
+        let mut payload = Vec::new();
+        let func_types = modules.iter().enumerate().flat_map(|(m, module)| module.funcs().iter().map(move |f| (m, f.type_item_idx)));
+        for (m, ty) in func_types {
+            write_leb128_u32(&mut payload, map(&type_maps[m], ty));
+        }
+        if let Some(ty) = start_type {
+            write_leb128_u32(&mut payload, ty);
+        }
+        push_section(&mut sections, SectionCode::Function, (defined_funcs + start_type.map_or(0, |_| 1)) as usize, payload);
+
+        for (id, items) in [
+            (SectionCode::Table, modules.iter().flat_map(|m| m.tables().iter()).map(|t| t as &dyn Encode).collect::<Vec<_>>()),
+            (SectionCode::Memory, modules.iter().flat_map(|m| m.memories().iter()).map(|t| t as &dyn Encode).collect()),
+        ] {
+            let mut payload = Vec::new();
+            items.iter().for_each(|item| item.write(&mut payload));
+            push_section(&mut sections, id, items.len(), payload);
+        }
+
+        let (mut payload, mut count) = (Vec::new(), 0);
+        for (m, global) in modules.iter().enumerate().flat_map(|(m, module)| module.globals().iter().map(move |g| (m, g))) {
+            let mut code = Vec::new();
+            let init_expr = renumbers[m].expr(&global.init_expr, &mut code)?;
+            AwwasmGlobalSectionItem { init_expr, ..global.clone() }.write(&mut payload);
+            count += 1;
+        }
+        push_section(&mut sections, SectionCode::Global, count, payload);
+
+        let (mut payload, mut count) = (Vec::new(), 0);
+        for (m, export) in modules.iter().enumerate().flat_map(|(m, module)| module.exports().iter().map(move |e| (m, e))) {
+            let space = match export.kind {
+                AwwasmExportKind::Function => &funcs,
+                AwwasmExportKind::Table => &tables,
+                AwwasmExportKind::Memory => &memories,
+                // Rejected with exception handling.
+                AwwasmExportKind::Global | AwwasmExportKind::Tag => &globals,
+            };
+            AwwasmExportSectionItem { index: map(&space.maps[m], export.index), ..export.clone() }.write(&mut payload);
+            count += 1;
+        }
+        push_section(&mut sections, SectionCode::Export, count, payload);
+
+        if let Some(start) = start {
+            let mut payload = Vec::new();
+            write_leb128_u32(&mut payload, start);
+            sections.push(AwwasmOwnedSection { id: SectionCode::Start, payload });
+        }
+
+        let (mut payload, mut count) = (Vec::new(), 0);
+        for (m, element) in modules.iter().enumerate().flat_map(|(m, module)| module.elements().iter().map(move |e| (m, e))) {
+            let codes = element.body.init_exprs().iter().map(|e| renumbers[m].code(e.code)).collect::<anyhow::Result<Vec<_>>>()?;
+            let mut element = element.clone();
+            for (expr, code) in element.body.init_exprs_mut().into_iter().zip(&codes) {
+                expr.code = code;
+            }
+            for idx in element.body.func_indices_mut().into_iter().flatten() {
+                *idx = map(&funcs.maps[m], *idx);
+            }
+            element.write(&mut payload);
+            count += 1;
+        }
+        push_section(&mut sections, SectionCode::Element, count, payload);
+
+        if modules.iter().any(|m| m.data_count.is_some()) {
+            let mut payload = Vec::new();
+            write_leb128_u32(&mut payload, data_base);
+            sections.push(AwwasmOwnedSection { id: SectionCode::DataCount, payload });
+        }
+
+        let (mut payload, mut count) = (Vec::new(), 0);
+        for (m, item) in modules.iter().enumerate().flat_map(|(m, module)| module.code().iter().map(move |c| (m, c))) {
+            let (_, code) = item.locals_and_code()?;
+            let mut body = item.func_body[..item.func_body.len() - code.len()].to_vec();
+            body.extend(renumbers[m].code(code)?);
+            write_leb128_u32(&mut payload, body.len() as u32);
+            payload.extend_from_slice(&body);
+            count += 1;
+        }
+        if start_type.is_some() {
+            // No locals, a call to each start function, `end`.
+            let mut body = vec![0];
+            for start in &starts {
+                body.push(0x10);
+                write_leb128_u32(&mut body, *start);
+            }
+            body.push(0x0b);
+            write_leb128_u32(&mut payload, body.len() as u32);
+            payload.extend_from_slice(&body);
+            count += 1;
+        }
+        push_section(&mut sections, SectionCode::Code, count, payload);
+
+        let (mut payload, mut count) = (Vec::new(), 0);
+        for (m, data) in modules.iter().enumerate().flat_map(|(m, module)| module.data().iter().map(move |d| (m, d))) {
+            let mut code = Vec::new();
+            let offset = data.header.offset.as_ref().map(|offset| renumbers[m].expr(offset, &mut code)).transpose()?;
+            let header = AwwasmDataSegmentHeader { offset, ..data.header.clone() };
+            AwwasmDataSectionItem { header, ..data.clone() }.write(&mut payload);
+            count += 1;
+        }
+        push_section(&mut sections, SectionCode::Data, count, payload);
+
+        Ok(AwwasmPassModule { version: WASM_VERSION, sections }.encode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::instructions::AwwasmOperands;
+    use crate::components::module::AwwasmModule;
+    use crate::components::types::AwwasmElemSegmentBody;
+
+    #[test]
+    fn merge_test() -> anyhow::Result<()> {
+        let lib = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (global $count (mut i32) (i32.const 0))
+                (func $add (export "add") (param i32 i32) (result i32)
+                    (i32.add (local.get 0) (local.get 1)))
+                (func $bump (export "bump")
+                    (global.set $count (call $add (global.get $count) (i32.const 1))))
+                (data (i32.const 0) "lib")
+                (start $bump))
+        "#)?;
+        let app = wat::parse_str(r#"
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (import "env" "add" (func $add (param i32 i32) (result i32)))
+                (import "env" "memory" (memory 1))
+                (global $base i32 (i32.const 16))
+                (table 1 funcref)
+                (elem (i32.const 0) $main)
+                (func $main (export "main") (result i32)
+                    (memory.init $greeting (global.get $base) (i32.const 0) (i32.const 2))
+                    (data.drop $greeting)
+                    (call $log (i32.const 1))
+                    (call $add (global.get $base) (i32.const 2)))
+                (func $init (call $log (i32.const 0)))
+                (data $greeting "hi")
+                (start $init))
+        "#)?;
+        let mut lib = AwwasmModule::new(&lib)?;
+        let mut app = AwwasmModule::new(&app)?;
+        lib.resolve_all_sections()?;
+        app.resolve_all_sections()?;
+
+        let bytes = AwwasmModule::merge(&[lib, app])?;
+        let mut merged = AwwasmModule::new(&bytes)?;
+        merged.resolve_all_sections()?;
+        assert!(merged.validate().is_empty());
+
+        // Only the import no module exports is left: func 0. Then add, bump
+        // from lib, main, init from app and the start function calling both.
+        let imports = merged.imports().iter().map(|i| Ok((i.module.as_str()?, i.name.as_str()?))).collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(imports, [("env", "log")]);
+        let exports = merged.exports().iter().map(|e| Ok((e.name.as_str()?, e.index))).collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(exports, [("memory", 0), ("add", 1), ("bump", 2), ("main", 3)]);
+        assert_eq!(merged.start.as_ref().map(|s| s.func_idx), Some(5));
+        assert_eq!(merged.globals.as_ref().map(Vec::len), Some(2));
+        assert_eq!(merged.data.as_ref().map(Vec::len), Some(2));
+        let elements = merged.elements.as_ref().expect("elements should exist");
+        assert!(matches!(&elements[0].body, AwwasmElemSegmentBody::ActiveImplicit(s) if s.func_indices == [3]));
+
+        let code = merged.code.as_ref().expect("code should exist");
+        let operands = |body: usize| -> anyhow::Result<Vec<String>> {
+            let mut texts = Vec::new();
+            for instr in &code[body].instructions()? {
+                match &instr.operands {
+                    AwwasmOperands::Call(_) | AwwasmOperands::GlobalGet(_) | AwwasmOperands::GlobalSet(_) | AwwasmOperands::Misc(_) => texts.push(instr.to_string()),
+                    _ => {}
+                }
+            }
+            Ok(texts)
+        };
+        assert_eq!(operands(1)?, ["global.get 0", "call 1", "global.set 0"]);
+        assert_eq!(operands(2)?, ["global.get 1", "memory.init 1 0", "data.drop 1", "call 0", "global.get 1", "call 1"]);
+        assert_eq!(operands(4)?, ["call 2", "call 4"]);
+        Ok(())
+    }
+
+    #[test]
+    fn merge_errors_test() -> anyhow::Result<()> {
+        let lib = wat::parse_str(r#"(module (func (export "add") (param i32 i32) (result i32) (local.get 0)))"#)?;
+        let app = wat::parse_str(r#"(module (import "env" "add" (func (param i64))))"#)?;
+        let memory = wat::parse_str("(module (memory 1))")?;
+        let mut modules = [AwwasmModule::new(&lib)?, AwwasmModule::new(&app)?, AwwasmModule::new(&memory)?, AwwasmModule::new(&memory)?];
+        for module in &mut modules {
+            module.resolve_all_sections()?;
+        }
+        let err = AwwasmModule::merge(&modules[..2]).expect_err("the add types should not match");
+        assert!(err.to_string().contains("does not match"));
+        let err = AwwasmModule::merge(&modules[2..]).expect_err("the memories should conflict");
+        assert!(err.to_string().contains("2 memories"));
+        Ok(())
+    }
+}
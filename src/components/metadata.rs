@@ -0,0 +1,79 @@
+use crate::components::error::AwwasmParseError;
+use crate::components::module::AwwasmModule;
+use crate::components::types::AwwasmCustomSectionItem;
+use nom_derive::*;
+use nom::combinator::all_consuming;
+use nom::multi::length_data;
+use crate::components::leb128::leb128_u32;
+
+const SOURCE_MAPPING_URL_SECTION: &[u8] = b"sourceMappingURL";
+pub(crate) const BUILD_ID_SECTION: &[u8] = b"build_id";
+
+/// Decode the payload of a `build_id` custom section: a length-prefixed byte
+/// vector, usually a UUID or content hash.
+pub(crate) fn parse_build_id(payload: &[u8]) -> anyhow::Result<&[u8]> {
+    let (_, id) = all_consuming(length_data(leb128_u32))(payload)
+        .map_err(|e: nom::Err<nom::error::Error<&[u8]>>| AwwasmParseError::nom("Build ID Section", e))?;
+    Ok(id)
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// First custom section called `name`, in binary order.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn custom_section(&self, name: &[u8]) -> Option<&AwwasmCustomSectionItem<'a>> {
+        self.customs().iter().find(|c| c.name.bytes == name)
+    }
+
+    /// URL of the module's source map, from the `sourceMappingURL` custom section.
+    ///
+    /// `None` if the section is absent or its payload is not a single UTF-8 name.
+    pub fn source_mapping_url(&self) -> Option<&'a str> {
+        let payload = self.custom_section(SOURCE_MAPPING_URL_SECTION)?.payload;
+        let (_, url) = all_consuming(length_data(leb128_u32::<()>))(payload).ok()?;
+        std::str::from_utf8(url).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn source_mapping_url_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (func)
+                (@custom "sourceMappingURL" "\11app.wasm.map?v=12")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.source_mapping_url(), Some("app.wasm.map?v=12"));
+
+        let module = wat::parse_str("(module (func))")?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.source_mapping_url(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn build_id_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (@custom "build_id" (before first) "\04\de\ad\be\ef")
+                (func)
+                (@custom "build_id" "\01\00")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.build_id, Some(&[0xde, 0xad, 0xbe, 0xef][..]));
+
+        let module = wat::parse_str(r#"(module (@custom "build_id" "\05\01"))"#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        assert!(module_parsed.resolve_all_sections().is_err());
+        Ok(())
+    }
+}
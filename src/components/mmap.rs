@@ -0,0 +1,76 @@
+use crate::components::module::AwwasmModule;
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+
+/// A resolved module parsed in place from a memory-mapped file, from
+/// `AwwasmModule::from_path()`. Requires the `mmap` feature.
+///
+/// Names, function bodies and data bytes borrow from the mapping, which the
+/// handle keeps alive; the file is never copied into memory.
+#[derive(Debug)]
+pub struct AwwasmMappedModule {
+    // Borrows from `map`. Only ever handed out with its lifetime shortened
+    // to a borrow of `self`, so nothing from it outlives `map`.
+    module: AwwasmModule<'static>,
+    map: Mmap,
+}
+
+impl AwwasmMappedModule {
+    /// The resolved module.
+    pub fn module(&self) -> &AwwasmModule<'_> {
+        &self.module
+    }
+
+    /// The mapped module binary.
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+impl AwwasmModule<'_> {
+    /// Memory-map the file at `path`, parse it and resolve every section.
+    ///
+    /// The file must not be modified or truncated while the handle is
+    /// alive; as with any memory map, doing so is undefined behavior.
+    pub fn from_path(path: impl AsRef<Path>) -> anyhow::Result<AwwasmMappedModule> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| anyhow::anyhow!("Failed to open {}: {}", path.display(), e))?;
+        // SAFETY: see the requirement above; the mapping is read-only.
+        let map = unsafe { Mmap::map(&file) }.map_err(|e| anyhow::anyhow!("Failed to map {}: {}", path.display(), e))?;
+        // SAFETY: the mapped pages stay at the same address until `map` is
+        // dropped, and `map` is dropped after `module`, which is only
+        // handed out borrowed from the handle.
+        let input: &'static [u8] = unsafe { &*(&map[..] as *const [u8]) };
+        let mut module = AwwasmModule::new(input)?;
+        module.resolve_all_sections()?;
+        Ok(AwwasmMappedModule { module, map })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn from_path_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"(module (memory 1) (func (export "run")) (data (i32.const 0) "hi"))"#)?;
+        let dir = std::env::temp_dir().join(format!("awwasm-mmap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("module.wasm");
+        std::fs::write(&path, &bytes)?;
+        let mapped = AwwasmModule::from_path(&path)?;
+        assert_eq!(mapped.bytes(), &bytes[..]);
+        let data = &mapped.module().data.as_ref().expect("data should exist")[0];
+        assert_eq!(data.data_bytes, b"hi");
+        assert_eq!(mapped.module().offset_of(data.data_bytes), Some(bytes.len() - 2));
+
+        std::fs::write(&path, &bytes[..bytes.len() - 1])?;
+        let err = AwwasmModule::from_path(&path).unwrap_err();
+        assert!(matches!(err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind), Some(AwwasmParseErrorKind::TruncatedSection(_))));
+        assert!(AwwasmModule::from_path(dir.join("missing.wasm")).is_err());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,6 +1,5 @@
-use crate::{limits::*};
 use crate::{consts::*};
-use crate::components::{section::*, types::*};
+use crate::components::{cancel::CancellationToken, component::COMPONENT_LAYER, error::{locate, AwwasmParseError, AwwasmParseErrorKind}, instruction_index::InstructionIndexCache, metadata::{parse_build_id, BUILD_ID_SECTION}, parse_limits::AwwasmParseLimits, section::*, types::*};
 use nom_derive::*;
 use nom::AsBytes;
 use nom::IResult;
@@ -18,21 +17,51 @@ pub struct AwwasmModulePreamble<'a> {
 impl Default for AwwasmModulePreamble<'_> {
     fn default() -> Self {
         Self {
-            magic: WASM_MAGIC_NUMBER.as_bytes().try_into().expect("Incorrect WASM MAGIC NUMBER"),
-            version: 1,
+            magic: WASM_MAGIC_NUMBER.as_bytes(),
+            version: WASM_VERSION,
         }
     }
 }
 
 impl AwwasmModulePreamble<'_> {
-    pub fn new(input: &[u8]) -> anyhow::Result<AwwasmModulePreamble> {
-        let (_, preamble) = AwwasmModulePreamble::parse(input).map_err(|e| anyhow::anyhow!("Failed to parse WASM module preamble: {}", e))?;
+    pub fn new(input: &[u8]) -> anyhow::Result<AwwasmModulePreamble<'_>> {
+        let (_, preamble) = AwwasmModulePreamble::parse(input).map_err(|e| AwwasmParseError::nom("module preamble", e))?;
         Ok(preamble)
     }
+
+    /// The layer field (the upper half of `version`): 0 for a core module,
+    /// `COMPONENT_LAYER` for a component.
+    pub fn layer(&self) -> u16 {
+        (self.version >> 16) as u16
+    }
+
+    /// Whether the binary is a component rather than a core module; parse
+    /// it with `AwwasmComponent::new()`.
+    pub fn is_component(&self) -> bool {
+        self.layer() == COMPONENT_LAYER
+    }
+
+    // Fail for anything but a core module, so a component is not misread
+    // as one.
+    pub(crate) fn check_core_module(&self) -> anyhow::Result<()> {
+        let what = "module preamble";
+        match self.layer() {
+            0 if self.version == WASM_VERSION => Ok(()),
+            COMPONENT_LAYER => Err(AwwasmParseError::new(what, AwwasmParseErrorKind::Malformed {
+                detail: format!("the binary is a component (version {:#x}), parse it with AwwasmComponent::new()", self.version & 0xffff),
+            }).into()),
+            _ => Err(AwwasmParseError::new(what, AwwasmParseErrorKind::UnsupportedVersion { version: self.version }).into()),
+        }
+    }
 }
 
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// A parsed module. After `resolve_all_sections()`, each resolved section
+/// field is `None` if the module has no such section and `Some(vec![])` if
+/// the section is present with an entry count of 0. Code that only needs
+/// the entries reads them through the accessor of the same name, such as
+/// `types()`, which is empty either way.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
 pub struct AwwasmModule<'a> {
     pub preamble: AwwasmModulePreamble<'a>,
     /// Raw parsed sections (before resolve).
@@ -57,28 +86,28 @@ pub struct AwwasmModule<'a> {
     pub tables: Option<Vec<AwwasmTableSectionItem>>,
     /// Resolved element section.
     pub elements: Option<Vec<AwwasmElementSectionItem<'a>>>,
+    /// Resolved tag section (exception handling).
+    pub tags: Option<Vec<AwwasmTagSectionItem>>,
     /// Start section item (from start section), if present.
     pub start: Option<AwwasmStartSectionItem>,
-}
-
-impl Default for AwwasmModule<'_> {
-    fn default() -> Self {
-        Self {
-            preamble: AwwasmModulePreamble::<'_>::default(),
-            sections: None,
-            types: None,
-            imports: None,
-            exports: None,
-            funcs: None,
-            code: None,
-            memories: None,
-            data: None,
-            globals: None,
-            tables: None,
-            elements: None,
-            start: None,
-        }
-    }
+    /// Declared data segment count (from the DataCount section), if present.
+    /// The DataCount section precedes the code section, so this is set before
+    /// `code` during `resolve_all_sections()`.
+    pub data_count: Option<u32>,
+    /// Custom sections, in the order they appear in the binary.
+    pub customs: Option<Vec<AwwasmCustomSectionItem<'a>>>,
+    /// Build ID from the `build_id` custom section, if present. Tools use it to
+    /// match a binary with its separately stored debug info.
+    pub build_id: Option<&'a [u8]>,
+    /// The last section, if it was cut short and the module was parsed with
+    /// `new_permissive()` or `parse_lossy()`.
+    pub truncated: Option<AwwasmPartialSection<'a>>,
+    /// Caps held to by `resolve_all_sections()`, `resolve_section()` and
+    /// the analyses that decode function bodies. Set by `new_with_options()`;
+    /// otherwise the defaults, which only cap nesting depth.
+    pub limits: AwwasmParseLimits,
+    /// Built by `instruction_index()`.
+    pub(crate) instruction_index_cache: InstructionIndexCache,
 }
 
 impl<'a> Parse<&'a[u8]> for AwwasmModule<'a> {
@@ -98,15 +127,64 @@ impl<'a> Parse<&'a[u8]> for AwwasmModule<'a> {
             globals: None,
             tables: None,
             elements: None,
+            tags: None,
             start: None,
+            data_count: None,
+            customs: None,
+            build_id: None,
+            truncated: None,
+            limits: AwwasmParseLimits::default(),
+            instruction_index_cache: Default::default(),
         }))
     }
 }
 
 impl AwwasmModule<'_> {
     /// Parses the entire module (for non-streaming cases).
-    pub fn new(input: &[u8]) -> anyhow::Result<AwwasmModule> {
-        let (_, module) = AwwasmModule::parse(input).map_err(|e| anyhow::anyhow!("Failed to parse WASM module: {}", e))?;
+    ///
+    /// A section whose declared size runs past the end of the input fails
+    /// with an `AwwasmParseError` of kind `TruncatedSection`.
+    pub fn new(input: &[u8]) -> anyhow::Result<AwwasmModule<'_>> {
+        Self::new_with_cancel(input, &CancellationToken::new())
+    }
+
+    /// Like `new`, but a truncated last section is kept in `truncated` instead
+    /// of failing the parse, so the sections before it (and the partial bytes)
+    /// can still be inspected.
+    pub fn new_permissive(input: &[u8]) -> anyhow::Result<AwwasmModule<'_>> {
+        Self::parse_sections(input, &CancellationToken::new(), true)
+    }
+
+    /// Like `new`, but checks `token` before each section and fails with
+    /// `Cancelled` once it is cancelled.
+    pub fn new_with_cancel<'a>(input: &'a [u8], token: &CancellationToken) -> anyhow::Result<AwwasmModule<'a>> {
+        Self::parse_sections(input, token, false)
+    }
+
+    fn parse_sections<'a>(input: &'a [u8], token: &CancellationToken, permissive: bool) -> anyhow::Result<AwwasmModule<'a>> {
+        token.check()?;
+        let start = input.as_ptr() as usize;
+        let (mut input, preamble) = AwwasmModulePreamble::parse(input)
+            .map_err(|e| locate(AwwasmParseError::nom("module preamble", e).within(input).into(), start, None, None))?;
+        preamble.check_core_module()?;
+        let mut module = AwwasmModule { preamble, ..Default::default() };
+        while !input.is_empty() {
+            token.check()?;
+            let (body, header) = complete(AwwasmSectionHeader::parse)(input)
+                .map_err(|e| locate(AwwasmParseError::section_header("section header", e).within(input).into(), start, None, None))?;
+            if header.section_size as usize > body.len() {
+                let error = TruncatedSection { section: header.section_type, declared: header.section_size, available: body.len() };
+                if !permissive {
+                    return Err(AwwasmParseError::from(error).into());
+                }
+                module.truncated = Some(AwwasmPartialSection { error, partial_body: body });
+                break;
+            }
+            let (rest, sec) = complete(AwwasmSection::parse)(input)
+                .map_err(|e| locate(AwwasmParseError::nom("module", e).within(input).into(), start, Some(&header.section_type), None))?;
+            module.sections.get_or_insert_with(Vec::new).push(sec);
+            input = rest;
+        }
         Ok(module)
     }
 }
@@ -136,6 +214,9 @@ impl<'a> AwwasmStreamingParser<'a> {
         if !self.preamble_parsed {
             // nom::Err::Incomplete will be returned here if input is < 8 bytes
             let (new_input, preamble) = AwwasmModulePreamble::parse(input)?;
+            if preamble.layer() != 0 {
+                return Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify)));
+            }
             self.module.preamble = preamble;
             self.preamble_parsed = true;
             input = new_input;
@@ -147,10 +228,7 @@ impl<'a> AwwasmStreamingParser<'a> {
             }
             match AwwasmSection::parse(input) {
                 Ok((new_input, sec)) => {
-                    if self.module.sections.is_none() {
-                        self.module.sections = Some(Vec::new());
-                    }
-                    self.module.sections.as_mut().unwrap().push(sec);
+                    self.module.sections.get_or_insert_with(Vec::new).push(sec);
                     input = new_input;
                     parsed_count += 1;
                 }
@@ -166,29 +244,238 @@ impl<'a> AwwasmStreamingParser<'a> {
 }
 
 impl<'a> AwwasmModule<'a> {
+    /// The raw sections; empty if there are none.
+    pub fn sections(&self) -> &[AwwasmSection<'a>] {
+        self.sections.as_deref().unwrap_or_default()
+    }
+
+    /// The type section entries; empty if there are none.
+    pub fn types(&self) -> &[AwwasmTypeSectionItem<'a>] {
+        self.types.as_deref().unwrap_or_default()
+    }
+
+    /// The import section entries; empty if there are none.
+    pub fn imports(&self) -> &[AwwasmImportSectionItem<'a>] {
+        self.imports.as_deref().unwrap_or_default()
+    }
+
+    /// The export section entries; empty if there are none.
+    pub fn exports(&self) -> &[AwwasmExportSectionItem<'a>] {
+        self.exports.as_deref().unwrap_or_default()
+    }
+
+    /// The function section entries; empty if there are none.
+    pub fn funcs(&self) -> &[AwwasmFuncSectionItem] {
+        self.funcs.as_deref().unwrap_or_default()
+    }
+
+    /// The function bodies; empty if there are none.
+    pub fn code(&self) -> &[AwwasmCodeSectionItem<'a>] {
+        self.code.as_deref().unwrap_or_default()
+    }
+
+    /// The memory section entries; empty if there are none.
+    pub fn memories(&self) -> &[AwwasmMemorySectionItem] {
+        self.memories.as_deref().unwrap_or_default()
+    }
+
+    /// The data segments; empty if there are none.
+    pub fn data(&self) -> &[AwwasmDataSectionItem<'a>] {
+        self.data.as_deref().unwrap_or_default()
+    }
+
+    /// The global section entries; empty if there are none.
+    pub fn globals(&self) -> &[AwwasmGlobalSectionItem<'a>] {
+        self.globals.as_deref().unwrap_or_default()
+    }
+
+    /// The table section entries; empty if there are none.
+    pub fn tables(&self) -> &[AwwasmTableSectionItem] {
+        self.tables.as_deref().unwrap_or_default()
+    }
+
+    /// The element segments; empty if there are none.
+    pub fn elements(&self) -> &[AwwasmElementSectionItem<'a>] {
+        self.elements.as_deref().unwrap_or_default()
+    }
+
+    /// The tag section entries; empty if there are none.
+    pub fn tags(&self) -> &[AwwasmTagSectionItem] {
+        self.tags.as_deref().unwrap_or_default()
+    }
+
+    /// The custom sections; empty if there are none.
+    pub fn customs(&self) -> &[AwwasmCustomSectionItem<'a>] {
+        self.customs.as_deref().unwrap_or_default()
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Non-custom sections that repeat or are out of order, in section
+    /// order. Resolving such a module lets later sections overwrite the
+    /// fields of earlier ones of the same kind.
+    pub fn section_order_issues(&self) -> Vec<SectionOrderError> {
+        let mut issues = Vec::new();
+        let mut seen: Vec<SectionCode> = Vec::new();
+        let mut last: Option<(u8, SectionCode)> = None;
+        for (position, sec) in self.sections().iter().enumerate() {
+            let section = sec.section_header.section_type.clone();
+            let Some(rank) = section.order() else { continue };
+            if seen.contains(&section) {
+                issues.push(SectionOrderError { section: section.clone(), position, after: section });
+                continue;
+            }
+            seen.push(section.clone());
+            match &last {
+                Some((last_rank, after)) if rank < *last_rank => issues.push(SectionOrderError { section, position, after: after.clone() }),
+                _ => last = Some((rank, section)),
+            }
+        }
+        issues
+    }
+
+    /// Offset of `bytes` from the start of the module binary, if they were
+    /// borrowed from it.
+    pub fn offset_of(&self, bytes: &[u8]) -> Option<usize> {
+        let start = self.start_address()?;
+        let offset = (bytes.as_ptr() as usize).checked_sub(start)?;
+        (offset.checked_add(bytes.len())? <= self.end_address()? - start).then_some(offset)
+    }
+
+    /// The original bytes of the section with id `code`, header included,
+    /// for copying it verbatim or comparing it across builds. For a section
+    /// that occurs more than once, the last occurrence, as with resolved
+    /// fields; `None` if the module has no such section.
+    pub fn section_raw(&self, code: SectionCode) -> Option<&'a [u8]> {
+        self.sections().iter().rev()
+            .find(|sec| sec.section_header.section_type == code)
+            .map(AwwasmSection::raw_bytes_with_header)
+    }
+
+    // Address of the first byte of the module binary; unknown for a module
+    // that was not parsed from one.
+    pub(crate) fn start_address(&self) -> Option<usize> {
+        (self.preamble.magic.as_ptr() != WASM_MAGIC_NUMBER.as_ptr()).then_some(self.preamble.magic.as_ptr() as usize)
+    }
+
+    // Address just past the last byte of the module binary that was parsed:
+    // the end of the last section, or of the cut-short one, or else of the
+    // preamble.
+    fn end_address(&self) -> Option<usize> {
+        let start = self.start_address()?;
+        let last = self.sections().last().map(|sec| sec.encoding).into_iter()
+            .chain(self.truncated.as_ref().map(|t| t.partial_body));
+        Some(last.map(|bytes| bytes.as_ptr() as usize + bytes.len()).fold(start + 8, usize::max))
+    }
+
     /// Resolve all raw section bodies into typed data.
     ///
     /// After calling this, fields like `types`, `imports`, `funcs`, `code`,
-    /// `memories`, `data`, `globals`, `tables`, `elements`, and `start` are
-    /// populated from the parsed sections.
+    /// `memories`, `data`, `globals`, `tables`, `elements`, `tags`, `start` and `data_count` are
+    /// populated from the parsed sections, every custom section is collected into `customs`,
+    /// and `build_id` is decoded from the first `build_id` custom section.
     pub fn resolve_all_sections(&mut self) -> anyhow::Result<()> {
-        self.sections.as_mut().unwrap().iter_mut().for_each(|sec| { 
-            let items = sec.resolve().map_err(|e| anyhow::anyhow!("Failed to parse WASM module: {}", e));
-            match items.unwrap() {
-                SectionItem::TypeSectionItems(x)     => { self.types    = x; }
-                SectionItem::ImportSectionItems(x)   => { self.imports  = x; }
-                SectionItem::FunctionSectionItems(x) => { self.funcs    = x; }
-                SectionItem::TableSectionItems(x)    => { self.tables   = x; }
-                SectionItem::MemorySectionItems(x)   => { self.memories = x; }
-                SectionItem::GlobalSectionItems(x)   => { self.globals  = x; }
-                SectionItem::ExportSectionItems(x)   => { self.exports  = x; }
-                SectionItem::ElementSectionItems(x)  => { self.elements = x; }
-                SectionItem::CodeSectionItems(x)     => { self.code     = x; }
-                SectionItem::DataSectionItems(x)     => { self.data     = x; }
-                SectionItem::StartSection(x)         => { self.start    = x; }
-                SectionItem::CustomSection           => { /* skip */ }
-            }
+        self.resolve_all_sections_with_cancel(&CancellationToken::new())
+    }
+
+    /// Like `resolve_all_sections`, but checks `token` before each section and
+    /// fails with `Cancelled` once it is cancelled.
+    ///
+    /// Both fail with `LimitExceeded` on a section over one of `limits`.
+    pub fn resolve_all_sections_with_cancel(&mut self, token: &CancellationToken) -> anyhow::Result<()> {
+        // Custom sections may already be in place from `resolve_section()`.
+        (self.customs, self.build_id) = (None, None);
+        let start = self.start_address();
+        // Taken out while resolving so `store` can borrow `self`.
+        let mut sections = self.sections.take();
+        let result = sections.iter_mut().flatten().try_for_each(|sec| {
+            token.check()?;
+            self.limits.check_section(sec)?;
+            let items = sec.resolve().map_err(|e| match start {
+                Some(start) => locate(e, start, Some(&sec.section_header.section_type), None),
+                None => e,
+            })?;
+            self.store(items)
+        });
+        self.sections = sections;
+        result
+    }
+
+    /// Resolve only the sections with id `code` into their field, leaving
+    /// the others undecoded; e.g. `resolve_section(SectionCode::Export)`
+    /// populates `exports` without decoding the code or data sections.
+    ///
+    /// May be called any number of times, for any sections, before or
+    /// instead of `resolve_all_sections()`. Resolving `SectionCode::Custom`
+    /// replaces `customs` and `build_id`. Fails with `LimitExceeded` on a
+    /// section over one of `limits`.
+    pub fn resolve_section(&mut self, code: SectionCode) -> anyhow::Result<()> {
+        if code == SectionCode::Custom {
+            (self.customs, self.build_id) = (None, None);
+        }
+        let start = self.start_address();
+        let sections = self.sections.take();
+        let result = sections.iter().flatten().filter(|sec| sec.section_header.section_type == code).try_for_each(|sec| {
+            self.limits.check_section(sec)?;
+            // Resolve a copy, since resolving consumes the body, so the
+            // section can be resolved again.
+            let items = sec.clone().resolve().map_err(|e| match start {
+                Some(start) => locate(e, start, Some(&code), None),
+                None => e,
+            })?;
+            self.store(items)
         });
+        self.sections = sections;
+        result
+    }
+
+    /// Resolve the type section alone and return its entries.
+    pub fn resolve_types(&mut self) -> anyhow::Result<&[AwwasmTypeSectionItem<'a>]> {
+        self.resolve_section(SectionCode::Type)?;
+        Ok(self.types())
+    }
+
+    /// Resolve the import section alone and return its entries.
+    pub fn resolve_imports(&mut self) -> anyhow::Result<&[AwwasmImportSectionItem<'a>]> {
+        self.resolve_section(SectionCode::Import)?;
+        Ok(self.imports())
+    }
+
+    /// Resolve the export section alone and return its entries.
+    pub fn resolve_exports(&mut self) -> anyhow::Result<&[AwwasmExportSectionItem<'a>]> {
+        self.resolve_section(SectionCode::Export)?;
+        Ok(self.exports())
+    }
+
+    /// Resolve the custom sections alone and return them.
+    pub fn resolve_customs(&mut self) -> anyhow::Result<&[AwwasmCustomSectionItem<'a>]> {
+        self.resolve_section(SectionCode::Custom)?;
+        Ok(self.customs())
+    }
+
+    // Move resolved section contents into their field.
+    pub(crate) fn store(&mut self, items: SectionItem<'a>) -> anyhow::Result<()> {
+        match items {
+            SectionItem::TypeSectionItems(x)     => { self.types    = Some(x); }
+            SectionItem::ImportSectionItems(x)   => { self.imports  = Some(x); }
+            SectionItem::FunctionSectionItems(x) => { self.funcs    = Some(x); }
+            SectionItem::TableSectionItems(x)    => { self.tables   = Some(x); }
+            SectionItem::MemorySectionItems(x)   => { self.memories = Some(x); }
+            SectionItem::GlobalSectionItems(x)   => { self.globals  = Some(x); }
+            SectionItem::ExportSectionItems(x)   => { self.exports  = Some(x); }
+            SectionItem::ElementSectionItems(x)  => { self.elements = Some(x); }
+            SectionItem::CodeSectionItems(x)     => { self.code     = Some(x); }
+            SectionItem::DataSectionItems(x)     => { self.data     = Some(x); }
+            SectionItem::TagSectionItems(x)      => { self.tags     = Some(x); }
+            SectionItem::StartSection(x)         => { self.start    = x; }
+            SectionItem::DataCountSection(x)     => { self.data_count = x; }
+            SectionItem::CustomSection(x)        => {
+                if x.name.bytes == BUILD_ID_SECTION && self.build_id.is_none() {
+                    self.build_id = Some(parse_build_id(x.payload)?);
+                }
+                self.customs.get_or_insert_with(Vec::new).push(x);
+            }
+        }
         Ok(())
     }
 }
@@ -196,16 +483,23 @@ impl<'a> AwwasmModule<'a> {
 
 #[cfg(test)]
 mod tests {
+    use crate::components::instructions::{
+        marker_text, AtomicOpCode, AwwasmCatchKind, AwwasmInstruction, AwwasmMiscOperands, AwwasmOperands, AwwasmTryTableCatch, BlockType,
+        eval_const_init_expr, InstructionIterator, MemoryInitOperands, MiscOpCode, RefNullOperands, WasmOpCode,
+    };
+    use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
     use crate::components::module::{AwwasmModule, AwwasmModulePreamble};
-    use crate::components::section::{AwwasmSection, AwwasmSectionHeader, SectionCode};
+    use crate::components::parse_limits::AwwasmParseLimits;
+    use crate::components::section::{write_leb128_u32, AwwasmSection, AwwasmSectionHeader, SectionCode, TruncatedSection};
     use crate::components::types::{
         AwwasmCodeSectionItem, AwwasmFuncSectionItem, AwwasmFunction, 
-        AwwasmFunctionLocals, AwwasmTypeSectionItem, ParamType, 
+        AwwasmFunctionLocals, AwwasmTypeSectionItem, ValType, 
         AwwasmImportKind, AwwasmExportKind,
         AwwasmGlobalMutability, AwwasmTableReferenceType,
-        AwwasmStartSectionItem,
+        AwwasmStartSectionItem, AwwasmElemSegmentBody, AwwasmHeapType, AwwasmRefType, AwwasmTagSectionItem,
     };
     use anyhow::Result;
+    use nom_derive::Parse;
 
     #[test]
     fn decode_module_preamble_test() -> Result<()> {
@@ -227,6 +521,38 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn resolve_empty_sections_test() -> Result<()> {
+        // Hand-built: every vector section, present with an entry count of 0.
+        let ids = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x09, 0x0a, 0x0b];
+        let module: Vec<u8> = b"\0asm\x01\0\0\0".iter().copied()
+            .chain(ids.iter().flat_map(|id| [*id, 0x01, 0x00]))
+            .collect();
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        assert_eq!(module_parsed.types, Some(vec![]));
+        assert_eq!(module_parsed.imports, Some(vec![]));
+        assert_eq!(module_parsed.funcs, Some(vec![]));
+        assert_eq!(module_parsed.tables, Some(vec![]));
+        assert_eq!(module_parsed.memories, Some(vec![]));
+        assert_eq!(module_parsed.globals, Some(vec![]));
+        assert_eq!(module_parsed.exports, Some(vec![]));
+        assert_eq!(module_parsed.elements, Some(vec![]));
+        assert_eq!(module_parsed.code, Some(vec![]));
+        assert_eq!(module_parsed.data, Some(vec![]));
+        assert!(module_parsed.validate().is_empty());
+
+        // Absent sections stay `None`.
+        let mut minimal = AwwasmModule::new(b"\0asm\x01\0\0\0")?;
+        minimal.resolve_all_sections()?;
+        assert_eq!((&minimal.types, &minimal.code), (&None, &None));
+        // The accessors are empty either way.
+        assert!(module_parsed.types().is_empty() && module_parsed.code().is_empty());
+        assert!(minimal.types().is_empty() && minimal.code().is_empty());
+        Ok(())
+    }
+
     #[test]
     fn decode_minimal_module_with_minimal_fuction_test() -> Result<()> {
         // Generate a wasm module with just preamble and an empty function.
@@ -242,6 +568,7 @@ mod tests {
                 },
                 entry_count: 1,
                 section_body: &[96, 0, 0],
+                encoding: &[1, 4, 1, 96, 0, 0],
             }, AwwasmSection {
                 section_header: AwwasmSectionHeader {
                     section_type: SectionCode::Function,
@@ -249,13 +576,15 @@ mod tests {
                 },
                 entry_count: 1,
                 section_body: &[0],
+                encoding: &[3, 2, 1, 0],
             }, AwwasmSection {
                 section_header: AwwasmSectionHeader {
                     section_type: SectionCode::Code,
                     section_size: 4,
                 },
                 entry_count: 1,
-                section_body: &[2, 0, 11], 
+                section_body: &[2, 0, 11],
+                encoding: &[10, 4, 1, 2, 0, 11],
             }]),
             // All resolved fields default to None before resolve_all_sections()
             ..AwwasmModule::default()
@@ -273,7 +602,7 @@ mod tests {
         module_parsed.resolve_all_sections()?;
         assert_eq!(module_parsed.types, Some(vec![AwwasmTypeSectionItem {
             type_magic: &[96],
-            fn_args: vec![ParamType::I32, ParamType::I64],
+            fn_args: vec![ValType::I32, ValType::I64],
             fn_rets: vec![],
         }]));
         assert_eq!(module_parsed.funcs, Some(vec![AwwasmFuncSectionItem {
@@ -323,10 +652,10 @@ mod tests {
             parsed_func: Some(AwwasmFunction {
                 fn_rets: vec![AwwasmFunctionLocals {
                     type_count: 1,
-                    param_type: ParamType::I32,
+                    param_type: ValType::I32,
                 }, AwwasmFunctionLocals {
                     type_count: 2,
-                    param_type: ParamType::I64,
+                    param_type: ValType::I64,
                 }],
                 code: &[],
             }),
@@ -366,6 +695,33 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn decode_memory_custom_page_size_test() -> anyhow::Result<()> {
+        // Hand-encoded: an imported memory with 1-byte pages (flags 0x9: max and
+        // page size) and a defined one declaring page size 2^20 (flags 0x8).
+        let mut module = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        module.extend_from_slice(&[0x02, 0x0a, 0x01, 0x01, b'e', 0x01, b'm', 0x02, 0x09, 0x10, 0x20, 0x00]);
+        module.extend_from_slice(&[0x05, 0x04, 0x01, 0x08, 0x01, 0x14]);
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let imported = module_parsed.imports.as_ref().expect("imports should exist")[0].mem.as_ref().expect("memory import");
+        assert_eq!((imported.min, imported.max, imported.page_size_log2), (16, Some(32), Some(0)));
+        assert_eq!(imported.page_size(), 1);
+        let defined = &module_parsed.memories.as_ref().expect("memories should exist")[0].limits;
+        assert_eq!((defined.min, defined.max, defined.page_size_log2), (1, None, Some(20)));
+        assert_eq!(module_parsed.features_used().names(), vec!["multi-memory", "custom-page-sizes"]);
+
+        let findings: Vec<String> = module_parsed.validate().iter().map(|f| f.to_string()).collect();
+        assert_eq!(findings, vec!["[memory] memory 1 declares page size 2^20 but only 1 and 65536 are allowed"]);
+
+        let default = wat::parse_str("(module (memory 1))")?;
+        let mut default_parsed = AwwasmModule::new(&default)?;
+        default_parsed.resolve_all_sections()?;
+        assert_eq!(default_parsed.memories.as_ref().expect("memories should exist")[0].limits.page_size(), 65536);
+        Ok(())
+    }
+
     #[test]
     fn decode_import_memory_and_function_test() -> anyhow::Result<()> {
         // Import a memory and a function; ensure both decode correctly
@@ -384,8 +740,8 @@ mod tests {
 
         // memory import
         let i0 = &imports[0];
-        assert_eq!(i0.module.bytes, b"env");
-        assert_eq!(i0.name.bytes, b"mem");
+        assert_eq!(&i0.module.bytes[..], b"env");
+        assert_eq!(&i0.name.bytes[..], b"mem");
         assert_eq!(i0.kind, AwwasmImportKind::Memory);
         assert!(i0.func_type_idx.is_none());
         let mp = i0.mem.as_ref().expect("memory params");
@@ -395,8 +751,8 @@ mod tests {
 
         // function import
         let i1 = &imports[1];
-        assert_eq!(i1.module.bytes, b"env");
-        assert_eq!(i1.name.bytes, b"add1");
+        assert_eq!(&i1.module.bytes[..], b"env");
+        assert_eq!(&i1.name.bytes[..], b"add1");
         assert_eq!(i1.kind, AwwasmImportKind::Function);
         assert!(i1.mem.is_none());
         // Function imports reference a type index; with this single func type it should be 0
@@ -406,12 +762,39 @@ mod tests {
         let types = module_parsed.types.as_ref().expect("types should exist");
         assert_eq!(types.len(), 1);
         assert_eq!(types[0].type_magic, &[0x60]);
-        assert_eq!(types[0].fn_args, vec![ParamType::I32]);
-        assert_eq!(types[0].fn_rets, vec![ParamType::I32]);
+        assert_eq!(types[0].fn_args, vec![ValType::I32]);
+        assert_eq!(types[0].fn_rets, vec![ValType::I32]);
 
         Ok(())
     }
 
+    #[test]
+    fn decode_import_table_and_global_test() -> anyhow::Result<()> {
+        // Table and global import descriptors must be consumed so later imports decode.
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "tbl" (table 2 10 funcref))
+                (import "env" "g" (global (mut i64)))
+                (import "env" "f" (func))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let imports = module_parsed.imports.as_ref().expect("imports should exist");
+        assert_eq!(imports.len(), 3);
+        let table = imports[0].table.as_ref().expect("table import");
+        assert_eq!(imports[0].kind, AwwasmImportKind::Table);
+        assert_eq!(table.elem_type, AwwasmTableReferenceType::Function);
+        assert_eq!(table.limits.max, Some(10));
+        let global = imports[1].global.as_ref().expect("global import");
+        assert_eq!(global.value_type, ValType::I64);
+        assert_eq!(global.mutability, AwwasmGlobalMutability::Mutable);
+        assert_eq!(&imports[2].name.bytes[..], b"f");
+        assert_eq!(imports[2].func_type_idx, Some(0));
+        Ok(())
+    }
+
     #[test]
     fn decode_export_memory_and_function_test() -> anyhow::Result<()> {
         // Define a module with one function and one memory, and export both.
@@ -432,13 +815,13 @@ mod tests {
 
         // First export: memory 0 as "mem"
         let e0 = &exports[0];
-        assert_eq!(e0.name.bytes, b"mem");
+        assert_eq!(&e0.name.bytes[..], b"mem");
         assert_eq!(e0.kind, AwwasmExportKind::Memory);
         assert_eq!(e0.index, 0);
 
         // Second export: func 0 as "add1"
         let e1 = &exports[1];
-        assert_eq!(e1.name.bytes, b"add1");
+        assert_eq!(&e1.name.bytes[..], b"add1");
         assert_eq!(e1.kind, AwwasmExportKind::Function);
         assert_eq!(e1.index, 0);
 
@@ -446,8 +829,8 @@ mod tests {
         let types = module_parsed.types.as_ref().expect("types should exist");
         assert_eq!(types.len(), 1);
         assert_eq!(types[0].type_magic, &[0x60]);
-        assert_eq!(types[0].fn_args, vec![ParamType::I32]);
-        assert_eq!(types[0].fn_rets, vec![ParamType::I32]);
+        assert_eq!(types[0].fn_args, vec![ValType::I32]);
+        assert_eq!(types[0].fn_rets, vec![ValType::I32]);
 
         Ok(())
     }
@@ -520,13 +903,565 @@ mod tests {
 
         let globals = module_parsed.globals.as_ref().expect("globals should exist");
         assert_eq!(globals.len(), 2);
-        assert_eq!(globals[0].value_type, ParamType::I32);
+        assert_eq!(globals[0].value_type, ValType::I32);
         assert_eq!(globals[0].mutability, AwwasmGlobalMutability::Immutable);
-        assert_eq!(globals[1].value_type, ParamType::I64);
+        assert_eq!(globals[1].value_type, ValType::I64);
         assert_eq!(globals[1].mutability, AwwasmGlobalMutability::Mutable);
         Ok(())
     }
 
+    #[test]
+    fn decode_extended_const_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "base" (global i32))
+                (memory 1)
+                (global i32 (i32.add (global.get 0) (i32.const 11)))
+                (global i64 (i64.mul (i64.const 11) (i64.const 3)))
+                (data (i32.sub (i32.mul (i32.const 4) (i32.const 16)) (i32.const 1)) "x")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        // The `i32.const 11` immediate is a 0x0B byte, not the end of the expression.
+        let globals = module_parsed.globals.as_ref().expect("globals should exist");
+        let ops: Result<Vec<&str>, _> = InstructionIterator::new(globals[0].init_expr.code)
+            .map(|i| i.map(|i| i.opcode.mnemonic()))
+            .collect();
+        assert_eq!(ops, Ok(vec!["global.get", "i32.const", "i32.add"]));
+        assert_eq!(globals[1].init_expr.code, &[0x42, 0x0b, 0x42, 0x03, 0x7e]);
+        assert!(eval_const_init_expr(globals[0].init_expr.code).is_err());
+
+        let offset = module_parsed.data.as_ref().expect("data should exist")[0].header.offset.as_ref().expect("active segment");
+        assert_eq!(eval_const_init_expr(offset.code)?, 63);
+        assert!(module_parsed.features_used().extended_const);
+
+        // `local.get` is not a constant instruction.
+        let mut bad = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        bad.extend_from_slice(&[0x06, 0x06, 0x01, 0x7f, 0x00, 0x20, 0x00, 0x0b]);
+        assert!(AwwasmModule::new(&bad)?.resolve_all_sections().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_value_types_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (global externref (ref.null extern))
+                (func (param externref funcref) (result f32)
+                    (local f64)
+                    (block (result f32)
+                        (select (result f32) (f32.const 1) (f32.const 2) (i32.const 0))))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let types = module_parsed.types.as_ref().expect("types should exist");
+        assert_eq!(types[0].fn_args, vec![ValType::ExternRef, ValType::FuncRef]);
+        assert_eq!(types[0].fn_rets, vec![ValType::F32]);
+        let globals = module_parsed.globals.as_ref().expect("globals should exist");
+        assert_eq!(globals[0].value_type, ValType::ExternRef);
+
+        let code = module_parsed.code.as_ref().expect("code should exist");
+        let (locals, _) = code[0].locals_and_code()?;
+        assert_eq!(locals[0].param_type, ValType::F64);
+        let instrs = code[0].instructions()?;
+        let AwwasmOperands::Block(block) = &instrs[0].operands else { panic!("expected block") };
+        assert_eq!(block.block_type, BlockType::Value(ValType::F32));
+        let AwwasmOperands::SelectTyped(select) = &block.body.0[3].operands else { panic!("expected typed select") };
+        assert_eq!(select.types, vec![ValType::F32]);
+
+        for t in [ValType::I32, ValType::I64, ValType::F32, ValType::F64, ValType::V128, ValType::FuncRef, ValType::ExternRef] {
+            assert_eq!(ValType::decode(t.encode()), Some(t));
+        }
+        assert_eq!(ValType::decode(0x40), None);
+        assert_eq!(ValType::ExternRef.to_string(), "externref");
+        assert!(ValType::FuncRef.is_ref() && ValType::I64.is_num() && ValType::V128.is_vec());
+        assert_eq!(ValType::from(AwwasmTableReferenceType::Function), ValType::FuncRef);
+        assert!(AwwasmTableReferenceType::try_from(ValType::I32).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_integer_opcodes_test() -> anyhow::Result<()> {
+        // Every MVP integer opcode, plus sign extension. The body is not
+        // type-correct; decoding does not need it to be.
+        const OPS: &str = "i32.eqz i32.eq i32.ne i32.lt_s i32.lt_u i32.gt_s i32.gt_u i32.le_s i32.le_u i32.ge_s i32.ge_u i32.clz i32.ctz i32.popcnt i32.add i32.sub i32.mul i32.div_s i32.div_u i32.rem_s i32.rem_u i32.and i32.or i32.xor i32.shl i32.shr_s i32.shr_u i32.rotl i32.rotr i64.eqz i64.eq i64.ne i64.lt_s i64.lt_u i64.gt_s i64.gt_u i64.le_s i64.le_u i64.ge_s i64.ge_u i64.clz i64.ctz i64.popcnt i64.add i64.sub i64.mul i64.div_s i64.div_u i64.rem_s i64.rem_u i64.and i64.or i64.xor i64.shl i64.shr_s i64.shr_u i64.rotl i64.rotr i32.wrap_i64 i64.extend_i32_s i64.extend_i32_u i32.extend8_s i32.extend16_s i64.extend8_s i64.extend16_s i64.extend32_s";
+        let module = wat::parse_str(format!("(module (func {} (br_table 0 1 300 (i32.const 0))))", OPS))?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+        let mnemonics: Vec<&str> = instrs.iter().map(|i| i.opcode.mnemonic()).collect();
+        let ops: Vec<&str> = OPS.split(' ').collect();
+        assert_eq!(mnemonics[..ops.len()], ops[..]);
+        let AwwasmOperands::BrTable(br_table) = &instrs[ops.len() + 1].operands else { panic!("expected br_table") };
+        assert_eq!(br_table.targets, vec![0, 1]);
+        assert_eq!(br_table.default, 300);
+        assert_eq!(mnemonics[ops.len() + 2..], ["end"]);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_float_opcodes_test() -> anyhow::Result<()> {
+        // Every f32/f64 comparison and arithmetic opcode (0x5B-0xA6).
+        const OPS: &str = "f32.eq f32.ne f32.lt f32.gt f32.le f32.ge f64.eq f64.ne f64.lt f64.gt f64.le f64.ge f32.abs f32.neg f32.ceil f32.floor f32.trunc f32.nearest f32.sqrt f32.add f32.sub f32.mul f32.div f32.min f32.max f32.copysign f64.abs f64.neg f64.ceil f64.floor f64.trunc f64.nearest f64.sqrt f64.add f64.sub f64.mul f64.div f64.min f64.max f64.copysign";
+        let module = wat::parse_str(format!("(module (func (f32.const 1.5) (f64.const -2.25) {}))", OPS))?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+        let AwwasmOperands::F32Const(f32_const) = &instrs[0].operands else { panic!("expected f32.const") };
+        assert_eq!(f32_const.value, 1.5);
+        let AwwasmOperands::F64Const(f64_const) = &instrs[1].operands else { panic!("expected f64.const") };
+        assert_eq!(f64_const.value, -2.25);
+        let mnemonics: Vec<&str> = instrs[2..].iter().map(|i| i.opcode.mnemonic()).collect();
+        let ops: Vec<&str> = OPS.split(' ').chain(["end"]).collect();
+        assert_eq!(mnemonics, ops);
+        assert!(instrs[2..instrs.len() - 1].iter().all(|i| (0x5B..=0xA6).contains(&(i.opcode as u8))));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_conversion_opcodes_test() -> anyhow::Result<()> {
+        // Every conversion, truncation and reinterpret opcode (0xA7-0xBF).
+        const OPS: &str = "i32.wrap_i64 i32.trunc_f32_s i32.trunc_f32_u i32.trunc_f64_s i32.trunc_f64_u i64.extend_i32_s i64.extend_i32_u i64.trunc_f32_s i64.trunc_f32_u i64.trunc_f64_s i64.trunc_f64_u f32.convert_i32_s f32.convert_i32_u f32.convert_i64_s f32.convert_i64_u f32.demote_f64 f64.convert_i32_s f64.convert_i32_u f64.convert_i64_s f64.convert_i64_u f64.promote_f32 i32.reinterpret_f32 i64.reinterpret_f64 f32.reinterpret_i32 f64.reinterpret_i64";
+        let module = wat::parse_str(format!("(module (func {} (drop (i32.const 0))))", OPS))?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let (_, code) = module_parsed.code.as_ref().expect("code should exist")[0].locals_and_code()?;
+        let instrs = InstructionIterator::new(code).collect::<Result<Vec<_>, _>>().map_err(|e| anyhow::anyhow!("{}", e))?;
+        let mnemonics: Vec<&str> = instrs.iter().map(|i| i.opcode.mnemonic()).collect();
+        let ops: Vec<&str> = OPS.split(' ').chain(["i32.const", "drop", "end"]).collect();
+        assert_eq!(mnemonics, ops);
+        assert_eq!(instrs.iter().filter(|i| (0xA7..=0xBF).contains(&(i.opcode as u8))).count(), 25);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_saturating_truncation_opcodes_test() -> anyhow::Result<()> {
+        const OPS: &str = "i32.trunc_sat_f32_s i32.trunc_sat_f32_u i32.trunc_sat_f64_s i32.trunc_sat_f64_u i64.trunc_sat_f32_s i64.trunc_sat_f32_u i64.trunc_sat_f64_s i64.trunc_sat_f64_u";
+        let module = wat::parse_str(format!("(module (func {}))", OPS))?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+        let names: Vec<String> = instrs.iter().map(|i| i.to_string()).collect();
+        assert_eq!(names, OPS.split(' ').chain(["end"]).collect::<Vec<_>>());
+        let AwwasmOperands::Misc(misc) = &instrs[7].operands else { panic!("expected 0xFC prefix") };
+        assert_eq!(misc.sub_op, MiscOpCode::I64TruncSatF64U);
+        assert_eq!(instrs[7].encoding, &[0xfc, 0x07]);
+
+        // The sub-opcode is a LEB128 u32, so a padded encoding is still valid;
+        // an undefined sub-opcode is not.
+        use nom_derive::Parse;
+        let (_, padded) = AwwasmInstruction::parse(&[0xfc, 0x81, 0x00])?;
+        assert_eq!(padded.to_string(), "i32.trunc_sat_f32_u");
+        assert!(AwwasmInstruction::parse(&[0xfc, 0x7f]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_sign_extension_opcodes_test() -> anyhow::Result<()> {
+        // Hand-built: (func (param i32 i64)) applying each of 0xC0-0xC4 to a parameter.
+        let module: Vec<u8> = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[0x01, 0x06, 0x01, 0x60, 0x02, 0x7f, 0x7e, 0x00],
+            &[0x03, 0x02, 0x01, 0x00],
+            &[0x0a, 0x18, 0x01, 0x16, 0x00,
+                0x20, 0x00, 0xc0, 0x1a,
+                0x20, 0x00, 0xc1, 0x1a,
+                0x20, 0x01, 0xc2, 0x1a,
+                0x20, 0x01, 0xc3, 0x1a,
+                0x20, 0x01, 0xc4, 0x1a,
+                0x0b],
+        ].concat();
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+        let extends: Vec<&str> = instrs.iter().skip(1).step_by(3).map(|i| i.opcode.mnemonic()).collect();
+        assert_eq!(extends, vec!["i32.extend8_s", "i32.extend16_s", "i64.extend8_s", "i64.extend16_s", "i64.extend32_s"]);
+        assert!(instrs.iter().skip(1).step_by(3).all(|i| i.encoding.len() == 1));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_parametric_opcodes_test() -> anyhow::Result<()> {
+        let module = wat::parse_str("(module (func nop (select (i32.const 1) (i32.const 2) (i32.const 0)) drop unreachable))")?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+        let ops: Vec<(&AwwasmOperands, &[u8])> = instrs.iter()
+            .filter(|i| !matches!(i.operands, AwwasmOperands::I32Const(_)))
+            .map(|i| (&i.operands, i.encoding))
+            .collect();
+        assert_eq!(ops, vec![
+            (&AwwasmOperands::Nop, &[0x01][..]),
+            (&AwwasmOperands::Select, &[0x1b][..]),
+            (&AwwasmOperands::Drop, &[0x1a][..]),
+            (&AwwasmOperands::Unreachable, &[0x00][..]),
+            (&AwwasmOperands::End, &[0x0b][..]),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_reference_opcodes_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (func $f)
+                (elem declare func $f)
+                (func (result i32 funcref externref)
+                    (ref.is_null (ref.func $f))
+                    (ref.func $f)
+                    (ref.null extern))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[1].instructions()?;
+        let decoded: Vec<(String, &[u8])> = instrs.iter().map(|i| (i.to_string(), i.encoding)).collect();
+        assert_eq!(decoded, vec![
+            ("ref.func 0".to_owned(), &[0xd2, 0x00][..]),
+            ("ref.is_null".to_owned(), &[0xd1][..]),
+            ("ref.func 0".to_owned(), &[0xd2, 0x00][..]),
+            ("ref.null extern".to_owned(), &[0xd0, 0x6f][..]),
+            ("end".to_owned(), &[0x0b][..]),
+        ]);
+        assert_eq!(instrs[3].operands, AwwasmOperands::RefNull(RefNullOperands { heap_type: AwwasmHeapType::Extern }));
+        assert!(module_parsed.features_used().reference_types);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_multi_value_block_type_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (type $pair (func (param i32) (result i32 i32)))
+                (func (result i32 i32)
+                    (i32.const 1)
+                    (block (type $pair) (param i32) (result i32 i32) (i32.const 2))
+                    (loop (type $pair) (param i32) (result i32 i32) (i32.const 3))
+                    (drop))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+        let AwwasmOperands::Block(block) = &instrs[1].operands else { panic!("expected block") };
+        assert_eq!(block.block_type, BlockType::TypeIndex(0));
+        assert_eq!(instrs[1].to_string(), "block (type 0)");
+        assert_eq!(instrs[2].operands.block_type(), Some(BlockType::TypeIndex(0)));
+        assert!(module_parsed.validate().is_empty());
+        assert!(module_parsed.features_used().multi_value);
+
+        // s33: a type index of 64 takes two bytes, and negative values other
+        // than the value types are rejected.
+        assert_eq!(BlockType::parse(&[0xc0, 0x00][..]), Ok((&[][..], BlockType::TypeIndex(64))));
+        assert!(BlockType::parse(&[0x7f, 0x01][..]).is_ok_and(|(rest, t)| rest == [0x01] && t == BlockType::Value(ValType::I32)));
+        assert!(BlockType::parse(&[0x41][..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_nesting_depth_limit_test() -> anyhow::Result<()> {
+        // One function whose body is `depth` blocks, each inside the last.
+        let nested = |depth: usize| {
+            let mut body = vec![0x00];
+            body.extend([0x02, 0x40].repeat(depth));
+            body.extend(vec![0x0b; depth + 1]);
+            let mut code = vec![0x01];
+            write_leb128_u32(&mut code, body.len() as u32);
+            code.extend(body);
+            let mut module = b"\0asm\x01\0\0\0\x01\x04\x01\x60\x00\x00\x03\x02\x01\x00\x0a".to_vec();
+            write_leb128_u32(&mut module, code.len() as u32);
+            module.extend(code);
+            module
+        };
+        let decode = |module: &[u8], limits: &AwwasmParseLimits| -> anyhow::Result<usize> {
+            let mut parsed = AwwasmModule::new(module)?;
+            parsed.resolve_all_sections()?;
+            let code = &parsed.code.as_ref().expect("code should exist")[0];
+            code.instructions_with_limits(limits).map(|instrs| instrs.len()).map_err(|e| parsed.locate_error(e))
+        };
+        let limits = AwwasmParseLimits { max_nesting_depth: 2, ..Default::default() };
+        assert_eq!(decode(&nested(2), &limits)?, 2);
+
+        let err = decode(&nested(3), &limits).unwrap_err();
+        let err = err.downcast_ref::<AwwasmParseError>().expect("should be an AwwasmParseError");
+        assert_eq!(err.kind, AwwasmParseErrorKind::LimitExceeded { limit: "nesting depth", section: None, actual: 3, max: 2 });
+        assert_eq!((err.offset, err.func_idx), (Some(0x1b), Some(0)));
+
+        // 200k nested blocks fail on the default limit, not the stack.
+        let err = decode(&nested(200_000), &AwwasmParseLimits::default()).unwrap_err();
+        let kind = err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind);
+        assert_eq!(kind, Some(&AwwasmParseErrorKind::LimitExceeded { limit: "nesting depth", section: None, actual: 1025, max: 1024 }));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_function_references_test() -> anyhow::Result<()> {
+        // Hand-built, as the pinned `wat` predates the final 0x63/0x64 encoding:
+        //   (type $f (func (param i32) (result i32)))
+        //   (type (func (param (ref null $f)) (result i32)))
+        //   (func (type $f) (local.get 0))
+        //   (func (type 1) (local $r (ref $f))
+        //       (local.set $r (ref.as_non_null (local.get 0)))
+        //       (block $l (br_on_null $l (local.get 0)) (drop))
+        //       (call_ref $f (i32.const 1) (local.get $r)))
+        let module: Vec<u8> = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[0x01, 0x0c, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x01, 0x63, 0x00, 0x01, 0x7f],
+            &[0x03, 0x03, 0x02, 0x00, 0x01],
+            &[0x0a, 0x1f, 0x02, 0x04, 0x00, 0x20, 0x00, 0x0b, 0x18, 0x01, 0x01, 0x64, 0x00],
+            &[0x20, 0x00, 0xd4, 0x21, 0x01, 0x02, 0x40, 0x20, 0x00, 0xd5, 0x00, 0x1a, 0x0b],
+            &[0x41, 0x01, 0x20, 0x01, 0x14, 0x00, 0x0b],
+        ].concat();
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let nullable_f = ValType::Ref(AwwasmRefType { nullable: true, heap_type: AwwasmHeapType::Type(0) });
+        assert_eq!(module_parsed.types.as_ref().expect("types should exist")[1].fn_args, vec![nullable_f]);
+        assert_eq!(nullable_f.to_string(), "(ref null 0)");
+
+        let code = &module_parsed.code.as_ref().expect("code should exist")[1];
+        let (locals, _) = code.locals_and_code()?;
+        assert_eq!(locals[0].param_type, ValType::Ref(AwwasmRefType { nullable: false, heap_type: AwwasmHeapType::Type(0) }));
+        let mut mnemonics = Vec::new();
+        for instr in &code.instructions()? {
+            instr.walk(&mut |i| mnemonics.push(i.to_string()));
+        }
+        assert_eq!(mnemonics, vec![
+            "local.get 0", "ref.as_non_null", "local.set 1", "block", "local.get 0", "br_on_null 0", "drop",
+            "i32.const 1", "local.get 1", "call_ref 0", "end",
+        ]);
+        assert!(module_parsed.features_used().function_references);
+
+        // Indices of 64 and up need a second byte to keep the s33 sign clear.
+        let mut encoded = Vec::new();
+        ValType::Ref(AwwasmRefType { nullable: false, heap_type: AwwasmHeapType::Type(64) }).write(&mut encoded);
+        assert_eq!(encoded, vec![0x64, 0xc0, 0x00]);
+        assert_eq!(ValType::parse(&encoded[..]).map(|(_, t)| t.to_string()), Ok("(ref 64)".to_owned()));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_exception_handling_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (type (func (param i32)))
+                (import "env" "js" (tag $js (type 0)))
+                (tag $e (param i32))
+                (export "e" (tag $e))
+                (func (param i32) (result i32)
+                    (try (result i32)
+                        (do (throw $e (local.get 0)))
+                        (catch $e)
+                        (catch_all (try (do (rethrow 1)) (delegate 0)) (i32.const 0))))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        assert_eq!(module_parsed.tags, Some(vec![AwwasmTagSectionItem { attribute: 0, type_idx: 0 }]));
+        let import = &module_parsed.imports.as_ref().expect("imports should exist")[0];
+        assert_eq!((import.kind.clone(), import.tag.clone()), (AwwasmImportKind::Tag, Some(AwwasmTagSectionItem { attribute: 0, type_idx: 0 })));
+        assert_eq!(module_parsed.exports.as_ref().expect("exports should exist")[0].kind, AwwasmExportKind::Tag);
+        assert_eq!(module_parsed.tag_count(), 2);
+        assert!(module_parsed.validate().is_empty());
+
+        let code = &module_parsed.code.as_ref().expect("code should exist")[0];
+        let instrs = code.instructions()?;
+        let AwwasmOperands::Try(op) = &instrs[0].operands else { panic!("expected try") };
+        assert_eq!(instrs[0].to_string(), "try i32");
+        assert_eq!(op.handlers.iter().map(|h| h.tag).collect::<Vec<_>>(), vec![Some(1), None]);
+        assert_eq!(op.delegate, None);
+        let bodies: Vec<String> = instrs[0].operands.bodies().iter().map(|(_, marker)| marker_text(marker)).collect();
+        assert_eq!(bodies, vec!["catch 1", "catch_all", "end"]);
+
+        let mut mnemonics = Vec::new();
+        instrs[0].walk(&mut |i| mnemonics.push(i.to_string()));
+        assert_eq!(mnemonics, vec!["try i32", "local.get 0", "throw 1", "try", "rethrow 1", "i32.const 0"]);
+        let AwwasmOperands::Try(inner) = &op.handlers[1].body.0[0].operands else { panic!("expected nested try") };
+        assert_eq!(inner.delegate, Some(0));
+        assert!(module_parsed.features_used().exception_handling);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_try_table_test() -> anyhow::Result<()> {
+        // No locals, then (try_table (result i32) (catch 0 0) (catch_all_ref 1) throw_ref).
+        let item: &[u8] = &[0x0c, 0x00, 0x1f, 0x7f, 0x02, 0x00, 0x00, 0x00, 0x03, 0x01, 0x0a, 0x0b, 0x0b];
+        let (_, code) = AwwasmCodeSectionItem::parse(item)?;
+        let instrs = code.instructions()?;
+        let AwwasmOperands::TryTable(op) = &instrs[0].operands else { panic!("expected try_table") };
+        assert_eq!(op.catches, vec![
+            AwwasmTryTableCatch { kind: AwwasmCatchKind::Catch, tag: Some(0), label: 0 },
+            AwwasmTryTableCatch { kind: AwwasmCatchKind::CatchAllRef, tag: None, label: 1 },
+        ]);
+        assert_eq!(instrs[0].encoding, &item[2..10]);
+        assert_eq!(op.body.0[0].opcode, WasmOpCode::ThrowRef);
+        assert_eq!(instrs[0].to_string(), "try_table i32 (catch 0 0) (catch_all_ref 1)");
+        Ok(())
+    }
+
+    #[test]
+    fn decode_bulk_memory_opcodes_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1)
+                (data "passive")
+                (data $d "another")
+                (func (param i32)
+                    (memory.init $d (local.get 0) (i32.const 0) (i32.const 7))
+                    (data.drop $d)
+                    (memory.copy (i32.const 0) (local.get 0) (i32.const 4))
+                    (memory.fill (local.get 0) (i32.const 0) (i32.const 16)))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+        let bulk: Vec<(String, &[u8])> = instrs.iter()
+            .filter(|i| i.opcode == WasmOpCode::Misc)
+            .map(|i| (i.to_string(), i.encoding))
+            .collect();
+        assert_eq!(bulk, vec![
+            ("memory.init 1 0".to_owned(), &[0xfc, 0x08, 0x01, 0x00][..]),
+            ("data.drop 1".to_owned(), &[0xfc, 0x09, 0x01][..]),
+            ("memory.copy 0 0".to_owned(), &[0xfc, 0x0a, 0x00, 0x00][..]),
+            ("memory.fill 0".to_owned(), &[0xfc, 0x0b, 0x00][..]),
+        ]);
+        let AwwasmOperands::Misc(init) = &instrs[3].operands else { panic!("expected 0xFC prefix") };
+        assert_eq!(init.operands, AwwasmMiscOperands::MemoryInit(MemoryInitOperands { dataidx: 1, memidx: 0 }));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_atomic_opcodes_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1 1 shared)
+                (func (param i32) (result i32)
+                    (i32.atomic.store offset=8 (local.get 0) (i32.const 1))
+                    (drop (i64.atomic.rmw16.cmpxchg_u (local.get 0) (i64.const 0) (i64.const 1)))
+                    (drop (memory.atomic.wait32 (local.get 0) (i32.const 0) (i64.const -1)))
+                    (atomic.fence)
+                    (memory.atomic.notify (local.get 0) (i32.const 1)))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let memory = &module_parsed.memories.as_ref().expect("memory should exist")[0];
+        assert!(memory.limits.is_shared());
+        assert_eq!(memory.limits.max, Some(1));
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+        let atomics: Vec<(String, &[u8])> = instrs.iter()
+            .filter(|i| i.opcode == WasmOpCode::Atomic)
+            .map(|i| (i.to_string(), i.encoding))
+            .collect();
+        assert_eq!(atomics, vec![
+            ("i32.atomic.store 2 8".to_owned(), &[0xfe, 0x17, 0x02, 0x08][..]),
+            ("i64.atomic.rmw16.cmpxchg_u 1 0".to_owned(), &[0xfe, 0x4d, 0x01, 0x00][..]),
+            ("memory.atomic.wait32 2 0".to_owned(), &[0xfe, 0x01, 0x02, 0x00][..]),
+            ("atomic.fence".to_owned(), &[0xfe, 0x03, 0x00][..]),
+            ("memory.atomic.notify 2 0".to_owned(), &[0xfe, 0x00, 0x02, 0x00][..]),
+        ]);
+        let AwwasmOperands::Atomic(fence) = &instrs[instrs.len() - 5].operands else { panic!("expected 0xFE prefix") };
+        assert_eq!((fence.sub_op, &fence.mem_arg), (AtomicOpCode::AtomicFence, &None));
+        assert!(module_parsed.validate().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_typed_select_test() -> anyhow::Result<()> {
+        // Hand-built: (func (param externref externref) (result externref))
+        // selecting between its parameters with `select (result externref)`.
+        let module: Vec<u8> = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[0x01, 0x07, 0x01, 0x60, 0x02, 0x6f, 0x6f, 0x01, 0x6f],
+            &[0x03, 0x02, 0x01, 0x00],
+            &[0x0a, 0x0d, 0x01, 0x0b, 0x00,
+                0x20, 0x00, 0x20, 0x01, 0x41, 0x01,
+                0x1c, 0x01, 0x6f,
+                0x0b],
+        ].concat();
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+        let AwwasmOperands::SelectTyped(select) = &instrs[3].operands else { panic!("expected typed select") };
+        assert_eq!(select.types, vec![ValType::ExternRef]);
+        assert_eq!(instrs[3].encoding, &[0x1c, 0x01, 0x6f]);
+        assert_eq!(instrs[3].to_string(), "select externref");
+        assert_eq!(instrs[4].operands, AwwasmOperands::End);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_memory_access_opcodes_test() -> anyhow::Result<()> {
+        // Every load and store (0x28-0x3E); each takes an address, stores also a value.
+        const LOADS: &str = "i32.load i64.load f32.load f64.load i32.load8_s i32.load8_u i32.load16_s i32.load16_u i64.load8_s i64.load8_u i64.load16_s i64.load16_u i64.load32_s i64.load32_u";
+        const STORES: &str = "i32.store i64.store f32.store f64.store i32.store8 i32.store16 i64.store8 i64.store16 i64.store32";
+        let body: String = LOADS.split(' ').map(|op| format!("(drop ({} offset=4 (i32.const 0)))", op))
+            .chain(STORES.split(' ').map(|op| {
+                let value = match &op[..3] { "i32" => "i32", "i64" => "i64", "f32" => "f32", _ => "f64" };
+                format!("({} offset=4 align=1 (i32.const 0) ({}.const 0))", op, value)
+            }))
+            .collect();
+        let module = wat::parse_str(format!("(module (memory 1) (func {}))", body))?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+        let accesses: Vec<&AwwasmInstruction> = instrs.iter().filter(|i| (0x28..=0x3E).contains(&(i.opcode as u8))).collect();
+        let mnemonics: Vec<&str> = accesses.iter().map(|i| i.opcode.mnemonic()).collect();
+        assert_eq!(mnemonics, LOADS.split(' ').chain(STORES.split(' ')).collect::<Vec<_>>());
+        assert_eq!(accesses[6].to_string(), "i32.load16_s 1 4");
+        assert_eq!(accesses[13].to_string(), "i64.load32_u 2 4");
+        assert!(accesses[14..].iter().all(|i| i.to_string().ends_with(" 0 4")));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_multi_memory_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1)
+                (memory $second 1)
+                (func (result i32)
+                    (i32.store $second offset=8 (i32.const 0) (i32.const 7))
+                    (drop (memory.grow $second (i32.const 1)))
+                    (drop (memory.size 0))
+                    (i32.load (i32.const 0)))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.memory_count(), 2);
+
+        let instrs = module_parsed.code.as_ref().expect("code should exist")[0].instructions()?;
+        let AwwasmOperands::I32Store(store) = &instrs[2].operands else { panic!("expected i32.store") };
+        assert_eq!((store.align, store.memidx, store.offset, store.memory()), (2, Some(1), 8, 1));
+        assert_eq!(instrs[2].to_string(), "i32.store 1 2 8");
+        assert_eq!(instrs[4].to_string(), "memory.grow 1");
+        assert_eq!(instrs[6].to_string(), "memory.size 0");
+        let AwwasmOperands::I32Load(load) = &instrs[9].operands else { panic!("expected i32.load") };
+        assert_eq!((load.memidx, load.memory()), (None, 0));
+        assert!(module_parsed.features_used().multi_memory);
+        Ok(())
+    }
+
     #[test]
     fn decode_table_section_test() -> anyhow::Result<()> {
         let module = wat::parse_str(r#"
@@ -545,6 +1480,112 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn decode_table_section_externref_with_max_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (table 1 funcref)
+                (table 2 8 externref)
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let tables = module_parsed.tables.as_ref().expect("tables should exist");
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].elem_type, AwwasmTableReferenceType::Function);
+        assert_eq!(tables[1].elem_type, AwwasmTableReferenceType::Extern);
+        assert_eq!(tables[1].limits.flags, 1);
+        assert_eq!(tables[1].limits.min, 2);
+        assert_eq!(tables[1].limits.max, Some(8));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_element_section_func_indices_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (type $t (func))
+                (table 2 funcref)
+                (func $a)
+                (func $b (call_indirect (type $t) (i32.const 0)))
+                (elem (i32.const 0) $a $b)
+                (elem func $b)
+                (elem declare func $a)
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let elements = module_parsed.elements.as_ref().expect("elements should exist");
+        assert_eq!(elements.len(), 3);
+        match &elements[0].body {
+            AwwasmElemSegmentBody::ActiveImplicit(seg) => {
+                assert_eq!(seg.offset.code, &[0x41, 0x00]);
+                assert_eq!(seg.func_indices, vec![0, 1]);
+            }
+            other => panic!("unexpected segment {:?}", other),
+        }
+        match &elements[1].body {
+            AwwasmElemSegmentBody::Passive(seg) => assert_eq!(seg.func_indices, vec![1]),
+            other => panic!("unexpected segment {:?}", other),
+        }
+        match &elements[2].body {
+            AwwasmElemSegmentBody::Declarative(seg) => assert_eq!(seg.func_indices, vec![0]),
+            other => panic!("unexpected segment {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn decode_element_section_expressions_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (table 2 funcref)
+                (table $ext 1 externref)
+                (func $a)
+                (elem (i32.const 0) funcref (ref.func $a) (ref.null func))
+                (elem funcref (ref.null func))
+                (elem (table $ext) (i32.const 0) externref (ref.null extern))
+                (elem declare funcref (ref.func $a))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let elements = module_parsed.elements.as_ref().expect("elements should exist");
+        assert_eq!(elements.len(), 4);
+        assert_eq!(elements.iter().map(|e| e.flags).collect::<Vec<_>>(), vec![4, 5, 6, 7]);
+        match &elements[0].body {
+            AwwasmElemSegmentBody::ActiveImplicitExprs(seg) => {
+                assert_eq!(seg.exprs.len(), 2);
+                assert_eq!(seg.exprs[0].code, &[0xD2, 0x00]);
+                assert_eq!(seg.exprs[1].code, &[0xD0, 0x70]);
+            }
+            other => panic!("unexpected segment {:?}", other),
+        }
+        match &elements[1].body {
+            AwwasmElemSegmentBody::PassiveExprs(seg) => {
+                assert_eq!(seg.reftype, AwwasmTableReferenceType::Function);
+                assert_eq!(seg.expr_count, 1);
+            }
+            other => panic!("unexpected segment {:?}", other),
+        }
+        match &elements[2].body {
+            AwwasmElemSegmentBody::ActiveExplicitExprs(seg) => {
+                assert_eq!(seg.tableidx, 1);
+                assert_eq!(seg.reftype, AwwasmTableReferenceType::Extern);
+                assert_eq!(seg.exprs[0].code, &[0xD0, 0x6F]);
+            }
+            other => panic!("unexpected segment {:?}", other),
+        }
+        match &elements[3].body {
+            AwwasmElemSegmentBody::DeclarativeExprs(seg) => assert_eq!(seg.exprs[0].code, &[0xD2, 0x00]),
+            other => panic!("unexpected segment {:?}", other),
+        }
+        Ok(())
+    }
+
     #[test]
     fn decode_start_section_test() -> anyhow::Result<()> {
         let module = wat::parse_str(r#"
@@ -560,12 +1601,105 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn decode_start_section_imported_and_absent_test() -> anyhow::Result<()> {
+        // The start index lives in the function index space, imports first.
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "init" (func))
+                (func)
+                (start 1)
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.start, Some(AwwasmStartSectionItem { func_idx: 1 }));
+
+        let module = wat::parse_str("(module (func))")?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.start, None);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_data_count_section_test() -> anyhow::Result<()> {
+        // Two passive data segments preceded by a DataCount section.
+        let module: Vec<u8> = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[0x0c, 0x01, 0x02],
+            &[0x0b, 0x07, 0x02, 0x01, 0x01, b'a', 0x01, 0x01, b'b'],
+        ].concat();
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        let sections = module_parsed.sections.as_ref().expect("sections should exist");
+        assert_eq!(sections[0].section_header.section_type, SectionCode::DataCount);
+        assert_eq!(sections[0].entry_count, 2);
+
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.data_count, Some(2));
+        let data = module_parsed.data.as_ref().expect("data should exist");
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0].header.flags, 0x01);
+        assert_eq!(data[1].data_bytes, b"b");
+        Ok(())
+    }
+
+    #[test]
+    fn decode_custom_sections_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (@custom "first" (before first) "abc")
+                (func)
+                (@custom "second" (after func) "")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let customs = module_parsed.customs.as_ref().expect("customs should exist");
+        assert_eq!(customs.len(), 2);
+        assert_eq!(&customs[0].name.bytes[..], b"first");
+        assert_eq!(customs[0].payload, b"abc");
+        assert_eq!(&customs[1].name.bytes[..], b"second");
+        assert!(customs[1].payload.is_empty());
+        // The function still decodes around the custom sections.
+        assert_eq!(module_parsed.funcs.as_ref().map(|f| f.len()), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_truncated_section_test() -> anyhow::Result<()> {
+        // A complete type section, then a code section declaring 16 bytes with 3 present.
+        let module: Vec<u8> = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[0x01, 0x04, 0x01, 0x60, 0x00, 0x00],
+            &[0x0a, 0x10, 0x01, 0x02, 0x00],
+        ].concat();
+
+        let err = AwwasmModule::new(&module).unwrap_err();
+        let truncated = TruncatedSection { section: SectionCode::Code, declared: 16, available: 3 };
+        let kind = err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind);
+        assert_eq!(kind, Some(&AwwasmParseErrorKind::TruncatedSection(truncated.clone())));
+        assert_eq!(err.to_string(), "Failed to parse WASM Code Section (Code section): Code section declares 16 bytes but only 3 are available");
+
+        let mut module_parsed = AwwasmModule::new_permissive(&module)?;
+        let partial = module_parsed.truncated.clone().expect("truncated section should be kept");
+        assert_eq!(partial.error, truncated);
+        assert_eq!(partial.partial_body, &[0x01, 0x02, 0x00]);
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.types.as_ref().map(|t| t.len()), Some(1));
+        assert!(module_parsed.code.is_none());
+        Ok(())
+    }
+
     #[test]
     fn decode_streaming_incomplete_test() -> anyhow::Result<()> {
         let module_bytes = wat::parse_str(r#"
             (module (memory 1))
         "#)?;
 
+
+
         let mut parser = crate::components::module::AwwasmStreamingParser::new();
         
         // Pass only the first 4 bytes (incomplete preamble)
@@ -591,4 +1725,78 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn resolve_section_test() -> anyhow::Result<()> {
+        let module_bytes = wat::parse_str(r#"
+            (module
+                (import "env" "f" (func))
+                (memory 1)
+                (func (export "run") (call 0))
+                (data (i32.const 0) "hi")
+                (@custom "note" "x"))
+        "#)?;
+        // A code section that cannot be decoded is never touched.
+        let mut bytes = module_bytes.clone();
+        let code_body = bytes.windows(4).position(|w| w == [0x00, 0x10, 0x00, 0x0b]).expect("body should exist");
+        bytes[code_body - 1] = 0x7f;
+        let mut module = AwwasmModule::new(&bytes)?;
+
+        let exports = module.resolve_exports()?;
+        assert_eq!((exports.len(), exports[0].name.as_str()?), (1, "run"));
+        assert_eq!(module.resolve_imports()?.len(), 1);
+        assert_eq!(module.resolve_customs()?.len(), 1);
+        assert_eq!(module.resolve_customs()?.len(), 1);
+        assert!(module.resolve_types()?.len() == 1 && module.code.is_none() && module.data.is_none());
+        module.resolve_section(SectionCode::Data)?;
+        assert_eq!(module.data.as_ref().map(|d| d[0].data_bytes), Some(&b"hi"[..]));
+        let err = module.resolve_section(SectionCode::Code).unwrap_err();
+        assert_eq!(err.downcast_ref::<AwwasmParseError>().and_then(|e| e.section.clone()), Some(SectionCode::Code));
+
+        let mut module = AwwasmModule::new(&module_bytes)?;
+        module.resolve_section(SectionCode::Custom)?;
+        module.resolve_all_sections()?;
+        assert_eq!(module.customs.as_ref().map(Vec::len), Some(1));
+        assert_eq!(module.exports.as_ref().map(Vec::len), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn section_raw_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (func (export "run"))
+                (@custom "note" "a")
+                (@custom "note" "b"))
+        "#)?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        let export = module.section_raw(SectionCode::Export).expect("export section should exist");
+        assert_eq!(export, b"\x07\x07\x01\x03run\x00\x00");
+        assert_eq!(module.section_raw(SectionCode::Custom), Some(&b"\x00\x06\x04noteb"[..]));
+        assert!(module.section_raw(SectionCode::Data).is_none());
+
+        // The bytes are those of the input, before and after resolving.
+        assert_eq!(module.offset_of(export), Some(bytes.windows(export.len()).position(|w| w == export).unwrap_or_default()));
+        module.resolve_all_sections()?;
+        let sections = module.sections.as_ref().expect("sections should exist");
+        assert_eq!(sections[0].raw_bytes_with_header(), b"\x01\x04\x01\x60\x00\x00");
+        assert_eq!(module.section_raw(SectionCode::Export), Some(export));
+        let total: usize = sections.iter().map(|sec| sec.raw_bytes_with_header().len()).sum();
+        assert_eq!(total + 8, bytes.len());
+        Ok(())
+    }
+
+    #[test]
+    fn offset_of_test() -> Result<()> {
+        let module = wat::parse_str("(module (func))")?;
+        // The module followed by bytes that are not part of it.
+        let buffer = [&module[..], &[0xaa; 4]].concat();
+        let end = module.len();
+        let module_parsed = AwwasmModule::new(&buffer[..end])?;
+        assert_eq!(module_parsed.offset_of(&buffer[end - 2..end]), Some(end - 2));
+        assert_eq!(module_parsed.offset_of(&buffer[end - 2..end + 2]), None);
+        assert_eq!(module_parsed.offset_of(&buffer[end..]), None);
+        assert_eq!(AwwasmModule::default().offset_of(&buffer[..4]), None);
+        Ok(())
+    }
 }
@@ -0,0 +1,186 @@
+use crate::json;
+use crate::components::instructions::{marker_text, AwwasmInstruction};
+use crate::components::module::AwwasmModule;
+use crate::components::section::SectionCode;
+use crate::components::types::*;
+
+/// Version of the `to_json()` schema. Bumped when a field is removed or
+/// changes meaning; new fields may appear without a bump.
+pub const MODULE_JSON_SCHEMA: u32 = 2;
+
+fn name(n: &AwwasmName) -> String {
+    json::string(&String::from_utf8_lossy(&n.bytes))
+}
+
+fn val_types(types: &[ValType]) -> String {
+    let types: Vec<String> = types.iter().map(|t| json::string(&t.to_string())).collect();
+    format!("[{}]", types.join(","))
+}
+
+// Local declarations as `[count, type]` pairs; a body may declare billions
+// of locals in a few bytes, so they are not expanded.
+fn local_groups(locals: &[AwwasmFunctionLocals]) -> String {
+    let groups: Vec<String> = locals.iter()
+        .map(|l| format!("[{},{}]", l.type_count, json::string(&l.param_type.to_string())))
+        .collect();
+    format!("[{}]", groups.join(","))
+}
+
+fn kind(kind: &AwwasmExportKind) -> &'static str {
+    match kind {
+        AwwasmExportKind::Function => "function",
+        AwwasmExportKind::Table => "table",
+        AwwasmExportKind::Memory => "memory",
+        AwwasmExportKind::Global => "global",
+        AwwasmExportKind::Tag => "tag",
+    }
+}
+
+fn import_kind(kind: &AwwasmImportKind) -> &'static str {
+    match kind {
+        AwwasmImportKind::Function => "function",
+        AwwasmImportKind::Table => "table",
+        AwwasmImportKind::Memory => "memory",
+        AwwasmImportKind::Global => "global",
+        AwwasmImportKind::Tag => "tag",
+    }
+}
+
+impl AwwasmModule<'_> {
+    /// The module as a single JSON object, for tools outside Rust:
+    ///
+    /// ```text
+    /// {"schema":2,"version":1,
+    ///  "sections":[{"id":1,"kind":"Type","size":5,"entries":1}, ...],
+    ///  "types":[{"params":["i32"],"results":["i32"]}, ...],
+    ///  "imports":[{"module":"env","name":"f","kind":"function","type":0}, ...],
+    ///  "exports":[{"name":"run","kind":"function","index":1}, ...],
+    ///  "customs":[{"name":"name","size":12}, ...],
+    ///  "functions":[{"index":1,"type":0,"params":[],"results":[],"locals":[[1,"i32"]],
+    ///                "instructions":[{"offset":42,"depth":0,"text":"local.get 0"}, ...]}, ...]}
+    /// ```
+    ///
+    /// `schema` is `MODULE_JSON_SCHEMA`. Section `kind`s are the
+    /// `SectionCode` names and `entries` is `null` for sections without an
+    /// entry count. `type` is `null` for non-function imports. `functions`
+    /// lists defined functions by function index, imports included in the
+    /// index space; `type`, `params` and `results` are `null` if the type
+    /// index is out of range. `locals` lists the body's local declarations
+    /// as `[count, type]` pairs, as they appear in the binary. Each
+    /// instruction, and each `else`, `end` or other marker closing a nested
+    /// body, is listed in binary order with its nesting depth; `offset` is from the start of the module, or
+    /// `null` for a module not parsed from a binary. Names that are not
+    /// UTF-8 have invalid sequences replaced with U+FFFD.
+    ///
+    /// Requires `resolve_all_sections()`. Fails if a function body does not
+    /// decode.
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        let sections: Vec<String> = self.sections().iter().map(|sec| {
+            let header = &sec.section_header;
+            let counted = !matches!(header.section_type, SectionCode::Custom | SectionCode::Start | SectionCode::DataCount);
+            let entries = counted.then_some(sec.entry_count);
+            format!(
+                "{{\"id\":{},\"kind\":\"{:?}\",\"size\":{},\"entries\":{}}}",
+                header.section_type.clone() as u8, header.section_type, header.section_size, json::opt_number(entries),
+            )
+        }).collect();
+        let types: Vec<String> = self.types().iter().map(|t| format!(
+            "{{\"params\":{},\"results\":{}}}", val_types(&t.fn_args), val_types(&t.fn_rets),
+        )).collect();
+        let imports: Vec<String> = self.imports().iter().map(|i| format!(
+            "{{\"module\":{},\"name\":{},\"kind\":\"{}\",\"type\":{}}}",
+            name(&i.module), name(&i.name), import_kind(&i.kind), json::opt_number(i.func_type_idx),
+        )).collect();
+        let exports: Vec<String> = self.exports().iter().map(|e| format!(
+            "{{\"name\":{},\"kind\":\"{}\",\"index\":{}}}", name(&e.name), kind(&e.kind), e.index,
+        )).collect();
+        let customs: Vec<String> = self.customs().iter().map(|c| format!(
+            "{{\"name\":{},\"size\":{}}}", name(&c.name), c.payload.len(),
+        )).collect();
+        let imported = self.imported_func_count();
+        let mut functions = Vec::new();
+        for (i, item) in self.code().iter().enumerate() {
+            let index = imported + i as u32;
+            let type_idx = self.funcs().get(i).map(|f| f.type_item_idx);
+            let ty = self.signature_of(index);
+            let (locals, _) = item.locals_and_code()?;
+            let mut listing = Vec::new();
+            for instr in &item.instructions_with_limits(&self.limits)? {
+                self.list_instruction(instr, 0, &mut listing);
+            }
+            functions.push(format!(
+                "{{\"index\":{},\"type\":{},\"params\":{},\"results\":{},\"locals\":{},\"instructions\":[{}]}}",
+                index, json::opt_number(type_idx),
+                ty.map_or_else(|| String::from("null"), |t| val_types(&t.fn_args)),
+                ty.map_or_else(|| String::from("null"), |t| val_types(&t.fn_rets)),
+                local_groups(&locals), listing.join(","),
+            ));
+        }
+        Ok(format!(
+            "{{\"schema\":{},\"version\":{},\"sections\":[{}],\"types\":[{}],\"imports\":[{}],\"exports\":[{}],\"customs\":[{}],\"functions\":[{}]}}",
+            MODULE_JSON_SCHEMA, self.preamble.version, sections.join(","), types.join(","), imports.join(","),
+            exports.join(","), customs.join(","), functions.join(","),
+        ))
+    }
+
+    fn list_instruction(&self, instr: &AwwasmInstruction, depth: usize, out: &mut Vec<String>) {
+        let line = |bytes: &[u8], text: String| format!(
+            "{{\"offset\":{},\"depth\":{},\"text\":{}}}", json::opt_number(self.offset_of(bytes)), depth, json::string(&text),
+        );
+        out.push(line(instr.encoding, instr.to_string()));
+        for (instrs, marker) in instr.operands.bodies() {
+            for instr in instrs {
+                self.list_instruction(instr, depth + 1, out);
+            }
+            out.push(line(marker, marker_text(marker)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn module_to_json_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (import "env" "log" (func (param i32)))
+                (func (export "run") (param i32) (result i32) (local i64)
+                    (if (result i32) (local.get 0) (then (i32.const 1)) (else (i32.const 2)))))
+        "#)?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        module.resolve_all_sections()?;
+        assert_eq!(module.to_json()?, concat!(
+            r#"{"schema":2,"version":1,"#,
+            r#""sections":[{"id":1,"kind":"Type","size":10,"entries":2},{"id":2,"kind":"Import","size":11,"entries":1},"#,
+            r#"{"id":3,"kind":"Function","size":2,"entries":1},{"id":7,"kind":"Export","size":7,"entries":1},"#,
+            r#"{"id":10,"kind":"Code","size":16,"entries":1}],"#,
+            r#""types":[{"params":["i32"],"results":[]},{"params":["i32"],"results":["i32"]}],"#,
+            r#""imports":[{"module":"env","name":"log","kind":"function","type":0}],"#,
+            r#""exports":[{"name":"run","kind":"function","index":1}],"customs":[],"#,
+            r#""functions":[{"index":1,"type":1,"params":["i32"],"results":["i32"],"locals":[[1,"i64"]],"instructions":["#,
+            r#"{"offset":53,"depth":0,"text":"local.get 0"},{"offset":55,"depth":0,"text":"if i32"},"#,
+            r#"{"offset":57,"depth":1,"text":"i32.const 1"},{"offset":59,"depth":0,"text":"else"},"#,
+            r#"{"offset":60,"depth":1,"text":"i32.const 2"},{"offset":62,"depth":0,"text":"end"},"#,
+            r#"{"offset":63,"depth":0,"text":"end"}]}]}"#,
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn module_to_json_huge_locals_test() -> anyhow::Result<()> {
+        // One body declaring 0xffffffff i32 locals and 2 i64 locals in a few bytes.
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x00,
+            0x0a, 0x0c, 0x01, 0x0a, 0x02, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x7f, 0x02, 0x7e, 0x0b,
+        ];
+        let mut module = AwwasmModule::new(&bytes)?;
+        module.resolve_all_sections()?;
+        let json = module.to_json()?;
+        assert!(json.contains(r#""locals":[[4294967295,"i32"],[2,"i64"]]"#), "{}", json);
+        Ok(())
+    }
+}
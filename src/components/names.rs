@@ -0,0 +1,306 @@
+use crate::components::error::AwwasmParseError;
+use crate::components::module::AwwasmModule;
+use crate::components::types::AwwasmName;
+use nom_derive::*;
+use crate::components::leb128::leb128_u32;
+use nom::multi::length_count;
+use std::borrow::Cow;
+
+pub(crate) const NAME_SECTION: &[u8] = b"name";
+
+const MODULE_NAME_SUBSECTION: u8 = 0;
+const FUNCTION_NAMES_SUBSECTION: u8 = 1;
+const LOCAL_NAMES_SUBSECTION: u8 = 2;
+const LABEL_NAMES_SUBSECTION: u8 = 3;
+const TYPE_NAMES_SUBSECTION: u8 = 4;
+const TABLE_NAMES_SUBSECTION: u8 = 5;
+const MEMORY_NAMES_SUBSECTION: u8 = 6;
+const GLOBAL_NAMES_SUBSECTION: u8 = 7;
+const ELEM_NAMES_SUBSECTION: u8 = 8;
+const DATA_NAMES_SUBSECTION: u8 = 9;
+
+/// One entry of a name map: an index and the name given to it.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmNameAssoc<'a> {
+    #[nom(Parse = "leb128_u32")]
+    pub index: u32,
+    pub name: AwwasmName<'a>,
+}
+
+/// One entry of an indirect name map: names for the items nested under `index`
+/// (e.g. the locals of one function).
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmIndirectNameAssoc<'a> {
+    #[nom(Parse = "leb128_u32")]
+    pub index: u32,
+    #[nom(LengthCount = "leb128_u32")]
+    pub names: Vec<AwwasmNameAssoc<'a>>,
+}
+
+/// A `[id: u8][size: leb128][body]` subsection, the framing shared by the
+/// `name`, `dylink.0` and `linking` custom sections.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub(crate) struct AwwasmSubsection<'a> {
+    pub(crate) id: u8,
+    #[nom(Parse = "leb128_u32")]
+    pub(crate) size: u32,
+    #[nom(Take = "size")]
+    pub(crate) body: &'a [u8],
+}
+
+/// Decoded contents of the `name` custom section, including the extended
+/// name subsections (labels through data segments).
+///
+/// Unknown subsections are skipped.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmNameSection<'a> {
+    pub module: Option<AwwasmName<'a>>,
+    pub functions: Vec<AwwasmNameAssoc<'a>>,
+    pub locals: Vec<AwwasmIndirectNameAssoc<'a>>,
+    /// Block labels, grouped by function; label indices count block-like
+    /// instructions in order of appearance.
+    pub labels: Vec<AwwasmIndirectNameAssoc<'a>>,
+    pub types: Vec<AwwasmNameAssoc<'a>>,
+    pub tables: Vec<AwwasmNameAssoc<'a>>,
+    pub memories: Vec<AwwasmNameAssoc<'a>>,
+    pub globals: Vec<AwwasmNameAssoc<'a>>,
+    pub elem_segments: Vec<AwwasmNameAssoc<'a>>,
+    pub data_segments: Vec<AwwasmNameAssoc<'a>>,
+}
+
+fn name_map<'a>(body: &'a [u8], what: &str) -> anyhow::Result<Vec<AwwasmNameAssoc<'a>>> {
+    let (_, map) = length_count(leb128_u32, AwwasmNameAssoc::parse)(body)
+        .map_err(|e| AwwasmParseError::nom(format!("{} Names", what), e))?;
+    Ok(map)
+}
+
+fn indirect_name_map<'a>(body: &'a [u8], what: &str) -> anyhow::Result<Vec<AwwasmIndirectNameAssoc<'a>>> {
+    let (_, map) = length_count(leb128_u32, AwwasmIndirectNameAssoc::parse)(body)
+        .map_err(|e| AwwasmParseError::nom(format!("{} Names", what), e))?;
+    Ok(map)
+}
+
+impl<'a> AwwasmNameSection<'a> {
+    /// Decode the payload of a `name` custom section.
+    pub fn parse(payload: &'a [u8]) -> anyhow::Result<Self> {
+        let mut names = Self::default();
+        let mut rest = payload;
+        while !rest.is_empty() {
+            let (next, sub) = AwwasmSubsection::parse(rest)
+                .map_err(|e| AwwasmParseError::nom("Name Subsection", e))?;
+            let body = sub.body;
+            match sub.id {
+                MODULE_NAME_SUBSECTION => {
+                    let (_, name) = AwwasmName::parse(body)
+                        .map_err(|e| AwwasmParseError::nom("Module Name", e))?;
+                    names.module = Some(name);
+                }
+                FUNCTION_NAMES_SUBSECTION => names.functions = name_map(body, "Function")?,
+                LOCAL_NAMES_SUBSECTION    => names.locals = indirect_name_map(body, "Local")?,
+                LABEL_NAMES_SUBSECTION    => names.labels = indirect_name_map(body, "Label")?,
+                TYPE_NAMES_SUBSECTION     => names.types = name_map(body, "Type")?,
+                TABLE_NAMES_SUBSECTION    => names.tables = name_map(body, "Table")?,
+                MEMORY_NAMES_SUBSECTION   => names.memories = name_map(body, "Memory")?,
+                GLOBAL_NAMES_SUBSECTION   => names.globals = name_map(body, "Global")?,
+                ELEM_NAMES_SUBSECTION     => names.elem_segments = name_map(body, "Element Segment")?,
+                DATA_NAMES_SUBSECTION     => names.data_segments = name_map(body, "Data Segment")?,
+                _ => { /* skip */ }
+            }
+            rest = next;
+        }
+        Ok(names)
+    }
+
+    /// Name of the function at `index` in the function index space, if any.
+    pub fn function_name(&self, index: u32) -> Option<&str> {
+        self.functions.iter()
+            .find(|n| n.index == index)
+            .and_then(|n| n.name.to_str())
+    }
+
+    /// Name of local `local` of function `func`, if any.
+    pub fn local_name(&self, func: u32, local: u32) -> Option<&str> {
+        self.locals.iter()
+            .find(|f| f.index == func)?
+            .names.iter()
+            .find(|n| n.index == local)
+            .and_then(|n| n.name.to_str())
+    }
+
+    /// Every name in the section, in subsection order.
+    pub fn iter(&self) -> impl Iterator<Item = &AwwasmName<'a>> + '_ {
+        fn direct<'s, 'a>(map: &'s [AwwasmNameAssoc<'a>]) -> impl Iterator<Item = &'s AwwasmName<'a>> {
+            map.iter().map(|n| &n.name)
+        }
+        fn indirect<'s, 'a>(map: &'s [AwwasmIndirectNameAssoc<'a>]) -> impl Iterator<Item = &'s AwwasmName<'a>> {
+            map.iter().flat_map(|f| direct(&f.names))
+        }
+        self.module.iter()
+            .chain(direct(&self.functions))
+            .chain(indirect(&self.locals))
+            .chain(indirect(&self.labels))
+            .chain(direct(&self.types))
+            .chain(direct(&self.tables))
+            .chain(direct(&self.memories))
+            .chain(direct(&self.globals))
+            .chain(direct(&self.elem_segments))
+            .chain(direct(&self.data_segments))
+    }
+}
+
+/// Where a name returned by `AwwasmModule::all_names()` was defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NameOrigin {
+    /// An import's module or field name.
+    Import,
+    /// An export name.
+    Export,
+    /// A name from the `name` custom section.
+    NameSection,
+    /// The name of a custom section.
+    Custom,
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Decode the `name` custom section, if the module has one.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn names(&self) -> anyhow::Result<Option<AwwasmNameSection<'a>>> {
+        self.custom_section(NAME_SECTION)
+            .map(|c| AwwasmNameSection::parse(c.payload))
+            .transpose()
+    }
+
+    /// Every name defined anywhere in the module, tagged with where it came from.
+    ///
+    /// Names are yielded in a fixed order — imports, exports, the name section,
+    /// then custom section names — each in binary order. Names that are not
+    /// valid UTF-8 are skipped. Requires `resolve_all_sections()`.
+    pub fn all_names(&self) -> anyhow::Result<impl Iterator<Item = (NameOrigin, Cow<'_, str>)> + '_> {
+        let name_section: Vec<String> = self.names()?.iter()
+            .flat_map(|n| n.iter())
+            .filter_map(|n| n.to_str().map(str::to_owned))
+            .collect();
+
+        let imports = self.imports().iter()
+            .flat_map(|i| [&i.module, &i.name])
+            .filter_map(|n| n.to_str())
+            .map(|n| (NameOrigin::Import, Cow::Borrowed(n)));
+        let exports = self.exports().iter()
+            .filter_map(|e| e.name.to_str())
+            .map(|n| (NameOrigin::Export, Cow::Borrowed(n)));
+        let customs = self.customs().iter()
+            .filter_map(|c| c.name.to_str())
+            .map(|n| (NameOrigin::Custom, Cow::Borrowed(n)));
+
+        Ok(imports
+            .chain(exports)
+            .chain(name_section.into_iter().map(|n| (NameOrigin::NameSection, Cow::Owned(n))))
+            .chain(customs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+    use crate::components::names::{AwwasmNameAssoc, NameOrigin};
+    use std::borrow::Cow;
+
+    #[test]
+    fn decode_name_section_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module $demo
+                (import "env" "log" (func $log (param i32)))
+                (func $add (param $lhs i32) (param $rhs i32) (result i32)
+                    (local $tmp i32)
+                    local.get $lhs
+                    local.get $rhs
+                    i32.add)
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let names = module_parsed.names()?.expect("name section should exist");
+        assert_eq!(names.module.as_ref().and_then(|n| n.to_str()), Some("demo"));
+        assert_eq!(names.function_name(0), Some("log"));
+        assert_eq!(names.function_name(1), Some("add"));
+        assert_eq!(names.function_name(2), None);
+        assert_eq!(names.local_name(1, 0), Some("lhs"));
+        assert_eq!(names.local_name(1, 1), Some("rhs"));
+        assert_eq!(names.local_name(1, 2), Some("tmp"));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_extended_name_subsections_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (type $sig (func))
+                (table $tbl 1 funcref)
+                (memory $mem 1)
+                (global $counter (mut i32) (i32.const 0))
+                (func $f (type $sig)
+                    (block $outer (loop $inner)))
+                (elem $handlers (i32.const 0) func $f)
+                (data $blob (i32.const 0) "x")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let names = module_parsed.names()?.expect("name section should exist");
+        let first = |map: &[AwwasmNameAssoc]| map.first().and_then(|n| n.name.to_str()).map(str::to_owned);
+        assert_eq!(first(&names.types).as_deref(), Some("sig"));
+        assert_eq!(first(&names.tables).as_deref(), Some("tbl"));
+        assert_eq!(first(&names.memories).as_deref(), Some("mem"));
+        assert_eq!(first(&names.globals).as_deref(), Some("counter"));
+        assert_eq!(first(&names.elem_segments).as_deref(), Some("handlers"));
+        assert_eq!(first(&names.data_segments).as_deref(), Some("blob"));
+
+        assert_eq!(names.labels.len(), 1);
+        assert_eq!(names.labels[0].index, 0);
+        let labels: Vec<(u32, &str)> = names.labels[0].names.iter()
+            .map(|n| (n.index, n.name.to_str().unwrap_or_default()))
+            .collect();
+        assert_eq!(labels, vec![(0, "outer"), (1, "inner")]);
+        Ok(())
+    }
+
+    #[test]
+    fn all_names_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "log" (func $log))
+                (func $main (export "run"))
+                (@custom "meta" "")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let names: Vec<(NameOrigin, Cow<str>)> = module_parsed.all_names()?.collect();
+        assert_eq!(names, vec![
+            (NameOrigin::Import, "env".into()),
+            (NameOrigin::Import, "log".into()),
+            (NameOrigin::Export, "run".into()),
+            (NameOrigin::NameSection, "log".into()),
+            (NameOrigin::NameSection, "main".into()),
+            (NameOrigin::Custom, "name".into()),
+            (NameOrigin::Custom, "meta".into()),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_without_name_section_test() -> anyhow::Result<()> {
+        let module = wat::parse_str("(module (func))")?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        assert!(module_parsed.names()?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use crate::components::features::AwwasmParseOptions;
+use crate::components::module::AwwasmModule;
+use std::sync::Arc;
+
+/// A resolved module that owns its binary, so it can be returned from the
+/// function that read the bytes, stored, or sent to another thread.
+///
+/// The binary is copied (or moved) in once; names, function bodies and data
+/// bytes borrow from that copy rather than being copied one by one. Clones
+/// share it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmModuleOwned {
+    // Borrows from `bytes`. Only ever handed out with its lifetime shortened
+    // to a borrow of `self`, so nothing from it outlives `bytes`.
+    module: AwwasmModule<'static>,
+    bytes: Arc<[u8]>,
+}
+
+impl AwwasmModuleOwned {
+    /// Parse `bytes` and resolve every section. Takes a `Vec<u8>` without
+    /// copying, or copies a `&[u8]`.
+    pub fn new(bytes: impl Into<Arc<[u8]>>) -> anyhow::Result<Self> {
+        Self::parse(bytes.into(), |input| {
+            let mut module = AwwasmModule::new(input)?;
+            module.resolve_all_sections()?;
+            Ok(module)
+        })
+    }
+
+    /// Like `new`, but parsed with `AwwasmModule::new_with_options()`.
+    pub fn new_with_options(bytes: impl Into<Arc<[u8]>>, options: &AwwasmParseOptions) -> anyhow::Result<Self> {
+        Self::parse(bytes.into(), |input| AwwasmModule::new_with_options(input, options))
+    }
+
+    fn parse(bytes: Arc<[u8]>, parse: impl FnOnce(&'static [u8]) -> anyhow::Result<AwwasmModule<'static>>) -> anyhow::Result<Self> {
+        // SAFETY: the slice lives in the `Arc` allocation, which is never
+        // mutated and is freed only after the last clone of `bytes` is
+        // dropped; every `AwwasmModuleOwned` holding `module` holds one.
+        let input: &'static [u8] = unsafe { &*Arc::as_ptr(&bytes) };
+        let module = parse(input)?;
+        Ok(AwwasmModuleOwned { module, bytes })
+    }
+
+    /// The resolved module.
+    pub fn module(&self) -> &AwwasmModule<'_> {
+        &self.module
+    }
+
+    /// The module binary.
+    pub fn bytes(&self) -> &Arc<[u8]> {
+        &self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::owned::AwwasmModuleOwned;
+
+    fn load(wat: &str) -> anyhow::Result<AwwasmModuleOwned> {
+        let bytes = wat::parse_str(wat)?;
+        AwwasmModuleOwned::new(bytes)
+    }
+
+    #[test]
+    fn owned_module_test() -> anyhow::Result<()> {
+        let owned = load(r#"(module (memory 1) (func (export "run")) (data (i32.const 0) "hi"))"#)?;
+        let copy = owned.clone();
+        drop(owned);
+
+        let names = std::thread::spawn(move || {
+            let module = copy.module();
+            let export = &module.exports.as_ref().expect("exports should exist")[0];
+            (export.name.bytes.to_vec(), module.data.as_ref().expect("data should exist")[0].data_bytes.to_vec())
+        }).join().expect("thread should not panic");
+        assert_eq!(names, (b"run".to_vec(), b"hi".to_vec()));
+
+        assert!(AwwasmModuleOwned::new(&b"\0asm\x02\0\0\0"[..]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+use crate::components::instructions::DEFAULT_MAX_NESTING_DEPTH;
+use crate::components::module::AwwasmModule;
+use crate::components::section::{AwwasmSection, SectionCode};
+use crate::components::types::*;
+use crate::limits::{MAX_WASM_FUNCTIONS, MAX_WASM_FUNCTION_LOCALS, MAX_WASM_FUNCTION_SIZE, MAX_WASM_MODULE_SIZE};
+use nom_derive::Parse;
+use std::mem::size_of;
+
+/// Caps on the work `AwwasmModule::new_with_options()` does for a module,
+/// for services parsing untrusted uploads. The caps on sections and entries
+/// are checked before the sections are decoded, so a module over one fails
+/// without allocating for its items; the module keeps the caps in
+/// `AwwasmModule::limits`, so sections resolved later and function bodies
+/// decoded by its analyses are held to them too.
+///
+/// The default sets no caps but the nesting depth; `untrusted()` sets the
+/// engine limits in `crate::limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmParseLimits {
+    /// Sections in the module, custom sections included.
+    pub max_sections: usize,
+    /// Entries declared by any one section.
+    pub max_section_entries: u32,
+    /// Size in bytes of any one function body.
+    pub max_function_body_size: u32,
+    /// Bytes the decoded section items may take, estimated from each
+    /// section's entry count and item size. Lists within items, such as
+    /// parameter types, are bounded by the section size and not counted.
+    pub max_allocation: usize,
+    /// Depth of `block`, `loop`, `if`, `try` and `try_table` bodies nested
+    /// within one another in a function body. Cloning, comparing and
+    /// printing the decoded bodies recurse into them, so unlike the other
+    /// caps this one is set by default, to `DEFAULT_MAX_NESTING_DEPTH`;
+    /// raising it needs a larger stack.
+    pub max_nesting_depth: u32,
+    /// Locals any one function body declares, parameters excluded.
+    pub max_function_locals: u32,
+    /// Instructions in any one function body, those in nested bodies
+    /// included.
+    pub max_function_instructions: u32,
+}
+
+impl Default for AwwasmParseLimits {
+    fn default() -> Self {
+        AwwasmParseLimits {
+            max_sections: usize::MAX,
+            max_section_entries: u32::MAX,
+            max_function_body_size: u32::MAX,
+            max_allocation: usize::MAX,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            max_function_locals: u32::MAX,
+            max_function_instructions: u32::MAX,
+        }
+    }
+}
+
+impl AwwasmParseLimits {
+    /// Caps that every module other engines accept fits within.
+    pub fn untrusted() -> Self {
+        AwwasmParseLimits {
+            max_sections: 1024,
+            max_section_entries: MAX_WASM_FUNCTIONS as u32,
+            max_function_body_size: MAX_WASM_FUNCTION_SIZE as u32,
+            max_allocation: MAX_WASM_MODULE_SIZE,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            max_function_locals: MAX_WASM_FUNCTION_LOCALS as u32,
+            max_function_instructions: MAX_WASM_FUNCTION_SIZE as u32,
+        }
+    }
+
+    /// Check the parsed, unresolved `module` against the caps.
+    pub(crate) fn check(&self, module: &AwwasmModule) -> Result<(), AwwasmParseError> {
+        let sections = module.sections();
+        exceeds("sections", None, sections.len() as u64, self.max_sections as u64)?;
+        let mut allocation = sections.len() as u64 * size_of::<AwwasmSection>() as u64;
+        for sec in sections {
+            self.check_section(sec)?;
+            let section = &sec.section_header.section_type;
+            let Some(item_size) = item_size(section) else { continue };
+            allocation += sec.entry_count as u64 * item_size as u64;
+            exceeds("allocation", Some(section), allocation, self.max_allocation as u64)?;
+        }
+        Ok(())
+    }
+
+    /// Check one unresolved section against the caps on its entries and,
+    /// for the code section, its function bodies.
+    pub(crate) fn check_section(&self, sec: &AwwasmSection) -> Result<(), AwwasmParseError> {
+        let section = &sec.section_header.section_type;
+        if item_size(section).is_none() {
+            return Ok(());
+        }
+        exceeds("section entries", Some(section), sec.entry_count as u64, self.max_section_entries as u64)?;
+        if *section == SectionCode::Code && (self.max_function_body_size != u32::MAX || self.max_function_locals != u32::MAX) {
+            bodies(sec.section_body, sec.entry_count).try_for_each(|body| self.check_body(&body))?;
+        }
+        Ok(())
+    }
+
+    /// Check the size and local declarations of one function body; local
+    /// declarations that cannot be decoded are left for the caller to report.
+    pub(crate) fn check_body(&self, body: &AwwasmCodeSectionItem) -> Result<(), AwwasmParseError> {
+        let code = Some(&SectionCode::Code);
+        exceeds("function body size", code, body.fn_body_size as u64, self.max_function_body_size as u64)?;
+        if self.max_function_locals != u32::MAX {
+            let Ok((locals, _)) = body.locals_and_code() else { return Ok(()) };
+            let declared = locals.iter().map(|l| l.type_count as u64).sum();
+            exceeds("function locals", code, declared, self.max_function_locals as u64)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn exceeds(limit: &'static str, section: Option<&SectionCode>, actual: u64, max: u64) -> Result<(), AwwasmParseError> {
+    if actual <= max {
+        return Ok(());
+    }
+    Err(AwwasmParseError::module(AwwasmParseErrorKind::LimitExceeded { limit, section: section.cloned(), actual, max }))
+}
+
+// Size of one decoded entry of a section with entries.
+fn item_size(section: &SectionCode) -> Option<usize> {
+    Some(match section {
+        SectionCode::Type => size_of::<AwwasmTypeSectionItem>(),
+        SectionCode::Import => size_of::<AwwasmImportSectionItem>(),
+        SectionCode::Function => size_of::<AwwasmFuncSectionItem>(),
+        SectionCode::Table => size_of::<AwwasmTableSectionItem>(),
+        SectionCode::Memory => size_of::<AwwasmMemorySectionItem>(),
+        SectionCode::Global => size_of::<AwwasmGlobalSectionItem>(),
+        SectionCode::Export => size_of::<AwwasmExportSectionItem>(),
+        SectionCode::Element => size_of::<AwwasmElementSectionItem>(),
+        SectionCode::Code => size_of::<AwwasmCodeSectionItem>(),
+        SectionCode::Data => size_of::<AwwasmDataSectionItem>(),
+        SectionCode::Tag => size_of::<AwwasmTagSectionItem>(),
+        SectionCode::Custom | SectionCode::Start | SectionCode::DataCount => return None,
+    })
+}
+
+// Each body in a code section, as far as the bodies go; malformed bodies
+// are left for `resolve()` to report.
+fn bodies<'a>(mut body: &'a [u8], entries: u32) -> impl Iterator<Item = AwwasmCodeSectionItem<'a>> {
+    (0..entries).map_while(move |_| {
+        let (rest, item) = AwwasmCodeSectionItem::parse(body).ok()?;
+        body = rest;
+        Some(item)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+    use crate::components::features::AwwasmParseOptions;
+    use crate::components::module::AwwasmModule;
+    use crate::components::parse_limits::AwwasmParseLimits;
+    use crate::components::section::SectionCode;
+
+    #[test]
+    fn parse_limits_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (func (result i32) (i32.const 1))
+                (func (result i32) (i32.add (i32.const 1) (i32.const 2)))
+                (@custom "note" "x"))
+        "#)?;
+        let limited = |limits| AwwasmModule::new_with_options(&module, &AwwasmParseOptions { limits, ..Default::default() });
+        assert!(limited(AwwasmParseLimits::default()).is_ok());
+        assert!(limited(AwwasmParseLimits::untrusted()).is_ok());
+
+        let kind = |limits| limited(limits).unwrap_err().downcast::<AwwasmParseError>().expect("should be an AwwasmParseError").kind;
+        assert_eq!(kind(AwwasmParseLimits { max_sections: 3, ..Default::default() }), AwwasmParseErrorKind::LimitExceeded { limit: "sections", section: None, actual: 4, max: 3 });
+        assert_eq!(
+            kind(AwwasmParseLimits { max_section_entries: 1, ..Default::default() }),
+            AwwasmParseErrorKind::LimitExceeded { limit: "section entries", section: Some(SectionCode::Function), actual: 2, max: 1 },
+        );
+
+        let err = limited(AwwasmParseLimits { max_function_body_size: 6, ..Default::default() }).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to parse WASM module: module exceeds the function body size limit in the Code section: 7 > 6");
+
+        let kind = kind(AwwasmParseLimits { max_allocation: 64, ..Default::default() });
+        assert!(matches!(kind, AwwasmParseErrorKind::LimitExceeded { limit: "allocation", .. }), "{:?}", kind);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_limits_entry_points_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (func (result i32) (i32.const 1))
+                (func (result i32) (local i32 i64 i64) (i32.add (i32.const 1) (i32.const 2))))
+        "#)?;
+        let options = |limits| AwwasmParseOptions { limits, ..Default::default() };
+        let kind = |err: anyhow::Error| err.downcast::<AwwasmParseError>().expect("should be an AwwasmParseError").kind;
+        let exceeded = |limit, section, actual, max| AwwasmParseErrorKind::LimitExceeded { limit, section, actual, max };
+
+        let err = AwwasmModule::new_with_options(&module, &options(AwwasmParseLimits { max_function_locals: 2, ..Default::default() })).unwrap_err();
+        assert_eq!(kind(err), exceeded("function locals", Some(SectionCode::Code), 3, 2));
+
+        // Bodies decoded after parsing are held to the limits the module was parsed with.
+        let parsed = AwwasmModule::new_with_options(&module, &options(AwwasmParseLimits { max_function_instructions: 2, ..Default::default() }))?;
+        let code = parsed.code();
+        assert!(code[0].instructions_with_limits(&parsed.limits).is_ok());
+        assert_eq!(kind(code[1].instructions_with_limits(&parsed.limits).unwrap_err()), exceeded("function instructions", None, 3, 2));
+        let mut functions = parsed.functions_lazy()?;
+        assert!(functions.next().expect("first function")?.2.all(|instr| instr.is_ok()));
+        assert!(functions.next().expect("second function")?.2.any(|instr| instr.is_err()));
+        assert!(parsed.instruction_index().is_err());
+
+        // So are sections resolved one at a time.
+        let mut parsed = AwwasmModule::new(&module)?;
+        parsed.limits = AwwasmParseLimits { max_section_entries: 1, ..Default::default() };
+        assert_eq!(kind(parsed.resolve_section(SectionCode::Function).unwrap_err()), exceeded("section entries", Some(SectionCode::Function), 2, 1));
+
+        let err = AwwasmModule::from_reader_with_options(&module[..], &options(AwwasmParseLimits { max_sections: 2, ..Default::default() })).unwrap_err();
+        assert_eq!(kind(err), exceeded("sections", None, 3, 2));
+        Ok(())
+    }
+}
@@ -0,0 +1,1087 @@
+use crate::consts::WASM_MAGIC_NUMBER;
+use crate::components::branch_hints::BRANCH_HINT_SECTION;
+use crate::components::encoder::Encode;
+use crate::components::error::AwwasmParseError;
+use crate::components::instructions::{span_in, AwwasmOperands, BlockType, InstructionIterator, WasmOpCode};
+use crate::components::merge::{map, Renumber};
+use crate::components::module::{AwwasmModule, AwwasmModulePreamble};
+use crate::components::names::NAME_SECTION;
+use crate::components::section::{write_leb128_s33, write_leb128_u32, AwwasmSectionHeader, SectionCode, TruncatedSection};
+use crate::components::types::*;
+use anyhow::Context;
+use nom::combinator::complete;
+use nom::multi::length_data;
+use nom_derive::Parse;
+use crate::components::leb128::leb128_u32;
+use std::collections::{BTreeMap, HashMap};
+
+/// An index space a pass can renumber.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AwwasmIndexSpace {
+    Type,
+    Func,
+    Table,
+    Memory,
+    Global,
+}
+
+/// Where the entries of each index space moved over a pipeline, from their
+/// index in the input module to their index in the output.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmIndexRemap {
+    spaces: BTreeMap<AwwasmIndexSpace, Vec<Option<u32>>>,
+}
+
+impl AwwasmIndexRemap {
+    /// Record that a pass moved entry `i` of `space` to `mapping[i]`, or
+    /// removed it if that is `None`. Composes with what earlier passes recorded.
+    pub fn record(&mut self, space: AwwasmIndexSpace, mapping: Vec<Option<u32>>) {
+        match self.spaces.get_mut(&space) {
+            Some(existing) => {
+                for slot in existing.iter_mut() {
+                    *slot = slot.and_then(|i| mapping.get(i as usize).copied().flatten());
+                }
+            }
+            None => {
+                self.spaces.insert(space, mapping);
+            }
+        }
+    }
+
+    /// Where index `idx` of the input module ended up; `None` if it was
+    /// removed. Spaces no pass renumbered map every index to itself.
+    pub fn get(&self, space: AwwasmIndexSpace, idx: u32) -> Option<u32> {
+        match self.spaces.get(&space) {
+            Some(mapping) => mapping.get(idx as usize).copied().flatten(),
+            None => Some(idx),
+        }
+    }
+
+    /// Whether any pass renumbered `space`.
+    pub fn is_remapped(&self, space: AwwasmIndexSpace) -> bool {
+        self.spaces.contains_key(&space)
+    }
+}
+
+/// A section owned by the pipeline: its id and everything after the section
+/// header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmOwnedSection {
+    pub id: SectionCode,
+    pub payload: Vec<u8>,
+}
+
+impl AwwasmOwnedSection {
+    /// The name of a custom section; `None` for other sections.
+    pub fn custom_name(&self) -> Option<&[u8]> {
+        if self.id != SectionCode::Custom {
+            return None;
+        }
+        length_data(leb128_u32::<()>)(&self.payload[..]).ok().map(|(_, name)| name)
+    }
+}
+
+// Call `f` with the id and payload of each section of a module binary, in
+// order, and return the module's version.
+pub(crate) fn split_sections<'a>(
+    bytes: &'a [u8],
+    mut f: impl FnMut(SectionCode, &'a [u8]) -> anyhow::Result<()>,
+) -> anyhow::Result<u32> {
+    let (mut input, preamble) = AwwasmModulePreamble::parse(bytes)
+        .map_err(|e| AwwasmParseError::nom("module preamble", e))?;
+    preamble.check_core_module()?;
+    while !input.is_empty() {
+        let (body, header) = complete(AwwasmSectionHeader::parse)(input)
+            .map_err(|e| AwwasmParseError::section_header("section header", e))?;
+        let size = header.section_size as usize;
+        if size > body.len() {
+            return Err(AwwasmParseError::from(TruncatedSection { section: header.section_type, declared: header.section_size, available: body.len() }).into());
+        }
+        f(header.section_type, &body[..size])?;
+        input = &body[size..];
+    }
+    Ok(preamble.version)
+}
+
+/// The module a pipeline works on, kept as owned sections so passes can
+/// replace them without re-encoding the rest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmPassModule {
+    pub version: u32,
+    pub sections: Vec<AwwasmOwnedSection>,
+}
+
+impl AwwasmPassModule {
+    /// Split a module binary into its sections.
+    pub fn new(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut sections = Vec::new();
+        let version = split_sections(bytes, |id, payload| {
+            sections.push(AwwasmOwnedSection { id, payload: payload.to_vec() });
+            Ok(())
+        })?;
+        Ok(Self { version, sections })
+    }
+
+    /// Encode the module back into a binary.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(WASM_MAGIC_NUMBER);
+        out.extend_from_slice(&self.version.to_le_bytes());
+        for section in &self.sections {
+            out.push(section.id.clone() as u8);
+            write_leb128_u32(&mut out, section.payload.len() as u32);
+            out.extend_from_slice(&section.payload);
+        }
+        out
+    }
+
+    /// Payload of the (non-custom) section `id`, if the module has one.
+    pub fn payload(&self, id: SectionCode) -> Option<&[u8]> {
+        self.sections.iter().find(|s| s.id == id).map(|s| &s.payload[..])
+    }
+
+    /// Replace the payload of the (non-custom) section `id`. The section must exist.
+    pub fn set_payload(&mut self, id: SectionCode, payload: Vec<u8>) -> anyhow::Result<()> {
+        let section = self.sections.iter_mut().find(|s| s.id == id)
+            .ok_or_else(|| anyhow::anyhow!("Module has no {:?} section", id))?;
+        section.payload = payload;
+        Ok(())
+    }
+
+    /// Append an entry, encoded as `entry`, to the (non-custom) section
+    /// `id`, adding the section in its place in the section order if the
+    /// module has none.
+    pub fn push_entry(&mut self, id: SectionCode, entry: &[u8]) -> anyhow::Result<()> {
+        let rank = id.order().ok_or_else(|| anyhow::anyhow!("Custom sections have no entries"))?;
+        let index = match self.sections.iter().position(|s| s.id == id) {
+            Some(index) => index,
+            None => {
+                let index = self.sections.iter().rposition(|s| s.id.order().is_some_and(|o| o < rank)).map_or(0, |i| i + 1);
+                self.sections.insert(index, AwwasmOwnedSection { id: id.clone(), payload: vec![0] });
+                index
+            }
+        };
+        let section = &mut self.sections[index];
+        let (rest, count) = leb128_u32::<nom::error::Error<&[u8]>>(&section.payload[..])
+            .map_err(|e| AwwasmParseError::nom("Section entry count", e))?;
+        let mut payload = Vec::with_capacity(section.payload.len() + entry.len() + 1);
+        write_leb128_u32(&mut payload, count + 1);
+        payload.extend_from_slice(rest);
+        payload.extend_from_slice(entry);
+        section.payload = payload;
+        Ok(())
+    }
+
+    // Replace the payload of the branch hint section, if the module has one.
+    fn set_hints(&mut self, payload: Option<Vec<u8>>) {
+        if let Some(hints) = self.sections.iter_mut().find(|s| s.custom_name() == Some(BRANCH_HINT_SECTION)) {
+            hints.payload = payload.unwrap_or_default();
+        }
+    }
+}
+
+/// One transform in a `AwwasmPassManager` pipeline.
+pub trait AwwasmPass {
+    /// Short name used in error messages.
+    fn name(&self) -> &str;
+
+    /// Rewrite `module` in place, recording any renumbering in `remap`.
+    fn run(&self, module: &mut AwwasmPassModule, remap: &mut AwwasmIndexRemap) -> anyhow::Result<()>;
+}
+
+/// The result of `AwwasmPassManager::run()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmPassOutput {
+    /// The rewritten module binary.
+    pub bytes: Vec<u8>,
+    pub remap: AwwasmIndexRemap,
+}
+
+/// Runs a sequence of passes over a module, then re-encodes the result and
+/// validates it.
+#[derive(Default)]
+pub struct AwwasmPassManager {
+    passes: Vec<Box<dyn AwwasmPass>>,
+}
+
+impl AwwasmPassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `pass` to the pipeline.
+    pub fn add(&mut self, pass: impl AwwasmPass + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    /// Run every pass in order on `bytes`.
+    ///
+    /// Fails if a pass fails, or if the output does not parse or has
+    /// validation findings.
+    pub fn run(&self, bytes: &[u8]) -> anyhow::Result<AwwasmPassOutput> {
+        let mut module = AwwasmPassModule::new(bytes)?;
+        let mut remap = AwwasmIndexRemap::default();
+        for pass in &self.passes {
+            pass.run(&mut module, &mut remap)
+                .with_context(|| format!("Pass {} failed", pass.name()))?;
+        }
+
+        let bytes = module.encode();
+        let mut output = AwwasmModule::new(&bytes)?;
+        output.resolve_all_sections()?;
+        if let Some(finding) = output.validate().into_iter().next() {
+            return Err(anyhow::anyhow!("Pass pipeline produced an invalid module: {}", finding));
+        }
+        Ok(AwwasmPassOutput { bytes, remap })
+    }
+}
+
+// Parse a snapshot of `module` for passes that need the decoded items.
+fn with_resolved<R>(module: &AwwasmPassModule, f: impl FnOnce(&AwwasmModule) -> anyhow::Result<R>) -> anyhow::Result<R> {
+    let bytes = module.encode();
+    let mut parsed = AwwasmModule::new(&bytes)?;
+    parsed.resolve_all_sections()?;
+    f(&parsed)
+}
+
+pub(crate) fn write_name(out: &mut Vec<u8>, name: &[u8]) {
+    write_leb128_u32(out, name.len() as u32);
+    out.extend_from_slice(name);
+}
+
+/// Remove custom sections, except those named in `keep`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmStripCustomSections {
+    pub keep: Vec<String>,
+}
+
+impl AwwasmPass for AwwasmStripCustomSections {
+    fn name(&self) -> &str {
+        "strip"
+    }
+
+    fn run(&self, module: &mut AwwasmPassModule, _: &mut AwwasmIndexRemap) -> anyhow::Result<()> {
+        module.sections.retain(|s| match s.custom_name() {
+            Some(name) => self.keep.iter().any(|k| k.as_bytes() == name),
+            None => s.id != SectionCode::Custom,
+        });
+        Ok(())
+    }
+}
+
+/// Rename exports, from old name to new name.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmRenameExports {
+    pub renames: Vec<(String, String)>,
+}
+
+impl AwwasmPass for AwwasmRenameExports {
+    fn name(&self) -> &str {
+        "rename"
+    }
+
+    fn run(&self, module: &mut AwwasmPassModule, _: &mut AwwasmIndexRemap) -> anyhow::Result<()> {
+        let payload = with_resolved(module, |parsed| {
+            let exports = parsed.exports();
+            if let Some((old, _)) = self.renames.iter().find(|(old, _)| !exports.iter().any(|e| e.name.bytes == old.as_bytes())) {
+                return Err(anyhow::anyhow!("Module has no export named \"{}\"", old));
+            }
+            let mut payload = Vec::new();
+            write_leb128_u32(&mut payload, exports.len() as u32);
+            for export in exports {
+                let name = self.renames.iter()
+                    .find(|(old, _)| old.as_bytes() == &*export.name.bytes)
+                    .map_or(&*export.name.bytes, |(_, new)| new.as_bytes());
+                write_name(&mut payload, name);
+                payload.push(export.kind.clone() as u8);
+                write_leb128_u32(&mut payload, export.index);
+            }
+            Ok(payload)
+        })?;
+        if !self.renames.is_empty() {
+            module.set_payload(SectionCode::Export, payload)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rename imports: single imports, whole import modules (e.g. `env` to
+/// `wasi_snapshot_preview1`), and a prefix put in front of every module
+/// name, to namespace a module before composing it with others.
+///
+/// Only the names change; the import descriptors, and so every index, are
+/// kept as they are.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmRemapImports {
+    /// Renames of single imports, from `(module, name)` to `(module, name)`.
+    /// Each must match an import.
+    pub imports: Vec<((String, String), (String, String))>,
+    /// Renames of import modules, from old name to new, for imports no
+    /// entry of `imports` renamed.
+    pub modules: Vec<(String, String)>,
+    /// Put in front of every module name, after the renames.
+    pub module_prefix: String,
+}
+
+impl AwwasmRemapImports {
+    // The new module and field names of an import.
+    fn remap(&self, module: &[u8], name: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let (module, name) = match self.imports.iter().find(|((m, n), _)| m.as_bytes() == module && n.as_bytes() == name) {
+            Some((_, (m, n))) => (m.as_bytes(), n.as_bytes()),
+            None => (self.modules.iter().find(|(old, _)| old.as_bytes() == module).map_or(module, |(_, new)| new.as_bytes()), name),
+        };
+        ([self.module_prefix.as_bytes(), module].concat(), name.to_vec())
+    }
+}
+
+impl AwwasmPass for AwwasmRemapImports {
+    fn name(&self) -> &str {
+        "remap-imports"
+    }
+
+    fn run(&self, module: &mut AwwasmPassModule, _: &mut AwwasmIndexRemap) -> anyhow::Result<()> {
+        let raw = module.payload(SectionCode::Import);
+        let present = raw.is_some();
+        let (mut rest, count) = match raw {
+            Some(raw) => leb128_u32::<nom::error::Error<&[u8]>>(raw).map_err(|e| AwwasmParseError::nom("Import Section", e))?,
+            None => (&[][..], 0),
+        };
+        let mut matched = vec![false; self.imports.len()];
+        let mut payload = Vec::with_capacity(rest.len());
+        write_leb128_u32(&mut payload, count);
+        for _ in 0..count {
+            let (next, import) = AwwasmImportSectionItem::parse(rest)
+                .map_err(|e| AwwasmParseError::nom("Import Section", e))?;
+            for (i, ((m, n), _)) in self.imports.iter().enumerate() {
+                matched[i] |= m.as_bytes() == &*import.module.bytes && n.as_bytes() == &*import.name.bytes;
+            }
+            let (module_name, name) = self.remap(&import.module.bytes, &import.name.bytes);
+            write_name(&mut payload, &module_name);
+            write_name(&mut payload, &name);
+            // The descriptor follows the names; copy it as it is.
+            let descriptor = span_in(rest, &import.name.bytes).map_or(0, |span| span.end);
+            payload.extend_from_slice(&rest[descriptor..rest.len() - next.len()]);
+            rest = next;
+        }
+        if let Some(((m, n), _)) = matched.iter().position(|m| !m).map(|i| &self.imports[i]) {
+            return Err(anyhow::anyhow!("Module has no import \"{}\" \"{}\"", m, n));
+        }
+        if present {
+            module.set_payload(SectionCode::Import, payload)?;
+        }
+        Ok(())
+    }
+}
+
+/// Merge identical function types and renumber every reference to them:
+/// function, tag and import declarations, `call_indirect` and block types. Type names in the
+/// name section are left as they are; branch hints are moved with the code.
+///
+/// Modules using typed function references are left unchanged, since their
+/// type indices also appear inside types, locals and instructions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmDedupTypes;
+
+impl AwwasmPass for AwwasmDedupTypes {
+    fn name(&self) -> &str {
+        "dedup"
+    }
+
+    fn run(&self, module: &mut AwwasmPassModule, remap: &mut AwwasmIndexRemap) -> anyhow::Result<()> {
+        let import_payload = module.payload(SectionCode::Import).map(<[u8]>::to_vec);
+        let rewritten = with_resolved(module, |parsed| {
+            if parsed.features_used().function_references {
+                return Ok(None);
+            }
+            let types = parsed.types();
+            let mut kept: Vec<&AwwasmTypeSectionItem> = Vec::new();
+            let mut first_seen: HashMap<&AwwasmTypeSectionItem, u32> = HashMap::new();
+            let mapping: Vec<u32> = types.iter().map(|t| {
+                *first_seen.entry(t).or_insert_with(|| {
+                    kept.push(t);
+                    kept.len() as u32 - 1
+                })
+            }).collect();
+            if kept.len() == types.len() {
+                return Ok(None);
+            }
+            // Out-of-range indices are left for validation to report.
+            let map = |idx: u32| mapping.get(idx as usize).copied().unwrap_or(idx);
+
+            let mut type_payload = Vec::new();
+            write_leb128_u32(&mut type_payload, kept.len() as u32);
+            for t in kept {
+                type_payload.extend_from_slice(t.type_magic);
+                write_leb128_u32(&mut type_payload, t.fn_args.len() as u32);
+                t.fn_args.iter().for_each(|v| v.write(&mut type_payload));
+                write_leb128_u32(&mut type_payload, t.fn_rets.len() as u32);
+                t.fn_rets.iter().for_each(|v| v.write(&mut type_payload));
+            }
+
+            let func_payload = parsed.funcs.as_ref().map(|funcs| {
+                let mut payload = Vec::new();
+                write_leb128_u32(&mut payload, funcs.len() as u32);
+                for f in funcs {
+                    write_leb128_u32(&mut payload, map(f.type_item_idx));
+                }
+                payload
+            });
+
+            let import_payload = import_payload.as_deref().map(|raw| rewrite_imports(raw, map)).transpose()?;
+
+            let tag_payload = parsed.tags.as_ref().map(|tags| {
+                let mut payload = Vec::new();
+                write_leb128_u32(&mut payload, tags.len() as u32);
+                for t in tags {
+                    payload.push(t.attribute);
+                    write_leb128_u32(&mut payload, map(t.type_idx));
+                }
+                payload
+            });
+
+            // Per function body, where splices ended in the old body and how far
+            // the bytes after them moved, for rebasing branch hint offsets.
+            let mut shifts: Vec<Vec<(usize, isize)>> = Vec::new();
+            let code_payload = match &parsed.code {
+                Some(code) => {
+                    let mut payload = Vec::new();
+                    write_leb128_u32(&mut payload, code.len() as u32);
+                    for item in code {
+                        let mut body = Vec::with_capacity(item.func_body.len());
+                        let mut moved = Vec::new();
+                        let mut pos = 0;
+                        for instr in &item.instructions()? {
+                            instr.walk(&mut |instr| {
+                                let Some(span) = span_in(item.func_body, instr.encoding) else { return };
+                                match (&instr.operands, instr.operands.block_type()) {
+                                    (AwwasmOperands::CallIndirect(op) | AwwasmOperands::ReturnCallIndirect(op), _) => {
+                                        body.extend_from_slice(&item.func_body[pos..span.start]);
+                                        body.push(instr.encoding[0]);
+                                        write_leb128_u32(&mut body, map(op.typeidx));
+                                        write_leb128_u32(&mut body, op.tableidx);
+                                        pos = span.end;
+                                        moved.push((pos, body.len() as isize - pos as isize));
+                                    }
+                                    (_, Some(BlockType::TypeIndex(idx))) => {
+                                        // Only the block type is replaced; the catch
+                                        // clauses of a `try_table` follow it.
+                                        let Ok((after, _)) = BlockType::parse(&instr.encoding[1..]) else { return };
+                                        body.extend_from_slice(&item.func_body[pos..span.start]);
+                                        body.push(instr.encoding[0]);
+                                        write_leb128_s33(&mut body, map(idx));
+                                        pos = span.end - after.len();
+                                        moved.push((pos, body.len() as isize - pos as isize));
+                                    }
+                                    _ => {}
+                                }
+                            });
+                        }
+                        body.extend_from_slice(&item.func_body[pos..]);
+                        write_leb128_u32(&mut payload, body.len() as u32);
+                        payload.extend_from_slice(&body);
+                        shifts.push(moved);
+                    }
+                    Some(payload)
+                }
+                None => None,
+            };
+
+            // Branch hints point into the bodies; move them with the code.
+            let hint_payload = moved_hints(parsed, &shifts, Some)?;
+
+            let rewritten = [
+                (SectionCode::Type, Some(type_payload)),
+                (SectionCode::Import, import_payload),
+                (SectionCode::Function, func_payload),
+                (SectionCode::Tag, tag_payload),
+                (SectionCode::Code, code_payload),
+            ];
+            Ok(Some((rewritten, hint_payload, mapping)))
+        })?;
+
+        let Some((sections, hint_payload, mapping)) = rewritten else {
+            return Ok(());
+        };
+        for (id, payload) in sections {
+            if let Some(payload) = payload {
+                module.set_payload(id, payload)?;
+            }
+        }
+        module.set_hints(hint_payload);
+        remap.record(AwwasmIndexSpace::Type, mapping.into_iter().map(Some).collect());
+        Ok(())
+    }
+}
+
+// Re-encode an import section payload with function and tag type indices
+// passed through `map`; other imports are copied as they are.
+fn rewrite_imports(raw: &[u8], map: impl Fn(u32) -> u32) -> anyhow::Result<Vec<u8>> {
+    let (mut rest, count) = leb128_u32::<nom::error::Error<&[u8]>>(raw)
+        .map_err(|e| AwwasmParseError::nom("Import Section", e))?;
+    let mut payload = Vec::with_capacity(raw.len());
+    write_leb128_u32(&mut payload, count);
+    for _ in 0..count {
+        let (next, import) = AwwasmImportSectionItem::parse(rest)
+            .map_err(|e| AwwasmParseError::nom("Import Section", e))?;
+        let attribute = import.tag.as_ref().map(|t| t.attribute);
+        match import.func_type_idx.or(import.tag.as_ref().map(|t| t.type_idx)) {
+            Some(idx) => {
+                write_name(&mut payload, &import.module.bytes);
+                write_name(&mut payload, &import.name.bytes);
+                payload.push(import.kind as u8);
+                payload.extend(attribute);
+                write_leb128_u32(&mut payload, map(idx));
+            }
+            None => payload.extend_from_slice(&rest[..rest.len() - next.len()]),
+        }
+        rest = next;
+    }
+    Ok(payload)
+}
+
+// Re-encode the branch hints of `parsed` for code a pass rewrote: `shifts`
+// holds, per defined function, where splices ended in its old body and how
+// far the bytes after them moved, and `func` maps each function index to
+// its new one, or to `None` for a removed function, whose hints are dropped.
+fn moved_hints(parsed: &AwwasmModule, shifts: &[Vec<(usize, isize)>], func: impl Fn(u32) -> Option<u32>) -> anyhow::Result<Option<Vec<u8>>> {
+    let Some(mut section) = parsed.branch_hints()? else { return Ok(None) };
+    let imported = parsed.imported_func_count();
+    section.functions.retain_mut(|f| {
+        let Some(idx) = func(f.func_idx) else { return false };
+        if let Some(moved) = f.func_idx.checked_sub(imported).and_then(|i| shifts.get(i as usize)) {
+            for hint in &mut f.hints {
+                let shift = moved.iter().rev().find(|(end, _)| *end <= hint.offset as usize).map_or(0, |(_, d)| *d);
+                hint.offset = (hint.offset as isize + shift) as u32;
+            }
+        }
+        f.func_idx = idx;
+        true
+    });
+    Ok(Some(section.encode()))
+}
+
+// Append the functions the instructions in `code` call or refer to.
+fn func_refs(code: &[u8], out: &mut Vec<u32>) -> anyhow::Result<()> {
+    for instr in InstructionIterator::new(code) {
+        let instr = instr.map_err(|e| AwwasmParseError::instruction("Function body", e).within(code))?;
+        instr.walk(&mut |instr| {
+            if let AwwasmOperands::Call(op) | AwwasmOperands::ReturnCall(op) | AwwasmOperands::RefFunc(op) = &instr.operands {
+                out.push(op.funcidx);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Remove the defined functions nothing can reach, and renumber the
+/// functions after them. The roots are the exported functions, the start
+/// function, element segments and global initializers; from them,
+/// functions are reached through `call` and `ref.func`. Imported functions
+/// are kept, so the module links against the same imports.
+///
+/// Branch hints are renumbered and moved with the code. The name section
+/// is dropped, since its function names no longer hold; other custom
+/// sections are left as they are.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AwwasmGcFunctions;
+
+impl AwwasmPass for AwwasmGcFunctions {
+    fn name(&self) -> &str {
+        "gc"
+    }
+
+    fn run(&self, module: &mut AwwasmPassModule, remap: &mut AwwasmIndexRemap) -> anyhow::Result<()> {
+        let rewritten = with_resolved(module, |parsed| {
+            let imported = parsed.imported_func_count();
+            let types = parsed.funcs();
+            let code = parsed.code();
+            if types.len() != code.len() {
+                return Err(anyhow::anyhow!("Module declares {} functions but has {} bodies", types.len(), code.len()));
+            }
+
+            let mut pending: Vec<u32> = parsed.exports().iter()
+                .filter(|e| e.kind == AwwasmExportKind::Function)
+                .map(|e| e.index)
+                .chain(parsed.start.as_ref().map(|s| s.func_idx))
+                .collect();
+            for element in parsed.elements() {
+                pending.extend(element.body.func_indices().unwrap_or_default());
+                for expr in element.body.init_exprs() {
+                    func_refs(expr.code, &mut pending)?;
+                }
+            }
+            for global in parsed.globals() {
+                func_refs(global.init_expr.code, &mut pending)?;
+            }
+            let mut live = vec![false; code.len()];
+            while let Some(idx) = pending.pop() {
+                let Some(i) = idx.checked_sub(imported).map(|i| i as usize).filter(|&i| i < code.len()) else { continue };
+                if !std::mem::replace(&mut live[i], true) {
+                    func_refs(code[i].locals_and_code()?.1, &mut pending)?;
+                }
+            }
+            if live.iter().all(|&l| l) {
+                return Ok(None);
+            }
+
+            let mut next = imported;
+            let mapping: Vec<Option<u32>> = (0..imported).map(Some)
+                .chain(live.iter().map(|&l| l.then(|| {
+                    next += 1;
+                    next - 1
+                })))
+                .collect();
+            // Removed functions are not referred to from what is kept.
+            let funcs: Vec<u32> = mapping.iter().enumerate().map(|(i, m)| m.unwrap_or(i as u32)).collect();
+            let renumber = Renumber { types: &[], funcs: &funcs, globals: &[], data_base: 0 };
+            let kept = live.iter().filter(|&&l| l).count() as u32;
+
+            let mut func_payload = Vec::new();
+            write_leb128_u32(&mut func_payload, kept);
+            for (f, _) in types.iter().zip(&live).filter(|(_, &l)| l) {
+                write_leb128_u32(&mut func_payload, f.type_item_idx);
+            }
+
+            let mut shifts = Vec::new();
+            let mut code_payload = Vec::new();
+            write_leb128_u32(&mut code_payload, kept);
+            for (item, &l) in code.iter().zip(&live) {
+                let mut moved = Vec::new();
+                if l {
+                    let (_, body_code) = item.locals_and_code()?;
+                    let locals = item.func_body.len() - body_code.len();
+                    let mut body = item.func_body[..locals].to_vec();
+                    body.extend(renumber.code_moving(body_code, &mut moved)?);
+                    moved.iter_mut().for_each(|(end, _)| *end += locals);
+                    write_leb128_u32(&mut code_payload, body.len() as u32);
+                    code_payload.extend_from_slice(&body);
+                }
+                shifts.push(moved);
+            }
+
+            let export_payload = parsed.exports.as_ref().map(|exports| {
+                let mut payload = Vec::new();
+                write_leb128_u32(&mut payload, exports.len() as u32);
+                for export in exports {
+                    let index = match export.kind {
+                        AwwasmExportKind::Function => map(&funcs, export.index),
+                        _ => export.index,
+                    };
+                    AwwasmExportSectionItem { index, ..export.clone() }.write(&mut payload);
+                }
+                payload
+            });
+
+            let start_payload = parsed.start.as_ref().map(|start| {
+                let mut payload = Vec::new();
+                write_leb128_u32(&mut payload, map(&funcs, start.func_idx));
+                payload
+            });
+
+            let element_payload = parsed.elements.as_ref().map(|elements| -> anyhow::Result<Vec<u8>> {
+                let mut payload = Vec::new();
+                write_leb128_u32(&mut payload, elements.len() as u32);
+                for element in elements {
+                    let codes = element.body.init_exprs().iter().map(|e| renumber.code(e.code)).collect::<anyhow::Result<Vec<_>>>()?;
+                    let mut element = element.clone();
+                    for (expr, code) in element.body.init_exprs_mut().into_iter().zip(&codes) {
+                        expr.code = code;
+                    }
+                    for idx in element.body.func_indices_mut().into_iter().flatten() {
+                        *idx = map(&funcs, *idx);
+                    }
+                    element.write(&mut payload);
+                }
+                Ok(payload)
+            }).transpose()?;
+
+            let global_payload = parsed.globals.as_ref().map(|globals| -> anyhow::Result<Vec<u8>> {
+                let mut payload = Vec::new();
+                write_leb128_u32(&mut payload, globals.len() as u32);
+                for global in globals {
+                    let mut code = Vec::new();
+                    let init_expr = renumber.expr(&global.init_expr, &mut code)?;
+                    AwwasmGlobalSectionItem { init_expr, ..global.clone() }.write(&mut payload);
+                }
+                Ok(payload)
+            }).transpose()?;
+
+            let hint_payload = moved_hints(parsed, &shifts, |idx| mapping.get(idx as usize).copied().flatten())?;
+            let rewritten = [
+                (SectionCode::Function, Some(func_payload)),
+                (SectionCode::Global, global_payload),
+                (SectionCode::Export, export_payload),
+                (SectionCode::Start, start_payload),
+                (SectionCode::Element, element_payload),
+                (SectionCode::Code, Some(code_payload)),
+            ];
+            Ok(Some((rewritten, hint_payload, mapping)))
+        })?;
+
+        let Some((sections, hint_payload, mapping)) = rewritten else {
+            return Ok(());
+        };
+        for (id, payload) in sections {
+            if let Some(payload) = payload {
+                module.set_payload(id, payload)?;
+            }
+        }
+        module.set_hints(hint_payload);
+        module.sections.retain(|s| s.custom_name() != Some(NAME_SECTION));
+        remap.record(AwwasmIndexSpace::Func, mapping);
+        Ok(())
+    }
+}
+
+/// Charge function bodies for the instructions they run, against a mutable
+/// `i64` global the pass adds and exports as `export`. On entering a
+/// function body, or a body nested in it, the number of instructions
+/// directly in that body is subtracted from the global, so a body left
+/// early by a branch is still charged in full; once the global is below
+/// zero, execution traps with `unreachable`. The global starts at zero:
+/// the host sets the budget before calling in, and reads what is left after.
+///
+/// Branch hints are moved with the code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmMeterInstructions {
+    /// Export name of the global holding the budget.
+    pub export: String,
+}
+
+// Subtract `cost` from global `global`, then trap if it is below zero.
+fn write_charge(out: &mut Vec<u8>, global: u32, cost: u32) {
+    out.push(WasmOpCode::GlobalGet as u8);
+    write_leb128_u32(out, global);
+    out.push(WasmOpCode::I64Const as u8);
+    write_leb128_s33(out, cost);
+    out.push(WasmOpCode::I64Sub as u8);
+    out.push(WasmOpCode::GlobalSet as u8);
+    write_leb128_u32(out, global);
+    out.push(WasmOpCode::GlobalGet as u8);
+    write_leb128_u32(out, global);
+    out.extend_from_slice(&[WasmOpCode::I64Const as u8, 0, WasmOpCode::I64LtS as u8]);
+    // `if` with an empty block type.
+    out.extend_from_slice(&[WasmOpCode::If as u8, 0x40, WasmOpCode::Unreachable as u8, WasmOpCode::End as u8]);
+}
+
+impl AwwasmPass for AwwasmMeterInstructions {
+    fn name(&self) -> &str {
+        "metering"
+    }
+
+    fn run(&self, module: &mut AwwasmPassModule, _: &mut AwwasmIndexRemap) -> anyhow::Result<()> {
+        let (global, code_payload, hint_payload) = with_resolved(module, |parsed| {
+            if parsed.exports().iter().any(|e| e.name.bytes == self.export.as_bytes()) {
+                return Err(anyhow::anyhow!("Module already exports \"{}\"", self.export));
+            }
+            let global = parsed.imported_count(AwwasmImportKind::Global) + parsed.globals().len() as u32;
+            let mut shifts = Vec::new();
+            let code_payload = match &parsed.code {
+                Some(code) => {
+                    let mut payload = Vec::new();
+                    write_leb128_u32(&mut payload, code.len() as u32);
+                    for item in code {
+                        let (_, body_code) = item.locals_and_code()?;
+                        let instrs = item.instructions()?;
+                        // Where each body starts in the function body, and the
+                        // instructions directly in it.
+                        let mut charges = vec![(item.func_body.len() - body_code.len(), instrs.len())];
+                        for instr in &instrs {
+                            instr.walk(&mut |instr| {
+                                for (body, marker) in instr.operands.bodies() {
+                                    let start = body.first().map_or(*marker, |first| first.encoding);
+                                    if let Some(span) = span_in(item.func_body, start) {
+                                        charges.push((span.start, body.len()));
+                                    }
+                                }
+                            });
+                        }
+                        charges.sort_unstable();
+
+                        let mut body = Vec::with_capacity(item.func_body.len());
+                        let mut moved = Vec::new();
+                        let mut pos = 0;
+                        for (at, cost) in charges.into_iter().filter(|&(_, cost)| cost > 0) {
+                            body.extend_from_slice(&item.func_body[pos..at]);
+                            write_charge(&mut body, global, cost as u32);
+                            pos = at;
+                            moved.push((pos, body.len() as isize - pos as isize));
+                        }
+                        body.extend_from_slice(&item.func_body[pos..]);
+                        write_leb128_u32(&mut payload, body.len() as u32);
+                        payload.extend_from_slice(&body);
+                        shifts.push(moved);
+                    }
+                    Some(payload)
+                }
+                None => None,
+            };
+            Ok((global, code_payload, moved_hints(parsed, &shifts, Some)?))
+        })?;
+
+        if let Some(payload) = code_payload {
+            module.set_payload(SectionCode::Code, payload)?;
+        }
+        module.set_hints(hint_payload);
+        // `mut i64`, initialized by `i64.const 0`.
+        module.push_entry(SectionCode::Global, &[0x7e, AwwasmGlobalMutability::Mutable as u8, WasmOpCode::I64Const as u8, 0, WasmOpCode::End as u8])?;
+        let mut export = Vec::new();
+        write_name(&mut export, self.export.as_bytes());
+        export.push(AwwasmExportKind::Global as u8);
+        write_leb128_u32(&mut export, global);
+        module.push_entry(SectionCode::Export, &export)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+    use crate::components::passes::*;
+    use crate::components::instructions::AwwasmInstruction;
+    use crate::components::types::{AwwasmExportKind, AwwasmImportKind};
+
+    #[test]
+    fn pass_manager_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (type $a (func (param i32) (result i32)))
+                (type $b (func))
+                (type $c (func (param i32) (result i32)))
+                (import "env" "g" (func $g (type $c)))
+                (table 1 funcref)
+                (func $f (export "run") (type $c)
+                    (call_indirect (type $c) (local.get 0) (i32.const 0)))
+                (func (type $b))
+                (@custom "note" "x")
+                (@custom "keep" "y")
+            )
+        "#)?;
+        let mut manager = AwwasmPassManager::new();
+        manager
+            .add(AwwasmDedupTypes)
+            .add(AwwasmStripCustomSections { keep: vec!["keep".to_owned()] })
+            .add(AwwasmRenameExports { renames: vec![("run".to_owned(), "main".to_owned())] });
+        let output = manager.run(&module)?;
+
+        let mut module_parsed = AwwasmModule::new(&output.bytes)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.types.as_ref().map(Vec::len), Some(2));
+        assert_eq!(module_parsed.imports.as_ref().expect("imports")[0].func_type_idx, Some(0));
+        assert_eq!(module_parsed.funcs.as_ref().expect("funcs").iter().map(|f| f.type_item_idx).collect::<Vec<_>>(), vec![0, 1]);
+        let instrs = module_parsed.code.as_ref().expect("code")[0].instructions()?;
+        assert!(matches!(&instrs[2].operands, AwwasmOperands::CallIndirect(op) if op.typeidx == 0));
+        assert_eq!(module_parsed.customs.as_ref().expect("customs").iter().map(|c| &*c.name.bytes).collect::<Vec<_>>(), vec![b"keep"]);
+        assert_eq!(&module_parsed.exports.as_ref().expect("exports")[0].name.bytes[..], b"main");
+
+        assert_eq!(output.remap.get(AwwasmIndexSpace::Type, 2), Some(0));
+        assert_eq!(output.remap.get(AwwasmIndexSpace::Type, 1), Some(1));
+        assert_eq!(output.remap.get(AwwasmIndexSpace::Func, 1), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn remap_imports_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+                (import "env" "memory" (memory 1))
+                (import "host" "log" (func (param i32)))
+                (func (export "run") (call 0 (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)) (drop) (call 1 (i32.const 1)))
+            )
+        "#)?;
+        let pass = AwwasmRemapImports {
+            imports: vec![(("env".to_owned(), "memory".to_owned()), ("mem".to_owned(), "main".to_owned()))],
+            modules: vec![("env".to_owned(), "wasi_snapshot_preview1".to_owned())],
+            module_prefix: "app:".to_owned(),
+        };
+        let output = AwwasmPassManager::new().add(pass.clone()).run(&module)?;
+        let mut module_parsed = AwwasmModule::new(&output.bytes)?;
+        module_parsed.resolve_all_sections()?;
+        let imports = module_parsed.imports().iter().map(|i| Ok((i.module.as_str()?, i.name.as_str()?, i.kind.clone()))).collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(imports, vec![
+            ("app:wasi_snapshot_preview1", "fd_write", AwwasmImportKind::Function),
+            ("app:mem", "main", AwwasmImportKind::Memory),
+            ("app:host", "log", AwwasmImportKind::Function),
+        ]);
+        // Everything but the import names is unchanged.
+        let mut original = AwwasmModule::new(&module)?;
+        original.resolve_all_sections()?;
+        assert_eq!(module_parsed.code, original.code);
+        assert_eq!(module_parsed.imports.as_ref().map(|i| i[2].func_type_idx), original.imports.as_ref().map(|i| i[2].func_type_idx));
+
+        let missing = AwwasmRemapImports { imports: vec![(("env".to_owned(), "nope".to_owned()), ("a".to_owned(), "b".to_owned()))], ..pass };
+        assert!(AwwasmPassManager::new().add(missing).run(&module).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn dedup_remaps_block_types_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (type $a (func (param i32) (result i32 i32)))
+                (type $b (func (param i32) (result i32 i32)))
+                (func (param i32) (result i32 i32)
+                    (local.get 0)
+                    (block (type $b) (param i32) (result i32 i32) (i32.const 1)))
+            )
+        "#)?;
+        let output = AwwasmPassManager::new().add(AwwasmDedupTypes).run(&module)?.bytes;
+        let mut module_parsed = AwwasmModule::new(&output)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.types.as_ref().map(Vec::len), Some(1));
+        let instrs = module_parsed.code.as_ref().expect("code")[0].instructions()?;
+        assert_eq!(instrs[1].operands.block_type(), Some(BlockType::TypeIndex(0)));
+        assert!(module_parsed.validate().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn dedup_moves_branch_hints_test() -> anyhow::Result<()> {
+        // Type 128 needs a 2-byte LEB128; once merged into type 0 the
+        // `call_indirect` shrinks by one byte and the hinted `br_if` at
+        // offset 11 moves to 10.
+        let module = wat::parse_str(format!(r#"
+            (module
+                {}
+                (table 1 funcref)
+                (@custom "metadata.code.branch_hint" (before code) "\01\00\01\0b\01\01")
+                (func (type 0)
+                    (block (call_indirect (type 128) (i32.const 0)) (br_if 0 (i32.const 1))))
+            )
+        "#, "(type (func))".repeat(129)))?;
+        let output = AwwasmPassManager::new().add(AwwasmDedupTypes).run(&module)?.bytes;
+        let mut module_parsed = AwwasmModule::new(&output)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.types.as_ref().map(Vec::len), Some(1));
+
+        let hints = module_parsed.branch_hints()?.expect("branch hints should be kept");
+        assert_eq!(hints.function(0).map(|h| h[0].offset), Some(10));
+        let hinted = module_parsed.hinted_instructions(0)?;
+        assert_eq!(hinted.len(), 1);
+        assert_eq!(hinted[0].0.to_string(), "br_if 0");
+        Ok(())
+    }
+
+    #[test]
+    fn gc_functions_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "log" (func $log (param i32)))
+                (table 1 funcref)
+                (elem (i32.const 0) $indirect)
+                (global funcref (ref.func $referenced))
+                (func $dead (call $log (i32.const 0)))
+                (func $helper (call $log (i32.const 1)))
+                (func $run (export "run") (call $helper))
+                (func $indirect)
+                (func $referenced)
+                (func $also_dead (call $dead))
+                (func $init (call $helper))
+                (start $init)
+            )
+        "#)?;
+        let output = AwwasmPassManager::new().add(AwwasmGcFunctions).run(&module)?;
+        let mut module_parsed = AwwasmModule::new(&output.bytes)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.funcs.as_ref().map(Vec::len), Some(5));
+        assert_eq!(module_parsed.exports.as_ref().expect("exports")[0].index, 2);
+        assert_eq!(module_parsed.start.as_ref().map(|s| s.func_idx), Some(5));
+        let elements = module_parsed.elements.as_ref().expect("elements");
+        assert_eq!(elements[0].body.func_indices(), Some(&[3][..]));
+        let global = &module_parsed.globals.as_ref().expect("globals")[0];
+        assert_eq!(global.init_expr.code, [0xd2, 0x04]);
+        let code = module_parsed.code.as_ref().expect("code");
+        assert_eq!(code[1].instructions()?[0].to_string(), "call 1");
+        assert_eq!(code[4].instructions()?[0].to_string(), "call 1");
+        assert!(module_parsed.names()?.is_none());
+
+        let func = |idx| output.remap.get(AwwasmIndexSpace::Func, idx);
+        assert_eq!((0..9).map(func).collect::<Vec<_>>(), [Some(0), None, Some(1), Some(2), Some(3), Some(4), None, Some(5), None]);
+        Ok(())
+    }
+
+    #[test]
+    fn gc_moves_branch_hints_test() -> anyhow::Result<()> {
+        // Once the 128 functions before it are removed, `call 129` shrinks
+        // by one byte and the hinted `br_if` of function 130 moves from
+        // offset 8 to 7.
+        let module = wat::parse_str(format!(r#"
+            (module
+                (@custom "metadata.code.branch_hint" (before code) "\01\82\01\01\08\01\01")
+                {}
+                (func (export "run") (call 129))
+                (func (export "leaf"))
+                (func (export "hinted") (block (call 129) (br_if 0 (i32.const 1))))
+            )
+        "#, "(func)".repeat(128)))?;
+        let output = AwwasmPassManager::new().add(AwwasmGcFunctions).run(&module)?.bytes;
+        let mut module_parsed = AwwasmModule::new(&output)?;
+        module_parsed.resolve_all_sections()?;
+        assert_eq!(module_parsed.funcs.as_ref().map(Vec::len), Some(3));
+        let hints = module_parsed.branch_hints()?.expect("branch hints should be kept");
+        assert_eq!(hints.function(2).map(|h| h[0].offset), Some(7));
+        let hinted = module_parsed.hinted_instructions(2)?;
+        assert_eq!(hinted.len(), 1);
+        assert_eq!(hinted[0].0.to_string(), "br_if 0");
+        Ok(())
+    }
+
+    #[test]
+    fn meter_instructions_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "g" (global i32))
+                (func (export "count") (param i32)
+                    (loop
+                        (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                        (br_if 0 (local.get 0))))
+            )
+        "#)?;
+        let pass = AwwasmMeterInstructions { export: "gas".to_owned() };
+        let output = AwwasmPassManager::new().add(pass.clone()).run(&module)?.bytes;
+        let mut module_parsed = AwwasmModule::new(&output)?;
+        module_parsed.resolve_all_sections()?;
+        let globals = module_parsed.globals.as_ref().expect("globals");
+        assert_eq!(globals.len(), 1);
+        let export = &module_parsed.exports.as_ref().expect("exports")[1];
+        assert_eq!((export.name.as_str()?, export.kind.clone(), export.index), ("gas", AwwasmExportKind::Global, 1));
+
+        // The function body is charged for `loop` and `end`, the loop body
+        // for its six instructions, on every iteration.
+        let instrs = module_parsed.code.as_ref().expect("code")[0].instructions()?;
+        let texts = |instrs: &[AwwasmInstruction]| instrs.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+        let charge = |cost: &str| ["global.get 1".to_owned(), format!("i64.const {}", cost), "i64.sub".to_owned(), "global.set 1".to_owned(),
+            "global.get 1".to_owned(), "i64.const 0".to_owned(), "i64.lt_s".to_owned(), "if".to_owned()];
+        assert_eq!(texts(&instrs[..8]), charge("2"));
+        assert_eq!(texts(&instrs[8..]), ["loop", "end"]);
+        let AwwasmOperands::Loop(op) = &instrs[8].operands else { panic!("expected a loop") };
+        assert_eq!(texts(&op.body.0[..8]), charge("6"));
+        assert_eq!(op.body.0.len(), 14);
+
+        let err = AwwasmPassManager::new().add(pass.clone()).add(pass).run(&module).unwrap_err();
+        assert_eq!(format!("{:#}", err), "Pass metering failed: Module already exports \"gas\"");
+        Ok(())
+    }
+
+    #[test]
+    fn pass_manager_reports_failing_pass_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"(module (func (export "run")))"#)?;
+        let mut manager = AwwasmPassManager::new();
+        manager.add(AwwasmRenameExports { renames: vec![("missing".to_owned(), "x".to_owned())] });
+        let err = manager.run(&module).unwrap_err();
+        assert_eq!(format!("{:#}", err), "Pass rename failed: Module has no export named \"missing\"");
+        Ok(())
+    }
+
+    #[test]
+    fn index_remap_composes_test() {
+        let mut remap = AwwasmIndexRemap::default();
+        remap.record(AwwasmIndexSpace::Func, vec![Some(0), None, Some(1)]);
+        remap.record(AwwasmIndexSpace::Func, vec![Some(1), Some(0)]);
+        assert_eq!(remap.get(AwwasmIndexSpace::Func, 0), Some(1));
+        assert_eq!(remap.get(AwwasmIndexSpace::Func, 1), None);
+        assert_eq!(remap.get(AwwasmIndexSpace::Func, 2), Some(0));
+        assert!(!remap.is_remapped(AwwasmIndexSpace::Global));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::components::error::AwwasmParseError;
+use crate::components::module::AwwasmModulePreamble;
+use crate::components::section::{AwwasmSection, AwwasmSectionHeader, SectionCode, TruncatedSection};
+use crate::components::types::AwwasmCodeSectionItem;
+use nom::combinator::complete;
+use nom_derive::Parse;
+
+/// One event from `AwwasmPayloadIterator`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmPayload<'a> {
+    /// The preamble of a core module, always the first event.
+    Version(u32),
+    /// A section other than the code section, undecoded; call `resolve()`
+    /// on it for its contents.
+    Section(AwwasmSection<'a>),
+    /// The start of the code section, followed by `count` `CodeEntry`
+    /// events unless iteration stops.
+    CodeSectionStart { count: u32 },
+    /// One function body, undecoded.
+    CodeEntry(AwwasmCodeSectionItem<'a>),
+    /// The input ended after a complete section; always the last event.
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Preamble,
+    Sections,
+    Code { remaining: u32 },
+    Done,
+}
+
+/// Scans a module binary one section, or one function body, at a time,
+/// without building an `AwwasmModule`. Stop iterating once a tool has what
+/// it needs; nothing after that point is read.
+///
+/// Yields `Err` with a located `AwwasmParseError` for the first malformed
+/// section, and nothing after it.
+#[derive(Debug, Clone)]
+pub struct AwwasmPayloadIterator<'a> {
+    start: usize,
+    input: &'a [u8],
+    // Rest of the code section body while in `State::Code`.
+    code: &'a [u8],
+    state: State,
+}
+
+impl<'a> AwwasmPayloadIterator<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        AwwasmPayloadIterator { start: input.as_ptr() as usize, input, code: &[], state: State::Preamble }
+    }
+
+    /// Offset in the binary of the next byte to be read.
+    pub fn offset(&self) -> usize {
+        let next = if matches!(self.state, State::Code { .. }) { self.code } else { self.input };
+        next.as_ptr() as usize - self.start
+    }
+
+    fn located(&self, mut err: AwwasmParseError, section: Option<&SectionCode>) -> anyhow::Error {
+        err.locate(self.start, section, None);
+        err.into()
+    }
+
+    fn step(&mut self) -> anyhow::Result<AwwasmPayload<'a>> {
+        match self.state {
+            State::Preamble => {
+                let (rest, preamble) = AwwasmModulePreamble::parse(self.input)
+                    .map_err(|e| self.located(AwwasmParseError::nom("module preamble", e).within(self.input), None))?;
+                preamble.check_core_module()?;
+                self.input = rest;
+                self.state = State::Sections;
+                Ok(AwwasmPayload::Version(preamble.version))
+            }
+            State::Sections if self.input.is_empty() => {
+                self.state = State::Done;
+                Ok(AwwasmPayload::End)
+            }
+            State::Sections => {
+                let (body, header) = complete(AwwasmSectionHeader::parse)(self.input)
+                    .map_err(|e| self.located(AwwasmParseError::section_header("section header", e).within(self.input), None))?;
+                if header.section_size as usize > body.len() {
+                    return Err(AwwasmParseError::from(TruncatedSection { section: header.section_type, declared: header.section_size, available: body.len() }).into());
+                }
+                let (rest, sec) = complete(AwwasmSection::parse)(self.input)
+                    .map_err(|e| self.located(AwwasmParseError::nom("module", e).within(self.input), Some(&header.section_type)))?;
+                self.input = rest;
+                if sec.section_header.section_type != SectionCode::Code {
+                    return Ok(AwwasmPayload::Section(sec));
+                }
+                self.code = sec.section_body;
+                self.state = State::Code { remaining: sec.entry_count };
+                Ok(AwwasmPayload::CodeSectionStart { count: sec.entry_count })
+            }
+            State::Code { remaining: 0 } => {
+                self.state = State::Sections;
+                self.step()
+            }
+            State::Code { remaining } => {
+                let (rest, item) = AwwasmCodeSectionItem::parse(self.code)
+                    .map_err(|e| self.located(AwwasmParseError::nom("Code Section", e).within(self.code), Some(&SectionCode::Code)))?;
+                self.code = rest;
+                self.state = State::Code { remaining: remaining - 1 };
+                Ok(AwwasmPayload::CodeEntry(item))
+            }
+            State::Done => Ok(AwwasmPayload::End),
+        }
+    }
+}
+
+impl<'a> Iterator for AwwasmPayloadIterator<'a> {
+    type Item = anyhow::Result<AwwasmPayload<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state == State::Done {
+            return None;
+        }
+        let payload = self.step();
+        if payload.is_err() {
+            self.state = State::Done;
+        }
+        Some(payload)
+    }
+}
+
+impl std::iter::FusedIterator for AwwasmPayloadIterator<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+    use crate::components::payload::{AwwasmPayload, AwwasmPayloadIterator};
+    use crate::components::section::{SectionCode, SectionItem};
+
+    #[test]
+    fn payload_iterator_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (import "env" "f" (func))
+                (func (call 0))
+                (func (nop)))
+        "#)?;
+        let events: Vec<String> = AwwasmPayloadIterator::new(&bytes)
+            .map(|p| p.map(|p| match p {
+                AwwasmPayload::Section(sec) => format!("{:?}", sec.section_header.section_type),
+                AwwasmPayload::CodeEntry(item) => format!("body {:02x?}", item.func_body),
+                other => format!("{:?}", other),
+            }))
+            .collect::<anyhow::Result<_>>()?;
+        assert_eq!(events, vec![
+            "Version(1)", "Type", "Import", "Function", "CodeSectionStart { count: 2 }",
+            "body [00, 10, 00, 0b]", "body [00, 01, 0b]", "End",
+        ]);
+
+        // Stopping at the imports leaves the truncated code section unread.
+        let mut cut = bytes.clone();
+        let code_at = cut.len() - 12;
+        assert_eq!(cut[code_at], 0x0a);
+        cut.truncate(code_at + 5);
+        let mut payloads = AwwasmPayloadIterator::new(&cut);
+        let imports = payloads.find_map(|p| match p {
+            Ok(AwwasmPayload::Section(mut sec)) if sec.section_header.section_type == SectionCode::Import => Some(sec.resolve()),
+            _ => None,
+        });
+        assert!(matches!(imports, Some(Ok(SectionItem::ImportSectionItems(ref i))) if i.len() == 1));
+        assert_eq!(payloads.offset(), code_at - 5);
+
+        // A malformed body ends iteration with a located error.
+        let mut bad = bytes.clone();
+        bad[code_at + 3] = 0x09;
+        let last = AwwasmPayloadIterator::new(&bad).last().expect("iteration should yield");
+        let err = last.unwrap_err();
+        let err = err.downcast_ref::<AwwasmParseError>().expect("should be an AwwasmParseError");
+        assert_eq!((err.kind.clone(), err.section.clone()), (AwwasmParseErrorKind::Truncated, Some(SectionCode::Code)));
+        Ok(())
+    }
+}
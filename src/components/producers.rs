@@ -0,0 +1,113 @@
+use crate::components::error::AwwasmParseError;
+use crate::components::module::AwwasmModule;
+use crate::components::types::AwwasmName;
+use nom_derive::*;
+use crate::components::leb128::leb128_u32;
+use nom::combinator::all_consuming;
+use nom::multi::length_count;
+
+const PRODUCERS_SECTION: &[u8] = b"producers";
+
+const LANGUAGE_FIELD: &[u8] = b"language";
+const PROCESSED_BY_FIELD: &[u8] = b"processed-by";
+const SDK_FIELD: &[u8] = b"sdk";
+
+/// A tool or language name with its version string.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmVersionedName<'a> {
+    pub name: AwwasmName<'a>,
+    pub version: AwwasmName<'a>,
+}
+
+/// One producers field (`language`, `processed-by` or `sdk`) and its values.
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmProducersField<'a> {
+    pub name: AwwasmName<'a>,
+    #[nom(LengthCount = "leb128_u32")]
+    pub values: Vec<AwwasmVersionedName<'a>>,
+}
+
+/// Decoded contents of the `producers` custom section.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AwwasmProducersSection<'a> {
+    pub fields: Vec<AwwasmProducersField<'a>>,
+}
+
+impl<'a> AwwasmProducersSection<'a> {
+    /// Decode the payload of a `producers` custom section.
+    pub fn parse(payload: &'a [u8]) -> anyhow::Result<Self> {
+        let (_, fields) = all_consuming(length_count(leb128_u32, AwwasmProducersField::parse))(payload)
+            .map_err(|e| AwwasmParseError::nom("Producers Section", e))?;
+        Ok(Self { fields })
+    }
+
+    /// Values of the field called `name`; empty if the field is absent.
+    pub fn field(&self, name: &[u8]) -> &[AwwasmVersionedName<'a>] {
+        self.fields.iter()
+            .find(|f| f.name.bytes == name)
+            .map_or(&[], |f| &f.values)
+    }
+
+    /// Source languages the module was compiled from.
+    pub fn language(&self) -> &[AwwasmVersionedName<'a>] {
+        self.field(LANGUAGE_FIELD)
+    }
+
+    /// Tools that produced or transformed the module.
+    pub fn processed_by(&self) -> &[AwwasmVersionedName<'a>] {
+        self.field(PROCESSED_BY_FIELD)
+    }
+
+    /// SDKs the module was built with.
+    pub fn sdk(&self) -> &[AwwasmVersionedName<'a>] {
+        self.field(SDK_FIELD)
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Decode the `producers` custom section, if the module has one.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn producers(&self) -> anyhow::Result<Option<AwwasmProducersSection<'a>>> {
+        self.custom_section(PRODUCERS_SECTION)
+            .map(|c| AwwasmProducersSection::parse(c.payload))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn decode_producers_section_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (@producers
+                    (language "Rust" "")
+                    (language "C" "11")
+                    (processed-by "rustc" "1.70.0")
+                )
+                (func)
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let producers = module_parsed.producers()?.expect("producers section should exist");
+        let pairs = |values: &[super::AwwasmVersionedName]| -> Vec<(String, String)> {
+            values.iter()
+                .map(|v| (String::from_utf8_lossy(&v.name.bytes).into_owned(), String::from_utf8_lossy(&v.version.bytes).into_owned()))
+                .collect()
+        };
+        assert_eq!(pairs(producers.language()), vec![
+            ("Rust".to_owned(), "".to_owned()),
+            ("C".to_owned(), "11".to_owned()),
+        ]);
+        assert_eq!(pairs(producers.processed_by()), vec![("rustc".to_owned(), "1.70.0".to_owned())]);
+        assert!(producers.sdk().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,407 @@
+use crate::components::index_space::AwwasmIndexed;
+use crate::components::module::AwwasmModule;
+use crate::components::types::*;
+use crate::json;
+use std::cmp::Ordering;
+use std::fmt;
+
+const KIB: u64 = 1024;
+const MIB: u64 = 1024 * 1024;
+
+/// A field value in a query result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmQueryValue {
+    Num(u64),
+    Str(String),
+}
+
+impl fmt::Display for AwwasmQueryValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AwwasmQueryValue::Num(n) => write!(f, "{}", n),
+            AwwasmQueryValue::Str(s) => f.write_str(s),
+        }
+    }
+}
+
+/// One item of a collection, as field name and value pairs.
+pub type AwwasmQueryRecord = Vec<(&'static str, AwwasmQueryValue)>;
+
+/// What a query evaluates to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AwwasmQueryResult {
+    /// `.count`: how many items matched.
+    Count(usize),
+    /// `.field`: that field of every matching item.
+    Values(Vec<AwwasmQueryValue>),
+    /// No projection: every matching item.
+    Records(Vec<AwwasmQueryRecord>),
+}
+
+impl fmt::Display for AwwasmQueryResult {
+    /// One line per value, or per record as a JSON object, for use in scripts.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AwwasmQueryResult::Count(n) => writeln!(f, "{}", n),
+            AwwasmQueryResult::Values(values) => values.iter().try_for_each(|v| writeln!(f, "{}", v)),
+            AwwasmQueryResult::Records(records) => records.iter().try_for_each(|record| {
+                let fields: Vec<String> = record.iter().map(|(name, value)| match value {
+                    AwwasmQueryValue::Num(n) => format!("{}:{}", json::string(name), n),
+                    AwwasmQueryValue::Str(s) => format!("{}:{}", json::string(name), json::string(s)),
+                }).collect();
+                writeln!(f, "{{{}}}", fields.join(","))
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Gt => ordering.is_gt(),
+            Op::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Filter<'q> {
+    field: &'q str,
+    op: Op,
+    value: &'q str,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Query<'q> {
+    collection: &'q str,
+    filters: Vec<Filter<'q>>,
+    projection: Option<&'q str>,
+}
+
+// `collection ('[' field op value ']')* ('.' field)?`
+struct QueryParser<'q> {
+    src: &'q str,
+    pos: usize,
+}
+
+impl<'q> QueryParser<'q> {
+    fn parse(src: &'q str) -> anyhow::Result<Query<'q>> {
+        let mut p = QueryParser { src, pos: 0 };
+        let collection = p.ident()?;
+        let mut filters = Vec::new();
+        while p.eat("[") {
+            let field = p.ident()?;
+            let op = p.op()?;
+            let value = p.value()?;
+            p.expect("]")?;
+            filters.push(Filter { field, op, value });
+        }
+        let projection = if p.eat(".") { Some(p.ident()?) } else { None };
+        p.skip_ws();
+        if p.pos < src.len() {
+            return Err(p.error("end of query"));
+        }
+        Ok(Query { collection, filters, projection })
+    }
+
+    fn rest(&self) -> &'q str {
+        &self.src[self.pos..]
+    }
+
+    fn error(&self, expected: &str) -> anyhow::Error {
+        anyhow::anyhow!("Failed to parse query at offset {}: expected {}", self.pos, expected)
+    }
+
+    fn skip_ws(&mut self) {
+        self.pos = self.src.len() - self.rest().trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, token: &str) -> anyhow::Result<()> {
+        if self.eat(token) { Ok(()) } else { Err(self.error(&format!("\"{}\"", token))) }
+    }
+
+    fn ident(&mut self) -> anyhow::Result<&'q str> {
+        self.skip_ws();
+        let len = self.rest().find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(self.error("a name"));
+        }
+        let ident = &self.rest()[..len];
+        self.pos += len;
+        Ok(ident)
+    }
+
+    fn op(&mut self) -> anyhow::Result<Op> {
+        // Two-character operators first, so `<=` is not read as `<`.
+        for (token, op) in [("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("=", Op::Eq), ("<", Op::Lt), (">", Op::Gt)] {
+            if self.eat(token) {
+                return Ok(op);
+            }
+        }
+        Err(self.error("a comparison operator"))
+    }
+
+    fn value(&mut self) -> anyhow::Result<&'q str> {
+        if self.eat("\"") {
+            let len = self.rest().find('"').ok_or_else(|| self.error("a closing quote"))?;
+            let value = &self.rest()[..len];
+            self.pos += len + 1;
+            return Ok(value);
+        }
+        self.skip_ws();
+        let len = self.rest().find(']').unwrap_or(self.rest().len());
+        let value = self.rest()[..len].trim_end();
+        if value.is_empty() {
+            return Err(self.error("a value"));
+        }
+        self.pos += value.len();
+        Ok(value)
+    }
+}
+
+// A number with an optional `kb` or `mb` suffix (binary units).
+fn parse_number(value: &str) -> Option<u64> {
+    let lower = value.to_ascii_lowercase();
+    let (digits, scale) = match lower.strip_suffix("kb") {
+        Some(digits) => (digits, KIB),
+        None => match lower.strip_suffix("mb") {
+            Some(digits) => (digits, MIB),
+            None => (lower.as_str(), 1),
+        },
+    };
+    digits.trim_end().parse::<u64>().ok()?.checked_mul(scale)
+}
+
+fn matches(record: &AwwasmQueryRecord, collection: &str, filter: &Filter) -> anyhow::Result<bool> {
+    let value = field(record, collection, filter.field)?;
+    match value {
+        AwwasmQueryValue::Num(n) => {
+            let expected = parse_number(filter.value)
+                .ok_or_else(|| anyhow::anyhow!("Field \"{}\" is a number, not \"{}\"", filter.field, filter.value))?;
+            Ok(filter.op.holds(n.cmp(&expected)))
+        }
+        AwwasmQueryValue::Str(s) => match filter.op {
+            Op::Eq | Op::Ne => Ok(filter.op.holds(s.as_str().cmp(filter.value))),
+            op => Err(anyhow::anyhow!("Field \"{}\" is a string and cannot be compared with {}", filter.field, op.symbol())),
+        },
+    }
+}
+
+fn field<'r>(record: &'r AwwasmQueryRecord, collection: &str, name: &str) -> anyhow::Result<&'r AwwasmQueryValue> {
+    record.iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+        .ok_or_else(|| anyhow::anyhow!("Unknown field \"{}\" for {}", name, collection))
+}
+
+fn num(n: impl Into<u64>) -> AwwasmQueryValue {
+    AwwasmQueryValue::Num(n.into())
+}
+
+fn string(s: impl Into<String>) -> AwwasmQueryValue {
+    AwwasmQueryValue::Str(s.into())
+}
+
+fn name(n: &AwwasmName) -> AwwasmQueryValue {
+    string(String::from_utf8_lossy(&n.bytes))
+}
+
+fn yes_no(b: bool) -> AwwasmQueryValue {
+    string(if b { "true" } else { "false" })
+}
+
+fn import_kind(kind: &AwwasmImportKind) -> &'static str {
+    match kind {
+        AwwasmImportKind::Function => "func",
+        AwwasmImportKind::Table => "table",
+        AwwasmImportKind::Memory => "memory",
+        AwwasmImportKind::Global => "global",
+        AwwasmImportKind::Tag => "tag",
+    }
+}
+
+fn export_kind(kind: &AwwasmExportKind) -> &'static str {
+    match kind {
+        AwwasmExportKind::Function => "func",
+        AwwasmExportKind::Table => "table",
+        AwwasmExportKind::Memory => "memory",
+        AwwasmExportKind::Global => "global",
+        AwwasmExportKind::Tag => "tag",
+    }
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Evaluate a query such as `exports[kind=func].name` or
+    /// `funcs[size>10kb].count`.
+    ///
+    /// A query names a collection (`types`, `imports`, `exports`, `funcs`,
+    /// `globals`, `memories`, `data`, `customs`), then any number of
+    /// `[field op value]` filters that must all hold, then optionally `.count`
+    /// or `.field`. Operators are `=`, `!=`, `<`, `<=`, `>`, `>=`; strings only
+    /// support the first two. Numbers accept `kb` and `mb` suffixes.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn query(&self, query: &str) -> anyhow::Result<AwwasmQueryResult> {
+        let query = QueryParser::parse(query)?;
+        let mut records = Vec::new();
+        for record in self.query_records(query.collection)? {
+            let mut keep = true;
+            for filter in &query.filters {
+                keep &= matches(&record, query.collection, filter)?;
+            }
+            if keep {
+                records.push(record);
+            }
+        }
+
+        match query.projection {
+            Some("count") => Ok(AwwasmQueryResult::Count(records.len())),
+            Some(projection) => {
+                let values = records.iter()
+                    .map(|r| field(r, query.collection, projection).cloned())
+                    .collect::<anyhow::Result<_>>()?;
+                Ok(AwwasmQueryResult::Values(values))
+            }
+            None => Ok(AwwasmQueryResult::Records(records)),
+        }
+    }
+
+    fn query_records(&self, collection: &str) -> anyhow::Result<Vec<AwwasmQueryRecord>> {
+        Ok(match collection {
+            "types" => self.types().iter().enumerate().map(|(i, t)| vec![
+                ("index", num(i as u32)),
+                ("params", num(t.fn_args.len() as u32)),
+                ("results", num(t.fn_rets.len() as u32)),
+            ]).collect(),
+            "imports" => self.imports().iter().map(|i| vec![
+                ("module", name(&i.module)),
+                ("name", name(&i.name)),
+                ("kind", string(import_kind(&i.kind))),
+            ]).collect(),
+            "exports" => self.exports().iter().map(|e| vec![
+                ("name", name(&e.name)),
+                ("kind", string(export_kind(&e.kind))),
+                ("index", num(e.index)),
+            ]).collect(),
+            "funcs" => {
+                let names = self.names()?;
+                self.index_spaces().funcs().map(|(i, func)| {
+                    let func_name = names.as_ref().and_then(|n| n.function_name(i)).unwrap_or_default();
+                    let (size, imported) = match func {
+                        AwwasmIndexed::Imported(_) => (0, true),
+                        AwwasmIndexed::Defined(def) => (def.code.map_or(0, |c| c.fn_body_size), false),
+                    };
+                    vec![
+                        ("index", num(i)),
+                        ("name", string(func_name)),
+                        ("type", num(func.type_idx())),
+                        ("size", num(size)),
+                        ("imported", yes_no(imported)),
+                    ]
+                }).collect()
+            }
+            "globals" => self.globals().iter().enumerate().map(|(i, g)| vec![
+                ("index", num(i as u32)),
+                ("type", string(g.value_type.to_string())),
+                ("mutable", yes_no(g.mutability == AwwasmGlobalMutability::Mutable)),
+            ]).collect(),
+            "memories" => self.memories().iter().enumerate().map(|(i, m)| {
+                let mut record = vec![("index", num(i as u32)), ("min", num(m.limits.min))];
+                record.extend(m.limits.max.map(|max| ("max", num(max))));
+                record.extend(m.limits.page_size_log2.map(|_| ("page_size", num(m.limits.page_size()))));
+                record
+            }).collect(),
+            "data" => self.data().iter().enumerate().map(|(i, d)| vec![
+                ("index", num(i as u32)),
+                ("size", num(d.size)),
+                ("active", yes_no(d.header.offset.is_some())),
+            ]).collect(),
+            "customs" => self.customs().iter().map(|c| vec![
+                ("name", name(&c.name)),
+                ("size", num(c.payload.len() as u64)),
+            ]).collect(),
+            other => return Err(anyhow::anyhow!("Unknown query collection \"{}\"", other)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+    use crate::components::query::{AwwasmQueryResult, AwwasmQueryValue};
+
+    const MODULE: &str = r#"
+        (module
+            (import "env" "log" (func $log (param i32)))
+            (memory (export "memory") 1)
+            (func $small (export "small"))
+            (func $big (export "big") (param i32)
+                (call $log (local.get 0)) (call $log (local.get 0)) (call $log (local.get 0)))
+            (global (export "counter") (mut i32) (i32.const 0))
+        )
+    "#;
+
+    #[test]
+    fn query_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(MODULE)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        assert_eq!(module_parsed.query("exports[kind=func].name")?.to_string(), "small\nbig\n");
+        assert_eq!(module_parsed.query("funcs[size>4][imported=false].name")?, AwwasmQueryResult::Values(vec![
+            AwwasmQueryValue::Str("big".to_owned()),
+        ]));
+        assert_eq!(module_parsed.query("funcs[size >= 1kb].count")?, AwwasmQueryResult::Count(0));
+        assert_eq!(module_parsed.query(r#"imports[ name = "log" ]"#)?.to_string(),
+            "{\"module\":\"env\",\"name\":\"log\",\"kind\":\"func\"}\n");
+        assert_eq!(module_parsed.query("globals[mutable=true].count")?.to_string(), "1\n");
+        Ok(())
+    }
+
+    #[test]
+    fn query_errors_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(MODULE)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let err = |q: &str| module_parsed.query(q).unwrap_err().to_string();
+        assert_eq!(err("tables.count"), "Unknown query collection \"tables\"");
+        assert_eq!(err("exports.size"), "Unknown field \"size\" for exports");
+        assert_eq!(err("exports[name>a]"), "Field \"name\" is a string and cannot be compared with >");
+        assert_eq!(err("funcs[size=big]"), "Field \"size\" is a number, not \"big\"");
+        assert_eq!(err("exports[kind=func"), "Failed to parse query at offset 17: expected \"]\"");
+        Ok(())
+    }
+}
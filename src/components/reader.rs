@@ -0,0 +1,134 @@
+use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+use crate::components::features::AwwasmParseOptions;
+use crate::components::module::{AwwasmModule, AwwasmModulePreamble};
+use crate::components::owned::AwwasmModuleOwned;
+use crate::components::parse_limits::exceeds;
+use crate::components::section::{AwwasmSectionHeader, TruncatedSection};
+use crate::consts::{WASM_PREAMBLE_MAGIC_SIZE_BYTES, WASM_PREAMBLE_VERSION_SIZE_BYTES};
+use crate::limits::MAX_WASM_MODULE_SIZE;
+use nom::combinator::complete;
+use nom_derive::Parse;
+use std::io::{self, Read};
+
+const PREAMBLE_BYTES: usize = WASM_PREAMBLE_MAGIC_SIZE_BYTES + WASM_PREAMBLE_VERSION_SIZE_BYTES;
+// Longest encoding of a section size.
+const MAX_LEB128_U32_BYTES: usize = 5;
+
+// Read one byte, or `None` at the end of the input.
+fn read_byte<R: Read>(r: &mut R) -> io::Result<Option<u8>> {
+    let mut byte = [0u8; 1];
+    loop {
+        match r.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl AwwasmModule<'_> {
+    /// Read a module from `reader` a section at a time and resolve it.
+    ///
+    /// The preamble is checked before anything else is read, and each
+    /// section header before its body, so a binary that is not a module or
+    /// has a bad header fails without reading on. A section body is read
+    /// only as far as the input goes, whatever size its header declares.
+    /// Modules over `MAX_WASM_MODULE_SIZE` (1 GiB) are rejected.
+    pub fn from_reader<R: Read>(reader: R) -> anyhow::Result<AwwasmModuleOwned> {
+        AwwasmModuleOwned::new(read_module(reader, usize::MAX)?)
+    }
+
+    /// Like `from_reader`, but parsed with `new_with_options()`. Reading
+    /// stops as soon as the module has more sections than `options.limits`
+    /// allow.
+    pub fn from_reader_with_options<R: Read>(reader: R, options: &AwwasmParseOptions) -> anyhow::Result<AwwasmModuleOwned> {
+        AwwasmModuleOwned::new_with_options(read_module(reader, options.limits.max_sections)?, options)
+    }
+}
+
+// Read a module of at most `max_sections` sections from `reader`, checking
+// each header before reading the body it announces.
+fn read_module<R: Read>(mut reader: R, max_sections: usize) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    reader.by_ref().take(PREAMBLE_BYTES as u64).read_to_end(&mut buf)?;
+    AwwasmModulePreamble::new(&buf)?.check_core_module()?;
+
+    let mut sections = 0;
+    while let Some(id) = read_byte(&mut reader)? {
+        sections += 1;
+        exceeds("sections", None, sections, max_sections as u64)?;
+        let header_start = buf.len();
+        buf.push(id);
+        for _ in 0..MAX_LEB128_U32_BYTES {
+            let Some(byte) = read_byte(&mut reader)? else { break };
+            buf.push(byte);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let start = buf.as_ptr() as usize;
+        let header_bytes = &buf[header_start..];
+        let (_, header) = complete(AwwasmSectionHeader::parse)(header_bytes).map_err(|e| {
+            let mut err = AwwasmParseError::section_header("section header", e).within(header_bytes);
+            err.locate(start, None, None);
+            err
+        })?;
+        let size = header.section_size as usize;
+        if buf.len().saturating_add(size) > MAX_WASM_MODULE_SIZE {
+            let detail = format!("the module is over the {} byte limit", MAX_WASM_MODULE_SIZE);
+            return Err(AwwasmParseError::new("module", AwwasmParseErrorKind::Malformed { detail }).into());
+        }
+        let read = reader.by_ref().take(size as u64).read_to_end(&mut buf)?;
+        if read < size {
+            return Err(AwwasmParseError::from(TruncatedSection { section: header.section_type, declared: header.section_size, available: read }).into());
+        }
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+    use crate::components::module::AwwasmModule;
+    use std::io::{Cursor, Read};
+
+    // Counts the bytes read through it.
+    struct Counting<R>(R, usize);
+
+    impl<R: Read> Read for Counting<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.read(buf)?;
+            self.1 += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn from_reader_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"(module (memory 1) (func (export "run")) (data (i32.const 0) "hi"))"#)?;
+        let owned = AwwasmModule::from_reader(Cursor::new(&bytes))?;
+        assert_eq!(owned.bytes()[..], bytes[..]);
+        assert_eq!(owned.module().exports.as_ref().map(Vec::len), Some(1));
+
+        // Only the preamble is read from a binary that is not a module.
+        let mut not_wasm = Counting(Cursor::new(vec![0x7fu8; 4096]), 0);
+        let err = AwwasmModule::from_reader(&mut not_wasm).unwrap_err();
+        assert_eq!(err.downcast_ref::<AwwasmParseError>().map(|e| e.kind.clone()), Some(AwwasmParseErrorKind::BadMagic));
+        assert_eq!(not_wasm.1, 8);
+
+        // An unknown section id stops reading after its header.
+        let mut unknown = bytes[..8].to_vec();
+        unknown.extend_from_slice(&[0x0e, 0x80, 0x80, 0x04]);
+        unknown.resize(1 << 17, 0);
+        let mut unknown = Counting(Cursor::new(unknown), 0);
+        let err = AwwasmModule::from_reader(&mut unknown).unwrap_err();
+        let err = err.downcast_ref::<AwwasmParseError>().expect("should be an AwwasmParseError");
+        assert_eq!((err.kind.clone(), err.offset), (AwwasmParseErrorKind::UnknownSection { id: 0x0e }, Some(8)));
+        assert_eq!(unknown.1, 12);
+
+        let err = AwwasmModule::from_reader(Cursor::new(&bytes[..bytes.len() - 1])).unwrap_err();
+        assert!(matches!(err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind), Some(AwwasmParseErrorKind::TruncatedSection(_))));
+        Ok(())
+    }
+}
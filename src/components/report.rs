@@ -0,0 +1,223 @@
+use crate::components::features::AwwasmFeatures;
+use crate::components::lint::{module_size, web_budget, Budget};
+use crate::components::module::AwwasmModule;
+use crate::components::section::SectionCode;
+use crate::json;
+
+// Points deducted from a perfect score of 100 per issue of each severity.
+const ERROR_PENALTY: u32 = 25;
+const WARNING_PENALTY: u32 = 10;
+const INFO_PENALTY: u32 = 2;
+
+/// Which part of the report an issue comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmHealthCategory {
+    /// A finding of `AwwasmModule::validate()`.
+    Validation,
+    /// A web budget finding or other size concern.
+    Size,
+}
+
+impl AwwasmHealthCategory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AwwasmHealthCategory::Validation => "validation",
+            AwwasmHealthCategory::Size => "size",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwwasmHealthSeverity {
+    /// The module is invalid and will not instantiate.
+    Error,
+    Warning,
+    Info,
+}
+
+impl AwwasmHealthSeverity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AwwasmHealthSeverity::Error => "error",
+            AwwasmHealthSeverity::Warning => "warning",
+            AwwasmHealthSeverity::Info => "info",
+        }
+    }
+
+    fn penalty(&self) -> u32 {
+        match self {
+            AwwasmHealthSeverity::Error => ERROR_PENALTY,
+            AwwasmHealthSeverity::Warning => WARNING_PENALTY,
+            AwwasmHealthSeverity::Info => INFO_PENALTY,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmHealthIssue {
+    pub category: AwwasmHealthCategory,
+    pub severity: AwwasmHealthSeverity,
+    /// Name of the check or budget rule that raised it (e.g. `"exports"`, `"function-size"`).
+    pub rule: &'static str,
+    pub message: String,
+}
+
+/// Byte counts of the encoded module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AwwasmSizeMetrics {
+    pub total_bytes: u64,
+    /// Code section body.
+    pub code_bytes: u64,
+    /// Data section body.
+    pub data_bytes: u64,
+    /// All custom sections, headers included.
+    pub custom_bytes: u64,
+    pub function_count: u32,
+    pub largest_function_bytes: u64,
+}
+
+/// Everything `health()` knows about a module, with an overall score.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmHealthReport {
+    /// 0 to 100: 100 minus 25 per error, 10 per warning and 2 per info issue.
+    pub score: u8,
+    pub size: AwwasmSizeMetrics,
+    pub features: AwwasmFeatures,
+    pub issues: Vec<AwwasmHealthIssue>,
+}
+
+impl AwwasmHealthReport {
+    /// The report as a single JSON object:
+    /// `{"score":..,"size":{..},"features":[..],"issues":[{"category","severity","rule","message"}]}`.
+    pub fn to_json(&self) -> String {
+        let size = format!(
+            "{{\"total_bytes\":{},\"code_bytes\":{},\"data_bytes\":{},\"custom_bytes\":{},\"function_count\":{},\"largest_function_bytes\":{}}}",
+            self.size.total_bytes, self.size.code_bytes, self.size.data_bytes,
+            self.size.custom_bytes, self.size.function_count, self.size.largest_function_bytes,
+        );
+        let features: Vec<String> = self.features.names().into_iter().map(json::string).collect();
+        let issues: Vec<String> = self.issues.iter().map(|i| format!(
+            "{{\"category\":\"{}\",\"severity\":\"{}\",\"rule\":{},\"message\":{}}}",
+            i.category.name(), i.severity.name(), json::string(i.rule), json::string(&i.message),
+        )).collect();
+        format!(
+            "{{\"score\":{},\"size\":{},\"features\":[{}],\"issues\":[{}]}}",
+            self.score, size, features.join(","), issues.join(","),
+        )
+    }
+}
+
+fn size_metrics(module: &AwwasmModule) -> AwwasmSizeMetrics {
+    let mut size = AwwasmSizeMetrics { total_bytes: module_size(module), ..Default::default() };
+    for section in module.sections() {
+        let header = &section.section_header;
+        match header.section_type {
+            SectionCode::Code => size.code_bytes = header.section_size as u64,
+            SectionCode::Data => size.data_bytes = header.section_size as u64,
+            SectionCode::Custom => size.custom_bytes += section.encoding.len() as u64,
+            _ => {}
+        }
+    }
+    for item in module.code() {
+        size.function_count += 1;
+        size.largest_function_bytes = size.largest_function_bytes.max(item.fn_body_size as u64);
+    }
+    size
+}
+
+/// Combine validation findings, web budget findings (with `Budget::default()`),
+/// feature usage and size metrics into one report.
+///
+/// Requires `resolve_all_sections()`.
+pub fn health(module: &AwwasmModule) -> AwwasmHealthReport {
+    let size = size_metrics(module);
+    let mut issues: Vec<AwwasmHealthIssue> = module.validate().into_iter()
+        .map(|f| AwwasmHealthIssue {
+            category: AwwasmHealthCategory::Validation,
+            severity: AwwasmHealthSeverity::Error,
+            rule: f.check,
+            message: f.to_string(),
+        })
+        .collect();
+    issues.extend(web_budget(module, Budget::default()).into_iter().map(|f| AwwasmHealthIssue {
+        category: AwwasmHealthCategory::Size,
+        severity: AwwasmHealthSeverity::Warning,
+        rule: f.rule.name(),
+        message: f.to_string(),
+    }));
+    if size.custom_bytes * 2 > size.total_bytes {
+        issues.push(AwwasmHealthIssue {
+            category: AwwasmHealthCategory::Size,
+            severity: AwwasmHealthSeverity::Info,
+            rule: "custom-sections",
+            message: format!("custom sections are {} of {} bytes; strip debug info before shipping", size.custom_bytes, size.total_bytes),
+        });
+    }
+
+    let penalty: u32 = issues.iter().map(|i| i.severity.penalty()).sum();
+    AwwasmHealthReport {
+        score: 100u32.saturating_sub(penalty) as u8,
+        size,
+        features: module.features_used(),
+        issues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+    use crate::components::report::{health, AwwasmHealthCategory, AwwasmHealthSeverity};
+
+    #[test]
+    fn health_report_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1)
+                (func (export "f") (param i32) (result i32)
+                    (i32.extend8_s (local.get 0)))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let report = health(&module_parsed);
+        assert_eq!(report.score, 100);
+        assert!(report.issues.is_empty());
+        assert_eq!(report.size.total_bytes, module.len() as u64);
+        assert_eq!((report.size.function_count, report.size.largest_function_bytes), (1, 5));
+        assert_eq!(report.to_json(), format!(
+            "{{\"score\":100,\"size\":{{\"total_bytes\":{},\"code_bytes\":7,\"data_bytes\":0,\"custom_bytes\":0,\
+             \"function_count\":1,\"largest_function_bytes\":5}},\"features\":[\"sign-extension\"],\"issues\":[]}}",
+            module.len(),
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn health_report_issues_test() -> anyhow::Result<()> {
+        // Hand-built: one function exported twice under the same name, plus
+        // a 64-byte custom section that outweighs the rest of the module.
+        let module: Vec<u8> = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[0x01, 0x04, 0x01, 0x60, 0x00, 0x00],
+            &[0x03, 0x02, 0x01, 0x00],
+            &[0x07, 0x09, 0x02, 0x01, b'a', 0x00, 0x00, 0x01, b'a', 0x00, 0x00],
+            &[0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b],
+            &[0x00, 0x40, 0x03, b'p', b'a', b'd'],
+            &[0u8; 60],
+        ].concat();
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let report = health(&module_parsed);
+        let issues: Vec<_> = report.issues.iter().map(|i| (i.category, i.severity, i.rule)).collect();
+        assert_eq!(issues, vec![
+            (AwwasmHealthCategory::Validation, AwwasmHealthSeverity::Error, "exports"),
+            (AwwasmHealthCategory::Size, AwwasmHealthSeverity::Info, "custom-sections"),
+        ]);
+        assert_eq!(report.score, 73);
+        assert_eq!(report.size.custom_bytes, 66);
+        assert!(report.to_json().contains("\"message\":\"[exports] duplicate export name \\\"a\\\"\""));
+        Ok(())
+    }
+}
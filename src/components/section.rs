@@ -1,14 +1,14 @@
 use num_derive::FromPrimitive;
 use nom_derive::*;
-use nom_leb128::leb128_u32;
+use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+use crate::components::leb128::leb128_u32;
 use nom::bytes::streaming::take;
-use nom::multi::count;
-use nom::combinator::cond;
 use crate::components::types::*;
+use std::fmt;
 
 // Helper: number of bytes needed to encode a u32 in unsigned LEB128
 #[inline]
-fn leb128_len_u32(mut v: u32) -> u32 {
+pub(crate) fn leb128_len_u32(mut v: u32) -> u32 {
     let mut len: u32 = 1;
     while v >= 0x80 {
         v >>= 7;
@@ -17,12 +17,42 @@ fn leb128_len_u32(mut v: u32) -> u32 {
     len
 }
 
+// Helper: append `v` to `out` in unsigned LEB128
+pub(crate) fn write_leb128_u32(out: &mut Vec<u8>, mut v: u32) {
+    while v >= 0x80 {
+        out.push((v as u8 & 0x7f) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+// Helper: append `v` to `out` in unsigned LEB128 stretched to exactly
+// `width` bytes with continuation bits (at most 5, and at least the
+// canonical length), which decodes to the same value
+pub(crate) fn write_leb128_u32_padded(out: &mut Vec<u8>, mut v: u32, width: usize) {
+    for _ in 1..width {
+        out.push((v as u8 & 0x7f) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+// Helper: append non-negative `v` to `out` in signed LEB128 (as for s33 type
+// indices), where the last byte's 0x40 bit is the sign and must be clear
+pub(crate) fn write_leb128_s33(out: &mut Vec<u8>, mut v: u32) {
+    while v >= 0x40 {
+        out.push((v as u8 & 0x7f) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
 /// Section IDs as defined by the WebAssembly binary format specification.
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq, FromPrimitive, Nom)]
 #[nom(LittleEndian)]
 pub enum SectionCode {
-    /// Custom section — arbitrary name + bytes; skipped during resolve.
+    /// Custom section — arbitrary name + bytes.
     Custom = 0x00,
     /// Type section (function signatures).
     Type = 0x01,
@@ -46,24 +76,81 @@ pub enum SectionCode {
     Code = 0x0a,
     /// Data section (memory initializers).
     Data = 0x0b,
+    /// DataCount section (number of data segments, for bulk memory validation).
+    DataCount = 0x0c,
+    /// Tag section (exception tags; exception handling proposal).
+    Tag = 0x0d,
+}
+
+impl SectionCode {
+    /// Rank of the section in the order a valid module lists them; `None`
+    /// for custom sections, which may appear anywhere. Tag and DataCount
+    /// sections sort by position, not id.
+    pub fn order(&self) -> Option<u8> {
+        use SectionCode::*;
+        Some(match self {
+            Custom => return None,
+            Type => 1,
+            Import => 2,
+            Function => 3,
+            Table => 4,
+            Memory => 5,
+            Tag => 6,
+            Global => 7,
+            Export => 8,
+            Start => 9,
+            Element => 10,
+            DataCount => 11,
+            Code => 12,
+            Data => 13,
+        })
+    }
+}
+
+/// A non-custom section that repeats an earlier one or comes after a
+/// section it must precede, from `AwwasmModule::section_order_issues()`.
+///
+/// Strict parsing fails with it as `AwwasmParseErrorKind::SectionOrder`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionOrderError {
+    pub section: SectionCode,
+    /// Position of the section among all sections, custom ones included.
+    pub position: usize,
+    /// The earlier section it conflicts with: the same kind for a
+    /// duplicate, else one that must come after it.
+    pub after: SectionCode,
+}
+
+impl fmt::Display for SectionOrderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.section == self.after {
+            write!(f, "{:?} section at position {} duplicates an earlier one", self.section, self.position)
+        } else {
+            write!(f, "{:?} section at position {} comes after the {:?} section", self.section, self.position, self.after)
+        }
+    }
 }
 
-/// Resolved section content after calling `AwwasmSection::resolve()`.
+/// Resolved section content after calling `AwwasmSection::resolve()`. A
+/// section with an entry count of 0 resolves to an empty `Vec`.
 pub enum SectionItem<'a> {
-    TypeSectionItems(Option<Vec<AwwasmTypeSectionItem<'a>>>),
-    ImportSectionItems(Option<Vec<AwwasmImportSectionItem<'a>>>),
-    FunctionSectionItems(Option<Vec<AwwasmFuncSectionItem>>),
-    TableSectionItems(Option<Vec<AwwasmTableSectionItem>>),
-    MemorySectionItems(Option<Vec<AwwasmMemorySectionItem>>),
-    GlobalSectionItems(Option<Vec<AwwasmGlobalSectionItem<'a>>>),
-    ExportSectionItems(Option<Vec<AwwasmExportSectionItem<'a>>>),
-    ElementSectionItems(Option<Vec<AwwasmElementSectionItem<'a>>>),
-    CodeSectionItems(Option<Vec<AwwasmCodeSectionItem<'a>>>),
-    DataSectionItems(Option<Vec<AwwasmDataSectionItem<'a>>>),
+    TypeSectionItems(Vec<AwwasmTypeSectionItem<'a>>),
+    ImportSectionItems(Vec<AwwasmImportSectionItem<'a>>),
+    FunctionSectionItems(Vec<AwwasmFuncSectionItem>),
+    TableSectionItems(Vec<AwwasmTableSectionItem>),
+    MemorySectionItems(Vec<AwwasmMemorySectionItem>),
+    GlobalSectionItems(Vec<AwwasmGlobalSectionItem<'a>>),
+    ExportSectionItems(Vec<AwwasmExportSectionItem<'a>>),
+    ElementSectionItems(Vec<AwwasmElementSectionItem<'a>>),
+    CodeSectionItems(Vec<AwwasmCodeSectionItem<'a>>),
+    DataSectionItems(Vec<AwwasmDataSectionItem<'a>>),
+    TagSectionItems(Vec<AwwasmTagSectionItem>),
     /// Start section: contains the start item (or None if section was empty).
     StartSection(Option<AwwasmStartSectionItem>),
-    /// Custom section: body was skipped, nothing to resolve.
-    CustomSection,
+    /// DataCount section: the declared number of data segments.
+    DataCountSection(Option<u32>),
+    /// Custom section: name and raw payload.
+    CustomSection(AwwasmCustomSectionItem<'a>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
@@ -74,61 +161,97 @@ pub struct AwwasmSectionHeader {
     pub section_size: u32,
 }
 
+/// Error returned when a section header declares more bytes than the input
+/// has left.
+///
+/// Surfaced as `AwwasmParseErrorKind::TruncatedSection`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TruncatedSection {
+    pub section: SectionCode,
+    /// Body size from the section header.
+    pub declared: u32,
+    /// Bytes actually left after the header.
+    pub available: usize,
+}
+
+impl fmt::Display for TruncatedSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} section declares {} bytes but only {} are available", self.section, self.declared, self.available)
+    }
+}
+
+/// The bytes of a truncated last section, kept by `AwwasmModule::new_permissive`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmPartialSection<'a> {
+    pub error: TruncatedSection,
+    /// Everything after the section header, up to the end of the input.
+    pub partial_body: &'a [u8],
+}
+
 /// A raw parsed section containing a header and unresolved body bytes.
 ///
 /// Parsing notes:
-/// - **Custom** sections: body is skipped entirely (`entry_count = 0`, `section_body = &[]`).
+/// - **Custom** sections: have no entry count; the whole body (name + payload) is kept in `section_body`.
 /// - **Start** sections: body is just a single funcidx encoded as LEB128, stored in `entry_count`.
+/// - **DataCount** sections: body is just the segment count encoded as LEB128, stored in `entry_count`.
 /// - All other sections follow the standard format: `[entry_count: leb128][body_bytes]`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AwwasmSection<'a> {
     pub section_header: AwwasmSectionHeader,
     /// For standard sections: number of entries.
     /// For Start sections: the funcidx.
+    /// For DataCount sections: the data segment count.
     /// For Custom sections: always 0.
     pub entry_count: u32,
-    /// Raw body bytes (empty for Custom and Start sections).
+    /// Raw body bytes (empty for Start and DataCount sections).
     pub section_body: &'a [u8],
+    /// The whole section as encoded, header included. Unlike `section_body`,
+    /// left in place by `resolve()`.
+    pub encoding: &'a [u8],
 }
 
 impl<'a> nom_derive::Parse<&'a [u8]> for AwwasmSection<'a> {
     fn parse(input: &'a [u8]) -> nom::IResult<&'a [u8], Self> {
+        let start = input;
+        let encoding = |rest: &'a [u8]| &start[..start.len() - rest.len()];
         let (input, section_header) = AwwasmSectionHeader::parse(input)?;
 
         match section_header.section_type {
             SectionCode::Custom => {
-                // Skip the entire custom section body — arbitrary content.
+                // Custom sections have no entry count — keep the whole body.
                 let size = section_header.section_size as usize;
-                let (input, _body) = take(size)(input)?;
+                let (input, section_body) = take(size)(input)?;
                 Ok((input, AwwasmSection {
                     section_header,
                     entry_count: 0,
-                    section_body: &[],
+                    section_body,
+                    encoding: encoding(input),
                 }))
             }
-            SectionCode::Start => {
-                // Start section body is exactly one funcidx encoded as LEB128.
-                // Reuse entry_count to store the funcidx value.
+            SectionCode::Start | SectionCode::DataCount => {
+                // Start and DataCount bodies are exactly one u32 encoded as LEB128
+                // (a funcidx or a segment count). Reuse entry_count to store it.
                 let size = section_header.section_size as usize;
                 let (input, body) = take(size)(input)?;
-                let (_, funcidx) = leb128_u32(body)?;
+                let (_, value) = leb128_u32(body)?;
                 Ok((input, AwwasmSection {
                     section_header,
-                    entry_count: funcidx,
+                    entry_count: value,
                     section_body: &[],
+                    encoding: encoding(input),
                 }))
             }
             _ => {
                 // Standard sections: [entry_count: leb128][body_bytes...]
                 let (input, entry_count) = leb128_u32(input)?;
                 let body_size = section_header.section_size
-                    .checked_sub(leb128_len_u32(entry_count))
-                    .unwrap_or(0) as usize;
+                    .saturating_sub(leb128_len_u32(entry_count)) as usize;
                 let (input, section_body) = take(body_size)(input)?;
                 Ok((input, AwwasmSection {
                     section_header,
                     entry_count,
                     section_body,
+                    encoding: encoding(input),
                 }))
             }
         }
@@ -136,10 +259,35 @@ impl<'a> nom_derive::Parse<&'a [u8]> for AwwasmSection<'a> {
 }
 
 impl<'a> AwwasmSection<'a> {
+    /// The section exactly as it is encoded in the module: the id byte, the
+    /// size LEB128 and the body. Available before and after `resolve()`.
+    pub fn raw_bytes_with_header(&self) -> &'a [u8] {
+        self.encoding
+    }
+
     /// Resolve this section's raw body bytes into typed `SectionItem` contents.
     pub fn resolve(&mut self) -> anyhow::Result<SectionItem<'a>> {
+        match self.resolve_partial()? {
+            (items, None) => Ok(items),
+            (_, Some(error)) => Err(error.into()),
+        }
+    }
+
+    /// Like `resolve`, but if an entry fails to decode, keep the entries
+    /// before it: returns them with the error that stopped decoding. Fails
+    /// only if nothing of the section can be decoded.
+    pub fn resolve_partial(&mut self) -> anyhow::Result<(SectionItem<'a>, Option<AwwasmParseError>)> {
+        let entries = usize::try_from(self.entry_count).map_err(|_| AwwasmParseError::new(
+            format!("{:?} Section", self.section_header.section_type),
+            AwwasmParseErrorKind::Malformed { detail: format!("entry count {} does not fit in usize", self.entry_count) },
+        ))?;
         match self.section_header.section_type {
-            SectionCode::Custom => Ok(SectionItem::CustomSection),
+            SectionCode::Custom => {
+                let (name_end, name) = AwwasmName::parse(self.section_body)
+                    .map_err(|e| AwwasmParseError::nom("Custom Section name", e).within(self.section_body))?;
+                self.section_body = &[];
+                Ok((SectionItem::CustomSection(AwwasmCustomSectionItem { name, payload: name_end }), None))
+            }
             SectionCode::Start => {
                 // entry_count holds the funcidx (set during parsing)
                 let item = if self.section_header.section_size > 0 {
@@ -147,98 +295,52 @@ impl<'a> AwwasmSection<'a> {
                 } else {
                     None
                 };
-                Ok(SectionItem::StartSection(item))
-            }
-            SectionCode::Type => {
-                let mut types: Option<Vec<AwwasmTypeSectionItem<'a>>> = None;
-                (self.section_body, types) = cond(
-                    !self.section_body.is_empty(),
-                    count(AwwasmTypeSectionItem::<'_>::parse, self.entry_count.try_into().unwrap()),
-                )(self.section_body)
-                .map_err(|e| anyhow::anyhow!("Failed to parse WASM Type Section: {}", e))?;
-                Ok(SectionItem::TypeSectionItems(types))
-            }
-            SectionCode::Import => {
-                let mut imports: Option<Vec<AwwasmImportSectionItem<'a>>> = None;
-                (self.section_body, imports) = cond(
-                    !self.section_body.is_empty(),
-                    count(AwwasmImportSectionItem::<'_>::parse, self.entry_count.try_into().unwrap()),
-                )(self.section_body)
-                .map_err(|e| anyhow::anyhow!("Failed to parse WASM Import Section: {}", e))?;
-                Ok(SectionItem::ImportSectionItems(imports))
-            }
-            SectionCode::Function => {
-                let mut funcs: Option<Vec<AwwasmFuncSectionItem>> = None;
-                (self.section_body, funcs) = cond(
-                    !self.section_body.is_empty(),
-                    count(AwwasmFuncSectionItem::parse, self.entry_count.try_into().unwrap()),
-                )(self.section_body)
-                .map_err(|e| anyhow::anyhow!("Failed to parse WASM Function Section: {}", e))?;
-                Ok(SectionItem::FunctionSectionItems(funcs))
-            }
-            SectionCode::Table => {
-                let mut tables: Option<Vec<AwwasmTableSectionItem>> = None;
-                (self.section_body, tables) = cond(
-                    !self.section_body.is_empty(),
-                    count(AwwasmTableSectionItem::parse, self.entry_count.try_into().unwrap()),
-                )(self.section_body)
-                .map_err(|e| anyhow::anyhow!("Failed to parse WASM Table Section: {}", e))?;
-                Ok(SectionItem::TableSectionItems(tables))
-            }
-            SectionCode::Memory => {
-                let mut memories: Option<Vec<AwwasmMemorySectionItem>> = None;
-                (self.section_body, memories) = cond(
-                    !self.section_body.is_empty(),
-                    count(AwwasmMemorySectionItem::parse, self.entry_count.try_into().unwrap()),
-                )(self.section_body)
-                .map_err(|e| anyhow::anyhow!("Failed to parse WASM Memory Section: {}", e))?;
-                Ok(SectionItem::MemorySectionItems(memories))
+                Ok((SectionItem::StartSection(item), None))
             }
-            SectionCode::Global => {
-                let mut globals: Option<Vec<AwwasmGlobalSectionItem<'a>>> = None;
-                (self.section_body, globals) = cond(
-                    !self.section_body.is_empty(),
-                    count(AwwasmGlobalSectionItem::<'_>::parse, self.entry_count.try_into().unwrap()),
-                )(self.section_body)
-                .map_err(|e| anyhow::anyhow!("Failed to parse WASM Global Section: {}", e))?;
-                Ok(SectionItem::GlobalSectionItems(globals))
+            SectionCode::DataCount => {
+                // entry_count holds the declared segment count (set during parsing)
+                let count = (self.section_header.section_size > 0).then_some(self.entry_count);
+                Ok((SectionItem::DataCountSection(count), None))
             }
-            SectionCode::Export => {
-                let mut exports: Option<Vec<AwwasmExportSectionItem<'a>>> = None;
-                (self.section_body, exports) = cond(
-                    !self.section_body.is_empty(),
-                    count(AwwasmExportSectionItem::<'_>::parse, self.entry_count.try_into().unwrap()),
-                )(self.section_body)
-                .map_err(|e| anyhow::anyhow!("Failed to parse WASM Export Section: {}", e))?;
-                Ok(SectionItem::ExportSectionItems(exports))
-            }
-            SectionCode::Element => {
-                let mut elements: Option<Vec<AwwasmElementSectionItem<'a>>> = None;
-                (self.section_body, elements) = cond(
-                    !self.section_body.is_empty(),
-                    count(AwwasmElementSectionItem::<'_>::parse, self.entry_count.try_into().unwrap()),
-                )(self.section_body)
-                .map_err(|e| anyhow::anyhow!("Failed to parse WASM Element Section: {}", e))?;
-                Ok(SectionItem::ElementSectionItems(elements))
-            }
-            SectionCode::Code => {
-                let mut code: Option<Vec<AwwasmCodeSectionItem<'a>>> = None;
-                (self.section_body, code) = cond(
-                    !self.section_body.is_empty(),
-                    count(AwwasmCodeSectionItem::<'_>::parse, self.entry_count.try_into().unwrap()),
-                )(self.section_body)
-                .map_err(|e| anyhow::anyhow!("Failed to parse WASM Code Section: {}", e))?;
-                Ok(SectionItem::CodeSectionItems(code))
-            }
-            SectionCode::Data => {
-                let mut data: Option<Vec<AwwasmDataSectionItem<'a>>> = None;
-                (self.section_body, data) = cond(
-                    !self.section_body.is_empty(),
-                    count(AwwasmDataSectionItem::<'_>::parse, self.entry_count.try_into().unwrap()),
-                )(self.section_body)
-                .map_err(|e| anyhow::anyhow!("Failed to parse WASM Data Section: {}", e))?;
-                Ok(SectionItem::DataSectionItems(data))
+            SectionCode::Type => self.items("Type Section", entries, AwwasmTypeSectionItem::<'_>::parse, SectionItem::TypeSectionItems),
+            SectionCode::Import => self.items("Import Section", entries, AwwasmImportSectionItem::<'_>::parse, SectionItem::ImportSectionItems),
+            SectionCode::Function => self.items("Function Section", entries, AwwasmFuncSectionItem::parse, SectionItem::FunctionSectionItems),
+            SectionCode::Table => self.items("Table Section", entries, AwwasmTableSectionItem::parse, SectionItem::TableSectionItems),
+            SectionCode::Memory => self.items("Memory Section", entries, AwwasmMemorySectionItem::parse, SectionItem::MemorySectionItems),
+            SectionCode::Global => self.items("Global Section", entries, AwwasmGlobalSectionItem::<'_>::parse, SectionItem::GlobalSectionItems),
+            SectionCode::Export => self.items("Export Section", entries, AwwasmExportSectionItem::<'_>::parse, SectionItem::ExportSectionItems),
+            SectionCode::Element => self.items("Element Section", entries, AwwasmElementSectionItem::<'_>::parse, SectionItem::ElementSectionItems),
+            SectionCode::Code => self.items("Code Section", entries, AwwasmCodeSectionItem::<'_>::parse, SectionItem::CodeSectionItems),
+            SectionCode::Data => self.items("Data Section", entries, AwwasmDataSectionItem::<'_>::parse, SectionItem::DataSectionItems),
+            SectionCode::Tag => self.items("Tag Section", entries, AwwasmTagSectionItem::parse, SectionItem::TagSectionItems),
+        }
+    }
+
+    // Decode up to `entries` entries of a vector section with `parse`,
+    // stopping at the first that fails.
+    fn items<T>(
+        &mut self,
+        what: &str,
+        entries: usize,
+        parse: fn(&'a [u8]) -> nom::IResult<&'a [u8], T>,
+        wrap: fn(Vec<T>) -> SectionItem<'a>,
+    ) -> anyhow::Result<(SectionItem<'a>, Option<AwwasmParseError>)> {
+        // Cap the up-front allocation, as `nom::multi::count` does, so a
+        // hostile entry count cannot reserve gigabytes.
+        let mut items = Vec::with_capacity(entries.min(4096));
+        let mut error = None;
+        for _ in 0..entries {
+            match parse(self.section_body) {
+                Ok((rest, item)) => {
+                    items.push(item);
+                    self.section_body = rest;
+                }
+                Err(e) => {
+                    error = Some(AwwasmParseError::nom(what, e).within(self.section_body));
+                    break;
+                }
             }
         }
+        Ok((wrap(items), error))
     }
 }
@@ -0,0 +1,152 @@
+use crate::components::error::{locate, AwwasmParseError};
+use crate::components::instructions::AwwasmInstruction;
+use crate::components::leb128::leb128_u32;
+use crate::components::module::AwwasmModule;
+use crate::components::section::{AwwasmSection, AwwasmSectionHeader, SectionCode};
+use crate::components::types::*;
+use nom::IResult;
+use nom_derive::Parse;
+use std::ops::Range;
+
+/// Byte ranges of a module's sections and their entries, as offsets into
+/// the module binary, from `AwwasmModule::spans()`.
+///
+/// Each list is parallel to the entries of its section: `imports[i]` is
+/// where the `i`th import is encoded. For a section that occurs more than
+/// once, the spans are those of the last occurrence, as with resolved items.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AwwasmSpanTable {
+    /// Every section, header included, parallel to `AwwasmModule::sections`.
+    pub sections: Vec<Range<usize>>,
+    pub types: Vec<Range<usize>>,
+    pub imports: Vec<Range<usize>>,
+    pub funcs: Vec<Range<usize>>,
+    pub tables: Vec<Range<usize>>,
+    pub memories: Vec<Range<usize>>,
+    pub globals: Vec<Range<usize>>,
+    pub exports: Vec<Range<usize>>,
+    pub elements: Vec<Range<usize>>,
+    /// Function bodies, each with its size field.
+    pub code: Vec<Range<usize>>,
+    pub data: Vec<Range<usize>>,
+    pub tags: Vec<Range<usize>>,
+}
+
+// Spans of the `count` entries at the start of `input`, each read by `parse`.
+fn entries<'a, T>(input: &'a [u8], count: u32, start: usize, parse: fn(&'a [u8]) -> IResult<&'a [u8], T>, what: &str) -> anyhow::Result<Vec<Range<usize>>> {
+    let mut input = input;
+    let mut spans = Vec::new();
+    for _ in 0..count {
+        let (rest, _) = parse(input).map_err(|e| AwwasmParseError::nom(what, e).within(input))?;
+        let offset = input.as_ptr() as usize - start;
+        spans.push(offset..offset + input.len() - rest.len());
+        input = rest;
+    }
+    Ok(spans)
+}
+
+impl AwwasmSpanTable {
+    // Record the spans of the entries of `sec`, if it has entries.
+    fn record(&mut self, sec: &AwwasmSection, start: usize) -> anyhow::Result<()> {
+        let code = &sec.section_header.section_type;
+        if matches!(code, SectionCode::Custom | SectionCode::Start | SectionCode::DataCount) {
+            return Ok(());
+        }
+        let what = format!("{:?} Section", code);
+        let (input, _) = AwwasmSectionHeader::parse(sec.encoding).map_err(|e| AwwasmParseError::section_header("section header", e).within(sec.encoding))?;
+        let (input, count) = leb128_u32::<nom::error::Error<_>>(input).map_err(|e| AwwasmParseError::nom("entry count", e).within(input))?;
+        let (list, spans) = match code {
+            SectionCode::Type => (&mut self.types, entries(input, count, start, AwwasmTypeSectionItem::parse, &what)),
+            SectionCode::Import => (&mut self.imports, entries(input, count, start, AwwasmImportSectionItem::parse, &what)),
+            SectionCode::Function => (&mut self.funcs, entries(input, count, start, AwwasmFuncSectionItem::parse, &what)),
+            SectionCode::Table => (&mut self.tables, entries(input, count, start, AwwasmTableSectionItem::parse, &what)),
+            SectionCode::Memory => (&mut self.memories, entries(input, count, start, AwwasmMemorySectionItem::parse, &what)),
+            SectionCode::Global => (&mut self.globals, entries(input, count, start, AwwasmGlobalSectionItem::parse, &what)),
+            SectionCode::Export => (&mut self.exports, entries(input, count, start, AwwasmExportSectionItem::parse, &what)),
+            SectionCode::Element => (&mut self.elements, entries(input, count, start, AwwasmElementSectionItem::parse, &what)),
+            SectionCode::Code => (&mut self.code, entries(input, count, start, AwwasmCodeSectionItem::parse, &what)),
+            SectionCode::Data => (&mut self.data, entries(input, count, start, AwwasmDataSectionItem::parse, &what)),
+            SectionCode::Tag => (&mut self.tags, entries(input, count, start, AwwasmTagSectionItem::parse, &what)),
+            // Returned above.
+            SectionCode::Custom | SectionCode::Start | SectionCode::DataCount => return Ok(()),
+        };
+        *list = spans?;
+        Ok(())
+    }
+}
+
+impl AwwasmModule<'_> {
+    /// Where each section and each entry of the sections with entries is
+    /// encoded in the module binary.
+    ///
+    /// Decodes the entries again from each section's `encoding`, so it works
+    /// whether or not the sections have been resolved. Fails for a module
+    /// not parsed from a binary, or with a located `AwwasmParseError` for an
+    /// entry that cannot be decoded.
+    pub fn spans(&self) -> anyhow::Result<AwwasmSpanTable> {
+        let Some(start) = self.start_address() else {
+            anyhow::bail!("the module was not parsed from a binary");
+        };
+        let mut table = AwwasmSpanTable::default();
+        for sec in self.sections() {
+            let offset = sec.encoding.as_ptr() as usize - start;
+            table.sections.push(offset..offset + sec.encoding.len());
+            table.record(sec, start).map_err(|e| locate(e, start, Some(&sec.section_header.section_type), None))?;
+        }
+        Ok(table)
+    }
+
+    /// Where `instr` is encoded in the module binary, if it was decoded from
+    /// one of the module's function bodies: `None` unless it lies within the
+    /// code section.
+    pub fn instruction_span(&self, instr: &AwwasmInstruction) -> Option<Range<usize>> {
+        let code = self.section_raw(SectionCode::Code)?;
+        let (code_offset, offset) = (self.offset_of(code)?, self.offset_of(instr.encoding)?);
+        let span = offset..offset + instr.encoding.len();
+        (code_offset <= span.start && span.end <= code_offset + code.len()).then_some(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::instructions::InstructionIterator;
+    use crate::components::module::AwwasmModule;
+
+    #[test]
+    fn spans_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (import "env" "f" (func))
+                (memory 1)
+                (func (export "run") (i32.store (i32.const 0) (i32.const 7)))
+                (data (i32.const 16) "hi"))
+        "#)?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        let spans = module.spans()?;
+        assert_eq!(spans.sections.len(), 7);
+        assert_eq!(spans.sections[0].start, 8);
+        assert_eq!(spans.sections.last().map(|s| s.end), Some(bytes.len()));
+        assert_eq!(&bytes[spans.imports[0].clone()], b"\x03env\x01f\x00\x00");
+        assert_eq!(&bytes[spans.exports[0].clone()], b"\x03run\x00\x01");
+        assert_eq!(&bytes[spans.data[0].clone()], b"\x00\x41\x10\x0b\x02hi");
+        assert_eq!(spans.code.len(), 1);
+
+        // Resolving leaves the spans in place.
+        module.resolve_all_sections()?;
+        assert_eq!(module.spans()?, spans);
+
+        let code = &module.code.as_ref().expect("code should exist")[0];
+        let instrs = code.instructions()?;
+        let store = module.instruction_span(&instrs[2]).expect("span should exist");
+        assert_eq!(&bytes[store.clone()], [0x36, 0x02, 0x00]);
+        assert!(spans.code[0].contains(&store.start) && store.end < spans.code[0].end);
+
+        // Instructions decoded from outside the code section have no span.
+        let data = &module.data()[0];
+        let offset = data.header.offset.as_ref().expect("active segment").code;
+        let in_data = InstructionIterator::new(offset).flatten().next().expect("offset expression");
+        assert_eq!(module.offset_of(in_data.encoding), Some(spans.data[0].start + 1));
+        assert_eq!(module.instruction_span(&in_data), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+// Test-only runner for the official spec testsuite (.wast scripts).
+//
+// Point `AWWASM_SPEC_TESTSUITE` at a checkout of
+// https://github.com/WebAssembly/testsuite to run it; without it the suite
+// test is skipped. Every binary `assert_malformed` module must fail to
+// decode, every `assert_invalid` module must decode but fail `validate()`,
+// and every other module must decode fully and validate. Text-format
+// `assert_malformed` cases and the execution assertions are out of scope.
+
+use crate::components::module::AwwasmModule;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use wast::core::{Module, ModuleKind};
+use wast::parser::{self, ParseBuffer};
+use wast::{QuoteWat, Wast, WastDirective, Wat};
+
+pub(crate) const TESTSUITE_ENV: &str = "AWWASM_SPEC_TESTSUITE";
+
+/// Outcome of one .wast script.
+#[derive(Debug, Default)]
+pub(crate) struct ScriptReport {
+    pub name: String,
+    pub passed: usize,
+    /// One line per failing case: `line: what went wrong`.
+    pub failures: Vec<String>,
+    /// Cases where the parser panicked rather than returning an error.
+    pub panics: usize,
+}
+
+// What a script expects of a module.
+enum Expect<'a> {
+    Valid,
+    Invalid(&'a str),
+    Malformed(&'a str),
+}
+
+// Decode the module the way a consumer would, down to every instruction,
+// then validate it. Returns the validation findings.
+fn decode(bytes: &[u8]) -> anyhow::Result<Vec<String>> {
+    let mut module = AwwasmModule::new(bytes)?;
+    module.resolve_all_sections()?;
+    for item in module.code() {
+        item.instructions()?;
+    }
+    Ok(module.validate().iter().map(|f| f.to_string()).collect())
+}
+
+fn is_binary(module: &QuoteWat) -> bool {
+    matches!(module, QuoteWat::Wat(Wat::Module(Module { kind: ModuleKind::Binary(_), .. })))
+}
+
+/// Run the decode cases of one script, named `name` in the report.
+pub(crate) fn run_script(name: &str, source: &str) -> anyhow::Result<ScriptReport> {
+    let buf = ParseBuffer::new(source).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+    let wast: Wast = parser::parse(&buf).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+    let mut report = ScriptReport { name: name.to_string(), ..Default::default() };
+    for directive in wast.directives {
+        let line = directive.span().linecol_in(source).0 + 1;
+        let (mut module, expect) = match directive {
+            WastDirective::Wat(module @ QuoteWat::Wat(Wat::Module(_))) => (module, Expect::Valid),
+            WastDirective::AssertInvalid { module: module @ QuoteWat::Wat(Wat::Module(_)), message, .. } => (module, Expect::Invalid(message)),
+            WastDirective::AssertMalformed { module, message, .. } if is_binary(&module) => (module, Expect::Malformed(message)),
+            _ => continue,
+        };
+        // Modules the text parser rejects say nothing about this crate.
+        let Ok(bytes) = module.encode() else { continue };
+        let outcome = match catch_unwind(AssertUnwindSafe(|| decode(&bytes))) {
+            Ok(outcome) => outcome,
+            Err(_) => {
+                report.panics += 1;
+                report.failures.push(format!("{}: parser panicked", line));
+                continue;
+            }
+        };
+        match (outcome, expect) {
+            (Err(_), Expect::Malformed(_)) => report.passed += 1,
+            (Ok(findings), Expect::Valid) if findings.is_empty() => report.passed += 1,
+            (Ok(findings), Expect::Invalid(_)) if !findings.is_empty() => report.passed += 1,
+            (Err(e), _) => report.failures.push(format!("{}: failed to decode: {}", line, e)),
+            (Ok(findings), Expect::Valid) => report.failures.push(format!("{}: rejected a valid module: {}", line, findings.join("; "))),
+            (Ok(_), Expect::Invalid(message)) => report.failures.push(format!("{}: validated a module that is invalid ({})", line, message)),
+            (Ok(_), Expect::Malformed(message)) => report.failures.push(format!("{}: decoded a module that is malformed ({})", line, message)),
+        }
+    }
+    Ok(report)
+}
+
+/// Run every .wast script at the top level of `dir`, in name order.
+/// Proposal subdirectories are not descended into.
+pub(crate) fn run_suite(dir: &Path) -> anyhow::Result<Vec<ScriptReport>> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|p| p.extension().is_some_and(|ext| ext == "wast"));
+    paths.sort();
+    paths.iter()
+        .map(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            run_script(&name, &std::fs::read_to_string(path)?)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::spec_suite::{run_script, run_suite, TESTSUITE_ENV};
+
+    #[test]
+    fn run_script_test() -> anyhow::Result<()> {
+        let report = run_script("inline.wast", r#"
+            (module (func (export "f") (result i32) (i32.const 1)))
+            (assert_malformed (module binary "\00asn" "\01\00\00\00") "magic header not detected")
+            (assert_malformed (module binary "\00asm" "\01\00\00\00" "\01\05\01\60\00") "unexpected end")
+            (assert_malformed (module binary "\00asm" "\01\00\00\00") "not a failure")
+            (assert_invalid (module (func (result i32))) "type mismatch")
+            (assert_invalid (module (func (result i32) (i32.const 0))) "not a failure")
+            (assert_malformed (module quote "(func") "unexpected token")
+            (assert_return (invoke "f") (i32.const 1))
+        "#)?;
+        assert_eq!(report.passed, 4, "{:?}", report.failures);
+        assert_eq!(report.failures.len(), 2, "{:?}", report.failures);
+        assert!(report.failures[0].starts_with("5: decoded a module that is malformed"), "{}", report.failures[0]);
+        assert!(report.failures[1].starts_with("7: validated a module that is invalid"), "{}", report.failures[1]);
+        assert_eq!(report.panics, 0);
+        Ok(())
+    }
+
+    // Runs the top-level (core spec) scripts of a testsuite checkout; CI pins
+    // the checkout to a fixed commit. Every failing case is listed, and any
+    // failure or panic fails the test.
+    #[test]
+    fn spec_suite_test() -> anyhow::Result<()> {
+        let Some(dir) = std::env::var_os(TESTSUITE_ENV) else {
+            eprintln!("{} is not set; skipping the spec testsuite", TESTSUITE_ENV);
+            return Ok(());
+        };
+        let reports = run_suite(dir.as_ref())?;
+        for report in &reports {
+            println!("{:<32} {:>5} passed {:>5} failed", report.name, report.passed, report.failures.len());
+            for failure in &report.failures {
+                println!("    {}:{}", report.name, failure);
+            }
+        }
+        let passed: usize = reports.iter().map(|r| r.passed).sum();
+        let failed: usize = reports.iter().map(|r| r.failures.len()).sum();
+        println!("total: {} passed, {} failed", passed, failed);
+        let panics: usize = reports.iter().map(|r| r.panics).sum();
+        assert_eq!(panics, 0, "the parser panicked on {} spec testsuite modules", panics);
+        assert_eq!(failed, 0, "{} spec testsuite cases failed", failed);
+        Ok(())
+    }
+}
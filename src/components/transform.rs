@@ -0,0 +1,286 @@
+use crate::consts::WASM_MAGIC_NUMBER;
+use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+use crate::components::instructions::span_in;
+use crate::components::leb128::leb128_u32;
+use crate::components::passes::{split_sections, write_name, AwwasmPassModule};
+use crate::components::section::{write_leb128_u32, write_leb128_u32_padded, SectionCode};
+use nom::multi::length_data;
+
+// Keystream domains, so the section name, payload mask, tag and layout bits
+// drawn from one key are independent.
+const DOMAIN_NAME: u64 = 1;
+const DOMAIN_MASK: u64 = 2;
+const DOMAIN_TAG: u64 = 3;
+const DOMAIN_LAYOUT: u64 = 4;
+// Width of a padded section size; every u32 fits in 5 LEB128 bytes.
+const PADDED_WIDTH: usize = 5;
+const TAG_LEN: usize = 8;
+// Keyed size fields needed before their padding counts as evidence: with
+// fewer, a wrong key matches the layout too often to mean anything.
+const MIN_LAYOUT_BITS: usize = 32;
+
+/// What `detect_watermark()` found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmWatermarkEvidence {
+    /// The embedded payload, if the watermark section survived and its tag
+    /// checks out under the key.
+    pub payload: Option<Vec<u8>>,
+    /// Whether the padding of the section and function body size fields
+    /// matches the key, or `None` if the module has fewer than 32 such
+    /// fields, too few for a match to be evidence. This survives stripping
+    /// custom sections, but not re-encoding the module.
+    pub section_layout: Option<bool>,
+}
+
+impl AwwasmWatermarkEvidence {
+    pub fn is_present(&self) -> bool {
+        self.payload.is_some() || self.section_layout == Some(true)
+    }
+}
+
+// splitmix64 seeded from an FNV-1a hash of the key. Deterministic across
+// platforms and releases, which is all a watermark needs; it is not a
+// cryptographic PRF, so the watermark deters casual removal, not a
+// determined adversary.
+struct Keystream(u64);
+
+impl Keystream {
+    fn new(key: &[u8], domain: u64) -> Self {
+        let hash = key.iter().fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ *b as u64).wrapping_mul(0x0100_0000_01b3));
+        Keystream(hash ^ domain.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn bytes(mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+
+    // Whether the next keyed size field is padded.
+    fn next_bit(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+}
+
+// Name of the watermark's custom section: opaque, but fixed for a key.
+fn section_name(key: &[u8]) -> Vec<u8> {
+    format!(".{:016x}", Keystream::new(key, DOMAIN_NAME).next_u64()).into_bytes()
+}
+
+fn tag(key: &[u8], payload: &[u8]) -> [u8; TAG_LEN] {
+    let mut stream = Keystream::new(key, DOMAIN_TAG);
+    let mut acc = stream.next_u64();
+    for b in payload {
+        acc = (acc ^ *b as u64).wrapping_mul(0x0100_0000_01b3) ^ stream.next_u64();
+    }
+    acc.to_le_bytes()
+}
+
+fn leb128_len(v: u32) -> usize {
+    (32 - v.leading_zeros() as usize).div_ceil(7).max(1)
+}
+
+// The function bodies of a code section payload, each with the width of
+// its size field.
+fn code_bodies(payload: &[u8]) -> anyhow::Result<Vec<(usize, &[u8])>> {
+    let (mut rest, count) = leb128_u32::<nom::error::Error<&[u8]>>(payload)
+        .map_err(|e| AwwasmParseError::nom("Code Section", e))?;
+    let mut bodies = Vec::new();
+    for _ in 0..count {
+        let (after, body) = length_data(leb128_u32::<nom::error::Error<&[u8]>>)(rest)
+            .map_err(|e| AwwasmParseError::nom("Code Section", e))?;
+        bodies.push((rest.len() - after.len() - body.len(), body));
+        rest = after;
+    }
+    if !rest.is_empty() {
+        return Err(AwwasmParseError::new("Code Section", AwwasmParseErrorKind::Malformed {
+            detail: format!("{} bytes after the last function body", rest.len()),
+        }).into());
+    }
+    Ok(bodies)
+}
+
+fn write_size(out: &mut Vec<u8>, size: u32, padded: bool) {
+    if padded {
+        write_leb128_u32_padded(out, size, PADDED_WIDTH);
+    } else {
+        write_leb128_u32(out, size);
+    }
+}
+
+/// Embed `payload` in a module under `key`: masked and tagged in a custom
+/// section named after the key, and spread over the module by padding some
+/// section and function body size fields to 5 LEB128 bytes (a no-op for
+/// every decoder).
+///
+/// Detect it with `detect_watermark()` and the same key.
+pub fn watermark(bytes: &[u8], key: &[u8], payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let module = AwwasmPassModule::new(bytes)?;
+    let name = section_name(key);
+
+    let mut section = Vec::new();
+    write_name(&mut section, &name);
+    section.extend(payload.iter().zip(Keystream::new(key, DOMAIN_MASK).bytes(payload.len())).map(|(b, m)| b ^ m));
+    section.extend_from_slice(&tag(key, payload));
+
+    let mut out = Vec::new();
+    out.extend_from_slice(WASM_MAGIC_NUMBER);
+    out.extend_from_slice(&module.version.to_le_bytes());
+    let mut layout = Keystream::new(key, DOMAIN_LAYOUT);
+    for s in &module.sections {
+        if s.custom_name() == Some(&name[..]) {
+            continue;
+        }
+        out.push(s.id.clone() as u8);
+        if s.id == SectionCode::Custom {
+            write_leb128_u32(&mut out, s.payload.len() as u32);
+            out.extend_from_slice(&s.payload);
+            continue;
+        }
+        let padded = layout.next_bit();
+        let payload = match s.id {
+            SectionCode::Code => {
+                let bodies = code_bodies(&s.payload)?;
+                let mut code = Vec::new();
+                write_leb128_u32(&mut code, bodies.len() as u32);
+                for (_, body) in bodies {
+                    write_size(&mut code, body.len() as u32, layout.next_bit());
+                    code.extend_from_slice(body);
+                }
+                code
+            }
+            _ => s.payload.clone(),
+        };
+        write_size(&mut out, payload.len() as u32, padded);
+        out.extend_from_slice(&payload);
+    }
+    out.push(SectionCode::Custom as u8);
+    write_leb128_u32(&mut out, section.len() as u32);
+    out.extend_from_slice(&section);
+    Ok(out)
+}
+
+/// Look for the watermark `watermark()` embeds under `key`.
+pub fn detect_watermark(bytes: &[u8], key: &[u8]) -> anyhow::Result<AwwasmWatermarkEvidence> {
+    let name = section_name(key);
+    let mut payload = None;
+    // Width and value of every keyed size field, in the order `watermark()`
+    // draws their bits.
+    let mut sizes = Vec::new();
+    let mut header_start = WASM_MAGIC_NUMBER.len() + 4;
+    split_sections(bytes, |id, body| {
+        let start = span_in(bytes, body).map_or(header_start, |span| span.start);
+        if id != SectionCode::Custom {
+            sizes.push((start - header_start - 1, body.len() as u32));
+            if id == SectionCode::Code {
+                sizes.extend(code_bodies(body)?.into_iter().map(|(width, body)| (width, body.len() as u32)));
+            }
+        } else if let Some(masked) = body.strip_prefix(&[name.len() as u8][..]).and_then(|b| b.strip_prefix(&name[..])) {
+            if let Some(split) = masked.len().checked_sub(TAG_LEN) {
+                let plain: Vec<u8> = masked[..split].iter()
+                    .zip(Keystream::new(key, DOMAIN_MASK).bytes(split))
+                    .map(|(b, m)| b ^ m)
+                    .collect();
+                if tag(key, &plain)[..] == masked[split..] {
+                    payload = Some(plain);
+                }
+            }
+        }
+        header_start = start + body.len();
+        Ok(())
+    })?;
+
+    // Sizes that need all 5 bytes anyway carry no information.
+    let mut layout = Keystream::new(key, DOMAIN_LAYOUT);
+    let matches: Vec<bool> = sizes.iter()
+        .filter_map(|(width, size)| {
+            let padded = layout.next_bit();
+            (leb128_len(*size) < PADDED_WIDTH).then_some((*width == PADDED_WIDTH) == padded)
+        })
+        .collect();
+    let section_layout = (matches.len() >= MIN_LAYOUT_BITS).then(|| matches.iter().all(|m| *m));
+    Ok(AwwasmWatermarkEvidence { payload, section_layout })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+    use crate::components::passes::{AwwasmPassManager, AwwasmStripCustomSections};
+    use crate::components::transform::{detect_watermark, watermark};
+
+    #[test]
+    fn watermark_round_trip_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory (export "memory") 1)
+                (global i32 (i32.const 7))
+                (func (export "f") (result i32) (i32.const 42))
+                (data (i32.const 0) "payload")
+            )
+        "#)?;
+        let marked = watermark(&module, b"vendor key", b"build 1234 for acme")?;
+        let mut parsed = AwwasmModule::new(&marked)?;
+        parsed.resolve_all_sections()?;
+        assert!(parsed.validate().is_empty());
+        assert_eq!(parsed.exports.as_ref().map(|e| e.len()), Some(2));
+
+        let found = detect_watermark(&marked, b"vendor key")?;
+        assert_eq!(found.payload.as_deref(), Some(&b"build 1234 for acme"[..]));
+        // Eight size fields are too few for the layout to count.
+        assert_eq!(found.section_layout, None);
+
+        let wrong_key = detect_watermark(&marked, b"other key")?;
+        assert!(!wrong_key.is_present());
+        assert!(!detect_watermark(&module, b"vendor key")?.is_present());
+
+        // Re-marking replaces the payload rather than adding a second one.
+        let remarked = watermark(&marked, b"vendor key", b"build 1235")?;
+        assert_eq!(detect_watermark(&remarked, b"vendor key")?.payload.as_deref(), Some(&b"build 1235"[..]));
+        Ok(())
+    }
+
+    #[test]
+    fn watermark_survives_custom_section_stripping_test() -> anyhow::Result<()> {
+        // Enough function bodies for the layout to count on its own.
+        let funcs = "(func (result i32) (i32.const 1))".repeat(40);
+        let module = wat::parse_str(format!(r#"(module {} (export "a" (func 0)))"#, funcs))?;
+        let marked = watermark(&module, b"k", b"id")?;
+        let mut parsed = AwwasmModule::new(&marked)?;
+        parsed.resolve_all_sections()?;
+        assert!(parsed.validate().is_empty());
+
+        // Cutting off the trailing watermark section keeps the padded sizes.
+        let custom_len = 1 + 1 + 1 + 17 + 2 + 8;
+        let found = detect_watermark(&marked[..marked.len() - custom_len], b"k")?;
+        assert_eq!(found.payload, None);
+        assert_eq!(found.section_layout, Some(true));
+        assert_eq!(detect_watermark(&marked, b"other key")?.section_layout, Some(false));
+
+        // A pass pipeline re-encodes every size canonically and loses both.
+        let reencoded = AwwasmPassManager::new().add(AwwasmStripCustomSections::default()).run(&marked)?.bytes;
+        assert!(!detect_watermark(&reencoded, b"k")?.is_present());
+        Ok(())
+    }
+
+    #[test]
+    fn watermark_wrong_key_small_module_test() -> anyhow::Result<()> {
+        for wat in ["(module (memory 1))", "(module (memory 1) (global i32 (i32.const 0)))"] {
+            let module = wat::parse_str(wat)?;
+            let marked = watermark(&module, b"k", b"id")?;
+            assert_eq!(detect_watermark(&marked, b"k")?.payload.as_deref(), Some(&b"id"[..]));
+            for i in 0..64 {
+                let key = format!("wrong key {}", i);
+                let found = detect_watermark(&marked, key.as_bytes())?;
+                assert_eq!(found.section_layout, None);
+                assert!(!found.is_present(), "{} detected with {:?}", wat, key);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,831 @@
+use std::collections::HashSet;
+
+use crate::components::instructions::*;
+use crate::components::module::AwwasmModule;
+use crate::components::types::*;
+
+// Types of the values an instruction pops and pushes.
+type Signature = (&'static [ValType], &'static [ValType]);
+
+const I32: ValType = ValType::I32;
+const I64: ValType = ValType::I64;
+const F32: ValType = ValType::F32;
+const F64: ValType = ValType::F64;
+
+// `Err(None)` stops checking the body without a finding: the problem (a
+// missing type, an undecodable body) is already reported by another check.
+type Check = Result<(), Option<String>>;
+
+fn mismatch<T>(message: String) -> Result<T, Option<String>> {
+    Err(Some(message))
+}
+
+// Name of an instruction for messages, sub-opcode included for prefixed ones.
+fn instr_name(instr: &AwwasmInstruction) -> &'static str {
+    match &instr.operands {
+        AwwasmOperands::Misc(op) => op.sub_op.mnemonic(),
+        AwwasmOperands::Atomic(op) => op.sub_op.mnemonic(),
+        _ => instr.opcode.mnemonic(),
+    }
+}
+
+fn list(types: &[ValType]) -> String {
+    format!("[{}]", types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" "))
+}
+
+// `funcref` and `externref` as the `(ref null ht)` they abbreviate.
+fn as_ref_type(ty: ValType) -> Option<AwwasmRefType> {
+    match ty {
+        ValType::FuncRef => Some(AwwasmRefType { nullable: true, heap_type: AwwasmHeapType::Func }),
+        ValType::ExternRef => Some(AwwasmRefType { nullable: true, heap_type: AwwasmHeapType::Extern }),
+        ValType::Ref(r) => Some(r),
+        _ => None,
+    }
+}
+
+/// Whether a value of type `actual` can be used where `expected` is
+/// required: the same type, or a reference subtype (non-null into nullable,
+/// a typed function reference into `funcref`).
+pub fn val_type_matches(actual: ValType, expected: ValType) -> bool {
+    match (as_ref_type(actual), as_ref_type(expected)) {
+        (Some(a), Some(e)) => {
+            let heap = a.heap_type == e.heap_type
+                || matches!((a.heap_type, e.heap_type), (AwwasmHeapType::Type(_), AwwasmHeapType::Func));
+            heap && (e.nullable || !a.nullable)
+        }
+        _ => actual == expected,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Block,
+    Loop,
+    If,
+    Else,
+    Try,
+    Catch,
+    TryTable,
+    Function,
+}
+
+impl FrameKind {
+    fn name(self) -> &'static str {
+        match self {
+            FrameKind::Block => "block",
+            FrameKind::Loop => "loop",
+            FrameKind::If => "if",
+            FrameKind::Else => "else",
+            FrameKind::Try => "try",
+            FrameKind::Catch => "catch",
+            FrameKind::TryTable => "try_table",
+            FrameKind::Function => "function",
+        }
+    }
+}
+
+// A control frame of the validation algorithm.
+struct Frame {
+    kind: FrameKind,
+    params: Vec<ValType>,
+    results: Vec<ValType>,
+    /// Operand stack height when the frame was entered.
+    height: usize,
+    /// Set after an unconditional branch: the rest of the frame is
+    /// stack-polymorphic.
+    unreachable: bool,
+    /// Number of locals set when the frame was entered.
+    inits: usize,
+}
+
+impl Frame {
+    // Types a branch to this frame's label must provide.
+    fn label_types(&self) -> &[ValType] {
+        if self.kind == FrameKind::Loop { &self.params } else { &self.results }
+    }
+}
+
+// Type checker state for one function body. `None` on the operand stack is
+// a value of unknown type, popped from a polymorphic stack.
+struct TypeChecker<'m, 'a> {
+    module: &'m AwwasmModule<'a>,
+    /// Locals, params first, as (end index, type) runs.
+    locals: Vec<(u64, ValType)>,
+    params: u32,
+    /// Non-defaultable locals set so far, in the order they were set; a
+    /// frame forgets the ones set inside it when it ends.
+    inits: Vec<u32>,
+    /// Functions `ref.func` may reference, computed on first use.
+    declared_funcs: Option<HashSet<u32>>,
+    globals: Vec<(ValType, bool)>,
+    stack: Vec<Option<ValType>>,
+    frames: Vec<Frame>,
+}
+
+impl<'m, 'a> TypeChecker<'m, 'a> {
+    fn local(&self, idx: u32) -> Option<ValType> {
+        let pos = self.locals.partition_point(|(end, _)| *end <= idx as u64);
+        self.locals.get(pos).map(|(_, ty)| *ty)
+    }
+
+    fn local_count(&self) -> u64 {
+        self.locals.last().map_or(0, |(end, _)| *end)
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("the function frame is never popped")
+    }
+
+    fn push(&mut self, ty: ValType) {
+        self.stack.push(Some(ty));
+    }
+
+    fn push_all(&mut self, types: &[ValType]) {
+        types.iter().for_each(|ty| self.push(*ty));
+    }
+
+    // Pop any value; `None` if its type is unknown.
+    fn pop_any(&mut self, what: &str) -> Result<Option<ValType>, Option<String>> {
+        let frame = self.frame();
+        if self.stack.len() == frame.height {
+            if frame.unreachable {
+                return Ok(None);
+            }
+            return mismatch(format!("{} expects a value but the stack is empty", what));
+        }
+        Ok(self.stack.pop().flatten())
+    }
+
+    fn pop(&mut self, expected: ValType, what: &str) -> Result<Option<ValType>, Option<String>> {
+        let frame = self.frame();
+        if self.stack.len() == frame.height {
+            if frame.unreachable {
+                return Ok(Some(expected));
+            }
+            return mismatch(format!("{} expects {} but the stack is empty", what, expected));
+        }
+        match self.stack.pop().flatten() {
+            Some(actual) if !val_type_matches(actual, expected) => mismatch(format!("{} expects {} but found {}", what, expected, actual)),
+            actual => Ok(actual.or(Some(expected))),
+        }
+    }
+
+    fn pop_all(&mut self, types: &[ValType], what: &str) -> Check {
+        for ty in types.iter().rev() {
+            self.pop(*ty, what)?;
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, (params, results): Signature, what: &str) -> Check {
+        self.pop_all(params, what)?;
+        self.push_all(results);
+        Ok(())
+    }
+
+    fn set_unreachable(&mut self) {
+        let frame = self.frames.last_mut().expect("the function frame is never popped");
+        self.stack.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    fn label(&self, depth: u32, what: &str) -> Result<Vec<ValType>, Option<String>> {
+        match self.frames.len().checked_sub(depth as usize + 1) {
+            Some(i) => Ok(self.frames[i].label_types().to_vec()),
+            None => mismatch(format!("{} references label {} but only {} are in scope", what, depth, self.frames.len())),
+        }
+    }
+
+    // Check the stack provides `types` without consuming it.
+    fn peek_all(&mut self, types: &[ValType], what: &str) -> Check {
+        let saved = self.stack.clone();
+        let result = self.pop_all(types, what);
+        self.stack = saved;
+        result
+    }
+
+    fn func_type(&self, type_idx: u32) -> Result<&'m AwwasmTypeSectionItem<'a>, Option<String>> {
+        self.module.types().get(type_idx as usize).ok_or(None)
+    }
+
+    fn block_signature(&self, block_type: BlockType) -> Result<(Vec<ValType>, Vec<ValType>), Option<String>> {
+        Ok(match block_type {
+            BlockType::Empty => (Vec::new(), Vec::new()),
+            BlockType::Value(ty) => (Vec::new(), vec![ty]),
+            BlockType::TypeIndex(idx) => {
+                let ty = self.func_type(idx)?;
+                (ty.fn_args.clone(), ty.fn_rets.clone())
+            }
+        })
+    }
+
+    fn tag_params(&self, tag_idx: u32, what: &str) -> Result<Vec<ValType>, Option<String>> {
+        let type_idx = self.module.imports().iter().filter_map(|i| i.tag.as_ref())
+            .chain(self.module.tags().iter())
+            .nth(tag_idx as usize)
+            .map(|t| t.type_idx);
+        match type_idx {
+            Some(idx) => Ok(self.func_type(idx)?.fn_args.clone()),
+            None => mismatch(format!("{} references tag {} but the module has {} tags", what, tag_idx, self.module.tag_count())),
+        }
+    }
+
+    // Push the results of a call; a tail call instead returns them, so they
+    // must match the function's results.
+    fn call_results(&mut self, opcode: WasmOpCode, rets: &[ValType], what: &str) -> Check {
+        if !matches!(opcode, WasmOpCode::ReturnCall | WasmOpCode::ReturnCallIndirect | WasmOpCode::ReturnCallRef) {
+            self.push_all(rets);
+            return Ok(());
+        }
+        let results = &self.frames[0].results;
+        if rets.len() != results.len() || !rets.iter().zip(results).all(|(a, e)| val_type_matches(*a, *e)) {
+            return mismatch(format!("{} returns {} but the function returns {}", what, list(rets), list(results)));
+        }
+        self.set_unreachable();
+        Ok(())
+    }
+
+    fn push_frame(&mut self, kind: FrameKind, params: Vec<ValType>, results: Vec<ValType>) {
+        let height = self.stack.len();
+        self.push_all(&params);
+        self.frames.push(Frame { kind, params, results, height, unreachable: false, inits: self.inits.len() });
+    }
+
+    // Check the stack holds exactly the frame's results, then leave it.
+    fn pop_frame(&mut self) -> Result<Frame, Option<String>> {
+        let results = self.frame().results.clone();
+        let what = format!("end of {}", self.frame().kind.name());
+        self.pop_all(&results, &what)?;
+        let frame = self.frames.pop().expect("the function frame is never popped");
+        self.inits.truncate(frame.inits);
+        if self.stack.len() != frame.height {
+            let left: Vec<ValType> = self.stack[frame.height..].iter().flatten().copied().collect();
+            return mismatch(format!("{} expects {} but {} extra values remain: {}", what, list(&results), self.stack.len() - frame.height, list(&left)));
+        }
+        Ok(frame)
+    }
+
+    fn check_body(&mut self, body: &[AwwasmInstruction<'a>]) -> Check {
+        body.iter().try_for_each(|instr| self.check_instruction(instr))
+    }
+
+    // A nested block: enter a frame, check the body, leave with the results.
+    fn check_block(&mut self, kind: FrameKind, block_type: BlockType, body: &[AwwasmInstruction<'a>], what: &str) -> Check {
+        let (params, results) = self.block_signature(block_type)?;
+        self.pop_all(&params, what)?;
+        self.push_frame(kind, params, results);
+        self.check_body(body)?;
+        let frame = self.pop_frame()?;
+        self.push_all(&frame.results);
+        Ok(())
+    }
+
+    fn check_instruction(&mut self, instr: &AwwasmInstruction<'a>) -> Check {
+        use AwwasmOperands::*;
+        let what = instr_name(instr);
+        match &instr.operands {
+            Unreachable => self.set_unreachable(),
+            Nop | Else | End | Catch(_) | CatchAll | Delegate(_) => {}
+            Block(op) => self.check_block(FrameKind::Block, op.block_type, &op.body.0, what)?,
+            Loop(op) => self.check_block(FrameKind::Loop, op.block_type, &op.body.0, what)?,
+            If(op) => {
+                self.pop(I32, what)?;
+                let (params, results) = self.block_signature(op.block_type)?;
+                self.pop_all(&params, what)?;
+                self.push_frame(FrameKind::If, params.clone(), results.clone());
+                self.check_body(&op.then_body.0)?;
+                self.pop_frame()?;
+                // A missing `else` is an empty one: it must pass the params through.
+                self.push_frame(FrameKind::Else, params, results);
+                if let Some((else_body, _)) = &op.else_body {
+                    self.check_body(else_body)?;
+                }
+                let frame = self.pop_frame()?;
+                self.push_all(&frame.results);
+            }
+            Try(op) => {
+                let (params, results) = self.block_signature(op.block_type)?;
+                self.pop_all(&params, what)?;
+                self.push_frame(FrameKind::Try, params, results.clone());
+                self.check_body(&op.body.0)?;
+                self.pop_frame()?;
+                for handler in &op.handlers {
+                    let caught = match handler.tag {
+                        Some(tag) => self.tag_params(tag, "catch")?,
+                        None => Vec::new(),
+                    };
+                    self.push_frame(FrameKind::Catch, caught, results.clone());
+                    self.check_body(&handler.body.0)?;
+                    self.pop_frame()?;
+                }
+                if let Some(depth) = op.delegate {
+                    self.label(depth, "delegate")?;
+                }
+                self.push_all(&results);
+            }
+            TryTable(op) => {
+                for catch in &op.catches {
+                    let label = self.label(catch.label, what)?;
+                    let caught = match (catch.kind, catch.tag) {
+                        (AwwasmCatchKind::Catch, Some(tag)) => self.tag_params(tag, what)?,
+                        (AwwasmCatchKind::CatchAll, _) => Vec::new(),
+                        // The `_ref` forms also push an exnref, which has no `ValType`.
+                        _ => continue,
+                    };
+                    if caught.len() != label.len() || !caught.iter().zip(&label).all(|(c, l)| val_type_matches(*c, *l)) {
+                        return mismatch(format!("{} catch to label {} provides {} but the label expects {}", what, catch.label, list(&caught), list(&label)));
+                    }
+                }
+                self.check_block(FrameKind::TryTable, op.block_type, &op.body.0, what)?;
+            }
+            Throw(op) => {
+                let params = self.tag_params(op.index, what)?;
+                self.pop_all(&params, what)?;
+                self.set_unreachable();
+            }
+            Rethrow(op) => {
+                let target = self.frames.len().checked_sub(op.labelidx as usize + 1).map(|i| self.frames[i].kind);
+                if target != Some(FrameKind::Catch) {
+                    return mismatch(format!("{} references label {} which is not a catch block", what, op.labelidx));
+                }
+                self.set_unreachable();
+            }
+            ThrowRef => {
+                self.pop_any(what)?;
+                self.set_unreachable();
+            }
+            Br(op) => {
+                let label = self.label(op.labelidx, what)?;
+                self.pop_all(&label, what)?;
+                self.set_unreachable();
+            }
+            BrIf(op) => {
+                self.pop(I32, what)?;
+                let label = self.label(op.labelidx, what)?;
+                self.pop_all(&label, what)?;
+                self.push_all(&label);
+            }
+            BrTable(op) => {
+                self.pop(I32, what)?;
+                let default = self.label(op.default, what)?;
+                for target in &op.targets {
+                    let label = self.label(*target, what)?;
+                    if label.len() != default.len() {
+                        return mismatch(format!("{} targets labels of different arity: {} and {}", what, list(&label), list(&default)));
+                    }
+                    self.peek_all(&label, what)?;
+                }
+                self.pop_all(&default, what)?;
+                self.set_unreachable();
+            }
+            Return => {
+                let results = self.frames[0].results.clone();
+                self.pop_all(&results, what)?;
+                self.set_unreachable();
+            }
+            Call(op) | ReturnCall(op) => {
+                let Some(ty) = self.module.signature_of(op.funcidx) else {
+                    return mismatch(format!("{} references function {} but the module has {}", what, op.funcidx, self.module.func_count()));
+                };
+                self.pop_all(&ty.fn_args, what)?;
+                self.call_results(instr.opcode, &ty.fn_rets, what)?;
+            }
+            CallIndirect(op) | ReturnCallIndirect(op) => {
+                let ty = self.func_type(op.typeidx)?;
+                self.pop(I32, what)?;
+                self.pop_all(&ty.fn_args, what)?;
+                self.call_results(instr.opcode, &ty.fn_rets, what)?;
+            }
+            CallRef(op) | ReturnCallRef(op) => {
+                let ty = self.func_type(op.typeidx)?;
+                self.pop(ValType::Ref(AwwasmRefType { nullable: true, heap_type: AwwasmHeapType::Type(op.typeidx) }), what)?;
+                self.pop_all(&ty.fn_args, what)?;
+                self.call_results(instr.opcode, &ty.fn_rets, what)?;
+            }
+            Drop => {
+                self.pop_any(what)?;
+            }
+            Select => {
+                self.pop(I32, what)?;
+                let a = self.pop_any(what)?;
+                let b = self.pop_any(what)?;
+                if let Some(ty) = a.or(b).filter(|ty| ty.is_ref()) {
+                    return mismatch(format!("{} without a type cannot choose between {} values", what, ty));
+                }
+                match (a, b) {
+                    (Some(a), Some(b)) if a != b => return mismatch(format!("{} operands have different types: {} and {}", what, b, a)),
+                    _ => self.stack.push(a.or(b)),
+                }
+            }
+            SelectTyped(op) => {
+                let [ty] = op.types[..] else {
+                    return mismatch(format!("{} must name exactly one type, not {}", what, op.types.len()));
+                };
+                self.pop(I32, what)?;
+                self.pop(ty, what)?;
+                self.pop(ty, what)?;
+                self.push(ty);
+            }
+            LocalGet(op) | LocalSet(op) | LocalTee(op) => {
+                let Some(ty) = self.local(op.index) else {
+                    return mismatch(format!("{} references local {} but the function has {}", what, op.index, self.local_count()));
+                };
+                // Params are set by the caller, and defaultable locals start
+                // out as their default value.
+                let tracked = op.index >= self.params && matches!(ty, ValType::Ref(r) if !r.nullable);
+                let set = !tracked || self.inits.contains(&op.index);
+                if instr.opcode == WasmOpCode::LocalGet && !set {
+                    return mismatch(format!("{} reads local {} of type {} before it is set", what, op.index, ty));
+                }
+                if instr.opcode != WasmOpCode::LocalGet {
+                    self.pop(ty, what)?;
+                    if !set {
+                        self.inits.push(op.index);
+                    }
+                }
+                if instr.opcode != WasmOpCode::LocalSet {
+                    self.push(ty);
+                }
+            }
+            GlobalGet(op) | GlobalSet(op) => {
+                let Some(&(ty, mutable)) = self.globals.get(op.index as usize) else {
+                    return mismatch(format!("{} references global {} but the module has {}", what, op.index, self.globals.len()));
+                };
+                if instr.opcode == WasmOpCode::GlobalGet {
+                    self.push(ty);
+                } else if !mutable {
+                    return mismatch(format!("{} references global {} which is immutable", what, op.index));
+                } else {
+                    self.pop(ty, what)?;
+                }
+            }
+            RefNull(op) => self.push(match op.heap_type {
+                AwwasmHeapType::Func => ValType::FuncRef,
+                AwwasmHeapType::Extern => ValType::ExternRef,
+                heap_type => ValType::Ref(AwwasmRefType { nullable: true, heap_type }),
+            }),
+            RefIsNull => {
+                if let Some(ty) = self.pop_any(what)?.filter(|ty| !ty.is_ref()) {
+                    return mismatch(format!("{} expects a reference but found {}", what, ty));
+                }
+                self.push(I32);
+            }
+            RefFunc(op) => {
+                if op.funcidx >= self.module.func_count() {
+                    return mismatch(format!("{} references function {} but the module has {}", what, op.funcidx, self.module.func_count()));
+                }
+                let module = self.module;
+                if !self.declared_funcs.get_or_insert_with(|| module.declared_funcs()).contains(&op.funcidx) {
+                    return mismatch(format!("{} references function {} which no element segment, export or global initializer declares", what, op.funcidx));
+                }
+                match self.module.func(op.funcidx) {
+                    Some(func) => self.push(ValType::Ref(AwwasmRefType { nullable: false, heap_type: AwwasmHeapType::Type(func.type_idx()) })),
+                    None => self.push(ValType::FuncRef),
+                }
+            }
+            RefAsNonNull | BrOnNull(_) | BrOnNonNull(_) => {
+                let popped = self.pop_any(what)?;
+                let heap_type = match popped.map(|ty| (ty, as_ref_type(ty))) {
+                    Some((_, Some(r))) => Some(r.heap_type),
+                    Some((ty, None)) => return mismatch(format!("{} expects a reference but found {}", what, ty)),
+                    None => None,
+                };
+                let non_null = heap_type.map(|heap_type| ValType::Ref(AwwasmRefType { nullable: false, heap_type }));
+                match &instr.operands {
+                    BrOnNull(op) => {
+                        let label = self.label(op.labelidx, what)?;
+                        self.pop_all(&label, what)?;
+                        self.push_all(&label);
+                        self.stack.push(non_null);
+                    }
+                    BrOnNonNull(op) => {
+                        let mut label = self.label(op.labelidx, what)?;
+                        match (label.pop(), non_null) {
+                            (Some(last), Some(ty)) if !val_type_matches(ty, last) =>
+                                return mismatch(format!("{} branches with {} but label {} expects {}", what, ty, op.labelidx, last)),
+                            (None, _) => return mismatch(format!("{} targets label {} which takes no values", what, op.labelidx)),
+                            _ => {}
+                        }
+                        self.pop_all(&label, what)?;
+                        self.push_all(&label);
+                    }
+                    _ => self.stack.push(non_null),
+                }
+            }
+            Misc(op) => self.apply(misc_signature(&op.operands), what)?,
+            Atomic(op) => self.apply(atomic_signature(op.sub_op), what)?,
+            operands => match simple_signature(operands) {
+                Some(signature) => self.apply(signature, what)?,
+                None => return mismatch(format!("{} is not supported by the type checker", what)),
+            },
+        }
+        Ok(())
+    }
+}
+
+fn misc_signature(operands: &AwwasmMiscOperands) -> Signature {
+    use AwwasmMiscOperands::*;
+    match operands {
+        I32TruncSatF32S | I32TruncSatF32U => (&[F32], &[I32]),
+        I32TruncSatF64S | I32TruncSatF64U => (&[F64], &[I32]),
+        I64TruncSatF32S | I64TruncSatF32U => (&[F32], &[I64]),
+        I64TruncSatF64S | I64TruncSatF64U => (&[F64], &[I64]),
+        MemoryInit(_) | MemoryCopy(_) | MemoryFill(_) => (&[I32, I32, I32], &[]),
+        DataDrop(_) => (&[], &[]),
+    }
+}
+
+fn atomic_signature(op: AtomicOpCode) -> Signature {
+    use AtomicOpCode::*;
+    let wide = op.mnemonic().starts_with("i64");
+    match op {
+        MemoryAtomicNotify => (&[I32, I32], &[I32]),
+        MemoryAtomicWait32 => (&[I32, I32, I64], &[I32]),
+        MemoryAtomicWait64 => (&[I32, I64, I64], &[I32]),
+        AtomicFence => (&[], &[]),
+        I32AtomicLoad | I32AtomicLoad8U | I32AtomicLoad16U => (&[I32], &[I32]),
+        I64AtomicLoad | I64AtomicLoad8U | I64AtomicLoad16U | I64AtomicLoad32U => (&[I32], &[I64]),
+        I32AtomicStore | I32AtomicStore8 | I32AtomicStore16 => (&[I32, I32], &[]),
+        I64AtomicStore | I64AtomicStore8 | I64AtomicStore16 | I64AtomicStore32 => (&[I32, I64], &[]),
+        I32AtomicRmwCmpxchg | I32AtomicRmw8CmpxchgU | I32AtomicRmw16CmpxchgU => (&[I32, I32, I32], &[I32]),
+        I64AtomicRmwCmpxchg | I64AtomicRmw8CmpxchgU | I64AtomicRmw16CmpxchgU | I64AtomicRmw32CmpxchgU => (&[I32, I64, I64], &[I64]),
+        // Every other read-modify-write: address and operand in, old value out.
+        _ if wide => (&[I32, I64], &[I64]),
+        _ => (&[I32, I32], &[I32]),
+    }
+}
+
+// Instructions with a fixed signature and no immediates that affect it.
+fn simple_signature(operands: &AwwasmOperands) -> Option<Signature> {
+    use AwwasmOperands::*;
+    Some(match operands {
+        I32Load(_) | I32Load8S(_) | I32Load8U(_) | I32Load16S(_) | I32Load16U(_) => (&[I32], &[I32]),
+        I64Load(_) | I64Load8S(_) | I64Load8U(_) | I64Load16S(_) | I64Load16U(_) | I64Load32S(_) | I64Load32U(_) => (&[I32], &[I64]),
+        F32Load(_) => (&[I32], &[F32]),
+        F64Load(_) => (&[I32], &[F64]),
+        I32Store(_) | I32Store8(_) | I32Store16(_) => (&[I32, I32], &[]),
+        I64Store(_) | I64Store8(_) | I64Store16(_) | I64Store32(_) => (&[I32, I64], &[]),
+        F32Store(_) => (&[I32, F32], &[]),
+        F64Store(_) => (&[I32, F64], &[]),
+        MemorySize(_) => (&[], &[I32]),
+        MemoryGrow(_) => (&[I32], &[I32]),
+        I32Const(_) => (&[], &[I32]),
+        I64Const(_) => (&[], &[I64]),
+        F32Const(_) => (&[], &[F32]),
+        F64Const(_) => (&[], &[F64]),
+
+        I32Eqz => (&[I32], &[I32]),
+        I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU => (&[I32, I32], &[I32]),
+        I32Clz | I32Ctz | I32Popcnt | I32Extend8S | I32Extend16S => (&[I32], &[I32]),
+        I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS | I32RemU | I32And | I32Or | I32Xor
+        | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr => (&[I32, I32], &[I32]),
+
+        I64Eqz => (&[I64], &[I32]),
+        I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU => (&[I64, I64], &[I32]),
+        I64Clz | I64Ctz | I64Popcnt | I64Extend8S | I64Extend16S | I64Extend32S => (&[I64], &[I64]),
+        I64Add | I64Sub | I64Mul | I64DivS | I64DivU | I64RemS | I64RemU | I64And | I64Or | I64Xor
+        | I64Shl | I64ShrS | I64ShrU | I64Rotl | I64Rotr => (&[I64, I64], &[I64]),
+
+        F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge => (&[F32, F32], &[I32]),
+        F64Eq | F64Ne | F64Lt | F64Gt | F64Le | F64Ge => (&[F64, F64], &[I32]),
+        F32Abs | F32Neg | F32Ceil | F32Floor | F32Trunc | F32Nearest | F32Sqrt => (&[F32], &[F32]),
+        F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Copysign => (&[F32, F32], &[F32]),
+        F64Abs | F64Neg | F64Ceil | F64Floor | F64Trunc | F64Nearest | F64Sqrt => (&[F64], &[F64]),
+        F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign => (&[F64, F64], &[F64]),
+
+        I32WrapI64 => (&[I64], &[I32]),
+        I32TruncF32S | I32TruncF32U | I32ReinterpretF32 => (&[F32], &[I32]),
+        I32TruncF64S | I32TruncF64U => (&[F64], &[I32]),
+        I64ExtendI32S | I64ExtendI32U => (&[I32], &[I64]),
+        I64TruncF32S | I64TruncF32U => (&[F32], &[I64]),
+        I64TruncF64S | I64TruncF64U | I64ReinterpretF64 => (&[F64], &[I64]),
+        F32ConvertI32S | F32ConvertI32U | F32ReinterpretI32 => (&[I32], &[F32]),
+        F32ConvertI64S | F32ConvertI64U => (&[I64], &[F32]),
+        F32DemoteF64 => (&[F64], &[F32]),
+        F64ConvertI32S | F64ConvertI32U => (&[I32], &[F64]),
+        F64ConvertI64S | F64ConvertI64U | F64ReinterpretI64 => (&[I64], &[F64]),
+        F64PromoteF32 => (&[F32], &[F64]),
+        _ => return None,
+    })
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Types of every global in the global index space (imports first),
+    /// with whether it is mutable.
+    pub fn global_types(&self) -> Vec<(ValType, bool)> {
+        let imported = self.imports().iter().filter_map(|i| i.global.as_ref())
+            .map(|g| (g.value_type, g.mutability == AwwasmGlobalMutability::Mutable));
+        let defined = self.globals().iter()
+            .map(|g| (g.value_type, g.mutability == AwwasmGlobalMutability::Mutable));
+        imported.chain(defined).collect()
+    }
+
+    // The functions `ref.func` may reference in a body: those in element
+    // segments, exports and global initializers.
+    fn declared_funcs(&self) -> HashSet<u32> {
+        let mut funcs: HashSet<u32> = self.exports().iter()
+            .filter(|e| e.kind == AwwasmExportKind::Function)
+            .map(|e| e.index)
+            .collect();
+        let mut exprs: Vec<&[u8]> = self.globals().iter().map(|g| g.init_expr.code).collect();
+        for element in self.elements() {
+            funcs.extend(element.body.func_indices().unwrap_or_default());
+            exprs.extend(element.body.init_exprs().iter().map(|e| e.code));
+        }
+        for code in exprs {
+            for instr in InstructionIterator::new(code).flatten() {
+                instr.walk(&mut |instr| {
+                    if let AwwasmOperands::RefFunc(op) = &instr.operands {
+                        funcs.insert(op.funcidx);
+                    }
+                });
+            }
+        }
+        funcs
+    }
+
+    /// Type-check the body of the function at `func_idx` against its
+    /// signature, following the spec's validation algorithm: operand stack
+    /// typing, block and label types, and stack-polymorphism after
+    /// `unreachable`, `br` and the like. Non-nullable locals must be set
+    /// before they are read, and `ref.func` may only name functions an
+    /// element segment, export or global initializer declares. Returns the
+    /// first error.
+    ///
+    /// `Ok` also when the body cannot be checked because of a problem other
+    /// checks report: a missing signature or block type, or a body that
+    /// fails to decode. Requires `resolve_all_sections()`.
+    pub fn typecheck_function(&self, func_idx: u32, item: &AwwasmCodeSectionItem<'a>) -> Result<(), String> {
+        let Some(signature) = self.signature_of(func_idx) else { return Ok(()) };
+        let (Ok((locals, _)), Ok(instrs)) = (item.locals_and_code(), item.instructions_with_limits(&self.limits)) else { return Ok(()) };
+
+        let mut runs = Vec::new();
+        let mut end = 0u64;
+        for ty in &signature.fn_args {
+            end += 1;
+            runs.push((end, *ty));
+        }
+        for local in &locals {
+            end += local.type_count as u64;
+            runs.push((end, local.param_type));
+        }
+        let mut checker = TypeChecker {
+            module: self,
+            locals: runs,
+            params: signature.fn_args.len() as u32,
+            inits: Vec::new(),
+            declared_funcs: None,
+            globals: self.global_types(),
+            stack: Vec::new(),
+            frames: Vec::new(),
+        };
+        checker.push_frame(FrameKind::Function, Vec::new(), signature.fn_rets.clone());
+
+        // The body is `expr end`; the final `end` closes the function frame.
+        let body = match instrs.split_last() {
+            Some((last, body)) if last.opcode == WasmOpCode::End => body,
+            _ => &instrs[..],
+        };
+        let result = checker.check_body(body).and_then(|()| checker.pop_frame().map(|_| ()));
+        match result {
+            Err(Some(message)) => Err(message),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+
+    fn typecheck(wat: &str) -> anyhow::Result<Vec<String>> {
+        typecheck_binary(&wat::parse_str(wat)?)
+    }
+
+    fn typecheck_binary(module: &[u8]) -> anyhow::Result<Vec<String>> {
+        let mut module_parsed = AwwasmModule::new(module)?;
+        module_parsed.resolve_all_sections()?;
+        let imported = module_parsed.imported_func_count();
+        Ok(module_parsed.code().iter().enumerate()
+            .filter_map(|(i, item)| module_parsed.typecheck_function(imported + i as u32, item).err())
+            .collect())
+    }
+
+    #[test]
+    fn typecheck_valid_bodies_test() -> anyhow::Result<()> {
+        let errors = typecheck(r#"
+            (module
+                (type $pair (func (param i32) (result i32 i64)))
+                (import "env" "f" (func $f (param i64) (result f32)))
+                (global $g (mut i32) (i32.const 0))
+                (memory 1)
+                (table 1 funcref)
+                (func (param i32 i64) (result f32)
+                    (local f64)
+                    (global.set $g (i32.add (local.get 0) (i32.load (i32.const 0))))
+                    (block $out (result f32)
+                        (loop $again
+                            (br_if $again (i32.eqz (global.get $g)))
+                            (br_table $again $again (i32.const 0)))
+                        (call $f (local.get 1))))
+                (func (param i32) (result i32)
+                    (if (result i32) (local.get 0)
+                        (then (return (i32.const 1)))
+                        (else (unreachable))))
+                (func (result i32 i64)
+                    (i32.const 1)
+                    (block (type $pair) (drop) (i32.const 2) (i64.const 3)))
+                (func (result i32)
+                    (unreachable)
+                    (select)
+                    (i32.add))
+                (func (param funcref) (result i32)
+                    (ref.is_null (select (result funcref) (local.get 0) (ref.null func) (i32.const 1))))
+                (func (param i32) (result i32)
+                    (if (local.get 0) (then (return_call 2 (i32.const 0))))
+                    (return_call_indirect (param i32) (result i32) (local.get 0) (i32.const 0)))
+            )
+        "#)?;
+        assert_eq!(errors, Vec::<String>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn typecheck_errors_test() -> anyhow::Result<()> {
+        let errors = typecheck(r#"
+            (module
+                (global $g i32 (i32.const 0))
+                (func (result i32) (i64.const 1))
+                (func (i32.add (i32.const 1) (i64.const 2)) (drop))
+                (func (result i32) (i32.const 1) (i32.const 2))
+                (func (result i32) (block (br 1)) (i32.const 0))
+                (func (if (result i32) (i32.const 1) (then (i32.const 2))) (drop))
+                (func (global.set $g (i32.const 1)))
+                (func (drop (local.get 2)))
+                (func (return_call 0))
+            )
+        "#)?;
+        assert_eq!(errors, vec![
+            "end of function expects i32 but found i64",
+            "i32.add expects i32 but found i64",
+            "end of function expects [i32] but 1 extra values remain: [i32]",
+            "br expects i32 but the stack is empty",
+            "end of else expects i32 but the stack is empty",
+            "global.set references global 0 which is immutable",
+            "local.get references local 2 but the function has 0",
+            "return_call returns [i32] but the function returns []",
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn typecheck_function_refs_test() -> anyhow::Result<()> {
+        // Hand-built, as the pinned `wat` predates the final 0x63/0x64 encoding:
+        //   (type $t (func (result i32)))
+        //   (type $g (func (result (ref $t))))
+        //   (type (func (param (ref $g)) (result funcref)))
+        //   (elem declare func $a)
+        //   (func $a (type $t) (i32.const 0))
+        //   (func (export "b") (type $g) (local $r (ref $t))
+        //       (local.set $r (ref.func $a))
+        //       (block (local.set $r (ref.func $a)))
+        //       (local.get $r))
+        //   (func (type 2) (return_call_ref $g (local.get 0)))
+        let module: Vec<u8> = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[0x01, 0x10, 0x03, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x00, 0x01, 0x64, 0x00, 0x60, 0x01, 0x64, 0x01, 0x01, 0x70],
+            &[0x03, 0x04, 0x03, 0x00, 0x01, 0x02],
+            &[0x07, 0x05, 0x01, 0x01, 0x62, 0x00, 0x01],
+            &[0x09, 0x05, 0x01, 0x03, 0x00, 0x01, 0x00],
+            &[0x0a, 0x20, 0x03, 0x04, 0x00, 0x41, 0x00, 0x0b, 0x12, 0x01, 0x01, 0x64, 0x00, 0xd2, 0x00, 0x21, 0x00],
+            &[0x02, 0x40, 0xd2, 0x00, 0x21, 0x00, 0x0b, 0x20, 0x00, 0x0b, 0x06, 0x00, 0x20, 0x00, 0x15, 0x01, 0x0b],
+        ].concat();
+        assert_eq!(typecheck_binary(&module)?, Vec::<String>::new());
+
+        //   (type $t (func (result i32)))
+        //   (type $g (func (result (ref $t))))
+        //   (type (func (result funcref)))
+        //   (elem declare func $a)
+        //   (func $a (type $t) (i32.const 0))
+        //   (func (type $g) (local $r (ref $t)) (local.get $r))
+        //   (func (type $g) (local $r (ref $t))
+        //       (block (local.set $r (ref.func $a)))
+        //       (local.get $r))
+        //   (func (type 2) (ref.func 1))
+        let module: Vec<u8> = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[0x01, 0x0e, 0x03, 0x60, 0x00, 0x01, 0x7f, 0x60, 0x00, 0x01, 0x64, 0x00, 0x60, 0x00, 0x01, 0x70],
+            &[0x03, 0x05, 0x04, 0x00, 0x01, 0x01, 0x02],
+            &[0x09, 0x05, 0x01, 0x03, 0x00, 0x01, 0x00],
+            &[0x0a, 0x22, 0x04, 0x04, 0x00, 0x41, 0x00, 0x0b, 0x07, 0x01, 0x01, 0x64, 0x00, 0x20, 0x00, 0x0b],
+            &[0x0e, 0x01, 0x01, 0x64, 0x00, 0x02, 0x40, 0xd2, 0x00, 0x21, 0x00, 0x0b, 0x20, 0x00, 0x0b],
+            &[0x04, 0x00, 0xd2, 0x01, 0x0b],
+        ].concat();
+        assert_eq!(typecheck_binary(&module)?, vec![
+            "local.get reads local 0 of type (ref 0) before it is set",
+            "local.get reads local 0 of type (ref 0) before it is set",
+            "ref.func references function 1 which no element segment, export or global initializer declares",
+        ]);
+        Ok(())
+    }
+}
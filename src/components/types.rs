@@ -1,38 +1,246 @@
 use crate::{consts::*};
-use crate::components::{instructions::*};
+use crate::components::cancel::{CancellationToken, CANCEL_CHECK_INTERVAL};
+use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+use crate::components::instructions::{const_expr, AwwasmInstruction, InstrContext, InstructionIterator};
+use crate::components::parse_limits::AwwasmParseLimits;
+use crate::components::section::write_leb128_s33;
 use num_derive::FromPrimitive;
 use nom_derive::*;
-use nom_leb128::leb128_u32;
+use crate::components::leb128::{leb128_s33, leb128_u32};
 use nom::bytes::complete::take_while;
+use nom::bytes::complete::take;
+use nom::combinator::map;
+use std::borrow::Cow;
 use nom::combinator::cond;
+use nom::multi::length_count;
 use nom::number::complete::le_u8;
+use nom::IResult;
+use std::fmt;
 
-#[repr(u8)]
-#[derive(Debug, Clone, PartialEq, Eq, FromPrimitive, Nom)]
-#[nom(LittleEndian)]
-pub enum ParamType {
-    IUnknown = 0x00,
-    F64 = 0x7C,
-    F32 = 0x7D,
-    I64 = 0x7E,
-    I32 = 0x7F,
+// Function, table, memory, global and tag indices count imports first, then
+// the module's own definitions.
+/// Index into the type section.
+pub type TypeIdx = u32;
+/// Index into the function index space.
+pub type FuncIdx = u32;
+/// Index into the table index space.
+pub type TableIdx = u32;
+/// Index into the memory index space.
+pub type MemIdx = u32;
+/// Index into the global index space.
+pub type GlobalIdx = u32;
+/// Index into the tag index space.
+pub type TagIdx = u32;
+
+// Value type prefixes of `(ref null ht)` and `(ref ht)` (function references).
+pub(crate) const REF_NULL_TYPE: u8 = 0x63;
+pub(crate) const REF_TYPE: u8 = 0x64;
+// Abstract heap types, as the s33 values of their one-byte encodings.
+const HEAP_TYPE_FUNC: i64 = 0x70 - 0x80;
+const HEAP_TYPE_EXTERN: i64 = 0x6F - 0x80;
+
+/// A WebAssembly value type, as used by function signatures, locals, globals,
+/// block results and typed `select`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+    FuncRef,
+    ExternRef,
+    /// `(ref null? ht)` from the function references proposal. `funcref` and
+    /// `externref` keep their shorthand variants.
+    Ref(AwwasmRefType),
 }
 
-impl Default for ParamType {
-    fn default() -> Self {
-        ParamType::IUnknown
+impl ValType {
+    /// The type's first byte: the whole encoding except for `Ref`, whose heap
+    /// type follows (see `write()`).
+    pub fn encode(self) -> u8 {
+        match self {
+            ValType::I32 => 0x7F,
+            ValType::I64 => 0x7E,
+            ValType::F32 => 0x7D,
+            ValType::F64 => 0x7C,
+            ValType::V128 => 0x7B,
+            ValType::FuncRef => 0x70,
+            ValType::ExternRef => 0x6F,
+            ValType::Ref(r) if r.nullable => REF_NULL_TYPE,
+            ValType::Ref(_) => REF_TYPE,
+        }
+    }
+
+    /// Decode a one-byte value type; `Ref` types need `parse()`.
+    pub fn decode(byte: u8) -> Option<Self> {
+        [ValType::I32, ValType::I64, ValType::F32, ValType::F64, ValType::V128, ValType::FuncRef, ValType::ExternRef]
+            .into_iter()
+            .find(|t| t.encode() == byte)
+    }
+
+    /// Append the type's full binary encoding to `out`.
+    pub fn write(self, out: &mut Vec<u8>) {
+        out.push(self.encode());
+        if let ValType::Ref(r) = self {
+            r.heap_type.write(out);
+        }
+    }
+
+    pub fn is_num(self) -> bool {
+        matches!(self, ValType::I32 | ValType::I64 | ValType::F32 | ValType::F64)
+    }
+
+    pub fn is_vec(self) -> bool {
+        self == ValType::V128
+    }
+
+    pub fn is_ref(self) -> bool {
+        matches!(self, ValType::FuncRef | ValType::ExternRef | ValType::Ref(_))
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+impl<'a> Parse<&'a [u8]> for ValType {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (rest, byte) = le_u8(i)?;
+        match byte {
+            REF_NULL_TYPE | REF_TYPE => {
+                let (rest, heap_type) = AwwasmHeapType::parse(rest)?;
+                Ok((rest, ValType::Ref(AwwasmRefType { nullable: byte == REF_NULL_TYPE, heap_type })))
+            }
+            _ => ValType::decode(byte)
+                .map(|t| (rest, t))
+                .ok_or_else(|| nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Switch))),
+        }
+    }
+}
+
+impl fmt::Display for ValType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+            ValType::F32 => "f32",
+            ValType::F64 => "f64",
+            ValType::V128 => "v128",
+            ValType::FuncRef => "funcref",
+            ValType::ExternRef => "externref",
+            ValType::Ref(r) => return write!(f, "{}", r),
+        })
+    }
+}
+
+/// A heap type, as in the immediate of `ref.null`: an abstract type, or a
+/// concrete function type (function references proposal).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AwwasmHeapType {
+    Func,
+    Extern,
+    /// A function type, by index in the type section.
+    Type(TypeIdx),
+}
+
+impl AwwasmHeapType {
+    /// The heap type's s33 value: the type index, or negative for an
+    /// abstract heap type (-16 for `func`, -17 for `extern`).
+    pub fn s33(self) -> i64 {
+        match self {
+            AwwasmHeapType::Func => HEAP_TYPE_FUNC,
+            AwwasmHeapType::Extern => HEAP_TYPE_EXTERN,
+            AwwasmHeapType::Type(idx) => idx as i64,
+        }
+    }
+
+    /// Append the heap type's s33 encoding to `out`.
+    pub fn write(self, out: &mut Vec<u8>) {
+        match self {
+            AwwasmHeapType::Func => out.push(0x70),
+            AwwasmHeapType::Extern => out.push(0x6F),
+            AwwasmHeapType::Type(idx) => write_leb128_s33(out, idx),
+        }
+    }
+}
+
+impl<'a> Parse<&'a [u8]> for AwwasmHeapType {
+    fn parse(i: &'a [u8]) -> IResult<&'a [u8], Self> {
+        let (rest, value) = leb128_s33(i)?;
+        match value {
+            HEAP_TYPE_FUNC => Ok((rest, AwwasmHeapType::Func)),
+            HEAP_TYPE_EXTERN => Ok((rest, AwwasmHeapType::Extern)),
+            0..=0xFFFF_FFFF => Ok((rest, AwwasmHeapType::Type(value as u32))),
+            _ => Err(nom::Err::Error(nom::error::Error::new(i, nom::error::ErrorKind::Switch))),
+        }
+    }
+}
+
+impl fmt::Display for AwwasmHeapType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AwwasmHeapType::Func => f.write_str("func"),
+            AwwasmHeapType::Extern => f.write_str("extern"),
+            AwwasmHeapType::Type(idx) => write!(f, "{}", idx),
+        }
+    }
+}
+
+/// A reference type `(ref null? ht)` (function references proposal).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AwwasmRefType {
+    pub nullable: bool,
+    pub heap_type: AwwasmHeapType,
+}
+
+impl fmt::Display for AwwasmRefType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.nullable {
+            write!(f, "(ref null {})", self.heap_type)
+        } else {
+            write!(f, "(ref {})", self.heap_type)
+        }
+    }
+}
+
+/// The type of `ref.null ht`: the shorthand for `func` and `extern`.
+impl From<AwwasmHeapType> for ValType {
+    fn from(t: AwwasmHeapType) -> Self {
+        match t {
+            AwwasmHeapType::Func => ValType::FuncRef,
+            AwwasmHeapType::Extern => ValType::ExternRef,
+            heap_type => ValType::Ref(AwwasmRefType { nullable: true, heap_type }),
+        }
+    }
+}
+
+impl From<AwwasmTableReferenceType> for ValType {
+    fn from(t: AwwasmTableReferenceType) -> Self {
+        match t {
+            AwwasmTableReferenceType::Function => ValType::FuncRef,
+            AwwasmTableReferenceType::Extern => ValType::ExternRef,
+        }
+    }
+}
+
+impl TryFrom<ValType> for AwwasmTableReferenceType {
+    type Error = anyhow::Error;
+
+    fn try_from(t: ValType) -> anyhow::Result<Self> {
+        match t {
+            ValType::FuncRef => Ok(AwwasmTableReferenceType::Function),
+            ValType::ExternRef => Ok(AwwasmTableReferenceType::Extern),
+            other => Err(anyhow::anyhow!("{} is not a reference type", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Nom)]
 #[nom(LittleEndian)]
 pub struct AwwasmTypeSectionItem<'a> {
     #[nom(Tag(WASM_TYPE_SECTION_OPCODE_FUNC))]
     pub type_magic: &'a[u8],
     #[nom(LengthCount="leb128_u32")]
-    pub fn_args: Vec<ParamType>,
+    pub fn_args: Vec<ValType>,
     #[nom(LengthCount="leb128_u32")]
-    pub fn_rets: Vec<ParamType>,
+    pub fn_rets: Vec<ValType>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
@@ -62,19 +270,58 @@ pub struct AwwasmFunction<'a> {
     pub code: &'a[u8],
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Eq, Nom)]
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
 #[nom(LittleEndian)]
 pub struct AwwasmFunctionLocals {
     #[nom(Parse="leb128_u32")]
     pub type_count: u32,
-    pub param_type: ParamType,
+    pub param_type: ValType,
 }
 
 impl<'a> AwwasmCodeSectionItem<'a> {
     pub fn resolve(&mut self) -> anyhow::Result<()> {
-        (self.func_body, self.parsed_func) = cond(!self.func_body.is_empty(), AwwasmFunction::<'_>::parse)(self.func_body).map_err(|e| anyhow::anyhow!("Failed to parse WASM Function: {}", e))?;
+        (self.func_body, self.parsed_func) = cond(!self.func_body.is_empty(), AwwasmFunction::<'_>::parse)(self.func_body).map_err(|e| AwwasmParseError::instruction("Function", e).within(self.func_body))?;
         Ok(())
     }
+
+    /// Split the raw body into its local declarations and its instruction bytes
+    /// (including the final `end`). Operates on the body as parsed, i.e. before `resolve()`.
+    pub fn locals_and_code(&self) -> anyhow::Result<(Vec<AwwasmFunctionLocals>, &'a [u8])> {
+        let (code, locals) = length_count(leb128_u32, AwwasmFunctionLocals::parse)(self.func_body)
+            .map_err(|e| AwwasmParseError::nom("Function locals", e).within(self.func_body))?;
+        Ok((locals, code))
+    }
+
+    /// Decode the function body into its top-level instructions.
+    pub fn instructions(&self) -> anyhow::Result<Vec<AwwasmInstruction<'a>>> {
+        self.decode(&AwwasmParseLimits::default(), &CancellationToken::new())
+    }
+
+    /// Like `instructions`, but checks `token` every `CANCEL_CHECK_INTERVAL`
+    /// top-level instructions, and as often within nested bodies, and fails
+    /// with `Cancelled` once it is cancelled.
+    pub fn instructions_with_cancel(&self, token: &CancellationToken) -> anyhow::Result<Vec<AwwasmInstruction<'a>>> {
+        self.decode(&AwwasmParseLimits::default(), token)
+    }
+
+    /// Like `instructions`, but fails with `LimitExceeded` if the body is
+    /// over one of the `limits` on function bodies.
+    pub fn instructions_with_limits(&self, limits: &AwwasmParseLimits) -> anyhow::Result<Vec<AwwasmInstruction<'a>>> {
+        self.decode(limits, &CancellationToken::new())
+    }
+
+    fn decode(&self, limits: &AwwasmParseLimits, token: &CancellationToken) -> anyhow::Result<Vec<AwwasmInstruction<'a>>> {
+        limits.check_body(self)?;
+        let (_, code) = self.locals_and_code()?;
+        let mut instrs = Vec::new();
+        for (i, instr) in InstructionIterator::with_context(code, InstrContext::new(limits).with_cancel(token)).enumerate() {
+            if i % CANCEL_CHECK_INTERVAL == 0 {
+                token.check()?;
+            }
+            instrs.push(instr.map_err(|e| AwwasmParseError::instruction_with_limits("Function body", e, limits).within(code))?);
+        }
+        Ok(instrs)
+    }
 }
 
 // Memory section types
@@ -87,6 +334,25 @@ pub struct AwwasmMemoryParams {
     pub min: u32,
     #[nom(Cond = "(flags & 0x1) != 0", Parse = "leb128_u32")]
     pub max: Option<u32>,
+    /// Log2 of the page size in bytes, when declared (custom page sizes,
+    /// limits flag 0x8). Only 0 and 16 are valid.
+    #[nom(Cond = "(flags & 0x8) != 0", Parse = "leb128_u32")]
+    pub page_size_log2: Option<u32>,
+}
+
+impl AwwasmMemoryParams {
+    /// Whether the memory is shared between threads (limits flag 0x2).
+    /// Atomic accesses to it are only ordered across agents when it is.
+    pub fn is_shared(&self) -> bool {
+        self.flags & 0x2 != 0
+    }
+
+    /// Page size in bytes: 64 KiB unless the memory declares its own.
+    /// `min` and `max` count pages of this size. 0 if the declared log2 is
+    /// 64 or more.
+    pub fn page_size(&self) -> u64 {
+        1u64.checked_shl(self.page_size_log2.unwrap_or(16)).unwrap_or(0)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
@@ -95,14 +361,54 @@ pub struct AwwasmMemorySectionItem {
     pub limits: AwwasmMemoryParams,
 }
 
+// Tag section types
+/// An exception tag: the function type of the values a `throw` of it carries
+/// (its results must be empty).
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmTagSectionItem {
+    /// Always 0 (exception).
+    #[nom(Verify = "*attribute == 0")]
+    pub attribute: u8,
+    #[nom(Parse = "leb128_u32")]
+    pub type_idx: u32,
+}
+
 // Import section types
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
 #[nom(LittleEndian)]
 pub struct AwwasmName<'a> {
     #[nom(Parse = "leb128_u32")]
     pub len: u32,
-    #[nom(Take = "len")]
-    pub bytes: &'a [u8],
+    #[nom(Parse = "map(take(len), Cow::Borrowed)")]
+    pub bytes: Cow<'a, [u8]>,
+}
+
+impl<'a> AwwasmName<'a> {
+    /// A name for a new or edited entry, borrowing `name` or taking
+    /// ownership of it.
+    pub fn new(name: impl Into<Cow<'a, str>>) -> Self {
+        let bytes = match name.into() {
+            Cow::Borrowed(name) => Cow::Borrowed(name.as_bytes()),
+            Cow::Owned(name) => Cow::Owned(name.into_bytes()),
+        };
+        AwwasmName { len: bytes.len() as u32, bytes }
+    }
+
+    /// The name as UTF-8, or `None` if the bytes are not valid UTF-8.
+    pub fn to_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.bytes).ok()
+    }
+
+    /// The name as UTF-8, or a `Malformed` error if the bytes are not valid
+    /// UTF-8. Import, export and custom section names are checked by
+    /// `AwwasmModule::validate()`, so this does not fail on them once it
+    /// reports no `names` findings.
+    pub fn as_str(&self) -> Result<&str, AwwasmParseError> {
+        std::str::from_utf8(&self.bytes).map_err(|e| AwwasmParseError::new("name", AwwasmParseErrorKind::Malformed {
+            detail: format!("invalid UTF-8 at byte {} of the name", e.valid_up_to()),
+        }))
+    }
 }
 
 #[repr(u8)]
@@ -113,6 +419,7 @@ pub enum AwwasmImportKind {
     Table = 0x01,
     Memory = 0x02,
     Global = 0x03,
+    Tag = 0x04,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
@@ -123,8 +430,14 @@ pub struct AwwasmImportSectionItem<'a> {
     pub kind: AwwasmImportKind,
     #[nom(Cond = "kind == AwwasmImportKind::Function", Parse = "leb128_u32")]
     pub func_type_idx: Option<u32>,
+    #[nom(Cond = "kind == AwwasmImportKind::Table")]
+    pub table: Option<AwwasmTableSectionItem>,
     #[nom(Cond = "kind == AwwasmImportKind::Memory")]
     pub mem: Option<AwwasmMemoryParams>,
+    #[nom(Cond = "kind == AwwasmImportKind::Global")]
+    pub global: Option<AwwasmGlobalType>,
+    #[nom(Cond = "kind == AwwasmImportKind::Tag")]
+    pub tag: Option<AwwasmTagSectionItem>,
 }
 
 // Export section types
@@ -136,6 +449,7 @@ pub enum AwwasmExportKind {
     Table = 0x01,
     Memory = 0x02,
     Global = 0x03,
+    Tag = 0x04,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
@@ -147,6 +461,14 @@ pub struct AwwasmExportSectionItem<'a> {
     pub index: u32,
 }
 
+// Custom section types
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmCustomSectionItem<'a> {
+    pub name: AwwasmName<'a>,
+    /// Everything after the name, uninterpreted.
+    pub payload: &'a [u8],
+}
+
 // Start section types
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
 #[nom(LittleEndian)]
@@ -159,7 +481,7 @@ pub struct AwwasmStartSectionItem {
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
 #[nom(LittleEndian)]
 pub struct AwwasmDataInitExpr<'a> {
-    #[nom(Parse = "take_while(|byte| byte != WASM_FUNC_SECTION_OPCODE_END)")]
+    #[nom(Parse = "const_expr")]
     pub code: &'a [u8],
     #[nom(Parse = "le_u8")]
     pub end: u8,
@@ -195,10 +517,18 @@ pub enum AwwasmGlobalMutability {
     Mutable   = 0x01,
 }
 
+// Global type, as used by global imports
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmGlobalType {
+    pub value_type: ValType,
+    pub mutability: AwwasmGlobalMutability,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
 #[nom(LittleEndian)]
 pub struct AwwasmGlobalSectionItem<'a> {
-    pub value_type: ParamType,
+    pub value_type: ValType,
     pub mutability: AwwasmGlobalMutability,
     pub init_expr: AwwasmDataInitExpr<'a>,
 }
@@ -275,6 +605,53 @@ pub struct AwwasmDeclarativeElemSeg {
     pub func_indices: Vec<u32>,
 }
 
+// Active element segment, implicit table, expression list
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmActiveImplicitExprElemSeg<'a> {
+    pub offset: AwwasmDataInitExpr<'a>,
+    #[nom(Parse = "leb128_u32")]
+    pub expr_count: u32,
+    #[nom(Count = "expr_count as usize")]
+    pub exprs: Vec<AwwasmDataInitExpr<'a>>,
+}
+
+// Passive element segment, expression list
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmPassiveExprElemSeg<'a> {
+    pub reftype: AwwasmTableReferenceType,
+    #[nom(Parse = "leb128_u32")]
+    pub expr_count: u32,
+    #[nom(Count = "expr_count as usize")]
+    pub exprs: Vec<AwwasmDataInitExpr<'a>>,
+}
+
+// Active element segment, explicit table, expression list
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmActiveExplicitExprElemSeg<'a> {
+    #[nom(Parse = "leb128_u32")]
+    pub tableidx: u32,
+    pub offset: AwwasmDataInitExpr<'a>,
+    pub reftype: AwwasmTableReferenceType,
+    #[nom(Parse = "leb128_u32")]
+    pub expr_count: u32,
+    #[nom(Count = "expr_count as usize")]
+    pub exprs: Vec<AwwasmDataInitExpr<'a>>,
+}
+
+// Declarative element segment, expression list
+#[derive(Debug, Clone, PartialEq, Eq, Nom)]
+#[nom(LittleEndian)]
+pub struct AwwasmDeclarativeExprElemSeg<'a> {
+    pub reftype: AwwasmTableReferenceType,
+    #[nom(Parse = "leb128_u32")]
+    pub expr_count: u32,
+    #[nom(Count = "expr_count as usize")]
+    pub exprs: Vec<AwwasmDataInitExpr<'a>>,
+}
+
 // Dispatcher enum — selects a payload subtype based on the flags value.
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
 #[nom(LittleEndian, Selector = "u32")]
@@ -291,6 +668,72 @@ pub enum AwwasmElemSegmentBody<'a> {
     // flags = 0x03: declarative
     #[nom(Selector = "3_u32")]
     Declarative(AwwasmDeclarativeElemSeg),
+    // flags = 0x04: active, implicit table 0, expressions
+    #[nom(Selector = "4_u32")]
+    ActiveImplicitExprs(AwwasmActiveImplicitExprElemSeg<'a>),
+    // flags = 0x05: passive, expressions
+    #[nom(Selector = "5_u32")]
+    PassiveExprs(AwwasmPassiveExprElemSeg<'a>),
+    // flags = 0x06: active, explicit tableidx, expressions
+    #[nom(Selector = "6_u32")]
+    ActiveExplicitExprs(AwwasmActiveExplicitExprElemSeg<'a>),
+    // flags = 0x07: declarative, expressions
+    #[nom(Selector = "7_u32")]
+    DeclarativeExprs(AwwasmDeclarativeExprElemSeg<'a>),
+}
+
+impl<'a> AwwasmElemSegmentBody<'a> {
+    /// Every constant expression in the segment: the offset of an active
+    /// segment, then the element expressions.
+    pub fn init_exprs(&self) -> Vec<&AwwasmDataInitExpr<'a>> {
+        let (offset, exprs): (Option<&AwwasmDataInitExpr<'a>>, &[AwwasmDataInitExpr<'a>]) = match self {
+            AwwasmElemSegmentBody::ActiveImplicit(s) => (Some(&s.offset), &[]),
+            AwwasmElemSegmentBody::ActiveExplicit(s) => (Some(&s.offset), &[]),
+            AwwasmElemSegmentBody::ActiveImplicitExprs(s) => (Some(&s.offset), &s.exprs),
+            AwwasmElemSegmentBody::ActiveExplicitExprs(s) => (Some(&s.offset), &s.exprs),
+            AwwasmElemSegmentBody::PassiveExprs(s) => (None, &s.exprs),
+            AwwasmElemSegmentBody::DeclarativeExprs(s) => (None, &s.exprs),
+            AwwasmElemSegmentBody::Passive(_) | AwwasmElemSegmentBody::Declarative(_) => (None, &[]),
+        };
+        offset.into_iter().chain(exprs).collect()
+    }
+
+    /// Like `init_exprs`, for editing them in place.
+    pub fn init_exprs_mut(&mut self) -> Vec<&mut AwwasmDataInitExpr<'a>> {
+        let (offset, exprs): (Option<&mut AwwasmDataInitExpr<'a>>, &mut [AwwasmDataInitExpr<'a>]) = match self {
+            AwwasmElemSegmentBody::ActiveImplicit(s) => (Some(&mut s.offset), &mut []),
+            AwwasmElemSegmentBody::ActiveExplicit(s) => (Some(&mut s.offset), &mut []),
+            AwwasmElemSegmentBody::ActiveImplicitExprs(s) => (Some(&mut s.offset), &mut s.exprs),
+            AwwasmElemSegmentBody::ActiveExplicitExprs(s) => (Some(&mut s.offset), &mut s.exprs),
+            AwwasmElemSegmentBody::PassiveExprs(s) => (None, &mut s.exprs),
+            AwwasmElemSegmentBody::DeclarativeExprs(s) => (None, &mut s.exprs),
+            AwwasmElemSegmentBody::Passive(_) | AwwasmElemSegmentBody::Declarative(_) => (None, &mut []),
+        };
+        offset.into_iter().chain(exprs).collect()
+    }
+
+    /// The function indices of a segment listing functions by index; `None`
+    /// for segments of expressions.
+    pub fn func_indices(&self) -> Option<&[u32]> {
+        match self {
+            AwwasmElemSegmentBody::ActiveImplicit(s) => Some(&s.func_indices),
+            AwwasmElemSegmentBody::Passive(s) => Some(&s.func_indices),
+            AwwasmElemSegmentBody::ActiveExplicit(s) => Some(&s.func_indices),
+            AwwasmElemSegmentBody::Declarative(s) => Some(&s.func_indices),
+            _ => None,
+        }
+    }
+
+    /// Like `func_indices`, but mutable.
+    pub fn func_indices_mut(&mut self) -> Option<&mut Vec<u32>> {
+        match self {
+            AwwasmElemSegmentBody::ActiveImplicit(s) => Some(&mut s.func_indices),
+            AwwasmElemSegmentBody::Passive(s) => Some(&mut s.func_indices),
+            AwwasmElemSegmentBody::ActiveExplicit(s) => Some(&mut s.func_indices),
+            AwwasmElemSegmentBody::Declarative(s) => Some(&mut s.func_indices),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Nom)]
@@ -0,0 +1,701 @@
+use crate::components::instructions::*;
+use crate::components::module::AwwasmModule;
+use crate::components::typecheck::val_type_matches;
+use crate::components::types::*;
+use crate::consts::WASM_TYPE_SECTION_OPCODE_FUNC;
+use std::collections::HashSet;
+use std::fmt;
+
+/// A validation error found by `AwwasmModule::validate()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwwasmValidationFinding {
+    /// Name of the check that reported it (e.g. `"exports"`).
+    pub check: &'static str,
+    /// Function index, for findings about a function body.
+    pub function: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for AwwasmValidationFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.function {
+            Some(idx) => write!(f, "[{}] function {}: {}", self.check, idx, self.message),
+            None => write!(f, "[{}] {}", self.check, self.message),
+        }
+    }
+}
+
+// A check over the whole module, independent of every other check.
+type ModuleCheck = for<'m, 'a> fn(&'m AwwasmModule<'a>) -> Vec<AwwasmValidationFinding>;
+
+const MODULE_CHECKS: &[ModuleCheck] = &[
+    check_types,
+    check_functions,
+    check_exports,
+    check_memories,
+    check_tables,
+    check_names,
+    check_section_order,
+];
+
+/// A group of checks run by `AwwasmModule::validate_stage()`, each assuming
+/// only that the module decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AwwasmValidationStage {
+    /// Section-level rules: declarations, exports, limits.
+    Module,
+    /// Global, element and data segment init expressions.
+    ConstExprs,
+    /// Function bodies, type checking included.
+    Functions,
+}
+
+impl AwwasmValidationStage {
+    /// Every stage, in the order `validate()` runs them.
+    pub const ALL: [AwwasmValidationStage; 3] =
+        [AwwasmValidationStage::Module, AwwasmValidationStage::ConstExprs, AwwasmValidationStage::Functions];
+}
+
+// Map `f` over `items`, in parallel when the `rayon` feature is enabled.
+// Results are always returned in input order.
+#[cfg(feature = "rayon")]
+fn map_tasks<T: Sync, R: Send>(items: &[T], f: impl Fn(usize, &T) -> R + Sync + Send) -> Vec<R> {
+    use rayon::prelude::*;
+    items.par_iter().enumerate().map(|(i, item)| f(i, item)).collect()
+}
+
+#[cfg(not(feature = "rayon"))]
+fn map_tasks<T: Sync, R: Send>(items: &[T], f: impl Fn(usize, &T) -> R + Sync + Send) -> Vec<R> {
+    items.iter().enumerate().map(|(i, item)| f(i, item)).collect()
+}
+
+fn finding(check: &'static str, function: Option<u32>, message: String) -> AwwasmValidationFinding {
+    AwwasmValidationFinding { check, function, message }
+}
+
+impl<'a> AwwasmModule<'a> {
+    pub(crate) fn imported_count(&self, kind: AwwasmImportKind) -> u32 {
+        self.imports().iter()
+            .filter(|i| i.kind == kind)
+            .count() as u32
+    }
+
+    /// Number of functions imported by the module; defined functions are
+    /// numbered after them in the function index space.
+    pub fn imported_func_count(&self) -> u32 {
+        self.imported_count(AwwasmImportKind::Function)
+    }
+
+    /// Size of the function index space (imports and definitions).
+    pub fn func_count(&self) -> u32 {
+        self.imported_func_count() + self.funcs().len() as u32
+    }
+
+    /// Size of the memory index space (imports and definitions).
+    pub fn memory_count(&self) -> u32 {
+        self.imported_count(AwwasmImportKind::Memory) + self.memories().len() as u32
+    }
+
+    /// Size of the global index space (imports and definitions).
+    pub fn global_count(&self) -> u32 {
+        self.imported_count(AwwasmImportKind::Global) + self.globals().len() as u32
+    }
+
+    /// Size of the tag index space (imports and definitions).
+    pub fn tag_count(&self) -> u32 {
+        self.imported_count(AwwasmImportKind::Tag) + self.tags().len() as u32
+    }
+
+    /// Reference types of every table in the table index space (imports first).
+    pub fn table_types(&self) -> Vec<AwwasmTableReferenceType> {
+        self.imports().iter()
+            .filter_map(|i| i.table.as_ref())
+            .chain(self.tables().iter())
+            .map(|t| t.elem_type.clone())
+            .collect()
+    }
+
+    /// Run every validation check and return all findings; empty means valid.
+    ///
+    /// Module-level checks and per-function body checks are independent tasks.
+    /// With the `rayon` feature they run in parallel; either way, findings are
+    /// returned in the same order: the stages of `AwwasmValidationStage::ALL`
+    /// in turn, function bodies by index.
+    /// Requires `resolve_all_sections()`.
+    pub fn validate(&self) -> Vec<AwwasmValidationFinding> {
+        AwwasmValidationStage::ALL.iter().flat_map(|stage| self.validate_stage(*stage)).collect()
+    }
+
+    /// Run one stage of `validate()`, e.g. to check init expressions before
+    /// paying for function bodies.
+    ///
+    /// Requires `resolve_all_sections()`.
+    pub fn validate_stage(&self, stage: AwwasmValidationStage) -> Vec<AwwasmValidationFinding> {
+        match stage {
+            AwwasmValidationStage::Module => map_tasks(MODULE_CHECKS, |_, check| check(self))
+                .into_iter()
+                .flatten()
+                .collect(),
+            AwwasmValidationStage::ConstExprs => check_const_exprs(self),
+            AwwasmValidationStage::Functions => {
+                let tables = self.table_types();
+                let imported = self.imported_func_count();
+                let code = self.code();
+                map_tasks(code, |i, item| self.check_function_body(imported + i as u32, item, &tables))
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            }
+        }
+    }
+
+    // Checks on one function body, independent of every other body.
+    fn check_function_body(&self, func_idx: u32, item: &AwwasmCodeSectionItem<'a>, tables: &[AwwasmTableReferenceType]) -> Vec<AwwasmValidationFinding> {
+        let instrs = match item.instructions_with_limits(&self.limits) {
+            Ok(instrs) => instrs,
+            Err(e) => return vec![finding("code", Some(func_idx), self.locate_error(e).to_string())],
+        };
+        let type_count = self.types().len();
+        let memory_count = self.memory_count();
+        let mut findings = Vec::new();
+        for instr in &instrs {
+            instr.walk(&mut |instr| {
+                let memidx = match &instr.operands {
+                    AwwasmOperands::MemorySize(op) | AwwasmOperands::MemoryGrow(op) => Some(op.memidx),
+                    operands => operands.mem_arg().and_then(|m| m.memidx),
+                };
+                if let Some(idx) = memidx.filter(|idx| *idx >= memory_count) {
+                    let message = format!("{} references memory {} but the module has {} memories", instr.opcode.mnemonic(), idx, memory_count);
+                    findings.push(finding("memory", Some(func_idx), message));
+                }
+                if let Some(BlockType::TypeIndex(idx)) = instr.operands.block_type() {
+                    if idx as usize >= type_count {
+                        let message = format!("{} references type {} but the module has {} types", instr.opcode.mnemonic(), idx, type_count);
+                        findings.push(finding("block_type", Some(func_idx), message));
+                    }
+                }
+                if let AwwasmOperands::Atomic(op) = &instr.operands {
+                    if let (Some(m), Some(natural)) = (&op.mem_arg, op.sub_op.natural_alignment()) {
+                        if m.align != natural {
+                            let message = format!("{} has alignment {} but atomic accesses require exactly {}", op.sub_op.mnemonic(), m.align, natural);
+                            findings.push(finding("atomic_alignment", Some(func_idx), message));
+                        }
+                    }
+                }
+                let (AwwasmOperands::CallIndirect(op) | AwwasmOperands::ReturnCallIndirect(op)) = &instr.operands else { return };
+                let what = instr.opcode.mnemonic();
+                let message = match tables.get(op.tableidx as usize) {
+                    None => format!("{} references table {} but the module has {} tables", what, op.tableidx, tables.len()),
+                    Some(AwwasmTableReferenceType::Extern) => format!("{} references table {} which is not a funcref table", what, op.tableidx),
+                    Some(AwwasmTableReferenceType::Function) if op.typeidx as usize >= type_count =>
+                        format!("{} references type {} but the module has {} types", what, op.typeidx, type_count),
+                    Some(AwwasmTableReferenceType::Function) => return,
+                };
+                findings.push(finding("call_indirect", Some(func_idx), message));
+            });
+        }
+        if let Err(message) = self.typecheck_function(func_idx, item) {
+            findings.push(finding("typecheck", Some(func_idx), message));
+        }
+        findings
+    }
+
+    /// Check that every `call_indirect` and `return_call_indirect` names an
+    /// existing funcref table and a defined function type.
+    ///
+    /// Requires `resolve_all_sections()`; function bodies are decoded from the
+    /// unresolved code section items.
+    pub fn validate_call_indirect(&self) -> anyhow::Result<()> {
+        let tables = self.table_types();
+        let imported = self.imported_func_count();
+        for (i, item) in self.code().iter().enumerate() {
+            let func_idx = imported + i as u32;
+            if let Some(f) = self.check_function_body(func_idx, item, &tables).into_iter().find(|f| f.check == "call_indirect") {
+                return Err(anyhow::anyhow!("function {}: {}", func_idx, f.message));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Type entries must use the function type form, and reference value types
+// must name defined types.
+fn check_types(module: &AwwasmModule) -> Vec<AwwasmValidationFinding> {
+    let mut findings = Vec::new();
+    let type_count = module.types().len();
+    for (i, ty) in module.types().iter().enumerate() {
+        if ty.type_magic != WASM_TYPE_SECTION_OPCODE_FUNC {
+            findings.push(finding("types", None,
+                format!("type {} has form {:02x?} but function types use 0x60", i, ty.type_magic)));
+        }
+        for val_type in ty.fn_args.iter().chain(&ty.fn_rets) {
+            if let ValType::Ref(AwwasmRefType { heap_type: AwwasmHeapType::Type(idx), .. }) = val_type {
+                if *idx as usize >= type_count {
+                    findings.push(finding("types", None,
+                        format!("type {} uses {} but the module has {} types", i, val_type, type_count)));
+                }
+            }
+        }
+    }
+    findings
+}
+
+// Function declarations must name defined types and line up with the code section.
+fn check_functions(module: &AwwasmModule) -> Vec<AwwasmValidationFinding> {
+    let mut findings = Vec::new();
+    let type_count = module.types().len();
+    let imported = module.imported_func_count();
+
+    for (i, import) in module.imports().iter().enumerate() {
+        if let Some(idx) = import.func_type_idx.filter(|idx| *idx as usize >= type_count) {
+            findings.push(finding("functions", None,
+                format!("import {} references type {} but the module has {} types", i, idx, type_count)));
+        }
+    }
+    for (i, func) in module.funcs().iter().enumerate() {
+        if func.type_item_idx as usize >= type_count {
+            findings.push(finding("functions", Some(imported + i as u32),
+                format!("references type {} but the module has {} types", func.type_item_idx, type_count)));
+        }
+    }
+
+    let declared = module.funcs().len();
+    let bodies = module.code().len();
+    if declared != bodies {
+        findings.push(finding("functions", None,
+            format!("function section declares {} functions but the code section has {} bodies", declared, bodies)));
+    }
+    findings
+}
+
+// Limits flag bits: a maximum follows, shared, 64-bit, a page size follows.
+const LIMITS_HAS_MAX: u32 = 0x1;
+const LIMITS_SHARED: u32 = 0x2;
+const LIMITS_64: u32 = 0x4;
+const LIMITS_PAGE_SIZE: u32 = 0x8;
+
+// min <= max, shared memories need a maximum, and the page size (1 byte or
+// 64 KiB) bounds the page counts so the memory fits in 4 GiB.
+// 64-bit limits are only reported as unsupported; their 2^48-page bound is
+// not applied.
+fn check_memories(module: &AwwasmModule) -> Vec<AwwasmValidationFinding> {
+    let imported = module.imports().iter().filter_map(|i| i.mem.as_ref());
+    let defined = module.memories().iter().map(|m| &m.limits);
+    let mut findings = Vec::new();
+    for (i, m) in imported.chain(defined).enumerate() {
+        let mut report = |message: String| findings.push(finding("memory", None, format!("memory {} {}", i, message)));
+        let known = LIMITS_HAS_MAX | LIMITS_SHARED | LIMITS_64 | LIMITS_PAGE_SIZE;
+        if m.flags & !known != 0 {
+            report(format!("has limits flags {:#04x} but only 0x00-0x0f are defined", m.flags));
+        } else if m.flags & LIMITS_64 != 0 {
+            report("has 64-bit limits, which are not supported".to_owned());
+        }
+        if let Some(log2) = m.page_size_log2.filter(|log2| *log2 != 0 && *log2 != 16) {
+            report(format!("declares page size 2^{} but only 1 and 65536 are allowed", log2));
+            continue;
+        }
+        let max_pages = (1u64 << 32) / m.page_size();
+        for (what, pages) in [("minimum", Some(m.min)), ("maximum", m.max)] {
+            if let Some(pages) = pages.filter(|p| *p as u64 > max_pages) {
+                report(format!("has a {} of {} pages but at most {} are allowed", what, pages, max_pages));
+            }
+        }
+        if let Some(max) = m.max.filter(|max| m.min > *max) {
+            report(format!("has minimum {} greater than maximum {}", m.min, max));
+        }
+        if m.is_shared() && m.max.is_none() {
+            report("is shared but has no maximum".to_owned());
+        }
+    }
+    findings
+}
+
+// Tables take only the maximum flag, and min <= max.
+fn check_tables(module: &AwwasmModule) -> Vec<AwwasmValidationFinding> {
+    let imported = module.imports().iter().filter_map(|i| i.table.as_ref());
+    let mut findings = Vec::new();
+    for (i, table) in imported.chain(module.tables().iter()).enumerate() {
+        let limits = &table.limits;
+        if limits.flags & !LIMITS_HAS_MAX != 0 {
+            findings.push(finding("table", None,
+                format!("table {} has limits flags {:#04x} but tables only allow 0x00 and 0x01", i, limits.flags)));
+        }
+        if let Some(max) = limits.max.filter(|max| limits.min > *max) {
+            findings.push(finding("table", None, format!("table {} has minimum {} greater than maximum {}", i, limits.min, max)));
+        }
+    }
+    findings
+}
+
+// Type of the single value a constant expression produces, or why it is
+// invalid. `global.get` may read any immutable global before `globals_visible`.
+fn const_expr_type(module: &AwwasmModule, expr: &AwwasmDataInitExpr, globals: &[(ValType, bool)], globals_visible: usize) -> Result<ValType, String> {
+    use AwwasmOperands::*;
+    let mut stack = Vec::new();
+    for instr in InstructionIterator::new(expr.code) {
+        let instr = instr.map_err(|e| format!("does not decode: {}", e))?;
+        let pushed = match &instr.operands {
+            I32Const(_) => ValType::I32,
+            I64Const(_) => ValType::I64,
+            F32Const(_) => ValType::F32,
+            F64Const(_) => ValType::F64,
+            RefNull(op) => match op.heap_type {
+                AwwasmHeapType::Func => ValType::FuncRef,
+                AwwasmHeapType::Extern => ValType::ExternRef,
+                heap_type => ValType::Ref(AwwasmRefType { nullable: true, heap_type }),
+            },
+            RefFunc(op) if op.funcidx >= module.func_count() => {
+                return Err(format!("references function {} but the module has {}", op.funcidx, module.func_count()));
+            }
+            RefFunc(_) => ValType::FuncRef,
+            GlobalGet(op) => match globals.get(op.index as usize) {
+                Some(_) if op.index as usize >= globals_visible => return Err(format!("reads global {} which is not defined before it", op.index)),
+                Some((_, true)) => return Err(format!("reads global {} which is mutable", op.index)),
+                Some((ty, false)) => *ty,
+                None => return Err(format!("reads global {} but the module has {}", op.index, module.global_count())),
+            },
+            I32Add | I32Sub | I32Mul | I64Add | I64Sub | I64Mul => {
+                let ty = if instr.opcode.mnemonic().starts_with("i32") { ValType::I32 } else { ValType::I64 };
+                for _ in 0..2 {
+                    match stack.pop() {
+                        Some(operand) if operand == ty => {}
+                        Some(operand) => return Err(format!("{} expects {} but found {}", instr.opcode.mnemonic(), ty, operand)),
+                        None => return Err(format!("{} expects {} but the stack is empty", instr.opcode.mnemonic(), ty)),
+                    }
+                }
+                ty
+            }
+            _ => return Err(format!("{} is not a constant instruction", instr.opcode.mnemonic())),
+        };
+        stack.push(pushed);
+    }
+    match stack[..] {
+        [ty] => Ok(ty),
+        _ => Err(format!("produces {} values instead of one", stack.len())),
+    }
+}
+
+// Init expressions must be constant and produce one value of the right type.
+fn check_const_exprs(module: &AwwasmModule) -> Vec<AwwasmValidationFinding> {
+    let globals = module.global_types();
+    let imported_globals = globals.len() - module.globals().len();
+    let mut findings = Vec::new();
+    let mut check = |what: String, expr: &AwwasmDataInitExpr, expected: ValType, visible: usize| {
+        let message = match const_expr_type(module, expr, &globals, visible) {
+            Ok(ty) if val_type_matches(ty, expected) => return,
+            Ok(ty) => format!("produces {} but must produce {}", ty, expected),
+            Err(message) => message,
+        };
+        findings.push(finding("const_expr", None, format!("{} {}", what, message)));
+    };
+
+    for (i, global) in module.globals().iter().enumerate() {
+        let idx = imported_globals + i;
+        check(format!("global {} init expression", idx), &global.init_expr, global.value_type, idx);
+    }
+    for (i, element) in module.elements().iter().enumerate() {
+        use AwwasmElemSegmentBody::*;
+        let (offset, reftype, exprs) = match &element.body {
+            ActiveImplicitExprs(s) => (Some(&s.offset), AwwasmTableReferenceType::Function, &s.exprs[..]),
+            ActiveExplicitExprs(s) => (Some(&s.offset), s.reftype.clone(), &s.exprs[..]),
+            PassiveExprs(s) => (None, s.reftype.clone(), &s.exprs[..]),
+            DeclarativeExprs(s) => (None, s.reftype.clone(), &s.exprs[..]),
+            ActiveImplicit(s) => (Some(&s.offset), AwwasmTableReferenceType::Function, &[][..]),
+            ActiveExplicit(s) => (Some(&s.offset), AwwasmTableReferenceType::Function, &[][..]),
+            Passive(_) | Declarative(_) => (None, AwwasmTableReferenceType::Function, &[][..]),
+        };
+        if let Some(offset) = offset {
+            check(format!("element segment {} offset", i), offset, ValType::I32, globals.len());
+        }
+        let item_type = match reftype {
+            AwwasmTableReferenceType::Function => ValType::FuncRef,
+            AwwasmTableReferenceType::Extern => ValType::ExternRef,
+        };
+        for (j, expr) in exprs.iter().enumerate() {
+            check(format!("element segment {} item {}", i, j), expr, item_type, globals.len());
+        }
+    }
+    for (i, data) in module.data().iter().enumerate() {
+        if let Some(offset) = &data.header.offset {
+            check(format!("data segment {} offset", i), offset, ValType::I32, globals.len());
+        }
+    }
+    findings
+}
+
+// Import, export and custom section names must be valid UTF-8.
+fn check_names(module: &AwwasmModule) -> Vec<AwwasmValidationFinding> {
+    let imports = module.imports().iter().enumerate()
+        .flat_map(|(i, import)| [(format!("import {} module name", i), &import.module), (format!("import {} field name", i), &import.name)]);
+    let exports = module.exports().iter().enumerate()
+        .map(|(i, export)| (format!("export {} name", i), &export.name));
+    let customs = module.customs().iter().enumerate()
+        .map(|(i, custom)| (format!("custom section {} name", i), &custom.name));
+    imports.chain(exports).chain(customs)
+        .filter_map(|(what, name)| {
+            let error = std::str::from_utf8(&name.bytes).err()?;
+            let message = match module.offset_of(&name.bytes) {
+                Some(offset) => format!("{} is not valid UTF-8 (invalid byte at offset {:#x})", what, offset + error.valid_up_to()),
+                None => format!("{} is not valid UTF-8", what),
+            };
+            Some(finding("names", None, message))
+        })
+        .collect()
+}
+
+// Non-custom sections appear at most once, in the order the spec lists them.
+fn check_section_order(module: &AwwasmModule) -> Vec<AwwasmValidationFinding> {
+    module.section_order_issues().into_iter()
+        .map(|issue| finding("section_order", None, issue.to_string()))
+        .collect()
+}
+
+// Exports must reference existing items and have unique names.
+fn check_exports(module: &AwwasmModule) -> Vec<AwwasmValidationFinding> {
+    let mut findings = Vec::new();
+    let mut seen = HashSet::new();
+    for export in module.exports() {
+        let name = String::from_utf8_lossy(&export.name.bytes);
+        if !seen.insert(&*export.name.bytes) {
+            findings.push(finding("exports", None, format!("duplicate export name \"{}\"", name)));
+        }
+        let (what, count) = match export.kind {
+            AwwasmExportKind::Function => ("function", module.func_count()),
+            AwwasmExportKind::Table => ("table", module.table_types().len() as u32),
+            AwwasmExportKind::Memory => ("memory", module.memory_count()),
+            AwwasmExportKind::Global => ("global", module.global_count()),
+            AwwasmExportKind::Tag => ("tag", module.tag_count()),
+        };
+        if export.index >= count {
+            findings.push(finding("exports", None,
+                format!("export \"{}\" references {} {} but the module has {}", name, what, export.index, count)));
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::module::AwwasmModule;
+    use crate::components::validate::AwwasmValidationStage;
+
+    fn validate(wat: &str) -> anyhow::Result<()> {
+        let module = wat::parse_str(wat)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+        module_parsed.validate_call_indirect()
+    }
+
+    #[test]
+    fn validate_call_indirect_ok_test() -> anyhow::Result<()> {
+        validate(r#"
+            (module
+                (type $t (func (result i32)))
+                (import "env" "tbl" (table 1 funcref))
+                (func (result i32)
+                    (block (result i32)
+                        (call_indirect (type $t) (i32.const 0))))
+            )
+        "#)
+    }
+
+    #[test]
+    fn validate_call_indirect_errors_test() {
+        let missing_table = validate(r#"
+            (module
+                (type $t (func))
+                (func (call_indirect (type $t) (i32.const 0)))
+            )
+        "#);
+        assert!(missing_table.unwrap_err().to_string().contains("references table 0 but the module has 0 tables"));
+
+        let externref_table = validate(r#"
+            (module
+                (type $t (func))
+                (table 1 externref)
+                (func (call_indirect (type $t) (i32.const 0)))
+            )
+        "#);
+        assert!(externref_table.unwrap_err().to_string().contains("not a funcref table"));
+
+        let missing_type = validate(r#"
+            (module
+                (table 1 funcref)
+                (func (call_indirect (type 3) (i32.const 0)))
+            )
+        "#);
+        assert!(missing_type.unwrap_err().to_string().contains("references type 3 but the module has"));
+    }
+
+    #[test]
+    fn validate_atomic_alignment_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1 1 shared)
+                (func (param i32) (result i64)
+                    (i64.atomic.load align=4 (local.get 0)))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let findings: Vec<String> = module_parsed.validate().iter().map(|f| f.to_string()).collect();
+        assert_eq!(findings, vec![
+            "[atomic_alignment] function 0: i64.atomic.load has alignment 2 but atomic accesses require exactly 3",
+        ]);
+        module_parsed.validate_call_indirect()
+    }
+
+    #[test]
+    fn validate_memory_index_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (memory 1)
+                (func (result i32)
+                    (drop (memory.grow 2 (i32.const 1)))
+                    (i32.load 1 (i32.const 0)))
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let findings: Vec<String> = module_parsed.validate().iter().map(|f| f.to_string()).collect();
+        assert_eq!(findings, vec![
+            "[memory] function 0: memory.grow references memory 2 but the module has 1 memories",
+            "[memory] function 0: i32.load references memory 1 but the module has 1 memories",
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn validate_aggregates_findings_in_order_test() -> anyhow::Result<()> {
+        // Hand-built: one type, two functions (the second naming type 5), an
+        // export of function 7 plus a duplicate export name, and bodies that
+        // call_indirect through a missing table.
+        let module: Vec<u8> = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[0x01, 0x04, 0x01, 0x60, 0x00, 0x00],                   // type: () -> ()
+            &[0x03, 0x03, 0x02, 0x00, 0x05],                         // funcs: type 0, type 5
+            &[0x07, 0x09, 0x02, 0x01, b'a', 0x00, 0x00, 0x01, b'a', 0x00, 0x07], // exports
+            &[0x0a, 0x11, 0x02,                                      // code
+                0x07, 0x00, 0x41, 0x00, 0x11, 0x00, 0x00, 0x0b,
+                0x07, 0x00, 0x41, 0x00, 0x11, 0x00, 0x00, 0x0b],
+        ].concat();
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let findings: Vec<String> = module_parsed.validate().iter().map(|f| f.to_string()).collect();
+        assert_eq!(findings, vec![
+            "[functions] function 1: references type 5 but the module has 1 types",
+            "[exports] duplicate export name \"a\"",
+            "[exports] export \"a\" references function 7 but the module has 2",
+            "[call_indirect] function 0: call_indirect references table 0 but the module has 0 tables",
+            "[call_indirect] function 1: call_indirect references table 0 but the module has 0 tables",
+        ]);
+
+        let valid = wat::parse_str(r#"(module (func (export "f")) (memory (export "m") 1))"#)?;
+        let mut valid_parsed = AwwasmModule::new(&valid)?;
+        valid_parsed.resolve_all_sections()?;
+        assert!(valid_parsed.validate().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn validate_limits_test() -> anyhow::Result<()> {
+        // Hand-built: memories 3..2, 70000 pages, shared without a maximum,
+        // and 1-byte pages (any u32 count fits); tables 5..1 and flags 0x02.
+        let module: Vec<u8> = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[0x04, 0x08, 0x02, 0x70, 0x01, 0x05, 0x01, 0x70, 0x02, 0x00],
+            &[0x05, 0x11, 0x04, 0x01, 0x03, 0x02, 0x00, 0xf0, 0xa2, 0x04, 0x02, 0x01, 0x08, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x00],
+        ].concat();
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let findings: Vec<String> = module_parsed.validate().iter().map(|f| f.to_string()).collect();
+        assert_eq!(findings, vec![
+            "[memory] memory 0 has minimum 3 greater than maximum 2",
+            "[memory] memory 1 has a minimum of 70000 pages but at most 65536 are allowed",
+            "[memory] memory 2 is shared but has no maximum",
+            "[table] table 0 has minimum 5 greater than maximum 1",
+            "[table] table 1 has limits flags 0x02 but tables only allow 0x00 and 0x01",
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn validate_const_exprs_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"
+            (module
+                (import "env" "base" (global $base i32))
+                (global $counter (mut i32) (i32.const 0))
+                (global i64 (i32.const 1))
+                (global i32 (i32.add (global.get $base) (global.get 4)))
+                (global i32 (global.get $base))
+                (global funcref (ref.func 7))
+                (memory 1)
+                (table 2 funcref)
+                (data (offset (global.get $counter)) "x")
+                (elem (i64.const 0) funcref (ref.null func))
+                (data (i32.add (global.get $base) (i32.const 8)) "y")
+            )
+        "#)?;
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let findings: Vec<String> = module_parsed.validate_stage(AwwasmValidationStage::ConstExprs).iter().map(|f| f.to_string()).collect();
+        assert_eq!(findings, vec![
+            "[const_expr] global 2 init expression produces i32 but must produce i64",
+            "[const_expr] global 3 init expression reads global 4 which is not defined before it",
+            "[const_expr] global 5 init expression references function 7 but the module has 0",
+            "[const_expr] element segment 0 offset produces i64 but must produce i32",
+            "[const_expr] data segment 0 offset reads global 1 which is mutable",
+        ]);
+        assert!(module_parsed.validate_stage(AwwasmValidationStage::Module).is_empty());
+        assert_eq!(module_parsed.validate().len(), findings.len());
+        Ok(())
+    }
+
+    #[test]
+    fn validate_types_test() -> anyhow::Result<()> {
+        // Hand-built: one type, (func (param (ref null 5))).
+        let module = b"\0asm\x01\0\0\0\x01\x06\x01\x60\x01\x63\x05\x00";
+        let mut module_parsed = AwwasmModule::new(module)?;
+        module_parsed.resolve_all_sections()?;
+        module_parsed.types.as_mut().expect("types should exist")[0].type_magic = b"\x5f";
+
+        let findings: Vec<String> = module_parsed.validate().iter().map(|f| f.to_string()).collect();
+        assert_eq!(findings, vec![
+            "[types] type 0 has form [5f] but function types use 0x60",
+            "[types] type 0 uses (ref null 5) but the module has 1 types",
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn validate_names_test() -> anyhow::Result<()> {
+        // Hand-built: a function exported as "a\xff", and a custom section
+        // named "\xc3" (a truncated two-byte sequence).
+        let module: Vec<u8> = [
+            &b"\0asm\x01\0\0\0"[..],
+            &[0x01, 0x04, 0x01, 0x60, 0x00, 0x00],
+            &[0x03, 0x02, 0x01, 0x00],
+            &[0x07, 0x06, 0x01, 0x02, b'a', 0xff, 0x00, 0x00],
+            &[0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b],
+            &[0x00, 0x02, 0x01, 0xc3],
+        ].concat();
+        let mut module_parsed = AwwasmModule::new(&module)?;
+        module_parsed.resolve_all_sections()?;
+
+        let findings: Vec<String> = module_parsed.validate().iter().map(|f| f.to_string()).collect();
+        assert_eq!(findings, vec![
+            "[names] export 0 name is not valid UTF-8 (invalid byte at offset 0x17)",
+            "[names] custom section 0 name is not valid UTF-8 (invalid byte at offset 0x23)",
+        ]);
+        assert_eq!((module[0x17], module[0x23]), (0xff, 0xc3));
+        assert_eq!(module_parsed.exports()[0].name.to_str(), None);
+        let err = module_parsed.exports()[0].name.as_str().unwrap_err();
+        assert_eq!(err.to_string(), "Failed to parse WASM name: invalid UTF-8 at byte 1 of the name");
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+use crate::components::instructions::AwwasmInstruction;
+use crate::components::module::AwwasmModule;
+use crate::components::types::*;
+
+/// Callbacks for `AwwasmModule::visit()`. Every method does nothing by
+/// default; implement the ones an analysis needs.
+///
+/// Items are visited section by section in binary section order. Indices
+/// are in their index space, so imports come first: the first defined
+/// function of a module with two function imports is visited with index 2.
+pub trait AwwasmModuleVisitor<'a> {
+    fn visit_type(&mut self, _idx: TypeIdx, _ty: &AwwasmTypeSectionItem<'a>) {}
+
+    /// `idx` is the import's index in the index space of its kind.
+    fn visit_import(&mut self, _idx: u32, _import: &AwwasmImportSectionItem<'a>) {}
+
+    /// A defined function's entry in the function section.
+    fn visit_function(&mut self, _idx: FuncIdx, _type_idx: TypeIdx) {}
+
+    fn visit_table(&mut self, _idx: TableIdx, _table: &AwwasmTableSectionItem) {}
+
+    fn visit_memory(&mut self, _idx: MemIdx, _memory: &AwwasmMemorySectionItem) {}
+
+    fn visit_tag(&mut self, _idx: TagIdx, _tag: &AwwasmTagSectionItem) {}
+
+    fn visit_global(&mut self, _idx: GlobalIdx, _global: &AwwasmGlobalSectionItem<'a>) {}
+
+    fn visit_export(&mut self, _export: &AwwasmExportSectionItem<'a>) {}
+
+    fn visit_start(&mut self, _func: FuncIdx) {}
+
+    fn visit_element(&mut self, _idx: u32, _element: &AwwasmElementSectionItem<'a>) {}
+
+    /// A function body, before its instructions. Return `false` to skip
+    /// decoding them.
+    fn visit_code(&mut self, _func: FuncIdx, _code: &AwwasmCodeSectionItem<'a>) -> bool {
+        true
+    }
+
+    /// An instruction of function `func`, `depth` blocks deep. Structured
+    /// instructions are visited before their bodies.
+    fn visit_instruction(&mut self, _func: FuncIdx, _instr: &AwwasmInstruction<'a>, _depth: usize) {}
+
+    /// The `else`, `catch`, `catch_all`, `delegate` or `end` closing a body
+    /// nested `depth + 1` blocks deep; `marker` is its encoding (see
+    /// `marker_text()`). The `end` of the function itself is an instruction.
+    fn visit_body_end(&mut self, _func: FuncIdx, _marker: &'a [u8], _depth: usize) {}
+
+    fn visit_data(&mut self, _idx: u32, _data: &AwwasmDataSectionItem<'a>) {}
+
+    fn visit_custom(&mut self, _custom: &AwwasmCustomSectionItem<'a>) {}
+}
+
+impl<'a> AwwasmModule<'a> {
+    /// Walk the resolved sections and every function body, calling
+    /// `visitor` for each item and instruction.
+    ///
+    /// Requires `resolve_all_sections()`. Fails, after visiting everything
+    /// before it, at the first function body that does not decode.
+    pub fn visit<V: AwwasmModuleVisitor<'a>>(&self, visitor: &mut V) -> anyhow::Result<()> {
+        for (i, ty) in self.types().iter().enumerate() {
+            visitor.visit_type(i as u32, ty);
+        }
+        let mut imported = [0u32; 5];
+        for import in self.imports() {
+            let n = &mut imported[import.kind.clone() as usize];
+            visitor.visit_import(*n, import);
+            *n += 1;
+        }
+        let [funcs, tables, memories, globals, tags] = imported;
+        for (i, func) in self.funcs().iter().enumerate() {
+            visitor.visit_function(funcs + i as u32, func.type_item_idx);
+        }
+        for (i, table) in self.tables().iter().enumerate() {
+            visitor.visit_table(tables + i as u32, table);
+        }
+        for (i, memory) in self.memories().iter().enumerate() {
+            visitor.visit_memory(memories + i as u32, memory);
+        }
+        for (i, tag) in self.tags().iter().enumerate() {
+            visitor.visit_tag(tags + i as u32, tag);
+        }
+        for (i, global) in self.globals().iter().enumerate() {
+            visitor.visit_global(globals + i as u32, global);
+        }
+        for export in self.exports() {
+            visitor.visit_export(export);
+        }
+        if let Some(start) = &self.start {
+            visitor.visit_start(start.func_idx);
+        }
+        for (i, element) in self.elements().iter().enumerate() {
+            visitor.visit_element(i as u32, element);
+        }
+        for (i, code) in self.code().iter().enumerate() {
+            let func = funcs + i as u32;
+            if !visitor.visit_code(func, code) {
+                continue;
+            }
+            let instrs = code.instructions_with_limits(&self.limits).map_err(|e| self.locate_error(e))?;
+            for instr in &instrs {
+                visit_instruction(visitor, func, instr, 0);
+            }
+        }
+        for (i, data) in self.data().iter().enumerate() {
+            visitor.visit_data(i as u32, data);
+        }
+        for custom in self.customs() {
+            visitor.visit_custom(custom);
+        }
+        Ok(())
+    }
+}
+
+fn visit_instruction<'a, V: AwwasmModuleVisitor<'a>>(visitor: &mut V, func: FuncIdx, instr: &AwwasmInstruction<'a>, depth: usize) {
+    visitor.visit_instruction(func, instr, depth);
+    for (body, marker) in instr.operands.bodies() {
+        for instr in body {
+            visit_instruction(visitor, func, instr, depth + 1);
+        }
+        visitor.visit_body_end(func, marker, depth);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::instructions::{marker_text, AwwasmInstruction, AwwasmOperands};
+    use crate::components::module::AwwasmModule;
+    use crate::components::types::*;
+    use crate::components::visitor::AwwasmModuleVisitor;
+
+    #[derive(Default)]
+    struct Trace(Vec<String>);
+
+    impl<'a> AwwasmModuleVisitor<'a> for Trace {
+        fn visit_import(&mut self, idx: u32, import: &AwwasmImportSectionItem<'a>) {
+            self.0.push(format!("import {} {:?}", idx, import.kind));
+        }
+
+        fn visit_function(&mut self, idx: FuncIdx, type_idx: TypeIdx) {
+            self.0.push(format!("func {} type {}", idx, type_idx));
+        }
+
+        fn visit_global(&mut self, idx: GlobalIdx, _: &AwwasmGlobalSectionItem<'a>) {
+            self.0.push(format!("global {}", idx));
+        }
+
+        fn visit_code(&mut self, func: FuncIdx, _: &AwwasmCodeSectionItem<'a>) -> bool {
+            func != 3
+        }
+
+        fn visit_instruction(&mut self, func: FuncIdx, instr: &AwwasmInstruction<'a>, depth: usize) {
+            if let AwwasmOperands::Call(call) = &instr.operands {
+                self.0.push(format!("{}: call {} at depth {}", func, call.funcidx, depth));
+            }
+        }
+
+        fn visit_body_end(&mut self, func: FuncIdx, marker: &'a [u8], depth: usize) {
+            self.0.push(format!("{}: {} at depth {}", func, marker_text(marker), depth));
+        }
+    }
+
+    #[test]
+    fn visit_test() -> anyhow::Result<()> {
+        let bytes = wat::parse_str(r#"
+            (module
+                (import "env" "f" (func))
+                (import "env" "g" (global i32))
+                (import "env" "h" (func))
+                (global i32 (i32.const 0))
+                (func (block (loop (call 0) (br 1))) (call 1))
+                (func (call 2)))
+        "#)?;
+        let mut module = AwwasmModule::new(&bytes)?;
+        module.resolve_all_sections()?;
+        let mut trace = Trace::default();
+        module.visit(&mut trace)?;
+        assert_eq!(trace.0, vec![
+            "import 0 Function", "import 0 Global", "import 1 Function",
+            "func 2 type 0", "func 3 type 0", "global 1",
+            "2: call 0 at depth 2", "2: end at depth 1", "2: end at depth 0", "2: call 1 at depth 0",
+        ]);
+        Ok(())
+    }
+}
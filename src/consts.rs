@@ -2,8 +2,8 @@
 pub(crate) const WASM_MAGIC_NUMBER: &[u8; 4] = b"\0asm";
 pub(crate) const WASM_PREAMBLE_MAGIC_SIZE_BYTES: usize = 4;
 pub(crate) const WASM_PREAMBLE_VERSION_SIZE_BYTES: usize = 4;
+pub(crate) const WASM_VERSION: u32 = 1;
 
 pub(crate) const WASM_TYPE_SECTION_OPCODE_FUNC: &[u8; 1] = b"\x60";
 pub(crate) const WASM_FUNC_SECTION_OPCODE_END: u8 = 0x0b;
 pub(crate) const WASM_FUNC_SECTION_OPCODE_THEN: u8 = 0x05;
-pub(crate) const WASM_INSTRUCTION_MEMORY_ZERO: &[u8; 1] = b"\x00";
@@ -0,0 +1,26 @@
+// Minimal JSON writing helpers shared by the report/export renderers.
+use core::fmt::Write;
+
+/// Quote and escape `s` as a JSON string literal.
+pub(crate) fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Render an optional number as a JSON number or `null`.
+pub(crate) fn opt_number<T: core::fmt::Display>(v: Option<T>) -> String {
+    v.map_or_else(|| String::from("null"), |v| v.to_string())
+}
@@ -1,5 +1,7 @@
 pub mod components;
+pub mod prelude;
 
 
 mod limits;
 mod consts;
+mod json;
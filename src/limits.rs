@@ -13,6 +13,9 @@
  * limitations under the License.
  */
 
+// Not every limit is enforced yet; keep the full table in sync with other engines.
+#![allow(dead_code)]
+
 // The following limits are imposed by awwasm-parser on WebAssembly modules.
 // The limits are agreed upon with other engines for consistency.
 pub const MAX_WASM_TYPES: usize = 1_000_000;
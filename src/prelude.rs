@@ -0,0 +1,54 @@
+//! The commonly used types of the crate behind one import,
+//! `use awwasm_parser::prelude::*;`.
+//!
+//! These names and this path are kept stable across minor releases; the
+//! `components` modules they are defined in may be reorganized.
+
+pub use crate::components::cancel::CancellationToken;
+pub use crate::components::component::AwwasmComponent;
+pub use crate::components::dump::AwwasmDumpOptions;
+pub use crate::components::error::{AwwasmParseError, AwwasmParseErrorKind};
+pub use crate::components::features::{AwwasmFeatures, AwwasmParseOptions, AwwasmSectionOrderMode};
+pub use crate::components::imports::AwwasmImportEntry;
+pub use crate::components::index_space::{AwwasmDefinedFunc, AwwasmFunc, AwwasmIndexSpaces, AwwasmIndexed};
+pub use crate::components::instructions::{AwwasmInstruction, AwwasmOperands, BlockType, InstructionIterator, WasmOpCode};
+pub use crate::components::lazy_functions::{AwwasmLazyFunction, AwwasmLazyFunctions};
+pub use crate::components::leb128::{AwwasmLeb128Issue, AwwasmLeb128Problem};
+pub use crate::components::lossy::AwwasmLossyModule;
+pub use crate::components::module::{AwwasmModule, AwwasmStreamingParser};
+pub use crate::components::owned::AwwasmModuleOwned;
+pub use crate::components::parse_limits::AwwasmParseLimits;
+pub use crate::components::payload::{AwwasmPayload, AwwasmPayloadIterator};
+pub use crate::components::section::{AwwasmSection, SectionCode, SectionItem, SectionOrderError, TruncatedSection};
+pub use crate::components::spans::AwwasmSpanTable;
+pub use crate::components::types::{
+    AwwasmCodeSectionItem, AwwasmExportKind, AwwasmExportSectionItem, AwwasmImportKind, AwwasmImportSectionItem,
+    AwwasmTypeSectionItem, FuncIdx, GlobalIdx, MemIdx, TableIdx, TagIdx, TypeIdx, ValType,
+};
+pub use crate::components::validate::{AwwasmValidationFinding, AwwasmValidationStage};
+pub use crate::components::visitor::AwwasmModuleVisitor;
+pub use nom_derive::Parse;
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn prelude_test() -> anyhow::Result<()> {
+        let module = wat::parse_str(r#"(module (func (export "f") (param i32) (result i32) (local.get 0)))"#)?;
+        let mut parsed = AwwasmModule::new(&module)?;
+        parsed.resolve_all_sections()?;
+
+        let export: &AwwasmExportSectionItem = &parsed.exports.as_ref().expect("exports should exist")[0];
+        let func: FuncIdx = export.index;
+        let ty: &AwwasmTypeSectionItem = parsed.signature_of(func).expect("type should exist");
+        assert_eq!(ty.fn_args, vec![ValType::I32]);
+        let code: &AwwasmCodeSectionItem = &parsed.code.as_ref().expect("code should exist")[0];
+        let opcodes: Vec<WasmOpCode> = code.instructions()?.iter().map(|i: &AwwasmInstruction| i.opcode).collect();
+        assert_eq!(opcodes, vec![WasmOpCode::LocalGet, WasmOpCode::End]);
+
+        let err = AwwasmModule::new(&module[..module.len() - 1]).unwrap_err();
+        assert!(matches!(err.downcast_ref::<AwwasmParseError>().map(|e| &e.kind), Some(AwwasmParseErrorKind::TruncatedSection(_))));
+        Ok(())
+    }
+}